@@ -3,9 +3,6 @@ use base::{run, Artifact};
 const ARTIFACT_BINARY: &[u8] =
     include_bytes!(concat!(env!("OUT_DIR"), "/FftAlgorithm/fft_app.bin"));
 
-/// Payload offset where the input filename is stored (must match MakeAlgorithm.lean).
-const INPUT_FILENAME_OFF: usize = 0x2200;
-
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
@@ -16,15 +13,13 @@ fn main() {
 
     let mut artifact = Artifact::from_bytes(ARTIFACT_BINARY);
 
-    // Write input filename into initial_memory (null-terminated)
-    let path_bytes = input_path.as_bytes();
-    assert!(
-        path_bytes.len() < 255,
-        "Input path too long (max 254 chars)"
-    );
-    artifact.setup.initial_memory[INPUT_FILENAME_OFF..INPUT_FILENAME_OFF + path_bytes.len()]
-        .copy_from_slice(path_bytes);
-    artifact.setup.initial_memory[INPUT_FILENAME_OFF + path_bytes.len()] = 0;
+    artifact
+        .main
+        .set_symbol_str("input_path", input_path)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
 
     let start = std::time::Instant::now();
     match run(artifact.setup, artifact.main) {
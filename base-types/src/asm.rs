@@ -0,0 +1,331 @@
+//! Line-oriented text format for `Setup::initial_memory`.
+//!
+//! ```text
+//! .equ PATH 0x100          # named constant
+//! .size 4096               # minimum memory length
+//! .bytes PATH "out.bin\0"  # string with \0 \n \t \\ \" \xNN escapes
+//! .bytes 0x40 de ad be ef  # raw hex bytes
+//! .u64 0x200 42            # also .u8 .u16 .u32 .i32 .i64 .f32 .f64
+//! ```
+//!
+//! Memory grows to cover every write, up to `MAX_SIZE` bytes; all values
+//! are little-endian.
+//! `format` emits text that `parse` turns back into identical bytes.
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Largest memory image `parse` will build; larger `.size` values and
+/// writes past it are errors rather than allocations.
+pub const MAX_SIZE: usize = 1 << 30;
+
+/// Build an initial memory image from directives.
+pub fn parse(src: &str) -> Result<Vec<u8>, ParseError> {
+    let mut memory = Vec::new();
+    let mut consts: HashMap<String, i128> = HashMap::new();
+    for (i, raw) in src.lines().enumerate() {
+        let err = |message: String| ParseError {
+            line: i + 1,
+            message,
+        };
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (directive, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match directive {
+            ".equ" => {
+                let (name, value) =
+                    split_arg(rest).ok_or_else(|| err(".equ needs a name and a value".into()))?;
+                if name.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+                    return Err(err(format!("invalid constant name `{name}`")));
+                }
+                let value = int(value, &consts).map_err(err)?;
+                consts.insert(name.to_string(), value);
+            }
+            ".size" => {
+                let size = offset(rest, &consts).map_err(err)?;
+                grow(&mut memory, size).map_err(err)?;
+            }
+            ".bytes" => {
+                let (off, data) =
+                    split_arg(rest).ok_or_else(|| err(".bytes needs an offset and data".into()))?;
+                let off = offset(off, &consts).map_err(err)?;
+                let bytes = if data.starts_with('"') {
+                    string_literal(data).map_err(err)?
+                } else {
+                    hex_bytes(data).map_err(err)?
+                };
+                write(&mut memory, off, &bytes).map_err(err)?;
+            }
+            ".u8" | ".u16" | ".u32" | ".u64" | ".i32" | ".i64" | ".f32" | ".f64" => {
+                let (off, value) = split_arg(rest)
+                    .ok_or_else(|| err(format!("{directive} needs an offset and a value")))?;
+                let off = offset(off, &consts).map_err(err)?;
+                let bytes = scalar(directive, value, &consts).map_err(err)?;
+                write(&mut memory, off, &bytes).map_err(err)?;
+            }
+            other => return Err(err(format!("unknown directive `{other}`"))),
+        }
+    }
+    Ok(memory)
+}
+
+/// Describe a memory image as directives: a `.size` line followed by one
+/// `.bytes` line per run of up to 16 non-zero bytes.
+pub fn format(memory: &[u8]) -> String {
+    let mut out = format!(".size {}\n", memory.len());
+    let mut i = 0;
+    while i < memory.len() {
+        if memory[i] == 0 {
+            i += 1;
+            continue;
+        }
+        let end = (i..memory.len().min(i + 16))
+            .find(|&j| memory[j] == 0)
+            .unwrap_or(memory.len().min(i + 16));
+        let hex: Vec<String> = memory[i..end].iter().map(|b| format!("{b:02x}")).collect();
+        out.push_str(&format!(".bytes {:#x} {}\n", i, hex.join(" ")));
+        i = end;
+    }
+    out
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `#` inside a string literal is data, not a comment.
+    let mut in_str = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_str => escaped = true,
+            '"' => in_str = !in_str,
+            '#' if !in_str => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn split_arg(s: &str) -> Option<(&str, &str)> {
+    let (a, b) = s.split_once(char::is_whitespace)?;
+    let b = b.trim();
+    (!b.is_empty()).then_some((a, b))
+}
+
+fn int(token: &str, consts: &HashMap<String, i128>) -> Result<i128, String> {
+    if let Some(&v) = consts.get(token) {
+        return Ok(v);
+    }
+    let (neg, digits) = match token.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, token),
+    };
+    let parsed = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => i128::from_str_radix(&hex.replace('_', ""), 16),
+        None => digits.replace('_', "").parse::<i128>(),
+    };
+    let v = parsed.map_err(|_| format!("invalid integer or unknown constant `{token}`"))?;
+    Ok(if neg { -v } else { v })
+}
+
+fn offset(token: &str, consts: &HashMap<String, i128>) -> Result<usize, String> {
+    let v = int(token, consts)?;
+    usize::try_from(v).map_err(|_| format!("offset `{token}` out of range"))
+}
+
+fn scalar(directive: &str, token: &str, consts: &HashMap<String, i128>) -> Result<Vec<u8>, String> {
+    let float = |t: &str| t.parse::<f64>().map_err(|_| format!("invalid float `{t}`"));
+    let ranged = |lo: i128, hi: i128| {
+        let v = int(token, consts)?;
+        if v < lo || v > hi {
+            return Err(format!("value `{token}` out of range for {directive}"));
+        }
+        Ok(v)
+    };
+    Ok(match directive {
+        ".u8" => vec![ranged(0, u8::MAX as i128)? as u8],
+        ".u16" => (ranged(0, u16::MAX as i128)? as u16).to_le_bytes().to_vec(),
+        ".u32" => (ranged(0, u32::MAX as i128)? as u32).to_le_bytes().to_vec(),
+        ".u64" => (ranged(0, u64::MAX as i128)? as u64).to_le_bytes().to_vec(),
+        ".i32" => (ranged(i32::MIN as i128, i32::MAX as i128)? as i32)
+            .to_le_bytes()
+            .to_vec(),
+        ".i64" => (ranged(i64::MIN as i128, i64::MAX as i128)? as i64)
+            .to_le_bytes()
+            .to_vec(),
+        ".f32" => (float(token)? as f32).to_le_bytes().to_vec(),
+        ".f64" => float(token)?.to_le_bytes().to_vec(),
+        _ => unreachable!(),
+    })
+}
+
+fn hex_bytes(data: &str) -> Result<Vec<u8>, String> {
+    data.split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16).map_err(|_| format!("invalid hex byte `{b}`")))
+        .collect()
+}
+
+fn string_literal(data: &str) -> Result<Vec<u8>, String> {
+    let body = data
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("unterminated string `{data}`"))?;
+    let mut out = Vec::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('0') => out.push(0),
+            Some('n') => out.push(b'\n'),
+            Some('t') => out.push(b'\t'),
+            Some('\\') => out.push(b'\\'),
+            Some('"') => out.push(b'"'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let b = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape `\\x{hex}`"))?;
+                out.push(b);
+            }
+            other => {
+                return Err(format!(
+                    "invalid escape `\\{}`",
+                    other.map(String::from).unwrap_or_default()
+                ))
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn grow(memory: &mut Vec<u8>, size: usize) -> Result<(), String> {
+    if size > MAX_SIZE {
+        return Err(format!(
+            "memory size {size} exceeds the {MAX_SIZE}-byte limit"
+        ));
+    }
+    if memory.len() < size {
+        memory.resize(size, 0);
+    }
+    Ok(())
+}
+
+fn write(memory: &mut Vec<u8>, off: usize, bytes: &[u8]) -> Result<(), String> {
+    let end = off
+        .checked_add(bytes.len())
+        .ok_or_else(|| format!("write at {off} overflows the address space"))?;
+    grow(memory, end)?;
+    memory[off..end].copy_from_slice(bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_directives() {
+        let src = r#"
+            # verify file path and two operands
+            .equ PATH 0x100
+            .size 512
+            .bytes PATH "out#1.bin\0"   # trailing comment
+            .u64 0x20 42
+            .i64 0x28 -7
+            .u32 0x30 0xDEAD_BEEF
+            .f32 0x34 1.5
+            .f64 0x38 -2.25
+            .bytes 0x40 de ad
+            .u8 PATH 0x2f
+        "#;
+        let mem = parse(src).unwrap();
+        assert_eq!(mem.len(), 512);
+        assert_eq!(&mem[0x100..0x10a], b"/ut#1.bin\0");
+        assert_eq!(u64::from_le_bytes(mem[0x20..0x28].try_into().unwrap()), 42);
+        assert_eq!(i64::from_le_bytes(mem[0x28..0x30].try_into().unwrap()), -7);
+        assert_eq!(
+            u32::from_le_bytes(mem[0x30..0x34].try_into().unwrap()),
+            0xDEAD_BEEF
+        );
+        assert_eq!(f32::from_le_bytes(mem[0x34..0x38].try_into().unwrap()), 1.5);
+        assert_eq!(
+            f64::from_le_bytes(mem[0x38..0x40].try_into().unwrap()),
+            -2.25
+        );
+        assert_eq!(&mem[0x40..0x42], &[0xde, 0xad]);
+    }
+
+    #[test]
+    fn memory_grows_to_cover_writes() {
+        let mem = parse(".size 4\n.u64 8 1").unwrap();
+        assert_eq!(mem.len(), 16);
+        assert_eq!(mem[8], 1);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let mem = parse(r#".bytes 0 "a\tb\n\\\"\x7f\0""#).unwrap();
+        assert_eq!(mem, b"a\tb\n\\\"\x7f\0");
+    }
+
+    #[test]
+    fn format_round_trips() {
+        let mut mem = vec![0u8; 300];
+        mem[3] = 1;
+        for (i, b) in mem[100..140].iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+        mem[299] = 0xff;
+        let text = format(&mem);
+        assert_eq!(parse(&text).unwrap(), mem);
+        assert_eq!(parse(&format(&[])).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn errors_report_line() {
+        let e = parse(".size 8\n.u8 0 256").unwrap_err();
+        assert_eq!(e.line, 2);
+        assert!(e.message.contains("out of range"));
+        assert_eq!(parse(".u64 MISSING 1").unwrap_err().line, 1);
+        assert!(parse(".word 0 1")
+            .unwrap_err()
+            .message
+            .contains("unknown directive"));
+        assert!(parse(r#".bytes 0 "open"#).is_err());
+        assert!(parse(".u32 -4 1").is_err());
+    }
+
+    #[test]
+    fn oversized_images_are_errors() {
+        let e = parse(&format!(".size {}", MAX_SIZE + 1)).unwrap_err();
+        assert!(e.message.contains("limit"));
+        let e = parse(&format!(".u8 {} 1", MAX_SIZE)).unwrap_err();
+        assert!(e.message.contains("limit"));
+        let e = parse(&format!(".u64 {} 1", usize::MAX - 3)).unwrap_err();
+        assert!(e.message.contains("overflows"));
+        let e = parse(&format!(".bytes {} de ad", usize::MAX)).unwrap_err();
+        assert!(e.message.contains("overflows"));
+    }
+}
@@ -1,14 +1,102 @@
+pub mod asm;
+mod bytes_b64;
+pub mod status;
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Arrow type of an output column. JSON stores the variant name; binary
+/// formats (bincode) store the stable `code` rather than the variant's
+/// position, so adding a variant cannot change what an existing blob means.
+/// The codes were frozen at the positions bincode used before, so older
+/// blobs read unchanged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum OutputType {
     I64,
     F64,
     Utf8,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl OutputType {
+    /// Every variant with its stable code. Append new variants with a new
+    /// code; never renumber or reuse one.
+    pub const CODES: [(OutputType, u32); 3] = [
+        (OutputType::I64, 0),
+        (OutputType::F64, 1),
+        (OutputType::Utf8, 2),
+    ];
+
+    pub fn code(self) -> u32 {
+        match self {
+            OutputType::I64 => 0,
+            OutputType::F64 => 1,
+            OutputType::Utf8 => 2,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<OutputType> {
+        Self::CODES
+            .iter()
+            .find(|&&(_, c)| c == code)
+            .map(|&(ty, _)| ty)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OutputType::I64 => "I64",
+            OutputType::F64 => "F64",
+            OutputType::Utf8 => "Utf8",
+        }
+    }
+}
+
+impl Serialize for OutputType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(self.name())
+        } else {
+            serializer.serialize_u32(self.code())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OutputType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<OutputType, D::Error> {
+        struct CodeVisitor;
+
+        impl serde::de::Visitor<'_> for CodeVisitor {
+            type Value = OutputType;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an output type name or code")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, code: u64) -> Result<OutputType, E> {
+                u32::try_from(code)
+                    .ok()
+                    .and_then(OutputType::from_code)
+                    .ok_or_else(|| E::custom(format!("unknown OutputType code {code}")))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<OutputType, E> {
+                OutputType::CODES
+                    .iter()
+                    .map(|&(ty, _)| ty)
+                    .find(|ty| ty.name() == name)
+                    .ok_or_else(|| E::unknown_variant(name, &["I64", "F64", "Utf8"]))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(CodeVisitor)
+        } else {
+            deserializer.deserialize_u32(CodeVisitor)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct OutputColumn {
     pub name: String,
     pub dtype: OutputType,
@@ -16,13 +104,13 @@ pub struct OutputColumn {
     pub len_offset: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct OutputBatchSchema {
     pub columns: Vec<OutputColumn>,
     pub row_count_offset: usize,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct IoOffsets {
     pub data_ptr: usize,
     pub data_len: usize,
@@ -30,22 +118,461 @@ pub struct IoOffsets {
     pub out_len: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Same layout as `IoOffsets.default` on the Lean side: four 8-byte slots at
+/// 0x18..0x38, after the ht/wgpu/cuda context slots.
+impl Default for IoOffsets {
+    fn default() -> Self {
+        IoOffsets {
+            data_ptr: 0x18,
+            data_len: 0x20,
+            out_ptr: 0x28,
+            out_len: 0x30,
+        }
+    }
+}
+
+impl IoOffsets {
+    /// First byte past the highest slot.
+    pub fn end(&self) -> usize {
+        [self.data_ptr, self.data_len, self.out_ptr, self.out_len]
+            .into_iter()
+            .max()
+            .unwrap_or(0)
+            + 8
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Setup {
     pub cranelift_ir: String,
     pub memory_size: usize,
     pub io_offsets: IoOffsets,
-    #[serde(default)]
+    #[serde(default, with = "bytes_b64")]
     pub initial_memory: Vec<u8>,
+    /// Region `arena_offset..arena_offset + arena_size` that `cl_arena_bump`
+    /// hands out and `cl_arena_reset` rewinds; no arena when `arena_size` is
+    /// 0. Memory is grown to cover it.
+    #[serde(default)]
+    pub arena_offset: usize,
+    #[serde(default)]
+    pub arena_size: usize,
+}
+
+impl Setup {
+    /// Zero-initialized memory with default `IoOffsets`. `memory_size` is
+    /// raised to cover the IO slots if needed.
+    pub fn new(cranelift_ir: impl Into<String>, memory_size: usize) -> Setup {
+        let io_offsets = IoOffsets::default();
+        Setup {
+            cranelift_ir: cranelift_ir.into(),
+            memory_size: memory_size.max(io_offsets.end()),
+            io_offsets,
+            initial_memory: Vec::new(),
+            arena_offset: 0,
+            arena_size: 0,
+        }
+    }
+
+    /// Memory starts as `initial_memory`; `memory_size` is its length, raised
+    /// to cover the IO slots if needed.
+    pub fn with_initial_memory(cranelift_ir: impl Into<String>, initial_memory: Vec<u8>) -> Setup {
+        let mut setup = Setup::new(cranelift_ir, initial_memory.len());
+        setup.initial_memory = initial_memory;
+        setup
+    }
+
+    /// Declare `offset..offset + size` as the bump arena.
+    pub fn with_arena(mut self, offset: usize, size: usize) -> Setup {
+        self.arena_offset = offset;
+        self.arena_size = size;
+        self
+    }
+
+    /// The bump arena's byte range, if one is declared.
+    pub fn arena(&self) -> Option<std::ops::Range<usize>> {
+        (self.arena_size > 0)
+            .then(|| self.arena_offset..self.arena_offset.saturating_add(self.arena_size))
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct Algorithm {
     pub fn_idx: u32,
     pub output: Vec<OutputBatchSchema>,
+    /// Named constants written into memory before the function runs.
+    #[serde(default)]
+    pub symbols: Vec<Symbol>,
+    /// Names of user functions (`u0:N`, by index) that validation issues,
+    /// execution errors, tracing spans and profiles show next to the index.
+    /// Only those paths look them up; unlabeled functions are left out.
+    #[serde(default)]
+    pub labels: Vec<(u32, String)>,
+}
+
+/// A named region of `len` bytes at `offset`. Once set, `value` is written
+/// there, zero-padded, at the start of every execution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Symbol {
+    pub name: String,
+    pub offset: u64,
+    pub len: u32,
+    #[serde(default, with = "bytes_b64")]
+    pub value: Vec<u8>,
+    /// Scratch space the algorithm's code writes on purpose.
+    /// `validate_artifact` reports constant-address writes into any other
+    /// symbol, since they clobber the value written there each run.
+    #[serde(default)]
+    pub writable: bool,
+}
+
+impl Symbol {
+    fn end(&self) -> u64 {
+        self.offset.saturating_add(self.len as u64)
+    }
+
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.offset < end && start < self.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolError {
+    /// No symbol with this name is declared.
+    Missing(String),
+    /// A symbol with this name is already declared.
+    Duplicate(String),
+    /// The value needs `needed` bytes but the symbol only has `len`.
+    TooLong {
+        name: String,
+        needed: usize,
+        len: u32,
+    },
+    /// The symbol's range intersects another symbol or a reserved region.
+    Overlap { name: String, other: String },
+    /// The symbol's range ends past the memory region.
+    OutOfBounds { name: String, memory_size: usize },
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolError::Missing(name) => write!(f, "symbol `{name}` is not declared"),
+            SymbolError::Duplicate(name) => write!(f, "symbol `{name}` is declared twice"),
+            SymbolError::TooLong { name, needed, len } => {
+                write!(
+                    f,
+                    "value for symbol `{name}` needs {needed} bytes, has {len}"
+                )
+            }
+            SymbolError::Overlap { name, other } => {
+                write!(f, "symbol `{name}` overlaps {other}")
+            }
+            SymbolError::OutOfBounds { name, memory_size } => {
+                write!(f, "symbol `{name}` ends past memory size {memory_size}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl Algorithm {
+    /// Run function `u0:fn_idx` with no Arrow output.
+    pub fn new(fn_idx: u32) -> Algorithm {
+        Algorithm {
+            fn_idx,
+            output: Vec::new(),
+            symbols: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// Label the function this algorithm runs (see `labels`).
+    pub fn labeled(mut self, label: impl Into<String>) -> Algorithm {
+        self.set_label(self.fn_idx, label);
+        self
+    }
+
+    /// Label function `u0:fn_idx`, replacing any label it had.
+    pub fn set_label(&mut self, fn_idx: u32, label: impl Into<String>) {
+        let label = label.into();
+        match self.labels.iter_mut().find(|(idx, _)| *idx == fn_idx) {
+            Some((_, old)) => *old = label,
+            None => self.labels.push((fn_idx, label)),
+        }
+    }
+
+    /// The label of function `u0:fn_idx`, if it has one.
+    pub fn label(&self, fn_idx: u32) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(idx, _)| *idx == fn_idx)
+            .map(|(_, label)| label.as_str())
+    }
+
+    /// Declare an empty symbol covering `offset..offset + len`.
+    pub fn declare_symbol(
+        &mut self,
+        name: impl Into<String>,
+        offset: u64,
+        len: u32,
+    ) -> Result<(), SymbolError> {
+        let symbol = Symbol {
+            name: name.into(),
+            offset,
+            len,
+            value: Vec::new(),
+            writable: false,
+        };
+        if self.symbols.iter().any(|s| s.name == symbol.name) {
+            return Err(SymbolError::Duplicate(symbol.name));
+        }
+        if let Some(other) = self
+            .symbols
+            .iter()
+            .find(|s| s.overlaps(symbol.offset, symbol.end()))
+        {
+            return Err(SymbolError::Overlap {
+                name: symbol.name,
+                other: format!("symbol `{}`", other.name),
+            });
+        }
+        self.symbols.push(symbol);
+        Ok(())
+    }
+
+    /// Declare a writable symbol covering `offset..offset + len`: scratch
+    /// space the algorithm's code may write at constant addresses.
+    pub fn declare_scratch(
+        &mut self,
+        name: impl Into<String>,
+        offset: u64,
+        len: u32,
+    ) -> Result<(), SymbolError> {
+        self.declare_symbol(name, offset, len)?;
+        self.symbols.last_mut().unwrap().writable = true;
+        Ok(())
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| s.name == name)
+    }
+
+    /// Set a declared symbol to raw bytes.
+    pub fn set_symbol_bytes(&mut self, name: &str, value: &[u8]) -> Result<(), SymbolError> {
+        let symbol = self
+            .symbols
+            .iter_mut()
+            .find(|s| s.name == name)
+            .ok_or_else(|| SymbolError::Missing(name.to_string()))?;
+        if value.len() > symbol.len as usize {
+            return Err(SymbolError::TooLong {
+                name: name.to_string(),
+                needed: value.len(),
+                len: symbol.len,
+            });
+        }
+        symbol.value = value.to_vec();
+        Ok(())
+    }
+
+    /// Set a declared symbol to `value` plus a NUL terminator, the form the
+    /// file and network FFI expect for paths and addresses.
+    pub fn set_symbol_str(&mut self, name: &str, value: &str) -> Result<(), SymbolError> {
+        let mut bytes = Vec::with_capacity(value.len() + 1);
+        bytes.extend_from_slice(value.as_bytes());
+        bytes.push(0);
+        self.set_symbol_bytes(name, &bytes)
+    }
+
+    /// Set a declared symbol to a little-endian u64.
+    pub fn set_symbol_u64(&mut self, name: &str, value: u64) -> Result<(), SymbolError> {
+        self.set_symbol_bytes(name, &value.to_le_bytes())
+    }
+
+    /// The value of a string symbol, without its NUL terminator.
+    pub fn symbol_str(&self, name: &str) -> Option<&str> {
+        let value = &self.symbol(name)?.value;
+        let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+        std::str::from_utf8(&value[..end]).ok()
+    }
+
+    pub fn symbol_u64(&self, name: &str) -> Option<u64> {
+        let value = self.symbol(name)?.value.get(..8)?;
+        Some(u64::from_le_bytes(value.try_into().ok()?))
+    }
+
+    /// Check that symbols fit in `memory_size` bytes and overlap neither each
+    /// other nor the `reserved` ranges (e.g. the IO slots).
+    pub fn check_symbols(
+        &self,
+        memory_size: usize,
+        reserved: &[(&str, std::ops::Range<u64>)],
+    ) -> Result<(), SymbolError> {
+        for (i, symbol) in self.symbols.iter().enumerate() {
+            if symbol.end() > memory_size as u64 {
+                return Err(SymbolError::OutOfBounds {
+                    name: symbol.name.clone(),
+                    memory_size,
+                });
+            }
+            let clash = reserved
+                .iter()
+                .find(|(_, r)| symbol.overlaps(r.start, r.end))
+                .map(|(what, _)| what.to_string())
+                .or_else(|| {
+                    self.symbols[..i]
+                        .iter()
+                        .find(|s| s.overlaps(symbol.offset, symbol.end()))
+                        .map(|s| format!("symbol `{}`", s.name))
+                });
+            if let Some(other) = clash {
+                return Err(SymbolError::Overlap {
+                    name: symbol.name.clone(),
+                    other,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Write every symbol's value, zero-padded to its length, into `memory`.
+    /// Symbols that were never set leave their bytes untouched, so defaults
+    /// from `initial_memory` survive.
+    pub fn write_symbols(&self, memory: &mut [u8]) -> Result<(), SymbolError> {
+        for symbol in self.symbols.iter().filter(|s| !s.value.is_empty()) {
+            let range = usize::try_from(symbol.offset)
+                .ok()
+                .zip(usize::try_from(symbol.end()).ok())
+                .filter(|&(_, end)| end <= memory.len())
+                .ok_or_else(|| SymbolError::OutOfBounds {
+                    name: symbol.name.clone(),
+                    memory_size: memory.len(),
+                })?;
+            let dst = &mut memory[range.0..range.1];
+            let n = symbol.value.len().min(dst.len());
+            dst[..n].copy_from_slice(&symbol.value[..n]);
+            dst[n..].fill(0);
+        }
+        Ok(())
+    }
+}
+
+/// One `cl_trace` call recorded during `Base::execute_traced`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub tag: i64,
+    pub args: [i64; 4],
+    pub elapsed_ns: u64,
+}
+
+/// What a `ProfileEntry` measures.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProfileKey {
+    /// User function `u0:N`, including the FFI calls it makes.
+    Function(u32),
+    /// Calls to the named FFI primitive, from any function.
+    Ffi(String),
+}
+
+impl fmt::Display for ProfileKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileKey::Function(idx) => write!(f, "fn {idx}"),
+            ProfileKey::Ffi(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    pub key: ProfileKey,
+    pub calls: u64,
+    /// Summed wall time across calls. Calls made concurrently from several
+    /// threads each count in full.
+    pub total_ns: u64,
+    /// The function's `Algorithm::labels` entry, once `Profile::label` has
+    /// been applied.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl fmt::Display for ProfileEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) => write!(f, "{} ({label})", self.key),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// Call counts and wall time collected by a profiled `Base`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub entries: Vec<ProfileEntry>,
+}
+
+impl Profile {
+    pub fn get(&self, key: &ProfileKey) -> Option<&ProfileEntry> {
+        self.entries.iter().find(|e| &e.key == key)
+    }
+
+    /// The `n` entries with the most total time, longest first.
+    pub fn top_n(&self, n: usize) -> Vec<&ProfileEntry> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.total_ns));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Attach `algorithm`'s labels to the entries of the functions it names.
+    pub fn label(&mut self, algorithm: &Algorithm) {
+        for entry in &mut self.entries {
+            if let ProfileKey::Function(idx) = entry.key {
+                entry.label = algorithm.label(idx).map(str::to_string);
+            }
+        }
+    }
+}
+
+/// A path or address argument found by `base::analyze_artifact`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StringArg {
+    /// The NUL-terminated string at a constant offset, as the main
+    /// algorithm's initial memory and symbols hold it.
+    Known(String),
+    /// Built or located at run time by the call `inst` in `function`.
+    Dynamic { function: usize, inst: String },
+}
+
+/// What an artifact reaches outside its memory region, and which constant
+/// memory ranges look like its inputs and outputs, as found statically by
+/// `base::analyze_artifact`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactReport {
+    /// Imported FFI symbols, sorted.
+    pub ffi_symbols: Vec<String>,
+    /// FFI families in use (`file`, `lmdb`, `thread`, `gpu`, ...), sorted.
+    pub ffi_families: Vec<String>,
+    pub files_read: Vec<StringArg>,
+    /// Files written by `cl_file_write*` and `cl_checkpoint`.
+    pub files_written: Vec<StringArg>,
+    pub net_addresses: Vec<StringArg>,
+    pub lmdb_paths: Vec<StringArg>,
+    /// Constant byte ranges read before anything writes them, in code order
+    /// (candidate inputs).
+    pub inputs: Vec<std::ops::Range<u64>>,
+    /// Constant byte ranges written and never read (candidate outputs).
+    pub outputs: Vec<std::ops::Range<u64>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl ArtifactReport {
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize report")
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Artifact {
     pub setup: Setup,
     pub main: Algorithm,
@@ -53,8 +580,1139 @@ pub struct Artifact {
     pub extras: HashMap<String, Algorithm>,
 }
 
+impl Algorithm {
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize algorithm")
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Algorithm, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Bincode of the current layout, the form `cl_execute_nested` reads
+    /// from memory.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("failed to serialize algorithm")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Algorithm, bincode::Error> {
+        bincode::deserialize(bytes)
+    }
+}
+
+/// Magic prefix of a versioned artifact blob.
+pub const ARTIFACT_MAGIC: [u8; 4] = *b"BART";
+
+/// Layout version written by `Artifact::to_versioned_bytes`.
+///
+/// 1. `setup` and `main` only.
+/// 2. Adds `extras`.
+/// 3. Adds `Algorithm::symbols`.
+/// 4. Adds `Symbol::writable`.
+/// 5. Adds `Algorithm::labels`.
+/// 6. Adds `Setup::arena_offset` and `Setup::arena_size`.
+pub const ARTIFACT_FORMAT_VERSION: u16 = 6;
+
+/// `Setup` before version 6, without the arena.
+#[derive(Deserialize)]
+struct SetupV5 {
+    cranelift_ir: String,
+    memory_size: usize,
+    io_offsets: IoOffsets,
+    #[serde(with = "bytes_b64")]
+    initial_memory: Vec<u8>,
+}
+
+impl From<SetupV5> for Setup {
+    fn from(v5: SetupV5) -> Setup {
+        Setup {
+            cranelift_ir: v5.cranelift_ir,
+            memory_size: v5.memory_size,
+            io_offsets: v5.io_offsets,
+            initial_memory: v5.initial_memory,
+            arena_offset: 0,
+            arena_size: 0,
+        }
+    }
+}
+
+/// `Algorithm` before version 3, without `symbols`.
+#[derive(Deserialize)]
+struct AlgorithmV2 {
+    fn_idx: u32,
+    output: Vec<OutputBatchSchema>,
+}
+
+impl From<AlgorithmV2> for Algorithm {
+    fn from(v2: AlgorithmV2) -> Algorithm {
+        Algorithm {
+            fn_idx: v2.fn_idx,
+            output: v2.output,
+            symbols: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// `Symbol` before version 4, without `writable`.
+#[derive(Deserialize)]
+struct SymbolV3 {
+    name: String,
+    offset: u64,
+    len: u32,
+    #[serde(with = "bytes_b64")]
+    value: Vec<u8>,
+}
+
+/// `Algorithm` in version 3.
+#[derive(Deserialize)]
+struct AlgorithmV3 {
+    fn_idx: u32,
+    output: Vec<OutputBatchSchema>,
+    symbols: Vec<SymbolV3>,
+}
+
+impl From<AlgorithmV3> for Algorithm {
+    fn from(v3: AlgorithmV3) -> Algorithm {
+        let symbols = v3.symbols.into_iter().map(|s| Symbol {
+            name: s.name,
+            offset: s.offset,
+            len: s.len,
+            value: s.value,
+            writable: false,
+        });
+        Algorithm {
+            fn_idx: v3.fn_idx,
+            output: v3.output,
+            symbols: symbols.collect(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// `Algorithm` in version 4, without `labels`.
+#[derive(Deserialize)]
+struct AlgorithmV4 {
+    fn_idx: u32,
+    output: Vec<OutputBatchSchema>,
+    symbols: Vec<Symbol>,
+}
+
+impl From<AlgorithmV4> for Algorithm {
+    fn from(v4: AlgorithmV4) -> Algorithm {
+        Algorithm {
+            fn_idx: v4.fn_idx,
+            output: v4.output,
+            symbols: v4.symbols,
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// Version 1 layout, kept so old blobs can be upgraded.
+#[derive(Deserialize)]
+struct ArtifactV1 {
+    setup: SetupV5,
+    main: AlgorithmV2,
+}
+
+impl From<ArtifactV1> for Artifact {
+    fn from(v1: ArtifactV1) -> Artifact {
+        Artifact {
+            setup: v1.setup.into(),
+            main: v1.main.into(),
+            extras: HashMap::new(),
+        }
+    }
+}
+
+/// Version 2 layout, kept so old blobs can be upgraded.
+#[derive(Deserialize)]
+struct ArtifactV2 {
+    setup: SetupV5,
+    main: AlgorithmV2,
+    extras: HashMap<String, AlgorithmV2>,
+}
+
+impl From<ArtifactV2> for Artifact {
+    fn from(v2: ArtifactV2) -> Artifact {
+        Artifact {
+            setup: v2.setup.into(),
+            main: v2.main.into(),
+            extras: v2.extras.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        }
+    }
+}
+
+/// Version 3 layout, kept so old blobs can be upgraded.
+#[derive(Deserialize)]
+struct ArtifactV3 {
+    setup: SetupV5,
+    main: AlgorithmV3,
+    extras: HashMap<String, AlgorithmV3>,
+}
+
+impl From<ArtifactV3> for Artifact {
+    fn from(v3: ArtifactV3) -> Artifact {
+        Artifact {
+            setup: v3.setup.into(),
+            main: v3.main.into(),
+            extras: v3.extras.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        }
+    }
+}
+
+/// Version 4 layout, kept so old blobs can be upgraded.
+#[derive(Deserialize)]
+struct ArtifactV4 {
+    setup: SetupV5,
+    main: AlgorithmV4,
+    extras: HashMap<String, AlgorithmV4>,
+}
+
+impl From<ArtifactV4> for Artifact {
+    fn from(v4: ArtifactV4) -> Artifact {
+        Artifact {
+            setup: v4.setup.into(),
+            main: v4.main.into(),
+            extras: v4.extras.into_iter().map(|(k, v)| (k, v.into())).collect(),
+        }
+    }
+}
+
+/// Version 5 layout, kept so old blobs can be upgraded.
+#[derive(Deserialize)]
+struct ArtifactV5 {
+    setup: SetupV5,
+    main: Algorithm,
+    extras: HashMap<String, Algorithm>,
+}
+
+impl From<ArtifactV5> for Artifact {
+    fn from(v5: ArtifactV5) -> Artifact {
+        Artifact {
+            setup: v5.setup.into(),
+            main: v5.main,
+            extras: v5.extras,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArtifactFormatError {
+    UnsupportedVersion(u16),
+    Bincode(bincode::Error),
+}
+
+impl fmt::Display for ArtifactFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactFormatError::UnsupportedVersion(v) => write!(
+                f,
+                "artifact format version {v} is newer than supported ({ARTIFACT_FORMAT_VERSION})"
+            ),
+            ArtifactFormatError::Bincode(e) => write!(f, "invalid artifact body: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactFormatError {}
+
 impl Artifact {
+    /// `setup` running `main`, with no extras.
+    pub fn new(setup: Setup, main: Algorithm) -> Artifact {
+        Artifact {
+            setup,
+            main,
+            extras: HashMap::new(),
+        }
+    }
+
+    /// The label of function `u0:fn_idx` in `main`, or else in the first
+    /// extra by name that labels it.
+    pub fn function_label(&self, fn_idx: u32) -> Option<&str> {
+        let mut extras: Vec<_> = self.extras.iter().collect();
+        extras.sort_by(|a, b| a.0.cmp(b.0));
+        std::iter::once(&self.main)
+            .chain(extras.into_iter().map(|(_, alg)| alg))
+            .find_map(|alg| alg.label(fn_idx))
+    }
+
+    /// Decode a blob from `to_versioned_bytes`, upgrading older layouts. Blobs
+    /// without the magic header are read as raw bincode of the current layout,
+    /// then of the older layouts.
     pub fn from_bytes(bytes: &[u8]) -> Artifact {
-        bincode::deserialize(bytes).expect("failed to deserialize artifact")
+        Artifact::from_versioned_bytes(bytes).expect("failed to deserialize artifact")
+    }
+
+    /// `ARTIFACT_MAGIC`, the little-endian u16 `ARTIFACT_FORMAT_VERSION`, then
+    /// the bincode body.
+    pub fn to_versioned_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::from(ARTIFACT_MAGIC);
+        out.extend_from_slice(&ARTIFACT_FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut out, self).expect("failed to serialize artifact");
+        out
+    }
+
+    pub fn from_versioned_bytes(bytes: &[u8]) -> Result<Artifact, ArtifactFormatError> {
+        let Some(body) = bytes.strip_prefix(&ARTIFACT_MAGIC) else {
+            return bincode::deserialize::<Artifact>(bytes)
+                .or_else(|_| bincode::deserialize::<ArtifactV5>(bytes).map(Artifact::from))
+                .or_else(|_| bincode::deserialize::<ArtifactV4>(bytes).map(Artifact::from))
+                .or_else(|_| bincode::deserialize::<ArtifactV3>(bytes).map(Artifact::from))
+                .or_else(|_| bincode::deserialize::<ArtifactV2>(bytes).map(Artifact::from))
+                .or_else(|_| bincode::deserialize::<ArtifactV1>(bytes).map(Artifact::from))
+                .map_err(ArtifactFormatError::Bincode);
+        };
+        let (version, body) = match body {
+            [lo, hi, rest @ ..] => (u16::from_le_bytes([*lo, *hi]), rest),
+            _ => return Err(ArtifactFormatError::UnsupportedVersion(0)),
+        };
+        match version {
+            1 => bincode::deserialize::<ArtifactV1>(body).map(Artifact::from),
+            2 => bincode::deserialize::<ArtifactV2>(body).map(Artifact::from),
+            3 => bincode::deserialize::<ArtifactV3>(body).map(Artifact::from),
+            4 => bincode::deserialize::<ArtifactV4>(body).map(Artifact::from),
+            5 => bincode::deserialize::<ArtifactV5>(body).map(Artifact::from),
+            6 => bincode::deserialize::<Artifact>(body),
+            v => return Err(ArtifactFormatError::UnsupportedVersion(v)),
+        }
+        .map_err(ArtifactFormatError::Bincode)
+    }
+
+    /// JSON form used by Python tooling. `initial_memory` is a base64 string;
+    /// a plain array of byte values is also accepted when reading.
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize artifact")
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Artifact, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+/// One difference found by `Artifact::diff` or `Algorithm::diff`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// Path of the differing field, e.g. `main.symbols[1]`,
+    /// `setup.initial_memory[64..72]` or `setup.cranelift_ir line 12`.
+    pub field: String,
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} != {}", self.field, self.left, self.right)
+    }
+}
+
+/// Byte ranges `diff` reports per buffer before giving up.
+const MAX_BYTE_RANGES: usize = 8;
+/// Bytes of each differing range shown in a `FieldDiff`.
+const MAX_SHOWN_BYTES: usize = 16;
+
+fn push_diff(
+    out: &mut Vec<FieldDiff>,
+    field: String,
+    left: impl fmt::Debug,
+    right: impl fmt::Debug,
+) {
+    out.push(FieldDiff {
+        field,
+        left: format!("{left:?}"),
+        right: format!("{right:?}"),
+    });
+}
+
+/// Element-wise differences of two lists, plus a length entry.
+fn diff_lists<T: PartialEq + fmt::Debug>(out: &mut Vec<FieldDiff>, field: &str, a: &[T], b: &[T]) {
+    if a.len() != b.len() {
+        push_diff(out, format!("{field}.len()"), a.len(), b.len());
+    }
+    for (i, (x, y)) in a.iter().zip(b).enumerate() {
+        if x != y {
+            push_diff(out, format!("{field}[{i}]"), x, y);
+        }
+    }
+}
+
+/// The first `MAX_BYTE_RANGES` runs of differing bytes, shown as hex.
+fn diff_bytes(out: &mut Vec<FieldDiff>, field: &str, a: &[u8], b: &[u8]) {
+    if a.len() != b.len() {
+        push_diff(out, format!("{field}.len()"), a.len(), b.len());
+    }
+    let hex = |bytes: &[u8]| {
+        let shown: String = bytes
+            .iter()
+            .take(MAX_SHOWN_BYTES)
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        if bytes.len() > MAX_SHOWN_BYTES {
+            shown + ".."
+        } else {
+            shown
+        }
+    };
+    let n = a.len().min(b.len());
+    let mut i = 0;
+    let mut ranges = 0;
+    while i < n && ranges < MAX_BYTE_RANGES {
+        if a[i] == b[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < n && a[i] != b[i] {
+            i += 1;
+        }
+        out.push(FieldDiff {
+            field: format!("{field}[{start}..{i}]"),
+            left: hex(&a[start..i]),
+            right: hex(&b[start..i]),
+        });
+        ranges += 1;
+    }
+}
+
+impl Algorithm {
+    /// Fields where `self` and `other` differ; empty when they are equal.
+    pub fn diff(&self, other: &Algorithm) -> Vec<FieldDiff> {
+        let mut out = Vec::new();
+        self.diff_into(other, "", &mut out);
+        out
+    }
+
+    fn diff_into(&self, other: &Algorithm, prefix: &str, out: &mut Vec<FieldDiff>) {
+        if self.fn_idx != other.fn_idx {
+            push_diff(out, format!("{prefix}fn_idx"), self.fn_idx, other.fn_idx);
+        }
+        diff_lists(out, &format!("{prefix}output"), &self.output, &other.output);
+        diff_lists(
+            out,
+            &format!("{prefix}symbols"),
+            &self.symbols,
+            &other.symbols,
+        );
+        diff_lists(out, &format!("{prefix}labels"), &self.labels, &other.labels);
+    }
+}
+
+impl Artifact {
+    /// Fields where `self` and `other` differ, to make golden-artifact test
+    /// failures actionable: the first differing CLIF line, the first ranges
+    /// of differing initial memory, and per-element differences of `main`
+    /// and each extra. Empty when the artifacts are equal.
+    pub fn diff(&self, other: &Artifact) -> Vec<FieldDiff> {
+        let mut out = Vec::new();
+        let (a, b) = (&self.setup, &other.setup);
+        if a.cranelift_ir != b.cranelift_ir {
+            let la: Vec<&str> = a.cranelift_ir.lines().collect();
+            let lb: Vec<&str> = b.cranelift_ir.lines().collect();
+            let line = (0..la.len().max(lb.len()))
+                .find(|&i| la.get(i) != lb.get(i))
+                .unwrap_or(la.len());
+            push_diff(
+                &mut out,
+                format!("setup.cranelift_ir line {}", line + 1),
+                la.get(line),
+                lb.get(line),
+            );
+        }
+        if a.memory_size != b.memory_size {
+            push_diff(
+                &mut out,
+                "setup.memory_size".into(),
+                a.memory_size,
+                b.memory_size,
+            );
+        }
+        if a.io_offsets != b.io_offsets {
+            push_diff(
+                &mut out,
+                "setup.io_offsets".into(),
+                a.io_offsets,
+                b.io_offsets,
+            );
+        }
+        diff_bytes(
+            &mut out,
+            "setup.initial_memory",
+            &a.initial_memory,
+            &b.initial_memory,
+        );
+        if a.arena() != b.arena() {
+            push_diff(&mut out, "setup.arena".into(), a.arena(), b.arena());
+        }
+        self.main.diff_into(&other.main, "main.", &mut out);
+        let mut names: Vec<&String> = self.extras.keys().chain(other.extras.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            match (self.extras.get(name), other.extras.get(name)) {
+                (Some(x), Some(y)) => x.diff_into(y, &format!("extras[{name:?}]."), &mut out),
+                (x, y) => push_diff(
+                    &mut out,
+                    format!("extras[{name:?}]"),
+                    x.map(|a| a.fn_idx),
+                    y.map(|a| a.fn_idx),
+                ),
+            }
+        }
+        out
+    }
+}
+
+/// JSON Schema (draft 2020-12) describing the JSON form of `Artifact`.
+pub fn json_schema() -> serde_json::Value {
+    let uint = serde_json::json!({ "type": "integer", "minimum": 0 });
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Artifact",
+        "type": "object",
+        "required": ["setup", "main"],
+        "properties": {
+            "setup": { "$ref": "#/$defs/Setup" },
+            "main": { "$ref": "#/$defs/Algorithm" },
+            "extras": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/$defs/Algorithm" }
+            }
+        },
+        "$defs": {
+            "Setup": {
+                "type": "object",
+                "required": ["cranelift_ir", "memory_size", "io_offsets"],
+                "properties": {
+                    "cranelift_ir": { "type": "string" },
+                    "memory_size": uint,
+                    "io_offsets": { "$ref": "#/$defs/IoOffsets" },
+                    "initial_memory": {
+                        "oneOf": [
+                            { "type": "string", "contentEncoding": "base64" },
+                            { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } }
+                        ]
+                    },
+                    "arena_offset": uint,
+                    "arena_size": uint
+                }
+            },
+            "IoOffsets": {
+                "type": "object",
+                "required": ["data_ptr", "data_len", "out_ptr", "out_len"],
+                "properties": {
+                    "data_ptr": uint,
+                    "data_len": uint,
+                    "out_ptr": uint,
+                    "out_len": uint
+                }
+            },
+            "Algorithm": {
+                "type": "object",
+                "required": ["fn_idx", "output"],
+                "properties": {
+                    "fn_idx": uint,
+                    "output": { "type": "array", "items": { "$ref": "#/$defs/OutputBatchSchema" } },
+                    "symbols": { "type": "array", "items": { "$ref": "#/$defs/Symbol" } },
+                    "labels": {
+                        "type": "array",
+                        "items": {
+                            "type": "array",
+                            "prefixItems": [uint, { "type": "string" }],
+                            "items": false
+                        }
+                    }
+                }
+            },
+            "Symbol": {
+                "type": "object",
+                "required": ["name", "offset", "len"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "offset": uint,
+                    "len": uint,
+                    "value": {
+                        "oneOf": [
+                            { "type": "string", "contentEncoding": "base64" },
+                            { "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } }
+                        ]
+                    },
+                    "writable": { "type": "boolean" }
+                }
+            },
+            "OutputBatchSchema": {
+                "type": "object",
+                "required": ["columns", "row_count_offset"],
+                "properties": {
+                    "columns": { "type": "array", "items": { "$ref": "#/$defs/OutputColumn" } },
+                    "row_count_offset": uint
+                }
+            },
+            "OutputColumn": {
+                "type": "object",
+                "required": ["name", "dtype", "data_offset", "len_offset"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "dtype": { "enum": ["I64", "F64", "Utf8"] },
+                    "data_offset": uint,
+                    "len_offset": uint
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_artifact() -> Artifact {
+        let mut initial_memory = vec![0u8; 300];
+        initial_memory[40..48].copy_from_slice(&7u64.to_le_bytes());
+        initial_memory[299] = 0xff;
+        let main = Algorithm {
+            fn_idx: 0,
+            output: vec![OutputBatchSchema {
+                columns: vec![
+                    OutputColumn {
+                        name: "id".into(),
+                        dtype: OutputType::I64,
+                        data_offset: 64,
+                        len_offset: 0,
+                    },
+                    OutputColumn {
+                        name: "label".into(),
+                        dtype: OutputType::Utf8,
+                        data_offset: 128,
+                        len_offset: 56,
+                    },
+                ],
+                row_count_offset: 40,
+            }],
+            symbols: vec![Symbol {
+                name: "input_path".into(),
+                offset: 256,
+                len: 32,
+                value: b"in.txt\0".to_vec(),
+                writable: false,
+            }],
+            labels: vec![],
+        };
+        let mut extras = HashMap::new();
+        extras.insert(
+            "prep".to_string(),
+            Algorithm {
+                fn_idx: 1,
+                output: vec![],
+                symbols: vec![],
+                labels: vec![],
+            },
+        );
+        Artifact {
+            setup: Setup {
+                cranelift_ir: "function u0:0(i64) system_v {\nblock0(v0: i64):\n    return\n}"
+                    .into(),
+                memory_size: 4096,
+                io_offsets: IoOffsets {
+                    data_ptr: 8,
+                    data_len: 16,
+                    out_ptr: 24,
+                    out_len: 32,
+                },
+                initial_memory,
+                arena_offset: 0,
+                arena_size: 0,
+            },
+            main,
+            extras,
+        }
+    }
+
+    #[test]
+    fn bincode_json_bincode_is_byte_identical() {
+        let bin = bincode::serialize(&sample_artifact()).unwrap();
+        let json = Artifact::from_bytes(&bin).to_json_string();
+        let back = Artifact::from_json_str(&json).unwrap();
+        assert_eq!(bincode::serialize(&back).unwrap(), bin);
+        assert_eq!(back, sample_artifact());
+    }
+
+    #[test]
+    fn output_type_codes_are_frozen() {
+        // Changing this table changes what existing blobs mean.
+        let frozen = [
+            (OutputType::I64, 0u32),
+            (OutputType::F64, 1),
+            (OutputType::Utf8, 2),
+        ];
+        assert_eq!(OutputType::CODES, frozen);
+        for (ty, code) in frozen {
+            assert_eq!(ty.code(), code);
+            assert_eq!(OutputType::from_code(code), Some(ty));
+            assert_eq!(bincode::serialize(&ty).unwrap(), code.to_le_bytes());
+            let json = serde_json::to_string(&ty).unwrap();
+            assert_eq!(json, format!("\"{ty:?}\""));
+            assert_eq!(serde_json::from_str::<OutputType>(&json).unwrap(), ty);
+        }
+        assert_eq!(OutputType::from_code(3), None);
+        let err = bincode::deserialize::<OutputType>(&7u32.to_le_bytes()).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown OutputType code 7"),
+            "{err}"
+        );
+        assert_eq!(
+            serde_json::from_str::<OutputType>("1").unwrap(),
+            OutputType::F64
+        );
+        assert!(serde_json::from_str::<OutputType>("\"U8\"").is_err());
+    }
+
+    #[test]
+    fn initial_memory_is_base64_in_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&sample_artifact().to_json_string()).unwrap();
+        assert!(json["setup"]["initial_memory"].is_string());
+    }
+
+    #[test]
+    fn initial_memory_accepts_byte_array() {
+        let json = r#"{"cranelift_ir": "", "memory_size": 4,
+            "io_offsets": {"data_ptr": 0, "data_len": 8, "out_ptr": 16, "out_len": 24},
+            "initial_memory": [1, 2, 3]}"#;
+        let setup: Setup = serde_json::from_str(json).unwrap();
+        assert_eq!(setup.initial_memory, [1, 2, 3]);
+    }
+
+    #[test]
+    fn algorithm_json_round_trip() {
+        let alg = sample_artifact().main;
+        let back = Algorithm::from_json_str(&alg.to_json_string()).unwrap();
+        assert_eq!(
+            bincode::serialize(&back).unwrap(),
+            bincode::serialize(&alg).unwrap()
+        );
+        assert_eq!(back, alg);
+    }
+
+    #[test]
+    fn schema_covers_serialized_fields() {
+        let schema = json_schema();
+        let json: serde_json::Value =
+            serde_json::from_str(&sample_artifact().to_json_string()).unwrap();
+        let defs = &schema["$defs"];
+        for (def, value) in [
+            ("Setup", &json["setup"]),
+            ("Algorithm", &json["main"]),
+            ("IoOffsets", &json["setup"]["io_offsets"]),
+            ("OutputBatchSchema", &json["main"]["output"][0]),
+            ("OutputColumn", &json["main"]["output"][0]["columns"][0]),
+            ("Symbol", &json["main"]["symbols"][0]),
+        ] {
+            let props = defs[def]["properties"].as_object().unwrap();
+            for key in value.as_object().unwrap().keys() {
+                assert!(props.contains_key(key), "{def}.{key} missing from schema");
+            }
+        }
+    }
+
+    /// Pre-version 6 `Setup` encoding: no arena.
+    fn v5_setup(setup: &Setup) -> Vec<u8> {
+        let io = &setup.io_offsets;
+        bincode::serialize(&(
+            &setup.cranelift_ir,
+            setup.memory_size,
+            io,
+            &setup.initial_memory,
+        ))
+        .unwrap()
+    }
+
+    /// Pre-version 3 `Algorithm` encoding: no `symbols`.
+    fn v2_algorithm(alg: &Algorithm) -> Vec<u8> {
+        bincode::serialize(&(alg.fn_idx, &alg.output)).unwrap()
+    }
+
+    /// Hand-built version 1 blob: `setup` then `main`, no `extras`.
+    fn v1_body(artifact: &Artifact) -> Vec<u8> {
+        let mut body = v5_setup(&artifact.setup);
+        body.extend(v2_algorithm(&artifact.main));
+        body
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let artifact = sample_artifact();
+        let bytes = artifact.to_versioned_bytes();
+        assert_eq!(&bytes[..4], b"BART");
+        assert_eq!(
+            u16::from_le_bytes([bytes[4], bytes[5]]),
+            ARTIFACT_FORMAT_VERSION
+        );
+        let back = Artifact::from_versioned_bytes(&bytes).unwrap();
+        assert_eq!(back.to_versioned_bytes(), bytes);
+        assert_eq!(back, artifact);
+    }
+
+    #[test]
+    fn raw_bincode_blob_still_loads() {
+        let artifact = sample_artifact();
+        let raw = bincode::serialize(&artifact).unwrap();
+        let back = Artifact::from_bytes(&raw);
+        assert_eq!(bincode::serialize(&back).unwrap(), raw);
+        assert_eq!(back, artifact);
+    }
+
+    #[test]
+    fn v1_blob_upgrades_with_empty_extras() {
+        let artifact = sample_artifact();
+        let mut versioned = Vec::from(ARTIFACT_MAGIC);
+        versioned.extend_from_slice(&1u16.to_le_bytes());
+        versioned.extend(v1_body(&artifact));
+        let mut expected = artifact.clone();
+        expected.extras.clear();
+        expected.main.symbols.clear();
+        for bytes in [versioned, v1_body(&artifact)] {
+            let back = Artifact::from_versioned_bytes(&bytes).unwrap();
+            assert!(back.extras.is_empty());
+            assert_eq!(back.main.fn_idx, artifact.main.fn_idx);
+            assert_eq!(back.setup.initial_memory, artifact.setup.initial_memory);
+            assert_eq!(back, expected);
+        }
+    }
+
+    #[test]
+    fn v2_blob_upgrades_with_empty_symbols() {
+        let artifact = sample_artifact();
+        let mut body = v5_setup(&artifact.setup);
+        body.extend(v2_algorithm(&artifact.main));
+        body.extend(bincode::serialize(&(artifact.extras.len() as u64)).unwrap());
+        for (name, alg) in &artifact.extras {
+            body.extend(bincode::serialize(name).unwrap());
+            body.extend(v2_algorithm(alg));
+        }
+        let mut versioned = Vec::from(ARTIFACT_MAGIC);
+        versioned.extend_from_slice(&2u16.to_le_bytes());
+        versioned.extend(&body);
+        let mut expected = artifact.clone();
+        expected.main.symbols.clear();
+        for bytes in [versioned, body] {
+            let back = Artifact::from_versioned_bytes(&bytes).unwrap();
+            assert!(back.main.symbols.is_empty());
+            assert_eq!(back.main.output.len(), 1);
+            assert_eq!(back.extras["prep"].fn_idx, 1);
+            assert_eq!(back, expected);
+        }
+    }
+
+    /// Version 3 `Algorithm` encoding: symbols without `writable`.
+    fn v3_algorithm(alg: &Algorithm) -> Vec<u8> {
+        let symbols: Vec<_> = alg
+            .symbols
+            .iter()
+            .map(|s| (&s.name, s.offset, s.len, &s.value))
+            .collect();
+        bincode::serialize(&(alg.fn_idx, &alg.output, symbols)).unwrap()
+    }
+
+    #[test]
+    fn v3_blob_upgrades_with_read_only_symbols() {
+        let artifact = sample_artifact();
+        let mut body = v5_setup(&artifact.setup);
+        body.extend(v3_algorithm(&artifact.main));
+        body.extend(bincode::serialize(&(artifact.extras.len() as u64)).unwrap());
+        for (name, alg) in &artifact.extras {
+            body.extend(bincode::serialize(name).unwrap());
+            body.extend(v3_algorithm(alg));
+        }
+        let mut versioned = Vec::from(ARTIFACT_MAGIC);
+        versioned.extend_from_slice(&3u16.to_le_bytes());
+        versioned.extend(&body);
+        for bytes in [versioned, body] {
+            let back = Artifact::from_versioned_bytes(&bytes).unwrap();
+            assert!(!back.main.symbols[0].writable);
+            assert_eq!(back, artifact);
+        }
+
+        let mut scratch = artifact;
+        scratch.main.declare_scratch("scratch", 512, 64).unwrap();
+        let bytes = scratch.to_versioned_bytes();
+        let back = Artifact::from_versioned_bytes(&bytes).unwrap();
+        assert!(back.main.symbol("scratch").unwrap().writable);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut bytes = sample_artifact().to_versioned_bytes();
+        bytes[4..6].copy_from_slice(&99u16.to_le_bytes());
+        let err = Artifact::from_versioned_bytes(&bytes).unwrap_err();
+        assert!(matches!(err, ArtifactFormatError::UnsupportedVersion(99)));
+        assert!(err.to_string().contains("version 99"));
+        assert!(Artifact::from_versioned_bytes(b"BART").is_err());
+    }
+
+    #[test]
+    fn diff_names_each_differing_field() {
+        let a = sample_artifact();
+        assert!(a.diff(&a.clone()).is_empty());
+
+        let mut b = a.clone();
+        b.setup.cranelift_ir = b.setup.cranelift_ir.replace("return", "trap user1");
+        b.setup.initial_memory[40] = 8;
+        b.setup.initial_memory[100..103].copy_from_slice(&[1, 2, 3]);
+        b.setup.initial_memory.push(0);
+        b.main.output[0].columns[1].dtype = OutputType::F64;
+        b.main.symbols.clear();
+        b.extras.get_mut("prep").unwrap().fn_idx = 2;
+        b.extras.insert("post".into(), Algorithm::new(3));
+        assert_ne!(a, b);
+
+        let fields: Vec<String> = a.diff(&b).into_iter().map(|d| d.field).collect();
+        assert_eq!(
+            fields,
+            [
+                "setup.cranelift_ir line 3",
+                "setup.initial_memory.len()",
+                "setup.initial_memory[40..41]",
+                "setup.initial_memory[100..103]",
+                "main.output[0]",
+                "main.symbols.len()",
+                "extras[\"post\"]",
+                "extras[\"prep\"].fn_idx",
+            ]
+        );
+        let diffs = a.diff(&b);
+        assert_eq!(diffs[3].left, "000000");
+        assert_eq!(diffs[3].right, "010203");
+        assert_eq!(
+            diffs[0].to_string(),
+            "setup.cranelift_ir line 3: Some(\"    return\") != Some(\"    trap user1\")"
+        );
+        assert_eq!(b.main.diff(&a.main).len(), 2);
+    }
+
+    #[test]
+    fn equal_algorithms_dedupe_in_a_hash_set() {
+        let mut alg = Algorithm::new(1);
+        alg.declare_symbol("n", 0x100, 8).unwrap();
+        let mut other = alg.clone();
+        let set: std::collections::HashSet<Algorithm> =
+            [alg.clone(), other.clone(), Algorithm::new(1)].into();
+        assert_eq!(set.len(), 2);
+        other.set_symbol_u64("n", 5).unwrap();
+        assert_ne!(alg, other);
+        assert_eq!(alg.diff(&other)[0].field, "symbols[0]");
+    }
+
+    #[test]
+    fn default_io_offsets_match_lean_layout() {
+        let io = IoOffsets::default();
+        assert_eq!(
+            (io.data_ptr, io.data_len, io.out_ptr, io.out_len),
+            (0x18, 0x20, 0x28, 0x30)
+        );
+        assert_eq!(io.end(), 0x38);
+    }
+
+    #[test]
+    fn setup_constructors_cover_io_slots() {
+        let setup = Setup::new("", 16);
+        assert_eq!(setup.memory_size, 0x38);
+        assert!(setup.initial_memory.is_empty());
+        assert_eq!(Setup::new("", 4096).memory_size, 4096);
+
+        let setup = Setup::with_initial_memory("", vec![7u8; 100]);
+        assert_eq!(setup.memory_size, 100);
+        assert_eq!(setup.initial_memory, vec![7u8; 100]);
+        assert_eq!(Setup::with_initial_memory("", vec![1]).memory_size, 0x38);
+    }
+
+    #[test]
+    fn algorithm_and_artifact_constructors() {
+        let alg = Algorithm::new(3);
+        assert_eq!(alg.fn_idx, 3);
+        assert!(alg.output.is_empty());
+        assert_eq!(Algorithm::default().fn_idx, 0);
+        let artifact = Artifact::new(Setup::new("", 64), alg);
+        assert!(artifact.extras.is_empty());
+    }
+
+    #[test]
+    fn symbol_set_get_round_trip() {
+        let mut alg = Algorithm::new(1);
+        alg.declare_symbol("input_path", 0x100, 16).unwrap();
+        alg.declare_symbol("block_size", 0x110, 8).unwrap();
+        alg.set_symbol_str("input_path", "data.bin").unwrap();
+        alg.set_symbol_u64("block_size", 4096).unwrap();
+        let mut alg = Algorithm::from_json_str(&alg.to_json_string()).unwrap();
+        assert_eq!(alg.symbol_str("input_path"), Some("data.bin"));
+        assert_eq!(alg.symbol_u64("block_size"), Some(4096));
+
+        let mut memory = vec![0xffu8; 0x120];
+        alg.write_symbols(&mut memory).unwrap();
+        assert_eq!(&memory[0x100..0x110], b"data.bin\0\0\0\0\0\0\0\0");
+        assert_eq!(memory[0x110..0x118], 4096u64.to_le_bytes());
+        assert_eq!(memory[0x118], 0xff);
+        assert_eq!(
+            alg.set_symbol_u64("missing", 1),
+            Err(SymbolError::Missing("missing".into()))
+        );
+    }
+
+    #[test]
+    fn overlapping_symbols_are_rejected() {
+        let mut alg = Algorithm::new(1);
+        alg.declare_symbol("a", 0x100, 16).unwrap();
+        let err = alg.declare_symbol("b", 0x10f, 8).unwrap_err();
+        assert!(matches!(err, SymbolError::Overlap { ref name, .. } if name == "b"));
+        assert!(alg.declare_symbol("b", 0x110, 8).is_ok());
+        assert_eq!(
+            alg.declare_symbol("a", 0x200, 8),
+            Err(SymbolError::Duplicate("a".into()))
+        );
+
+        let io = IoOffsets::default();
+        let reserved = [("IO slots", 0..io.end() as u64)];
+        assert!(alg.check_symbols(0x200, &reserved).is_ok());
+        alg.symbols.push(Symbol {
+            name: "header".into(),
+            offset: 0x30,
+            len: 8,
+            value: vec![],
+            writable: false,
+        });
+        let err = alg.check_symbols(0x200, &reserved).unwrap_err();
+        assert_eq!(err.to_string(), "symbol `header` overlaps IO slots");
+        alg.symbols.pop();
+        assert!(matches!(
+            alg.check_symbols(0x110, &reserved),
+            Err(SymbolError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn too_long_symbol_value_is_rejected() {
+        let mut alg = Algorithm::new(1);
+        alg.declare_symbol("input_path", 0x100, 8).unwrap();
+        alg.declare_symbol("count", 0x108, 4).unwrap();
+        alg.set_symbol_str("input_path", "1234567").unwrap();
+        let err = alg.set_symbol_str("input_path", "12345678").unwrap_err();
+        assert_eq!(
+            err,
+            SymbolError::TooLong {
+                name: "input_path".into(),
+                needed: 9,
+                len: 8
+            }
+        );
+        assert_eq!(alg.symbol_str("input_path"), Some("1234567"));
+        assert!(alg.set_symbol_u64("count", 1).is_err());
+    }
+
+    #[test]
+    fn profile_top_n_orders_by_total_time() {
+        let entry = |key, calls, total_ns| ProfileEntry {
+            key,
+            calls,
+            total_ns,
+            label: None,
+        };
+        let profile = Profile {
+            entries: vec![
+                entry(ProfileKey::Function(0), 1, 500),
+                entry(ProfileKey::Ffi("cl_file_read".into()), 2, 400),
+                entry(ProfileKey::Ffi("cl_mem_fill".into()), 10, 20),
+            ],
+        };
+        let top: Vec<String> = profile.top_n(2).iter().map(|e| e.key.to_string()).collect();
+        assert_eq!(top, ["fn 0", "cl_file_read"]);
+        let json = serde_json::to_string(&profile).unwrap();
+        assert_eq!(serde_json::from_str::<Profile>(&json).unwrap(), profile);
+
+        let mut profile = profile;
+        profile.label(&Algorithm::new(0).labeled("parse rows"));
+        let top: Vec<String> = profile.top_n(2).iter().map(|e| e.to_string()).collect();
+        assert_eq!(top, ["fn 0 (parse rows)", "cl_file_read"]);
+    }
+
+    #[test]
+    fn labels_round_trip_and_v4_blobs_upgrade_without_them() {
+        let mut labeled = sample_artifact();
+        labeled.main = labeled.main.labeled("read input");
+        labeled.main.set_label(3, "parse header");
+        labeled.main.set_label(3, "parse row");
+        labeled.extras.get_mut("prep").unwrap().set_label(1, "prep");
+        assert_eq!(labeled.main.labels.len(), 2);
+        assert_eq!(labeled.function_label(3), Some("parse row"));
+        assert_eq!(labeled.function_label(1), Some("prep"));
+        assert_eq!(labeled.function_label(2), None);
+        let back = Artifact::from_versioned_bytes(&labeled.to_versioned_bytes()).unwrap();
+        assert_eq!(back, labeled);
+        let back = Artifact::from_json_str(&labeled.to_json_string()).unwrap();
+        assert_eq!(back, labeled);
+        let main = Algorithm::from_bytes(&labeled.main.to_bytes()).unwrap();
+        assert_eq!(main, labeled.main);
+
+        // Version 4: the current layout without labels.
+        let artifact = sample_artifact();
+        let v4_algorithm =
+            |alg: &Algorithm| bincode::serialize(&(alg.fn_idx, &alg.output, &alg.symbols)).unwrap();
+        let mut body = v5_setup(&artifact.setup);
+        body.extend(v4_algorithm(&artifact.main));
+        body.extend(bincode::serialize(&(artifact.extras.len() as u64)).unwrap());
+        for (name, alg) in &artifact.extras {
+            body.extend(bincode::serialize(name).unwrap());
+            body.extend(v4_algorithm(alg));
+        }
+        let mut versioned = Vec::from(ARTIFACT_MAGIC);
+        versioned.extend_from_slice(&4u16.to_le_bytes());
+        versioned.extend(&body);
+        for bytes in [versioned, body] {
+            assert_eq!(Artifact::from_versioned_bytes(&bytes).unwrap(), artifact);
+        }
+
+        // JSON written before labels existed.
+        let mut json: serde_json::Value = serde_json::from_str(&artifact.to_json_string()).unwrap();
+        json["main"].as_object_mut().unwrap().remove("labels");
+        assert_eq!(
+            Artifact::from_json_str(&json.to_string()).unwrap(),
+            artifact
+        );
+    }
+
+    #[test]
+    fn arena_round_trips_and_v5_blobs_upgrade_without_it() {
+        let mut artifact = sample_artifact();
+        artifact.setup = artifact.setup.with_arena(1024, 512);
+        assert_eq!(artifact.setup.arena(), Some(1024..1536));
+        let back = Artifact::from_versioned_bytes(&artifact.to_versioned_bytes()).unwrap();
+        assert_eq!(back, artifact);
+        let back = Artifact::from_json_str(&artifact.to_json_string()).unwrap();
+        assert_eq!(back, artifact);
+        let diffs = artifact.diff(&sample_artifact());
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].field, "setup.arena");
+
+        // Version 5: the current layout without the arena.
+        let artifact = sample_artifact();
+        assert_eq!(artifact.setup.arena(), None);
+        let mut body = v5_setup(&artifact.setup);
+        body.extend(bincode::serialize(&artifact.main).unwrap());
+        body.extend(bincode::serialize(&artifact.extras).unwrap());
+        let mut versioned = Vec::from(ARTIFACT_MAGIC);
+        versioned.extend_from_slice(&5u16.to_le_bytes());
+        versioned.extend(&body);
+        for bytes in [versioned, body] {
+            assert_eq!(Artifact::from_versioned_bytes(&bytes).unwrap(), artifact);
+        }
+
+        // JSON written before the arena existed.
+        let mut json: serde_json::Value = serde_json::from_str(&artifact.to_json_string()).unwrap();
+        let setup = json["setup"].as_object_mut().unwrap();
+        setup.remove("arena_offset");
+        setup.remove("arena_size");
+        assert_eq!(
+            Artifact::from_json_str(&json.to_string()).unwrap(),
+            artifact
+        );
     }
 }
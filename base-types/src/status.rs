@@ -0,0 +1,83 @@
+//! Completion word reported by file, network, memory, hash table, and LMDB
+//! FFI calls, read back from CLIF with `cl_last_status`.
+//!
+//! ```text
+//! bits  0..32  status   0 = success, 1..=0xFFFF = OS errno, >= 0x1_0000 = sentinel
+//! bits 32..64  payload  call-specific result, e.g. bytes written or read
+//! ```
+//!
+//! Network and file calls made under a retry policy put their attempt count
+//! in bits 56..64 instead, leaving the payload 24 bits (see `with_attempts`).
+//!
+//! A word is non-zero whenever the call failed, so code can branch on the
+//! low 32 bits alone.
+
+/// The call succeeded.
+pub const OK: u32 = 0;
+/// The call failed without a more specific code (e.g. an unknown handle).
+pub const FAILED: u32 = 0x1_0000;
+/// A pointer, offset, or size argument was rejected before doing any work.
+pub const INVALID_ARGUMENT: u32 = 0x1_0001;
+/// The requested key or entry does not exist.
+pub const NOT_FOUND: u32 = 0x1_0002;
+/// An I/O error the OS did not attach an errno to.
+pub const IO_ERROR: u32 = 0x1_0003;
+/// The path lies outside the allowed prefixes of the execution's sandbox.
+pub const PATH_DENIED: u32 = 0x1_0004;
+/// A message was larger than the buffer it was read into and was discarded;
+/// the payload holds its size.
+pub const TOO_LARGE: u32 = 0x1_0005;
+
+/// Combine a status and payload; payloads above `u32::MAX` saturate.
+pub fn pack(status: u32, payload: u64) -> u64 {
+    (payload.min(u32::MAX as u64) << 32) | status as u64
+}
+
+pub fn status(word: u64) -> u32 {
+    word as u32
+}
+
+pub fn payload(word: u64) -> u32 {
+    (word >> 32) as u32
+}
+
+/// Store an attempt count (saturating at 255) in the top byte of `word`.
+/// The payload saturates at 24 bits: read it as `payload(word) & 0xFF_FFFF`.
+pub fn with_attempts(word: u64, attempts: u32) -> u64 {
+    let payload = (payload(word) as u64).min(0xFF_FFFF);
+    ((attempts.min(255) as u64) << 56) | (payload << 32) | status(word) as u64
+}
+
+/// The attempt count of a word built by `with_attempts`.
+pub fn attempts(word: u64) -> u32 {
+    (word >> 56) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trips() {
+        let word = pack(OK, 4096);
+        assert_eq!((status(word), payload(word)), (OK, 4096));
+        let word = pack(NOT_FOUND, 0);
+        assert_eq!((status(word), payload(word)), (NOT_FOUND, 0));
+        assert_eq!(payload(pack(OK, u64::MAX)), u32::MAX);
+        assert_ne!(pack(13, 0), 0, "errno-only failures stay non-zero");
+    }
+
+    #[test]
+    fn attempts_share_the_payload_bits() {
+        let word = with_attempts(pack(111, 0), 5);
+        assert_eq!(
+            (status(word), attempts(word), payload(word) & 0xFF_FFFF),
+            (111, 5, 0)
+        );
+        let word = with_attempts(pack(OK, u64::MAX), 300);
+        assert_eq!(
+            (attempts(word), payload(word) & 0xFF_FFFF),
+            (255, 0xFF_FFFF)
+        );
+    }
+}
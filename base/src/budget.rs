@@ -0,0 +1,230 @@
+//! Compile-time instrumentation behind `Base::new_with_step_budget`.
+//!
+//! Every loop header (a block some branch reaches from itself or from a
+//! later block in layout order) starts by decrementing a counter. Once it
+//! goes negative, the header calls `cl_step_budget_exhausted` and its
+//! function returns zeros; every other function on the stack does the same
+//! at its next loop header, so control unwinds back to `execute*`, which
+//! reports `Error::Execution`. The counter's address is compiled in as an
+//! immediate. Threads share it without synchronization, so concurrent loops
+//! may lose a few of each other's decrements.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use base_types::Algorithm;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, Block, Function, InstBuilder, MemFlags, Signature, Type, Value,
+};
+use cranelift_module::FuncId;
+
+use crate::ffi::{cancel, thread};
+use crate::profile;
+
+/// A block that `instrument` made count against the budget.
+pub(crate) struct LoopHeader {
+    fn_idx: usize,
+    block: Block,
+    /// Whether any block between the header and its furthest back-edge
+    /// calls out. A loop without calls cannot see anything but memory
+    /// change, so one that runs out the budget is most likely spinning.
+    makes_calls: bool,
+}
+
+/// The per-execution counter and where it ran out.
+pub(crate) struct StepBudget {
+    limit: i64,
+    remaining: AtomicI64,
+    /// Index of the header that exhausted the budget, plus one; 0 while
+    /// steps remain.
+    exhausted_at: AtomicI64,
+    headers: Vec<LoopHeader>,
+}
+
+impl StepBudget {
+    pub(crate) fn new(limit: u64, headers: Vec<LoopHeader>) -> StepBudget {
+        let limit = limit.min(i64::MAX as u64) as i64;
+        StepBudget {
+            limit,
+            remaining: AtomicI64::new(limit),
+            exhausted_at: AtomicI64::new(0),
+            headers,
+        }
+    }
+
+    pub(crate) fn headers(&self) -> &[LoopHeader] {
+        &self.headers
+    }
+
+    /// Refill the counter for the next execution.
+    pub(crate) fn reset(&self) {
+        self.remaining.store(self.limit, Ordering::Relaxed);
+        self.exhausted_at.store(0, Ordering::Relaxed);
+    }
+
+    /// Where the budget ran out, if it did, naming the function by its label
+    /// in `algorithm` when it has one.
+    pub(crate) fn exhausted(&self, algorithm: &Algorithm) -> Option<String> {
+        let at = self.exhausted_at.load(Ordering::Acquire);
+        let header = self.headers.get(at.checked_sub(1)? as usize)?;
+        let tight = if header.makes_calls {
+            ""
+        } else {
+            "; the loop makes no calls, so it is likely spinning"
+        };
+        let label = match algorithm.label(header.fn_idx as u32) {
+            Some(label) => format!(" (`{label}`)"),
+            None => String::new(),
+        };
+        Some(format!(
+            "step budget of {} loop iterations exceeded at {} in function {}{label}{tight}",
+            self.limit, header.block, header.fn_idx
+        ))
+    }
+}
+
+/// Called once per function as the stack unwinds after the budget runs out;
+/// the first call records the header and cancels the execution, which wakes
+/// threads parked on flags the stopped loops would have set.
+pub(crate) unsafe extern "C" fn cl_step_budget_exhausted(state: *const StepBudget, header: i64) {
+    let Some(state) = state.as_ref() else {
+        return;
+    };
+    let first = state
+        .exhausted_at
+        .compare_exchange(0, header + 1, Ordering::AcqRel, Ordering::Acquire)
+        .is_ok();
+    if first {
+        if let Some(token) = cancel::current_token() {
+            token.store(true, Ordering::Release);
+            thread::wake_all_waiters();
+        }
+    }
+}
+
+pub(crate) fn hook_signature(call_conv: cranelift_codegen::isa::CallConv) -> Signature {
+    let mut sig = Signature::new(call_conv);
+    sig.params.extend([AbiParam::new(types::I64); 2]);
+    sig
+}
+
+/// The loop headers of `func` (user function `fn_idx`) in layout order.
+/// Functions returning a type `zero` cannot build get none, and run
+/// unbudgeted.
+pub(crate) fn loop_headers(func: &Function, fn_idx: usize) -> Vec<LoopHeader> {
+    if !func
+        .signature
+        .returns
+        .iter()
+        .all(|r| zeroable(r.value_type))
+    {
+        return Vec::new();
+    }
+    let blocks: Vec<Block> = func.layout.blocks().collect();
+    let position: HashMap<Block, usize> = blocks.iter().enumerate().map(|(i, &b)| (b, i)).collect();
+    let calls: Vec<bool> = blocks
+        .iter()
+        .map(|&b| {
+            func.layout
+                .block_insts(b)
+                .any(|inst| func.dfg.insts[inst].opcode().is_call())
+        })
+        .collect();
+    // For each header, the furthest block that branches back to it.
+    let mut latest = vec![None; blocks.len()];
+    for (i, &block) in blocks.iter().enumerate() {
+        for inst in func.layout.block_insts(block) {
+            let dests = func.dfg.insts[inst].branch_destination(&func.dfg.jump_tables);
+            for dest in dests {
+                let target = dest.block(&func.dfg.value_lists);
+                if let Some(&t) = position.get(&target).filter(|&&t| t <= i) {
+                    latest[t] = latest[t].max(Some(i));
+                }
+            }
+        }
+    }
+    latest
+        .iter()
+        .enumerate()
+        .filter_map(|(t, &end)| {
+            Some(LoopHeader {
+                fn_idx,
+                block: blocks[t],
+                makes_calls: calls[t..=end?].contains(&true),
+            })
+        })
+        .collect()
+}
+
+fn zeroable(ty: Type) -> bool {
+    let lane = ty.lane_type();
+    lane.is_int() && lane.bits() <= 128 || lane == types::F32 || lane == types::F64
+}
+
+fn zero(pos: &mut FuncCursor, ty: Type) -> Value {
+    if ty.is_vector() {
+        let lane = zero(pos, ty.lane_type());
+        return pos.ins().splat(ty, lane);
+    }
+    match ty {
+        types::F32 => pos.ins().f32const(0.0),
+        types::F64 => pos.ins().f64const(0.0),
+        types::I128 => {
+            let low = pos.ins().iconst(types::I64, 0);
+            pos.ins().uextend(types::I128, low)
+        }
+        _ => pos.ins().iconst(ty, 0),
+    }
+}
+
+/// Make each of `headers` (the ones `loop_headers` found in `func`, which
+/// `state` numbers from `first_id`) count a step on entry.
+pub(crate) fn instrument(
+    func: &mut Function,
+    headers: &[LoopHeader],
+    first_id: usize,
+    hook: FuncId,
+    state: &Arc<StepBudget>,
+) {
+    if headers.is_empty() {
+        return;
+    }
+    let hook_sig = hook_signature(func.signature.call_conv);
+    let hook = profile::import(func, hook, hook_sig);
+    let returns: Vec<Type> = func
+        .signature
+        .returns
+        .iter()
+        .map(|r| r.value_type)
+        .collect();
+    let remaining = &state.remaining as *const AtomicI64 as i64;
+    let state = Arc::as_ptr(state) as i64;
+
+    let mut pos = FuncCursor::new(func);
+    for (i, header) in headers.iter().enumerate() {
+        let body = pos.func.dfg.make_block();
+        let out = pos.func.dfg.make_block();
+        let first = pos.func.layout.first_inst(header.block).unwrap();
+        pos.func.layout.split_block(body, first);
+        pos.func.layout.append_block(out);
+        pos.func.layout.set_cold(out);
+
+        pos.goto_bottom(header.block);
+        let counter = pos.ins().iconst(types::I64, remaining);
+        let left = pos.ins().load(types::I64, MemFlags::trusted(), counter, 0);
+        let left = pos.ins().iadd_imm(left, -1);
+        pos.ins().store(MemFlags::trusted(), left, counter, 0);
+        let spent = pos.ins().icmp_imm(IntCC::SignedLessThan, left, 0);
+        pos.ins().brif(spent, out, &[], body, &[]);
+
+        pos.goto_bottom(out);
+        let state = pos.ins().iconst(types::I64, state);
+        let id = pos.ins().iconst(types::I64, (first_id + i) as i64);
+        pos.ins().call(hook, &[state, id]);
+        let zeros: Vec<Value> = returns.iter().map(|&ty| zero(&mut pos, ty)).collect();
+        pos.ins().return_(&zeros);
+    }
+}
@@ -0,0 +1,225 @@
+//! Growable memory outside the fixed shared region.
+//!
+//! Shared memory cannot move once code holds pointers into it, so extra space
+//! comes from separate host allocations. `cl_arena_alloc` returns the
+//! region's address, which CLIF can load and store through directly or pass
+//! as the `ptr` argument of any offset-based primitive (`cl_mem_scan`,
+//! `cl_file_read`, ...) to address the region from offset 0.
+//!
+//! Short-lived intermediates can instead come from the bump arena declared
+//! in shared memory (`Setup::arena_offset`, `Setup::arena_size`):
+//! `cl_arena_bump` hands out the next bytes of it and `cl_arena_reset`
+//! rewinds to its start, freeing everything at once, say at the end of a
+//! loop iteration. Nothing is allocated, so both are a few instructions.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::{clear_ctx_slot, read_ctx_ref, status, write_ctx_slot};
+use base_types::status::{FAILED, INVALID_ARGUMENT, TOO_LARGE};
+
+/// Regions keyed by address. Freed on `cl_arena_free` or, all at once, on
+/// `cl_arena_cleanup`.
+pub(crate) struct CraneliftArena {
+    regions: Mutex<HashMap<usize, Box<[u8]>>>,
+}
+
+pub(crate) unsafe extern "C" fn cl_arena_init(ctx_slot_ptr: *mut *mut CraneliftArena) {
+    let ctx = Box::new(CraneliftArena {
+        regions: Mutex::new(HashMap::new()),
+    });
+    let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
+}
+
+/// Allocate `size` zeroed bytes. Returns the region's address, or 0 if the
+/// size is invalid or the allocation fails.
+pub(crate) unsafe extern "C" fn cl_arena_alloc(ctx_ptr: *const CraneliftArena, size: i64) -> i64 {
+    if size <= 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return 0;
+    }
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftArena>(ctx_ptr) else {
+        return 0;
+    };
+    let mut buf = Vec::new();
+    if buf.try_reserve_exact(size as usize).is_err() {
+        return 0;
+    }
+    buf.resize(size as usize, 0u8);
+    let mut region = buf.into_boxed_slice();
+    let addr = region.as_mut_ptr() as usize;
+    ctx.regions.lock().unwrap().insert(addr, region);
+    status::ok(size as u64);
+    addr as i64
+}
+
+/// Size in bytes of the region at `addr`, or -1 if `addr` is not the start of
+/// a live region.
+pub(crate) unsafe extern "C" fn cl_arena_size(ctx_ptr: *const CraneliftArena, addr: i64) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftArena>(ctx_ptr) else {
+        return -1;
+    };
+    let regions = ctx.regions.lock().unwrap();
+    regions.get(&(addr as usize)).map_or(-1, |r| r.len() as i64)
+}
+
+/// Release the region at `addr`. Returns 0, or -1 if `addr` is not the start
+/// of a live region.
+pub(crate) unsafe extern "C" fn cl_arena_free(ctx_ptr: *const CraneliftArena, addr: i64) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftArena>(ctx_ptr) else {
+        return -1;
+    };
+    match ctx.regions.lock().unwrap().remove(&(addr as usize)) {
+        Some(_) => {
+            status::ok(0);
+            0
+        }
+        None => -1,
+    }
+}
+
+pub(crate) unsafe extern "C" fn cl_arena_cleanup(ctx_slot_ptr: *mut *mut CraneliftArena) {
+    let ctx_ptr = clear_ctx_slot::<CraneliftArena>(ctx_slot_ptr);
+    if !ctx_ptr.is_null() {
+        drop(Box::from_raw(ctx_ptr));
+    }
+}
+
+/// The bump arena of one execution: `size` bytes at address `start`, of
+/// which the first `used` are handed out.
+pub(crate) struct BumpArena {
+    start: usize,
+    size: usize,
+    used: AtomicUsize,
+}
+
+impl BumpArena {
+    pub(crate) fn new(start: *mut u8, size: usize) -> BumpArena {
+        BumpArena {
+            start: start as usize,
+            size,
+            used: AtomicUsize::new(0),
+        }
+    }
+}
+
+thread_local! {
+    /// Arena of the execution running on this thread (shared with threads
+    /// started through `cl_thread_*`).
+    static BUMP: RefCell<Option<Arc<BumpArena>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current_bump() -> Option<Arc<BumpArena>> {
+    BUMP.with(|cell| cell.borrow().clone())
+}
+
+pub(crate) fn set_bump(arena: Option<Arc<BumpArena>>) {
+    BUMP.with(|cell| *cell.borrow_mut() = arena);
+}
+
+/// Take `size` bytes from the bump arena, rounded up to a multiple of 8 so
+/// every allocation stays 8-byte aligned relative to the arena's start.
+/// Returns their address, or 0 with status `TOO_LARGE` (payload: bytes still
+/// free) once the arena cannot fit them, or `FAILED` when no arena is
+/// declared. The bytes keep whatever the previous user left there.
+pub(crate) unsafe extern "C" fn cl_arena_bump(size: i64) -> i64 {
+    if size <= 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return 0;
+    }
+    let Some(arena) = current_bump() else {
+        status::set(FAILED, 0);
+        return 0;
+    };
+    let Some(len) = (size as usize).checked_next_multiple_of(8) else {
+        status::set(TOO_LARGE, 0);
+        return 0;
+    };
+    let taken = arena
+        .used
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+            used.checked_add(len).filter(|&end| end <= arena.size)
+        });
+    match taken {
+        Ok(offset) => {
+            status::ok(len as u64);
+            (arena.start + offset) as i64
+        }
+        Err(used) => {
+            status::set(TOO_LARGE, (arena.size - used) as u64);
+            0
+        }
+    }
+}
+
+/// Rewind the bump arena so the next `cl_arena_bump` starts over at its
+/// first byte. Memory is not cleared: a new allocation sees the bytes the
+/// previous one left. Every thread of the execution shares the arena, so
+/// only reset once none of them still uses an allocation.
+pub(crate) unsafe extern "C" fn cl_arena_reset() {
+    if let Some(arena) = current_bump() {
+        arena.used.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alloc_size_free_lifecycle() {
+        let mut slot: *mut CraneliftArena = std::ptr::null_mut();
+        unsafe {
+            cl_arena_init(&mut slot);
+            assert!(!slot.is_null());
+            let a = cl_arena_alloc(slot, 1 << 20);
+            let b = cl_arena_alloc(slot, 16);
+            assert!(a != 0 && b != 0 && a != b);
+            let region = std::slice::from_raw_parts_mut(a as *mut u8, 1 << 20);
+            assert!(region.iter().all(|&x| x == 0));
+            region[(1 << 20) - 1] = 7;
+
+            assert_eq!(cl_arena_size(slot, a), 1 << 20);
+            assert_eq!(cl_arena_size(slot, a + 1), -1);
+            assert_eq!(cl_arena_free(slot, a), 0);
+            assert_eq!(cl_arena_free(slot, a), -1, "double free is rejected");
+            assert_eq!(cl_arena_size(slot, b), 16);
+
+            assert_eq!(cl_arena_alloc(slot, 0), 0);
+            assert_eq!(cl_arena_alloc(std::ptr::null(), 16), 0);
+            cl_arena_cleanup(&mut slot);
+            assert!(slot.is_null());
+        }
+    }
+
+    #[test]
+    fn bump_reuses_offsets_after_reset_and_reports_exhaustion() {
+        let mut memory = vec![0u8; 64];
+        let start = memory.as_mut_ptr();
+        set_bump(Some(Arc::new(BumpArena::new(start, 32))));
+        unsafe {
+            let a = cl_arena_bump(5);
+            assert_eq!(a, start as i64);
+            assert_eq!(cl_arena_bump(16), a + 8, "rounded up to 8 bytes");
+            assert_eq!(cl_arena_bump(16), 0);
+            let word = status::word();
+            assert_eq!(base_types::status::status(word), TOO_LARGE);
+            assert_eq!(base_types::status::payload(word), 8);
+
+            *(a as *mut u8) = 42;
+            cl_arena_reset();
+            assert_eq!(cl_arena_bump(32), a);
+            assert_eq!(*(a as *const u8), 42, "reset does not clear memory");
+            assert_eq!(cl_arena_bump(0), 0);
+
+            set_bump(None);
+            assert_eq!(cl_arena_bump(8), 0);
+            let word = status::word();
+            assert_eq!(base_types::status::status(word), FAILED);
+        }
+    }
+}
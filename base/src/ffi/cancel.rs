@@ -0,0 +1,94 @@
+//! Cooperative cancellation of a running `Base::execute*` call.
+//!
+//! JIT code cannot be interrupted, so cancellation is observed at FFI
+//! boundaries: blocking primitives give up early and CLIF loops can poll
+//! `cl_cancelled`.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+thread_local! {
+    /// Token of the execution running on this thread (propagated to threads
+    /// started through `cl_thread_*`).
+    pub(crate) static CANCEL_TOKEN: RefCell<Option<Arc<AtomicBool>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current_token() -> Option<Arc<AtomicBool>> {
+    CANCEL_TOKEN.with(|cell| cell.borrow().clone())
+}
+
+pub(crate) fn set_token(token: Option<Arc<AtomicBool>>) {
+    CANCEL_TOKEN.with(|cell| *cell.borrow_mut() = token);
+}
+
+/// Whether the execution running on this thread has been cancelled.
+pub(crate) fn is_cancelled() -> bool {
+    CANCEL_TOKEN.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .is_some_and(|t| t.load(Ordering::Acquire))
+    })
+}
+
+/// Read timeout of sockets read through `Cancellable`: how long a blocked
+/// read can take to notice a cancel.
+#[cfg(feature = "net")]
+pub(crate) const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(20);
+
+/// The error a socket call fails with once the execution is cancelled.
+#[cfg(feature = "net")]
+pub(crate) fn cancelled_error() -> std::io::Error {
+    std::io::Error::other("execution cancelled")
+}
+
+/// A reader over a socket with a `POLL_INTERVAL` read timeout that waits out
+/// the timeouts until data arrives or the execution running on this thread
+/// is cancelled, which fails the read.
+#[cfg(feature = "net")]
+pub(crate) struct Cancellable<R>(pub R);
+
+#[cfg(feature = "net")]
+impl<R: std::io::Read> std::io::Read for Cancellable<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.0.read(buf) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if is_cancelled() {
+                        return Err(cancelled_error());
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+/// Returns 1 once the current execution has been cancelled, else 0. Long
+/// CLIF loops should poll this and return early.
+pub(crate) unsafe extern "C" fn cl_cancelled() -> i64 {
+    is_cancelled() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reflects_installed_token() {
+        set_token(None);
+        assert_eq!(unsafe { cl_cancelled() }, 0);
+        let token = Arc::new(AtomicBool::new(false));
+        set_token(Some(token.clone()));
+        assert_eq!(unsafe { cl_cancelled() }, 0);
+        token.store(true, Ordering::Release);
+        assert_eq!(unsafe { cl_cancelled() }, 1);
+        set_token(None);
+        assert_eq!(unsafe { cl_cancelled() }, 0);
+    }
+}
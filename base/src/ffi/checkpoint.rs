@@ -0,0 +1,126 @@
+//! Memory snapshots for resuming long-running algorithms.
+//!
+//! A checkpoint holds the first `len` bytes of shared memory. Native code has
+//! no program counter to save, so a resumable algorithm keeps its progress
+//! (loop counters, phase) in memory and picks up from there when
+//! `Base::execute_resume` runs it against the restored snapshot.
+//!
+//! Only memory is captured: open files, sockets, LMDB handles, GPU buffers,
+//! and running threads are not. Checkpoints are therefore only valid at
+//! quiescent points, which `cl_checkpoint` enforces by refusing to write
+//! while the caller's in-flight counter is non-zero.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::{io_log, read_path, sandbox, status};
+use base_types::status::INVALID_ARGUMENT;
+
+const MAGIC: [u8; 4] = *b"BCKP";
+const VERSION: u16 = 1;
+const HEADER_LEN: usize = 4 + 2 + 8;
+
+fn write_checkpoint(path: &Path, memory: &[u8]) -> io::Result<()> {
+    // Write beside the target and rename, so a crash mid-write never leaves a
+    // truncated checkpoint in place of the previous one.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&(memory.len() as u64).to_le_bytes())?;
+    file.write_all(memory)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)
+}
+
+/// Read the memory image stored by `cl_checkpoint`.
+pub(crate) fn read_checkpoint(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = fs::read(path)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    if bytes.len() < HEADER_LEN || bytes[..4] != MAGIC {
+        return Err(invalid("not a checkpoint file"));
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(invalid(&format!(
+            "unsupported checkpoint version {version}"
+        )));
+    }
+    let len = u64::from_le_bytes(bytes[6..HEADER_LEN].try_into().unwrap()) as usize;
+    if bytes.len() - HEADER_LEN != len {
+        return Err(invalid("truncated checkpoint"));
+    }
+    Ok(bytes[HEADER_LEN..].to_vec())
+}
+
+/// Snapshot memory `[0, len)` to the NUL-terminated path at `path_off`.
+/// `inflight_off` holds a u64 counter of outstanding work (threads, pool jobs,
+/// GPU dispatches) that the algorithm maintains; while it is non-zero the
+/// snapshot is refused. Returns `len`, -2 if work is still in flight, or -1.
+pub(crate) unsafe extern "C" fn cl_checkpoint(
+    ptr: *mut u8,
+    path_off: i64,
+    len: i64,
+    inflight_off: i64,
+) -> i64 {
+    if ptr.is_null() || path_off < 0 || len <= 0 || inflight_off < 0 || inflight_off % 8 != 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    status::begin();
+    if io_log::refuse_unlogged("cl_checkpoint") {
+        return -1;
+    }
+    let inflight = &*(ptr.add(inflight_off as usize) as *const AtomicU64);
+    if inflight.load(Ordering::Acquire) != 0 {
+        return -2;
+    }
+    let Some(path) = sandbox::resolve(read_path(ptr, path_off as usize)) else {
+        return -1;
+    };
+    let memory = std::slice::from_raw_parts(ptr, len as usize);
+    match write_checkpoint(&path, memory) {
+        Ok(()) => {
+            status::ok(len as u64);
+            len
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn checkpoint_round_trips_and_refuses_in_flight_work() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("state.ckpt");
+
+        let mut mem = vec![0u8; 256];
+        let p = path.to_str().unwrap().as_bytes();
+        mem[64..64 + p.len()].copy_from_slice(p);
+        mem[200..208].copy_from_slice(&42u64.to_le_bytes());
+        mem[8] = 1;
+        let ptr = mem.as_mut_ptr();
+        assert_eq!(unsafe { cl_checkpoint(ptr, 64, 256, 8) }, -2);
+        assert!(!path.exists());
+
+        mem[8] = 0;
+        assert_eq!(unsafe { cl_checkpoint(ptr, 64, 256, 8) }, 256);
+        assert_eq!(read_checkpoint(&path).unwrap(), mem);
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        fs::write(&path, &bytes).unwrap();
+        assert!(read_checkpoint(&path).is_err());
+        assert_eq!(unsafe { cl_checkpoint(ptr, 64, 0, 8) }, -1);
+    }
+}
@@ -0,0 +1,304 @@
+//! Checksums over the memory region, for stamping and verifying frames.
+
+use super::status;
+use base_types::status::INVALID_ARGUMENT;
+
+/// `cl_checksum` algorithm: CRC-32 (IEEE 802.3, as in zlib and gzip).
+pub(crate) const CHECKSUM_CRC32: i64 = 0;
+/// `cl_checksum` algorithm: CRC-32C (Castagnoli, as in iSCSI and ext4).
+pub(crate) const CHECKSUM_CRC32C: i64 = 1;
+/// `cl_checksum` algorithm: XXH64 with seed 0.
+pub(crate) const CHECKSUM_XXH64: i64 = 2;
+/// `cl_checksum` flag: continue the CRC already stored at `dst_off` rather
+/// than starting a new one.
+pub(crate) const CHECKSUM_CONTINUE: i64 = 1 << 8;
+
+const CRC32_POLY: u32 = 0xEDB8_8320;
+const CRC32C_POLY: u32 = 0x82F6_3B78;
+
+/// Slicing-by-8 tables: `t[0]` is the classic byte table, `t[k]` advances a
+/// byte that is followed by `k` more.
+const fn crc_tables(poly: u32) -> [[u32; 256]; 8] {
+    let mut t = [[0u32; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { (c >> 1) ^ poly } else { c >> 1 };
+            k += 1;
+        }
+        t[0][i] = c;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut k = 1;
+        while k < 8 {
+            let prev = t[k - 1][i];
+            t[k][i] = (prev >> 8) ^ t[0][(prev & 0xFF) as usize];
+            k += 1;
+        }
+        i += 1;
+    }
+    t
+}
+
+static CRC32_TABLES: [[u32; 256]; 8] = crc_tables(CRC32_POLY);
+static CRC32C_TABLES: [[u32; 256]; 8] = crc_tables(CRC32C_POLY);
+
+/// Extend `crc` (a finished CRC, 0 for an empty prefix) over `data`.
+fn crc_software(t: &[[u32; 256]; 8], crc: u32, data: &[u8]) -> u32 {
+    let mut c = !crc;
+    let mut chunks = data.chunks_exact(8);
+    for w in &mut chunks {
+        let lo = c ^ u32::from_le_bytes([w[0], w[1], w[2], w[3]]);
+        c = t[7][(lo & 0xFF) as usize]
+            ^ t[6][((lo >> 8) & 0xFF) as usize]
+            ^ t[5][((lo >> 16) & 0xFF) as usize]
+            ^ t[4][(lo >> 24) as usize]
+            ^ t[3][w[4] as usize]
+            ^ t[2][w[5] as usize]
+            ^ t[1][w[6] as usize]
+            ^ t[0][w[7] as usize];
+    }
+    for &b in chunks.remainder() {
+        c = (c >> 8) ^ t[0][((c ^ b as u32) & 0xFF) as usize];
+    }
+    !c
+}
+
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    crc_software(&CRC32_TABLES, crc, data)
+}
+
+/// CRC-32C, using the SSE4.2 or ARMv8 CRC instructions when the CPU has them.
+fn crc32c(crc: u32, data: &[u8]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("sse4.2") {
+        return unsafe { crc32c_sse42(crc, data) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("crc") {
+        return unsafe { crc32c_arm(crc, data) };
+    }
+    crc_software(&CRC32C_TABLES, crc, data)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+unsafe fn crc32c_sse42(crc: u32, data: &[u8]) -> u32 {
+    use std::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+    let mut c = !crc as u64;
+    let mut chunks = data.chunks_exact(8);
+    for w in &mut chunks {
+        c = _mm_crc32_u64(c, u64::from_le_bytes(w.try_into().unwrap()));
+    }
+    let mut c = c as u32;
+    for &b in chunks.remainder() {
+        c = _mm_crc32_u8(c, b);
+    }
+    !c
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "crc")]
+unsafe fn crc32c_arm(crc: u32, data: &[u8]) -> u32 {
+    use std::arch::aarch64::{__crc32cb, __crc32cd};
+    let mut c = !crc;
+    let mut chunks = data.chunks_exact(8);
+    for w in &mut chunks {
+        c = __crc32cd(c, u64::from_le_bytes(w.try_into().unwrap()));
+    }
+    for &b in chunks.remainder() {
+        c = __crc32cb(c, b);
+    }
+    !c
+}
+
+const P1: u64 = 0x9E37_79B1_85EB_CA87;
+const P2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const P3: u64 = 0x1656_67B1_9E37_79F9;
+const P4: u64 = 0x85EB_CA77_C2B2_AE63;
+const P5: u64 = 0x27D4_EB2F_1656_67C5;
+
+fn xxh_round(acc: u64, lane: u64) -> u64 {
+    acc.wrapping_add(lane.wrapping_mul(P2))
+        .rotate_left(31)
+        .wrapping_mul(P1)
+}
+
+fn xxh_merge(acc: u64, v: u64) -> u64 {
+    (acc ^ xxh_round(0, v)).wrapping_mul(P1).wrapping_add(P4)
+}
+
+fn xxh64(data: &[u8], seed: u64) -> u64 {
+    let u64_at = |i: usize| u64::from_le_bytes(data[i..i + 8].try_into().unwrap());
+    let mut i = 0;
+    let mut h = if data.len() >= 32 {
+        let mut v = [
+            seed.wrapping_add(P1).wrapping_add(P2),
+            seed.wrapping_add(P2),
+            seed,
+            seed.wrapping_sub(P1),
+        ];
+        while i + 32 <= data.len() {
+            for (k, acc) in v.iter_mut().enumerate() {
+                *acc = xxh_round(*acc, u64_at(i + 8 * k));
+            }
+            i += 32;
+        }
+        let h = v[0]
+            .rotate_left(1)
+            .wrapping_add(v[1].rotate_left(7))
+            .wrapping_add(v[2].rotate_left(12))
+            .wrapping_add(v[3].rotate_left(18));
+        v.iter().fold(h, |h, &acc| xxh_merge(h, acc))
+    } else {
+        seed.wrapping_add(P5)
+    };
+    h = h.wrapping_add(data.len() as u64);
+
+    while i + 8 <= data.len() {
+        h = (h ^ xxh_round(0, u64_at(i)))
+            .rotate_left(27)
+            .wrapping_mul(P1)
+            .wrapping_add(P4);
+        i += 8;
+    }
+    if i + 4 <= data.len() {
+        let lane = u32::from_le_bytes(data[i..i + 4].try_into().unwrap()) as u64;
+        h = (h ^ lane.wrapping_mul(P1))
+            .rotate_left(23)
+            .wrapping_mul(P2)
+            .wrapping_add(P3);
+        i += 4;
+    }
+    for &b in &data[i..] {
+        h = (h ^ (b as u64).wrapping_mul(P5))
+            .rotate_left(11)
+            .wrapping_mul(P1);
+    }
+
+    h ^= h >> 33;
+    h = h.wrapping_mul(P2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(P3);
+    h ^ (h >> 32)
+}
+
+/// Checksum the `size` bytes at `src_off` and write the result as a u64 at
+/// `dst_off` (CRCs zero-extended). The low byte of `algo` selects
+/// `CHECKSUM_CRC32`, `CHECKSUM_CRC32C` or `CHECKSUM_XXH64`. With
+/// `CHECKSUM_CONTINUE` set, a CRC resumes from the value already at
+/// `dst_off`, so a stream checksummed in pieces gives the same result as in
+/// one call; XXH64 needs more state than fits in the slot and rejects the
+/// flag. Returns `size`, or -1 with `INVALID_ARGUMENT` on bad arguments.
+pub(crate) unsafe extern "C" fn cl_checksum(
+    ptr: *mut u8,
+    algo: i64,
+    src_off: i64,
+    size: i64,
+    dst_off: i64,
+) -> i64 {
+    let resume = algo & CHECKSUM_CONTINUE != 0;
+    let valid = match algo & !CHECKSUM_CONTINUE {
+        CHECKSUM_CRC32 | CHECKSUM_CRC32C => true,
+        CHECKSUM_XXH64 => !resume,
+        _ => false,
+    };
+    if ptr.is_null() || !valid || src_off < 0 || size < 0 || dst_off < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let data = std::slice::from_raw_parts(ptr.add(src_off as usize), size as usize);
+    let slot = ptr.add(dst_off as usize) as *mut u64;
+    let prev = if resume {
+        std::ptr::read_unaligned(slot) as u32
+    } else {
+        0
+    };
+    let sum = match algo & 0xFF {
+        CHECKSUM_CRC32 => crc32(prev, data) as u64,
+        CHECKSUM_CRC32C => crc32c(prev, data) as u64,
+        _ => xxh64(data, 0),
+    };
+    std::ptr::write_unaligned(slot, sum);
+    status::ok(size as u64);
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_answers() {
+        assert_eq!(crc32(0, b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(0, b""), 0);
+        assert_eq!(
+            crc32(0, b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+        assert_eq!(crc32c(0, b"123456789"), 0xE306_9283);
+        assert_eq!(crc_software(&CRC32C_TABLES, 0, b"123456789"), 0xE306_9283);
+        assert_eq!(crc32c(0, &[0u8; 32]), 0x8A91_36AA);
+        assert_eq!(xxh64(b"", 0), 0xEF46_DB37_51D8_E999);
+        assert_eq!(xxh64(b"abc", 0), 0x44BC_2CF5_AD77_0999);
+        assert_eq!(
+            xxh64(b"Nobody inspects the spammish repetition", 0),
+            0xFBCE_A83C_8A37_8BF1
+        );
+    }
+
+    #[test]
+    fn incremental_crc_equals_one_shot() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let mut mem = data.clone();
+        mem.extend([0u8; 16]);
+        let p = mem.as_mut_ptr();
+        let n = data.len() as i64;
+        for algo in [CHECKSUM_CRC32, CHECKSUM_CRC32C] {
+            unsafe {
+                assert_eq!(cl_checksum(p, algo, 0, n, n), n);
+                let one_shot = u64::from_le_bytes(mem[data.len()..][..8].try_into().unwrap());
+                assert_eq!(cl_checksum(p, algo, 0, 3, n + 8), 3);
+                assert_eq!(
+                    cl_checksum(p, algo | CHECKSUM_CONTINUE, 3, 4000, n + 8),
+                    4000
+                );
+                assert_eq!(
+                    cl_checksum(p, algo | CHECKSUM_CONTINUE, 4003, n - 4003, n + 8),
+                    n - 4003
+                );
+                let pieces = u64::from_le_bytes(mem[data.len() + 8..][..8].try_into().unwrap());
+                assert_eq!(pieces, one_shot);
+                assert!(one_shot <= u32::MAX as u64);
+            }
+        }
+        assert_eq!(
+            crc_software(&CRC32C_TABLES, 0, &data),
+            crc32c(0, &data),
+            "hardware and software CRC-32C agree"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        let mut mem = [0u8; 16];
+        let p = mem.as_mut_ptr();
+        unsafe {
+            assert_eq!(cl_checksum(p, 3, 0, 8, 8), -1);
+            assert_eq!(
+                cl_checksum(p, CHECKSUM_XXH64 | CHECKSUM_CONTINUE, 0, 8, 8),
+                -1
+            );
+            assert_eq!(cl_checksum(p, CHECKSUM_CRC32, -1, 8, 8), -1);
+            assert_eq!(
+                cl_checksum(std::ptr::null_mut(), CHECKSUM_CRC32, 0, 8, 8),
+                -1
+            );
+            assert_eq!(cl_checksum(p, CHECKSUM_XXH64, 0, 8, 8), 8);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Clocks for timing and ordering work inside an execution.
+//!
+//! Each `Base::execute*` call starts a fresh `ExecClock`; threads started
+//! through `cl_thread_*` share it, so monotonic readings and sequence
+//! numbers are comparable across workers.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::thread::sleep_unless_cancelled;
+
+/// `cl_clock` sources.
+pub(crate) const CLOCK_WALL: i64 = 0;
+pub(crate) const CLOCK_MONOTONIC: i64 = 1;
+pub(crate) const CLOCK_SEQUENCE: i64 = 2;
+
+pub(crate) struct ExecClock {
+    start: Instant,
+    sequence: AtomicU64,
+}
+
+impl ExecClock {
+    fn new() -> ExecClock {
+        ExecClock {
+            start: Instant::now(),
+            sequence: AtomicU64::new(0),
+        }
+    }
+}
+
+thread_local! {
+    /// Clock of the execution running on this thread (propagated to threads
+    /// started through `cl_thread_*`).
+    static EXEC_CLOCK: RefCell<Option<Arc<ExecClock>>> = const { RefCell::new(None) };
+}
+
+/// Start a new execution clock on this thread.
+pub(crate) fn begin() {
+    set_clock(Some(Arc::new(ExecClock::new())));
+}
+
+pub(crate) fn current_clock() -> Option<Arc<ExecClock>> {
+    EXEC_CLOCK.with(|cell| cell.borrow().clone())
+}
+
+pub(crate) fn set_clock(clock: Option<Arc<ExecClock>>) {
+    EXEC_CLOCK.with(|cell| *cell.borrow_mut() = clock);
+}
+
+/// Used outside an execution, e.g. by FFI unit tests.
+fn fallback_clock() -> &'static ExecClock {
+    static CLOCK: OnceLock<ExecClock> = OnceLock::new();
+    CLOCK.get_or_init(ExecClock::new)
+}
+
+fn with_clock<R>(f: impl FnOnce(&ExecClock) -> R) -> R {
+    EXEC_CLOCK.with(|cell| match cell.borrow().as_deref() {
+        Some(clock) => f(clock),
+        None => f(fallback_clock()),
+    })
+}
+
+/// Read clock `source`: `CLOCK_WALL` (UNIX time in ns), `CLOCK_MONOTONIC`
+/// (ns since the execution started), or `CLOCK_SEQUENCE` (the next value of
+/// a counter that starts at 0 each execution and is shared by all of its
+/// threads). Returns -1 for an unknown source.
+pub(crate) unsafe extern "C" fn cl_clock(source: i64) -> i64 {
+    match source {
+        CLOCK_WALL => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as i64),
+        CLOCK_MONOTONIC => with_clock(|c| c.start.elapsed().as_nanos() as i64),
+        CLOCK_SEQUENCE => with_clock(|c| c.sequence.fetch_add(1, Ordering::Relaxed) as i64),
+        _ => -1,
+    }
+}
+
+/// Sleep for `ns` nanoseconds without spinning, e.g. between retries or to
+/// pace sends. Other threads keep running, and cancelling the execution
+/// (including an `execute_with_timeout` deadline) ends the sleep at once.
+/// Returns 0, -1 for a negative duration, or -2 if cancelled.
+pub(crate) unsafe extern "C" fn cl_sleep(ns: i64) -> i64 {
+    if ns < 0 {
+        return -1;
+    }
+    if sleep_unless_cancelled(Duration::from_nanos(ns as u64)) {
+        0
+    } else {
+        -2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_and_sleep() {
+        begin();
+        unsafe {
+            let wall = cl_clock(CLOCK_WALL);
+            assert!(wall > 1_600_000_000 * 1_000_000_000);
+            let t0 = cl_clock(CLOCK_MONOTONIC);
+            assert_eq!(cl_sleep(2_000_000), 0);
+            assert!(cl_clock(CLOCK_MONOTONIC) - t0 >= 2_000_000);
+            assert_eq!(cl_clock(CLOCK_SEQUENCE), 0);
+            assert_eq!(cl_clock(CLOCK_SEQUENCE), 1);
+            assert_eq!(cl_clock(3), -1);
+            assert_eq!(cl_sleep(-1), -1);
+        }
+        begin();
+        assert_eq!(
+            unsafe { cl_clock(CLOCK_SEQUENCE) },
+            0,
+            "restarts per execution"
+        );
+        set_clock(None);
+    }
+}
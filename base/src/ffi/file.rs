@@ -1,7 +1,201 @@
+use std::borrow::Cow;
+use std::cell::Cell;
 use std::fs;
-use std::io::{Read as IoRead, Seek, Write as IoWrite};
+use std::io::{self, Read as IoRead, Seek, Write as IoWrite};
+use std::path::Path;
 
-use super::{read_cstr, read_cstr_ptr};
+use super::retry::{self, RetryPolicy};
+use super::{io_log, read_path, read_path_ptr, sandbox, status};
+use base_types::status::INVALID_ARGUMENT;
+use tracing::error;
+
+/// Process streams named by the pseudo-paths `/dev/stdin`, `/dev/stdout` and
+/// `/dev/stderr`. They are served from the process's own handles instead of
+/// being opened, so they work where `/dev` does not exist; `file_offset` is
+/// ignored, and a size of 0 is rejected for stdin since it has no length.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Stream {
+    Stdin,
+    Stdout,
+    Stderr,
+}
+
+fn stream(path: &Path) -> Option<Stream> {
+    match path.to_str()? {
+        "/dev/stdin" => Some(Stream::Stdin),
+        "/dev/stdout" => Some(Stream::Stdout),
+        "/dev/stderr" => Some(Stream::Stderr),
+        _ => None,
+    }
+}
+
+/// Read side of a stream pseudo-path: only stdin, and only sized reads. Fills
+/// `size` bytes, stopping early only at end of input.
+unsafe fn read_stream(stream: Stream, dst: *mut u8, size: i64) -> i64 {
+    if stream != Stream::Stdin || size <= 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let dst = std::slice::from_raw_parts_mut(dst, size as usize);
+    let mut stdin = io::stdin().lock();
+    let mut total = 0;
+    while total < dst.len() {
+        match stdin.read(&mut dst[total..]) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                status::io(&e);
+                return -1;
+            }
+        }
+    }
+    status::ok(total as u64);
+    total as i64
+}
+
+/// Write all of `data` under the stream's lock, so writes from concurrent
+/// threads never interleave within one call.
+fn write_stream(stream: Stream, data: &[u8]) -> i64 {
+    let result = match stream {
+        Stream::Stdout => {
+            let mut out = io::stdout().lock();
+            out.write_all(data).and_then(|_| out.flush())
+        }
+        Stream::Stderr => io::stderr().lock().write_all(data),
+        Stream::Stdin => {
+            status::set(INVALID_ARGUMENT, 0);
+            return -1;
+        }
+    };
+    match result {
+        Ok(()) => {
+            status::ok(data.len() as u64);
+            data.len() as i64
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    }
+}
+
+thread_local! {
+    /// The policy `cl_file_retry` set for this thread's file calls.
+    static RETRY: Cell<Option<RetryPolicy>> = const { Cell::new(None) };
+}
+
+pub(crate) fn retry_policy() -> Option<RetryPolicy> {
+    RETRY.with(Cell::get)
+}
+
+pub(crate) fn set_retry_policy(policy: Option<RetryPolicy>) {
+    RETRY.with(|cell| cell.set(policy));
+}
+
+/// Retry transient failures of this thread's `cl_file_read`,
+/// `cl_file_write`, `cl_file_read_to_ptr` and `cl_file_write_from_ptr` calls
+/// under the policy packed at `policy_ptr` (see `retry::RETRY_POLICY`).
+/// Transient means EINTR, EAGAIN or EIO; anything else, such as ENOENT,
+/// EACCES or ENOSPC, fails at once. Calls made under a policy report their
+/// attempt count in the status word (see `base_types::status::with_attempts`).
+/// Threads started afterwards inherit the policy. A null `policy_ptr` turns
+/// retries off, which is the default. Returns 0.
+pub(crate) unsafe extern "C" fn cl_file_retry(policy_ptr: *const u8) -> i64 {
+    set_retry_policy(RetryPolicy::read(policy_ptr));
+    0
+}
+
+/// `EIO`, which has no `io::ErrorKind`; the number is the same on Linux,
+/// macOS and the BSDs.
+const EIO: i32 = 5;
+
+/// Whether the failure in status word `word` is worth another attempt:
+/// EINTR or EAGAIN, classified by `io::ErrorKind` so each platform's
+/// numbering applies, or EIO.
+fn is_transient(word: u64) -> bool {
+    let code = base_types::status::status(word);
+    if code == 0 || code > 0xFFFF {
+        return false;
+    }
+    let err = io::Error::from_raw_os_error(code as i32);
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    ) || err.raw_os_error() == Some(EIO)
+}
+
+/// Run the file call `op` on `path` under this thread's retry policy. Stream
+/// pseudo-paths run once: a retry could repeat a partial read or write.
+fn retried(path: &Path, mut op: impl FnMut() -> i64) -> i64 {
+    let policy = retry_policy().filter(|_| stream(path).is_none());
+    let Some(policy) = policy else {
+        return op();
+    };
+    let (result, attempts) = retry::with_retry(
+        Some(policy),
+        |_| is_transient(status::word()),
+        || match op() {
+            n if n >= 0 => Ok(n),
+            n => Err(n),
+        },
+    );
+    status::attempts(attempts);
+    result.unwrap_or_else(|n| n)
+}
+
+/// Test-only fault injection: `/__fail_n_times/N/<path>` fails with EIO the
+/// first N times it is opened, then stands for `/<path>`.
+#[cfg(test)]
+fn inject(path: &Path) -> io::Result<Cow<'_, Path>> {
+    use std::path::{Component, PathBuf};
+    use std::sync::Mutex;
+    static FAILURES: Mutex<Vec<(PathBuf, u32)>> = Mutex::new(Vec::new());
+    let Ok(rest) = path.strip_prefix("/__fail_n_times") else {
+        return Ok(Cow::Borrowed(path));
+    };
+    let mut components = rest.components();
+    let Some(Component::Normal(n)) = components.next() else {
+        return Ok(Cow::Borrowed(path));
+    };
+    let n: u32 = n.to_str().and_then(|n| n.parse().ok()).unwrap_or(0);
+    let mut failures = FAILURES.lock().unwrap();
+    let left = match failures.iter_mut().find(|(p, _)| p == path) {
+        Some((_, left)) => left,
+        None => {
+            failures.push((path.to_path_buf(), n));
+            &mut failures.last_mut().unwrap().1
+        }
+    };
+    if *left > 0 {
+        *left -= 1;
+        return Err(io::Error::from_raw_os_error(EIO));
+    }
+    Ok(Cow::Owned(Path::new("/").join(components.as_path())))
+}
+
+#[cfg(not(test))]
+fn inject(path: &Path) -> io::Result<Cow<'_, Path>> {
+    Ok(Cow::Borrowed(path))
+}
+
+/// `path` as the sandbox resolves it, or `None` with the status set.
+fn resolve(call: &'static str, path: &Path) -> Option<std::path::PathBuf> {
+    match inject(path) {
+        Ok(path) => sandbox::resolve(path.into_owned()),
+        Err(e) => {
+            io_failed(call, path, &e);
+            None
+        }
+    }
+}
+
+/// Report a failed file operation: set the status word and log the path.
+pub(super) fn io_failed(call: &'static str, path: &Path, err: &io::Error) -> i64 {
+    status::io(err);
+    error!(call, path = %path.display(), error = %err, "file call failed");
+    -1
+}
 
 pub(crate) unsafe extern "C" fn cl_file_read(
     ptr: *mut u8,
@@ -10,10 +204,32 @@ pub(crate) unsafe extern "C" fn cl_file_read(
     file_offset: i64,
     size: i64,
 ) -> i64 {
-    let filename = read_cstr(ptr, path_off as usize);
+    status::begin();
+    let filename = read_path(ptr, path_off as usize);
+    let dst = ptr.add(dst_off as usize);
+    let key = || format!("cl_file_read {} {file_offset} {size}", filename.display());
+    // Size 0 reads the whole file, however long.
+    let capacity = if size == 0 {
+        usize::MAX
+    } else {
+        size.max(0) as usize
+    };
+    io_log::read(key, -1, dst, capacity, || {
+        let n = retried(&filename, || read_file(&filename, dst, file_offset, size));
+        (n, n.max(0) as usize)
+    })
+}
+
+unsafe fn read_file(filename: &Path, dst: *mut u8, file_offset: i64, size: i64) -> i64 {
+    if let Some(stream) = stream(filename) {
+        return read_stream(stream, dst, size);
+    }
+    let Some(filename) = resolve("cl_file_read", filename) else {
+        return -1;
+    };
     let mut file = match fs::File::open(&filename) {
         Ok(f) => f,
-        Err(_) => return -1,
+        Err(e) => return io_failed("cl_file_read", &filename, &e),
     };
     if file_offset > 0 {
         let _ = file.seek(std::io::SeekFrom::Start(file_offset as u64));
@@ -21,9 +237,10 @@ pub(crate) unsafe extern "C" fn cl_file_read(
     if size == 0 {
         let file_len = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
         if file_len == 0 {
+            status::ok(0);
             return 0;
         }
-        let dst = std::slice::from_raw_parts_mut(ptr.add(dst_off as usize), file_len);
+        let dst = std::slice::from_raw_parts_mut(dst, file_len);
         let mut total = 0;
         while total < file_len {
             match file.read(&mut dst[total..]) {
@@ -32,12 +249,16 @@ pub(crate) unsafe extern "C" fn cl_file_read(
                 Err(_) => break,
             }
         }
+        status::ok(total as u64);
         total as i64
     } else {
-        let dst = std::slice::from_raw_parts_mut(ptr.add(dst_off as usize), size as usize);
+        let dst = std::slice::from_raw_parts_mut(dst, size as usize);
         match file.read(dst) {
-            Ok(n) => n as i64,
-            Err(_) => -1,
+            Ok(n) => {
+                status::ok(n as u64);
+                n as i64
+            }
+            Err(e) => io_failed("cl_file_read", &filename, &e),
         }
     }
 }
@@ -51,23 +272,38 @@ pub(crate) unsafe extern "C" fn cl_file_write_from_ptr(
     size: i64,
 ) -> i64 {
     if path_ptr.is_null() || src_ptr.is_null() || size <= 0 || file_offset < 0 {
+        status::set(INVALID_ARGUMENT, 0);
         return -1;
     }
-    let path = read_cstr_ptr(path_ptr);
+    status::begin();
+    let path = read_path_ptr(path_ptr);
+    let src = std::slice::from_raw_parts(src_ptr, size as usize);
+    let key = || format!("cl_file_write_from_ptr {} {file_offset}", path.display());
+    io_log::write(key, -1, src, || {
+        retried(&path, || write_at(&path, src, file_offset))
+    })
+}
+
+fn write_at(path: &Path, src: &[u8], file_offset: i64) -> i64 {
+    if let Some(stream) = stream(path) {
+        return write_stream(stream, src);
+    }
+    let Some(path) = resolve("cl_file_write_from_ptr", path) else {
+        return -1;
+    };
     let mut file = match fs::OpenOptions::new().write(true).create(true).open(&path) {
         Ok(f) => f,
-        Err(_) => return -1,
+        Err(e) => return io_failed("cl_file_write_from_ptr", &path, &e),
     };
-    if file
-        .seek(std::io::SeekFrom::Start(file_offset as u64))
-        .is_err()
-    {
-        return -1;
+    if let Err(e) = file.seek(std::io::SeekFrom::Start(file_offset as u64)) {
+        return io_failed("cl_file_write_from_ptr", &path, &e);
     }
-    let src = std::slice::from_raw_parts(src_ptr, size as usize);
     match file.write_all(src) {
-        Ok(_) => size,
-        Err(_) => -1,
+        Ok(_) => {
+            status::ok(src.len() as u64);
+            src.len() as i64
+        }
+        Err(e) => io_failed("cl_file_write_from_ptr", &path, &e),
     }
 }
 
@@ -79,19 +315,37 @@ pub(crate) unsafe extern "C" fn cl_file_read_to_ptr(
     size: i64,
 ) -> i64 {
     if path_ptr.is_null() || dst_ptr.is_null() || size <= 0 {
+        status::set(INVALID_ARGUMENT, 0);
         return -1;
     }
-    let path = read_cstr_ptr(path_ptr);
+    status::begin();
+    let path = read_path_ptr(path_ptr);
+    let key = || {
+        format!(
+            "cl_file_read_to_ptr {} {file_offset} {size}",
+            path.display()
+        )
+    };
+    io_log::read(key, -1, dst_ptr, size.max(0) as usize, || {
+        let n = retried(&path, || read_to(&path, dst_ptr, file_offset, size));
+        (n, n.max(0) as usize)
+    })
+}
+
+unsafe fn read_to(path: &Path, dst_ptr: *mut u8, file_offset: i64, size: i64) -> i64 {
+    if let Some(stream) = stream(path) {
+        return read_stream(stream, dst_ptr, size);
+    }
+    let Some(path) = resolve("cl_file_read_to_ptr", path) else {
+        return -1;
+    };
     let mut file = match fs::File::open(&path) {
         Ok(f) => f,
-        Err(_) => return -1,
+        Err(e) => return io_failed("cl_file_read_to_ptr", &path, &e),
     };
     if file_offset > 0 {
-        if file
-            .seek(std::io::SeekFrom::Start(file_offset as u64))
-            .is_err()
-        {
-            return -1;
+        if let Err(e) = file.seek(std::io::SeekFrom::Start(file_offset as u64)) {
+            return io_failed("cl_file_read_to_ptr", &path, &e);
         }
     }
     let dst = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
@@ -100,12 +354,28 @@ pub(crate) unsafe extern "C" fn cl_file_read_to_ptr(
         match file.read(&mut dst[total..]) {
             Ok(0) => break,
             Ok(n) => total += n,
-            Err(_) => return -1,
+            Err(e) => return io_failed("cl_file_read_to_ptr", &path, &e),
         }
     }
+    status::ok(total as u64);
     total as i64
 }
 
+/// `size` bytes at `src_off`, or the NUL-terminated string there when `size`
+/// is 0.
+pub(super) unsafe fn write_source<'a>(ptr: *mut u8, src_off: i64, size: i64) -> &'a [u8] {
+    let base = ptr.add(src_off as usize);
+    if size == 0 {
+        let mut len = 0;
+        while *base.add(len) != 0 {
+            len += 1;
+        }
+        std::slice::from_raw_parts(base, len)
+    } else {
+        std::slice::from_raw_parts(base, size as usize)
+    }
+}
+
 pub(crate) unsafe extern "C" fn cl_file_write(
     ptr: *mut u8,
     path_off: i64,
@@ -113,11 +383,26 @@ pub(crate) unsafe extern "C" fn cl_file_write(
     file_offset: i64,
     size: i64,
 ) -> i64 {
-    let filename = read_cstr(ptr, path_off as usize);
+    status::begin();
+    let filename = read_path(ptr, path_off as usize);
+    let data = write_source(ptr, src_off, size);
+    let key = || format!("cl_file_write {} {file_offset}", filename.display());
+    io_log::write(key, -1, data, || {
+        retried(&filename, || write_file(&filename, data, file_offset))
+    })
+}
+
+fn write_file(filename: &Path, data: &[u8], file_offset: i64) -> i64 {
+    if let Some(stream) = stream(filename) {
+        return write_stream(stream, data);
+    }
+    let Some(filename) = resolve("cl_file_write", filename) else {
+        return -1;
+    };
     let mut file = if file_offset == 0 {
         match fs::File::create(&filename) {
             Ok(f) => f,
-            Err(_) => return -1,
+            Err(e) => return io_failed("cl_file_write", &filename, &e),
         }
     } else {
         match fs::OpenOptions::new()
@@ -129,35 +414,21 @@ pub(crate) unsafe extern "C" fn cl_file_write(
                 let _ = f.seek(std::io::SeekFrom::Start(file_offset as u64));
                 f
             }
-            Err(_) => return -1,
+            Err(e) => return io_failed("cl_file_write", &filename, &e),
         }
     };
-    let written = if size == 0 {
-        let base = ptr.add(src_off as usize);
-        let mut len = 0;
-        while *base.add(len) != 0 {
-            len += 1;
-        }
-        if len > 0 {
-            let data = std::slice::from_raw_parts(base, len);
-            match file.write_all(data) {
-                Ok(_) => len as i64,
-                Err(_) => -1,
-            }
-        } else {
-            0
-        }
-    } else {
-        let data = std::slice::from_raw_parts(ptr.add(src_off as usize), size as usize);
-        match file.write_all(data) {
-            Ok(_) => size,
-            Err(_) => -1,
+    if data.is_empty() {
+        status::ok(0);
+        return 0;
+    }
+    match file.write_all(data) {
+        Ok(_) => {
+            let _ = file.sync_all();
+            status::ok(data.len() as u64);
+            data.len() as i64
         }
-    };
-    if written >= 0 {
-        let _ = file.sync_all();
+        Err(e) => io_failed("cl_file_write", &filename, &e),
     }
-    written
 }
 
 #[cfg(test)]
@@ -181,6 +452,53 @@ mod tests {
         (mem, path_off, src_off)
     }
 
+    /// Run as a child process by `stdio_pseudo_paths_round_trip`: copies 11
+    /// bytes from stdin to stdout, then to stderr.
+    #[test]
+    fn stdio_pseudo_paths_child() {
+        if std::env::var_os("CL_FILE_STDIO_CHILD").is_none() {
+            return;
+        }
+        let mut mem = vec![0u8; 256];
+        mem[..11].copy_from_slice(b"/dev/stdin\0");
+        mem[16..28].copy_from_slice(b"/dev/stdout\0");
+        mem[32..44].copy_from_slice(b"/dev/stderr\0");
+        let p = mem.as_mut_ptr();
+        unsafe {
+            let n = cl_file_read(p, 0, 64, 0, 11);
+            assert_eq!(n, 11);
+            assert_eq!(cl_file_write(p, 16, 64, 0, n), n);
+            assert_eq!(cl_file_write_from_ptr(p.add(32), p.add(64), 0, n), n);
+            assert_eq!(cl_file_write(p, 0, 64, 0, n), -1, "stdin is read-only");
+            assert_eq!(cl_file_read(p, 0, 128, 0, 0), -1, "stdin has no length");
+        }
+    }
+
+    #[test]
+    fn stdio_pseudo_paths_round_trip() {
+        use std::process::{Command, Stdio};
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "ffi::file::tests::stdio_pseudo_paths_child"])
+            .args(["--nocapture", "--test-threads=1"])
+            .env("CL_FILE_STDIO_CHILD", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"hello\x00world")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{output:?}");
+        let contains = |hay: &[u8]| hay.windows(11).any(|w| w == b"hello\x00world");
+        assert!(contains(&output.stdout), "{output:?}");
+        assert!(contains(&output.stderr), "{output:?}");
+    }
+
     #[test]
     fn write_then_read_roundtrip() {
         let tmp = TempDir::new().unwrap();
@@ -285,6 +603,27 @@ mod tests {
         assert_eq!(&mem[dst_off..dst_off + payload.len()], payload);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_path_bytes_name_the_file() {
+        use std::os::unix::ffi::OsStrExt;
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path().as_os_str().as_bytes();
+        let mut mem = vec![0u8; 1024];
+        mem[..dir.len()].copy_from_slice(dir);
+        let name = b"/caf\xE9 \xFF";
+        mem[dir.len()..dir.len() + name.len()].copy_from_slice(name);
+        mem[512..516].copy_from_slice(b"data");
+        unsafe {
+            assert_eq!(cl_file_write(mem.as_mut_ptr(), 0, 512, 0, 4), 4);
+            assert_eq!(cl_file_read(mem.as_mut_ptr(), 0, 768, 0, 4), 4);
+        }
+        assert_eq!(&mem[768..772], b"data");
+        let entry = std::fs::read_dir(tmp.path()).unwrap().next().unwrap();
+        let created = entry.unwrap().file_name();
+        assert_eq!(created.as_bytes(), &name[1..], "raw bytes, not U+FFFD");
+    }
+
     #[test]
     fn write_size_zero_treats_src_as_cstring() {
         let tmp = TempDir::new().unwrap();
@@ -482,4 +821,101 @@ mod tests {
             assert_eq!(n, -1);
         }
     }
+
+    #[test]
+    fn last_status_reports_errno_and_bytes() {
+        use base_types::status as st;
+        let tmp = TempDir::new().unwrap();
+        let good = tmp.path().join("ok.bin");
+        let bad = tmp.path().join("missing-dir").join("x.bin");
+        let (mut mem, path_off, src_off) = make_memory(good.to_str().unwrap(), b"abcdef");
+        unsafe {
+            assert_eq!(
+                cl_file_write(mem.as_mut_ptr(), path_off as i64, src_off as i64, 0, 6),
+                6
+            );
+            let word = status::cl_last_status() as u64;
+            assert_eq!((st::status(word), st::payload(word)), (st::OK, 6));
+
+            let (mut mem, path_off, src_off) = make_memory(bad.to_str().unwrap(), b"abcdef");
+            assert_eq!(
+                cl_file_write(mem.as_mut_ptr(), path_off as i64, src_off as i64, 0, 6),
+                -1
+            );
+            let word = status::cl_last_status() as u64;
+            assert_eq!(st::status(word), 2, "ENOENT");
+
+            assert_eq!(
+                cl_file_write_from_ptr(std::ptr::null(), mem.as_ptr(), 0, 1),
+                -1
+            );
+            let word = status::cl_last_status() as u64;
+            assert_eq!(st::status(word), st::INVALID_ARGUMENT);
+        }
+    }
+
+    #[test]
+    fn retry_policy_rides_out_transient_failures() {
+        use base_types::status as st;
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("flaky.bin");
+        fs::write(&file, b"abcdef").unwrap();
+        let flaky = |n: u32| format!("/__fail_n_times/{n}{}", file.display());
+        // max_attempts 3, no backoff.
+        let policy = [3u8, 0, 0, 10, 0, 0];
+        let mut dst = [0u8; 6];
+        let read = |path: &str, dst: &mut [u8]| {
+            let path = CString::new(path).unwrap();
+            let n = unsafe { cl_file_read_to_ptr(path.as_ptr() as _, dst.as_mut_ptr(), 0, 6) };
+            (n, status::word())
+        };
+        unsafe { cl_file_retry(policy.as_ptr()) };
+
+        let (n, word) = read(&flaky(2), &mut dst);
+        assert_eq!((n, st::attempts(word)), (6, 3));
+        assert_eq!(&dst, b"abcdef");
+        let (n, word) = read(&flaky(5), &mut dst);
+        assert_eq!(
+            (n, st::status(word), st::attempts(word)),
+            (-1, EIO as u32, 3)
+        );
+        let missing = tmp.path().join("missing.bin");
+        let (n, word) = read(missing.to_str().unwrap(), &mut dst);
+        assert_eq!(
+            (n, st::status(word), st::attempts(word)),
+            (-1, 2, 1),
+            "ENOENT"
+        );
+
+        unsafe { cl_file_retry(std::ptr::null()) };
+        let (n, word) = read(&flaky(1), &mut dst);
+        assert_eq!(
+            (n, st::status(word), st::attempts(word)),
+            (-1, EIO as u32, 0)
+        );
+        let (n, word) = read(&flaky(1), &mut dst);
+        assert_eq!((n, st::attempts(word)), (6, 0), "no policy, no count");
+    }
+
+    #[test]
+    fn transient_errors_are_classified_by_kind() {
+        use base_types::status as st;
+        let transient: Vec<u32> = (1..=0xFFFF)
+            .filter(|&code| is_transient(st::pack(code, 0)))
+            .collect();
+        let code = |kind| {
+            (1..=0xFFFF)
+                .find(|&c| io::Error::from_raw_os_error(c as i32).kind() == kind)
+                .unwrap()
+        };
+        let mut expected = vec![
+            code(io::ErrorKind::Interrupted),
+            code(io::ErrorKind::WouldBlock),
+            EIO as u32,
+        ];
+        expected.sort();
+        assert_eq!(transient, expected);
+        assert!(!is_transient(st::pack(st::OK, 0)));
+        assert!(!is_transient(st::pack(st::IO_ERROR, 0)));
+    }
 }
@@ -0,0 +1,351 @@
+//! Atomic file replacement: output goes to a temporary sibling of the
+//! destination (`<path>.tmp.<random>`), which is synced and renamed over the
+//! destination on commit. Until then, and whenever the writer fails or is
+//! dropped, the destination keeps its previous contents (or stays absent)
+//! and the temporary file is removed, so readers never see a torn file.
+
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io::{self, Seek, Write as IoWrite};
+use std::path::{Path, PathBuf};
+
+use super::handles::HandleTable;
+use super::{
+    clear_ctx_slot, io_log, read_ctx_mut, read_path, read_path_ptr, sandbox, status, write_ctx_slot,
+};
+use base_types::status::{INVALID_ARGUMENT, NOT_FOUND};
+
+/// An uncommitted output: the open temporary file and where it goes.
+struct AtomicFile {
+    file: fs::File,
+    tmp: PathBuf,
+    dest: PathBuf,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Create the temporary file next to `dest` (already sandbox-resolved).
+    fn create(dest: PathBuf) -> io::Result<AtomicFile> {
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp.");
+        loop {
+            let mut tmp_name = name.clone();
+            tmp_name.push(format!("{:016x}", RandomState::new().hash_one(&dest)));
+            let tmp = dest.with_file_name(tmp_name);
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&tmp)
+            {
+                Ok(file) => {
+                    return Ok(AtomicFile {
+                        file,
+                        tmp,
+                        dest,
+                        committed: false,
+                    })
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.file.seek(io::SeekFrom::Start(offset))?;
+        self.file.write_all(data)
+    }
+
+    /// Sync the data and rename it into place. On failure the temporary
+    /// file is removed when `self` drops.
+    fn commit(mut self) -> io::Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.tmp, &self.dest)?;
+        self.committed = true;
+        // The rename is durable once the directory entry is.
+        #[cfg(unix)]
+        if let Ok(dir) = fs::File::open(self.dest.parent().unwrap_or(Path::new("."))) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+/// Open atomic writers by handle. Dropping the context (`cl_file_atomic_cleanup`)
+/// discards every writer that was not committed.
+pub(crate) struct CraneliftFileAtomicContext {
+    files: HandleTable<AtomicFile>,
+}
+
+pub(crate) unsafe extern "C" fn cl_file_atomic_init(
+    ctx_slot_ptr: *mut *mut CraneliftFileAtomicContext,
+) {
+    let ctx = Box::new(CraneliftFileAtomicContext {
+        files: HandleTable::new(),
+    });
+    let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
+}
+
+pub(crate) unsafe extern "C" fn cl_file_atomic_cleanup(
+    ctx_slot_ptr: *mut *mut CraneliftFileAtomicContext,
+) {
+    let ctx_ptr = clear_ctx_slot::<CraneliftFileAtomicContext>(ctx_slot_ptr);
+    if !ctx_ptr.is_null() {
+        drop(Box::from_raw(ctx_ptr));
+    }
+}
+
+/// Start replacing the file at the NUL-terminated `path_ptr`. Returns a
+/// handle for `cl_file_atomic_write`, or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_file_atomic_open(
+    ctx: *mut CraneliftFileAtomicContext,
+    path_ptr: *const u8,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_atomic_open") {
+        return -1;
+    }
+    let Some(ctx) = read_ctx_mut::<CraneliftFileAtomicContext>(ctx) else {
+        return -1;
+    };
+    if path_ptr.is_null() {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(dest) = sandbox::resolve(read_path_ptr(path_ptr)) else {
+        return -1;
+    };
+    let file = match AtomicFile::create(dest) {
+        Ok(file) => file,
+        Err(e) => {
+            status::io(&e);
+            return -1;
+        }
+    };
+    match ctx.files.insert(file) {
+        Some(handle) => {
+            status::ok(0);
+            handle as i64
+        }
+        None => {
+            status::set(INVALID_ARGUMENT, 0);
+            -1
+        }
+    }
+}
+
+/// Write `size` bytes from `src_ptr` at `file_offset` of the pending file.
+/// A failed write discards the writer, leaving the destination untouched.
+/// Returns `size`, or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_file_atomic_write(
+    ctx: *mut CraneliftFileAtomicContext,
+    handle: i64,
+    src_ptr: *const u8,
+    file_offset: i64,
+    size: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftFileAtomicContext>(ctx) else {
+        return -1;
+    };
+    if src_ptr.is_null() || size < 0 || file_offset < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(file) = ctx.files.get_mut(handle as u32) else {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    };
+    let data = std::slice::from_raw_parts(src_ptr, size as usize);
+    match file.write_at(file_offset as u64, data) {
+        Ok(()) => {
+            status::ok(size as u64);
+            size
+        }
+        Err(e) => {
+            status::io(&e);
+            ctx.files.remove(handle as u32);
+            -1
+        }
+    }
+}
+
+/// Sync the pending file and rename it over its destination, retiring the
+/// handle. Returns 0, or -1 with the status set; on failure the destination
+/// is unchanged and the temporary file removed.
+pub(crate) unsafe extern "C" fn cl_file_commit(
+    ctx: *mut CraneliftFileAtomicContext,
+    handle: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftFileAtomicContext>(ctx) else {
+        return -1;
+    };
+    let Some(file) = ctx.files.remove(handle as u32) else {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    };
+    match file.commit() {
+        Ok(()) => {
+            status::ok(0);
+            0
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    }
+}
+
+/// Discard the pending file, leaving the destination as it was. Returns 0,
+/// or -1 (status `NOT_FOUND`) for an unknown or retired handle.
+pub(crate) unsafe extern "C" fn cl_file_abort(
+    ctx: *mut CraneliftFileAtomicContext,
+    handle: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftFileAtomicContext>(ctx) else {
+        return -1;
+    };
+    if ctx.files.remove(handle as u32).is_none() {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    }
+    status::ok(0);
+    0
+}
+
+/// Atomic counterpart of `cl_file_write` for whole files: replaces the file
+/// named at `path_off` with `size` bytes at `src_off` (the NUL-terminated
+/// string there when `size` is 0) through a synced temporary file. Returns
+/// the bytes written, or -1 with the status set and the file unchanged.
+pub(crate) unsafe extern "C" fn cl_file_write_atomic(
+    ptr: *mut u8,
+    path_off: i64,
+    src_off: i64,
+    size: i64,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_write_atomic") {
+        return -1;
+    }
+    if size < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(dest) = sandbox::resolve(read_path(ptr, path_off as usize)) else {
+        return -1;
+    };
+    let data = super::file::write_source(ptr, src_off, size);
+    let result = AtomicFile::create(dest).and_then(|mut file| {
+        file.write_at(0, data)?;
+        file.commit()
+    });
+    match result {
+        Ok(()) => {
+            status::ok(data.len() as u64);
+            data.len() as i64
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn commit_replaces_and_failures_leave_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("out.lz4");
+        fs::write(&dest, b"previous").unwrap();
+        let path = CString::new(dest.to_str().unwrap()).unwrap();
+        let path_ptr = path.as_ptr() as *const u8;
+        let mut slot: *mut CraneliftFileAtomicContext = std::ptr::null_mut();
+        unsafe {
+            cl_file_atomic_init(&mut slot);
+            // Abandoned mid-stream after a failed write.
+            let h = cl_file_atomic_open(slot, path_ptr);
+            assert!(h >= 0);
+            assert_eq!(entries(dir.path()).len(), 2, "temporary file beside it");
+            assert_eq!(cl_file_atomic_write(slot, h, b"half".as_ptr(), 0, 4), 4);
+            assert_eq!(cl_file_atomic_write(slot, h, std::ptr::null(), 4, 4), -1);
+            assert_eq!(cl_file_abort(slot, h), 0);
+            assert_eq!(cl_file_commit(slot, h), -1, "handle retired");
+            assert_eq!(fs::read(&dest).unwrap(), b"previous");
+            assert_eq!(entries(dir.path()), ["out.lz4"]);
+
+            // Chunks at offsets, then commit.
+            let h = cl_file_atomic_open(slot, path_ptr);
+            assert_eq!(cl_file_atomic_write(slot, h, b"world".as_ptr(), 6, 5), 5);
+            assert_eq!(cl_file_atomic_write(slot, h, b"hello ".as_ptr(), 0, 6), 6);
+            assert_eq!(fs::read(&dest).unwrap(), b"previous");
+            assert_eq!(cl_file_commit(slot, h), 0);
+            assert_eq!(fs::read(&dest).unwrap(), b"hello world");
+            assert_eq!(cl_file_abort(slot, h), -1, "handle retired");
+
+            // Explicit abort and writers left open at cleanup are discarded.
+            let h = cl_file_atomic_open(slot, path_ptr);
+            assert_eq!(cl_file_atomic_write(slot, h, b"x".as_ptr(), 0, 1), 1);
+            assert_eq!(cl_file_abort(slot, h), 0);
+            let h = cl_file_atomic_open(slot, path_ptr);
+            assert_eq!(cl_file_atomic_write(slot, h, b"x".as_ptr(), 0, 1), 1);
+            cl_file_atomic_cleanup(&mut slot);
+            assert!(slot.is_null());
+        }
+        assert_eq!(fs::read(&dest).unwrap(), b"hello world");
+        assert_eq!(entries(dir.path()), ["out.lz4"]);
+    }
+
+    #[test]
+    fn failed_rename_keeps_destination() {
+        // A non-empty directory cannot be replaced by a file.
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("taken");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("keep"), b"k").unwrap();
+        let mut mem = vec![0u8; 256];
+        let path = dest.to_str().unwrap().as_bytes();
+        mem[..path.len()].copy_from_slice(path);
+        mem[200..205].copy_from_slice(b"data\0");
+        unsafe {
+            assert_eq!(cl_file_write_atomic(mem.as_mut_ptr(), 0, 200, 0), -1);
+            let word = status::cl_last_status() as u64;
+            assert_ne!(base_types::status::status(word), base_types::status::OK);
+        }
+        assert!(dest.is_dir());
+        assert_eq!(entries(dir.path()), ["taken"]);
+
+        let file = dir.path().join("new.txt");
+        let path = file.to_str().unwrap().as_bytes();
+        mem[..path.len()].copy_from_slice(path);
+        mem[path.len()] = 0;
+        unsafe {
+            assert_eq!(cl_file_write_atomic(mem.as_mut_ptr(), 0, 200, 0), 4);
+        }
+        assert_eq!(fs::read(&file).unwrap(), b"data");
+        assert_eq!(entries(dir.path()), ["new.txt", "taken"]);
+    }
+}
@@ -0,0 +1,314 @@
+//! Files held open across calls. `cl_file_read` and `cl_file_write` open and
+//! close the file each time; a record-appending loop instead opens it once
+//! with `cl_file_open` and writes by handle, so appends go through one
+//! `O_APPEND` descriptor. Handles belong to the context that opened them:
+//! another context rejects them instead of reaching one of its own files.
+
+use std::fs;
+use std::io::{self, Read as IoRead, Seek, Write as IoWrite};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::file::io_failed;
+use super::handles::HandleTable;
+use super::{clear_ctx_slot, io_log, read_ctx_mut, read_path_ptr, sandbox, status, write_ctx_slot};
+use base_types::status::{INVALID_ARGUMENT, NOT_FOUND};
+
+/// `cl_file_open` mode flags.
+pub(crate) const OPEN_READ: i64 = 1;
+pub(crate) const OPEN_WRITE: i64 = 2;
+/// Every write goes to the end of the file, whatever the offset.
+pub(crate) const OPEN_APPEND: i64 = 4;
+pub(crate) const OPEN_CREATE: i64 = 8;
+pub(crate) const OPEN_TRUNCATE: i64 = 16;
+
+struct OpenFile {
+    file: fs::File,
+    path: PathBuf,
+    writable: bool,
+}
+
+impl Drop for OpenFile {
+    fn drop(&mut self) {
+        if self.writable {
+            let _ = self.file.sync_all();
+        }
+    }
+}
+
+/// Open files by handle. A handle carries the context's id in its high 32
+/// bits. Dropping the context (`cl_file_handle_cleanup`) syncs and closes
+/// every file still open.
+pub(crate) struct CraneliftFileHandleContext {
+    id: u32,
+    files: HandleTable<OpenFile>,
+}
+
+impl CraneliftFileHandleContext {
+    /// The file `handle` names, or `None` with the status set:
+    /// `INVALID_ARGUMENT` for another context's handle, `NOT_FOUND` for a
+    /// closed or unknown one.
+    fn file(&mut self, handle: i64) -> Option<&mut OpenFile> {
+        if (handle >> 32) as u32 != self.id {
+            status::set(INVALID_ARGUMENT, 0);
+            return None;
+        }
+        let file = self.files.get_mut(handle as u32);
+        if file.is_none() {
+            status::set(NOT_FOUND, 0);
+        }
+        file
+    }
+}
+
+pub(crate) unsafe extern "C" fn cl_file_handle_init(
+    ctx_slot_ptr: *mut *mut CraneliftFileHandleContext,
+) {
+    static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+    let ctx = Box::new(CraneliftFileHandleContext {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        files: HandleTable::new(),
+    });
+    let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
+}
+
+pub(crate) unsafe extern "C" fn cl_file_handle_cleanup(
+    ctx_slot_ptr: *mut *mut CraneliftFileHandleContext,
+) {
+    let ctx_ptr = clear_ctx_slot::<CraneliftFileHandleContext>(ctx_slot_ptr);
+    if !ctx_ptr.is_null() {
+        drop(Box::from_raw(ctx_ptr));
+    }
+}
+
+/// Open the file at the NUL-terminated `path_ptr` with `flags` (`OPEN_READ`,
+/// `OPEN_WRITE`, `OPEN_APPEND`, `OPEN_CREATE`, `OPEN_TRUNCATE`). Returns a
+/// handle, or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_file_open(
+    ctx: *mut CraneliftFileHandleContext,
+    path_ptr: *const u8,
+    flags: i64,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_open") {
+        return -1;
+    }
+    let Some(ctx) = read_ctx_mut::<CraneliftFileHandleContext>(ctx) else {
+        return -1;
+    };
+    let known = OPEN_READ | OPEN_WRITE | OPEN_APPEND | OPEN_CREATE | OPEN_TRUNCATE;
+    if path_ptr.is_null()
+        || flags & !known != 0
+        || flags & (OPEN_READ | OPEN_WRITE | OPEN_APPEND) == 0
+    {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(path) = sandbox::resolve(read_path_ptr(path_ptr)) else {
+        return -1;
+    };
+    let writable = flags & (OPEN_WRITE | OPEN_APPEND) != 0;
+    let file = match fs::OpenOptions::new()
+        .read(flags & OPEN_READ != 0)
+        .write(flags & OPEN_WRITE != 0)
+        .append(flags & OPEN_APPEND != 0)
+        .create(flags & OPEN_CREATE != 0)
+        .truncate(flags & OPEN_TRUNCATE != 0)
+        .open(&path)
+    {
+        Ok(file) => file,
+        Err(e) => return io_failed("cl_file_open", &path, &e),
+    };
+    let open = OpenFile {
+        file,
+        path,
+        writable,
+    };
+    match ctx.files.insert(open) {
+        Some(handle) => {
+            status::ok(0);
+            (ctx.id as i64) << 32 | handle as i64
+        }
+        None => {
+            status::set(INVALID_ARGUMENT, 0);
+            -1
+        }
+    }
+}
+
+/// Sync (when writable) and close the file. Returns 0, or -1 with the status
+/// set; the handle is retired either way.
+pub(crate) unsafe extern "C" fn cl_file_close(
+    ctx: *mut CraneliftFileHandleContext,
+    handle: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftFileHandleContext>(ctx) else {
+        return -1;
+    };
+    if ctx.file(handle).is_none() {
+        return -1;
+    }
+    let open = ctx.files.remove(handle as u32).unwrap();
+    if open.writable {
+        if let Err(e) = open.file.sync_all() {
+            return io_failed("cl_file_close", &open.path, &e);
+        }
+    }
+    status::ok(0);
+    0
+}
+
+/// Read up to `size` bytes into `dst_ptr`, from `file_offset` or, when it is
+/// negative, from the current position. Returns the bytes read (short only
+/// at end of file), or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_file_read_handle(
+    ctx: *mut CraneliftFileHandleContext,
+    handle: i64,
+    dst_ptr: *mut u8,
+    file_offset: i64,
+    size: i64,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_read_handle") {
+        return -1;
+    }
+    let Some(ctx) = read_ctx_mut::<CraneliftFileHandleContext>(ctx) else {
+        return -1;
+    };
+    if dst_ptr.is_null() || size < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(open) = ctx.file(handle) else {
+        return -1;
+    };
+    if file_offset >= 0 {
+        if let Err(e) = open.file.seek(io::SeekFrom::Start(file_offset as u64)) {
+            return io_failed("cl_file_read_handle", &open.path, &e);
+        }
+    }
+    let dst = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
+    let mut total = 0;
+    while total < dst.len() {
+        match open.file.read(&mut dst[total..]) {
+            Ok(0) => break,
+            Ok(n) => total += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return io_failed("cl_file_read_handle", &open.path, &e),
+        }
+    }
+    status::ok(total as u64);
+    total as i64
+}
+
+/// Write `size` bytes from `src_ptr` at `file_offset` or, when it is
+/// negative, at the current position (always the end for `OPEN_APPEND`).
+/// Returns `size`, or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_file_write_handle(
+    ctx: *mut CraneliftFileHandleContext,
+    handle: i64,
+    src_ptr: *const u8,
+    file_offset: i64,
+    size: i64,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_write_handle") {
+        return -1;
+    }
+    let Some(ctx) = read_ctx_mut::<CraneliftFileHandleContext>(ctx) else {
+        return -1;
+    };
+    if src_ptr.is_null() || size < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let Some(open) = ctx.file(handle) else {
+        return -1;
+    };
+    if file_offset >= 0 {
+        if let Err(e) = open.file.seek(io::SeekFrom::Start(file_offset as u64)) {
+            return io_failed("cl_file_write_handle", &open.path, &e);
+        }
+    }
+    let data = std::slice::from_raw_parts(src_ptr, size as usize);
+    match open.file.write_all(data) {
+        Ok(()) => {
+            status::ok(size as u64);
+            size
+        }
+        Err(e) => io_failed("cl_file_write_handle", &open.path, &e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn append_by_handle_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("records.bin");
+        let path = CString::new(dest.to_str().unwrap()).unwrap();
+        let path_ptr = path.as_ptr() as *const u8;
+        let mut slot: *mut CraneliftFileHandleContext = std::ptr::null_mut();
+        unsafe {
+            cl_file_handle_init(&mut slot);
+            let h = cl_file_open(slot, path_ptr, OPEN_APPEND | OPEN_CREATE);
+            assert!(h >= 0);
+            for i in 0..100u32 {
+                let record = i.to_le_bytes();
+                // The offset is ignored in append mode.
+                assert_eq!(cl_file_write_handle(slot, h, record.as_ptr(), 0, 4), 4);
+            }
+            assert_eq!(cl_file_close(slot, h), 0);
+            assert_eq!(cl_file_close(slot, h), -1, "handle retired");
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), NOT_FOUND);
+
+            let h = cl_file_open(slot, path_ptr, OPEN_READ);
+            let mut buf = [0u8; 8];
+            assert_eq!(cl_file_read_handle(slot, h, buf.as_mut_ptr(), 396, 8), 4);
+            assert_eq!(&buf[..4], &99u32.to_le_bytes());
+            assert_eq!(cl_file_read_handle(slot, h, buf.as_mut_ptr(), 4, 4), 4);
+            assert_eq!(cl_file_read_handle(slot, h, buf.as_mut_ptr(), -1, 4), 4);
+            assert_eq!(
+                &buf[..4],
+                &2u32.to_le_bytes(),
+                "continues after the last read"
+            );
+            assert!(
+                cl_file_write_handle(slot, h, buf.as_ptr(), -1, 4) < 0,
+                "read-only"
+            );
+            cl_file_handle_cleanup(&mut slot);
+        }
+        let expected: Vec<u8> = (0..100u32).flat_map(|i| i.to_le_bytes()).collect();
+        assert_eq!(fs::read(&dest).unwrap(), expected);
+    }
+
+    #[test]
+    fn handles_are_tied_to_their_context() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().join("a").to_str().unwrap()).unwrap();
+        let path_ptr = path.as_ptr() as *const u8;
+        let (mut a, mut b): (*mut CraneliftFileHandleContext, _) =
+            (std::ptr::null_mut(), std::ptr::null_mut());
+        unsafe {
+            cl_file_handle_init(&mut a);
+            cl_file_handle_init(&mut b);
+            let h = cl_file_open(a, path_ptr, OPEN_WRITE | OPEN_CREATE);
+            let hb = cl_file_open(b, path_ptr, OPEN_READ);
+            assert_eq!(h as u32, hb as u32, "same slot in each table");
+            assert_eq!(cl_file_write_handle(b, h, b"x".as_ptr(), 0, 1), -1);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+            assert_eq!(cl_file_write_handle(a, h, b"x".as_ptr(), 0, 1), 1);
+            assert_eq!(cl_file_open(a, path_ptr, 0), -1, "no access mode");
+            assert_eq!(cl_file_open(a, path_ptr, OPEN_READ | 64), -1);
+            cl_file_handle_cleanup(&mut a);
+            cl_file_handle_cleanup(&mut b);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Read-ahead of a file into a ring in shared memory, so CLIF code can
+//! consume a large file while the next chunks are still being read.
+//!
+//! Ring header at `ring_off` (8-byte aligned, both u64): head, the number of
+//! bytes read so far, and tail, the number consumed. File byte `i` lands at
+//! `dst_off + i % capacity`. The reader only overwrites bytes below the
+//! tail, so consumers wait with `cl_thread_wait_until(head, want, WAIT_GE)`
+//! and release space with `cl_thread_wake(tail, new_tail)`. Once reading
+//! stops (end of file, an I/O error, cancellation, or `cl_file_stream_end`)
+//! the head gets `STREAM_EOF` or'ed in, which also wakes those waits.
+
+use std::fs;
+use std::io::{self, Read as IoRead};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use super::thread::{cl_thread_wait_until, cl_thread_wake, WAIT_GE};
+use super::{cancel, io_log, read_path, sandbox, status};
+use base_types::status::INVALID_ARGUMENT;
+
+/// Set in the head word once no more bytes will arrive.
+pub(crate) const STREAM_EOF: u64 = 1 << 63;
+
+/// A running reader, addressed by the handle `cl_file_stream_start` returns.
+struct FileStream {
+    stop: Arc<AtomicBool>,
+    tail: usize,
+    reader: JoinHandle<io::Result<u64>>,
+}
+
+/// Fill the ring until end of file, an error, or `stop`. Returns the bytes
+/// read.
+unsafe fn read_ahead(
+    mut file: fs::File,
+    head: usize,
+    tail: usize,
+    dst: usize,
+    chunk: u64,
+    capacity: u64,
+    stop: &AtomicBool,
+) -> io::Result<u64> {
+    let mut read = 0u64;
+    while !stop.load(Ordering::Acquire) {
+        // Wait until a whole chunk fits behind the consumer.
+        if read + chunk > capacity
+            && cl_thread_wait_until(tail as *const u8, (read + chunk - capacity) as i64, WAIT_GE)
+                != 0
+        {
+            break;
+        }
+        if stop.load(Ordering::Acquire) {
+            break;
+        }
+        let pos = read % capacity;
+        let len = chunk.min(capacity - pos) as usize;
+        let buf = std::slice::from_raw_parts_mut((dst + pos as usize) as *mut u8, len);
+        match file.read(buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                read += n as u64;
+                cl_thread_wake(head as *mut u8, read as i64);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                cl_thread_wake(head as *mut u8, (read | STREAM_EOF) as i64);
+                return Err(e);
+            }
+        }
+    }
+    cl_thread_wake(head as *mut u8, (read | STREAM_EOF) as i64);
+    Ok(read)
+}
+
+/// Open the file named at `path_off` and start reading it into the ring at
+/// `ring_off` on a background thread. `config_off` holds two u64: the chunk
+/// size of each read and the ring capacity in bytes (at least the chunk
+/// size), whose data region starts at `dst_off`. Resets head and tail to 0.
+/// Returns a handle for `cl_file_stream_end`, which must be called before
+/// the memory goes away, or 0: with `INVALID_ARGUMENT` for bad arguments, or
+/// the I/O error if the file cannot be opened.
+pub(crate) unsafe extern "C" fn cl_file_stream_start(
+    ptr: *mut u8,
+    path_off: i64,
+    ring_off: i64,
+    dst_off: i64,
+    config_off: i64,
+) -> i64 {
+    if ptr.is_null() || path_off.min(ring_off).min(dst_off).min(config_off) < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return 0;
+    }
+    let ring = ptr.add(ring_off as usize);
+    let config = ptr.add(config_off as usize).cast::<u64>();
+    let chunk = config.read_unaligned();
+    let capacity = config.add(1).read_unaligned();
+    if !ring.cast::<u64>().is_aligned() || chunk == 0 || capacity < chunk {
+        status::set(INVALID_ARGUMENT, 0);
+        return 0;
+    }
+    status::begin();
+    if io_log::refuse_unlogged("cl_file_stream_start") {
+        return 0;
+    }
+    let Some(path) = sandbox::resolve(read_path(ptr, path_off as usize)) else {
+        return 0;
+    };
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            status::io(&e);
+            return 0;
+        }
+    };
+    let [head, tail] = [0, 8].map(|off| ring.add(off) as usize);
+    for word in [head, tail] {
+        (*(word as *const AtomicU64)).store(0, Ordering::Release);
+    }
+    let dst = ptr.add(dst_off as usize) as usize;
+    let stop = Arc::new(AtomicBool::new(false));
+    let token = cancel::current_token();
+    let reader = std::thread::spawn({
+        let stop = stop.clone();
+        move || {
+            cancel::set_token(token);
+            read_ahead(file, head, tail, dst, chunk, capacity, &stop)
+        }
+    });
+    status::ok(0);
+    Box::into_raw(Box::new(FileStream { stop, tail, reader })) as i64
+}
+
+/// Stop the reader started by `cl_file_stream_start` and release `handle`.
+/// Bytes not yet consumed stay in the ring. Returns the total bytes read
+/// into the ring, or -1 with the reader's I/O error as the status.
+pub(crate) unsafe extern "C" fn cl_file_stream_end(handle: i64) -> i64 {
+    if handle == 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let stream = Box::from_raw(handle as *mut FileStream);
+    stream.stop.store(true, Ordering::Release);
+    // Release a reader waiting for room, then put the tail back.
+    let tail = stream.tail as *mut u8;
+    let consumed = (*(tail as *const AtomicU64)).load(Ordering::Acquire);
+    cl_thread_wake(tail, i64::MAX);
+    let result = stream.reader.join();
+    cl_thread_wake(tail, consumed as i64);
+    match result {
+        Ok(Ok(read)) => {
+            status::ok(read);
+            read as i64
+        }
+        Ok(Err(e)) => {
+            status::io(&e);
+            -1
+        }
+        Err(_) => {
+            status::begin();
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const RING: usize = 64;
+    const CONFIG: usize = 80;
+    const DST: usize = 128;
+
+    fn memory(path: &std::path::Path, chunk: u64, capacity: u64) -> Vec<u8> {
+        let mut mem = vec![0u8; DST + capacity as usize];
+        let path = path.to_str().unwrap().as_bytes();
+        mem[..path.len()].copy_from_slice(path);
+        mem[CONFIG..CONFIG + 8].copy_from_slice(&chunk.to_le_bytes());
+        mem[CONFIG + 8..CONFIG + 16].copy_from_slice(&capacity.to_le_bytes());
+        mem
+    }
+
+    fn word(mem: &[u8], off: usize) -> &AtomicU64 {
+        unsafe { &*(mem.as_ptr().add(off) as *const AtomicU64) }
+    }
+
+    #[test]
+    fn consumer_sees_every_byte_through_a_small_ring() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        fs::File::create(&path).unwrap().write_all(&data).unwrap();
+        let mut mem = memory(&path, 1000, 4096);
+        let p = mem.as_mut_ptr();
+        let handle = unsafe { cl_file_stream_start(p, 0, RING as i64, DST as i64, CONFIG as i64) };
+        assert_ne!(handle, 0);
+
+        let mut seen = Vec::new();
+        loop {
+            let want = seen.len() as i64 + 1;
+            unsafe { cl_thread_wait_until(p.add(RING), want, WAIT_GE) };
+            let head = word(&mem, RING).load(Ordering::Acquire);
+            let available = head & !STREAM_EOF;
+            for i in seen.len() as u64..available {
+                seen.push(mem[DST + (i % 4096) as usize]);
+            }
+            unsafe { cl_thread_wake(p.add(RING + 8), available as i64) };
+            if head & STREAM_EOF != 0 {
+                break;
+            }
+        }
+        assert!(seen == data);
+        assert_eq!(unsafe { cl_file_stream_end(handle) }, data.len() as i64);
+    }
+
+    #[test]
+    fn end_stops_a_reader_blocked_on_a_full_ring() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.bin");
+        fs::write(&path, vec![1u8; 1 << 16]).unwrap();
+        let mut mem = memory(&path, 256, 1024);
+        let p = mem.as_mut_ptr();
+        unsafe {
+            let handle = cl_file_stream_start(p, 0, RING as i64, DST as i64, CONFIG as i64);
+            cl_thread_wait_until(p.add(RING), 1024, WAIT_GE);
+            assert_eq!(cl_file_stream_end(handle), 1024);
+        }
+        assert_eq!(word(&mem, RING).load(Ordering::Acquire), 1024 | STREAM_EOF);
+        assert_eq!(word(&mem, RING + 8).load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn rejects_bad_config_and_missing_file() {
+        use base_types::status as st;
+        let dir = tempfile::tempdir().unwrap();
+        let mut mem = memory(&dir.path().join("missing"), 512, 256);
+        let p = mem.as_mut_ptr();
+        unsafe {
+            assert_eq!(cl_file_stream_start(p, 0, 64, 128, 80), 0, "ring < chunk");
+            let word = status::cl_last_status() as u64;
+            assert_eq!(st::status(word), st::INVALID_ARGUMENT);
+            assert_eq!(cl_file_stream_start(p, 0, 60, 128, 80), 0, "unaligned ring");
+            mem[CONFIG + 8..CONFIG + 16].copy_from_slice(&1024u64.to_le_bytes());
+            assert_eq!(cl_file_stream_start(p, 0, 64, 128, 80), 0);
+            assert_eq!(st::status(status::cl_last_status() as u64), 2, "ENOENT");
+        }
+    }
+}
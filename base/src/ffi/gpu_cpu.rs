@@ -0,0 +1,147 @@
+//! CPU stand-in for the GPU context, used by `cl_gpu_init_fallback` when no
+//! adapter is available (or when asked to). Buffers are host memory, and a
+//! dispatch runs the compiled function attached to the pipeline with
+//! `cl_gpu_pipeline_cpu` instead of its shader; transfers and completion
+//! flags behave as on a device, so the rest of the algorithm is unchanged.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::thread::cl_thread_wake;
+use super::wgpu::{decode_elems, encode_elems, Region};
+
+struct Pipeline {
+    bindings: Vec<usize>,
+    fn_index: Option<usize>,
+}
+
+pub(crate) struct CpuGpuContext {
+    compiled_fns: Arc<Vec<unsafe extern "C" fn(*mut u8)>>,
+    buffers: Vec<Vec<u8>>,
+    pipelines: Vec<Pipeline>,
+    pub(super) regions: HashMap<usize, Vec<Region>>,
+}
+
+impl CpuGpuContext {
+    pub(crate) fn new(compiled_fns: Arc<Vec<unsafe extern "C" fn(*mut u8)>>) -> CpuGpuContext {
+        CpuGpuContext {
+            compiled_fns,
+            buffers: Vec::new(),
+            pipelines: Vec::new(),
+            regions: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn create_buffer(&mut self, size: usize) -> i32 {
+        self.buffers.push(vec![0; size]);
+        self.buffers.len() as i32 - 1
+    }
+
+    pub(crate) fn create_pipeline(&mut self, bindings: Vec<usize>) -> i32 {
+        if bindings.iter().any(|&b| b >= self.buffers.len()) {
+            return -1;
+        }
+        self.pipelines.push(Pipeline {
+            bindings,
+            fn_index: None,
+        });
+        self.pipelines.len() as i32 - 1
+    }
+
+    pub(crate) fn set_pipeline_fn(&mut self, pipeline: usize, fn_index: usize) -> i32 {
+        if fn_index >= self.compiled_fns.len() {
+            return -1;
+        }
+        match self.pipelines.get_mut(pipeline) {
+            Some(p) => {
+                p.fn_index = Some(fn_index);
+                0
+            }
+            None => -1,
+        }
+    }
+
+    fn range(&mut self, buf: usize, offset: usize, size: usize) -> Option<&mut [u8]> {
+        self.buffers
+            .get_mut(buf)?
+            .get_mut(offset..offset.checked_add(size)?)
+    }
+
+    pub(crate) fn upload(&mut self, buf: usize, data: &[u8]) -> i32 {
+        match self.range(buf, 0, data.len()) {
+            Some(dst) => {
+                dst.copy_from_slice(data);
+                0
+            }
+            None => -1,
+        }
+    }
+
+    pub(crate) fn download(&mut self, buf: usize, offset: usize, dst: &mut [u8]) -> i32 {
+        match self.range(buf, offset, dst.len()) {
+            Some(src) => {
+                dst.copy_from_slice(src);
+                0
+            }
+            None => -1,
+        }
+    }
+
+    pub(crate) fn upload_typed(&mut self, buf: usize, elem: i32, src: &[u8], size: usize) -> i32 {
+        let mut data = encode_elems(elem, src);
+        data.resize(size, 0);
+        self.upload(buf, &data)
+    }
+
+    pub(crate) fn download_typed(
+        &mut self,
+        buf: usize,
+        elem: i32,
+        size: usize,
+        dst: &mut [u8],
+    ) -> i32 {
+        match self.range(buf, 0, size) {
+            Some(src) => {
+                decode_elems(elem, src, dst);
+                0
+            }
+            None => -1,
+        }
+    }
+
+    /// Copy at once and set `flag` to 1, waking its waiters, as a device
+    /// readback would on the next poll.
+    pub(crate) unsafe fn download_async(
+        &mut self,
+        buf: usize,
+        dst: &mut [u8],
+        flag: *mut i64,
+    ) -> i32 {
+        if self.download(buf, 0, dst) != 0 {
+            return -1;
+        }
+        cl_thread_wake(flag.cast(), 1);
+        0
+    }
+
+    /// Run the pipeline's CPU function on an argument block holding the
+    /// workgroup counts and each binding's address and length. Fails for a
+    /// pipeline without one.
+    pub(crate) unsafe fn dispatch(&mut self, pipeline: usize, wg: [i32; 3]) -> i32 {
+        let Some(Pipeline {
+            bindings,
+            fn_index: Some(fn_index),
+        }) = self.pipelines.get(pipeline)
+        else {
+            return -1;
+        };
+        let mut args: Vec<i64> = wg.iter().map(|&n| n as i64).collect();
+        for &b in bindings {
+            let buffer = &mut self.buffers[b];
+            args.push(buffer.as_mut_ptr() as i64);
+            args.push(buffer.len() as i64);
+        }
+        (self.compiled_fns[*fn_index])(args.as_mut_ptr().cast());
+        0
+    }
+}
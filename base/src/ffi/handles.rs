@@ -0,0 +1,125 @@
+//! Generational handle tables for FFI units that hand out integer handles.
+//!
+//! A handle packs a slot index (low `SLOT_BITS`) with the slot's generation.
+//! Closing a handle frees the slot for reuse under the next generation, so a
+//! stale copy of the old handle misses instead of reaching the new occupant.
+//! Generations stay below 2^15, which keeps handles non-negative as i32; a
+//! slot must be reused 32768 times before an old handle can match again.
+
+const SLOT_BITS: u32 = 16;
+const SLOT_MASK: u32 = (1 << SLOT_BITS) - 1;
+const GENERATIONS: u32 = 1 << 15;
+
+pub(crate) struct HandleTable<T> {
+    slots: Vec<(u32, Option<T>)>,
+    free: Vec<u32>,
+    live: usize,
+}
+
+impl<T> HandleTable<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            live: 0,
+        }
+    }
+
+    /// Store `value` and return its handle, or `None` once all 65536 slots
+    /// are live. Fresh slots are numbered from 0, so until something is
+    /// closed the handles run 0, 1, 2, ...
+    pub(crate) fn insert(&mut self, value: T) -> Option<u32> {
+        let slot = match self.free.pop() {
+            Some(slot) => slot,
+            None if self.slots.len() <= SLOT_MASK as usize => {
+                self.slots.push((0, None));
+                self.slots.len() as u32 - 1
+            }
+            None => return None,
+        };
+        let (generation, entry) = &mut self.slots[slot as usize];
+        *entry = Some(value);
+        self.live += 1;
+        Some(*generation << SLOT_BITS | slot)
+    }
+
+    #[cfg_attr(not(feature = "lmdb"), allow(dead_code))]
+    pub(crate) fn get(&self, handle: u32) -> Option<&T> {
+        let (generation, entry) = self.slots.get((handle & SLOT_MASK) as usize)?;
+        entry
+            .as_ref()
+            .filter(|_| handle >> SLOT_BITS == *generation)
+    }
+
+    pub(crate) fn get_mut(&mut self, handle: u32) -> Option<&mut T> {
+        let (generation, entry) = self.slots.get_mut((handle & SLOT_MASK) as usize)?;
+        entry
+            .as_mut()
+            .filter(|_| handle >> SLOT_BITS == *generation)
+    }
+
+    /// The live value in `slot`, whatever its generation.
+    pub(crate) fn in_slot(&self, slot: u32) -> Option<&T> {
+        self.slots.get(slot as usize)?.1.as_ref()
+    }
+
+    pub(crate) fn in_slot_mut(&mut self, slot: u32) -> Option<&mut T> {
+        self.slots.get_mut(slot as usize)?.1.as_mut()
+    }
+
+    /// Take the value out of `handle` and retire the handle. `None` for an
+    /// unknown, closed or stale handle.
+    pub(crate) fn remove(&mut self, handle: u32) -> Option<T> {
+        let slot = handle & SLOT_MASK;
+        let (generation, entry) = self.slots.get_mut(slot as usize)?;
+        if handle >> SLOT_BITS != *generation {
+            return None;
+        }
+        let value = entry.take()?;
+        *generation = (*generation + 1) % GENERATIONS;
+        self.free.push(slot);
+        self.live -= 1;
+        Some(value)
+    }
+
+    /// Number of live handles.
+    pub(crate) fn len(&self) -> usize {
+        self.live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reused_slot_rejects_the_stale_handle() {
+        let mut table = HandleTable::new();
+        let a = table.insert("a").unwrap();
+        let b = table.insert("b").unwrap();
+        assert_eq!((a, b), (0, 1));
+        assert_eq!(table.remove(a), Some("a"));
+        assert_eq!(table.remove(a), None, "double close");
+        let c = table.insert("c").unwrap();
+        assert_eq!(c & SLOT_MASK, 0, "slot 0 is reused");
+        assert_ne!(c, a);
+        assert_eq!(table.get(a), None);
+        assert_eq!(table.get(c), Some(&"c"));
+        assert_eq!(table.in_slot(0), Some(&"c"));
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn create_close_cycles_keep_one_slot() {
+        let mut table = HandleTable::new();
+        let mut seen = std::collections::HashSet::new();
+        for i in 0..1000 {
+            let h = table.insert(i).unwrap();
+            assert!(seen.insert(h), "handle {h:#x} handed out twice");
+            assert!((h as i32) >= 0);
+            assert_eq!(table.remove(h), Some(i));
+        }
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.slots.len(), 1);
+    }
+}
@@ -1,17 +1,20 @@
 use std::collections::HashMap;
+use std::hash::BuildHasher;
 
-use super::{clear_ctx_slot, read_ctx_mut, read_ctx_ref, write_ctx_slot};
+use super::handles::HandleTable;
+use super::{clear_ctx_slot, read_ctx_mut, read_ctx_ref, status, write_ctx_slot};
+use base_types::status::{NOT_FOUND, TOO_LARGE};
 
+/// Tables by handle. The accessors take no handle: they work on the table in
+/// slot 0, the first one created, or whichever replaced it after a close.
 pub(crate) struct CraneliftHashTableContext {
-    tables: HashMap<u32, HashMap<Vec<u8>, Vec<u8>>>,
-    next_handle: u32,
+    tables: HandleTable<HashMap<Vec<u8>, Vec<u8>>>,
 }
 
 impl CraneliftHashTableContext {
     fn new() -> Self {
         Self {
-            tables: HashMap::new(),
-            next_handle: 0,
+            tables: HandleTable::new(),
         }
     }
 }
@@ -32,10 +35,54 @@ pub(crate) unsafe extern "C" fn cl_ht_create(ctx: *mut CraneliftHashTableContext
     let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
         return u32::MAX;
     };
-    let handle = ctx.next_handle;
-    ctx.next_handle += 1;
-    ctx.tables.insert(handle, HashMap::new());
-    handle
+    ctx.tables.insert(HashMap::new()).unwrap_or(u32::MAX)
+}
+
+/// Like `ht_create`, but the table starts with room for at least `capacity`
+/// entries (0 for the default), so bulk loads do not rehash as they grow.
+/// Returns `u32::MAX` (status `TOO_LARGE`) when that much cannot be
+/// allocated.
+pub(crate) unsafe extern "C" fn cl_ht_create_with_capacity(
+    ctx: *mut CraneliftHashTableContext,
+    capacity: u64,
+) -> u32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
+        return u32::MAX;
+    };
+    let mut table = HashMap::new();
+    if table.try_reserve(capacity as usize).is_err() {
+        status::set(TOO_LARGE, capacity);
+        return u32::MAX;
+    }
+    status::ok(table.capacity() as u64);
+    ctx.tables.insert(table).unwrap_or(u32::MAX)
+}
+
+/// Drop the table behind `handle`. Returns 0, or -1 (status `NOT_FOUND`)
+/// for an unknown, closed or stale handle.
+pub(crate) unsafe extern "C" fn cl_ht_close(
+    ctx: *mut CraneliftHashTableContext,
+    handle: u32,
+) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
+        return -1;
+    };
+    if ctx.tables.remove(handle).is_none() {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    }
+    status::ok(0);
+    0
+}
+
+/// Number of live tables, for leak checks.
+pub(crate) unsafe extern "C" fn cl_ht_handle_count(ctx: *const CraneliftHashTableContext) -> u32 {
+    let Some(ctx) = read_ctx_ref::<CraneliftHashTableContext>(ctx) else {
+        return 0;
+    };
+    ctx.tables.len() as u32
 }
 
 pub(crate) unsafe extern "C" fn cl_ht_lookup(
@@ -44,13 +91,16 @@ pub(crate) unsafe extern "C" fn cl_ht_lookup(
     key_len: u32,
     result: *mut u8,
 ) -> u32 {
+    status::begin();
     let Some(ctx) = read_ctx_ref::<CraneliftHashTableContext>(ctx) else {
         return 0xFFFF_FFFF;
     };
+    status::set(NOT_FOUND, 0);
     let key = std::slice::from_raw_parts(key, key_len as usize);
-    if let Some(table) = ctx.tables.get(&0) {
+    if let Some(table) = ctx.tables.in_slot(0) {
         if let Some(val) = table.get(key) {
             std::ptr::copy_nonoverlapping(val.as_ptr(), result, val.len());
+            status::ok(val.len() as u64);
             return val.len() as u32;
         }
     }
@@ -64,12 +114,13 @@ pub(crate) unsafe extern "C" fn cl_ht_insert(
     val: *const u8,
     val_len: u32,
 ) {
+    status::begin();
     let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
         return;
     };
     let key_slice = std::slice::from_raw_parts(key, key_len as usize);
     let val_slice = std::slice::from_raw_parts(val, val_len as usize);
-    if let Some(table) = ctx.tables.get_mut(&0) {
+    if let Some(table) = ctx.tables.in_slot_mut(0) {
         if let Some(existing) = table.get_mut(key_slice) {
             if existing.len() == val_len as usize {
                 existing.copy_from_slice(val_slice);
@@ -79,6 +130,7 @@ pub(crate) unsafe extern "C" fn cl_ht_insert(
         } else {
             table.insert(key_slice.to_vec(), val_slice.to_vec());
         }
+        status::ok(val_len as u64);
     }
 }
 
@@ -86,7 +138,7 @@ pub(crate) unsafe extern "C" fn cl_ht_count(ctx: *const CraneliftHashTableContex
     let Some(ctx) = read_ctx_ref::<CraneliftHashTableContext>(ctx) else {
         return 0;
     };
-    ctx.tables.get(&0).map(|t| t.len() as u32).unwrap_or(0)
+    ctx.tables.in_slot(0).map(|t| t.len() as u32).unwrap_or(0)
 }
 
 pub(crate) unsafe extern "C" fn cl_ht_get_entry(
@@ -95,13 +147,16 @@ pub(crate) unsafe extern "C" fn cl_ht_get_entry(
     key_out: *mut u8,
     val_out: *mut u8,
 ) -> i32 {
+    status::begin();
     let Some(ctx) = read_ctx_ref::<CraneliftHashTableContext>(ctx) else {
         return -1;
     };
-    if let Some(table) = ctx.tables.get(&0) {
+    status::set(NOT_FOUND, 0);
+    if let Some(table) = ctx.tables.in_slot(0) {
         if let Some((key, val)) = table.iter().nth(index as usize) {
             std::ptr::copy_nonoverlapping(key.as_ptr(), key_out, key.len());
             std::ptr::copy_nonoverlapping(val.as_ptr(), val_out, val.len());
+            status::ok(val.len() as u64);
             return key.len() as i32;
         }
     }
@@ -118,7 +173,7 @@ pub(crate) unsafe extern "C" fn cl_ht_increment(
         return addend;
     };
     let key_slice = std::slice::from_raw_parts(key, key_len as usize);
-    if let Some(table) = ctx.tables.get_mut(&0) {
+    if let Some(table) = ctx.tables.in_slot_mut(0) {
         if let Some(existing) = table.get_mut(key_slice) {
             let current = i64::from_le_bytes(existing[..8].try_into().unwrap_or([0; 8]));
             let new_val = current + addend;
@@ -130,6 +185,84 @@ pub(crate) unsafe extern "C" fn cl_ht_increment(
     addend
 }
 
+/// Remove `key` from the table. Returns 0, or -1 (status `NOT_FOUND`) when
+/// it is absent.
+pub(crate) unsafe extern "C" fn cl_ht_remove(
+    ctx: *mut CraneliftHashTableContext,
+    key: *const u8,
+    key_len: u32,
+) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
+        return -1;
+    };
+    let key = std::slice::from_raw_parts(key, key_len as usize);
+    match ctx.tables.in_slot_mut(0).and_then(|t| t.remove(key)) {
+        Some(val) => {
+            status::ok(val.len() as u64);
+            0
+        }
+        None => {
+            status::set(NOT_FOUND, 0);
+            -1
+        }
+    }
+}
+
+/// Remove every entry from the table, keeping its capacity and handle.
+/// Returns 0, or -1 (status `NOT_FOUND`) when there is no table.
+pub(crate) unsafe extern "C" fn cl_ht_clear(ctx: *mut CraneliftHashTableContext) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftHashTableContext>(ctx) else {
+        return -1;
+    };
+    let Some(table) = ctx.tables.in_slot_mut(0) else {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    };
+    table.clear();
+    status::ok(0);
+    0
+}
+
+/// Bytes `cl_ht_stats` writes: u64 entries, u64 capacity, u64 key and value
+/// bytes, u32 longest chain.
+pub(crate) const HT_STATS_SIZE: usize = 28;
+
+/// Write the table's statistics to `out` (`HT_STATS_SIZE` bytes): entry
+/// count, capacity, total bytes of keys and values, and the most keys that
+/// share one home bucket, a measure of probe lengths. Returns 0, or -1
+/// (status `NOT_FOUND`) when there is no table.
+pub(crate) unsafe extern "C" fn cl_ht_stats(
+    ctx: *const CraneliftHashTableContext,
+    out: *mut u8,
+) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftHashTableContext>(ctx) else {
+        return -1;
+    };
+    let Some(table) = ctx.tables.in_slot(0) else {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    };
+    let bytes: usize = table.iter().map(|(k, v)| k.len() + v.len()).sum();
+    // Buckets are a power of two, at most 7/8 full.
+    let buckets = (table.capacity() * 8 / 7).next_power_of_two();
+    let mut chains = vec![0u32; if table.is_empty() { 0 } else { buckets }];
+    for key in table.keys() {
+        chains[table.hasher().hash_one(key) as usize & (buckets - 1)] += 1;
+    }
+    let longest = chains.into_iter().max().unwrap_or(0);
+    let mut stats = [0u8; HT_STATS_SIZE];
+    stats[0..8].copy_from_slice(&(table.len() as u64).to_le_bytes());
+    stats[8..16].copy_from_slice(&(table.capacity() as u64).to_le_bytes());
+    stats[16..24].copy_from_slice(&(bytes as u64).to_le_bytes());
+    stats[24..28].copy_from_slice(&longest.to_le_bytes());
+    std::ptr::copy_nonoverlapping(stats.as_ptr(), out, HT_STATS_SIZE);
+    status::ok(0);
+    0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -277,6 +410,48 @@ mod tests {
         }
     }
 
+    unsafe fn stats(ctx: *mut CraneliftHashTableContext) -> (u64, u64, u64, u32) {
+        let mut out = [0u8; HT_STATS_SIZE];
+        assert_eq!(cl_ht_stats(ctx, out.as_mut_ptr()), 0);
+        let u64_at = |i: usize| u64::from_le_bytes(out[i..i + 8].try_into().unwrap());
+        let chain = u32::from_le_bytes(out[24..28].try_into().unwrap());
+        (u64_at(0), u64_at(8), u64_at(16), chain)
+    }
+
+    #[test]
+    fn capacity_hint_stats_remove_and_clear() {
+        unsafe {
+            let ctx = init();
+            let mut out = [0u8; HT_STATS_SIZE];
+            assert_eq!(cl_ht_stats(ctx, out.as_mut_ptr()), -1);
+            assert_eq!(cl_ht_create_with_capacity(ctx, 10_000), 0);
+            let (len, capacity, bytes, chain) = stats(ctx);
+            assert_eq!((len, bytes, chain), (0, 0, 0));
+            assert!(capacity >= 10_000, "capacity {capacity}");
+
+            for i in 0..1000u32 {
+                insert(ctx, &i.to_le_bytes(), &[7; 12]);
+            }
+            for i in 0..300u32 {
+                assert_eq!(cl_ht_remove(ctx, i.to_le_bytes().as_ptr(), 4), 0);
+            }
+            assert_eq!(cl_ht_remove(ctx, 0u32.to_le_bytes().as_ptr(), 4), -1);
+            let (len, after, bytes, chain) = stats(ctx);
+            assert_eq!((len, after, bytes), (700, capacity, 700 * 16));
+            assert!((1..700).contains(&chain), "chain {chain}");
+
+            assert_eq!(cl_ht_clear(ctx), 0);
+            assert_eq!(stats(ctx), (0, capacity, 0, 0));
+            assert_eq!(lookup(ctx, &500u32.to_le_bytes()), None);
+            insert(ctx, b"k", b"v");
+            assert_eq!(lookup(ctx, b"k").as_deref(), Some(&b"v"[..]));
+            assert_eq!(cl_ht_create_with_capacity(ctx, u64::MAX), u32::MAX);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), TOO_LARGE);
+            cleanup(ctx);
+        }
+    }
+
     #[test]
     fn get_entry_iterates_all_pairs() {
         unsafe {
@@ -367,6 +542,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn close_then_create_reuses_the_slot_and_rejects_stale_handles() {
+        use base_types::status as st;
+        unsafe {
+            let ctx = init();
+            let first = cl_ht_create(ctx);
+            insert(ctx, b"k", b"old");
+            let second = cl_ht_create(ctx);
+            assert_eq!(cl_ht_handle_count(ctx), 2);
+            assert_eq!(cl_ht_close(ctx, first), 0);
+            assert_eq!(cl_ht_close(ctx, first), -1, "double close");
+            assert_eq!(st::status(status::cl_last_status() as u64), st::NOT_FOUND);
+            assert!(lookup(ctx, b"k").is_none(), "closed table is gone");
+
+            for _ in 0..100 {
+                let h = cl_ht_create(ctx);
+                assert_ne!(h, first);
+                assert_eq!(cl_ht_handle_count(ctx), 2);
+                assert_eq!(cl_ht_close(ctx, h), 0);
+            }
+            let third = cl_ht_create(ctx);
+            assert_eq!(cl_ht_close(ctx, first), -1, "stale handle");
+            assert_eq!(cl_ht_handle_count(ctx), 2);
+            insert(ctx, b"k", b"new");
+            assert_eq!(lookup(ctx, b"k").as_deref(), Some(&b"new"[..]));
+            assert_eq!(cl_ht_close(ctx, second), 0);
+            assert_eq!(cl_ht_close(ctx, third), 0);
+            assert_eq!(cl_ht_handle_count(ctx), 0);
+            cleanup(ctx);
+        }
+    }
+
     #[test]
     fn null_ctx_returns_sentinels() {
         let null_ctx = std::ptr::null_mut::<CraneliftHashTableContext>();
@@ -0,0 +1,486 @@
+//! Minimal HTTP/1.1 client over `std::net::TcpStream`, so CLIF code can fetch
+//! a URL into memory without hand-rolling the protocol over `cl_net_*`.
+//! Plain `http://` only; one request per connection (`Connection: close`).
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use super::cancel::{Cancellable, POLL_INTERVAL};
+use super::{io_log, read_cstr_ptr, status};
+use base_types::status::INVALID_ARGUMENT;
+
+/// Bytes of the response header written before the header and body bytes.
+pub(crate) const HTTP_RESPONSE_HEADER: usize = 24;
+
+/// Longest status, header, chunk-size or trailer line accepted.
+const MAX_LINE: u64 = 64 * 1024;
+
+struct Response {
+    code: u32,
+    headers: Capped,
+    body: Capped,
+}
+
+/// The first `limit` bytes written to it, and how many were written in all;
+/// the server picks the response size, so nothing past the caller's buffer
+/// is kept.
+struct Capped {
+    bytes: Vec<u8>,
+    limit: usize,
+    len: u64,
+}
+
+impl Capped {
+    fn new(limit: usize) -> Self {
+        Capped {
+            bytes: Vec::new(),
+            limit,
+            len: 0,
+        }
+    }
+
+    fn truncated(&self) -> bool {
+        (self.bytes.len() as u64) < self.len
+    }
+}
+
+impl Write for Capped {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        let keep = data.len().min(self.limit - self.bytes.len());
+        self.bytes
+            .try_reserve(keep)
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::OutOfMemory))?;
+        self.bytes.extend_from_slice(&data[..keep]);
+        self.len += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Split `http://host[:port][/path]` into a connect address, Host header
+/// value and request target.
+fn parse_url(url: &str) -> Option<(String, String, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return None;
+    }
+    let path = if path.starts_with('?') {
+        format!("/{path}")
+    } else {
+        path.to_string()
+    };
+    let addr = if authority
+        .rsplit_once(':')
+        .is_some_and(|(_, p)| !p.contains(']'))
+    {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    Some((addr, authority.to_string(), path))
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+fn read_line(reader: &mut impl BufRead) -> std::io::Result<String> {
+    let mut line = String::new();
+    let n = reader.take(MAX_LINE).read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(invalid_data(if n as u64 == MAX_LINE {
+            "line too long"
+        } else {
+            "connection closed mid-response"
+        }));
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Copy exactly `len` bytes from `reader` into `body`.
+fn copy_exact(reader: &mut impl BufRead, len: u64, body: &mut Capped) -> std::io::Result<()> {
+    if std::io::copy(&mut reader.take(len), body)? < len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+fn read_chunked(reader: &mut impl BufRead, body: &mut Capped) -> std::io::Result<()> {
+    loop {
+        let line = read_line(reader)?;
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| invalid_data("bad chunk size"))?;
+        if size == 0 {
+            // Trailers, up to the blank line.
+            while !read_line(reader)?.is_empty() {}
+            return Ok(());
+        }
+        copy_exact(reader, size, body)?;
+        read_line(reader)?;
+    }
+}
+
+/// Send the request and read the response, keeping at most `limit` bytes of
+/// headers and body together. Reads give up once the execution is cancelled.
+fn request(
+    method: &str,
+    url: &str,
+    headers: &str,
+    body: &[u8],
+    limit: usize,
+) -> std::io::Result<Response> {
+    let (addr, host, path) = parse_url(url)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "bad URL"))?;
+    let mut stream = TcpStream::connect(addr)?;
+
+    let mut head = format!("{method} {path} HTTP/1.1\r\nHost: {host}\r\n");
+    for line in headers.lines().filter(|l| !l.trim().is_empty()) {
+        head.push_str(line.trim_end_matches('\r'));
+        head.push_str("\r\n");
+    }
+    if !body.is_empty() || matches!(method, "POST" | "PUT" | "PATCH") {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    let mut reader = BufReader::new(Cancellable(stream));
+    let status_line = read_line(&mut reader)?;
+    let code = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|c| c.parse::<u32>().ok())
+        .filter(|_| status_line.starts_with("HTTP/1."))
+        .ok_or_else(|| invalid_data("bad status line"))?;
+
+    let mut headers = Capped::new(limit);
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<u64>().ok();
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            }
+        }
+        headers.write_all(line.as_bytes())?;
+        headers.write_all(b"\r\n")?;
+    }
+
+    let mut body = Capped::new(limit - headers.bytes.len());
+    if method == "HEAD" || code / 100 == 1 || code == 204 || code == 304 {
+        // No body follows, whatever the headers say.
+    } else if chunked {
+        read_chunked(&mut reader, &mut body)?;
+    } else if let Some(len) = content_length {
+        copy_exact(&mut reader, len, &mut body)?;
+    } else {
+        std::io::copy(&mut reader, &mut body)?;
+    }
+    Ok(Response {
+        code,
+        headers,
+        body,
+    })
+}
+
+/// Perform the HTTP request described at `req_ptr` and write the response to
+/// `resp_ptr`, using at most `capacity` bytes.
+///
+/// The request is five i64 words: method (C string), URL (C string), extra
+/// header lines (C string of `Name: value` lines, or 0), body pointer (or 0)
+/// and body length. `Host`, `Content-Length` and `Connection: close` are
+/// added. Chunked and `Content-Length` responses are decoded.
+///
+/// The response is u32 status code, u32 truncated flag, u64 header bytes
+/// stored, u64 body bytes stored, then the header lines (CRLF-terminated,
+/// without the status line) followed by the body. When both do not fit, the
+/// headers are kept first and the flag is set to 1; the status word payload
+/// is always the full body length. Bytes past `capacity` are read and
+/// dropped, and a read waiting on the server gives up once the execution
+/// is cancelled.
+///
+/// Returns the HTTP status code, or -1 (status `INVALID_ARGUMENT` for a bad
+/// descriptor, URL or capacity, otherwise an I/O error or `FAILED`).
+pub(crate) unsafe extern "C" fn cl_http_request(
+    req_ptr: *const u8,
+    resp_ptr: *mut u8,
+    capacity: i64,
+) -> i64 {
+    status::begin();
+    if io_log::refuse_unlogged("cl_http_request") {
+        return -1;
+    }
+    if req_ptr.is_null() || resp_ptr.is_null() || capacity < HTTP_RESPONSE_HEADER as i64 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let word = |i: usize| std::ptr::read_unaligned((req_ptr as *const u64).add(i));
+    let (method_ptr, url_ptr, headers_ptr, body_ptr, body_len) =
+        (word(0), word(1), word(2), word(3), word(4));
+    if method_ptr == 0 || url_ptr == 0 || (body_ptr == 0 && body_len != 0) {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let method = read_cstr_ptr(method_ptr as *const u8);
+    let url = read_cstr_ptr(url_ptr as *const u8);
+    let headers = if headers_ptr == 0 {
+        String::new()
+    } else {
+        read_cstr_ptr(headers_ptr as *const u8)
+    };
+    let body: &[u8] = if body_ptr == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(body_ptr as *const u8, body_len as usize)
+    };
+
+    let room = capacity as usize - HTTP_RESPONSE_HEADER;
+    let response = match request(&method, &url, &headers, body, room) {
+        Ok(r) => r,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {
+            status::set(INVALID_ARGUMENT, 0);
+            return -1;
+        }
+        // Malformed responses keep the `FAILED` word from `begin`.
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => return -1,
+        Err(e) => {
+            status::io(&e);
+            return -1;
+        }
+    };
+
+    let (headers, body) = (&response.headers.bytes, &response.body.bytes);
+    let (header_len, body_len) = (headers.len(), body.len());
+    let truncated = response.headers.truncated() || response.body.truncated();
+    std::ptr::write_unaligned(resp_ptr as *mut u32, response.code);
+    std::ptr::write_unaligned(resp_ptr.add(4) as *mut u32, truncated as u32);
+    std::ptr::write_unaligned(resp_ptr.add(8) as *mut u64, header_len as u64);
+    std::ptr::write_unaligned(resp_ptr.add(16) as *mut u64, body_len as u64);
+    let data = resp_ptr.add(HTTP_RESPONSE_HEADER);
+    std::ptr::copy_nonoverlapping(headers.as_ptr(), data, header_len);
+    std::ptr::copy_nonoverlapping(body.as_ptr(), data.add(header_len), body_len);
+    status::ok(response.body.len);
+    response.code as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base_types::status::IO_ERROR;
+    use std::ffi::CString;
+    use std::net::TcpListener;
+
+    /// Serve one connection with `reply`, returning the raw request.
+    fn serve_once(reply: &'static [u8]) -> (u16, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(v) = line.strip_prefix("Content-Length: ") {
+                    content_length = v.trim().parse().unwrap();
+                }
+                request.push_str(&line);
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body).unwrap();
+            request.push_str(&String::from_utf8(body).unwrap());
+            reader.get_mut().write_all(reply).unwrap();
+            request
+        });
+        (port, server)
+    }
+
+    fn call(
+        method: &str,
+        url: &str,
+        headers: Option<&str>,
+        body: &[u8],
+        cap: usize,
+    ) -> (i64, Vec<u8>) {
+        let method = CString::new(method).unwrap();
+        let url = CString::new(url).unwrap();
+        let headers = headers.map(|h| CString::new(h).unwrap());
+        let desc: [u64; 5] = [
+            method.as_ptr() as u64,
+            url.as_ptr() as u64,
+            headers.as_ref().map_or(0, |h| h.as_ptr() as u64),
+            if body.is_empty() {
+                0
+            } else {
+                body.as_ptr() as u64
+            },
+            body.len() as u64,
+        ];
+        let mut resp = vec![0u8; cap];
+        let code =
+            unsafe { cl_http_request(desc.as_ptr() as *const u8, resp.as_mut_ptr(), cap as i64) };
+        (code, resp)
+    }
+
+    fn field(resp: &[u8], off: usize) -> u64 {
+        u64::from_le_bytes(resp[off..off + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn get_with_content_length() {
+        let (port, server) =
+            serve_once(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: yes\r\n\r\nhello");
+        let (code, resp) = call(
+            "GET",
+            &format!("http://127.0.0.1:{port}/greet?x=1"),
+            None,
+            &[],
+            256,
+        );
+        assert_eq!(code, 200);
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /greet?x=1 HTTP/1.1\r\n"));
+        assert!(request.contains(&format!("Host: 127.0.0.1:{port}\r\n")));
+
+        assert_eq!(u32::from_le_bytes(resp[0..4].try_into().unwrap()), 200);
+        assert_eq!(u32::from_le_bytes(resp[4..8].try_into().unwrap()), 0);
+        let (hlen, blen) = (field(&resp, 8) as usize, field(&resp, 16) as usize);
+        let headers = std::str::from_utf8(&resp[24..24 + hlen]).unwrap();
+        assert_eq!(headers, "Content-Length: 5\r\nX-Test: yes\r\n");
+        assert_eq!(&resp[24 + hlen..24 + hlen + blen], b"hello");
+    }
+
+    #[test]
+    fn post_body_and_chunked_response() {
+        let (port, server) = serve_once(
+            b"HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n\
+              4\r\nchun\r\n6;ext=1\r\nked ok\r\n0\r\nX-Trailer: 1\r\n\r\n",
+        );
+        let (code, resp) = call(
+            "POST",
+            &format!("http://127.0.0.1:{port}/items"),
+            Some("Content-Type: text/plain\r\nX-Id: 7\r\n"),
+            b"payload",
+            256,
+        );
+        assert_eq!(code, 201);
+        let request = server.join().unwrap();
+        assert!(request.contains("Content-Type: text/plain\r\nX-Id: 7\r\n"));
+        assert!(request.contains("Content-Length: 7\r\n"));
+        assert!(request.ends_with("\r\n\r\npayload"));
+
+        let (hlen, blen) = (field(&resp, 8) as usize, field(&resp, 16) as usize);
+        assert_eq!(&resp[24 + hlen..24 + hlen + blen], b"chunked ok");
+    }
+
+    #[test]
+    fn truncates_to_capacity_and_reports_full_length() {
+        let (port, server) = serve_once(b"HTTP/1.1 200 OK\r\n\r\n0123456789abcdef");
+        let (code, resp) = call(
+            "GET",
+            &format!("http://127.0.0.1:{port}/"),
+            None,
+            &[],
+            24 + 10,
+        );
+        server.join().unwrap();
+        assert_eq!(code, 200);
+        assert_eq!(u32::from_le_bytes(resp[4..8].try_into().unwrap()), 1);
+        assert_eq!((field(&resp, 8), field(&resp, 16)), (0, 10));
+        assert_eq!(&resp[24..34], b"0123456789");
+        let word = unsafe { crate::ffi::status::cl_last_status() } as u64;
+        assert_eq!(base_types::status::payload(word), 16);
+    }
+
+    #[test]
+    fn hostile_lengths_do_not_allocate() {
+        let replies: [&'static [u8]; 2] = [
+            b"HTTP/1.1 200 OK\r\nContent-Length: 4611686018427387904\r\n\r\nhello",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4000000000000000\r\nhello",
+        ];
+        for reply in replies {
+            let (port, server) = serve_once(reply);
+            let (code, _) = call("GET", &format!("http://127.0.0.1:{port}/"), None, &[], 64);
+            server.join().unwrap();
+            assert_eq!(code, -1);
+            let word = unsafe { crate::ffi::status::cl_last_status() } as u64;
+            assert_eq!(base_types::status::status(word), IO_ERROR);
+        }
+    }
+
+    #[test]
+    fn stalled_server_gives_up_on_cancel() {
+        use crate::ffi::cancel;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\n").unwrap();
+            let mut rest = Vec::new();
+            let _ = stream.read_to_end(&mut rest);
+        });
+        let token = Arc::new(AtomicBool::new(false));
+        cancel::set_token(Some(token.clone()));
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            token.store(true, Ordering::Release);
+        });
+        let start = std::time::Instant::now();
+        let (code, _) = call("GET", &format!("http://127.0.0.1:{port}/"), None, &[], 64);
+        cancel::set_token(None);
+        canceller.join().unwrap();
+        server.join().unwrap();
+        assert_eq!(code, -1);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rejects_bad_urls_and_small_capacity() {
+        for url in ["https://example.com/", "ftp://x/", "http:///path"] {
+            assert_eq!(call("GET", url, None, &[], 64).0, -1, "{url}");
+            let word = unsafe { crate::ffi::status::cl_last_status() } as u64;
+            assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+        }
+        assert_eq!(call("GET", "http://127.0.0.1:1/", None, &[], 16).0, -1);
+        assert_eq!(call("GET", "http://127.0.0.1:1/", None, &[], 64).0, -1);
+    }
+
+    #[test]
+    fn parse_url_defaults() {
+        assert_eq!(
+            parse_url("http://example.com"),
+            Some(("example.com:80".into(), "example.com".into(), "/".into()))
+        );
+        assert_eq!(
+            parse_url("http://[::1]?q=1"),
+            Some(("[::1]:80".into(), "[::1]".into(), "/?q=1".into()))
+        );
+        assert_eq!(
+            parse_url("http://[::1]:8080/a"),
+            Some(("[::1]:8080".into(), "[::1]:8080".into(), "/a".into()))
+        );
+    }
+}
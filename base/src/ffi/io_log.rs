@@ -0,0 +1,385 @@
+//! Per-execution recording and replay of file and network I/O, so an
+//! algorithm that reads machine-specific files or talks to a server can be
+//! tested hermetically against one recorded run (see `Base::set_io_log`).
+//!
+//! While recording, logged calls run as usual and append their outcome to
+//! the log: the return value, the status word, and the bytes they read (for
+//! reads and receives) or wrote (for writes and sends). While replaying they
+//! never reach the OS: reads are served from the log, and writes and sends
+//! only check their bytes against the recorded ones. Entries are keyed by
+//! the call with its arguments and by how many times that call has been
+//! made, so threads may interleave differently as long as each makes the
+//! same calls. A call with no entry, a read whose recorded bytes do not fit
+//! the call's buffer, or a write whose bytes differ, is a divergence: it
+//! fails with status `FAILED`, and the execution returns `Error::Execution`
+//! naming the first one. I/O calls the log does not cover (see
+//! `refuse_unlogged`) are divergences too while replaying.
+//!
+//! ```text
+//! log    "BASEIOL1" entry*
+//! entry  [u32 len][u16 key_len][key][u64 invocation][i64 ret][u64 status][data]
+//! ```
+//!
+//! `len` counts the bytes after it; `data` runs to the end of the entry.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::status;
+use base_types::status::FAILED;
+use tracing::error;
+
+const MAGIC: &[u8; 8] = b"BASEIOL1";
+
+/// What file and network calls do with their I/O; see `Base::set_io_log`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IoLog {
+    /// Perform I/O as usual and write each outcome to a new log at this path.
+    Record(PathBuf),
+    /// Serve I/O from the log at this path without touching files or sockets.
+    Replay(PathBuf),
+}
+
+struct Entry {
+    ret: i64,
+    status: u64,
+    data: Vec<u8>,
+}
+
+enum Mode {
+    Record(Mutex<BufWriter<fs::File>>),
+    Replay(Mutex<HashMap<(String, u64), Entry>>),
+}
+
+/// One execution's log, shared with the threads it starts.
+pub(crate) struct Session {
+    mode: Mode,
+    invocations: Mutex<HashMap<String, u64>>,
+    // The first divergence, or the first failed write to a recording.
+    failure: Mutex<Option<String>>,
+}
+
+impl Session {
+    /// Create the recording, or load the whole log to replay.
+    pub(crate) fn open(log: &IoLog) -> io::Result<Session> {
+        let mode = match log {
+            IoLog::Record(path) => {
+                let mut out = BufWriter::new(fs::File::create(path)?);
+                out.write_all(MAGIC)?;
+                Mode::Record(Mutex::new(out))
+            }
+            IoLog::Replay(path) => Mode::Replay(Mutex::new(parse(&fs::read(path)?)?)),
+        };
+        Ok(Session {
+            mode,
+            invocations: Mutex::new(HashMap::new()),
+            failure: Mutex::new(None),
+        })
+    }
+
+    /// Flush a recording, then report the first failure, if any.
+    pub(crate) fn finish(&self) -> Result<(), String> {
+        if let Mode::Record(out) = &self.mode {
+            if let Err(e) = out.lock().unwrap().flush() {
+                self.fail(format!("writing the I/O log failed: {e}"));
+            }
+        }
+        match self.failure.lock().unwrap().take() {
+            Some(msg) => Err(msg),
+            None => Ok(()),
+        }
+    }
+
+    fn fail(&self, msg: String) {
+        error!(%msg, "I/O log");
+        self.failure.lock().unwrap().get_or_insert(msg);
+    }
+
+    /// How many times `key` was called before this call.
+    fn invocation(&self, key: &str) -> u64 {
+        let mut invocations = self.invocations.lock().unwrap();
+        let count = invocations.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Arc<Session>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current() -> Option<Arc<Session>> {
+    SESSION.with(|cell| cell.borrow().clone())
+}
+
+pub(crate) fn set(session: Option<Arc<Session>>) {
+    SESSION.with(|cell| *cell.borrow_mut() = session);
+}
+
+fn parse(mut bytes: &[u8]) -> io::Result<HashMap<(String, u64), Entry>> {
+    fn take<'a>(bytes: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+        if bytes.len() < n {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated I/O log",
+            ));
+        }
+        let (head, tail) = bytes.split_at(n);
+        *bytes = tail;
+        Ok(head)
+    }
+    fn u64_at(bytes: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+    }
+    if take(&mut bytes, MAGIC.len())? != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not an I/O log"));
+    }
+    let mut entries = HashMap::new();
+    while !bytes.is_empty() {
+        let len = u32::from_le_bytes(take(&mut bytes, 4)?.try_into().unwrap());
+        let mut entry = take(&mut bytes, len as usize)?;
+        let key_len = u16::from_le_bytes(take(&mut entry, 2)?.try_into().unwrap());
+        let key = String::from_utf8_lossy(take(&mut entry, key_len as usize)?).into_owned();
+        let fixed = take(&mut entry, 24)?;
+        let invocation = u64_at(fixed, 0);
+        let recorded = Entry {
+            ret: u64_at(fixed, 8) as i64,
+            status: u64_at(fixed, 16),
+            data: entry.to_vec(),
+        };
+        entries.insert((key, invocation), recorded);
+    }
+    Ok(entries)
+}
+
+fn encode(key: &str, invocation: u64, entry: &Entry) -> Vec<u8> {
+    let len = 2 + key.len() + 24 + entry.data.len();
+    let mut out = Vec::with_capacity(4 + len);
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+    out.extend_from_slice(&(key.len() as u16).to_le_bytes());
+    out.extend_from_slice(key.as_bytes());
+    out.extend_from_slice(&invocation.to_le_bytes());
+    out.extend_from_slice(&entry.ret.to_le_bytes());
+    out.extend_from_slice(&entry.status.to_le_bytes());
+    out.extend_from_slice(&entry.data);
+    out
+}
+
+/// The bytes a logged call exchanges with the outside world.
+enum Data<'a> {
+    #[cfg(feature = "net")]
+    None,
+    /// Read at most this many bytes into this address; the live call reports
+    /// how many it stored.
+    Read(*mut u8, usize),
+    Write(&'a [u8]),
+}
+
+/// Run `live` under the execution's log, or directly without one. `key`
+/// names the call and its arguments; `failure` is returned on divergence.
+fn exchange(
+    key: impl FnOnce() -> String,
+    data: Data,
+    failure: i64,
+    live: impl FnOnce() -> (i64, usize),
+) -> i64 {
+    let Some(session) = current() else {
+        return live().0;
+    };
+    let key = key();
+    let invocation = session.invocation(&key);
+    match &session.mode {
+        Mode::Record(out) => {
+            let (ret, produced) = live();
+            let data = match data {
+                #[cfg(feature = "net")]
+                Data::None => Vec::new(),
+                Data::Read(dst, _) => unsafe { std::slice::from_raw_parts(dst, produced) }.to_vec(),
+                Data::Write(src) => src.to_vec(),
+            };
+            let entry = Entry {
+                ret,
+                status: status::word(),
+                data,
+            };
+            let bytes = encode(&key, invocation, &entry);
+            if let Err(e) = out.lock().unwrap().write_all(&bytes) {
+                session.fail(format!("writing the I/O log failed: {e}"));
+            }
+            ret
+        }
+        Mode::Replay(entries) => {
+            let call = invocation + 1;
+            let Some(entry) = entries.lock().unwrap().remove(&(key.clone(), invocation)) else {
+                session.fail(format!(
+                    "replay diverged: call {call} of `{key}` is not in the I/O log"
+                ));
+                status::set(FAILED, 0);
+                return failure;
+            };
+            match data {
+                Data::Read(_, capacity) if entry.data.len() > capacity => {
+                    session.fail(format!(
+                        "replay diverged: call {call} of `{key}` recorded {} bytes, more than \
+                         the {capacity} it reads",
+                        entry.data.len()
+                    ));
+                    status::set(FAILED, 0);
+                    return failure;
+                }
+                Data::Read(dst, _) => unsafe {
+                    std::ptr::copy_nonoverlapping(entry.data.as_ptr(), dst, entry.data.len());
+                },
+                Data::Write(src) if src != entry.data => {
+                    session.fail(format!(
+                        "replay diverged: call {call} of `{key}` wrote {} bytes that differ \
+                         from the {} recorded",
+                        src.len(),
+                        entry.data.len()
+                    ));
+                    status::set(FAILED, 0);
+                    return failure;
+                }
+                _ => {}
+            }
+            status::restore(entry.status);
+            entry.ret
+        }
+    }
+}
+
+/// A call whose outcome is just its return value, such as a connect.
+#[cfg(feature = "net")]
+pub(super) fn call(key: impl FnOnce() -> String, failure: i64, live: impl FnOnce() -> i64) -> i64 {
+    exchange(key, Data::None, failure, || (live(), 0))
+}
+
+/// A call that reads at most `capacity` bytes into `dst`; `live` returns its
+/// result and the number of bytes it stored there.
+pub(super) fn read(
+    key: impl FnOnce() -> String,
+    failure: i64,
+    dst: *mut u8,
+    capacity: usize,
+    live: impl FnOnce() -> (i64, usize),
+) -> i64 {
+    exchange(key, Data::Read(dst, capacity), failure, live)
+}
+
+/// A call that writes `src`.
+pub(super) fn write(
+    key: impl FnOnce() -> String,
+    failure: i64,
+    src: &[u8],
+    live: impl FnOnce() -> i64,
+) -> i64 {
+    exchange(key, Data::Write(src), failure, || (live(), 0))
+}
+
+/// Check `name`, an I/O call the log does not cover, before it touches the
+/// OS. While replaying it is a divergence: returns true with status `FAILED`,
+/// and the caller returns its failure value.
+pub(super) fn refuse_unlogged(name: &str) -> bool {
+    let Some(session) = current() else {
+        return false;
+    };
+    if !matches!(session.mode, Mode::Replay(_)) {
+        return false;
+    }
+    session.fail(format!(
+        "replay diverged: `{name}` is not covered by the I/O log"
+    ));
+    status::set(FAILED, 0);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(log: IoLog) -> Arc<Session> {
+        let session = Arc::new(Session::open(&log).unwrap());
+        set(Some(session.clone()));
+        session
+    }
+
+    #[test]
+    fn replay_serves_reads_and_checks_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("io.log");
+        let recording = session(IoLog::Record(path.clone()));
+        let mut buf = [0u8; 8];
+        for word in [7u64, 9] {
+            let n = read(
+                || "r".into(),
+                -1,
+                buf.as_mut_ptr(),
+                8,
+                || {
+                    buf.copy_from_slice(&word.to_le_bytes());
+                    status::ok(8);
+                    (8, 8)
+                },
+            );
+            assert_eq!(n, 8);
+        }
+        assert_eq!(write(|| "w".into(), -1, b"abc", || 3), 3);
+        set(None);
+        assert_eq!(recording.finish(), Ok(()));
+
+        let replay = session(IoLog::Replay(path));
+        let live = || -> (i64, usize) { panic!("replay reached the OS") };
+        for word in [7u64, 9] {
+            assert_eq!(read(|| "r".into(), -1, buf.as_mut_ptr(), 8, live), 8);
+            assert_eq!(u64::from_le_bytes(buf), word, "served in call order");
+            assert_eq!(status::word(), base_types::status::pack(0, 8));
+        }
+        assert_eq!(write(|| "w".into(), -1, b"abd", || 3), -1);
+        assert_eq!(read(|| "r".into(), -1, buf.as_mut_ptr(), 8, live), -1);
+        set(None);
+        let err = replay.finish().unwrap_err();
+        assert!(err.contains("call 1 of `w`"), "first divergence: {err}");
+    }
+
+    #[test]
+    fn replay_bounds_reads_and_refuses_unlogged_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("io.log");
+        let recording = session(IoLog::Record(path.clone()));
+        let mut buf = [0u8; 8];
+        let live = || {
+            status::ok(8);
+            (8, 8)
+        };
+        assert_eq!(read(|| "r".into(), -1, buf.as_mut_ptr(), 8, live), 8);
+        assert!(!refuse_unlogged("cl_file_open"));
+        set(None);
+        assert_eq!(recording.finish(), Ok(()));
+
+        let replay = session(IoLog::Replay(path));
+        let mut small = [0xffu8; 5];
+        let live = || -> (i64, usize) { panic!("replay reached the OS") };
+        assert_eq!(read(|| "r".into(), -1, small.as_mut_ptr(), 4, live), -1);
+        assert_eq!(small, [0xff; 5], "nothing copied past the buffer");
+        assert!(refuse_unlogged("cl_file_open"));
+        assert_eq!(base_types::status::status(status::word()), FAILED);
+        set(None);
+        let err = replay.finish().unwrap_err();
+        assert!(err.contains("recorded 8 bytes, more than the 4"), "{err}");
+        assert!(!refuse_unlogged("cl_file_open"), "no log, no refusal");
+    }
+
+    #[test]
+    fn rejects_malformed_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("io.log");
+        fs::write(&path, b"BASEIOL1\x40\0\0\0").unwrap();
+        assert!(Session::open(&IoLog::Replay(path.clone())).is_err());
+        fs::write(&path, b"something else").unwrap();
+        assert!(Session::open(&IoLog::Replay(path)).is_err());
+    }
+}
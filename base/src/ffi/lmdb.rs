@@ -1,12 +1,37 @@
 use lmdb_zero as lmdb;
 use std::collections::HashMap;
 
-use super::{clear_ctx_slot, read_cstr_ptr, read_ctx_mut, read_ctx_ref, write_ctx_slot};
+use super::handles::HandleTable;
+use super::{
+    clear_ctx_slot, read_cstr_ptr, read_ctx_mut, read_ctx_ref, sandbox, status, write_ctx_slot,
+};
+use base_types::status::{FAILED, INVALID_ARGUMENT, NOT_FOUND};
+
+/// `cl_lmdb_open_with` option flags.
+pub(crate) const LMDB_READ_ONLY: u32 = 1;
+pub(crate) const LMDB_NO_SYNC: u32 = 1 << 1;
+pub(crate) const LMDB_NO_META_SYNC: u32 = 1 << 2;
+pub(crate) const LMDB_WRITE_MAP: u32 = 1 << 3;
+
+/// Size of the `cl_lmdb_open_with` options block: u64 map size in bytes,
+/// u32 max named databases (0 for the default), u32 `LMDB_*` flags.
+pub(crate) const LMDB_OPTIONS_SIZE: usize = 16;
 
 pub(crate) struct CraneliftLmdbContext {
-    envs: HashMap<u32, (lmdb::Environment, liblmdb_sys::MDB_dbi)>,
+    envs: HandleTable<Env>,
     active_write_txns: HashMap<u32, *mut liblmdb_sys::MDB_txn>,
-    next_handle: u32,
+}
+
+type Env = (lmdb::Environment, liblmdb_sys::MDB_dbi);
+
+/// The environment behind `handle`, or `None` with a `NOT_FOUND` status for
+/// an unknown, closed or stale handle.
+fn env(envs: &HandleTable<Env>, handle: u32) -> Option<&Env> {
+    let found = envs.get(handle);
+    if found.is_none() {
+        status::set(NOT_FOUND, 0);
+    }
+    found
 }
 
 impl Drop for CraneliftLmdbContext {
@@ -19,14 +44,25 @@ impl Drop for CraneliftLmdbContext {
     }
 }
 
+/// Report an LMDB failure: positive codes are errno values (e.g. `EACCES`
+/// for a write to a read-only environment), LMDB's own codes are `FAILED`.
+fn set_lmdb_error(err: &lmdb::Error) {
+    match err {
+        lmdb::Error::Code(code) if *code > 0 => status::set(*code as u32, 0),
+        _ => status::set(FAILED, 0),
+    }
+}
+
 fn lmdb_raw_begin_txn(env: &lmdb::Environment, readonly: bool) -> *mut liblmdb_sys::MDB_txn {
     let mut txn = std::ptr::null_mut();
     let flags = if readonly { liblmdb_sys::MDB_RDONLY } else { 0 };
     unsafe {
-        if liblmdb_sys::mdb_txn_begin(env.as_raw(), std::ptr::null_mut(), flags, &mut txn) == 0 {
-            txn
-        } else {
-            std::ptr::null_mut()
+        match liblmdb_sys::mdb_txn_begin(env.as_raw(), std::ptr::null_mut(), flags, &mut txn) {
+            0 => txn,
+            code => {
+                set_lmdb_error(&lmdb::Error::Code(code));
+                std::ptr::null_mut()
+            }
         }
     }
 }
@@ -48,11 +84,13 @@ fn lmdb_raw_put(
     unsafe { liblmdb_sys::mdb_put(txn, dbi, &mut k, &mut v, 0) == 0 }
 }
 
-fn lmdb_raw_get(
+/// The value stored under `key`, borrowed from the map: valid until `txn`
+/// ends.
+unsafe fn lmdb_raw_get<'t>(
     txn: *mut liblmdb_sys::MDB_txn,
     dbi: liblmdb_sys::MDB_dbi,
     key: &[u8],
-) -> Option<Vec<u8>> {
+) -> Option<&'t [u8]> {
     let mut k = liblmdb_sys::MDB_val {
         mv_size: key.len(),
         mv_data: key.as_ptr() as *const _,
@@ -61,12 +99,13 @@ fn lmdb_raw_get(
         mv_size: 0,
         mv_data: std::ptr::null(),
     };
-    unsafe {
-        if liblmdb_sys::mdb_get(txn, dbi, &mut k, &mut v) == 0 {
-            Some(std::slice::from_raw_parts(v.mv_data as *const u8, v.mv_size).to_vec())
-        } else {
-            None
-        }
+    if liblmdb_sys::mdb_get(txn, dbi, &mut k, &mut v) == 0 {
+        Some(std::slice::from_raw_parts(
+            v.mv_data as *const u8,
+            v.mv_size,
+        ))
+    } else {
+        None
     }
 }
 
@@ -83,6 +122,7 @@ fn lmdb_raw_cursor_scan(
     dbi: liblmdb_sys::MDB_dbi,
     start_key: Option<&[u8]>,
     max_entries: usize,
+    budget: usize,
 ) -> Vec<u8> {
     let mut result = Vec::new();
     result.extend_from_slice(&0u32.to_le_bytes());
@@ -125,6 +165,9 @@ fn lmdb_raw_cursor_scan(
                 if k.mv_size > u16::MAX as usize || v.mv_size > u16::MAX as usize {
                     break;
                 }
+                if budget - result.len() < 4 + k.mv_size + v.mv_size {
+                    break;
+                }
                 result.extend_from_slice(&(k.mv_size as u16).to_le_bytes());
                 result.extend_from_slice(&(v.mv_size as u16).to_le_bytes());
                 result.extend_from_slice(std::slice::from_raw_parts(
@@ -155,9 +198,8 @@ fn lmdb_raw_cursor_scan(
 
 pub(crate) unsafe extern "C" fn cl_lmdb_init(ctx_slot_ptr: *mut *mut CraneliftLmdbContext) {
     let ctx = Box::new(CraneliftLmdbContext {
-        envs: HashMap::new(),
+        envs: HandleTable::new(),
         active_write_txns: HashMap::new(),
-        next_handle: 0,
     });
     let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
 }
@@ -167,6 +209,7 @@ pub(crate) unsafe extern "C" fn cl_lmdb_open(
     path_ptr: *const u8,
     map_size_mb: i32,
 ) -> i32 {
+    status::begin();
     let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
         return -1;
     };
@@ -176,32 +219,110 @@ pub(crate) unsafe extern "C" fn cl_lmdb_open(
     } else {
         (map_size_mb as usize) * 1024 * 1024
     };
+    let flags = lmdb::open::WRITEMAP | lmdb::open::NOSYNC;
+    open_env(ctx, &path_str, map_size, 1, flags)
+}
+
+/// Open like `cl_lmdb_open`, configured by the `LMDB_OPTIONS_SIZE` block at
+/// `opts_ptr`: map size (0 for 1GB), max databases, and `LMDB_*` flags
+/// applied as-is (none of `cl_lmdb_open`'s defaults). A read-only
+/// environment must already exist; writes to it fail with `EACCES` in the
+/// status word. Failures, including a map size the system refuses, return
+/// -1 with the LMDB errno or `FAILED` in the status word.
+pub(crate) unsafe extern "C" fn cl_lmdb_open_with(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    path_ptr: *const u8,
+    opts_ptr: *const u8,
+) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
+        return -1;
+    };
+    if opts_ptr.is_null() {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let opts = std::slice::from_raw_parts(opts_ptr, LMDB_OPTIONS_SIZE);
+    let map_size = u64::from_le_bytes(opts[0..8].try_into().unwrap());
+    let max_dbs = u32::from_le_bytes(opts[8..12].try_into().unwrap());
+    let bits = u32::from_le_bytes(opts[12..16].try_into().unwrap());
+    let all = LMDB_READ_ONLY | LMDB_NO_SYNC | LMDB_NO_META_SYNC | LMDB_WRITE_MAP;
+    if bits & !all != 0 || map_size > isize::MAX as u64 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let mut flags = lmdb::open::Flags::empty();
+    for (bit, flag) in [
+        (LMDB_READ_ONLY, lmdb::open::RDONLY),
+        (LMDB_NO_SYNC, lmdb::open::NOSYNC),
+        (LMDB_NO_META_SYNC, lmdb::open::NOMETASYNC),
+        (LMDB_WRITE_MAP, lmdb::open::WRITEMAP),
+    ] {
+        if bits & bit != 0 {
+            flags |= flag;
+        }
+    }
+    let map_size = if map_size == 0 {
+        1024 * 1024 * 1024
+    } else {
+        map_size as usize
+    };
+    let path_str = read_cstr_ptr(path_ptr);
+    open_env(ctx, &path_str, map_size, max_dbs.max(1), flags)
+}
 
-    if std::fs::create_dir_all(&path_str).is_err() {
+unsafe fn open_env(
+    ctx: &mut CraneliftLmdbContext,
+    path_str: &str,
+    map_size: usize,
+    max_dbs: u32,
+    flags: lmdb::open::Flags,
+) -> i32 {
+    let Some(path) = sandbox::resolve(path_str.into()) else {
+        return -1;
+    };
+    let path_str = &*path.to_string_lossy();
+    if let Err(e) = std::fs::create_dir_all(path_str) {
+        status::io(&e);
         return -1;
     }
 
     let env = match lmdb::EnvBuilder::new() {
         Ok(mut builder) => {
-            builder.set_mapsize(map_size).ok();
-            builder.set_maxdbs(1).ok();
-            let flags = lmdb::open::WRITEMAP | lmdb::open::NOSYNC;
-            match builder.open(&path_str, flags, 0o600) {
+            if let Err(e) = builder
+                .set_mapsize(map_size)
+                .and_then(|_| builder.set_maxdbs(max_dbs))
+            {
+                set_lmdb_error(&e);
+                return -1;
+            }
+            match builder.open(path_str, flags, 0o600) {
                 Ok(env) => env,
-                Err(_) => return -1,
+                Err(e) => {
+                    set_lmdb_error(&e);
+                    return -1;
+                }
             }
         }
-        Err(_) => return -1,
+        Err(e) => {
+            set_lmdb_error(&e);
+            return -1;
+        }
     };
 
     let dbi = match lmdb::Database::open(&env, None, &lmdb::DatabaseOptions::defaults()) {
         Ok(db) => db.into_raw(),
-        Err(_) => return -1,
+        Err(e) => {
+            set_lmdb_error(&e);
+            return -1;
+        }
     };
 
-    let handle = ctx.next_handle;
-    ctx.next_handle += 1;
-    ctx.envs.insert(handle, (env, dbi));
+    let Some(handle) = ctx.envs.insert((env, dbi)) else {
+        status::set(FAILED, 0);
+        return -1;
+    };
+    status::ok(0);
     handle as i32
 }
 
@@ -213,16 +334,18 @@ pub(crate) unsafe extern "C" fn cl_lmdb_put(
     val_ptr: *const u8,
     val_len: i32,
 ) -> i32 {
+    status::begin();
     let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
         return -1;
     };
-    if let Some((env, dbi)) = ctx.envs.get(&handle) {
+    if let Some((env, dbi)) = env(&ctx.envs, handle) {
         let key = std::slice::from_raw_parts(key_ptr, key_len as usize);
         let val = std::slice::from_raw_parts(val_ptr, val_len as usize);
         let dbi = *dbi;
 
         if let Some(&txn) = ctx.active_write_txns.get(&handle) {
             return if lmdb_raw_put(txn, dbi, key, val) {
+                status::ok(val.len() as u64);
                 0
             } else {
                 -1
@@ -238,6 +361,7 @@ pub(crate) unsafe extern "C" fn cl_lmdb_put(
             if liblmdb_sys::mdb_txn_commit(txn) != 0 {
                 return -1;
             }
+            status::ok(val.len() as u64);
             return 0;
         }
     }
@@ -250,11 +374,53 @@ pub(crate) unsafe extern "C" fn cl_lmdb_get(
     key_ptr: *const u8,
     key_len: i32,
     result_ptr: *mut u8,
+) -> i32 {
+    status::begin();
+    get_into(ctx_ptr, handle, key_ptr, key_len, result_ptr, usize::MAX)
+}
+
+/// Like `cl_lmdb_get`, but writes at most `capacity` value bytes after the
+/// u32 length header. The header always holds the full length, so a caller
+/// can tell a truncated value from a complete one and retry into a larger
+/// buffer; capacity 0 queries the length alone. Returns the full length, or
+/// -1 (header 0xFFFFFFFF) with the status set.
+pub(crate) unsafe extern "C" fn cl_lmdb_get_bounded(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    handle: u32,
+    key_ptr: *const u8,
+    key_len: i32,
+    result_ptr: *mut u8,
+    capacity: i32,
+) -> i32 {
+    status::begin();
+    if capacity < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    get_into(
+        ctx_ptr,
+        handle,
+        key_ptr,
+        key_len,
+        result_ptr,
+        capacity as usize,
+    )
+}
+
+/// Write the value's u32 length and up to `capacity` of its bytes to
+/// `result_ptr`, straight from the map.
+unsafe fn get_into(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    handle: u32,
+    key_ptr: *const u8,
+    key_len: i32,
+    result_ptr: *mut u8,
+    capacity: usize,
 ) -> i32 {
     let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
         return -1;
     };
-    if let Some((env, dbi)) = ctx.envs.get(&handle) {
+    if let Some((env, dbi)) = env(&ctx.envs, handle) {
         let key = std::slice::from_raw_parts(key_ptr, key_len as usize);
         let dbi = *dbi;
 
@@ -267,15 +433,18 @@ pub(crate) unsafe extern "C" fn cl_lmdb_get(
                 let len = val.len() as u32;
                 let dst = result_ptr;
                 std::ptr::copy_nonoverlapping(len.to_le_bytes().as_ptr(), dst, 4);
-                std::ptr::copy_nonoverlapping(val.as_ptr(), dst.add(4), val.len());
+                let n = val.len().min(capacity);
+                std::ptr::copy_nonoverlapping(val.as_ptr(), dst.add(4), n);
                 if owned {
                     liblmdb_sys::mdb_txn_abort(txn);
                 }
+                status::ok(len as u64);
                 return len as i32;
             }
             if owned {
                 liblmdb_sys::mdb_txn_abort(txn);
             }
+            status::set(NOT_FOUND, 0);
         }
     }
     std::ptr::copy_nonoverlapping(0xFFFF_FFFFu32.to_le_bytes().as_ptr(), result_ptr, 4);
@@ -288,15 +457,20 @@ pub(crate) unsafe extern "C" fn cl_lmdb_delete(
     key_ptr: *const u8,
     key_len: i32,
 ) -> i32 {
+    status::begin();
     let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
         return -1;
     };
-    if let Some((env, dbi)) = ctx.envs.get(&handle) {
+    if let Some((env, dbi)) = env(&ctx.envs, handle) {
         let key = std::slice::from_raw_parts(key_ptr, key_len as usize);
         let dbi = *dbi;
 
         if let Some(&txn) = ctx.active_write_txns.get(&handle) {
-            return if lmdb_raw_del(txn, dbi, key) { 0 } else { -1 };
+            if !lmdb_raw_del(txn, dbi, key) {
+                return -1;
+            }
+            status::ok(0);
+            return 0;
         }
         let txn = lmdb_raw_begin_txn(env, false);
         if !txn.is_null() {
@@ -308,6 +482,7 @@ pub(crate) unsafe extern "C" fn cl_lmdb_delete(
             if liblmdb_sys::mdb_txn_commit(txn) != 0 {
                 return -1;
             }
+            status::ok(0);
             return 0;
         }
     }
@@ -324,7 +499,7 @@ pub(crate) unsafe extern "C" fn cl_lmdb_begin_write_txn(
     if let Some(old_txn) = ctx.active_write_txns.remove(&handle) {
         liblmdb_sys::mdb_txn_abort(old_txn);
     }
-    if let Some((env, _)) = ctx.envs.get(&handle) {
+    if let Some((env, _)) = env(&ctx.envs, handle) {
         let txn = lmdb_raw_begin_txn(env, false);
         if !txn.is_null() {
             ctx.active_write_txns.insert(handle, txn);
@@ -358,11 +533,63 @@ pub(crate) unsafe extern "C" fn cl_lmdb_cursor_scan(
     key_len: i32,
     max_entries: i32,
     result_ptr: *mut u8,
+) -> i32 {
+    scan_into(
+        ctx_ptr,
+        handle,
+        key_ptr,
+        key_len,
+        max_entries,
+        result_ptr,
+        usize::MAX,
+    )
+}
+
+/// Like `cl_lmdb_cursor_scan`, but writes at most `capacity` bytes in all,
+/// count header included: the scan stops before the first entry that would
+/// not fit, so the output always holds whole entries. Returns the entry
+/// count, with the bytes written as the status payload, or 0 with the status
+/// set (`INVALID_ARGUMENT` for a capacity below the 4-byte header).
+pub(crate) unsafe extern "C" fn cl_lmdb_cursor_scan_bounded(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    handle: u32,
+    key_ptr: *const u8,
+    key_len: i32,
+    max_entries: i32,
+    result_ptr: *mut u8,
+    capacity: i32,
+) -> i32 {
+    status::begin();
+    if capacity < 4 {
+        status::set(INVALID_ARGUMENT, 0);
+        return 0;
+    }
+    scan_into(
+        ctx_ptr,
+        handle,
+        key_ptr,
+        key_len,
+        max_entries,
+        result_ptr,
+        capacity as usize,
+    )
+}
+
+/// Scan into `result_ptr`, writing at most `budget` bytes; on success the
+/// status payload is the bytes written.
+unsafe fn scan_into(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    handle: u32,
+    key_ptr: *const u8,
+    key_len: i32,
+    max_entries: i32,
+    result_ptr: *mut u8,
+    budget: usize,
 ) -> i32 {
     let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
         return 0;
     };
-    if let Some((env, dbi)) = ctx.envs.get(&handle) {
+    if let Some((env, dbi)) = env(&ctx.envs, handle) {
         let start_key = if key_len > 0 {
             Some(std::slice::from_raw_parts(key_ptr, key_len as usize))
         } else {
@@ -375,12 +602,13 @@ pub(crate) unsafe extern "C" fn cl_lmdb_cursor_scan(
             None => (lmdb_raw_begin_txn(env, true), true),
         };
         if !txn.is_null() {
-            let result = lmdb_raw_cursor_scan(txn, dbi, start_key, max_entries as usize);
+            let result = lmdb_raw_cursor_scan(txn, dbi, start_key, max_entries as usize, budget);
             std::ptr::copy_nonoverlapping(result.as_ptr(), result_ptr, result.len());
             let count = u32::from_le_bytes(result[0..4].try_into().unwrap());
             if owned {
                 liblmdb_sys::mdb_txn_abort(txn);
             }
+            status::ok(result.len() as u64);
             return count as i32;
         }
     }
@@ -392,18 +620,54 @@ pub(crate) unsafe extern "C" fn cl_lmdb_sync(
     ctx_ptr: *const CraneliftLmdbContext,
     handle: u32,
 ) -> i32 {
+    status::begin();
     let Some(ctx) = read_ctx_ref::<CraneliftLmdbContext>(ctx_ptr) else {
         return -1;
     };
-    if let Some((env, _)) = ctx.envs.get(&handle) {
+    if let Some((env, _)) = env(&ctx.envs, handle) {
         match env.sync(true) {
-            Ok(_) => return 0,
+            Ok(_) => {
+                status::ok(0);
+                return 0;
+            }
             Err(_) => return -1,
         }
     }
     -1
 }
 
+/// Close the environment behind `handle`, aborting its open write
+/// transaction if any. Returns 0, or -1 (status `NOT_FOUND`) for an unknown,
+/// closed or stale handle.
+pub(crate) unsafe extern "C" fn cl_lmdb_close(
+    ctx_ptr: *mut CraneliftLmdbContext,
+    handle: u32,
+) -> i32 {
+    status::begin();
+    let Some(ctx) = read_ctx_mut::<CraneliftLmdbContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some(env) = ctx.envs.remove(handle) else {
+        status::set(NOT_FOUND, 0);
+        return -1;
+    };
+    // The transaction must end before its environment closes.
+    if let Some(txn) = ctx.active_write_txns.remove(&handle) {
+        liblmdb_sys::mdb_txn_abort(txn);
+    }
+    drop(env);
+    status::ok(0);
+    0
+}
+
+/// Number of open environments, for leak checks.
+pub(crate) unsafe extern "C" fn cl_lmdb_handle_count(ctx_ptr: *const CraneliftLmdbContext) -> u32 {
+    let Some(ctx) = read_ctx_ref::<CraneliftLmdbContext>(ctx_ptr) else {
+        return 0;
+    };
+    ctx.envs.len() as u32
+}
+
 pub(crate) unsafe extern "C" fn cl_lmdb_cleanup(ctx_slot_ptr: *mut *mut CraneliftLmdbContext) {
     let ctx_ptr = clear_ctx_slot::<CraneliftLmdbContext>(ctx_slot_ptr);
     drop(Box::from_raw(ctx_ptr));
@@ -735,6 +999,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bounded_get_fits_truncates_and_queries_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut slot = init();
+        unsafe {
+            let h = open_db(slot, dir.path());
+            put(slot, h, b"k", b"0123456789");
+            let get = |buf: &mut [u8], capacity: i32| {
+                cl_lmdb_get_bounded(slot, h, b"k".as_ptr(), 1, buf.as_mut_ptr(), capacity)
+            };
+
+            let mut buf = [0xAAu8; 16];
+            assert_eq!(get(&mut buf, 10), 10);
+            assert_eq!(&buf[..4], &10u32.to_le_bytes());
+            assert_eq!(&buf[4..14], b"0123456789");
+            assert_eq!(&buf[14..], &[0xAA; 2], "exact fit writes nothing past it");
+
+            let mut buf = [0xAAu8; 16];
+            assert_eq!(get(&mut buf, 4), 10, "returns the full length");
+            assert_eq!(&buf[..4], &10u32.to_le_bytes());
+            assert_eq!(&buf[4..8], b"0123");
+            assert!(buf[8..].iter().all(|&b| b == 0xAA));
+
+            let mut buf = [0xAAu8; 16];
+            assert_eq!(get(&mut buf, 0), 10);
+            assert_eq!(&buf[..4], &10u32.to_le_bytes());
+            assert!(buf[4..].iter().all(|&b| b == 0xAA), "length only");
+
+            assert_eq!(get(&mut buf, -1), -1);
+            cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn bounded_scan_stops_before_overflowing_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut slot = init();
+        unsafe {
+            let h = open_db(slot, dir.path());
+            put(slot, h, b"a", b"11");
+            put(slot, h, b"b", b"22");
+            put(slot, h, b"c", b"33");
+
+            // Header plus two 7-byte entries, one byte short of a third.
+            let mut buf = vec![0xAAu8; 64];
+            let ptr = buf.as_mut_ptr();
+            let count = cl_lmdb_cursor_scan_bounded(slot, h, std::ptr::null(), 0, 10, ptr, 24);
+            assert_eq!(count, 2);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::payload(word), 18);
+            let entries = decode_scan(&buf, 2);
+            assert_eq!(entries[1], (b"b".to_vec(), b"22".to_vec()));
+            assert!(buf[18..].iter().all(|&b| b == 0xAA));
+
+            let count = cl_lmdb_cursor_scan_bounded(slot, h, std::ptr::null(), 0, 10, ptr, 25);
+            assert_eq!(count, 3);
+            let count = cl_lmdb_cursor_scan_bounded(slot, h, std::ptr::null(), 0, 10, ptr, 3);
+            assert_eq!(count, 0);
+            cleanup(&mut slot);
+        }
+    }
+
     // ── sync ──────────────────────────────────────────────────────────────────
 
     #[test]
@@ -749,6 +1075,82 @@ mod tests {
         }
     }
 
+    // ── open options ──────────────────────────────────────────────────────────
+
+    fn options(map_size: u64, max_dbs: u32, flags: u32) -> [u8; LMDB_OPTIONS_SIZE] {
+        let mut opts = [0u8; LMDB_OPTIONS_SIZE];
+        opts[0..8].copy_from_slice(&map_size.to_le_bytes());
+        opts[8..12].copy_from_slice(&max_dbs.to_le_bytes());
+        opts[12..16].copy_from_slice(&flags.to_le_bytes());
+        opts
+    }
+
+    fn last_status() -> u32 {
+        base_types::status::status(unsafe { status::cl_last_status() } as u64)
+    }
+
+    #[test]
+    fn open_with_large_map_holds_10mb_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+        let opts = options(1 << 30, 0, LMDB_NO_SYNC | LMDB_WRITE_MAP);
+        let mut slot = init();
+        unsafe {
+            let h = cl_lmdb_open_with(slot, path.as_ptr() as *const u8, opts.as_ptr());
+            assert!(h >= 0);
+            let val: Vec<u8> = (0..10 << 20).map(|i| (i % 251) as u8).collect();
+            assert_eq!(put(slot, h as u32, b"big", &val), 0);
+            let mut buf = vec![0u8; 4 + val.len()];
+            let rc = cl_lmdb_get(slot, h as u32, b"big".as_ptr(), 3, buf.as_mut_ptr());
+            assert_eq!(rc as usize, val.len());
+            assert!(buf[4..] == val[..]);
+            cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn open_read_only_rejects_put_but_serves_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+        let mut slot = init();
+        unsafe {
+            let h = open_db(slot, dir.path());
+            assert_eq!(put(slot, h, b"k", b"v1"), 0);
+            cleanup(&mut slot);
+
+            slot = init();
+            let opts = options(0, 0, LMDB_READ_ONLY);
+            let h = cl_lmdb_open_with(slot, path.as_ptr() as *const u8, opts.as_ptr());
+            assert!(h >= 0);
+            let h = h as u32;
+            assert_eq!(put(slot, h, b"k", b"v2"), -1);
+            assert_eq!(last_status(), 13, "EACCES");
+            assert_eq!(get(slot, h, b"k"), Some(b"v1".to_vec()));
+            cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn open_with_reports_bad_options_as_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = CString::new(dir.path().to_str().unwrap()).unwrap();
+        let path = path.as_ptr() as *const u8;
+        let mut slot = init();
+        unsafe {
+            let opts = options(0, 0, 1 << 4);
+            assert_eq!(cl_lmdb_open_with(slot, path, opts.as_ptr()), -1);
+            assert_eq!(last_status(), base_types::status::INVALID_ARGUMENT);
+            assert_eq!(cl_lmdb_open_with(slot, path, std::ptr::null()), -1);
+            assert_eq!(last_status(), base_types::status::INVALID_ARGUMENT);
+
+            // A map far beyond the address space fails to open, not panic.
+            let opts = options(1 << 50, 0, LMDB_WRITE_MAP);
+            assert_eq!(cl_lmdb_open_with(slot, path, opts.as_ptr()), -1);
+            assert_ne!(last_status(), base_types::status::OK);
+            cleanup(&mut slot);
+        }
+    }
+
     // ── multi-db & error cases ────────────────────────────────────────────────
 
     #[test]
@@ -783,6 +1185,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn close_rejects_stale_handles_after_reopen() {
+        use base_types::status as st;
+        let dir = tempfile::tempdir().unwrap();
+        let mut slot = init();
+        unsafe {
+            let h = open_db(slot, dir.path());
+            assert_eq!(put(slot, h, b"k", b"kept"), 0);
+            assert_eq!(cl_lmdb_begin_write_txn(slot, h), 0);
+            assert_eq!(put(slot, h, b"k", b"dropped"), 0);
+            assert_eq!(cl_lmdb_handle_count(slot), 1);
+            assert_eq!(cl_lmdb_close(slot, h), 0, "aborts the open txn");
+            assert_eq!(cl_lmdb_handle_count(slot), 0);
+
+            let reopened = open_db(slot, dir.path());
+            assert_ne!(reopened, h);
+            assert!(get(slot, h, b"k").is_none(), "stale handle");
+            assert_eq!(st::status(status::cl_last_status() as u64), st::NOT_FOUND);
+            assert_eq!(put(slot, h, b"k", b"v"), -1);
+            assert_eq!(cl_lmdb_close(slot, h), -1);
+            assert_eq!(get(slot, reopened, b"k").unwrap(), b"kept");
+            assert_eq!(cl_lmdb_close(slot, reopened), 0);
+            assert_eq!(cl_lmdb_close(slot, reopened), -1, "double close");
+            cleanup(&mut slot);
+        }
+    }
+
     #[test]
     fn null_ctx_returns_errors() {
         let null = std::ptr::null_mut::<CraneliftLmdbContext>();
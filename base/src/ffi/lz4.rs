@@ -0,0 +1,313 @@
+//! LZ4 block compression over the memory region.
+//!
+//! Blocks use the standard LZ4 block format (no frame header or checksum),
+//! so they can be wrapped in an LZ4 frame and read by other tools.
+
+use super::status;
+use base_types::status::{FAILED, INVALID_ARGUMENT};
+
+const MIN_MATCH: usize = 4;
+// The last 5 bytes are always literals and no match starts in the last 12.
+const LAST_LITERALS: usize = 5;
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 65535;
+const HASH_BITS: u32 = 12;
+/// Largest input `cl_lz4_compress` accepts (LZ4's own limit).
+const MAX_INPUT: usize = 0x7E00_0000;
+
+#[derive(Debug, PartialEq)]
+enum Lz4Error {
+    /// The output does not fit in the destination.
+    Overflow,
+    /// The input is not a valid LZ4 block.
+    Corrupt,
+}
+
+fn read_u32(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes(buf[i..i + 4].try_into().unwrap())
+}
+
+fn hash(seq: u32) -> usize {
+    (seq.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+fn push_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn push_sequence(out: &mut Vec<u8>, literals: &[u8], offset_and_len: Option<(usize, usize)>) {
+    let lit = literals.len();
+    let match_code = offset_and_len.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    out.push(((lit.min(15) as u8) << 4) | match_code as u8);
+    if lit >= 15 {
+        push_length(out, lit - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, len)) = offset_and_len {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if len - MIN_MATCH >= 15 {
+            push_length(out, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+/// Greedy single-pass compressor with a 4096-entry hash table.
+fn compress(src: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(compress_bound(src.len()));
+    let mut table = vec![0u32; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+    if src.len() > MF_LIMIT {
+        let limit = src.len() - MF_LIMIT;
+        let match_end = src.len() - LAST_LITERALS;
+        while i < limit {
+            let seq = read_u32(src, i);
+            let slot = &mut table[hash(seq)];
+            // Entries are position + 1 so that 0 means empty.
+            let candidate = (*slot as usize).checked_sub(1);
+            *slot = i as u32 + 1;
+            match candidate {
+                Some(c) if i - c <= MAX_OFFSET && read_u32(src, c) == seq => {
+                    let mut len = MIN_MATCH;
+                    while i + len < match_end && src[c + len] == src[i + len] {
+                        len += 1;
+                    }
+                    push_sequence(&mut out, &src[anchor..i], Some((i - c, len)));
+                    i += len;
+                    anchor = i;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+    push_sequence(&mut out, &src[anchor..], None);
+    out
+}
+
+/// Largest compressed size of `len` input bytes.
+fn compress_bound(len: usize) -> usize {
+    len + len / 255 + 16
+}
+
+fn read_length(src: &[u8], ip: &mut usize, mut len: usize) -> Result<usize, Lz4Error> {
+    loop {
+        let b = *src.get(*ip).ok_or(Lz4Error::Corrupt)?;
+        *ip += 1;
+        len += b as usize;
+        if b != 255 {
+            return Ok(len);
+        }
+    }
+}
+
+/// Decode a block into `dst`, returning the decoded length.
+fn decompress(src: &[u8], dst: &mut [u8]) -> Result<usize, Lz4Error> {
+    let mut ip = 0;
+    let mut op = 0;
+    loop {
+        let token = *src.get(ip).ok_or(Lz4Error::Corrupt)?;
+        ip += 1;
+        let mut lit = (token >> 4) as usize;
+        if lit == 15 {
+            lit = read_length(src, &mut ip, lit)?;
+        }
+        let literals = src.get(ip..ip + lit).ok_or(Lz4Error::Corrupt)?;
+        dst.get_mut(op..op + lit)
+            .ok_or(Lz4Error::Overflow)?
+            .copy_from_slice(literals);
+        ip += lit;
+        op += lit;
+        if ip == src.len() {
+            return Ok(op);
+        }
+
+        let offset = src.get(ip..ip + 2).ok_or(Lz4Error::Corrupt)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        ip += 2;
+        if offset == 0 || offset > op {
+            return Err(Lz4Error::Corrupt);
+        }
+        let mut len = (token & 15) as usize;
+        if len == 15 {
+            len = read_length(src, &mut ip, len)?;
+        }
+        len += MIN_MATCH;
+        if op + len > dst.len() {
+            return Err(Lz4Error::Overflow);
+        }
+        // Byte by byte: a match may overlap the bytes it produces.
+        for k in op..op + len {
+            dst[k] = dst[k - offset];
+        }
+        op += len;
+    }
+}
+
+/// Check `cl_lz4_*` arguments, returning `[dst, src, size, capacity]`. The
+/// ranges must be disjoint: the destination is written while the source is
+/// still being read.
+fn ranges(ptr: *mut u8, dst: i64, src: i64, size: i64, cap: i64) -> Option<[usize; 4]> {
+    if ptr.is_null() || dst.min(src).min(size).min(cap) < 0 {
+        return None;
+    }
+    let [dst, src, size, cap] = [dst, src, size, cap].map(|v| v as usize);
+    let disjoint = dst.checked_add(cap)? <= src || src.checked_add(size)? <= dst;
+    disjoint.then_some([dst, src, size, cap])
+}
+
+/// Compress the `size` bytes (at most 0x7E000000) at `src_off` into one LZ4
+/// block at `dst_off`, which has room for `capacity` bytes (`size + size /
+/// 255 + 16` always suffices). Returns the compressed length, or -1: with
+/// `INVALID_ARGUMENT` for bad or overlapping ranges, or `FAILED` with the
+/// needed length as the status payload when the block does not fit.
+pub(crate) unsafe extern "C" fn cl_lz4_compress(
+    ptr: *mut u8,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+    capacity: i64,
+) -> i64 {
+    let Some([dst, src, size, cap]) = ranges(ptr, dst_off, src_off, size, capacity) else {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    };
+    if size > MAX_INPUT {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let block = compress(std::slice::from_raw_parts(ptr.add(src), size));
+    if block.len() > cap {
+        status::set(FAILED, block.len() as u64);
+        return -1;
+    }
+    std::ptr::copy_nonoverlapping(block.as_ptr(), ptr.add(dst), block.len());
+    status::ok(block.len() as u64);
+    block.len() as i64
+}
+
+/// Decompress the LZ4 block of `size` bytes at `src_off` into `dst_off`,
+/// which has room for `capacity` bytes. Returns the decompressed length, or
+/// -1: with `INVALID_ARGUMENT` for bad or overlapping ranges, or `FAILED`
+/// when the block is corrupt or decodes to more than `capacity` bytes (the
+/// destination contents are then unspecified).
+pub(crate) unsafe extern "C" fn cl_lz4_decompress(
+    ptr: *mut u8,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+    capacity: i64,
+) -> i64 {
+    let Some([dst, src, size, cap]) = ranges(ptr, dst_off, src_off, size, capacity) else {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    };
+    let block = std::slice::from_raw_parts(ptr.add(src), size);
+    let out = std::slice::from_raw_parts_mut(ptr.add(dst), cap);
+    match decompress(block, out) {
+        Ok(n) => {
+            status::ok(n as u64);
+            n as i64
+        }
+        Err(_) => {
+            status::set(FAILED, 0);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    fn roundtrip(data: &[u8]) -> usize {
+        let block = compress(data);
+        assert!(block.len() <= compress_bound(data.len()));
+        let mut out = vec![0u8; data.len()];
+        assert_eq!(decompress(&block, &mut out), Ok(data.len()));
+        assert!(out == data);
+        block.len()
+    }
+
+    #[test]
+    fn roundtrip_random_and_compressible() {
+        for len in [0, 1, 12, 13, 100, 65536 + 100] {
+            roundtrip(&random_bytes(len, len as u64 + 1));
+        }
+        let repeated: Vec<u8> = b"ABCDEFGHIJ"
+            .iter()
+            .cycle()
+            .take(1 << 20)
+            .copied()
+            .collect();
+        assert!(roundtrip(&repeated) < 5000);
+        assert!(roundtrip(&vec![7u8; 300_000]) < 2000);
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(500);
+        assert!(roundtrip(&text) < text.len() / 10);
+    }
+
+    #[test]
+    fn decodes_reference_block() {
+        // "abc", then a 9-byte match at offset 3, then the literal "x".
+        let block = [0x35, b'a', b'b', b'c', 3, 0, 0x10, b'x'];
+        let mut out = [0u8; 13];
+        assert_eq!(decompress(&block, &mut out), Ok(13));
+        assert_eq!(&out, b"abcabcabcabcx");
+        let mut short = [0u8; 12];
+        assert_eq!(decompress(&block, &mut short), Err(Lz4Error::Overflow));
+        assert_eq!(
+            decompress(&[0x35, b'a', b'b', b'c', 4, 0], &mut out),
+            Err(Lz4Error::Corrupt)
+        );
+        assert_eq!(decompress(&[0xF0], &mut out), Err(Lz4Error::Corrupt));
+        assert_eq!(decompress(&[], &mut out), Err(Lz4Error::Corrupt));
+    }
+
+    #[test]
+    fn ffi_reports_overflow_and_corrupt_input() {
+        use base_types::status as st;
+        let data = b"hello hello hello hello hello hello".to_vec();
+        let mut mem = vec![0u8; 256];
+        mem[..data.len()].copy_from_slice(&data);
+        let p = mem.as_mut_ptr();
+        let n = data.len() as i64;
+        unsafe {
+            let c = cl_lz4_compress(p, 64, 0, n, 64);
+            assert!(c > 0 && c < n);
+            assert_eq!(cl_lz4_decompress(p, 192, 64, c, 64), n);
+            assert_eq!(&mem[192..192 + data.len()], &data[..]);
+
+            assert_eq!(cl_lz4_compress(p, 64, 0, n, 2), -1);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(
+                (st::status(word), st::payload(word)),
+                (st::FAILED, c as u32)
+            );
+            assert_eq!(cl_lz4_decompress(p, 192, 64, c, 8), -1);
+            assert_eq!(st::status(status::cl_last_status() as u64), st::FAILED);
+            mem[64] = 0xFF;
+            assert_eq!(cl_lz4_decompress(p, 192, 64, c, 64), -1);
+            assert_eq!(cl_lz4_compress(p, 8, 0, n, 64), -1, "overlapping");
+            assert_eq!(
+                st::status(status::cl_last_status() as u64),
+                st::INVALID_ARGUMENT
+            );
+            assert_eq!(cl_lz4_compress(p, 64, 0, -1, 64), -1);
+        }
+    }
+}
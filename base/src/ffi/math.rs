@@ -0,0 +1,154 @@
+//! Selectable f64 elementary functions for CLIF code.
+//!
+//! `cl_approx(func, x, iters)` evaluates one of:
+//!
+//! | `func` | result | method | relative error |
+//! |--------|--------|--------|----------------|
+//! | 0 | `sqrt(x)` | `iters == 0`: hardware; else Newton on `1/sqrt(x)` then `x * r` | exact; `iters` 1/2/3/4+: 2e-3 / 5e-6 / 4e-11 / 4e-16 |
+//! | 1 | `1/sqrt(x)` | `iters == 0`: `1 / sqrt(x)`; else Newton from a bit-level estimate | 1 ulp; `iters` 1/2/3/4+: 2e-3 / 5e-6 / 4e-11 / 4e-16 |
+//! | 2 | `exp(x)` | libm | 1 ulp |
+//! | 3 | `ln(x)` | libm | 1 ulp |
+//! | 4 | `sin(x)` | libm | 1 ulp |
+//! | 5 | `cos(x)` | libm | 1 ulp |
+//! | 6 | `1/x` | `iters == 0`: division; else Newton from a bit-level estimate | exact; `iters` 1/2/3/4+: 3e-3 / 7e-6 / 5e-11 / 4e-16 |
+//!
+//! `iters` is the Newton step count for the iterative functions, capped at 8,
+//! and ignored by the libm ones. Negative counts behave like 0. Zeros,
+//! infinities, NaNs, negative inputs and subnormals skip the iteration and take
+//! the exact path, so the special values match std: `sqrt` and `ln` of a
+//! negative number are NaN, `ln(0)` is `-inf`, `exp` overflows to `inf` above
+//! ~709.8 and underflows to 0. An unknown `func` returns NaN.
+
+pub(crate) const SQRT: i64 = 0;
+pub(crate) const RSQRT: i64 = 1;
+pub(crate) const EXP: i64 = 2;
+pub(crate) const LN: i64 = 3;
+pub(crate) const SIN: i64 = 4;
+pub(crate) const COS: i64 = 5;
+pub(crate) const RECIP: i64 = 6;
+
+const MAX_ITERS: i64 = 8;
+
+fn rsqrt_newton(x: f64, iters: i64) -> f64 {
+    let mut y = f64::from_bits(0x5FE6_EB50_C7B5_37A9 - (x.to_bits() >> 1));
+    for _ in 0..iters {
+        y *= 1.5 - 0.5 * x * y * y;
+    }
+    y
+}
+
+fn recip_newton(x: f64, iters: i64) -> f64 {
+    let a = x.abs();
+    let mut y = f64::from_bits(0x7FDE_6238_22FC_16E6 - a.to_bits());
+    for _ in 0..iters {
+        y *= 2.0 - a * y;
+    }
+    y.copysign(x)
+}
+
+pub(crate) unsafe extern "C" fn cl_approx(func: i64, x: f64, iters: i64) -> f64 {
+    let iters = iters.clamp(0, MAX_ITERS);
+    let iterate = iters > 0 && x.is_normal();
+    match func {
+        SQRT if iterate && x > 0.0 => x * rsqrt_newton(x, iters),
+        SQRT => x.sqrt(),
+        RSQRT if iterate && x > 0.0 => rsqrt_newton(x, iters),
+        RSQRT => 1.0 / x.sqrt(),
+        EXP => x.exp(),
+        LN => x.ln(),
+        SIN => x.sin(),
+        COS => x.cos(),
+        RECIP if iterate => recip_newton(x, iters),
+        RECIP => 1.0 / x,
+        _ => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx(func: i64, x: f64, iters: i64) -> f64 {
+        unsafe { cl_approx(func, x, iters) }
+    }
+
+    fn inputs() -> Vec<f64> {
+        let mut xs = vec![1e-300, 1e-10, 0.1, 0.5, 1.0, 2.0, 3.0, 16.0, 1e10, 1e300];
+        xs.extend((1..200).map(|i| i as f64 * 0.37));
+        xs.extend((0..3000).map(|i| 1.0 + i as f64 / 1000.0));
+        xs
+    }
+
+    fn rel(got: f64, want: f64) -> f64 {
+        ((got - want) / want).abs()
+    }
+
+    #[test]
+    fn matches_std_within_the_documented_bounds() {
+        let iterative = [
+            (
+                SQRT,
+                f64::sqrt as fn(f64) -> f64,
+                [2e-3, 5e-6, 4e-11, 4e-16],
+            ),
+            (RSQRT, |x| 1.0 / x.sqrt(), [2e-3, 5e-6, 4e-11, 4e-16]),
+            (RECIP, |x| 1.0 / x, [3e-3, 7e-6, 5e-11, 4e-16]),
+        ];
+        for (func, exact, bounds) in iterative {
+            for x in inputs() {
+                assert_eq!(approx(func, x, 0), exact(x), "func {func} x {x}");
+                for iters in 1..=10 {
+                    let bound = bounds[(iters as usize).min(4) - 1];
+                    let got = approx(func, x, iters);
+                    assert!(
+                        rel(got, exact(x)) <= bound,
+                        "func {func} x {x} iters {iters}"
+                    );
+                }
+            }
+        }
+        assert!(rel(approx(RECIP, -3.0, 5), -1.0 / 3.0) <= 4e-16);
+
+        let libm = [
+            (EXP, f64::exp as fn(f64) -> f64),
+            (LN, f64::ln),
+            (SIN, f64::sin),
+            (COS, f64::cos),
+        ];
+        for (func, exact) in libm {
+            for x in inputs() {
+                assert_eq!(approx(func, x, 0).to_bits(), exact(x).to_bits());
+                assert_eq!(
+                    approx(func, x, 3).to_bits(),
+                    exact(x).to_bits(),
+                    "iters ignored"
+                );
+            }
+        }
+        assert_eq!(approx(SQRT, 16.0, 0), 4.0);
+    }
+
+    #[test]
+    fn special_values_follow_std() {
+        for iters in [0, 4] {
+            assert_eq!(approx(SQRT, 0.0, iters), 0.0);
+            assert!(approx(SQRT, -1.0, iters).is_nan());
+            assert_eq!(approx(SQRT, f64::INFINITY, iters), f64::INFINITY);
+            assert_eq!(approx(RSQRT, 0.0, iters), f64::INFINITY);
+            assert!(approx(RSQRT, -4.0, iters).is_nan());
+            assert_eq!(approx(RECIP, 0.0, iters), f64::INFINITY);
+            assert_eq!(approx(RECIP, -0.0, iters), f64::NEG_INFINITY);
+            assert_eq!(approx(RECIP, f64::INFINITY, iters), 0.0);
+            assert_eq!(approx(LN, 0.0, iters), f64::NEG_INFINITY);
+            assert!(approx(LN, -1.0, iters).is_nan());
+            assert_eq!(approx(EXP, 710.0, iters), f64::INFINITY);
+            assert_eq!(approx(EXP, 1e6, iters), f64::INFINITY);
+            assert_eq!(approx(EXP, -1e6, iters), 0.0);
+            assert!(approx(SIN, f64::INFINITY, iters).is_nan());
+            assert!(approx(COS, f64::NAN, iters).is_nan());
+        }
+        assert_eq!(approx(SQRT, 5e-324, 4), 5e-324f64.sqrt(), "subnormal");
+        assert!(approx(7, 1.0, 0).is_nan());
+        assert!(approx(-1, 1.0, 0).is_nan());
+    }
+}
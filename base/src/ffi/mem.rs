@@ -0,0 +1,1201 @@
+use std::cell::Cell;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use super::{cancel, status};
+use base_types::status::{FAILED, INVALID_ARGUMENT};
+
+/// Fill `size` bytes at `dst_off` with a repeating little-endian pattern taken
+/// from the low `width` bytes of `pattern` (width 1, 2, 4, or 8). A trailing
+/// partial pattern is truncated. Returns bytes filled, or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_mem_fill(
+    ptr: *mut u8,
+    dst_off: i64,
+    size: i64,
+    pattern: i64,
+    width: i64,
+) -> i64 {
+    if ptr.is_null() || dst_off < 0 || size < 0 || !matches!(width, 1 | 2 | 4 | 8) {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    status::ok(size as u64);
+    let dst = std::slice::from_raw_parts_mut(ptr.add(dst_off as usize), size as usize);
+    if width == 1 {
+        dst.fill(pattern as u8);
+        return size;
+    }
+    let bytes = pattern.to_le_bytes();
+    let seed = (width as usize).min(dst.len());
+    dst[..seed].copy_from_slice(&bytes[..seed]);
+    // Double the initialized prefix each pass so large fills are memcpy-bound.
+    let mut filled = seed;
+    while filled < dst.len() {
+        let n = filled.min(dst.len() - filled);
+        dst.copy_within(..n, filled);
+        filled += n;
+    }
+    size
+}
+
+// Copies below this size per worker stay on the calling thread.
+const COPY_MIN_CHUNK: usize = 1 << 20;
+// Copies at least this large bypass the cache with streaming stores where
+// the CPU has them, unless `Base::set_copy_stream_min` says otherwise: the
+// destination would evict the working set long before anything read it
+// back.
+pub(crate) const COPY_STREAM_MIN: usize = 8 << 20;
+
+thread_local! {
+    /// The streaming threshold of the execution running on this thread.
+    static STREAM_MIN: Cell<usize> = const { Cell::new(COPY_STREAM_MIN) };
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Copies this thread started with streaming stores requested.
+    static STREAMED_COPIES: Cell<usize> = const { Cell::new(0) };
+}
+
+pub(crate) fn copy_stream_min() -> usize {
+    STREAM_MIN.with(Cell::get)
+}
+
+/// Set this thread's streaming threshold; `None` restores `COPY_STREAM_MIN`.
+pub(crate) fn set_copy_stream_min(bytes: Option<usize>) {
+    STREAM_MIN.with(|cell| cell.set(bytes.unwrap_or(COPY_STREAM_MIN)));
+}
+
+/// Copy `size` bytes from `src_off` to `dst_off`, split into up to `workers`
+/// contiguous chunks copied on parallel threads; returns once every chunk has
+/// landed. From the streaming threshold (8 MiB unless set with
+/// `Base::set_copy_stream_min`), chunks are written with non-temporal stores
+/// on x86-64 CPUs with AVX. Chunks complete in no particular order, so
+/// overlapping ranges are copied on the calling thread with memmove
+/// semantics instead.
+/// Returns `size`, or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_mem_copy(
+    ptr: *mut u8,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+    workers: i64,
+) -> i64 {
+    if ptr.is_null() || dst_off < 0 || src_off < 0 || size < 0 || workers < 1 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let (dst_off, src_off, len) = (dst_off as usize, src_off as usize, size as usize);
+    let overlap = src_off < dst_off + len && dst_off < src_off + len;
+    let workers = (workers as usize).min(len / COPY_MIN_CHUNK).max(1);
+    let stream = len >= copy_stream_min();
+    #[cfg(test)]
+    if stream {
+        STREAMED_COPIES.with(|c| c.set(c.get() + 1));
+    }
+    if overlap {
+        std::ptr::copy(ptr.add(src_off), ptr.add(dst_off), len);
+    } else if workers == 1 {
+        copy_chunk(ptr.add(src_off), ptr.add(dst_off), len, stream);
+    } else {
+        let chunk = len.div_ceil(workers);
+        let base = ptr as usize;
+        std::thread::scope(|s| {
+            for start in (0..len).step_by(chunk) {
+                let n = chunk.min(len - start);
+                s.spawn(move || {
+                    let p = base as *mut u8;
+                    copy_chunk(p.add(src_off + start), p.add(dst_off + start), n, stream);
+                });
+            }
+        });
+    }
+    status::ok(len as u64);
+    size
+}
+
+/// Copy `n` non-overlapping bytes, with streaming stores when `stream` is set
+/// and the CPU supports them.
+unsafe fn copy_chunk(src: *const u8, dst: *mut u8, n: usize, stream: bool) {
+    #[cfg(target_arch = "x86_64")]
+    if stream && std::arch::is_x86_feature_detected!("avx") {
+        return stream_copy_avx(src, dst, n);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = stream;
+    std::ptr::copy_nonoverlapping(src, dst, n);
+}
+
+/// Plain copies up to the first 32-byte aligned destination byte and after
+/// the last whole block; `_mm256_stream_si256` in between, then an `sfence`
+/// so the streamed bytes are visible before the caller signals completion.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn stream_copy_avx(src: *const u8, dst: *mut u8, n: usize) {
+    use std::arch::x86_64::{__m256i, _mm256_loadu_si256, _mm256_stream_si256, _mm_sfence};
+    let head = dst.align_offset(32).min(n);
+    std::ptr::copy_nonoverlapping(src, dst, head);
+    let mut i = head;
+    while i + 128 <= n {
+        for k in (0..128).step_by(32) {
+            let v = _mm256_loadu_si256(src.add(i + k) as *const __m256i);
+            _mm256_stream_si256(dst.add(i + k) as *mut __m256i, v);
+        }
+        i += 128;
+    }
+    while i + 32 <= n {
+        let v = _mm256_loadu_si256(src.add(i) as *const __m256i);
+        _mm256_stream_si256(dst.add(i) as *mut __m256i, v);
+        i += 32;
+    }
+    std::ptr::copy_nonoverlapping(src.add(i), dst.add(i), n - i);
+    _mm_sfence();
+}
+
+/// Compare `size` bytes at `a_off` and `b_off`. Returns -1 when equal,
+/// otherwise the index of the first differing byte (-2 on bad arguments).
+pub(crate) unsafe extern "C" fn cl_mem_compare(
+    ptr: *const u8,
+    a_off: i64,
+    b_off: i64,
+    size: i64,
+) -> i64 {
+    if ptr.is_null() || a_off < 0 || b_off < 0 || size < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -2;
+    }
+    status::ok(0);
+    let a = std::slice::from_raw_parts(ptr.add(a_off as usize), size as usize);
+    let b = std::slice::from_raw_parts(ptr.add(b_off as usize), size as usize);
+    match first_difference(a, b) {
+        Some(i) => i as i64,
+        None => -1,
+    }
+}
+
+// Block-wise memcmp keeps multi-megabyte compares bandwidth bound; only the
+// mismatching block is rescanned byte by byte.
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    const BLOCK: usize = 32;
+    let mut base = 0;
+    for (ca, cb) in a.chunks(BLOCK).zip(b.chunks(BLOCK)) {
+        if ca != cb {
+            return ca
+                .iter()
+                .zip(cb)
+                .position(|(x, y)| x != y)
+                .map(|i| base + i);
+        }
+        base += ca.len();
+    }
+    None
+}
+
+/// `cl_mem_cond_write` mode, in bits 60..64 of `size`: copy when any of the
+/// 8 bytes at `cond_off` is non-zero. A mode of 0 means the same.
+pub(crate) const COND_NONZERO: i64 = 1;
+/// `cl_mem_cond_write` mode: select between `src_off` and the `size` bytes
+/// right after it, writing the second when the condition bytes are all zero.
+pub(crate) const COND_SELECT: i64 = 2;
+/// `cl_mem_cond_write` mode: copy when the i64 at `cond_off` is greater than
+/// zero.
+pub(crate) const COND_I64_POSITIVE: i64 = 3;
+/// `cl_mem_cond_write` mode: copy when the f64 at `cond_off` is greater than
+/// zero (false for -0.0 and NaN).
+pub(crate) const COND_F64_POSITIVE: i64 = 4;
+const COND_MODE_SHIFT: u32 = 60;
+
+/// Copy `size` bytes from `src_off` to `dst_off` depending on the 8-byte
+/// condition at `cond_off`, as selected by the mode in the top four bits of
+/// `size` (see `COND_NONZERO` and the following modes). Returns the bytes
+/// written (0 when the condition fails), or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_mem_cond_write(
+    ptr: *mut u8,
+    cond_off: i64,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+) -> i64 {
+    let mode = (size as u64 >> COND_MODE_SHIFT) as i64;
+    let len = (size as u64 & ((1 << COND_MODE_SHIFT) - 1)) as usize;
+    if ptr.is_null() || cond_off < 0 || dst_off < 0 || src_off < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let cond = std::ptr::read_unaligned(ptr.add(cond_off as usize) as *const u64);
+    let src = src_off as usize;
+    let (taken, src) = match mode {
+        0 | COND_NONZERO => (cond != 0, src),
+        COND_SELECT if cond == 0 => (true, src + len),
+        COND_SELECT => (true, src),
+        COND_I64_POSITIVE => (cond as i64 > 0, src),
+        COND_F64_POSITIVE => (f64::from_bits(cond) > 0.0, src),
+        _ => {
+            status::set(INVALID_ARGUMENT, 0);
+            return -1;
+        }
+    };
+    if !taken {
+        status::ok(0);
+        return 0;
+    }
+    std::ptr::copy(ptr.add(src), ptr.add(dst_off as usize), len);
+    status::ok(len as u64);
+    len as i64
+}
+
+/// Copy `size` bytes from `src_off` to `dst_off`, reversing the byte order of
+/// each `width`-byte element (2, 4, or 8), e.g. to read network-order
+/// records; `src_off == dst_off` swaps in place. Returns `size`, or -1 on bad
+/// arguments, including a `size` that is not a whole number of elements.
+pub(crate) unsafe extern "C" fn cl_mem_byteswap(
+    ptr: *mut u8,
+    dst_off: i64,
+    src_off: i64,
+    width: i64,
+    size: i64,
+) -> i64 {
+    if ptr.is_null() || dst_off < 0 || src_off < 0 || size < 0 || !matches!(width, 2 | 4 | 8) {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    if size % width != 0 {
+        status::set(INVALID_ARGUMENT, (size % width) as u64);
+        return -1;
+    }
+    let dst = ptr.add(dst_off as usize);
+    std::ptr::copy(ptr.add(src_off as usize), dst, size as usize);
+    swap_elements(
+        std::slice::from_raw_parts_mut(dst, size as usize),
+        width as usize,
+    );
+    status::ok(size as u64);
+    size
+}
+
+/// Reverse each `width`-byte element of `data` in place, 16 bytes per
+/// shuffle with SSSE3 or NEON.
+fn swap_elements(data: &mut [u8], width: usize) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("ssse3") {
+        return unsafe { swap_elements_ssse3(data, width) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return unsafe { swap_elements_neon(data, width) };
+    }
+    swap_elements_scalar(data, width)
+}
+
+fn swap_elements_scalar(data: &mut [u8], width: usize) {
+    for element in data.chunks_exact_mut(width) {
+        element.reverse();
+    }
+}
+
+/// Byte order within a 16-byte block that reverses each `width`-byte element.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn swap_order(width: usize) -> [u8; 16] {
+    std::array::from_fn(|i| (i - i % width + width - 1 - i % width) as u8)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "ssse3")]
+unsafe fn swap_elements_ssse3(data: &mut [u8], width: usize) {
+    use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_shuffle_epi8, _mm_storeu_si128};
+    let order = swap_order(width);
+    let mask = _mm_loadu_si128(order.as_ptr() as *const __m128i);
+    let mut blocks = data.chunks_exact_mut(16);
+    for block in &mut blocks {
+        let v = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+        _mm_storeu_si128(
+            block.as_mut_ptr() as *mut __m128i,
+            _mm_shuffle_epi8(v, mask),
+        );
+    }
+    swap_elements_scalar(blocks.into_remainder(), width);
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn swap_elements_neon(data: &mut [u8], width: usize) {
+    use std::arch::aarch64::{vld1q_u8, vqtbl1q_u8, vst1q_u8};
+    let order = swap_order(width);
+    let mask = vld1q_u8(order.as_ptr());
+    let mut blocks = data.chunks_exact_mut(16);
+    for block in &mut blocks {
+        let v = vld1q_u8(block.as_ptr());
+        vst1q_u8(block.as_mut_ptr(), vqtbl1q_u8(v, mask));
+    }
+    swap_elements_scalar(blocks.into_remainder(), width);
+}
+
+/// `cl_mem_array_op` operations: element type and arithmetic. Integer
+/// operations wrap.
+pub(crate) const ARRAY_ADD_F32: i64 = 1;
+pub(crate) const ARRAY_SUB_F32: i64 = 2;
+pub(crate) const ARRAY_MUL_F32: i64 = 3;
+pub(crate) const ARRAY_ADD_I32: i64 = 4;
+pub(crate) const ARRAY_SUB_I32: i64 = 5;
+pub(crate) const ARRAY_MUL_I32: i64 = 6;
+
+/// Combine the 4-byte elements of the `size`-byte arrays at `a_off` and
+/// `b_off` element-wise with `op` (`ARRAY_ADD_F32`, ...), writing the result
+/// at `dst_off`: a whole array in one call, vectorized to the widest SIMD the
+/// CPU has. The destination may be either source exactly but must not
+/// partially overlap one. Returns `size`, or -1 on bad arguments (including
+/// a partial element or an overlap).
+pub(crate) unsafe extern "C" fn cl_mem_array_op(
+    ptr: *mut u8,
+    dst_off: i64,
+    a_off: i64,
+    b_off: i64,
+    size: i64,
+    op: i64,
+) -> i64 {
+    if ptr.is_null() || dst_off < 0 || a_off < 0 || b_off < 0 || size < 0 || size % 4 != 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let (dst, len) = (dst_off as usize, size as usize);
+    let partial_overlap = |src: usize| src != dst && src < dst + len && dst < src + len;
+    if partial_overlap(a_off as usize) || partial_overlap(b_off as usize) {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let (dst, a, b) = (
+        ptr.add(dst),
+        ptr.add(a_off as usize),
+        ptr.add(b_off as usize),
+    );
+    let n = len / 4;
+    let (f, i) = (|p: *mut u8| p.cast::<f32>(), |p: *mut u8| p.cast::<i32>());
+    match op {
+        ARRAY_ADD_F32 => zip_elements(f(dst), f(a), f(b), n, |x, y| x + y),
+        ARRAY_SUB_F32 => zip_elements(f(dst), f(a), f(b), n, |x, y| x - y),
+        ARRAY_MUL_F32 => zip_elements(f(dst), f(a), f(b), n, |x, y| x * y),
+        ARRAY_ADD_I32 => zip_elements(i(dst), i(a), i(b), n, i32::wrapping_add),
+        ARRAY_SUB_I32 => zip_elements(i(dst), i(a), i(b), n, i32::wrapping_sub),
+        ARRAY_MUL_I32 => zip_elements(i(dst), i(a), i(b), n, i32::wrapping_mul),
+        _ => {
+            status::set(INVALID_ARGUMENT, 0);
+            return -1;
+        }
+    }
+    status::ok(size as u64);
+    size
+}
+
+/// `dst[i] = f(a[i], b[i])` for `n` unaligned elements, through AVX2 when the
+/// CPU has it.
+unsafe fn zip_elements<T: Copy, F: Fn(T, T) -> T>(
+    dst: *mut T,
+    a: *mut T,
+    b: *mut T,
+    n: usize,
+    f: F,
+) {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        return zip_elements_avx2(dst, a, b, n, f);
+    }
+    zip_lanes(dst, a, b, n, f)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn zip_elements_avx2<T: Copy, F: Fn(T, T) -> T>(
+    dst: *mut T,
+    a: *mut T,
+    b: *mut T,
+    n: usize,
+    f: F,
+) {
+    zip_lanes(dst, a, b, n, f)
+}
+
+/// Eight elements per step, each block read before it is written so the
+/// destination may alias a source, then the remainder one at a time.
+#[inline(always)]
+unsafe fn zip_lanes<T: Copy, F: Fn(T, T) -> T>(dst: *mut T, a: *mut T, b: *mut T, n: usize, f: F) {
+    const LANES: usize = 8;
+    let mut i = 0;
+    while i + LANES <= n {
+        let x = a.add(i).cast::<[T; LANES]>().read_unaligned();
+        let y = b.add(i).cast::<[T; LANES]>().read_unaligned();
+        let out: [T; LANES] = std::array::from_fn(|j| f(x[j], y[j]));
+        dst.add(i).cast::<[T; LANES]>().write_unaligned(out);
+        i += LANES;
+    }
+    for i in i..n {
+        let out = f(a.add(i).read_unaligned(), b.add(i).read_unaligned());
+        dst.add(i).write_unaligned(out);
+    }
+}
+
+/// `cl_mem_scan` flag: report every match instead of only the first.
+pub(crate) const SCAN_ALL: i64 = 1;
+/// `cl_mem_scan` flag: with `SCAN_ALL`, resume after each match instead of
+/// one byte past its start.
+pub(crate) const SCAN_NON_OVERLAPPING: i64 = 2;
+/// `cl_mem_scan` flag: the pattern is followed by a mask of the same length,
+/// and a byte matches when it equals the pattern byte under the mask (0xFF
+/// compares the whole byte, 0x00 matches anything).
+pub(crate) const SCAN_MASKED: i64 = 4;
+
+/// Search `hay_len` bytes at `hay_off` for the `pat_len`-byte pattern at
+/// `pat_off` (under the mask after it, with `SCAN_MASKED`).
+///
+/// By default writes the i64 offset of the first match (or -1) at `out_off`
+/// and returns it. With `SCAN_ALL` in `flags`, writes a u32 match count at
+/// `out_off` followed by up to `flags >> 32` u32 offsets, and returns the
+/// total number of matches (which may exceed the capacity). Returns -2 on bad
+/// arguments.
+pub(crate) unsafe extern "C" fn cl_mem_scan(
+    ptr: *mut u8,
+    hay_off: i64,
+    hay_len: i64,
+    pat_off: i64,
+    pat_len: i64,
+    out_off: i64,
+    flags: i64,
+) -> i64 {
+    if ptr.is_null() || hay_off < 0 || hay_len < 0 || pat_off < 0 || pat_len <= 0 || out_off < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -2;
+    }
+    let hay = std::slice::from_raw_parts(ptr.add(hay_off as usize), hay_len as usize);
+    let pat = std::slice::from_raw_parts(ptr.add(pat_off as usize), pat_len as usize);
+    let mask = (flags & SCAN_MASKED != 0)
+        .then(|| std::slice::from_raw_parts(pat.as_ptr().add(pat.len()), pat.len()));
+    let find = |from| match mask {
+        Some(mask) => find_masked_from(hay, pat, mask, from),
+        None => find_from(hay, pat, from),
+    };
+    let out = ptr.add(out_off as usize);
+
+    if flags & SCAN_ALL == 0 {
+        let found = find(0).map_or(-1, |i| i as i64);
+        std::ptr::write_unaligned(out as *mut i64, found);
+        status::ok(0);
+        return found;
+    }
+
+    let cap = (flags as u64 >> 32) as usize;
+    let step_past = flags & SCAN_NON_OVERLAPPING != 0;
+    let mut count = 0usize;
+    let mut from = 0;
+    while let Some(i) = find(from) {
+        if count < cap {
+            std::ptr::write_unaligned((out.add(4) as *mut u32).add(count), i as u32);
+        }
+        count += 1;
+        from = if step_past { i + pat.len() } else { i + 1 };
+    }
+    std::ptr::write_unaligned(out as *mut u32, count as u32);
+    status::ok(count as u64);
+    count as i64
+}
+
+fn find_from(hay: &[u8], pat: &[u8], from: usize) -> Option<usize> {
+    if from >= hay.len() || hay.len() - from < pat.len() {
+        return None;
+    }
+    let last = hay.len() - pat.len();
+    let mut i = from;
+    while i <= last {
+        // Skip ahead on the first byte before comparing the full window.
+        i += hay[i..=last].iter().position(|&b| b == pat[0])?;
+        if &hay[i..i + pat.len()] == pat {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Like `find_from`, comparing each byte under `mask`. The first exact byte
+/// of the pattern (else its first masked one) is the prefilter; a pattern
+/// that is all wildcards matches wherever it fits.
+fn find_masked_from(hay: &[u8], pat: &[u8], mask: &[u8], from: usize) -> Option<usize> {
+    if from >= hay.len() || hay.len() - from < pat.len() {
+        return None;
+    }
+    let exact = mask.iter().position(|&m| m == 0xFF);
+    let Some(at) = exact.or_else(|| mask.iter().position(|&m| m != 0)) else {
+        return Some(from);
+    };
+    let (m, want) = (mask[at], pat[at] & mask[at]);
+    let last = hay.len() - pat.len();
+    let mut i = from;
+    while i <= last {
+        let window = &hay[i + at..=last + at];
+        i += window.iter().position(|&b| b & m == want)?;
+        if (0..pat.len()).all(|j| hay[i + j] & mask[j] == pat[j] & mask[j]) {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The u64 sequence word at `seq_off`, or `None` (with the status set) for
+/// bad arguments.
+unsafe fn seqlock<'a>(
+    ptr: *mut u8,
+    seq_off: i64,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+) -> Option<&'a AtomicU64> {
+    if ptr.is_null() || seq_off < 0 || seq_off % 8 != 0 || dst_off < 0 || src_off < 0 || size < 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return None;
+    }
+    Some(&*(ptr.add(seq_off as usize) as *const AtomicU64))
+}
+
+/// Copy `size` bytes from `src_off` to `dst_off` as a seqlock writer on the
+/// u64 sequence word at `seq_off` (8-byte aligned, starting even): the word
+/// is odd while the copy is in progress and two higher once it is done.
+/// Concurrent publishers on one word take turns. Pair with
+/// `cl_mem_snapshot`, so a reader never sees half of one record and half of
+/// the next. Returns `size`, or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_mem_publish(
+    ptr: *mut u8,
+    seq_off: i64,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+) -> i64 {
+    let Some(seq) = seqlock(ptr, seq_off, dst_off, src_off, size) else {
+        return -1;
+    };
+    let mut at = seq.load(Ordering::Relaxed);
+    loop {
+        if at % 2 == 1 {
+            std::hint::spin_loop();
+            at = seq.load(Ordering::Relaxed);
+            continue;
+        }
+        match seq.compare_exchange_weak(at, at + 1, Ordering::Acquire, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(now) => at = now,
+        }
+    }
+    // Keep the copy's stores after the odd sequence number.
+    fence(Ordering::Release);
+    std::ptr::copy(
+        ptr.add(src_off as usize),
+        ptr.add(dst_off as usize),
+        size as usize,
+    );
+    seq.store(at + 2, Ordering::Release);
+    status::ok(size as u64);
+    size
+}
+
+/// Copy `size` bytes from `src_off` to `dst_off` as a seqlock reader on the
+/// sequence word at `seq_off` that `cl_mem_publish` writers bump: the copy
+/// is retried until the word was even and unchanged across it, so the
+/// destination holds exactly one published version. Returns `size` (the
+/// status payload holds the retries), -1 on bad arguments, or -2 if the
+/// execution is cancelled while a writer holds the word.
+pub(crate) unsafe extern "C" fn cl_mem_snapshot(
+    ptr: *mut u8,
+    seq_off: i64,
+    dst_off: i64,
+    src_off: i64,
+    size: i64,
+) -> i64 {
+    let Some(seq) = seqlock(ptr, seq_off, dst_off, src_off, size) else {
+        return -1;
+    };
+    let (src, dst) = (ptr.add(src_off as usize), ptr.add(dst_off as usize));
+    let mut retries = 0u64;
+    loop {
+        let before = seq.load(Ordering::Acquire);
+        if before % 2 == 0 {
+            std::ptr::copy(src, dst, size as usize);
+            // Keep the copy's loads before the second sequence read.
+            fence(Ordering::Acquire);
+            if seq.load(Ordering::Relaxed) == before {
+                break;
+            }
+        }
+        retries += 1;
+        if retries.is_multiple_of(1024) && cancel::is_cancelled() {
+            status::set(FAILED, retries);
+            return -2;
+        }
+        std::hint::spin_loop();
+    }
+    status::ok(retries);
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_1mb_with_4_byte_pattern() {
+        let mut mem = vec![0u8; (1 << 20) + 64];
+        let n = unsafe { cl_mem_fill(mem.as_mut_ptr(), 32, 1 << 20, 0xDEADBEEF, 4) };
+        assert_eq!(n, 1 << 20);
+        let pat = 0xDEADBEEFu32.to_le_bytes();
+        assert_eq!(&mem[32..36], &pat);
+        assert_eq!(&mem[(1 << 20) + 28..(1 << 20) + 32], &pat);
+        assert!(mem[..32].iter().all(|&b| b == 0));
+        assert!(mem[(1 << 20) + 32..].iter().all(|&b| b == 0));
+        assert!(mem[32..(1 << 20) + 32].chunks_exact(4).all(|c| c == pat));
+    }
+
+    #[test]
+    fn copy_splits_large_ranges_across_workers() {
+        let len = 8 << 20;
+        let mut mem: Vec<u8> = (0..2 * len + 16).map(|i| (i % 253) as u8).collect();
+        let expected = mem[..len].to_vec();
+        let n = unsafe { cl_mem_copy(mem.as_mut_ptr(), len as i64 + 16, 0, len as i64, 4) };
+        assert_eq!(n, len as i64);
+        assert_eq!(&mem[len + 16..], &expected[..]);
+        assert_eq!(&mem[..len], &expected[..], "source untouched");
+    }
+
+    #[test]
+    fn streamed_copy_handles_unaligned_edges_and_chunk_boundaries() {
+        // An odd length at odd offsets, so every chunk has a plain head and
+        // tail around its streamed blocks.
+        let len = COPY_STREAM_MIN + 45;
+        for workers in [1, 3] {
+            let mut mem: Vec<u8> = (0..2 * len + 64).map(|i| (i % 251) as u8).collect();
+            let expected = mem[3..3 + len].to_vec();
+            let dst = len as i64 + 17;
+            let n = unsafe { cl_mem_copy(mem.as_mut_ptr(), dst, 3, len as i64, workers) };
+            assert_eq!(n, len as i64);
+            assert_eq!(
+                &mem[len + 17..2 * len + 17],
+                &expected[..],
+                "{workers} workers"
+            );
+            assert_eq!(
+                mem[2 * len + 17],
+                ((2 * len + 17) % 251) as u8,
+                "past the end"
+            );
+        }
+    }
+
+    #[test]
+    fn stream_threshold_is_configurable() {
+        let streamed = || STREAMED_COPIES.with(Cell::get);
+        let mut mem: Vec<u8> = (0..4096).map(|i| (i % 251) as u8).collect();
+        let expected = mem[3..1503].to_vec();
+        let before = streamed();
+        set_copy_stream_min(Some(1024));
+        unsafe {
+            assert_eq!(cl_mem_copy(mem.as_mut_ptr(), 2048, 0, 1000, 1), 1000);
+            assert_eq!(streamed(), before);
+            assert_eq!(cl_mem_copy(mem.as_mut_ptr(), 2053, 3, 1500, 1), 1500);
+            assert_eq!(streamed(), before + 1);
+        }
+        assert_eq!(&mem[2053..3553], &expected[..]);
+        set_copy_stream_min(None);
+        assert_eq!(copy_stream_min(), COPY_STREAM_MIN);
+        unsafe { assert_eq!(cl_mem_copy(mem.as_mut_ptr(), 2053, 3, 1500, 1), 1500) };
+        assert_eq!(streamed(), before + 1);
+    }
+
+    #[test]
+    fn large_overlapping_copy_behaves_like_memmove() {
+        let len = COPY_STREAM_MIN;
+        let mut mem: Vec<u8> = (0..len + 4096).map(|i| (i % 253) as u8).collect();
+        let expected = mem[..len].to_vec();
+        let n = unsafe { cl_mem_copy(mem.as_mut_ptr(), 4096, 0, len as i64, 4) };
+        assert_eq!(n, len as i64);
+        assert_eq!(&mem[4096..], &expected[..]);
+    }
+
+    #[test]
+    fn copy_overlapping_ranges_behaves_like_memmove() {
+        let mut mem: Vec<u8> = (0..64u8).collect();
+        let n = unsafe { cl_mem_copy(mem.as_mut_ptr(), 8, 0, 32, 4) };
+        assert_eq!(n, 32);
+        assert_eq!(&mem[8..40], &(0..32u8).collect::<Vec<_>>()[..]);
+        unsafe {
+            assert_eq!(cl_mem_copy(mem.as_mut_ptr(), 0, 0, 8, 0), -1);
+            assert_eq!(cl_mem_copy(mem.as_mut_ptr(), -1, 0, 8, 1), -1);
+        }
+    }
+
+    #[test]
+    fn fill_single_byte_zeroes_region() {
+        let mut mem = vec![0xFFu8; 100];
+        let n = unsafe { cl_mem_fill(mem.as_mut_ptr(), 10, 80, 0, 1) };
+        assert_eq!(n, 80);
+        assert!(mem[10..90].iter().all(|&b| b == 0));
+        assert_eq!(mem[9], 0xFF);
+        assert_eq!(mem[90], 0xFF);
+    }
+
+    #[test]
+    fn fill_truncates_trailing_partial_pattern() {
+        let mut mem = vec![0u8; 16];
+        let n = unsafe { cl_mem_fill(mem.as_mut_ptr(), 0, 11, 0x0807060504030201, 8) };
+        assert_eq!(n, 11);
+        assert_eq!(&mem[..11], &[1, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3]);
+        assert_eq!(&mem[11..], &[0; 5]);
+    }
+
+    #[test]
+    fn fill_shorter_than_pattern() {
+        let mut mem = vec![0u8; 4];
+        let n = unsafe { cl_mem_fill(mem.as_mut_ptr(), 0, 1, 0xAABB, 2) };
+        assert_eq!(n, 1);
+        assert_eq!(mem, [0xBB, 0, 0, 0]);
+    }
+
+    #[test]
+    fn fill_zero_size_is_noop() {
+        let mut mem = vec![7u8; 8];
+        assert_eq!(unsafe { cl_mem_fill(mem.as_mut_ptr(), 0, 0, 1, 4) }, 0);
+        assert_eq!(mem, [7; 8]);
+    }
+
+    #[test]
+    fn fill_rejects_invalid() {
+        let mut mem = vec![0u8; 8];
+        unsafe {
+            assert_eq!(cl_mem_fill(mem.as_mut_ptr(), 0, 8, 1, 3), -1);
+            assert_eq!(cl_mem_fill(mem.as_mut_ptr(), 0, -1, 1, 1), -1);
+            assert_eq!(cl_mem_fill(mem.as_mut_ptr(), -1, 8, 1, 1), -1);
+            assert_eq!(cl_mem_fill(std::ptr::null_mut(), 0, 8, 1, 1), -1);
+        }
+    }
+
+    unsafe fn compare(mem: &[u8], a_off: usize, b_off: usize, size: usize) -> i64 {
+        cl_mem_compare(mem.as_ptr(), a_off as i64, b_off as i64, size as i64)
+    }
+
+    #[test]
+    fn compare_identical_buffers() {
+        let mut mem: Vec<u8> = (0..2048).map(|i| (i % 1024 * 7) as u8).collect();
+        mem[1000..1024].fill(0xEE);
+        assert_eq!(unsafe { compare(&mem, 0, 1024, 1000) }, -1);
+    }
+
+    #[test]
+    fn compare_difference_at_first_byte() {
+        let mut mem = vec![0u8; 256];
+        mem[128] = 1;
+        assert_eq!(unsafe { compare(&mem, 0, 128, 100) }, 0);
+    }
+
+    #[test]
+    fn compare_difference_at_last_byte() {
+        let mut mem = vec![0u8; 2048];
+        mem[1024 + 999] = 0xFF;
+        assert_eq!(unsafe { compare(&mem, 0, 1024, 1000) }, 999);
+    }
+
+    #[test]
+    fn compare_difference_inside_later_block() {
+        let mut mem = vec![0u8; 512];
+        mem[256 + 70] = 3;
+        mem[256 + 90] = 4;
+        assert_eq!(unsafe { compare(&mem, 0, 256, 200) }, 70);
+    }
+
+    #[test]
+    fn compare_zero_length_is_equal() {
+        let mem = [1u8, 2];
+        assert_eq!(unsafe { compare(&mem, 0, 1, 0) }, -1);
+    }
+
+    #[test]
+    fn compare_rejects_invalid() {
+        let mem = [0u8; 8];
+        unsafe {
+            assert_eq!(cl_mem_compare(mem.as_ptr(), -1, 0, 4), -2);
+            assert_eq!(cl_mem_compare(mem.as_ptr(), 0, 0, -4), -2);
+            assert_eq!(cl_mem_compare(std::ptr::null(), 0, 0, 4), -2);
+        }
+    }
+
+    #[test]
+    fn byteswap_arrays_of_each_width() {
+        // 67 elements: several SIMD blocks plus a scalar tail.
+        let words: Vec<u64> = (0..67u64)
+            .map(|i| i.wrapping_mul(0x0123_4567_89AB_CDEF))
+            .collect();
+        let src: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        for width in [2usize, 4, 8] {
+            let size = (67 * width) as i64;
+            let mut mem = src.clone();
+            mem.resize(src.len() * 2, 0);
+            let dst = src.len() as i64;
+            let n = unsafe { cl_mem_byteswap(mem.as_mut_ptr(), dst, 0, width as i64, size) };
+            assert_eq!(n, size);
+            let out = &mem[src.len()..src.len() + size as usize];
+            for (i, (a, b)) in src
+                .chunks(width)
+                .zip(out.chunks(width))
+                .take(67)
+                .enumerate()
+            {
+                let expected: Vec<u8> = match width {
+                    2 => u16::from_be_bytes(a.try_into().unwrap())
+                        .to_le_bytes()
+                        .to_vec(),
+                    4 => u32::from_be_bytes(a.try_into().unwrap())
+                        .to_le_bytes()
+                        .to_vec(),
+                    _ => u64::from_be_bytes(a.try_into().unwrap())
+                        .to_le_bytes()
+                        .to_vec(),
+                };
+                assert_eq!(b, &expected[..], "width {width}, element {i}");
+            }
+            assert_eq!(&mem[..src.len()], &src[..], "source untouched");
+        }
+    }
+
+    #[test]
+    fn byteswap_in_place_round_trips() {
+        let original: Vec<u8> = (0..40u8).collect();
+        let mut mem = original.clone();
+        unsafe {
+            assert_eq!(cl_mem_byteswap(mem.as_mut_ptr(), 0, 0, 4, 40), 40);
+            assert_eq!(&mem[..4], &[3, 2, 1, 0]);
+            assert_eq!(cl_mem_byteswap(mem.as_mut_ptr(), 0, 0, 4, 40), 40);
+        }
+        assert_eq!(mem, original);
+    }
+
+    #[test]
+    fn byteswap_rejects_partial_elements() {
+        let mut mem = [0u8; 32];
+        unsafe {
+            assert_eq!(cl_mem_byteswap(mem.as_mut_ptr(), 0, 0, 4, 10), -1);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+            assert_eq!(cl_mem_byteswap(mem.as_mut_ptr(), 0, 0, 3, 9), -1);
+            assert_eq!(cl_mem_byteswap(mem.as_mut_ptr(), 0, 0, 8, 0), 0);
+        }
+    }
+
+    #[test]
+    fn byteswap_vector_path_matches_scalar() {
+        // Pseudo-random bytes led by float edge cases (negative zero, NaN,
+        // denormals), at lengths that leave a partial 16-byte block.
+        let specials = [-0.0f32, f32::NAN, f32::MIN_POSITIVE / 2.0, -1e-45];
+        let mut data: Vec<u8> = specials.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let mut x = 0x9E37_79B9_7F4A_7C15u64;
+        while data.len() < 1000 {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            data.push(x as u8);
+        }
+        for width in [2, 4, 8] {
+            for len in [16, 40, 1000 - 1000 % width] {
+                let mut fast = data[..len].to_vec();
+                let mut scalar = fast.clone();
+                swap_elements(&mut fast, width);
+                swap_elements_scalar(&mut scalar, width);
+                assert_eq!(fast, scalar, "width {width}, len {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn array_op_matches_scalar_reference() {
+        // Indexed by op - ARRAY_ADD_F32 (or - ARRAY_ADD_I32).
+        let float_ops: [fn(f32, f32) -> f32; 3] = [|x, y| x + y, |x, y| x - y, |x, y| x * y];
+        let int_ops: [fn(i32, i32) -> i32; 3] =
+            [i32::wrapping_add, i32::wrapping_sub, i32::wrapping_mul];
+        for n in [0usize, 1, 3, 4, 1000, 100_003] {
+            let len = n * 4;
+            // Arrays at 0, len and 2 * len, offset by 1 so none is aligned.
+            let mut mem = vec![0u8; 1 + 3 * len];
+            let a: Vec<i32> = (0..n as i32).map(|i| i.wrapping_mul(-7919)).collect();
+            let b: Vec<i32> = (0..n as i32).map(|i| (i % 1000) * 40503 + 1).collect();
+            for (f32s, base) in [(false, ARRAY_ADD_I32), (true, ARRAY_ADD_F32)] {
+                for k in 0..3 {
+                    for (i, (&x, &y)) in a.iter().zip(&b).enumerate() {
+                        let (x, y) = if f32s {
+                            ((x as f32 / 3.0).to_bits(), (y as f32).to_bits())
+                        } else {
+                            (x as u32, y as u32)
+                        };
+                        mem[1 + i * 4..][..4].copy_from_slice(&x.to_le_bytes());
+                        mem[1 + len + i * 4..][..4].copy_from_slice(&y.to_le_bytes());
+                    }
+                    let (a_off, b_off, dst_off) = (1, 1 + len as i64, 1 + 2 * len as i64);
+                    let size = len as i64;
+                    let code = base + k as i64;
+                    let r = unsafe {
+                        cl_mem_array_op(mem.as_mut_ptr(), dst_off, a_off, b_off, size, code)
+                    };
+                    assert_eq!(r, size, "n {n}, op {k}");
+                    let word =
+                        |off: usize| u32::from_le_bytes(mem[off..off + 4].try_into().unwrap());
+                    for i in 0..n {
+                        let (x, y, got) = (
+                            word(1 + i * 4),
+                            word(1 + len + i * 4),
+                            word(1 + 2 * len + i * 4),
+                        );
+                        let expected = if f32s {
+                            float_ops[k](f32::from_bits(x), f32::from_bits(y)).to_bits()
+                        } else {
+                            int_ops[k](x as i32, y as i32) as u32
+                        };
+                        assert_eq!(got, expected, "n {n}, op {k}, element {i}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn array_op_aliasing_and_bad_arguments() {
+        let mut mem = vec![0u8; 256];
+        for i in 0..40 {
+            mem[i * 4..i * 4 + 4].copy_from_slice(&(i as i32).to_le_bytes());
+        }
+        let p = mem.as_mut_ptr();
+        unsafe {
+            // dst == a: a[i] += b[i] in place, with b at 80.
+            assert_eq!(cl_mem_array_op(p, 0, 0, 80, 80, ARRAY_ADD_I32), 80);
+            assert_eq!(&mem[4..8], &(1 + 21i32).to_le_bytes());
+            // dst partially overlapping b is rejected and writes nothing.
+            let before = mem.clone();
+            assert_eq!(cl_mem_array_op(p, 84, 0, 80, 80, ARRAY_ADD_I32), -1);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+            assert_eq!(mem, before);
+            assert_eq!(cl_mem_array_op(p, 160, 0, 80, 6, ARRAY_ADD_I32), -1);
+            assert_eq!(cl_mem_array_op(p, 160, 0, 80, 8, 0), -1);
+            assert_eq!(cl_mem_array_op(p, 160, 0, 80, 8, ARRAY_MUL_I32 + 1), -1);
+        }
+    }
+
+    // Condition word at 0, destination at 8, sources at 16 and 24.
+    unsafe fn cond_write(cond: u64, mode: i64) -> (i64, u64) {
+        let mut mem = [0u64; 4];
+        mem[0] = cond;
+        mem[1] = 0xDD;
+        mem[2] = 0xAA;
+        mem[3] = 0xBB;
+        let size = 8 | (mode << COND_MODE_SHIFT);
+        let n = cl_mem_cond_write(mem.as_mut_ptr().cast(), 0, 8, 16, size);
+        (n, mem[1])
+    }
+
+    #[test]
+    fn cond_write_nonzero_bytes() {
+        unsafe {
+            assert_eq!(cond_write(1 << 56, COND_NONZERO), (8, 0xAA));
+            assert_eq!(cond_write(0, COND_NONZERO), (0, 0xDD));
+            assert_eq!(cond_write(5, 0), (8, 0xAA), "mode 0 behaves as mode 1");
+            assert_eq!(cond_write((-1i64) as u64, COND_NONZERO), (8, 0xAA));
+        }
+    }
+
+    #[test]
+    fn cond_write_select_takes_either_source() {
+        unsafe {
+            assert_eq!(cond_write(1, COND_SELECT), (8, 0xAA));
+            assert_eq!(cond_write(0, COND_SELECT), (8, 0xBB));
+        }
+    }
+
+    #[test]
+    fn cond_write_typed_conditions() {
+        unsafe {
+            assert_eq!(cond_write(7, COND_I64_POSITIVE), (8, 0xAA));
+            assert_eq!(cond_write(0, COND_I64_POSITIVE), (0, 0xDD));
+            assert_eq!(cond_write((-3i64) as u64, COND_I64_POSITIVE), (0, 0xDD));
+            assert_eq!(cond_write(2.5f64.to_bits(), COND_F64_POSITIVE), (8, 0xAA));
+            assert_eq!(
+                cond_write((-2.5f64).to_bits(), COND_F64_POSITIVE),
+                (0, 0xDD)
+            );
+            assert_eq!(
+                cond_write((-0.0f64).to_bits(), COND_F64_POSITIVE),
+                (0, 0xDD)
+            );
+            assert_eq!(cond_write(f64::NAN.to_bits(), COND_F64_POSITIVE), (0, 0xDD));
+        }
+    }
+
+    #[test]
+    fn cond_write_rejects_invalid() {
+        unsafe {
+            assert_eq!(cond_write(1, 5).0, -1);
+            assert_eq!(cl_mem_cond_write(std::ptr::null_mut(), 0, 8, 16, 8), -1);
+        }
+    }
+
+    fn scan_memory(hay: &[u8], pat: &[u8]) -> Vec<u8> {
+        let mut mem = vec![0u8; 1024];
+        mem[..hay.len()].copy_from_slice(hay);
+        mem[256..256 + pat.len()].copy_from_slice(pat);
+        mem
+    }
+
+    unsafe fn scan(mem: &mut [u8], hay_len: usize, pat_len: usize, flags: i64) -> i64 {
+        cl_mem_scan(
+            mem.as_mut_ptr(),
+            0,
+            hay_len as i64,
+            256,
+            pat_len as i64,
+            512,
+            flags,
+        )
+    }
+
+    fn read_u32(mem: &[u8], off: usize) -> u32 {
+        u32::from_le_bytes(mem[off..off + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn scan_first_match_is_default() {
+        let hay = b"xxabcxxabc";
+        let mut mem = scan_memory(hay, b"abc");
+        assert_eq!(unsafe { scan(&mut mem, hay.len(), 3, 0) }, 2);
+        assert_eq!(i64::from_le_bytes(mem[512..520].try_into().unwrap()), 2);
+    }
+
+    #[test]
+    fn scan_no_match_writes_minus_one() {
+        let hay = b"xxxxxxxx";
+        let mut mem = scan_memory(hay, b"ab");
+        assert_eq!(unsafe { scan(&mut mem, hay.len(), 2, 0) }, -1);
+        assert_eq!(i64::from_le_bytes(mem[512..520].try_into().unwrap()), -1);
+    }
+
+    #[test]
+    fn scan_all_finds_overlapping_matches() {
+        // Matches at 0, 7, 9 (overlapping 7), 15, 20.
+        let hay = b"abaxxxxabababxxabaxxaba";
+        let mut mem = scan_memory(hay, b"aba");
+        let flags = SCAN_ALL | (16 << 32);
+        assert_eq!(unsafe { scan(&mut mem, hay.len(), 3, flags) }, 5);
+        assert_eq!(read_u32(&mem, 512), 5);
+        let offsets: Vec<u32> = (0..5).map(|i| read_u32(&mem, 516 + 4 * i)).collect();
+        assert_eq!(offsets, [0, 7, 9, 15, 20]);
+    }
+
+    #[test]
+    fn scan_all_non_overlapping_skips_past_match() {
+        let hay = b"abaxxxxabababxxabaxxaba";
+        let mut mem = scan_memory(hay, b"aba");
+        let flags = SCAN_ALL | SCAN_NON_OVERLAPPING | (16 << 32);
+        assert_eq!(unsafe { scan(&mut mem, hay.len(), 3, flags) }, 4);
+        let offsets: Vec<u32> = (0..4).map(|i| read_u32(&mem, 516 + 4 * i)).collect();
+        assert_eq!(offsets, [0, 7, 15, 20]);
+    }
+
+    #[test]
+    fn scan_all_respects_capacity() {
+        let hay = b"aaaaaa";
+        let mut mem = scan_memory(hay, b"a");
+        let flags = SCAN_ALL | (2 << 32);
+        assert_eq!(unsafe { scan(&mut mem, hay.len(), 1, flags) }, 6);
+        assert_eq!(read_u32(&mem, 512), 6);
+        assert_eq!(read_u32(&mem, 516), 0);
+        assert_eq!(read_u32(&mem, 520), 1);
+        assert_eq!(read_u32(&mem, 524), 0);
+    }
+
+    #[test]
+    fn scan_masked_wildcards_and_bit_masks() {
+        // ELF-style: "\x7fELF" ?? ?? 02 at 3; the decoy at 14 has 01.
+        let hay = b"abc\x7fELF\x01\x01\x02zzzz\x7fELF\x02\x01\x01";
+        let pat_mask = b"\x7fELF\0\0\x02\xff\xff\xff\xff\0\0\xff";
+        let mut mem = scan_memory(hay, pat_mask);
+        unsafe {
+            assert_eq!(scan(&mut mem, hay.len(), 7, SCAN_MASKED), 3);
+            // Fully wildcarded leading byte: "?ELF" matches at 3 and 14.
+            let mut mem = scan_memory(hay, b"\0ELF\0\xff\xff\xff");
+            let flags = SCAN_ALL | SCAN_MASKED | (4 << 32);
+            assert_eq!(scan(&mut mem, hay.len(), 4, flags), 2);
+            assert_eq!((read_u32(&mem, 516), read_u32(&mem, 520)), (3, 14));
+            // Upper nibble in the middle: 'E' 0x4? 'F' matches "ELF" only.
+            let mut mem = scan_memory(hay, b"E\x40F\xff\xf0\xff");
+            let flags = SCAN_ALL | SCAN_MASKED | (4 << 32);
+            assert_eq!(scan(&mut mem, hay.len(), 3, flags), 2);
+            let mut mem = scan_memory(hay, b"\0\0\0");
+            assert_eq!(scan(&mut mem, hay.len(), 3, SCAN_ALL | SCAN_MASKED), 19);
+        }
+    }
+
+    #[test]
+    fn scan_masked_pattern_longer_than_remaining_haystack() {
+        let hay = b"xxxxab";
+        let mut mem = scan_memory(hay, b"ab\0\xff\xff\0");
+        unsafe {
+            // "ab?" would start at 4 but needs 3 bytes where 2 remain.
+            assert_eq!(scan(&mut mem, hay.len(), 3, SCAN_MASKED), -1);
+            assert_eq!(scan(&mut mem, 3, 3, SCAN_MASKED), -1);
+            assert_eq!(scan(&mut mem, 0, 3, SCAN_MASKED | SCAN_ALL), 0);
+        }
+    }
+
+    #[test]
+    fn scan_rejects_invalid() {
+        let mut mem = vec![0u8; 1024];
+        unsafe {
+            assert_eq!(scan(&mut mem, 8, 0, 0), -2);
+            assert_eq!(cl_mem_scan(std::ptr::null_mut(), 0, 8, 0, 1, 0, 0), -2);
+        }
+    }
+
+    #[test]
+    fn snapshot_never_sees_a_torn_record() {
+        // u64 words: the sequence at 0, the record (seven copies of a
+        // counter and their sum) at 8..16, each writer's staging record at
+        // 16 + 8 * writer, the reader's copy at 48.
+        let mut mem = vec![0u64; 64];
+        let base = mem.as_mut_ptr() as usize;
+        let writers: Vec<_> = (0..2u64)
+            .map(|w| {
+                std::thread::spawn(move || {
+                    let p = base as *mut u8;
+                    let staging = 16 + 8 * w as usize;
+                    for i in 0..20_000u64 {
+                        let value = i * 2 + w;
+                        unsafe {
+                            let words = (p as *mut u64).add(staging);
+                            for k in 0..7 {
+                                *words.add(k) = value;
+                            }
+                            *words.add(7) = value * 7;
+                            let src = staging as i64 * 8;
+                            assert_eq!(cl_mem_publish(p, 0, 64, src, 64), 64);
+                        }
+                    }
+                })
+            })
+            .collect();
+        let p = base as *mut u8;
+        for _ in 0..20_000 {
+            unsafe {
+                assert_eq!(cl_mem_snapshot(p, 0, 384, 64, 64), 64);
+                let copy = std::slice::from_raw_parts((p as *const u64).add(48), 8);
+                assert!(copy[..7].iter().all(|&v| v == copy[0]), "torn: {copy:?}");
+                assert_eq!(copy[7], copy[0] * 7);
+            }
+        }
+        for w in writers {
+            w.join().unwrap();
+        }
+        assert_eq!(mem[0], 2 * 2 * 20_000, "each publish bumps the word by 2");
+        unsafe {
+            assert_eq!(cl_mem_publish(p, 4, 64, 128, 64), -1, "misaligned sequence");
+            assert_eq!(cl_mem_snapshot(p, 0, 384, 64, -1), -1);
+        }
+    }
+}
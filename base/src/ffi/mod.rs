@@ -1,13 +1,45 @@
+pub(crate) mod arena;
+pub(crate) mod cancel;
+pub(crate) mod checkpoint;
+pub(crate) mod checksum;
+pub(crate) mod clock;
+#[cfg(feature = "cuda")]
 pub(crate) mod cuda;
 pub(crate) mod file;
+pub(crate) mod file_atomic;
+pub(crate) mod file_handle;
+pub(crate) mod file_stream;
+#[cfg(feature = "gpu")]
+pub(crate) mod gpu_cpu;
+pub(crate) mod handles;
 pub(crate) mod ht;
+#[cfg(feature = "net")]
+pub(crate) mod http;
+pub(crate) mod io_log;
+#[cfg(feature = "lmdb")]
 pub(crate) mod lmdb;
+pub(crate) mod lz4;
+pub(crate) mod math;
+pub(crate) mod mem;
+pub(crate) mod nested;
+#[cfg(feature = "net")]
 pub(crate) mod net;
+pub(crate) mod queue;
+pub(crate) mod random;
+pub(crate) mod retry;
+pub(crate) mod sandbox;
+pub(crate) mod status;
 pub(crate) mod stdio;
 pub(crate) mod thread;
+pub(crate) mod trace;
+#[cfg(feature = "gpu")]
 pub(crate) mod wgpu;
+#[cfg(feature = "gpu")]
 pub(crate) mod window;
 
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
+
 pub(super) unsafe fn read_ctx_ref<T>(ctx_ptr: *const T) -> Option<&'static T> {
     ctx_ptr.as_ref()
 }
@@ -35,11 +67,7 @@ pub(super) unsafe fn clear_ctx_slot<T>(slot_ptr: *mut *mut T) -> *mut T {
     raw
 }
 
-pub(super) unsafe fn read_cstr(ptr: *mut u8, off: usize) -> String {
-    let start = ptr.add(off);
-    read_cstr_ptr(start)
-}
-
+#[cfg(any(feature = "cuda", feature = "lmdb", feature = "net"))]
 pub(super) unsafe fn read_cstr_ptr(start: *const u8) -> String {
     let mut len = 0;
     while *start.add(len) != 0 {
@@ -48,6 +76,28 @@ pub(super) unsafe fn read_cstr_ptr(start: *const u8) -> String {
     String::from_utf8_lossy(std::slice::from_raw_parts(start, len)).into_owned()
 }
 
+pub(super) unsafe fn read_path(ptr: *mut u8, off: usize) -> PathBuf {
+    read_path_ptr(ptr.add(off))
+}
+
+/// Read a NUL-terminated path as raw bytes. On Unix the bytes become the path
+/// unchanged, so names that are not UTF-8 still open. Windows paths are UTF-16:
+/// the bytes are decoded as UTF-8 (lossless for valid UTF-8; invalid sequences
+/// become U+FFFD and name a different file) and `/` becomes `\`. Paths beyond
+/// `MAX_PATH` need no prefix, std adds `\\?\` when opening them.
+pub(super) unsafe fn read_path_ptr(start: *const u8) -> PathBuf {
+    let bytes = CStr::from_ptr(start as *const c_char).to_bytes();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(bytes).replace('/', "\\"))
+    }
+}
+
 // Stateless libm wrappers — exposed as FFI for CLIF code that needs trig/pow.
 
 pub(crate) unsafe extern "C" fn cl_sinf(x: f32) -> f32 {
@@ -0,0 +1,162 @@
+//! Running an algorithm stored in memory as a sub-algorithm of the current
+//! one. An outer algorithm can carry a library of small inner ones ("parse
+//! header", "decode block") as `Algorithm::to_bytes` blobs and pick which to
+//! run at execution time instead of calling a fixed function.
+//!
+//! The child is one of the artifact's own compiled functions, so it needs no
+//! extra compilation; what the blob adds is the entry point and the symbols
+//! written before it runs. The child sees a window of the parent's memory as
+//! its whole memory: offset 0 in the child is `window_off` in the parent, and
+//! its symbols must fit inside the window. It runs to completion on the
+//! calling thread, under the parent's cancellation, clock, seed and sandbox;
+//! threads and pools it starts work as they do for the parent.
+
+use std::cell::Cell;
+use std::sync::atomic::Ordering;
+
+use base_types::status::{FAILED, INVALID_ARGUMENT};
+use base_types::Algorithm;
+use tracing::error;
+
+use super::{cancel, status, thread};
+use crate::jit::THREAD_COMPILED_FNS;
+
+/// A child that fails makes the parent's execution stop as if cancelled,
+/// instead of only reporting the failure to the caller.
+pub(crate) const NESTED_STRICT: i64 = 1;
+/// Nesting allowed when `flags` does not set a limit.
+pub(crate) const DEFAULT_MAX_DEPTH: u32 = 4;
+
+thread_local! {
+    /// Sub-algorithms running on this thread.
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Restores the depth when the child returns.
+struct Nesting;
+
+impl Drop for Nesting {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Why the child could not run, as a status code and a log message.
+fn prepare(
+    memory: *mut u8,
+    algo_off: i64,
+    algo_len: i64,
+    window_off: i64,
+    window_len: i64,
+    max_depth: u32,
+) -> Result<(unsafe extern "C" fn(*mut u8), *mut u8), (u32, String)> {
+    let invalid = |msg: &str| (INVALID_ARGUMENT, msg.to_string());
+    if memory.is_null() || algo_off < 0 || algo_len <= 0 || window_off < 0 || window_len <= 0 {
+        return Err(invalid("negative offset or empty range"));
+    }
+    let depth = DEPTH.with(Cell::get);
+    if depth >= max_depth {
+        return Err((
+            FAILED,
+            format!("nesting depth limit of {max_depth} reached"),
+        ));
+    }
+    let blob =
+        unsafe { std::slice::from_raw_parts(memory.add(algo_off as usize), algo_len as usize) };
+    let algorithm = Algorithm::from_bytes(blob)
+        .map_err(|e| invalid(&format!("invalid algorithm blob: {e}")))?;
+    let func = THREAD_COMPILED_FNS
+        .with(|cell| {
+            cell.borrow()
+                .as_ref()?
+                .get(algorithm.fn_idx as usize)
+                .copied()
+        })
+        .ok_or_else(|| invalid(&format!("no function u0:{}", algorithm.fn_idx)))?;
+    let window = memory.wrapping_add(window_off as usize);
+    let child = unsafe { std::slice::from_raw_parts_mut(window, window_len as usize) };
+    algorithm
+        .write_symbols(child)
+        .map_err(|e| invalid(&format!("symbols do not fit the window: {e}")))?;
+    Ok((func, window))
+}
+
+/// Run the algorithm serialized at `algo_off..algo_off + algo_len` with
+/// `window_off..window_off + window_len` as its memory. `flags` holds
+/// `NESTED_STRICT` in bit 0 and the nesting limit in bits 8..16, 0 meaning
+/// `DEFAULT_MAX_DEPTH`; the limit counts sub-algorithms running on this
+/// thread, this one included. Returns 0 once the child has returned, or -1
+/// with the status set (`INVALID_ARGUMENT` for a bad blob, function index or
+/// window, `FAILED` past the limit) without running it.
+pub(crate) unsafe extern "C" fn cl_execute_nested(
+    memory: *mut u8,
+    algo_off: i64,
+    algo_len: i64,
+    window_off: i64,
+    window_len: i64,
+    flags: i64,
+) -> i64 {
+    status::begin();
+    let max_depth = match (flags >> 8) as u8 {
+        0 => DEFAULT_MAX_DEPTH,
+        n => n as u32,
+    };
+    match prepare(
+        memory, algo_off, algo_len, window_off, window_len, max_depth,
+    ) {
+        Ok((func, window)) => {
+            DEPTH.with(|depth| depth.set(depth.get() + 1));
+            let _nesting = Nesting;
+            func(window);
+            status::ok(0);
+            0
+        }
+        Err((code, msg)) => {
+            error!(algo_off, window_off, %msg, "cl_execute_nested failed");
+            if flags & NESTED_STRICT != 0 {
+                if let Some(token) = cancel::current_token() {
+                    token.store(true, Ordering::Release);
+                    thread::wake_all_waiters();
+                }
+            }
+            status::set(code, 0);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Child memory: the blob of its own algorithm at 64, a call counter at 8.
+    unsafe extern "C" fn recurse(memory: *mut u8) {
+        *memory.add(8) += 1;
+        let len = *memory.add(16) as i64;
+        if cl_execute_nested(memory, 64, len, 0, 256, 0) < 0 {
+            *memory.add(24) += 1;
+        }
+    }
+
+    #[test]
+    fn nesting_stops_at_the_depth_limit() {
+        THREAD_COMPILED_FNS.with(|cell| *cell.borrow_mut() = Some(Arc::new(vec![recurse])));
+        let blob = Algorithm::new(0).to_bytes();
+        let mut mem = vec![0u8; 512];
+        // The child's window starts at 256, where a copy of the blob sits.
+        mem[256 + 16] = blob.len() as u8;
+        mem[256 + 64..256 + 64 + blob.len()].copy_from_slice(&blob);
+        let ret =
+            unsafe { cl_execute_nested(mem.as_mut_ptr(), 320, blob.len() as i64, 256, 256, 0) };
+        assert_eq!(ret, 0);
+        assert_eq!(mem[256 + 8], DEFAULT_MAX_DEPTH as u8);
+        assert_eq!(mem[256 + 24], 1, "the innermost call was refused");
+        assert_eq!(DEPTH.with(Cell::get), 0);
+
+        let ret = unsafe { cl_execute_nested(mem.as_mut_ptr(), 320, 3, 256, 256, 0) };
+        assert_eq!(ret, -1);
+        assert_eq!(base_types::status::status(status::word()), INVALID_ARGUMENT);
+        THREAD_COMPILED_FNS.with(|cell| *cell.borrow_mut() = None);
+    }
+}
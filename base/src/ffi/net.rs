@@ -1,60 +1,199 @@
 use std::collections::HashMap;
-use std::io::{Read as IoRead, Write as IoWrite};
-use std::net::{TcpListener, TcpStream};
+use std::io::{self, Read as IoRead, Write as IoWrite};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use super::{clear_ctx_slot, read_cstr_ptr, read_ctx_mut, read_ctx_ref, write_ctx_slot};
+use super::cancel::{cancelled_error, Cancellable, POLL_INTERVAL};
+use super::retry::{self, RetryPolicy};
+use super::{clear_ctx_slot, io_log, read_cstr_ptr, read_ctx_ref, status, write_ctx_slot};
+use base_types::status::{INVALID_ARGUMENT, NOT_FOUND, TOO_LARGE};
 
+/// Socket handles shared by every thread using this context. Blocking calls
+/// (accept, send, recv) run on a cloned `Arc` outside the lock, so one
+/// thread parked in `cl_net_accept` does not stall another's connect or recv.
+/// Calls on the same connection from different threads are not ordered.
+/// Accepts and receives give up once the execution is cancelled: listeners
+/// are non-blocking and connections have a `POLL_INTERVAL` read timeout.
 pub(crate) struct CraneliftNetContext {
-    connections: HashMap<u32, TcpStream>,
-    listeners: HashMap<u32, TcpListener>,
+    tables: Mutex<NetTables>,
+}
+
+struct NetTables {
+    connections: HashMap<u32, Arc<TcpStream>>,
+    listeners: HashMap<u32, Arc<TcpListener>>,
+    // Length prefixes `cl_net_recv_framed` consumed for frames it had no
+    // room for yet, by connection.
+    pending_frames: HashMap<u32, u32>,
     next_handle: u32,
+    retry: Option<RetryPolicy>,
+}
+
+/// Add the attempt count of a call made under a retry policy to the status
+/// word it has just set.
+fn report_attempts(attempts: Option<u32>) {
+    if let Some(n) = attempts {
+        status::attempts(n);
+    }
+}
+
+/// Failures worth another attempt: nothing listening yet, a timeout, a
+/// would-block, an interrupted call, or a send on a broken pipe. Anything
+/// else (a bad address, a denied connect) fails at once.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Run `op` under `policy`, retrying the failures `is_transient` accepts.
+fn with_retry<T>(
+    policy: Option<RetryPolicy>,
+    op: impl FnMut() -> io::Result<T>,
+) -> (io::Result<T>, u32) {
+    retry::with_retry(policy, is_transient, op)
+}
+
+impl CraneliftNetContext {
+    /// Run `op` under this context's retry policy. The attempt count is
+    /// `None` without a policy.
+    fn retry<T>(&self, op: impl FnMut() -> io::Result<T>) -> (io::Result<T>, Option<u32>) {
+        let policy = self.tables.lock().unwrap().retry;
+        let (result, attempts) = with_retry(policy, op);
+        (result, policy.map(|_| attempts))
+    }
+
+    fn insert_connection(&self, stream: TcpStream) -> u32 {
+        let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+        let mut t = self.tables.lock().unwrap();
+        let handle = t.next_handle;
+        t.next_handle += 1;
+        t.connections.insert(handle, Arc::new(stream));
+        handle
+    }
+
+    /// The connection behind `conn`, or `None` with a `NOT_FOUND` status.
+    fn connection(&self, conn: i64) -> Option<Arc<TcpStream>> {
+        let found = self
+            .tables
+            .lock()
+            .unwrap()
+            .connections
+            .get(&(conn as u32))
+            .cloned();
+        if found.is_none() {
+            status::set(NOT_FOUND, 0);
+        }
+        found
+    }
+
+    /// The listener behind `listener`, or `None` with a `NOT_FOUND` status.
+    fn listener(&self, listener: i64) -> Option<Arc<TcpListener>> {
+        let found = self
+            .tables
+            .lock()
+            .unwrap()
+            .listeners
+            .get(&(listener as u32))
+            .cloned();
+        if found.is_none() {
+            status::set(NOT_FOUND, 0);
+        }
+        found
+    }
 }
 
 pub(crate) unsafe extern "C" fn cl_net_init(ctx_slot_ptr: *mut *mut CraneliftNetContext) {
     let ctx = Box::new(CraneliftNetContext {
-        connections: HashMap::new(),
-        listeners: HashMap::new(),
-        next_handle: 1,
+        tables: Mutex::new(NetTables {
+            connections: HashMap::new(),
+            listeners: HashMap::new(),
+            pending_frames: HashMap::new(),
+            next_handle: 1,
+            retry: None,
+        }),
     });
     let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
 }
 
+/// Retry transient failures (see `is_transient`) of this context's connect,
+/// send, and recv calls under the policy packed at `policy_ptr` (see
+/// `retry::RETRY_POLICY`). Calls made under a policy report their attempt
+/// count in the status word (see `base_types::status::with_attempts`). A
+/// null `policy_ptr` turns retries off. Returns 0, or -1 for a null context.
+pub(crate) unsafe extern "C" fn cl_net_retry(
+    ctx_ptr: *const CraneliftNetContext,
+    policy_ptr: *const u8,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
+        return -1;
+    };
+    let policy = RetryPolicy::read(policy_ptr);
+    ctx.tables.lock().unwrap().retry = policy;
+    0
+}
+
 pub(crate) unsafe extern "C" fn cl_net_listen(
-    ctx_ptr: *mut CraneliftNetContext,
+    ctx_ptr: *const CraneliftNetContext,
     addr_ptr: *const u8,
 ) -> i64 {
-    let Some(ctx) = read_ctx_mut::<CraneliftNetContext>(ctx_ptr) else {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
         return 0;
     };
     let addr = read_cstr_ptr(addr_ptr);
-    match TcpListener::bind(&addr) {
+    let key = || format!("cl_net_listen {addr}");
+    let bind = || {
+        let listener = TcpListener::bind(&addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    };
+    io_log::call(key, 0, || match bind() {
         Ok(listener) => {
-            let handle = ctx.next_handle;
-            ctx.next_handle += 1;
-            ctx.listeners.insert(handle, listener);
+            let mut t = ctx.tables.lock().unwrap();
+            let handle = t.next_handle;
+            t.next_handle += 1;
+            t.listeners.insert(handle, Arc::new(listener));
+            status::ok(0);
             handle as i64
         }
-        Err(_) => 0,
-    }
+        Err(e) => {
+            status::io(&e);
+            0
+        }
+    })
 }
 
 pub(crate) unsafe extern "C" fn cl_net_connect(
-    ctx_ptr: *mut CraneliftNetContext,
+    ctx_ptr: *const CraneliftNetContext,
     addr_ptr: *const u8,
 ) -> i64 {
-    let Some(ctx) = read_ctx_mut::<CraneliftNetContext>(ctx_ptr) else {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
         return 0;
     };
     let addr = read_cstr_ptr(addr_ptr);
-    match TcpStream::connect(&addr) {
-        Ok(stream) => {
-            let handle = ctx.next_handle;
-            ctx.next_handle += 1;
-            ctx.connections.insert(handle, stream);
-            handle as i64
-        }
-        Err(_) => 0,
-    }
+    let key = || format!("cl_net_connect {addr}");
+    io_log::call(key, 0, || {
+        let (result, attempts) = ctx.retry(|| TcpStream::connect(&addr));
+        let handle = match result {
+            Ok(stream) => {
+                status::ok(0);
+                ctx.insert_connection(stream) as i64
+            }
+            Err(e) => {
+                status::io(&e);
+                0
+            }
+        };
+        report_attempts(attempts);
+        handle
+    })
 }
 
 pub(crate) unsafe extern "C" fn cl_net_listener_port(
@@ -64,74 +203,302 @@ pub(crate) unsafe extern "C" fn cl_net_listener_port(
     let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
         return -1;
     };
-    match ctx.listeners.get(&(listener as u32)) {
+    let key = || format!("cl_net_listener_port {listener}");
+    io_log::call(key, -1, || match ctx.listener(listener) {
         Some(l) => match l.local_addr() {
             Ok(a) => a.port() as i64,
             Err(_) => -1,
         },
         None => -1,
-    }
+    })
 }
 
 pub(crate) unsafe extern "C" fn cl_net_accept(
-    ctx_ptr: *mut CraneliftNetContext,
+    ctx_ptr: *const CraneliftNetContext,
     listener: i64,
 ) -> i64 {
-    let Some(ctx) = read_ctx_mut::<CraneliftNetContext>(ctx_ptr) else {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
         return 0;
     };
-    if let Some(l) = ctx.listeners.get(&(listener as u32)) {
-        if let Ok((stream, _)) = l.accept() {
-            let handle = ctx.next_handle;
-            ctx.next_handle += 1;
-            ctx.connections.insert(handle, stream);
-            return handle as i64;
+    let key = || format!("cl_net_accept {listener}");
+    io_log::call(key, 0, || {
+        if let Some(l) = ctx.listener(listener) {
+            match accept(&l) {
+                Ok(stream) => {
+                    status::ok(0);
+                    return ctx.insert_connection(stream) as i64;
+                }
+                Err(e) => status::io(&e),
+            }
+        }
+        0
+    })
+}
+
+/// Wait for a connection on the non-blocking `listener`, polling more slowly
+/// the longer none arrives, until the execution is cancelled.
+fn accept(listener: &TcpListener) -> io::Result<TcpStream> {
+    let mut pause = Duration::from_millis(1);
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if !super::thread::sleep_unless_cancelled(pause) {
+                    return Err(cancelled_error());
+                }
+                pause = (pause * 2).min(POLL_INTERVAL);
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
         }
     }
-    0
 }
 
 pub(crate) unsafe extern "C" fn cl_net_send(
-    ctx_ptr: *mut CraneliftNetContext,
+    ctx_ptr: *const CraneliftNetContext,
     conn: i64,
     src_ptr: *const u8,
     size: i64,
 ) -> i64 {
-    let Some(ctx) = read_ctx_mut::<CraneliftNetContext>(ctx_ptr) else {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
+        return -1;
+    };
+    let data = std::slice::from_raw_parts(src_ptr, size as usize);
+    let key = || format!("cl_net_send {conn}");
+    io_log::write(key, -1, data, || send(ctx, conn, data))
+}
+
+fn send(ctx: &CraneliftNetContext, conn: i64, data: &[u8]) -> i64 {
+    let Some(stream) = ctx.connection(conn) else {
         return -1;
     };
-    if let Some(stream) = ctx.connections.get_mut(&(conn as u32)) {
-        let data = std::slice::from_raw_parts(src_ptr, size as usize);
-        match IoWrite::write_all(stream, data) {
-            Ok(_) => return 0,
-            Err(_) => return -1,
+    // A retry resumes after the bytes already sent.
+    let mut sent = 0;
+    let (result, attempts) = ctx.retry(|| {
+        while sent < data.len() {
+            match IoWrite::write(&mut &*stream, &data[sent..]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => sent += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
         }
-    }
-    -1
+        Ok(())
+    });
+    let ret = match result {
+        Ok(_) => {
+            status::ok(data.len() as u64);
+            0
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    };
+    report_attempts(attempts);
+    ret
 }
 
 pub(crate) unsafe extern "C" fn cl_net_recv(
-    ctx_ptr: *mut CraneliftNetContext,
+    ctx_ptr: *const CraneliftNetContext,
     conn: i64,
     dst_ptr: *mut u8,
     size: i64,
 ) -> i64 {
-    let Some(ctx) = read_ctx_mut::<CraneliftNetContext>(ctx_ptr) else {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
         return -1;
     };
-    if let Some(stream) = ctx.connections.get_mut(&(conn as u32)) {
+    let key = || format!("cl_net_recv {conn} {size}");
+    io_log::read(key, -1, dst_ptr, size.max(0) as usize, || {
         let buf = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
-        let mut total = 0;
-        while total < size as usize {
-            match IoRead::read(stream, &mut buf[total..]) {
+        let n = recv(ctx, conn, buf);
+        (n, n.max(0) as usize)
+    })
+}
+
+fn recv(ctx: &CraneliftNetContext, conn: i64, buf: &mut [u8]) -> i64 {
+    let Some(stream) = ctx.connection(conn) else {
+        return -1;
+    };
+    // A retry keeps the bytes already received.
+    let mut total = 0;
+    let (result, attempts) = ctx.retry(|| {
+        while total < buf.len() {
+            match Cancellable(&*stream).read(&mut buf[total..]) {
                 Ok(0) => break,
                 Ok(n) => total += n,
-                Err(_) => return -1,
+                Err(e) => return Err(e),
             }
         }
-        return total as i64;
+        Ok(())
+    });
+    let ret = match result {
+        Ok(()) => {
+            status::ok(total as u64);
+            total as i64
+        }
+        Err(e) => {
+            status::io(&e);
+            -1
+        }
+    };
+    report_attempts(attempts);
+    ret
+}
+
+/// Receive up to `max_frames` length-prefixed frames (a u32 little-endian
+/// length, then that many bytes) into `dst_ptr`, laid out back to back as
+/// `[u32 len][payload]` within `capacity` bytes. Blocks until `max_frames`
+/// frames have arrived, the peer closes the connection between frames, or
+/// the next frame does not fit in the space left; that frame is then
+/// returned first by the next call, so do not mix this with `cl_net_recv`
+/// on one connection. A frame larger than `capacity - 4` is read and
+/// discarded, ending the call with status `TOO_LARGE` and its length as
+/// payload. Returns the number of frames written (payload: bytes used), or
+/// -1 on a bad argument, unknown handle, or a connection that breaks
+/// mid-frame.
+pub(crate) unsafe extern "C" fn cl_net_recv_framed(
+    ctx_ptr: *const CraneliftNetContext,
+    conn: i64,
+    dst_ptr: *mut u8,
+    capacity: i64,
+    max_frames: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
+        return -1;
+    };
+    if dst_ptr.is_null() || capacity < 4 || max_frames <= 0 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
     }
-    -1
+    let key = || format!("cl_net_recv_framed {conn} {capacity} {max_frames}");
+    io_log::read(key, -1, dst_ptr, capacity as usize, || {
+        let buf = std::slice::from_raw_parts_mut(dst_ptr, capacity as usize);
+        let frames = recv_frames(ctx, conn, buf, max_frames);
+        (frames, frames_len(buf, frames))
+    })
+}
+
+fn recv_frames(ctx: &CraneliftNetContext, conn: i64, buf: &mut [u8], max_frames: i64) -> i64 {
+    let Some(stream) = ctx.connection(conn) else {
+        return -1;
+    };
+    let pending = ctx
+        .tables
+        .lock()
+        .unwrap()
+        .pending_frames
+        .remove(&(conn as u32));
+    let mut reader = Cancellable(&*stream);
+    let mut used = 0;
+    let mut frames = 0;
+    let mut next_len = pending;
+    while frames < max_frames {
+        let len = match next_len.take() {
+            Some(len) => len,
+            None => match read_frame_len(&mut reader) {
+                Ok(Some(len)) => len,
+                Ok(None) => break,
+                Err(e) => {
+                    status::io(&e);
+                    return -1;
+                }
+            },
+        };
+        let need = 4 + len as usize;
+        if need > buf.len() {
+            let mut body = (&mut reader).take(len as u64);
+            match io::copy(&mut body, &mut io::sink()) {
+                Ok(n) if n == len as u64 => {}
+                Ok(_) => {
+                    status::io(&io::ErrorKind::UnexpectedEof.into());
+                    return -1;
+                }
+                Err(e) => {
+                    status::io(&e);
+                    return -1;
+                }
+            }
+            status::set(TOO_LARGE, len as u64);
+            return frames;
+        }
+        if need > buf.len() - used {
+            ctx.tables
+                .lock()
+                .unwrap()
+                .pending_frames
+                .insert(conn as u32, len);
+            break;
+        }
+        if let Err(e) = reader.read_exact(&mut buf[used + 4..used + need]) {
+            status::io(&e);
+            return -1;
+        }
+        buf[used..used + 4].copy_from_slice(&len.to_le_bytes());
+        used += need;
+        frames += 1;
+    }
+    status::ok(used as u64);
+    frames
+}
+
+/// Bytes taken by the first `frames` frames stored at the start of `buf`.
+fn frames_len(buf: &[u8], frames: i64) -> usize {
+    let mut used = 0;
+    for _ in 0..frames.max(0) {
+        used += 4 + u32::from_le_bytes(buf[used..used + 4].try_into().unwrap()) as usize;
+    }
+    used
+}
+
+/// The next frame's length prefix, or `None` if the peer closed the
+/// connection before sending one.
+fn read_frame_len(stream: &mut impl IoRead) -> io::Result<Option<u32>> {
+    let mut prefix = [0u8; 4];
+    let mut got = 0;
+    while got < 4 {
+        match stream.read(&mut prefix[got..]) {
+            Ok(0) if got == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => got += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(u32::from_le_bytes(prefix)))
+}
+
+/// Release a connection or listener handle. A closed connection is shut
+/// down, so a recv blocked on it in another thread returns. Returns 0, or -1
+/// (status `NOT_FOUND`) for an unknown or already closed handle.
+pub(crate) unsafe extern "C" fn cl_net_close(
+    ctx_ptr: *const CraneliftNetContext,
+    handle: i64,
+) -> i64 {
+    status::begin();
+    let Some(ctx) = read_ctx_ref::<CraneliftNetContext>(ctx_ptr) else {
+        return -1;
+    };
+    let key = || format!("cl_net_close {handle}");
+    io_log::call(key, -1, || {
+        let mut t = ctx.tables.lock().unwrap();
+        if let Some(stream) = t.connections.remove(&(handle as u32)) {
+            t.pending_frames.remove(&(handle as u32));
+            let _ = stream.shutdown(Shutdown::Both);
+        } else if t.listeners.remove(&(handle as u32)).is_none() {
+            status::set(NOT_FOUND, 0);
+            return -1;
+        }
+        status::ok(0);
+        0
+    })
 }
 
 pub(crate) unsafe extern "C" fn cl_net_cleanup(ctx_slot_ptr: *mut *mut CraneliftNetContext) {
@@ -144,10 +511,97 @@ pub(crate) unsafe extern "C" fn cl_net_cleanup(ctx_slot_ptr: *mut *mut Cranelift
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ffi::{cancel, thread};
     use std::ffi::CString;
     use std::io::{Read, Write};
     use std::net::{TcpListener, TcpStream};
 
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut out = (payload.len() as u32).to_le_bytes().to_vec();
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Payloads of the `[u32 len][payload]` records in `buf`.
+    fn frames(buf: &[u8]) -> Vec<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut at = 0;
+        while at + 4 <= buf.len() {
+            let len = u32::from_le_bytes(buf[at..at + 4].try_into().unwrap()) as usize;
+            out.push(buf[at + 4..at + 4 + len].to_vec());
+            at += 4 + len;
+        }
+        out
+    }
+
+    #[test]
+    fn recv_framed_batches_splits_and_skips_oversized_frames() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = CString::new(listener.local_addr().unwrap().to_string()).unwrap();
+        let (go_tx, go_rx) = std::sync::mpsc::channel::<()>();
+        let server = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let batch: Vec<u8> = [&b"one"[..], b"two", b"three"]
+                .iter()
+                .flat_map(|p| frame(p))
+                .collect();
+            s.write_all(&batch).unwrap();
+            // One frame in three segments, split inside the prefix and body.
+            let split = frame(b"split across segments");
+            for part in [&split[..2], &split[2..10], &split[10..]] {
+                s.write_all(part).unwrap();
+                s.flush().unwrap();
+                std::thread::sleep(std::time::Duration::from_millis(20));
+            }
+            go_rx.recv().unwrap();
+            s.write_all(&frame(&[7u8; 100])).unwrap();
+            s.write_all(&frame(b"after")).unwrap();
+            s.write_all(&frame(b"0123456789")).unwrap();
+            s.write_all(&frame(b"abcdefghijklmnopqrst")).unwrap();
+        });
+
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        let mut buf = [0u8; 64];
+        let last = || base_types::status::status(unsafe { status::cl_last_status() } as u64);
+        unsafe {
+            cl_net_init(&mut slot);
+            let conn = cl_net_connect(slot, addr.as_ptr() as *const u8);
+            assert!(conn > 0);
+            let recv = |buf: &mut [u8], max| {
+                cl_net_recv_framed(slot, conn, buf.as_mut_ptr(), buf.len() as i64, max)
+            };
+
+            assert_eq!(recv(&mut buf, 3), 3);
+            assert_eq!(frames(&buf[..23]), [&b"one"[..], b"two", b"three"]);
+            assert_eq!(recv(&mut buf, 1), 1);
+            assert_eq!(frames(&buf[..25]), [b"split across segments"]);
+
+            go_tx.send(()).unwrap();
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::payload(word), 25);
+            assert_eq!(recv(&mut buf[..32], 4), 0);
+            assert_eq!(last(), TOO_LARGE);
+            assert_eq!(
+                base_types::status::payload(status::cl_last_status() as u64),
+                100
+            );
+            // The stream is still in sync; the third frame has no room and
+            // waits for the next call.
+            assert_eq!(recv(&mut buf[..32], 4), 2);
+            assert_eq!(frames(&buf[..23]), [&b"after"[..], b"0123456789"]);
+            assert_eq!(recv(&mut buf[..32], 4), 1);
+            assert_eq!(frames(&buf[..24]), [b"abcdefghijklmnopqrst"]);
+
+            server.join().unwrap();
+            assert_eq!(recv(&mut buf, 4), 0, "peer closed between frames");
+            assert_eq!(last(), base_types::status::OK);
+            assert_eq!(recv(&mut buf[..3], 1), -1);
+            assert_eq!(recv(&mut buf, 0), -1);
+            assert_eq!(cl_net_recv_framed(slot, 999, buf.as_mut_ptr(), 64, 1), -1);
+            cl_net_cleanup(&mut slot);
+        }
+    }
+
     #[test]
     fn init_then_cleanup_lifecycle() {
         let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
@@ -303,6 +757,88 @@ mod tests {
         server.join().unwrap();
     }
 
+    #[test]
+    fn accept_loop_serves_clients_on_separate_handles() {
+        let addr = CString::new("127.0.0.1:0").unwrap();
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        unsafe {
+            cl_net_init(&mut slot);
+            let listen_h = cl_net_listen(slot, addr.as_ptr() as *const u8);
+            let port = cl_net_listener_port(slot, listen_h) as u16;
+            let clients = std::thread::spawn(move || {
+                [b"first", b"other"].map(|msg| {
+                    let mut s = TcpStream::connect(("127.0.0.1", port)).unwrap();
+                    s.write_all(msg).unwrap();
+                    let mut echo = Vec::new();
+                    s.read_to_end(&mut echo).unwrap();
+                    echo
+                })
+            });
+
+            let mut handles = Vec::new();
+            for _ in 0..2 {
+                let conn = cl_net_accept(slot, listen_h);
+                assert!(conn > 0 && conn != listen_h);
+                assert!(!handles.contains(&conn));
+                let mut buf = [0u8; 5];
+                assert_eq!(cl_net_recv(slot, conn, buf.as_mut_ptr(), 5), 5);
+                buf.reverse();
+                assert_eq!(cl_net_send(slot, conn, buf.as_ptr(), 5), 0);
+                assert_eq!(cl_net_close(slot, conn), 0);
+                handles.push(conn);
+            }
+            let echoes = clients.join().unwrap();
+            assert_eq!(echoes, [b"tsrif".to_vec(), b"rehto".to_vec()]);
+
+            for conn in handles {
+                assert_eq!(cl_net_send(slot, conn, b"x".as_ptr(), 1), -1);
+                let word = status::cl_last_status() as u64;
+                assert_eq!(base_types::status::status(word), NOT_FOUND);
+                assert_eq!(cl_net_close(slot, conn), -1);
+            }
+            assert_eq!(cl_net_close(slot, listen_h), 0);
+            assert_eq!(cl_net_accept(slot, listen_h), 0);
+            cl_net_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn blocked_accept_does_not_stall_other_threads() {
+        // One thread parks in cl_net_accept and echoes; the main thread
+        // connects, sends and receives through the same context meanwhile.
+        let addr = CString::new("127.0.0.1:0").unwrap();
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        unsafe {
+            cl_net_init(&mut slot);
+            let listen_h = cl_net_listen(slot, addr.as_ptr() as *const u8);
+            let port = cl_net_listener_port(slot, listen_h);
+            let ctx = slot as usize;
+
+            let server = std::thread::spawn(move || {
+                let ctx = ctx as *const CraneliftNetContext;
+                let conn = cl_net_accept(ctx, listen_h);
+                assert!(conn > 0);
+                let mut buf = [0u8; 4];
+                assert_eq!(cl_net_recv(ctx, conn, buf.as_mut_ptr(), 4), 4);
+                buf.reverse();
+                assert_eq!(cl_net_send(ctx, conn, buf.as_ptr(), 4), 0);
+            });
+
+            // Give the server time to block inside accept.
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            let target = CString::new(format!("127.0.0.1:{port}")).unwrap();
+            let conn = cl_net_connect(slot, target.as_ptr() as *const u8);
+            assert!(conn > 0);
+            assert_eq!(cl_net_send(slot, conn, b"abcd".as_ptr(), 4), 0);
+            let mut buf = [0u8; 4];
+            assert_eq!(cl_net_recv(slot, conn, buf.as_mut_ptr(), 4), 4);
+            assert_eq!(&buf, b"dcba");
+
+            server.join().unwrap();
+            cl_net_cleanup(&mut slot);
+        }
+    }
+
     #[test]
     fn send_recv_on_invalid_handle_returns_neg1() {
         let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
@@ -348,4 +884,226 @@ mod tests {
         unsafe { cl_net_cleanup(&mut null_slot) };
         assert!(null_slot.is_null());
     }
+
+    /// A `cl_net_retry` policy descriptor.
+    fn policy(max_attempts: u8, initial_ms: u16, multiplier_x10: u8, max_ms: u16) -> [u8; 6] {
+        let [i0, i1] = initial_ms.to_le_bytes();
+        let [m0, m1] = max_ms.to_le_bytes();
+        [max_attempts, i0, i1, multiplier_x10, m0, m1]
+    }
+
+    /// A loopback address nothing is listening on.
+    fn closed_addr() -> std::net::SocketAddr {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[test]
+    fn retry_backs_off_and_stops_on_permanent_errors() {
+        let p = RetryPolicy {
+            max_attempts: 4,
+            initial_backoff_ms: 100,
+            multiplier_x10: 20,
+            max_backoff_ms: 250,
+        };
+        let ms: Vec<_> = (1..=3).map(|a| p.backoff(a).as_millis()).collect();
+        assert_eq!(ms, [100, 200, 250]);
+
+        let fast = Some(RetryPolicy {
+            initial_backoff_ms: 0,
+            ..p
+        });
+        let failing = |kind: io::ErrorKind, failures: u32| {
+            let mut calls = 0;
+            move || {
+                calls += 1;
+                if calls <= failures {
+                    Err(io::Error::from(kind))
+                } else {
+                    Ok(calls)
+                }
+            }
+        };
+        let (r, attempts) = with_retry(fast, failing(io::ErrorKind::ConnectionRefused, 2));
+        assert_eq!((r.unwrap(), attempts), (3, 3));
+        let (r, attempts) = with_retry(fast, failing(io::ErrorKind::TimedOut, 9));
+        assert_eq!(
+            (r.unwrap_err().kind(), attempts),
+            (io::ErrorKind::TimedOut, 4)
+        );
+        let (r, attempts) = with_retry(fast, failing(io::ErrorKind::PermissionDenied, 9));
+        assert_eq!(
+            (r.unwrap_err().kind(), attempts),
+            (io::ErrorKind::PermissionDenied, 1)
+        );
+        let (r, attempts) = with_retry(None, failing(io::ErrorKind::ConnectionRefused, 1));
+        assert!(r.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn retry_stops_when_cancelled_during_backoff() {
+        let policy = Some(RetryPolicy {
+            max_attempts: 255,
+            initial_backoff_ms: 60_000,
+            multiplier_x10: 10,
+            max_backoff_ms: 60_000,
+        });
+        let token = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        cancel::set_token(Some(token.clone()));
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            token.store(true, std::sync::atomic::Ordering::Release);
+            thread::wake_all_waiters();
+        });
+        let started = std::time::Instant::now();
+        let (r, attempts) = with_retry(policy, || {
+            Err::<(), _>(io::Error::from(io::ErrorKind::ConnectionRefused))
+        });
+        cancel::set_token(None);
+        canceller.join().unwrap();
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            (r.unwrap_err().kind(), attempts),
+            (io::ErrorKind::ConnectionRefused, 1)
+        );
+    }
+
+    #[test]
+    fn accept_and_recv_give_up_when_cancelled() {
+        // Cancel the blocked call 50 ms in.
+        fn cancelled_after_50ms(call: impl FnOnce() -> i64) -> i64 {
+            let token = Arc::new(std::sync::atomic::AtomicBool::new(false));
+            cancel::set_token(Some(token.clone()));
+            let canceller = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                token.store(true, std::sync::atomic::Ordering::Release);
+                thread::wake_all_waiters();
+            });
+            let started = std::time::Instant::now();
+            let ret = call();
+            cancel::set_token(None);
+            canceller.join().unwrap();
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+            ret
+        }
+        let addr = CString::new("127.0.0.1:0").unwrap();
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        unsafe {
+            cl_net_init(&mut slot);
+            let listener = cl_net_listen(slot, addr.as_ptr() as *const u8);
+            assert_eq!(cancelled_after_50ms(|| cl_net_accept(slot, listener)), 0);
+
+            let port = cl_net_listener_port(slot, listener);
+            let _client = TcpStream::connect(("127.0.0.1", port as u16)).unwrap();
+            let conn = cl_net_accept(slot, listener);
+            assert!(conn > 0);
+            let mut buf = [0u8; 16];
+            let recv = || cl_net_recv(slot, conn, buf.as_mut_ptr(), 16);
+            assert_eq!(cancelled_after_50ms(recv), -1);
+            let framed = || cl_net_recv_framed(slot, conn, buf.as_mut_ptr(), 16, 1);
+            assert_eq!(cancelled_after_50ms(framed), -1);
+            cl_net_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn connect_retries_until_the_listener_appears() {
+        // Attempts at about 0, 100, and 300 ms; the listener binds at 200.
+        let addr = closed_addr();
+        let server = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            let listener = TcpListener::bind(addr).unwrap();
+            listener.accept().unwrap();
+        });
+        let addr = CString::new(addr.to_string()).unwrap();
+        let retry = policy(5, 100, 20, 1000);
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        unsafe {
+            cl_net_init(&mut slot);
+            assert_eq!(cl_net_retry(slot, retry.as_ptr()), 0);
+            assert!(cl_net_connect(slot, addr.as_ptr() as *const u8) > 0);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::status(word), 0);
+            assert_eq!(base_types::status::attempts(word), 3);
+            cl_net_cleanup(&mut slot);
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn connect_gives_up_after_max_attempts() {
+        let refused = CString::new(closed_addr().to_string()).unwrap();
+        let invalid = CString::new("not an address").unwrap();
+        let retry = policy(3, 10, 10, 10);
+        let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+        unsafe {
+            cl_net_init(&mut slot);
+            cl_net_retry(slot, retry.as_ptr());
+            assert_eq!(cl_net_connect(slot, refused.as_ptr() as *const u8), 0);
+            let word = status::cl_last_status() as u64;
+            let errno = base_types::status::status(word) as i32;
+            let kind = io::Error::from_raw_os_error(errno).kind();
+            assert_eq!(kind, io::ErrorKind::ConnectionRefused);
+            assert_eq!(base_types::status::attempts(word), 3);
+
+            assert_eq!(cl_net_connect(slot, invalid.as_ptr() as *const u8), 0);
+            let word = status::cl_last_status() as u64;
+            assert_ne!(base_types::status::status(word), 0);
+            assert_eq!(base_types::status::attempts(word), 1, "not transient");
+
+            cl_net_retry(slot, std::ptr::null());
+            assert_eq!(cl_net_connect(slot, refused.as_ptr() as *const u8), 0);
+            let word = status::cl_last_status() as u64;
+            assert_eq!(base_types::status::attempts(word), 0, "no policy");
+            cl_net_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn replay_serves_a_recorded_exchange_without_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = CString::new(listener.local_addr().unwrap().to_string()).unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut ping = [0u8; 4];
+            s.read_exact(&mut ping).unwrap();
+            s.write_all(b"pong").unwrap();
+        });
+        // Connect, send `msg`, receive 4 bytes: (send rc, recv rc, reply).
+        let exchange = |msg: &[u8; 4]| unsafe {
+            let mut slot: *mut CraneliftNetContext = std::ptr::null_mut();
+            cl_net_init(&mut slot);
+            let conn = cl_net_connect(slot, addr.as_ptr() as *const u8);
+            let sent = cl_net_send(slot, conn, msg.as_ptr(), 4);
+            let mut reply = [0u8; 4];
+            let got = cl_net_recv(slot, conn, reply.as_mut_ptr(), 4);
+            cl_net_close(slot, conn);
+            cl_net_cleanup(&mut slot);
+            (sent, got, reply)
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("net.log");
+        let open = |log: io_log::IoLog| {
+            let session = Arc::new(io_log::Session::open(&log).unwrap());
+            io_log::set(Some(session.clone()));
+            session
+        };
+
+        let recording = open(io_log::IoLog::Record(log.clone()));
+        assert_eq!(exchange(b"ping"), (0, 4, *b"pong"));
+        assert_eq!(recording.finish(), Ok(()));
+        server.join().unwrap();
+
+        let replay = open(io_log::IoLog::Replay(log.clone()));
+        assert_eq!(exchange(b"ping"), (0, 4, *b"pong"));
+        assert_eq!(replay.finish(), Ok(()));
+
+        let replay = open(io_log::IoLog::Replay(log));
+        assert_eq!(exchange(b"pint").0, -1);
+        io_log::set(None);
+        assert!(replay.finish().unwrap_err().contains("cl_net_send"));
+    }
 }
@@ -0,0 +1,258 @@
+//! Bounded lock-free packet queue living in shared memory, safe for any
+//! number of concurrent producers and consumers (Vyukov-style sequence slots).
+//!
+//! Layout at `ring_off` (all u64): enqueue position, dequeue position,
+//! capacity, slot size; then `capacity` slots of `[seq, len, data...]`, with
+//! `data` padded to a multiple of 8 bytes.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const HEADER: usize = 32;
+
+/// `cl_queue_push` flag: spin until a slot frees up instead of returning 0.
+pub(crate) const QUEUE_BLOCK: i64 = 1;
+
+struct Ring {
+    base: *mut u8,
+    capacity: u64,
+    slot_size: usize,
+}
+
+impl Ring {
+    unsafe fn open(ptr: *mut u8, ring_off: i64) -> Option<Ring> {
+        if ptr.is_null() || ring_off < 0 {
+            return None;
+        }
+        let base = ptr.add(ring_off as usize);
+        if !base.cast::<u64>().is_aligned() {
+            return None;
+        }
+        let capacity = (*base.add(16).cast::<AtomicU64>()).load(Ordering::Relaxed);
+        let slot_size = (*base.add(24).cast::<AtomicU64>()).load(Ordering::Relaxed) as usize;
+        (capacity > 0).then_some(Ring {
+            base,
+            capacity,
+            slot_size,
+        })
+    }
+
+    fn stride(slot_size: usize) -> usize {
+        16 + slot_size.next_multiple_of(8)
+    }
+
+    unsafe fn word(&self, off: usize) -> &AtomicU64 {
+        &*self.base.add(off).cast::<AtomicU64>()
+    }
+
+    unsafe fn slot(&self, pos: u64) -> *mut u8 {
+        let idx = (pos % self.capacity) as usize;
+        self.base.add(HEADER + idx * Ring::stride(self.slot_size))
+    }
+}
+
+/// Initialize an empty queue of `capacity` slots holding up to `slot_size`
+/// bytes each at `ring_off` (8-byte aligned). Returns the bytes the ring
+/// occupies, or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_queue_init(
+    ptr: *mut u8,
+    ring_off: i64,
+    capacity: i64,
+    slot_size: i64,
+) -> i64 {
+    if ptr.is_null() || ring_off < 0 || capacity <= 0 || slot_size <= 0 {
+        return -1;
+    }
+    let base = ptr.add(ring_off as usize);
+    if !base.cast::<u64>().is_aligned() {
+        return -1;
+    }
+    let header = base.cast::<u64>();
+    *header = 0;
+    *header.add(1) = 0;
+    *header.add(2) = capacity as u64;
+    *header.add(3) = slot_size as u64;
+    let stride = Ring::stride(slot_size as usize);
+    for i in 0..capacity as usize {
+        *base.add(HEADER + i * stride).cast::<u64>() = i as u64;
+    }
+    (HEADER + capacity as usize * stride) as i64
+}
+
+/// Copy `size` bytes from `src_off` into the next free slot. Returns `size`,
+/// 0 if the queue is full (without `QUEUE_BLOCK`, or once the execution is
+/// cancelled), or -1 on bad arguments.
+pub(crate) unsafe extern "C" fn cl_queue_push(
+    ptr: *mut u8,
+    ring_off: i64,
+    src_off: i64,
+    size: i64,
+    flags: i64,
+) -> i64 {
+    let Some(ring) = Ring::open(ptr, ring_off) else {
+        return -1;
+    };
+    if src_off < 0 || size <= 0 || size as usize > ring.slot_size {
+        return -1;
+    }
+    let enqueue = ring.word(0);
+    let mut pos = enqueue.load(Ordering::Relaxed);
+    let slot = loop {
+        let slot = ring.slot(pos);
+        let seq = (*slot.cast::<AtomicU64>()).load(Ordering::Acquire);
+        let diff = seq.wrapping_sub(pos) as i64;
+        if diff == 0 {
+            match enqueue.compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break slot,
+                Err(actual) => pos = actual,
+            }
+        } else if diff < 0 {
+            if flags & QUEUE_BLOCK == 0 || super::cancel::is_cancelled() {
+                return 0;
+            }
+            std::thread::yield_now();
+            pos = enqueue.load(Ordering::Relaxed);
+        } else {
+            pos = enqueue.load(Ordering::Relaxed);
+        }
+    };
+    *slot.add(8).cast::<u64>() = size as u64;
+    std::ptr::copy_nonoverlapping(ptr.add(src_off as usize), slot.add(16), size as usize);
+    (*slot.cast::<AtomicU64>()).store(pos + 1, Ordering::Release);
+    size
+}
+
+/// Move the oldest packet to `dst_off` (which must have room for the slot
+/// size). Returns the packet length, 0 if the queue is empty, or -1 on bad
+/// arguments.
+pub(crate) unsafe extern "C" fn cl_queue_pop(ptr: *mut u8, ring_off: i64, dst_off: i64) -> i64 {
+    let Some(ring) = Ring::open(ptr, ring_off) else {
+        return -1;
+    };
+    if dst_off < 0 {
+        return -1;
+    }
+    let dequeue = ring.word(8);
+    let mut pos = dequeue.load(Ordering::Relaxed);
+    let slot = loop {
+        let slot = ring.slot(pos);
+        let seq = (*slot.cast::<AtomicU64>()).load(Ordering::Acquire);
+        let diff = seq.wrapping_sub(pos + 1) as i64;
+        if diff == 0 {
+            match dequeue.compare_exchange_weak(pos, pos + 1, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break slot,
+                Err(actual) => pos = actual,
+            }
+        } else if diff < 0 {
+            return 0;
+        } else {
+            pos = dequeue.load(Ordering::Relaxed);
+        }
+    };
+    let len = *slot.add(8).cast::<u64>() as usize;
+    std::ptr::copy_nonoverlapping(slot.add(16), ptr.add(dst_off as usize), len);
+    (*slot.cast::<AtomicU64>()).store(pos + ring.capacity, Ordering::Release);
+    len as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(align(8))]
+    struct Mem<const N: usize>([u8; N]);
+
+    #[test]
+    fn push_pop_fifo_with_lengths() {
+        let mut mem = Box::new(Mem([0u8; 1024]));
+        let p = mem.0.as_mut_ptr();
+        unsafe {
+            assert_eq!(cl_queue_init(p, 0, 4, 12), 32 + 4 * 32);
+            p.add(512).copy_from(b"hello world!".as_ptr(), 12);
+            assert_eq!(cl_queue_push(p, 0, 512, 5, 0), 5);
+            assert_eq!(cl_queue_push(p, 0, 512, 12, 0), 12);
+            assert_eq!(cl_queue_pop(p, 0, 600), 5);
+            assert_eq!(&mem.0[600..605], b"hello");
+            assert_eq!(cl_queue_pop(mem.0.as_mut_ptr(), 0, 600), 12);
+            assert_eq!(&mem.0[600..612], b"hello world!");
+            assert_eq!(cl_queue_pop(mem.0.as_mut_ptr(), 0, 600), 0);
+        }
+    }
+
+    #[test]
+    fn full_queue_reports_zero_and_wraps() {
+        let mut mem = Box::new(Mem([0u8; 512]));
+        let p = mem.0.as_mut_ptr();
+        unsafe {
+            cl_queue_init(p, 0, 2, 8);
+            for round in 0..5u8 {
+                *p.add(400) = round;
+                assert_eq!(cl_queue_push(p, 0, 400, 1, 0), 1);
+                assert_eq!(cl_queue_push(p, 0, 400, 1, 0), 1);
+                assert_eq!(cl_queue_push(p, 0, 400, 1, 0), 0, "ring of 2 is full");
+                assert_eq!(cl_queue_pop(p, 0, 408), 1);
+                assert_eq!(cl_queue_pop(p, 0, 409), 1);
+                assert_eq!((*p.add(408), *p.add(409)), (round, round));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        let mut mem = Box::new(Mem([0u8; 256]));
+        let p = mem.0.as_mut_ptr();
+        unsafe {
+            assert_eq!(cl_queue_init(p, 4, 2, 8), -1);
+            assert_eq!(cl_queue_init(p, 0, 0, 8), -1);
+            assert_eq!(cl_queue_pop(p, 0, 128), -1, "uninitialized ring");
+            cl_queue_init(p, 0, 2, 8);
+            assert_eq!(cl_queue_push(p, 0, 128, 9, 0), -1);
+            assert_eq!(cl_queue_push(p, 0, 128, 0, 0), -1);
+            assert_eq!(cl_queue_push(std::ptr::null_mut(), 0, 128, 1, 0), -1);
+        }
+    }
+
+    #[test]
+    fn concurrent_producers_lose_and_duplicate_nothing() {
+        const PRODUCERS: u64 = 4;
+        const PER_PRODUCER: u64 = 10_000;
+        // Ring of 64 slots at 0; each producer stages its packet at 8192 + 64 * id.
+        let mut mem = vec![0u64; 2048];
+        let p = mem.as_mut_ptr() as *mut u8;
+        unsafe { cl_queue_init(p, 0, 64, 16) };
+        let base = p as usize;
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|id| {
+                std::thread::spawn(move || unsafe {
+                    let p = base as *mut u8;
+                    let staging = 8192 + 64 * id as i64;
+                    for n in 0..PER_PRODUCER {
+                        *p.add(staging as usize).cast::<[u64; 2]>() = [id, n];
+                        assert_eq!(cl_queue_push(p, 0, staging, 16, QUEUE_BLOCK), 16);
+                    }
+                })
+            })
+            .collect();
+
+        let mut next = [0u64; PRODUCERS as usize];
+        let mut received = 0;
+        while received < PRODUCERS * PER_PRODUCER {
+            match unsafe { cl_queue_pop(p, 0, 12288) } {
+                0 => std::thread::yield_now(),
+                16 => {
+                    let [id, n] = unsafe { *p.add(12288).cast::<[u64; 2]>() };
+                    assert_eq!(n, next[id as usize], "producer {id} out of order");
+                    next[id as usize] += 1;
+                    received += 1;
+                }
+                other => panic!("unexpected pop result {other}"),
+            }
+        }
+        for h in producers {
+            h.join().unwrap();
+        }
+        assert_eq!(next, [PER_PRODUCER; PRODUCERS as usize]);
+        assert_eq!(unsafe { cl_queue_pop(p, 0, 12288) }, 0);
+    }
+}
@@ -0,0 +1,324 @@
+//! Pseudo-random numbers for CLIF code.
+//!
+//! Every thread draws from its own PCG32 stream. Under
+//! `Base::set_random_seed`, each stream is derived from the seed and a unit
+//! id that does not depend on scheduling: 0 for the executing thread, the
+//! thread handle for `cl_thread_spawn`, and pool handle plus submission index
+//! for pool jobs. Runs with the same seed and inputs therefore draw the same
+//! values. Without a seed, streams start from per-process random state.
+//!
+//! `cl_random_weighted` samples an index from f64 weights in memory with
+//! Vose's alias method: the first call for a weight array builds the table in
+//! O(n), later calls with the same offset and count reuse it and cost two
+//! draws. Tables are cached per thread until the next `install`, i.e. for the
+//! current execution, thread, or pool job, so weights rewritten in place
+//! during that time are not seen.
+
+use super::status;
+use base_types::status::INVALID_ARGUMENT;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+/// PCG-XSH-RR with 64-bit state and 32-bit output.
+#[derive(Copy, Clone)]
+struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULT: u64 = 6364136223846793005;
+
+    fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULT).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+}
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+thread_local! {
+    /// Seed of the execution running on this thread (propagated to threads
+    /// started through `cl_thread_*`).
+    static SEED: Cell<Option<u64>> = const { Cell::new(None) };
+    static RNG: Cell<Option<Pcg32>> = const { Cell::new(None) };
+    /// Alias tables keyed by weight address and count.
+    static ALIAS: RefCell<HashMap<(usize, usize), Alias>> = RefCell::new(HashMap::new());
+}
+
+pub(crate) fn current_seed() -> Option<u64> {
+    SEED.with(|cell| cell.get())
+}
+
+/// Start this thread's stream for `unit` of an execution seeded with `seed`,
+/// or from fresh random state when `seed` is `None`.
+pub(crate) fn install(seed: Option<u64>, unit: u64) {
+    let rng = match seed {
+        Some(seed) => Pcg32::new(splitmix64(seed ^ splitmix64(unit)), unit),
+        None => {
+            let s = RandomState::new();
+            Pcg32::new(s.hash_one(unit), s.hash_one(!unit))
+        }
+    };
+    SEED.with(|cell| cell.set(seed));
+    RNG.with(|cell| cell.set(Some(rng)));
+    ALIAS.with(|cache| cache.borrow_mut().clear());
+}
+
+fn next_u64() -> u64 {
+    RNG.with(|cell| {
+        let mut rng = cell.get().unwrap_or_else(|| {
+            let s = RandomState::new();
+            Pcg32::new(s.hash_one(0u64), s.hash_one(1u64))
+        });
+        let value = rng.next_u64();
+        cell.set(Some(rng));
+        value
+    })
+}
+
+/// Unbiased draw from `0..range`, `range > 0`: skips the 2^64 % range
+/// lowest draws so every residue is equally likely.
+fn below(range: u64) -> u64 {
+    let threshold = range.wrapping_neg() % range;
+    loop {
+        let x = next_u64();
+        if x >= threshold {
+            return x % range;
+        }
+    }
+}
+
+/// A uniform value in `[min, max]` (inclusive) from this thread's stream.
+/// Returns `min` when `max < min`.
+pub(crate) unsafe extern "C" fn cl_random(min: i64, max: i64) -> i64 {
+    if max <= min {
+        return min;
+    }
+    let span = max.wrapping_sub(min) as u64;
+    if span == u64::MAX {
+        return next_u64() as i64;
+    }
+    min.wrapping_add(below(span + 1) as i64)
+}
+
+/// Alias table: column `i` keeps `i` with probability `prob[i]`, else
+/// yields `alias[i]`.
+struct Alias {
+    prob: Vec<f64>,
+    alias: Vec<u32>,
+}
+
+impl Alias {
+    /// `None` unless every weight is finite and non-negative and the total is
+    /// positive and finite.
+    fn new(weights: &[f64]) -> Option<Alias> {
+        if weights.iter().any(|w| !(w.is_finite() && *w >= 0.0)) {
+            return None;
+        }
+        let total: f64 = weights.iter().sum();
+        if !(total.is_finite() && total > 0.0) {
+            return None;
+        }
+        let n = weights.len();
+        let mut prob: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut alias: Vec<u32> = (0..n as u32).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| prob[i] < 1.0);
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            alias[s] = l as u32;
+            prob[l] -= 1.0 - prob[s];
+            if prob[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Leftovers are 1 up to rounding.
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+        }
+        Some(Alias { prob, alias })
+    }
+
+    fn sample(&self) -> u32 {
+        let i = below(self.prob.len() as u64) as usize;
+        let u = (next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64);
+        if u < self.prob[i] {
+            i as u32
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+/// Draw an index in `0..count` with probability proportional to the f64
+/// weight at `weights_off + 8 * index`. Returns -1 with `INVALID_ARGUMENT`
+/// when `count` is not positive or the weights are negative, non-finite, or
+/// sum to zero; the status payload is the index on success.
+pub(crate) unsafe extern "C" fn cl_random_weighted(
+    ptr: *mut u8,
+    weights_off: i64,
+    count: i64,
+) -> i64 {
+    status::begin();
+    if ptr.is_null() || weights_off < 0 || count <= 0 || count > u32::MAX as i64 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let key = (ptr.add(weights_off as usize) as usize, count as usize);
+    let index = ALIAS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let table = match cache.entry(key) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let weights = std::slice::from_raw_parts(key.0 as *const u8, key.1 * 8)
+                    .chunks_exact(8)
+                    .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+                    .collect::<Vec<_>>();
+                e.insert(Alias::new(&weights)?)
+            }
+        };
+        Some(table.sample())
+    });
+    match index {
+        Some(i) => {
+            status::ok(i as u64);
+            i as i64
+        }
+        None => {
+            status::set(INVALID_ARGUMENT, 0);
+            -1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draws(n: usize) -> Vec<i64> {
+        (0..n).map(|_| unsafe { cl_random(-5, 5) }).collect()
+    }
+
+    #[test]
+    fn seeded_streams_repeat_and_units_differ() {
+        install(Some(42), 0);
+        let a = draws(64);
+        install(Some(42), 0);
+        assert_eq!(draws(64), a);
+        install(Some(42), 1);
+        assert_ne!(draws(64), a);
+        install(Some(43), 0);
+        assert_ne!(draws(64), a);
+        assert!(a.iter().all(|v| (-5..=5).contains(v)));
+        assert!((-5..=5).all(|v| a.contains(&v)), "every value is drawn");
+    }
+
+    #[test]
+    fn degenerate_ranges() {
+        install(Some(7), 0);
+        assert_eq!(unsafe { cl_random(3, 3) }, 3);
+        assert_eq!(unsafe { cl_random(9, 2) }, 9);
+        let full: Vec<i64> = (0..4)
+            .map(|_| unsafe { cl_random(i64::MIN, i64::MAX) })
+            .collect();
+        assert!(full.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    fn weights(w: &[f64]) -> Vec<u8> {
+        let mut mem = vec![0u8; 8];
+        mem.extend(w.iter().flat_map(|w| w.to_le_bytes()));
+        mem
+    }
+
+    fn weighted(mem: &mut [u8], count: i64) -> i64 {
+        unsafe { cl_random_weighted(mem.as_mut_ptr(), 8, count) }
+    }
+
+    #[test]
+    fn weighted_draws_follow_the_weights() {
+        let w = [1.0, 0.0, 2.0, 3.0, 4.0];
+        let mut mem = weights(&w);
+        install(Some(42), 0);
+        let first: Vec<i64> = (0..12).map(|_| weighted(&mut mem, 5)).collect();
+        assert_eq!(first, [3, 4, 3, 2, 4, 3, 0, 3, 4, 4, 4, 4]);
+        install(Some(42), 0);
+        let again: Vec<i64> = (0..12).map(|_| weighted(&mut mem, 5)).collect();
+        assert_eq!(first, again, "seeded draws repeat");
+
+        let n = 100_000;
+        let mut counts = [0u32; 5];
+        for _ in 0..n {
+            counts[weighted(&mut mem, 5) as usize] += 1;
+        }
+        assert_eq!(counts[1], 0, "zero weight is never drawn");
+        let total: f64 = w.iter().sum();
+        let chi2: f64 = w
+            .iter()
+            .zip(counts)
+            .filter(|(w, _)| **w > 0.0)
+            .map(|(w, c)| {
+                let expected = n as f64 * w / total;
+                (c as f64 - expected).powi(2) / expected
+            })
+            .sum();
+        // 3 degrees of freedom: p = 0.001 at 16.27.
+        assert!(chi2 < 16.27, "chi2 {chi2}, counts {counts:?}");
+    }
+
+    #[test]
+    fn weighted_rejects_bad_weights() {
+        install(Some(1), 0);
+        for w in [
+            &[0.0, 0.0][..],
+            &[1.0, -1.0],
+            &[f64::NAN],
+            &[f64::INFINITY, 1.0],
+        ] {
+            let mut mem = weights(w);
+            assert_eq!(weighted(&mut mem, w.len() as i64), -1, "{w:?}");
+            let word = unsafe { status::cl_last_status() } as u64;
+            assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+        }
+        let mut mem = weights(&[1.0]);
+        assert_eq!(weighted(&mut mem, 0), -1);
+        assert_eq!(weighted(&mut mem, 1), 0);
+        assert_eq!(unsafe { status::cl_last_status() }, 0);
+    }
+
+    #[test]
+    fn weighted_table_is_cached_until_install() {
+        install(Some(3), 0);
+        let mut mem = weights(&[1.0, 0.0]);
+        assert_eq!(weighted(&mut mem, 2), 0);
+        mem[8..16].copy_from_slice(&0.0f64.to_le_bytes());
+        mem[16..24].copy_from_slice(&1.0f64.to_le_bytes());
+        assert_eq!(weighted(&mut mem, 2), 0, "cached table");
+        install(Some(3), 0);
+        assert_eq!(weighted(&mut mem, 2), 1, "rebuilt after install");
+    }
+}
@@ -0,0 +1,68 @@
+//! Retry policies for calls that can fail transiently, shared by network
+//! contexts (`cl_net_retry`) and file calls (`cl_file_retry`).
+
+use std::time::Duration;
+
+use super::thread::sleep_unless_cancelled;
+
+/// Bytes in a packed policy: `max_attempts` (u8), `initial_backoff_ms`
+/// (u16), `multiplier_x10` (u8), `max_backoff_ms` (u16), integers
+/// little-endian. The backoff starts at `initial_backoff_ms`, is multiplied
+/// by `multiplier_x10 / 10` after each further failure, and is capped at
+/// `max_backoff_ms`.
+pub(crate) const RETRY_POLICY: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct RetryPolicy {
+    pub(super) max_attempts: u32,
+    pub(super) initial_backoff_ms: u16,
+    pub(super) multiplier_x10: u8,
+    pub(super) max_backoff_ms: u16,
+}
+
+impl RetryPolicy {
+    /// The policy packed at `policy_ptr`, or `None` for a null pointer.
+    pub(super) unsafe fn read(policy_ptr: *const u8) -> Option<RetryPolicy> {
+        if policy_ptr.is_null() {
+            return None;
+        }
+        let p = std::slice::from_raw_parts(policy_ptr, RETRY_POLICY);
+        Some(RetryPolicy {
+            max_attempts: p[0].max(1) as u32,
+            initial_backoff_ms: u16::from_le_bytes([p[1], p[2]]),
+            multiplier_x10: p[3],
+            max_backoff_ms: u16::from_le_bytes([p[4], p[5]]),
+        })
+    }
+
+    /// The sleep after failed attempt `attempt` (1-based).
+    pub(super) fn backoff(&self, attempt: u32) -> Duration {
+        let factor = (self.multiplier_x10 as f64 / 10.0).powi(attempt as i32 - 1);
+        let ms = (self.initial_backoff_ms as f64 * factor).min(self.max_backoff_ms as f64);
+        Duration::from_millis(ms as u64)
+    }
+}
+
+/// Run `op` under `policy` (once without one), sleeping between attempts
+/// while its error is `transient`. A cancel during a backoff ends the
+/// retries with the error that preceded it. Returns its last result and the
+/// number of attempts made.
+pub(super) fn with_retry<T, E>(
+    policy: Option<RetryPolicy>,
+    transient: impl Fn(&E) -> bool,
+    mut op: impl FnMut() -> Result<T, E>,
+) -> (Result<T, E>, u32) {
+    let max_attempts = policy.map_or(1, |p| p.max_attempts);
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if attempt < max_attempts && transient(&e) => {
+                if !sleep_unless_cancelled(policy.unwrap().backoff(attempt)) {
+                    return (Err(e), attempt);
+                }
+                attempt += 1;
+            }
+            result => return (result, attempt),
+        }
+    }
+}
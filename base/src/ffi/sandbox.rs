@@ -0,0 +1,157 @@
+//! Per-execution path policy for the FFI calls that open files: file reads
+//! and writes, file streaming, checkpoints, and LMDB environments.
+//!
+//! Relative paths are joined onto `working_dir`. With
+//! `allowed_path_prefixes` set, the path is then resolved through every
+//! symlink (for a file that does not exist yet, through its nearest existing
+//! ancestor) and the call fails with `PATH_DENIED`, before touching the
+//! file, unless the result lies under one of the prefixes. The resolved
+//! path is what gets opened.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use super::status;
+use base_types::status::PATH_DENIED;
+use tracing::warn;
+
+/// Where file FFI calls may reach; see `Base::set_path_sandbox`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathSandbox {
+    /// Prepended to every relative path.
+    pub working_dir: Option<PathBuf>,
+    /// When set, paths must resolve under one of these directories.
+    pub allowed_path_prefixes: Option<Vec<PathBuf>>,
+}
+
+impl PathSandbox {
+    /// Resolve the prefixes once, so a symlinked prefix such as macOS's
+    /// `/tmp` still matches resolved paths. Missing prefixes are kept as
+    /// given and match nothing that exists.
+    pub(crate) fn canonicalized(mut self) -> PathSandbox {
+        if let Some(prefixes) = &mut self.allowed_path_prefixes {
+            for prefix in prefixes.iter_mut() {
+                let absolute = match &self.working_dir {
+                    Some(dir) if prefix.is_relative() => dir.join(&*prefix),
+                    _ => prefix.clone(),
+                };
+                *prefix = resolve_links(&absolute, 0).unwrap_or(absolute);
+            }
+        }
+        self
+    }
+}
+
+thread_local! {
+    static SANDBOX: RefCell<Option<Arc<PathSandbox>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn current() -> Option<Arc<PathSandbox>> {
+    SANDBOX.with(|cell| cell.borrow().clone())
+}
+
+pub(crate) fn set(sandbox: Option<Arc<PathSandbox>>) {
+    SANDBOX.with(|cell| *cell.borrow_mut() = sandbox);
+}
+
+/// Symlink hops followed before giving up, as for `ELOOP`.
+const MAX_LINKS: usize = 40;
+
+/// `path` with every symlink resolved, including a dangling final link and
+/// missing trailing components. `None` on a loop, or when a missing
+/// component is `..` and so cannot be resolved without the directory.
+fn resolve_links(path: &Path, depth: usize) -> Option<PathBuf> {
+    if depth > MAX_LINKS {
+        return None;
+    }
+    if let Ok(real) = fs::canonicalize(path) {
+        return Some(real);
+    }
+    if let Ok(target) = fs::read_link(path) {
+        let parent = path.parent().unwrap_or(Path::new("."));
+        return resolve_links(&parent.join(target), depth + 1);
+    }
+    let name = match path.components().next_back()? {
+        Component::Normal(name) => name,
+        Component::CurDir => return resolve_links(path.parent()?, depth),
+        _ => return None,
+    };
+    let parent = match path.parent()? {
+        p if p.as_os_str().is_empty() => Path::new("."),
+        p => p,
+    };
+    Some(resolve_links(parent, depth)?.join(name))
+}
+
+/// The path a file call on this thread should open, or `None` with the
+/// status word set to `PATH_DENIED` when the sandbox rejects it.
+pub(super) fn resolve(path: PathBuf) -> Option<PathBuf> {
+    let Some(sandbox) = current() else {
+        return Some(path);
+    };
+    let path = match &sandbox.working_dir {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    };
+    let Some(prefixes) = &sandbox.allowed_path_prefixes else {
+        return Some(path);
+    };
+    match resolve_links(&path, 0) {
+        Some(real) if prefixes.iter().any(|p| real.starts_with(p)) => Some(real),
+        _ => {
+            warn!(path = %path.display(), "path outside the sandbox");
+            status::set(PATH_DENIED, 0);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandboxed(root: &Path) {
+        set(Some(Arc::new(
+            PathSandbox {
+                working_dir: Some(root.to_path_buf()),
+                allowed_path_prefixes: Some(vec![root.to_path_buf()]),
+            }
+            .canonicalized(),
+        )));
+    }
+
+    #[test]
+    fn resolves_inside_and_denies_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(dir.path().join("secret"), b"x").unwrap();
+        let real_root = fs::canonicalize(&root).unwrap();
+        sandboxed(&root);
+
+        assert_eq!(resolve("out.bin".into()), Some(real_root.join("out.bin")));
+        assert_eq!(
+            resolve("sub/../new.bin".into()),
+            None,
+            "`..` past a missing directory"
+        );
+        assert_eq!(resolve(dir.path().join("secret")), None);
+        assert_eq!(resolve("../secret".into()), None);
+        let word = unsafe { status::cl_last_status() } as u64;
+        assert_eq!(base_types::status::status(word), PATH_DENIED);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("secret"), root.join("link")).unwrap();
+            std::os::unix::fs::symlink(dir.path().join("gone"), root.join("dangling")).unwrap();
+            std::os::unix::fs::symlink(dir.path(), root.join("up")).unwrap();
+            assert_eq!(resolve("link".into()), None);
+            assert_eq!(resolve("dangling".into()), None);
+            assert_eq!(resolve("up/new.bin".into()), None);
+        }
+        set(None);
+        assert_eq!(resolve("out.bin".into()), Some(PathBuf::from("out.bin")));
+    }
+}
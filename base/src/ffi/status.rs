@@ -0,0 +1,53 @@
+//! Per-thread completion word (see `base_types::status`) set by fallible FFI
+//! calls, errno-style.
+
+use std::cell::Cell;
+
+use base_types::status::{self, FAILED, IO_ERROR, OK};
+
+thread_local! {
+    static LAST_STATUS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Mark the current call as failed until it reports otherwise, so every
+/// early return leaves a non-zero word.
+pub(super) fn begin() {
+    set(FAILED, 0);
+}
+
+pub(super) fn set(code: u32, payload: u64) {
+    LAST_STATUS.with(|s| s.set(status::pack(code, payload)));
+}
+
+pub(super) fn ok(payload: u64) {
+    set(OK, payload);
+}
+
+/// Record `attempts` in the current word (see `status::with_attempts`).
+pub(super) fn attempts(attempts: u32) {
+    LAST_STATUS.with(|s| s.set(status::with_attempts(s.get(), attempts)));
+}
+
+/// The word the last call on this thread set.
+pub(super) fn word() -> u64 {
+    LAST_STATUS.with(|s| s.get())
+}
+
+/// Set a word captured with `word`, as the replayed call would have.
+pub(super) fn restore(word: u64) {
+    LAST_STATUS.with(|s| s.set(word));
+}
+
+pub(super) fn io(err: &std::io::Error) {
+    let code = err
+        .raw_os_error()
+        .filter(|&e| e > 0)
+        .map_or(IO_ERROR, |e| e as u32);
+    set(code, 0);
+}
+
+/// Completion word of the last file, network, memory, hash table, or LMDB
+/// call made on this thread.
+pub(crate) unsafe extern "C" fn cl_last_status() -> i64 {
+    LAST_STATUS.with(|s| s.get()) as i64
+}
@@ -1,13 +1,348 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
+use super::retry::RetryPolicy;
+use super::{arena, cancel, clock, file, io_log, mem, random, sandbox, status};
 use super::{clear_ctx_slot, read_ctx_mut, read_ctx_ref, write_ctx_slot};
 use crate::jit::THREAD_COMPILED_FNS;
+use base_types::status::{FAILED, INVALID_ARGUMENT};
+use tracing::{debug, info_span, warn, Level, Span};
+
+/// Worker counters shared by every thread context created while stats
+/// collection is enabled (see `Base::execute_with_stats`).
+#[derive(Default)]
+pub(crate) struct ThreadStats {
+    pub(crate) spawned: AtomicU64,
+    pub(crate) busy_ns: AtomicU64,
+    pub(crate) join_wait_ns: AtomicU64,
+    pub(crate) inline_calls: AtomicU64,
+}
+
+thread_local! {
+    pub(crate) static THREAD_STATS: RefCell<Option<Arc<ThreadStats>>> = const { RefCell::new(None) };
+}
 
 pub(crate) struct CraneliftThreadContext {
     threads: HashMap<u32, std::thread::JoinHandle<()>>,
+    pools: HashMap<u32, WorkerPool>,
     next_handle: u32,
     compiled_fns: Arc<Vec<unsafe extern "C" fn(*mut u8)>>,
+    stats: Option<Arc<ThreadStats>>,
+    cancel: Option<Arc<AtomicBool>>,
+    clock: Option<Arc<clock::ExecClock>>,
+    seed: Option<u64>,
+    sandbox: Option<Arc<sandbox::PathSandbox>>,
+    io_log: Option<Arc<io_log::Session>>,
+    file_retry: Option<RetryPolicy>,
+    bump: Option<Arc<arena::BumpArena>>,
+    copy_stream_min: usize,
+}
+
+/// Persistent workers pulling `(fn, arg)` jobs from a shared FIFO, so
+/// pipelines can submit many small items without a thread per item.
+struct WorkerPool {
+    shared: Arc<PoolShared>,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct PoolShared {
+    state: Mutex<PoolState>,
+    work_ready: Condvar,
+    drained: Condvar,
+    // Signalled when a worker takes a job off a bounded queue.
+    space: Condvar,
+    // Most jobs queued but not yet started; 0 means unbounded.
+    capacity: usize,
+    // Random seed of the execution, and the base of this pool's job unit ids.
+    seed: Option<u64>,
+    unit_base: u64,
+}
+
+#[derive(Default)]
+struct PoolState {
+    jobs: VecDeque<Job>,
+    submitted: u64,
+    // Queued plus running jobs; `cl_thread_pool_wait` blocks until zero.
+    pending: usize,
+    // Jobs taken off the queue and not yet finished.
+    running: usize,
+    // Workers blocked on `work_ready` and callers blocked on `drained`, so
+    // the common case of nobody waiting skips the wake-up.
+    idle: usize,
+    drain_waiters: usize,
+    stop: bool,
+}
+
+struct Job {
+    func: unsafe extern "C" fn(*mut u8),
+    arg: usize,
+    // Submission index, the job's random stream unit within the pool.
+    index: u64,
+    done: Completion,
+    next: Option<Continuation>,
+    // A `cl_thread_pool_fence` barrier: runs nothing, and is only taken off
+    // the queue once no job is running.
+    fence: bool,
+}
+
+impl Job {
+    fn new(func: unsafe extern "C" fn(*mut u8), arg: usize, done: Completion) -> Job {
+        Job {
+            func,
+            arg,
+            index: 0,
+            done,
+            next: None,
+            fence: false,
+        }
+    }
+}
+
+unsafe extern "C" fn no_op(_: *mut u8) {}
+
+/// Most stages `cl_thread_pool_chain` accepts.
+const MAX_CHAIN_STAGES: usize = 8;
+
+/// Stages of a `cl_thread_pool_chain`: each `(pool, fn)` runs on `arg` in
+/// turn, and `done` is signalled after the last.
+struct Chain {
+    stages: Vec<(Arc<PoolShared>, unsafe extern "C" fn(*mut u8))>,
+    arg: usize,
+    done: Completion,
+}
+
+/// Carried by a chained job: the stage it runs, so the worker that finishes
+/// it can queue the following stage directly.
+struct Continuation {
+    chain: Arc<Chain>,
+    stage: usize,
+}
+
+impl Continuation {
+    /// The job for `stage` of `chain`.
+    fn job(chain: &Arc<Chain>, stage: usize) -> (Arc<PoolShared>, Job) {
+        let (pool, func) = &chain.stages[stage];
+        let last = stage + 1 == chain.stages.len();
+        let done = if last {
+            chain.done
+        } else {
+            Completion::default()
+        };
+        let job = Job {
+            next: (!last).then(|| Continuation {
+                chain: chain.clone(),
+                stage,
+            }),
+            ..Job::new(*func, chain.arg, done)
+        };
+        (pool.clone(), job)
+    }
+
+    /// Queue the stage after this one. It bypasses a bounded pool's capacity:
+    /// a worker waiting for room in its own pool could never make any.
+    fn forward(self) {
+        let (pool, job) = Continuation::job(&self.chain, self.stage + 1);
+        pool.enqueue(job);
+    }
+}
+
+/// Words a job signals when it finishes (0 = none): `flag` is set to 1 and
+/// `countdown` decremented, each waking `cl_thread_wait_until` waiters.
+#[derive(Clone, Copy, Default)]
+struct Completion {
+    flag: usize,
+    countdown: usize,
+}
+
+impl Completion {
+    /// Split an operand block into its leading completion flag and the
+    /// operands that follow, or `None` for a null or misaligned block.
+    fn in_block(block_ptr: *mut u8) -> Option<(Completion, *mut u8)> {
+        if block_ptr.is_null() || !block_ptr.cast::<u64>().is_aligned() {
+            return None;
+        }
+        let done = Completion {
+            flag: block_ptr as usize,
+            countdown: 0,
+        };
+        Some((done, block_ptr.wrapping_add(8)))
+    }
+
+    fn signal(self) {
+        if self.flag != 0 {
+            unsafe { (*(self.flag as *const AtomicU64)).store(1, Ordering::Release) };
+            notify_waiters(self.flag);
+        }
+        if self.countdown != 0 {
+            unsafe { (*(self.countdown as *const AtomicU64)).fetch_sub(1, Ordering::AcqRel) };
+            notify_waiters(self.countdown);
+        }
+    }
+}
+
+impl WorkerPool {
+    fn start(n: usize, capacity: usize, handle: u32, ctx: &CraneliftThreadContext) -> WorkerPool {
+        let shared = Arc::new(PoolShared {
+            capacity,
+            seed: ctx.seed,
+            unit_base: (handle as u64) << 32,
+            ..PoolShared::default()
+        });
+        let (compiled_fns, cancel, exec_clock) = (&ctx.compiled_fns, &ctx.cancel, &ctx.clock);
+        let (paths, log, file_retry) = (&ctx.sandbox, &ctx.io_log, ctx.file_retry);
+        let (bump, copy_stream_min) = (&ctx.bump, ctx.copy_stream_min);
+        let parent = Span::current();
+        let workers = (0..n)
+            .map(|worker| {
+                let shared = shared.clone();
+                let compiled_fns = compiled_fns.clone();
+                let cancel = cancel.clone();
+                let exec_clock = exec_clock.clone();
+                let paths = paths.clone();
+                let log = log.clone();
+                let bump = bump.clone();
+                let span = info_span!(parent: &parent, "pool_worker", pool = handle, worker);
+                std::thread::spawn(move || {
+                    let _span = span.entered();
+                    THREAD_COMPILED_FNS.with(|cell| {
+                        *cell.borrow_mut() = Some(compiled_fns);
+                    });
+                    cancel::set_token(cancel);
+                    clock::set_clock(exec_clock);
+                    sandbox::set(paths);
+                    io_log::set(log);
+                    file::set_retry_policy(file_retry);
+                    arena::set_bump(bump);
+                    mem::set_copy_stream_min(Some(copy_stream_min));
+                    shared.run_worker();
+                })
+            })
+            .collect();
+        WorkerPool { shared, workers }
+    }
+
+    fn stop(self) {
+        self.shared.state.lock().unwrap().stop = true;
+        self.shared.work_ready.notify_all();
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl PoolShared {
+    fn run_worker(&self) {
+        // The lock taken to retire a job is kept to pick up the next one.
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let job = loop {
+                let front_fence = state.jobs.front().map(|job| job.fence);
+                if front_fence == Some(true) && state.running == 0 {
+                    // Every job queued before the fence has finished, and
+                    // their writes happened before the unlock that let us
+                    // in, so the Release flag store publishes them all.
+                    let fence = state.jobs.pop_front().unwrap();
+                    fence.done.signal();
+                    state.pending -= 1;
+                    if state.pending == 0 && state.drain_waiters > 0 {
+                        self.drained.notify_all();
+                    }
+                    if self.capacity > 0 {
+                        self.space.notify_one();
+                    }
+                    if state.idle > 0 {
+                        self.work_ready.notify_all();
+                    }
+                    continue;
+                }
+                if front_fence == Some(false) {
+                    let job = state.jobs.pop_front().unwrap();
+                    state.running += 1;
+                    if self.capacity > 0 {
+                        self.space.notify_one();
+                    }
+                    break job;
+                }
+                if state.stop && state.jobs.is_empty() {
+                    return;
+                }
+                state.idle += 1;
+                state = self.work_ready.wait(state).unwrap();
+                state.idle -= 1;
+            };
+            drop(state);
+            if self.seed.is_some() {
+                random::install(self.seed, self.unit_base.wrapping_add(job.index));
+            }
+            let start = tracing::enabled!(Level::DEBUG).then(|| {
+                debug!(job = job.index, "job started");
+                Instant::now()
+            });
+            unsafe { (job.func)(job.arg as *mut u8) };
+            if let Some(start) = start {
+                debug!(job = job.index, elapsed = ?start.elapsed(), "job finished");
+            }
+            job.done.signal();
+            if let Some(next) = job.next {
+                next.forward();
+            }
+            // A fence now at the front is taken by this worker's next pass.
+            state = self.state.lock().unwrap();
+            state.running -= 1;
+            state.pending -= 1;
+            if state.pending == 0 && state.drain_waiters > 0 {
+                self.drained.notify_all();
+            }
+        }
+    }
+
+    /// Queue a job. When a bounded queue is full, either give up (`block`
+    /// unset) or wait for a worker to take a job. Returns 0, or -2 if the job
+    /// was not queued because the queue was full or the execution cancelled.
+    fn submit(&self, job: Job, block: bool) -> i64 {
+        let mut state = self.state.lock().unwrap();
+        while self.capacity > 0 && state.jobs.len() >= self.capacity {
+            if !block {
+                warn!(capacity = self.capacity, "pool queue full");
+                return -2;
+            }
+            if cancel::is_cancelled() {
+                return -2;
+            }
+            // Cancellation does not signal `space`, so recheck periodically.
+            state = self
+                .space
+                .wait_timeout(state, Duration::from_millis(10))
+                .unwrap()
+                .0;
+        }
+        self.push(state, job);
+        0
+    }
+
+    /// Queue a job regardless of capacity.
+    fn enqueue(&self, job: Job) {
+        self.push(self.state.lock().unwrap(), job);
+    }
+
+    fn push(&self, mut state: std::sync::MutexGuard<'_, PoolState>, mut job: Job) {
+        if !job.fence {
+            job.index = state.submitted;
+            state.submitted += 1;
+        }
+        state.jobs.push_back(job);
+        state.pending += 1;
+        let wake = state.idle > 0;
+        drop(state);
+        if wake {
+            self.work_ready.notify_one();
+        }
+    }
 }
 
 pub(crate) unsafe extern "C" fn cl_thread_init(ctx_slot_ptr: *mut *mut CraneliftThreadContext) {
@@ -16,10 +351,21 @@ pub(crate) unsafe extern "C" fn cl_thread_init(ctx_slot_ptr: *mut *mut Cranelift
             .clone()
             .expect("cl_thread_init: no compiled functions available")
     });
+    let stats = THREAD_STATS.with(|cell| cell.borrow().clone());
     let ctx = Box::new(CraneliftThreadContext {
         threads: HashMap::new(),
+        pools: HashMap::new(),
         next_handle: 1,
         compiled_fns,
+        stats,
+        cancel: cancel::current_token(),
+        clock: clock::current_clock(),
+        seed: random::current_seed(),
+        sandbox: sandbox::current(),
+        io_log: io_log::current(),
+        file_retry: file::retry_policy(),
+        bump: arena::current_bump(),
+        copy_stream_min: mem::copy_stream_min(),
     });
     let raw = Box::into_raw(ctx);
     if !write_ctx_slot(ctx_slot_ptr, raw) {
@@ -27,6 +373,25 @@ pub(crate) unsafe extern "C" fn cl_thread_init(ctx_slot_ptr: *mut *mut Cranelift
     }
 }
 
+/// Compiled function `fn_index`, or `None` (with a warning) when there is no
+/// such function.
+fn compiled_fn(
+    ctx: &CraneliftThreadContext,
+    fn_index: i64,
+) -> Option<unsafe extern "C" fn(*mut u8)> {
+    let func = usize::try_from(fn_index)
+        .ok()
+        .and_then(|i| ctx.compiled_fns.get(i).copied());
+    if func.is_none() {
+        warn!(
+            fn_index,
+            available = ctx.compiled_fns.len(),
+            "unknown dispatch target"
+        );
+    }
+    func
+}
+
 pub(crate) unsafe extern "C" fn cl_thread_spawn(
     ctx_ptr: *mut CraneliftThreadContext,
     fn_index: i64,
@@ -35,21 +400,51 @@ pub(crate) unsafe extern "C" fn cl_thread_spawn(
     let Some(ctx) = read_ctx_mut::<CraneliftThreadContext>(ctx_ptr) else {
         return -1;
     };
-    let idx = fn_index as usize;
-    if idx >= ctx.compiled_fns.len() {
+    let Some(func) = compiled_fn(ctx, fn_index) else {
         return -1;
-    }
-    let func = ctx.compiled_fns[idx];
+    };
     let thread_arg = thread_ptr as usize;
     let handle_id = ctx.next_handle;
     ctx.next_handle += 1;
 
     let compiled_fns_clone = ctx.compiled_fns.clone();
+    let stats = ctx.stats.clone();
+    let cancel = ctx.cancel.clone();
+    let exec_clock = ctx.clock.clone();
+    let seed = ctx.seed;
+    let paths = ctx.sandbox.clone();
+    let log = ctx.io_log.clone();
+    let file_retry = ctx.file_retry;
+    let bump = ctx.bump.clone();
+    let copy_stream_min = ctx.copy_stream_min;
+    if let Some(stats) = &stats {
+        stats.spawned.fetch_add(1, Ordering::Relaxed);
+    }
+    let span = info_span!("thread", handle = handle_id, fn_index);
     let join = std::thread::spawn(move || {
+        let _span = span.entered();
         THREAD_COMPILED_FNS.with(|cell| {
             *cell.borrow_mut() = Some(compiled_fns_clone);
         });
-        func(thread_arg as *mut u8);
+        cancel::set_token(cancel);
+        clock::set_clock(exec_clock);
+        sandbox::set(paths);
+        io_log::set(log);
+        file::set_retry_policy(file_retry);
+        arena::set_bump(bump);
+        mem::set_copy_stream_min(Some(copy_stream_min));
+        if seed.is_some() {
+            random::install(seed, handle_id as u64);
+        }
+        match stats {
+            Some(stats) => {
+                let start = Instant::now();
+                func(thread_arg as *mut u8);
+                let busy = start.elapsed().as_nanos() as u64;
+                stats.busy_ns.fetch_add(busy, Ordering::Relaxed);
+            }
+            None => func(thread_arg as *mut u8),
+        }
     });
 
     ctx.threads.insert(handle_id, join);
@@ -64,7 +459,13 @@ pub(crate) unsafe extern "C" fn cl_thread_join(
         return -1;
     };
     if let Some(join) = ctx.threads.remove(&(handle as u32)) {
-        match join.join() {
+        let start = ctx.stats.as_ref().map(|_| Instant::now());
+        let joined = join.join();
+        if let (Some(stats), Some(start)) = (&ctx.stats, start) {
+            let waited = start.elapsed().as_nanos() as u64;
+            stats.join_wait_ns.fetch_add(waited, Ordering::Relaxed);
+        }
+        match joined {
             Ok(_) => 0,
             Err(_) => -1,
         }
@@ -79,6 +480,326 @@ pub(crate) unsafe extern "C" fn cl_thread_cleanup(ctx_slot_ptr: *mut *mut Cranel
     for (_, join) in ctx.threads.drain() {
         let _ = join.join();
     }
+    for (_, pool) in ctx.pools.drain() {
+        pool.stop();
+    }
+}
+
+/// Start `n_workers` persistent workers. Returns a pool handle, or -1.
+pub(crate) unsafe extern "C" fn cl_thread_pool_start(
+    ctx_ptr: *mut CraneliftThreadContext,
+    n_workers: i64,
+) -> i64 {
+    start_pool(ctx_ptr, n_workers, 0)
+}
+
+/// Like `cl_thread_pool_start`, but at most `capacity` jobs may wait for a
+/// worker: `cl_thread_pool_submit` blocks and `cl_thread_pool_try_submit`
+/// fails once that many are queued. Returns a pool handle, or -1 (including
+/// for `capacity <= 0`).
+pub(crate) unsafe extern "C" fn cl_thread_pool_start_bounded(
+    ctx_ptr: *mut CraneliftThreadContext,
+    n_workers: i64,
+    capacity: i64,
+) -> i64 {
+    if capacity <= 0 {
+        return -1;
+    }
+    start_pool(ctx_ptr, n_workers, capacity as usize)
+}
+
+unsafe fn start_pool(ctx_ptr: *mut CraneliftThreadContext, n_workers: i64, capacity: usize) -> i64 {
+    let Some(ctx) = read_ctx_mut::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    if n_workers <= 0 {
+        return -1;
+    }
+    let handle_id = ctx.next_handle;
+    let pool = WorkerPool::start(n_workers as usize, capacity, handle_id, ctx);
+    ctx.next_handle += 1;
+    ctx.pools.insert(handle_id, pool);
+    handle_id as i64
+}
+
+/// Queue compiled function `fn_index` to run on a pool worker with `arg_ptr`.
+/// Jobs start in submission order. On a full bounded pool, waits for room.
+/// Returns 0, -1 on a bad pool/fn, or -2 if cancelled while waiting.
+pub(crate) unsafe extern "C" fn cl_thread_pool_submit(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    arg_ptr: *mut u8,
+) -> i64 {
+    submit_job(ctx_ptr, pool, fn_index, arg_ptr, Completion::default(), true)
+}
+
+/// Like `cl_thread_pool_submit`, but returns -2 at once instead of waiting
+/// when a bounded pool's queue is full, so the caller can back off and retry.
+pub(crate) unsafe extern "C" fn cl_thread_pool_try_submit(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    arg_ptr: *mut u8,
+) -> i64 {
+    submit_job(ctx_ptr, pool, fn_index, arg_ptr, Completion::default(), false)
+}
+
+/// Queue compiled function `fn_index` on its own operand block, so one
+/// function serves any number of dispatches with different operands. The
+/// block at `block_ptr` (8-byte aligned) starts with a u64 completion flag,
+/// set to 0 now and to 1 when the job finishes (waitable with
+/// `cl_thread_wait_until`); the function is passed `block_ptr + 8`, the
+/// operands after it. Waits for room like `cl_thread_pool_submit`. Returns 0,
+/// -1 on a bad argument, or -2 if cancelled while waiting (the flag then
+/// stays 0).
+pub(crate) unsafe extern "C" fn cl_thread_pool_dispatch(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    block_ptr: *mut u8,
+) -> i64 {
+    let Some((done, arg)) = Completion::in_block(block_ptr) else {
+        return -1;
+    };
+    submit_job(ctx_ptr, pool, fn_index, arg, done, true)
+}
+
+/// `cl_thread_pool_dispatch` when `cond` is non-zero. When it is zero the
+/// job is skipped but its completion flag is still set to 1 (after the same
+/// argument checks), so an unconditional `cl_thread_wait_until` on the flag
+/// returns at once instead of hanging. Returns what the dispatch returns,
+/// or 1 when skipped.
+pub(crate) unsafe extern "C" fn cl_thread_pool_dispatch_if(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    block_ptr: *mut u8,
+    cond: i64,
+) -> i64 {
+    if cond != 0 {
+        return cl_thread_pool_dispatch(ctx_ptr, pool, fn_index, block_ptr);
+    }
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some((done, _)) = Completion::in_block(block_ptr) else {
+        return -1;
+    };
+    if !ctx.pools.contains_key(&(pool as u32)) || compiled_fn(ctx, fn_index).is_none() {
+        return -1;
+    }
+    done.signal();
+    1
+}
+
+unsafe fn submit_job(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    arg_ptr: *mut u8,
+    done: Completion,
+    block: bool,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some(pool) = ctx.pools.get(&(pool as u32)) else {
+        return -1;
+    };
+    let Some(func) = compiled_fn(ctx, fn_index) else {
+        return -1;
+    };
+    if done.flag != 0 {
+        (*(done.flag as *const AtomicU64)).store(0, Ordering::Relaxed);
+    }
+    pool.shared.submit(Job::new(func, arg_ptr as usize, done), block)
+}
+
+/// Queue `count` jobs running `fn_index` on `arg_ptr + i * stride`. When
+/// `flags_ptr` (8-byte aligned) is non-null it addresses `count + 1` u64
+/// words: word 0 is a countdown set to `count` and decremented as each job
+/// finishes, and word `1 + i` goes from 0 to 1 when job `i` finishes. Both
+/// are waitable with `cl_thread_wait_until`, so a caller can act on early
+/// items while later ones still run, or wait for the countdown to reach 0.
+/// Returns 0, -1 on a bad argument, or -2 if cancelled while waiting for room
+/// in a bounded pool (jobs not queued are taken off the countdown).
+pub(crate) unsafe extern "C" fn cl_thread_pool_broadcast(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    fn_index: i64,
+    arg_ptr: *mut u8,
+    stride: i64,
+    count: i64,
+    flags_ptr: *mut u8,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some(pool) = ctx.pools.get(&(pool as u32)) else {
+        return -1;
+    };
+    if count < 0 || !flags_ptr.cast::<u64>().is_aligned() {
+        return -1;
+    }
+    let Some(func) = compiled_fn(ctx, fn_index) else {
+        return -1;
+    };
+    let words = flags_ptr as *const AtomicU64;
+    if !flags_ptr.is_null() {
+        (*words).store(count as u64, Ordering::Relaxed);
+        for i in 0..count as usize {
+            (*words.add(1 + i)).store(0, Ordering::Relaxed);
+        }
+    }
+    for i in 0..count {
+        let done = if flags_ptr.is_null() {
+            Completion::default()
+        } else {
+            Completion {
+                flag: words.add(1 + i as usize) as usize,
+                countdown: words as usize,
+            }
+        };
+        let arg = (arg_ptr as usize).wrapping_add((i * stride) as usize);
+        if pool.shared.submit(Job::new(func, arg, done), true) != 0 {
+            if !flags_ptr.is_null() {
+                (*words).fetch_sub((count - i) as u64, Ordering::AcqRel);
+                notify_waiters(words as usize);
+            }
+            return -2;
+        }
+    }
+    0
+}
+
+/// Run `n_stages` (at most `MAX_CHAIN_STAGES`) compiled functions on
+/// `arg_ptr` one after another, each on its own pool, without returning to
+/// the caller between stages: the worker that finishes a stage queues the
+/// next onto that stage's pool. `stages_ptr` holds `n_stages` pairs of i64
+/// (pool handle, fn index), all checked before anything runs. When
+/// `flag_ptr` (8-byte aligned) is non-null it is set to 0 now and to 1 after
+/// the last stage, waitable with `cl_thread_wait_until`. Every pool in the
+/// chain must stay running until it completes. Returns 0, -1 on a bad
+/// argument, or -2 if cancelled while waiting for room in the first pool.
+pub(crate) unsafe extern "C" fn cl_thread_pool_chain(
+    ctx_ptr: *const CraneliftThreadContext,
+    stages_ptr: *const u8,
+    n_stages: i64,
+    arg_ptr: *mut u8,
+    flag_ptr: *mut u8,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let n = n_stages as usize;
+    if stages_ptr.is_null() || !(1..=MAX_CHAIN_STAGES).contains(&n) {
+        return -1;
+    }
+    if !flag_ptr.cast::<u64>().is_aligned() {
+        return -1;
+    }
+    let mut stages = Vec::with_capacity(n);
+    for i in 0..n {
+        let pair = stages_ptr.add(i * 16) as *const i64;
+        let pool = std::ptr::read_unaligned(pair);
+        let fn_index = std::ptr::read_unaligned(pair.add(1));
+        let Some(pool) = ctx.pools.get(&(pool as u32)) else {
+            return -1;
+        };
+        let Some(func) = compiled_fn(ctx, fn_index) else {
+            return -1;
+        };
+        stages.push((pool.shared.clone(), func));
+    }
+    let mut done = Completion::default();
+    if !flag_ptr.is_null() {
+        (*(flag_ptr as *const AtomicU64)).store(0, Ordering::Relaxed);
+        done.flag = flag_ptr as usize;
+    }
+    let chain = Arc::new(Chain {
+        stages,
+        arg: arg_ptr as usize,
+        done,
+    });
+    let (pool, job) = Continuation::job(&chain, 0);
+    pool.submit(job, true)
+}
+
+/// Queue a barrier: jobs submitted to `pool` after it start only once every
+/// job submitted before it has finished. When `flag_ptr` (8-byte aligned) is
+/// non-null it is set to 0 now and, once those earlier jobs are done, to 1
+/// with release ordering and a wake for `cl_thread_wait_until`, so a waiter
+/// that sees 1 also sees everything they wrote. Does not block and ignores a
+/// bounded pool's capacity. Returns 0, or -1 on a bad argument.
+pub(crate) unsafe extern "C" fn cl_thread_pool_fence(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+    flag_ptr: *mut u8,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some(pool) = ctx.pools.get(&(pool as u32)) else {
+        return -1;
+    };
+    if !flag_ptr.cast::<u64>().is_aligned() {
+        return -1;
+    }
+    let mut done = Completion::default();
+    if !flag_ptr.is_null() {
+        (*(flag_ptr as *const AtomicU64)).store(0, Ordering::Relaxed);
+        done.flag = flag_ptr as usize;
+    }
+    let job = Job {
+        fence: true,
+        ..Job::new(no_op, 0, done)
+    };
+    pool.shared.enqueue(job);
+    0
+}
+
+/// Block until every submitted job has finished. Returns 0, or -1.
+pub(crate) unsafe extern "C" fn cl_thread_pool_wait(
+    ctx_ptr: *const CraneliftThreadContext,
+    pool: i64,
+) -> i64 {
+    let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    let Some(pool) = ctx.pools.get(&(pool as u32)) else {
+        return -1;
+    };
+    let start = ctx.stats.as_ref().map(|_| Instant::now());
+    let mut state = pool.shared.state.lock().unwrap();
+    state.drain_waiters += 1;
+    while state.pending > 0 {
+        state = pool.shared.drained.wait(state).unwrap();
+    }
+    state.drain_waiters -= 1;
+    drop(state);
+    if let (Some(stats), Some(start)) = (&ctx.stats, start) {
+        let waited = start.elapsed().as_nanos() as u64;
+        stats.join_wait_ns.fetch_add(waited, Ordering::Relaxed);
+    }
+    0
+}
+
+/// Run any queued jobs, then stop and join the workers. Returns 0, or -1.
+pub(crate) unsafe extern "C" fn cl_thread_pool_stop(
+    ctx_ptr: *mut CraneliftThreadContext,
+    pool: i64,
+) -> i64 {
+    let Some(ctx) = read_ctx_mut::<CraneliftThreadContext>(ctx_ptr) else {
+        return -1;
+    };
+    match ctx.pools.remove(&(pool as u32)) {
+        Some(pool) => {
+            pool.stop();
+            0
+        }
+        None => -1,
+    }
 }
 
 pub(crate) unsafe extern "C" fn cl_thread_call(
@@ -89,18 +810,226 @@ pub(crate) unsafe extern "C" fn cl_thread_call(
     let Some(ctx) = read_ctx_ref::<CraneliftThreadContext>(ctx_ptr) else {
         return -1;
     };
-    let idx = fn_index as usize;
-    if idx >= ctx.compiled_fns.len() {
+    let Some(func) = compiled_fn(ctx, fn_index) else {
         return -1;
+    };
+    if let Some(stats) = &ctx.stats {
+        stats.inline_calls.fetch_add(1, Ordering::Relaxed);
     }
-    let func = ctx.compiled_fns[idx];
     func(arg_ptr);
     0
 }
 
+// Waiters block on the bucket their address hashes to; a wake notifies the
+// whole bucket and each waiter rechecks its own word.
+const WAIT_BUCKETS: usize = 64;
+
+#[derive(Default)]
+struct WaitBucket {
+    lock: Mutex<()>,
+    cvar: Condvar,
+    // Threads in `cl_thread_wait_until` on this bucket; a wake with none
+    // skips the lock.
+    waiters: AtomicUsize,
+}
+
+fn wait_bucket(addr: usize) -> &'static WaitBucket {
+    static BUCKETS: OnceLock<Vec<WaitBucket>> = OnceLock::new();
+    let buckets =
+        BUCKETS.get_or_init(|| (0..WAIT_BUCKETS).map(|_| WaitBucket::default()).collect());
+    &buckets[(addr >> 3) % WAIT_BUCKETS]
+}
+
+/// Wake every waiter so it can notice a cancelled execution.
+pub(crate) fn wake_all_waiters() {
+    for i in 0..WAIT_BUCKETS {
+        let bucket = wait_bucket(i << 3);
+        drop(bucket.lock.lock().unwrap());
+        bucket.cvar.notify_all();
+    }
+}
+
+/// Sleep for `duration`, returning early (with `false`) once the execution
+/// running on this thread is cancelled.
+pub(crate) fn sleep_unless_cancelled(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+    // Any bucket works: a cancel wakes them all, and the lock held across
+    // the check keeps that wake from slipping in before the wait.
+    let bucket = wait_bucket(0);
+    let mut guard = bucket.lock.lock().unwrap();
+    loop {
+        if cancel::is_cancelled() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        guard = bucket.cvar.wait_timeout(guard, deadline - now).unwrap().0;
+    }
+}
+
+/// `cl_thread_wait_until` predicates on the u64 word vs `value`.
+pub(crate) const WAIT_EQ: i64 = 0;
+pub(crate) const WAIT_NE: i64 = 1;
+pub(crate) const WAIT_GE: i64 = 2;
+
+/// Block until the 8-byte-aligned u64 at `addr` compares to `value` under
+/// `pred` (`WAIT_EQ`, `WAIT_NE`, or unsigned `WAIT_GE`). Writes made before
+/// the `cl_thread_wake` that satisfied it are visible on return. Returns 0,
+/// -1 on a bad address or predicate, or -2 if the execution was cancelled.
+pub(crate) unsafe extern "C" fn cl_thread_wait_until(
+    addr: *const u8,
+    value: i64,
+    pred: i64,
+) -> i64 {
+    let aligned = addr.cast::<u64>().is_aligned();
+    if addr.is_null() || !aligned || !matches!(pred, WAIT_EQ | WAIT_NE | WAIT_GE) {
+        return -1;
+    }
+    let word = &*(addr as *const AtomicU64);
+    let value = value as u64;
+    let satisfied = || {
+        let cur = word.load(Ordering::Acquire);
+        match pred {
+            WAIT_EQ => cur == value,
+            WAIT_NE => cur != value,
+            _ => cur >= value,
+        }
+    };
+    if satisfied() {
+        return 0;
+    }
+    let bucket = wait_bucket(addr as usize);
+    let mut guard = bucket.lock.lock().unwrap();
+    // Pairs with the fence in `notify_waiters`: either the waker sees this
+    // count, or the recheck below sees its store.
+    bucket.waiters.fetch_add(1, Ordering::Relaxed);
+    fence(Ordering::SeqCst);
+    let mut result = 0;
+    while !satisfied() {
+        if cancel::is_cancelled() {
+            result = -2;
+            break;
+        }
+        guard = bucket.cvar.wait(guard).unwrap();
+    }
+    bucket.waiters.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+/// Store `value` to the 8-byte-aligned u64 at `addr` and wake every thread
+/// blocked on it in `cl_thread_wait_until`. Returns 0, or -1 on a bad address.
+pub(crate) unsafe extern "C" fn cl_thread_wake(addr: *mut u8, value: i64) -> i64 {
+    if addr.is_null() || !addr.cast::<u64>().is_aligned() {
+        return -1;
+    }
+    (*(addr as *const AtomicU64)).store(value as u64, Ordering::Release);
+    notify_waiters(addr as usize);
+    0
+}
+
+/// Wake the threads blocked in `cl_thread_wait_until` on `addr`'s bucket.
+fn notify_waiters(addr: usize) {
+    let bucket = wait_bucket(addr);
+    fence(Ordering::SeqCst);
+    if bucket.waiters.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    // Taking the lock orders this notify after any waiter's recheck.
+    drop(bucket.lock.lock().unwrap());
+    bucket.cvar.notify_all();
+}
+
+/// Set up the 24-byte, 8-byte-aligned barrier at `barrier_ptr` for
+/// `participants` threads: a u64 packing the participant count (low 32
+/// bits) and the arrivals so far in this generation (high 32 bits, now 0),
+/// a u64 generation counter (now 0), and a u64 count of participants still
+/// to leave the last completed generation (now 0). Must not race with waits
+/// on it. Returns 0, or -1 with status `INVALID_ARGUMENT` on a bad address
+/// or a count outside 1..=u32::MAX.
+pub(crate) unsafe extern "C" fn cl_thread_barrier_init(
+    barrier_ptr: *mut u8,
+    participants: i64,
+) -> i64 {
+    let aligned = barrier_ptr.cast::<u64>().is_aligned();
+    if barrier_ptr.is_null() || !aligned || participants <= 0 || participants > u32::MAX as i64 {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let bucket = wait_bucket(barrier_ptr as usize);
+    let _guard = bucket.lock.lock().unwrap();
+    (*(barrier_ptr as *const AtomicU64)).store(participants as u64, Ordering::Relaxed);
+    (*(barrier_ptr.add(8) as *const AtomicU64)).store(0, Ordering::Relaxed);
+    (*(barrier_ptr.add(16) as *const AtomicU64)).store(0, Ordering::Relaxed);
+    status::ok(0);
+    0
+}
+
+/// Arrive at the barrier at `barrier_ptr` and block until every participant
+/// of this generation has arrived. The last arrival completes the
+/// generation, which stays full until every participant has seen it
+/// complete; only then does any of them return, so the barrier is reused as
+/// is for the following phase, by the same threads or by others, and writes
+/// made by any participant before arriving are visible to all of them on
+/// return. An arrival while the generation is full is one waiter too many
+/// and fails at once instead of blocking for a partner that never comes.
+/// Returns 1 on the last arrival and 0 on the others, with status `OK`
+/// carrying the completed generation; -1 with status `INVALID_ARGUMENT`
+/// (payload: arrivals recorded) when the barrier is not initialized or
+/// already holds as many arrivals as participants; or -2 if the execution
+/// was cancelled before the generation completed, which also withdraws the
+/// arrival.
+pub(crate) unsafe extern "C" fn cl_thread_barrier_wait(barrier_ptr: *mut u8) -> i64 {
+    if barrier_ptr.is_null() || !barrier_ptr.cast::<u64>().is_aligned() {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let counts = &*(barrier_ptr as *const AtomicU64);
+    let generation = &*(barrier_ptr.add(8) as *const AtomicU64);
+    let leaving = &*(barrier_ptr.add(16) as *const AtomicU64);
+    // Every update happens under the bucket lock, which also orders the
+    // participants' earlier writes before everyone's return.
+    let bucket = wait_bucket(barrier_ptr as usize);
+    let mut guard = bucket.lock.lock().unwrap();
+    let packed = counts.load(Ordering::Relaxed);
+    let (participants, arrived) = (packed as u32, (packed >> 32) as u32);
+    if participants == 0 || arrived >= participants {
+        status::set(INVALID_ARGUMENT, arrived as u64);
+        return -1;
+    }
+    counts.store(packed + (1 << 32), Ordering::Relaxed);
+    let current = generation.load(Ordering::Relaxed);
+    let last = arrived + 1 == participants;
+    if last {
+        generation.store(current.wrapping_add(1), Ordering::Relaxed);
+        leaving.store(participants as u64, Ordering::Relaxed);
+        bucket.cvar.notify_all();
+    }
+    while generation.load(Ordering::Relaxed) == current {
+        if cancel::is_cancelled() {
+            counts.fetch_sub(1 << 32, Ordering::Relaxed);
+            status::set(FAILED, 0);
+            return -2;
+        }
+        guard = bucket.cvar.wait(guard).unwrap();
+    }
+    // The last participant to leave empties the generation for the next.
+    if leaving.fetch_sub(1, Ordering::Relaxed) == 1 {
+        counts.store(participants as u64, Ordering::Relaxed);
+        bucket.cvar.notify_all();
+    }
+    while leaving.load(Ordering::Relaxed) != 0 {
+        guard = bucket.cvar.wait(guard).unwrap();
+    }
+    status::ok(current.wrapping_add(1));
+    last as i64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use base_types::status::OK;
 
     unsafe extern "C" fn write_42(p: *mut u8) {
         *(p as *mut u64) = 42;
@@ -281,4 +1210,613 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn stats_count_spawns_calls_and_busy_time() {
+        install_fns(vec![slow_write_77, write_42]);
+        let stats = Arc::new(ThreadStats::default());
+        THREAD_STATS.with(|cell| *cell.borrow_mut() = Some(stats.clone()));
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut v0: u64 = 0;
+        let mut v1: u64 = 0;
+        unsafe {
+            cl_thread_init(&mut slot);
+            let h = cl_thread_spawn(slot, 0, &mut v0 as *mut u64 as *mut u8);
+            assert_eq!(cl_thread_join(slot, h), 0);
+            assert_eq!(cl_thread_call(slot, 1, &mut v1 as *mut u64 as *mut u8), 0);
+            cl_thread_cleanup(&mut slot);
+        }
+        THREAD_STATS.with(|cell| *cell.borrow_mut() = None);
+        assert_eq!(stats.spawned.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.inline_calls.load(Ordering::Relaxed), 1);
+        assert!(stats.busy_ns.load(Ordering::Relaxed) >= 20_000_000);
+        assert!(stats.join_wait_ns.load(Ordering::Relaxed) > 0);
+    }
+
+    unsafe extern "C" fn copy_first_to_second(p: *mut u8) {
+        let v = *(p as *const u64);
+        *(p as *mut u64).add(1) = v;
+    }
+
+    #[test]
+    fn pool_runs_every_submitted_job() {
+        install_fns(vec![copy_first_to_second]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut slots: Vec<[u64; 2]> = (0..1000).map(|i| [i + 1, 0]).collect();
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 4);
+            assert!(pool > 0);
+            for s in slots.iter_mut() {
+                assert_eq!(
+                    cl_thread_pool_submit(slot, pool, 0, s.as_mut_ptr() as *mut u8),
+                    0
+                );
+            }
+            assert_eq!(cl_thread_pool_wait(slot, pool), 0);
+            assert!(slots.iter().all(|s| s[0] == s[1]));
+            assert_eq!(cl_thread_pool_stop(slot, pool), 0);
+            assert_eq!(cl_thread_pool_stop(slot, pool), -1);
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn pool_stop_drains_queue_and_cleanup_stops_pools() {
+        install_fns(vec![slow_write_77, write_42]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut vals = [0u64; 3];
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 1);
+            for (i, v) in vals.iter_mut().enumerate() {
+                let f = (i > 0) as i64;
+                cl_thread_pool_submit(slot, pool, f, v as *mut u64 as *mut u8);
+            }
+            assert_eq!(cl_thread_pool_stop(slot, pool), 0);
+            assert_eq!(vals, [77, 42, 42]);
+
+            let pool = cl_thread_pool_start(slot, 2);
+            vals = [0; 3];
+            cl_thread_pool_submit(slot, pool, 0, &mut vals[0] as *mut u64 as *mut u8);
+            cl_thread_cleanup(&mut slot);
+        }
+        assert_eq!(vals[0], 77, "cleanup should stop pools after draining them");
+    }
+
+    #[test]
+    fn pool_rejects_invalid_arguments() {
+        install_fns(vec![write_42]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut val = 0u64;
+        unsafe {
+            cl_thread_init(&mut slot);
+            assert_eq!(cl_thread_pool_start(slot, 0), -1);
+            let pool = cl_thread_pool_start(slot, 1);
+            assert_eq!(
+                cl_thread_pool_submit(slot, pool, 9, &mut val as *mut u64 as *mut u8),
+                -1
+            );
+            assert_eq!(
+                cl_thread_pool_submit(slot, 999, 0, &mut val as *mut u64 as *mut u8),
+                -1
+            );
+            assert_eq!(cl_thread_pool_wait(slot, 999), -1);
+            assert_eq!(cl_thread_pool_wait(slot, pool), 0);
+            assert_eq!(cl_thread_pool_start(std::ptr::null_mut(), 1), -1);
+            cl_thread_cleanup(&mut slot);
+        }
+        assert_eq!(val, 0);
+    }
+
+    // Marks word 0 as started, then spins until word 1 is set.
+    unsafe extern "C" fn gate(p: *mut u8) {
+        let words = &*(p as *const [AtomicU64; 2]);
+        words[0].store(1, Ordering::Release);
+        while words[1].load(Ordering::Acquire) == 0 {
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn bounded_pool_try_submit_reports_full_queue() {
+        install_fns(vec![gate, write_42]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let gate_words = [AtomicU64::new(0), AtomicU64::new(0)];
+        let mut vals = [0u64; 3];
+        unsafe {
+            cl_thread_init(&mut slot);
+            assert_eq!(cl_thread_pool_start_bounded(slot, 1, 0), -1);
+            let pool = cl_thread_pool_start_bounded(slot, 1, 2);
+            assert!(pool > 0);
+            // Occupy the only worker so queued jobs stay queued.
+            let gate_ptr = &gate_words as *const _ as *mut u8;
+            assert_eq!(cl_thread_pool_submit(slot, pool, 0, gate_ptr), 0);
+            while gate_words[0].load(Ordering::Acquire) == 0 {
+                std::thread::yield_now();
+            }
+            let ptrs: Vec<*mut u8> = vals.iter_mut().map(|v| v as *mut u64 as *mut u8).collect();
+            assert_eq!(cl_thread_pool_try_submit(slot, pool, 1, ptrs[0]), 0);
+            assert_eq!(cl_thread_pool_try_submit(slot, pool, 1, ptrs[1]), 0);
+            assert_eq!(cl_thread_pool_try_submit(slot, pool, 1, ptrs[2]), -2);
+
+            gate_words[1].store(1, Ordering::Release);
+            assert_eq!(cl_thread_pool_wait(slot, pool), 0);
+            cl_thread_cleanup(&mut slot);
+        }
+        assert_eq!(vals, [42, 42, 0]);
+    }
+
+    // Copies word 1 to word 2 after sleeping word 0 milliseconds.
+    unsafe extern "C" fn delayed_copy(p: *mut u8) {
+        let words = p as *mut u64;
+        std::thread::sleep(Duration::from_millis(*words));
+        *words.add(2) = *words.add(1);
+    }
+
+    #[test]
+    fn broadcast_flags_report_items_as_they_finish() {
+        install_fns(vec![delayed_copy]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        // Items 2 and 3 are held back, so item 1 finishes well before them.
+        let mut items: Vec<[u64; 3]> = [0, 0, 300, 300]
+            .iter()
+            .enumerate()
+            .map(|(i, &delay)| [delay, 10 + i as u64, 0])
+            .collect();
+        let flags: Vec<AtomicU64> = (0..5).map(|_| AtomicU64::new(9)).collect();
+        let flags_ptr = flags.as_ptr() as *mut u8;
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 4);
+            let arg = items.as_mut_ptr() as *mut u8;
+            assert_eq!(
+                cl_thread_pool_broadcast(slot, pool, 0, arg, 24, 4, flags_ptr),
+                0
+            );
+
+            assert_eq!(cl_thread_wait_until(flags_ptr.add(16), 1, WAIT_EQ), 0);
+            assert_eq!(std::ptr::read_volatile(&items[1][2]), 11);
+            assert!(
+                flags[0].load(Ordering::Acquire) >= 2,
+                "items 2 and 3 pending"
+            );
+            assert_eq!(flags[3].load(Ordering::Acquire), 0);
+
+            assert_eq!(cl_thread_wait_until(flags_ptr, 0, WAIT_EQ), 0);
+            assert!(flags[1..].iter().all(|f| f.load(Ordering::Acquire) == 1));
+            assert!(items
+                .iter()
+                .enumerate()
+                .all(|(i, it)| it[2] == 10 + i as u64));
+
+            let misaligned = flags_ptr.add(1);
+            assert_eq!(
+                cl_thread_pool_broadcast(slot, pool, 0, arg, 24, 1, misaligned),
+                -1
+            );
+            assert_eq!(
+                cl_thread_pool_broadcast(slot, pool, 0, arg, 24, -1, flags_ptr),
+                -1
+            );
+            assert_eq!(
+                cl_thread_pool_broadcast(slot, pool, 5, arg, 24, 1, flags_ptr),
+                -1
+            );
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn dispatch_runs_one_fn_on_each_operand_block() {
+        install_fns(vec![copy_first_to_second]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        // Each block: completion flag, then the two operand words.
+        let mut blocks: Vec<[u64; 3]> = (0..3).map(|i| [9, 100 + i, 0]).collect();
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 2);
+            for b in blocks.iter_mut() {
+                let block = b.as_mut_ptr() as *mut u8;
+                assert_eq!(cl_thread_pool_dispatch(slot, pool, 0, block), 0);
+            }
+            for b in blocks.iter_mut() {
+                let flag = b.as_mut_ptr() as *mut u8;
+                assert_eq!(cl_thread_wait_until(flag, 1, WAIT_EQ), 0);
+            }
+            for (i, b) in blocks.iter().enumerate() {
+                assert_eq!(b[2], 100 + i as u64);
+            }
+
+            let block = blocks[0].as_mut_ptr() as *mut u8;
+            assert_eq!(cl_thread_pool_dispatch(slot, pool, 0, block.add(4)), -1);
+            let null = std::ptr::null_mut();
+            assert_eq!(cl_thread_pool_dispatch(slot, pool, 0, null), -1);
+            assert_eq!(cl_thread_pool_dispatch(slot, pool, 3, block), -1);
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn dispatch_if_skips_but_still_completes() {
+        install_fns(vec![copy_first_to_second]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut blocks: Vec<[u64; 3]> = (0..2).map(|i| [9, 100 + i, 0]).collect();
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 2);
+            for (cond, b) in blocks.iter_mut().enumerate() {
+                let block = b.as_mut_ptr() as *mut u8;
+                let expected = if cond == 0 { 1 } else { 0 };
+                let ret = cl_thread_pool_dispatch_if(slot, pool, 0, block, cond as i64);
+                assert_eq!(ret, expected);
+                assert_eq!(cl_thread_wait_until(block, 1, WAIT_EQ), 0);
+            }
+            assert_eq!(blocks[0][2], 0, "skipped");
+            assert_eq!(blocks[1][2], 101, "dispatched");
+
+            let block = blocks[0].as_mut_ptr() as *mut u8;
+            blocks[0][0] = 0;
+            assert_eq!(cl_thread_pool_dispatch_if(slot, pool, 3, block, 0), -1);
+            assert_eq!(cl_thread_pool_dispatch_if(slot, 99, 0, block, 0), -1);
+            assert_eq!(blocks[0][0], 0, "flag untouched on a bad argument");
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    unsafe extern "C" fn add_one(p: *mut u8) {
+        *(p as *mut u64) += 1;
+    }
+    unsafe extern "C" fn triple(p: *mut u8) {
+        *(p as *mut u64) *= 3;
+    }
+
+    #[test]
+    fn chain_runs_stages_in_order_across_pools() {
+        install_fns(vec![add_one, triple]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut val: u64 = 4;
+        let flag = AtomicU64::new(9);
+        let flag_ptr = &flag as *const AtomicU64 as *mut u8;
+        let arg = &mut val as *mut u64 as *mut u8;
+        unsafe {
+            cl_thread_init(&mut slot);
+            let a = cl_thread_pool_start(slot, 1);
+            let b = cl_thread_pool_start(slot, 2);
+            // ((4 + 1) * 3 + 1) * 3 = 48, alternating pools.
+            let stages: [i64; 8] = [a, 0, b, 1, a, 0, b, 1];
+            let stages_ptr = stages.as_ptr() as *const u8;
+            assert_eq!(cl_thread_pool_chain(slot, stages_ptr, 4, arg, flag_ptr), 0);
+            assert_eq!(cl_thread_wait_until(flag_ptr, 1, WAIT_EQ), 0);
+            assert_eq!(std::ptr::read_volatile(&val), 48);
+
+            // Without a flag, waiting on each pool in stage order suffices.
+            let null = std::ptr::null_mut();
+            assert_eq!(cl_thread_pool_chain(slot, stages_ptr, 2, arg, null), 0);
+            assert_eq!(cl_thread_pool_wait(slot, a), 0);
+            assert_eq!(cl_thread_pool_wait(slot, b), 0);
+            assert_eq!(std::ptr::read_volatile(&val), 147);
+
+            assert_eq!(cl_thread_pool_chain(slot, stages_ptr, 0, arg, null), -1);
+            assert_eq!(cl_thread_pool_chain(slot, stages_ptr, 9, arg, null), -1);
+            let bad_fn: [i64; 4] = [a, 0, b, 2];
+            let bad_fn_ptr = bad_fn.as_ptr() as *const u8;
+            assert_eq!(cl_thread_pool_chain(slot, bad_fn_ptr, 2, arg, null), -1);
+            let bad_pool: [i64; 4] = [a, 0, 99, 1];
+            let bad_pool_ptr = bad_pool.as_ptr() as *const u8;
+            assert_eq!(cl_thread_pool_chain(slot, bad_pool_ptr, 2, arg, null), -1);
+            let misaligned = flag_ptr.add(1);
+            assert_eq!(
+                cl_thread_pool_chain(slot, stages_ptr, 1, arg, misaligned),
+                -1
+            );
+            assert_eq!(std::ptr::read_volatile(&val), 147, "nothing ran");
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn fence_holds_later_jobs_until_earlier_ones_finish() {
+        install_fns(vec![slow_write_77, copy_first_to_second]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let mut pairs = [[0u64; 2]; 4];
+        let flag = AtomicU64::new(9);
+        let flag_ptr = &flag as *const AtomicU64 as *mut u8;
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 4);
+            let ptrs: Vec<*mut u8> = pairs
+                .iter_mut()
+                .map(|p| p.as_mut_ptr() as *mut u8)
+                .collect();
+            for &p in &ptrs {
+                assert_eq!(cl_thread_pool_submit(slot, pool, 0, p), 0);
+            }
+            assert_eq!(cl_thread_pool_fence(slot, pool, flag_ptr), 0);
+            // Idle workers would take these at once without the fence.
+            for &p in &ptrs {
+                assert_eq!(cl_thread_pool_submit(slot, pool, 1, p), 0);
+            }
+            assert_eq!(cl_thread_wait_until(flag_ptr, 1, WAIT_EQ), 0);
+            for p in &ptrs {
+                assert_eq!(std::ptr::read_volatile(*p as *const u64), 77);
+            }
+            assert_eq!(cl_thread_pool_wait(slot, pool), 0);
+            assert_eq!(std::ptr::read_volatile(&pairs), [[77, 77]; 4]);
+
+            let null = std::ptr::null_mut();
+            assert_eq!(cl_thread_pool_fence(slot, pool, null), 0);
+            assert_eq!(cl_thread_pool_fence(slot, 99, null), -1);
+            assert_eq!(cl_thread_pool_fence(slot, pool, flag_ptr.add(1)), -1);
+            assert_eq!(cl_thread_pool_fence(std::ptr::null(), pool, null), -1);
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn fence_hands_plain_writes_across_workers() {
+        // Each round, one job copies the round number from word 0 to 1 and,
+        // behind a fence, another copies word 1 to 2, on whichever workers
+        // are free. Plain reads must always see the previous stage's writes.
+        install_fns(vec![copy_first_to_second]);
+        let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+        let words = [0u64, 0, 0].map(AtomicU64::new);
+        let flags = [AtomicU64::new(0), AtomicU64::new(0)];
+        let flag_ptr = |i: usize| &flags[i] as *const AtomicU64 as *mut u8;
+        let word_ptr = |i: usize| &words[i] as *const AtomicU64 as *mut u8;
+        unsafe {
+            cl_thread_init(&mut slot);
+            let pool = cl_thread_pool_start(slot, 4);
+            for round in 1..=2000u64 {
+                *word_ptr(0).cast::<u64>() = round;
+                assert_eq!(cl_thread_pool_submit(slot, pool, 0, word_ptr(0)), 0);
+                assert_eq!(cl_thread_pool_fence(slot, pool, flag_ptr(0)), 0);
+                assert_eq!(cl_thread_pool_submit(slot, pool, 0, word_ptr(1)), 0);
+                assert_eq!(cl_thread_pool_fence(slot, pool, flag_ptr(1)), 0);
+                assert_eq!(cl_thread_wait_until(flag_ptr(1), 1, WAIT_EQ), 0);
+                assert_eq!(flags[0].load(Ordering::Acquire), 1);
+                assert_eq!(*word_ptr(2).cast::<u64>(), round);
+            }
+            cl_thread_cleanup(&mut slot);
+        }
+    }
+
+    #[test]
+    fn wait_until_sees_data_written_before_wake() {
+        let mut buf = Box::new([0u64; 4]);
+        let base = buf.as_mut_ptr() as usize;
+        let consumer = std::thread::spawn(move || unsafe {
+            let flag = (base + 8) as *const u8;
+            assert_eq!(cl_thread_wait_until(flag, 1, WAIT_EQ), 0);
+            let data = *(base as *const u64);
+            *((base + 16) as *mut u64) = data * 2;
+            cl_thread_wake((base + 24) as *mut u8, 2);
+        });
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        unsafe {
+            *(base as *mut u64) = 21;
+            assert_eq!(cl_thread_wake((base + 8) as *mut u8, 1), 0);
+            assert_eq!(
+                cl_thread_wait_until((base + 24) as *const u8, 2, WAIT_GE),
+                0
+            );
+        }
+        consumer.join().unwrap();
+        assert_eq!(buf[2], 42);
+    }
+
+    #[test]
+    fn wait_until_returns_immediately_when_satisfied() {
+        let word = [5u64];
+        let p = word.as_ptr() as *const u8;
+        unsafe {
+            assert_eq!(cl_thread_wait_until(p, 5, WAIT_EQ), 0);
+            assert_eq!(cl_thread_wait_until(p, 0, WAIT_NE), 0);
+            assert_eq!(cl_thread_wait_until(p, 3, WAIT_GE), 0);
+        }
+    }
+
+    /// Run `f` on its own thread, failing if it does not finish within
+    /// `secs`: a lost wake-up shows up as a hang.
+    fn finishes_within(secs: u64, f: impl FnOnce() + Send + 'static) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            f();
+            tx.send(()).unwrap();
+        });
+        rx.recv_timeout(Duration::from_secs(secs))
+            .expect("stalled or panicked: a wake-up was lost");
+    }
+
+    #[test]
+    fn wake_and_wait_race_on_one_bucket() {
+        // Two threads ping-pong through words 512 bytes apart, which share
+        // a wait bucket, so every wake races the other side's recheck.
+        const ROUNDS: u64 = 20_000;
+        finishes_within(60, || {
+            let words = Arc::new([const { AtomicU64::new(0) }; 65]);
+            let (ping, pong) = (words[0].as_ptr() as usize, words[64].as_ptr() as usize);
+            assert!(std::ptr::eq(wait_bucket(ping), wait_bucket(pong)));
+            let keep = words.clone();
+            let echo = std::thread::spawn(move || {
+                let _keep = keep;
+                for i in 1..=ROUNDS as i64 {
+                    unsafe {
+                        assert_eq!(cl_thread_wait_until(ping as *const u8, i, WAIT_GE), 0);
+                        assert_eq!(cl_thread_wake(pong as *mut u8, i), 0);
+                    }
+                }
+            });
+            for i in 1..=ROUNDS as i64 {
+                unsafe {
+                    assert_eq!(cl_thread_wake(ping as *mut u8, i), 0);
+                    assert_eq!(cl_thread_wait_until(pong as *const u8, i, WAIT_GE), 0);
+                }
+            }
+            echo.join().unwrap();
+            assert_eq!(words[64].load(Ordering::Relaxed), ROUNDS);
+        });
+    }
+
+    #[test]
+    fn pool_submit_races_idle_workers() {
+        // One job at a time, so the workers go idle between submits and each
+        // submit races a worker about to sleep; dispatches do the same for
+        // completion flags waited on with `cl_thread_wait_until`.
+        const ROUNDS: u64 = 5_000;
+        finishes_within(60, || {
+            install_fns(vec![copy_first_to_second]);
+            let mut slot: *mut CraneliftThreadContext = std::ptr::null_mut();
+            let mut job = [0u64; 2];
+            let mut block = [0u64; 3];
+            unsafe {
+                cl_thread_init(&mut slot);
+                let pool = cl_thread_pool_start(slot, 2);
+                for i in 1..=ROUNDS {
+                    job[0] = i;
+                    let arg = job.as_mut_ptr() as *mut u8;
+                    assert_eq!(cl_thread_pool_submit(slot, pool, 0, arg), 0);
+                    assert_eq!(cl_thread_pool_wait(slot, pool), 0);
+                    assert_eq!(job[1], i);
+
+                    block[1] = i;
+                    let flag = block.as_mut_ptr() as *mut u8;
+                    assert_eq!(cl_thread_pool_dispatch(slot, pool, 0, flag), 0);
+                    assert_eq!(cl_thread_wait_until(flag, 1, WAIT_EQ), 0);
+                    assert_eq!(block[2], i);
+                }
+                cl_thread_cleanup(&mut slot);
+            }
+        });
+    }
+
+    #[test]
+    fn wait_and_wake_reject_invalid() {
+        let mut word = [0u64; 2];
+        let p = word.as_mut_ptr() as *mut u8;
+        unsafe {
+            assert_eq!(cl_thread_wait_until(p, 0, 7), -1);
+            assert_eq!(cl_thread_wait_until(p.add(4), 0, WAIT_EQ), -1);
+            assert_eq!(cl_thread_wait_until(std::ptr::null(), 0, WAIT_EQ), -1);
+            assert_eq!(cl_thread_wake(p.add(1), 1), -1);
+        }
+    }
+
+    #[test]
+    fn barrier_orders_phases_across_generations() {
+        let mut barrier = [0u64; 3];
+        let b = barrier.as_mut_ptr() as usize;
+        unsafe { assert_eq!(cl_thread_barrier_init(b as *mut u8, 4), 0) };
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let leaders = Arc::new(AtomicUsize::new(0));
+        let workers: Vec<_> = (0..4)
+            .map(|w| {
+                let (log, leaders) = (log.clone(), leaders.clone());
+                std::thread::spawn(move || {
+                    for phase in 0..3 {
+                        std::thread::sleep(Duration::from_millis(w * 3));
+                        log.lock().unwrap().push(phase);
+                        let r = unsafe { cl_thread_barrier_wait(b as *mut u8) };
+                        assert!(r == 0 || r == 1);
+                        leaders.fetch_add(r as usize, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+        let log = log.lock().unwrap();
+        assert!(log.windows(2).all(|p| p[0] <= p[1]), "{log:?}");
+        assert_eq!(log.len(), 12);
+        assert_eq!(leaders.load(Ordering::Relaxed), 3);
+        assert_eq!(barrier, [4, 3, 0]);
+    }
+
+    #[test]
+    fn barrier_is_reused_by_new_threads() {
+        // Three rounds of four fresh threads, each waiting twice.
+        let mut barrier = [0u64; 3];
+        let b = barrier.as_mut_ptr() as usize;
+        unsafe { assert_eq!(cl_thread_barrier_init(b as *mut u8, 4), 0) };
+        for round in 0..3u64 {
+            let waiters: Vec<_> = (0..4)
+                .map(|_| {
+                    std::thread::spawn(move || {
+                        (0..2)
+                            .map(|_| unsafe { cl_thread_barrier_wait(b as *mut u8) })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            let mut results: Vec<i64> = waiters
+                .into_iter()
+                .flat_map(|w| w.join().unwrap())
+                .collect();
+            results.sort();
+            assert_eq!(results, [0, 0, 0, 0, 0, 0, 1, 1], "round {round}");
+            assert_eq!(barrier, [4, 2 * round + 2, 0]);
+        }
+    }
+
+    #[test]
+    fn barrier_never_strands_the_waiter_beyond_participants() {
+        // Five threads wait once on a barrier for four: four pass together.
+        // The fifth fails at once if it arrives while they are leaving, and
+        // otherwise waits for a next generation until the cancel.
+        let mut barrier = [0u64; 3];
+        let b = barrier.as_mut_ptr() as usize;
+        unsafe { assert_eq!(cl_thread_barrier_init(b as *mut u8, 4), 0) };
+        let token = Arc::new(AtomicBool::new(false));
+        let waiters: Vec<_> = (0..5)
+            .map(|_| {
+                let token = token.clone();
+                std::thread::spawn(move || {
+                    cancel::set_token(Some(token));
+                    let r = unsafe { cl_thread_barrier_wait(b as *mut u8) };
+                    (r, status::word() as u32)
+                })
+            })
+            .collect();
+        std::thread::sleep(Duration::from_millis(200));
+        token.store(true, Ordering::Release);
+        wake_all_waiters();
+        let mut results: Vec<_> = waiters.into_iter().map(|w| w.join().unwrap()).collect();
+        results.sort();
+        assert!(
+            matches!(results[0], (-1, INVALID_ARGUMENT) | (-2, FAILED)),
+            "{results:?}"
+        );
+        assert_eq!(results[1..], [(0, OK), (0, OK), (0, OK), (1, OK)]);
+        assert_eq!(barrier, [4, 1, 0]);
+    }
+
+    #[test]
+    fn barrier_rejects_misuse() {
+        let mut barrier = [0u64; 3];
+        let p = barrier.as_mut_ptr() as *mut u8;
+        unsafe {
+            assert_eq!(cl_thread_barrier_wait(p), -1);
+            assert_eq!(status::word() as u32, INVALID_ARGUMENT);
+            assert_eq!(cl_thread_barrier_init(p, 0), -1);
+            assert_eq!(cl_thread_barrier_init(p.add(4), 2), -1);
+            assert_eq!(cl_thread_barrier_init(p, 1), 0);
+            assert_eq!(cl_thread_barrier_wait(p), 1);
+            assert_eq!(cl_thread_barrier_wait(p), 1);
+            assert_eq!(barrier[1], 2);
+            // Any thread may take part.
+            let addr = p as usize;
+            let other = std::thread::spawn(move || cl_thread_barrier_wait(addr as *mut u8));
+            assert_eq!(other.join().unwrap(), 1);
+            // A full generation whose participants are still leaving.
+            *(p as *mut u64) = 2 | 2 << 32;
+            *(p.add(16) as *mut u64) = 1;
+            assert_eq!(cl_thread_barrier_wait(p), -1);
+            assert_eq!(status::word() >> 32, 2);
+            // Two arrivals recorded against one participant.
+            *(p as *mut u64) = 1 | 2 << 32;
+            assert_eq!(cl_thread_barrier_wait(p), -1);
+            assert_eq!(status::word() >> 32, 2);
+        }
+    }
 }
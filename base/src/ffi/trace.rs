@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::time::Instant;
+
+use base_types::TraceEvent;
+
+struct TraceLog {
+    start: Instant,
+    events: Vec<TraceEvent>,
+}
+
+thread_local! {
+    // Only the executing thread records; calls from spawned workers are dropped.
+    static TRACE_LOG: RefCell<Option<TraceLog>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn begin() {
+    TRACE_LOG.with(|cell| {
+        *cell.borrow_mut() = Some(TraceLog {
+            start: Instant::now(),
+            events: Vec::new(),
+        });
+    });
+}
+
+pub(crate) fn finish() -> Vec<TraceEvent> {
+    TRACE_LOG.with(|cell| {
+        cell.borrow_mut()
+            .take()
+            .map(|log| log.events)
+            .unwrap_or_default()
+    })
+}
+
+/// Record a trace event with a caller-chosen `tag` (e.g. a block or step id)
+/// and four operand values. Returns 1 if recorded, 0 when tracing is off.
+pub(crate) unsafe extern "C" fn cl_trace(tag: i64, a: i64, b: i64, c: i64, d: i64) -> i64 {
+    TRACE_LOG.with(|cell| match cell.borrow_mut().as_mut() {
+        Some(log) => {
+            let elapsed_ns = log.start.elapsed().as_nanos() as u64;
+            log.events.push(TraceEvent {
+                tag,
+                args: [a, b, c, d],
+                elapsed_ns,
+            });
+            1
+        }
+        None => 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trace_disabled_records_nothing() {
+        finish();
+        assert_eq!(unsafe { cl_trace(1, 2, 3, 4, 5) }, 0);
+        assert!(finish().is_empty());
+    }
+
+    #[test]
+    fn trace_records_events_in_order() {
+        begin();
+        unsafe {
+            assert_eq!(cl_trace(1, 10, 0, 0, 0), 1);
+            assert_eq!(cl_trace(2, 20, 21, 22, 23), 1);
+        }
+        let events = finish();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].tag, events[0].args), (1, [10, 0, 0, 0]));
+        assert_eq!((events[1].tag, events[1].args), (2, [20, 21, 22, 23]));
+        assert!(events[0].elapsed_ns <= events[1].elapsed_ns);
+        assert!(finish().is_empty());
+    }
+
+    #[test]
+    fn trace_ignores_other_threads() {
+        begin();
+        std::thread::spawn(|| unsafe { cl_trace(9, 0, 0, 0, 0) })
+            .join()
+            .unwrap();
+        assert!(finish().is_empty());
+    }
+}
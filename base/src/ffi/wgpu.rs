@@ -1,14 +1,24 @@
+use half::f16;
+use half::slice::HalfFloatSliceExt;
 use pollster::block_on;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use wgpu::{
-    BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    AdapterInfo, Backends, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
     BindingResource, BindingType, BufferBindingType, BufferDescriptor, BufferUsages,
     CommandEncoderDescriptor, ComputePassDescriptor, ComputePipelineDescriptor, DeviceDescriptor,
+    DeviceType, ErrorFilter, Features,
     InstanceDescriptor, PipelineCompilationOptions, PipelineLayoutDescriptor, PowerPreference,
     RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, ShaderStages,
 };
 
-use super::{clear_ctx_slot, read_ctx_mut, read_ctx_ref, write_ctx_slot};
+use super::gpu_cpu::CpuGpuContext;
+use super::thread::cl_thread_wake;
+use super::{clear_ctx_slot, read_ctx_mut, status, write_ctx_slot};
+use crate::jit::THREAD_COMPILED_FNS;
+use base_types::status::INVALID_ARGUMENT;
+use tracing::error;
 
 // Shared wgpu handles. Creating many wgpu Devices exhausts OS GPU driver handles
 // (~60 limit), so there is exactly one Instance/Adapter/Device/Queue per process.
@@ -24,26 +34,191 @@ pub(crate) struct GpuHandles {
     pub(crate) queue: Arc<wgpu::Queue>,
 }
 
-pub(crate) fn cached_gpu_handles() -> GpuHandles {
-    use std::sync::OnceLock;
-    static GPU: OnceLock<GpuHandles> = OnceLock::new();
-    GPU.get_or_init(|| {
-        let instance = wgpu::Instance::new(InstanceDescriptor::default());
-        let adapter = block_on(instance.request_adapter(&RequestAdapterOptions {
+static GPU: OnceLock<GpuHandles> = OnceLock::new();
+
+/// How `select_gpu_adapter` chooses the process-wide adapter.
+#[derive(Debug, Clone)]
+pub struct GpuPreferences {
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    /// Only accept a software (CPU) adapter.
+    pub force_fallback_adapter: bool,
+    /// Case-insensitive filter on `AdapterInfo::name`.
+    pub adapter_name_substring: Option<String>,
+    /// Open the device with `Features::SHADER_F16`, so WGSL can
+    /// `enable f16;`. Adapters without it are skipped.
+    pub shader_f16: bool,
+}
+
+impl Default for GpuPreferences {
+    fn default() -> Self {
+        GpuPreferences {
+            backends: Backends::all(),
             power_preference: PowerPreference::HighPerformance,
-            ..Default::default()
-        }))
-        .expect("Failed to find GPU adapter");
-        let (device, queue) = block_on(adapter.request_device(&DeviceDescriptor::default(), None))
-            .expect("Failed to create GPU device");
-        GpuHandles {
-            instance: Arc::new(instance),
-            adapter: Arc::new(adapter),
-            device: Arc::new(device),
-            queue: Arc::new(queue),
+            force_fallback_adapter: false,
+            adapter_name_substring: None,
+            shader_f16: false,
         }
-    })
-    .clone()
+    }
+}
+
+fn open_device(
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
+    required_features: Features,
+) -> GpuHandles {
+    let desc = DeviceDescriptor {
+        required_features,
+        ..Default::default()
+    };
+    let (device, queue) =
+        block_on(adapter.request_device(&desc, None)).expect("Failed to create GPU device");
+    GpuHandles {
+        instance: Arc::new(instance),
+        adapter: Arc::new(adapter),
+        device: Arc::new(device),
+        queue: Arc::new(queue),
+    }
+}
+
+pub(crate) fn cached_gpu_handles() -> GpuHandles {
+    try_gpu_handles().expect("Failed to find GPU adapter")
+}
+
+/// The shared handles, opening the device on first use; `None` when no
+/// adapter is available.
+fn try_gpu_handles() -> Option<GpuHandles> {
+    if let Some(h) = GPU.get() {
+        return Some(h.clone());
+    }
+    let instance = wgpu::Instance::new(InstanceDescriptor::default());
+    let adapter = block_on(instance.request_adapter(&RequestAdapterOptions {
+        power_preference: PowerPreference::HighPerformance,
+        ..Default::default()
+    }))?;
+    Some(
+        GPU.get_or_init(|| open_device(instance, adapter, Features::empty()))
+            .clone(),
+    )
+}
+
+pub(crate) fn enumerate_adapters(backends: Backends) -> Vec<AdapterInfo> {
+    let instance = wgpu::Instance::new(InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    instance
+        .enumerate_adapters(backends)
+        .iter()
+        .map(|a| a.get_info())
+        .collect()
+}
+
+/// Open the shared device on the adapter `prefs` picks. Must run before any
+/// GPU FFI call, since the device is created once per process.
+pub(crate) fn select_adapter(prefs: &GpuPreferences) -> Result<AdapterInfo, String> {
+    if let Some(h) = GPU.get() {
+        return Err(format!(
+            "GPU device already initialized on adapter `{}`",
+            h.adapter.get_info().name
+        ));
+    }
+    let instance = wgpu::Instance::new(InstanceDescriptor {
+        backends: prefs.backends,
+        ..Default::default()
+    });
+    let mut adapters = instance.enumerate_adapters(prefs.backends);
+    let features = if prefs.shader_f16 {
+        Features::SHADER_F16
+    } else {
+        Features::empty()
+    };
+    if !features.is_empty() {
+        let names: Vec<String> = adapters.iter().map(|a| a.get_info().name).collect();
+        adapters.retain(|a| a.features().contains(features));
+        if adapters.is_empty() {
+            return Err(format!(
+                "no GPU adapter supports {features:?}; available: [{}]",
+                names.join(", ")
+            ));
+        }
+    }
+    let infos: Vec<AdapterInfo> = adapters.iter().map(|a| a.get_info()).collect();
+    let idx = pick_adapter(&infos, prefs)?;
+    let info = infos[idx].clone();
+    GPU.set(open_device(instance, adapters.swap_remove(idx), features))
+        .map_err(|_| "GPU device was initialized concurrently".to_string())?;
+    Ok(info)
+}
+
+/// Index of the adapter in `infos` best matching `prefs`, or a message
+/// listing what is available.
+fn pick_adapter(infos: &[AdapterInfo], prefs: &GpuPreferences) -> Result<usize, String> {
+    let needle = prefs.adapter_name_substring.as_deref().map(str::to_lowercase);
+    infos
+        .iter()
+        .enumerate()
+        .filter(|(_, info)| {
+            needle
+                .as_ref()
+                .is_none_or(|n| info.name.to_lowercase().contains(n))
+        })
+        .filter(|(_, info)| !prefs.force_fallback_adapter || info.device_type == DeviceType::Cpu)
+        .min_by_key(|(_, info)| adapter_rank(info.device_type, prefs.power_preference))
+        .map(|(i, _)| i)
+        .ok_or_else(|| {
+            let available: Vec<String> = infos
+                .iter()
+                .map(|i| format!("{} ({:?}, {:?})", i.name, i.device_type, i.backend))
+                .collect();
+            format!(
+                "no GPU adapter matches {prefs:?}; available: [{}]",
+                available.join(", ")
+            )
+        })
+}
+
+fn adapter_rank(device_type: DeviceType, power: PowerPreference) -> u8 {
+    match (device_type, power) {
+        (DeviceType::DiscreteGpu, PowerPreference::HighPerformance) => 0,
+        (DeviceType::IntegratedGpu, PowerPreference::LowPower) => 0,
+        (DeviceType::DiscreteGpu | DeviceType::IntegratedGpu, _) => 1,
+        (DeviceType::VirtualGpu, _) => 2,
+        (DeviceType::Other, _) => 3,
+        (DeviceType::Cpu, _) => 4,
+    }
+}
+
+/// Devices opened by `cl_gpu_init_adapter`, by adapter index; like the
+/// default device, each is opened once per process.
+static ADAPTER_DEVICES: Mutex<Vec<Option<GpuHandles>>> = Mutex::new(Vec::new());
+
+/// The device on adapter `index` in `enumerate_adapters(Backends::all())`
+/// order, opening it on first use. An index past the last adapter wraps
+/// around when `wrap` is set and is `None` otherwise, as it is with no
+/// adapters at all. Returns the index used with the handles.
+fn adapter_handles(index: usize, wrap: bool) -> Option<(usize, GpuHandles)> {
+    // A failed device open poisons the lock without leaving a bad entry.
+    let mut devices = ADAPTER_DEVICES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let instance = wgpu::Instance::new(InstanceDescriptor::default());
+    let mut adapters = instance.enumerate_adapters(Backends::all());
+    let index = match adapters.len() {
+        0 => return None,
+        n if index < n => index,
+        n if wrap => index % n,
+        _ => return None,
+    };
+    if let Some(Some(h)) = devices.get(index) {
+        return Some((index, h.clone()));
+    }
+    let handles = open_device(instance, adapters.swap_remove(index), Features::empty());
+    if devices.len() <= index {
+        devices.resize(index + 1, None);
+    }
+    devices[index] = Some(handles.clone());
+    Some((index, handles))
 }
 
 fn cached_gpu_device() -> (Arc<wgpu::Device>, Arc<wgpu::Queue>) {
@@ -51,16 +226,73 @@ fn cached_gpu_device() -> (Arc<wgpu::Device>, Arc<wgpu::Queue>) {
     (h.device, h.queue)
 }
 
-pub(crate) struct CraneliftGpuContext {
+/// The context behind the `cl_gpu_*` calls: the shared wgpu device, or the
+/// CPU stand-in chosen by `cl_gpu_init_fallback`.
+pub(crate) enum CraneliftGpuContext {
+    Device(DeviceContext),
+    Cpu(CpuGpuContext),
+}
+
+impl CraneliftGpuContext {
+    /// Borrow a storage buffer by id; `None` on the CPU stand-in.
+    pub(crate) fn buffer(&self, id: usize) -> Option<&wgpu::Buffer> {
+        match self {
+            CraneliftGpuContext::Device(ctx) => ctx.buffer(id),
+            CraneliftGpuContext::Cpu(_) => None,
+        }
+    }
+
+    pub(crate) fn flush_pending(&mut self) {
+        if let CraneliftGpuContext::Device(ctx) = self {
+            ctx.flush_pending();
+        }
+    }
+
+    /// The memory regions bound by each `cl_gpu_create_pipeline_regions`
+    /// pipeline, by pipeline id.
+    fn regions(&mut self) -> &mut HashMap<usize, Vec<Region>> {
+        match self {
+            CraneliftGpuContext::Device(ctx) => &mut ctx.regions,
+            CraneliftGpuContext::Cpu(cpu) => &mut cpu.regions,
+        }
+    }
+}
+
+/// A binding of a `cl_gpu_create_pipeline_regions` pipeline: buffer `buf`
+/// mirrors `len` bytes at `offset` from the memory base.
+#[derive(Clone, Copy)]
+pub(crate) struct Region {
+    buf: i32,
+    offset: usize,
+    len: usize,
+    read_only: bool,
+}
+
+/// A GPU context on the shared wgpu device.
+pub(crate) struct DeviceContext {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
     buffers: Vec<wgpu::Buffer>,
     staging_buffers: Vec<wgpu::Buffer>,
     pipelines: Vec<(wgpu::ComputePipeline, wgpu::BindGroup)>,
     pending_encoder: Option<wgpu::CommandEncoder>,
+    readbacks: Vec<Readback>,
+    spare_staging: Vec<wgpu::Buffer>,
+    regions: HashMap<usize, Vec<Region>>,
 }
 
-impl CraneliftGpuContext {
+/// An in-flight `cl_gpu_download_async`: `state` is set by the map callback
+/// (1 mapped, -1 failed); the bytes are copied to `dst` and `flag` is stored
+/// on the next poll that sees it.
+struct Readback {
+    staging: wgpu::Buffer,
+    size: u64,
+    dst: *mut u8,
+    flag: *mut i64,
+    state: Arc<AtomicI32>,
+}
+
+impl DeviceContext {
     /// Borrow a storage buffer by id (used by the window FFI to present a game
     /// framebuffer directly, without copying it back through host memory).
     pub(crate) fn buffer(&self, id: usize) -> Option<&wgpu::Buffer> {
@@ -74,21 +306,186 @@ impl CraneliftGpuContext {
             self.queue.submit(Some(enc.finish()));
         }
     }
+
+    /// Drive the device (blocking until all work is done when `wait`) and
+    /// finish every readback whose mapping has resolved. Returns how many are
+    /// still pending.
+    fn complete_readbacks(&mut self, wait: bool) -> usize {
+        if self.readbacks.is_empty() {
+            return 0;
+        }
+        let mode = if wait {
+            wgpu::Maintain::Wait
+        } else {
+            wgpu::Maintain::Poll
+        };
+        self.device.poll(mode);
+        let mut i = 0;
+        while i < self.readbacks.len() {
+            let state = self.readbacks[i].state.load(Ordering::Acquire);
+            if state == 0 {
+                i += 1;
+                continue;
+            }
+            let rb = self.readbacks.swap_remove(i);
+            let flag = if state > 0 {
+                let mapped = rb.staging.slice(..rb.size).get_mapped_range();
+                unsafe { std::ptr::copy_nonoverlapping(mapped.as_ptr(), rb.dst, mapped.len()) };
+                drop(mapped);
+                rb.staging.unmap();
+                1
+            } else {
+                -1
+            };
+            // Release: the copied bytes are visible before the flag is, and
+            // threads in `cl_thread_wait_until` on it wake.
+            unsafe { cl_thread_wake(rb.flag.cast(), flag) };
+            self.spare_staging.push(rb.staging);
+        }
+        self.readbacks.len()
+    }
+
+    /// Copy `size` bytes from `buf_offset` in buffer `bid` through its staging
+    /// buffer, after any pending dispatch, and hand them to `read`.
+    fn read_back(&mut self, bid: usize, buf_offset: u64, size: u64, read: impl FnOnce(&[u8])) {
+        let mut encoder = self.pending_encoder.take().unwrap_or_else(|| {
+            self.device
+                .create_command_encoder(&CommandEncoderDescriptor { label: None })
+        });
+        encoder.copy_buffer_to_buffer(
+            &self.buffers[bid],
+            buf_offset,
+            &self.staging_buffers[bid],
+            0,
+            size,
+        );
+        self.queue.submit(Some(encoder.finish()));
+        let slice = self.staging_buffers[bid].slice(..size);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        read(&slice.get_mapped_range());
+        self.staging_buffers[bid].unmap();
+        self.complete_readbacks(false);
+    }
+
+    /// A mappable staging buffer of at least `size` bytes, reused when possible.
+    fn take_staging(&mut self, size: u64) -> wgpu::Buffer {
+        match self.spare_staging.iter().position(|b| b.size() >= size) {
+            Some(i) => self.spare_staging.swap_remove(i),
+            None => self.device.create_buffer(&BufferDescriptor {
+                label: None,
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+        }
+    }
 }
 
-pub(crate) unsafe extern "C" fn cl_gpu_init(ctx_slot_ptr: *mut *mut CraneliftGpuContext) {
-    let (device, queue) = cached_gpu_device();
-    let ctx = Box::new(CraneliftGpuContext {
+fn device_context(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> CraneliftGpuContext {
+    CraneliftGpuContext::Device(DeviceContext {
         device,
         queue,
         buffers: Vec::new(),
         staging_buffers: Vec::new(),
         pipelines: Vec::new(),
         pending_encoder: None,
-    });
+        readbacks: Vec::new(),
+        spare_staging: Vec::new(),
+        regions: HashMap::new(),
+    })
+}
+
+pub(crate) unsafe extern "C" fn cl_gpu_init(ctx_slot_ptr: *mut *mut CraneliftGpuContext) {
+    let (device, queue) = cached_gpu_device();
+    let ctx = Box::new(device_context(device, queue));
     let _ = write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
 }
 
+/// Like `cl_gpu_init`, but when no adapter is available, or `force_cpu` is
+/// nonzero, the context runs on the CPU instead: buffers live in host
+/// memory and each dispatch runs the function attached to its pipeline with
+/// `cl_gpu_pipeline_cpu`. Returns 1 on a GPU device, 0 on the CPU, or -1 for
+/// a null slot.
+pub(crate) unsafe extern "C" fn cl_gpu_init_fallback(
+    ctx_slot_ptr: *mut *mut CraneliftGpuContext,
+    force_cpu: i32,
+) -> i32 {
+    if ctx_slot_ptr.is_null() {
+        return -1;
+    }
+    let handles = if force_cpu != 0 {
+        None
+    } else {
+        std::panic::catch_unwind(try_gpu_handles).ok().flatten()
+    };
+    let (ctx, on_device) = match handles {
+        Some(h) => (device_context(h.device, h.queue), 1),
+        None => {
+            let compiled_fns = THREAD_COMPILED_FNS
+                .with(|cell| cell.borrow().clone())
+                .unwrap_or_default();
+            (
+                CraneliftGpuContext::Cpu(CpuGpuContext::new(compiled_fns)),
+                0,
+            )
+        }
+    };
+    write_ctx_slot(ctx_slot_ptr, Box::into_raw(Box::new(ctx)));
+    on_device
+}
+
+/// Like `cl_gpu_init`, but on adapter `adapter` in
+/// `base::enumerate_gpu_adapters(Backends::all())` order, so contexts on
+/// different adapters split work across GPUs. Each adapter's device is
+/// shared by every context on it; buffers and pipelines belong to the
+/// context, so a shader used on several adapters is created on each, and
+/// transfers and completion flags work as on the default device. Past the
+/// last adapter, a nonzero `allow_oversubscribe` wraps onto adapter
+/// `adapter % count`; otherwise the call fails. Returns the adapter index
+/// used, or -1 with the slot left unset.
+pub(crate) unsafe extern "C" fn cl_gpu_init_adapter(
+    ctx_slot_ptr: *mut *mut CraneliftGpuContext,
+    adapter: i32,
+    allow_oversubscribe: i32,
+) -> i32 {
+    if ctx_slot_ptr.is_null() || adapter < 0 {
+        return -1;
+    }
+    let wrap = allow_oversubscribe != 0;
+    match std::panic::catch_unwind(|| adapter_handles(adapter as usize, wrap)) {
+        Ok(Some((index, h))) => {
+            let ctx = Box::new(device_context(h.device, h.queue));
+            write_ctx_slot(ctx_slot_ptr, Box::into_raw(ctx));
+            index as i32
+        }
+        _ => -1,
+    }
+}
+
+/// Attach compiled function `fn_index` to `pipeline_id` as its CPU
+/// equivalent. A dispatch on the CPU stand-in calls it with a pointer to an
+/// argument block: `wg_x`, `wg_y`, `wg_z`, then the address and byte length
+/// of each binding's buffer, all as i64s. It must leave the buffers as the
+/// shader would. Ignored on a GPU device. Returns 0, or -1 for an unknown
+/// pipeline or function.
+pub(crate) unsafe extern "C" fn cl_gpu_pipeline_cpu(
+    ctx_ptr: *mut CraneliftGpuContext,
+    pipeline_id: i32,
+    fn_index: i64,
+) -> i32 {
+    if pipeline_id < 0 || fn_index < 0 {
+        return -1;
+    }
+    match read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) {
+        Some(CraneliftGpuContext::Cpu(cpu)) => {
+            cpu.set_pipeline_fn(pipeline_id as usize, fn_index as usize)
+        }
+        Some(CraneliftGpuContext::Device(ctx)) if (pipeline_id as usize) < ctx.pipelines.len() => 0,
+        _ => -1,
+    }
+}
+
 pub(crate) unsafe extern "C" fn cl_gpu_create_buffer(
     ctx_ptr: *mut CraneliftGpuContext,
     size: i64,
@@ -100,6 +497,10 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_buffer(
         let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => return cpu.create_buffer(size as usize),
+        };
         let buffer = ctx.device.create_buffer(&BufferDescriptor {
             label: None,
             size: size as u64,
@@ -120,6 +521,11 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_buffer(
     .unwrap_or(-1)
 }
 
+/// Create a compute pipeline running the NUL-terminated WGSL at
+/// `shader_ptr` (entry point `main`) over `n_bindings` 8-byte descriptors
+/// (buffer id, read-only flag) at `bind_ptr`. Returns the pipeline id, or -1;
+/// a shader or pipeline that wgpu rejects sets status `INVALID_ARGUMENT` and
+/// logs wgpu's message, which locates the error in the shader.
 pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline(
     ctx_ptr: *mut CraneliftGpuContext,
     shader_ptr: *const u8,
@@ -133,6 +539,15 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline(
         let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                let bindings = (0..n_bindings as usize)
+                    .map(|i| std::ptr::read_unaligned(bind_ptr.add(i * 8) as *const i32) as usize)
+                    .collect();
+                return cpu.create_pipeline(bindings);
+            }
+        };
         let mut len = 0;
         while *shader_ptr.add(len) != 0 {
             len += 1;
@@ -141,10 +556,6 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline(
             Ok(s) => s,
             Err(_) => return -1,
         };
-        let shader = ctx.device.create_shader_module(ShaderModuleDescriptor {
-            label: None,
-            source: ShaderSource::Wgsl(shader_src.into()),
-        });
         let mut bgl_entries = Vec::new();
         let mut bg_entries = Vec::new();
         let bind_base = bind_ptr;
@@ -168,6 +579,13 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline(
             });
             bg_entries.push((i as u32, buf_id));
         }
+        // Without a scope, wgpu reports an invalid shader or pipeline to its
+        // uncaptured error handler, which panics.
+        ctx.device.push_error_scope(ErrorFilter::Validation);
+        let shader = ctx.device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(shader_src.into()),
+        });
         let bgl = ctx
             .device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -190,6 +608,11 @@ pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline(
                 entry_point: "main",
                 compilation_options: PipelineCompilationOptions::default(),
             });
+        if let Some(e) = block_on(ctx.device.pop_error_scope()) {
+            error!(call = "cl_gpu_create_pipeline", "{e}");
+            status::set(INVALID_ARGUMENT, 0);
+            return -1;
+        }
         let entries: Vec<BindGroupEntry> = bg_entries
             .iter()
             .map(|&(binding, buf_id)| BindGroupEntry {
@@ -219,9 +642,18 @@ pub(crate) unsafe extern "C" fn cl_gpu_upload(
         return -1;
     }
     std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        let Some(ctx) = read_ctx_ref::<CraneliftGpuContext>(ctx_ptr) else {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr.cast_mut()) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.upload(
+                    buf_id as usize,
+                    std::slice::from_raw_parts(src_ptr, size as usize),
+                )
+            }
+        };
         let bid = buf_id as usize;
         if bid >= ctx.buffers.len() {
             return -1;
@@ -243,9 +675,18 @@ pub(crate) unsafe extern "C" fn cl_gpu_upload_ptr(
         return -1;
     }
     std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        let Some(ctx) = read_ctx_ref::<CraneliftGpuContext>(ctx_ptr) else {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr.cast_mut()) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.upload(
+                    buf_id as usize,
+                    std::slice::from_raw_parts(src_ptr, size as usize),
+                )
+            }
+        };
         let bid = buf_id as usize;
         if bid >= ctx.buffers.len() {
             return -1;
@@ -271,32 +712,24 @@ pub(crate) unsafe extern "C" fn cl_gpu_download_ptr(
         let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.download(
+                    buf_id as usize,
+                    buf_offset as usize,
+                    std::slice::from_raw_parts_mut(dst_ptr, size as usize),
+                )
+            }
+        };
         let bid = buf_id as usize;
         if bid >= ctx.buffers.len() {
             return -1;
         }
-        let size = size as u64;
-        let buf_offset = buf_offset as u64;
-        let mut encoder = ctx.pending_encoder.take().unwrap_or_else(|| {
-            ctx.device
-                .create_command_encoder(&CommandEncoderDescriptor { label: None })
-        });
-        encoder.copy_buffer_to_buffer(
-            &ctx.buffers[bid],
-            buf_offset,
-            &ctx.staging_buffers[bid],
-            0,
-            size,
-        );
-        ctx.queue.submit(Some(encoder.finish()));
-        let slice = ctx.staging_buffers[bid].slice(..size);
-        slice.map_async(wgpu::MapMode::Read, |_| {});
-        ctx.device.poll(wgpu::Maintain::Wait);
-        let mapped = slice.get_mapped_range();
         let dst = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
-        dst.copy_from_slice(&mapped);
-        drop(mapped);
-        ctx.staging_buffers[bid].unmap();
+        ctx.read_back(bid, buf_offset as u64, size as u64, |bytes| {
+            dst.copy_from_slice(bytes)
+        });
         0
     }))
     .unwrap_or(-1)
@@ -316,6 +749,12 @@ pub(crate) unsafe extern "C" fn cl_gpu_dispatch(
         let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.dispatch(pipeline_id as usize, [wg_x, wg_y, wg_z])
+            }
+        };
         let pid = pipeline_id as usize;
         if pid >= ctx.pipelines.len() {
             return -1;
@@ -323,6 +762,7 @@ pub(crate) unsafe extern "C" fn cl_gpu_dispatch(
         if let Some(enc) = ctx.pending_encoder.take() {
             ctx.queue.submit(Some(enc.finish()));
         }
+        ctx.complete_readbacks(false);
         let (pipeline, bind_group) = &ctx.pipelines[pid];
         let mut encoder = ctx
             .device
@@ -355,25 +795,453 @@ pub(crate) unsafe extern "C" fn cl_gpu_download(
         let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
             return -1;
         };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.download(
+                    buf_id as usize,
+                    0,
+                    std::slice::from_raw_parts_mut(dst_ptr, size as usize),
+                )
+            }
+        };
         let bid = buf_id as usize;
         if bid >= ctx.buffers.len() {
             return -1;
         }
+        let dst = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
+        ctx.read_back(bid, 0, size as u64, |bytes| dst.copy_from_slice(bytes));
+        0
+    }))
+    .unwrap_or(-1)
+}
+
+/// Bytes per `cl_gpu_create_pipeline_regions` table entry: the region's
+/// offset from the memory base (i64), its byte length (i32), and a read-only
+/// flag (i32).
+pub(crate) const GPU_REGION_DESC: usize = 16;
+/// Most entries a region table may have.
+pub(crate) const GPU_MAX_REGIONS: i32 = 8;
+
+/// Parse and validate `src` as WGSL, the checks wgpu makes when creating a
+/// pipeline from it. The error holds naga's message and the 1-based line and
+/// column it points at (0 when it points nowhere).
+pub(crate) fn check_shader(src: &str) -> Result<(), (u32, u32, String)> {
+    use wgpu::naga::valid::{Capabilities, ValidationFlags, Validator};
+    let at = |loc: Option<wgpu::naga::SourceLocation>| {
+        loc.map_or((0, 0), |l| (l.line_number, l.line_position))
+    };
+    let module = wgpu::naga::front::wgsl::parse_str(src).map_err(|e| {
+        let (line, column) = at(e.location(src));
+        (line, column, e.message().to_string())
+    })?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| {
+            let (line, column) = at(e.location(src));
+            // The top-level error only names the function; its sources say
+            // what is wrong.
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(inner) = source {
+                message += &format!(": {inner}");
+                source = inner.source();
+            }
+            (line, column, message)
+        })?;
+    Ok(())
+}
+
+/// Check the region table against the shader's group 0 storage bindings:
+/// each binding the shader declares needs an entry (read-write if the shader
+/// writes it), and each entry a binding. The error names the first
+/// mismatched binding. A shader that does not parse passes, to fail in
+/// pipeline creation like any other.
+fn check_region_bindings(src: &str, read_only: &[bool]) -> Result<(), (u32, String)> {
+    use wgpu::naga::{AddressSpace, StorageAccess};
+    let Ok(module) = wgpu::naga::front::wgsl::parse_str(src) else {
+        return Ok(());
+    };
+    let mut declared = vec![false; read_only.len()];
+    for (_, var) in module.global_variables.iter() {
+        let Some(rb) = var.binding.as_ref().filter(|rb| rb.group == 0) else {
+            continue;
+        };
+        let AddressSpace::Storage { access } = var.space else {
+            continue;
+        };
+        let Some(&entry_read_only) = read_only.get(rb.binding as usize) else {
+            let msg = format!(
+                "shader declares @binding({}) but the table has {} entries",
+                rb.binding,
+                read_only.len()
+            );
+            return Err((rb.binding, msg));
+        };
+        if entry_read_only && access.contains(StorageAccess::STORE) {
+            let msg = "shader writes a binding whose region is read-only".to_string();
+            return Err((rb.binding, msg));
+        }
+        declared[rb.binding as usize] = true;
+    }
+    match declared.iter().position(|&d| !d) {
+        Some(i) => Err((i as u32, "no shader binding for this entry".to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Create a pipeline whose bindings are memory regions rather than buffer
+/// ids. `table_ptr` holds `n_regions` (1 to `GPU_MAX_REGIONS`) entries of
+/// `GPU_REGION_DESC` bytes; entry `n` gets its own storage buffer at
+/// `@binding(n)`, sized to its region. Dispatch it with
+/// `cl_gpu_dispatch_regions`. Returns the pipeline id, or -1 with the status
+/// set: `INVALID_ARGUMENT` with the entry index as payload for a bad entry
+/// or one that does not match the shader's bindings.
+pub(crate) unsafe extern "C" fn cl_gpu_create_pipeline_regions(
+    ctx_ptr: *mut CraneliftGpuContext,
+    shader_ptr: *const u8,
+    table_ptr: *const u8,
+    n_regions: i32,
+) -> i32 {
+    status::begin();
+    if shader_ptr.is_null()
+        || table_ptr.is_null()
+        || !(1..=GPU_MAX_REGIONS).contains(&n_regions)
+        || read_ctx_mut::<CraneliftGpuContext>(ctx_ptr).is_none()
+    {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    }
+    let mut regions = Vec::with_capacity(n_regions as usize);
+    for i in 0..n_regions as usize {
+        let entry = table_ptr.add(i * GPU_REGION_DESC);
+        let offset = std::ptr::read_unaligned(entry as *const i64);
+        let len = std::ptr::read_unaligned(entry.add(8) as *const i32);
+        // Storage buffers hold whole 4-byte words.
+        if offset < 0 || len <= 0 || len % 4 != 0 {
+            status::set(INVALID_ARGUMENT, i as u64);
+            return -1;
+        }
+        regions.push(Region {
+            buf: -1,
+            offset: offset as usize,
+            len: len as usize,
+            read_only: std::ptr::read_unaligned(entry.add(12) as *const i32) != 0,
+        });
+    }
+    if let Some(CraneliftGpuContext::Device(_)) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) {
+        let src = String::from_utf8_lossy(std::ffi::CStr::from_ptr(shader_ptr.cast()).to_bytes());
+        let read_only: Vec<bool> = regions.iter().map(|r| r.read_only).collect();
+        if let Err((binding, msg)) = check_region_bindings(&src, &read_only) {
+            error!(call = "cl_gpu_create_pipeline_regions", binding, "{msg}");
+            status::set(INVALID_ARGUMENT, binding as u64);
+            return -1;
+        }
+    }
+    let mut descs = Vec::with_capacity(regions.len() * 8);
+    for region in &mut regions {
+        region.buf = cl_gpu_create_buffer(ctx_ptr, region.len as i64);
+        if region.buf < 0 {
+            return -1;
+        }
+        descs.extend_from_slice(&region.buf.to_le_bytes());
+        descs.extend_from_slice(&(region.read_only as i32).to_le_bytes());
+    }
+    let pipeline = cl_gpu_create_pipeline(ctx_ptr, shader_ptr, descs.as_ptr(), n_regions);
+    if pipeline < 0 {
+        return -1;
+    }
+    let ctx = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr).unwrap();
+    ctx.regions().insert(pipeline as usize, regions);
+    status::ok(0);
+    pipeline
+}
+
+/// Dispatch a `cl_gpu_create_pipeline_regions` pipeline on the regions at
+/// their offsets from `ptr`: every region is copied into its buffer, and
+/// read-write ones are copied back once the dispatch completes. Returns 0,
+/// or -1 with the status set.
+pub(crate) unsafe extern "C" fn cl_gpu_dispatch_regions(
+    ctx_ptr: *mut CraneliftGpuContext,
+    pipeline_id: i32,
+    ptr: *mut u8,
+    wg_x: i32,
+    wg_y: i32,
+    wg_z: i32,
+) -> i32 {
+    status::begin();
+    let regions = match read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) {
+        Some(ctx) if !ptr.is_null() && pipeline_id >= 0 => {
+            ctx.regions().get(&(pipeline_id as usize)).cloned()
+        }
+        _ => None,
+    };
+    let Some(regions) = regions else {
+        status::set(INVALID_ARGUMENT, 0);
+        return -1;
+    };
+    for r in &regions {
+        if cl_gpu_upload_ptr(ctx_ptr, r.buf, ptr.add(r.offset), r.len as i64) != 0 {
+            return -1;
+        }
+    }
+    if cl_gpu_dispatch(ctx_ptr, pipeline_id, wg_x, wg_y, wg_z) != 0 {
+        return -1;
+    }
+    for r in regions.iter().filter(|r| !r.read_only) {
+        if cl_gpu_download_ptr(ctx_ptr, r.buf, 0, ptr.add(r.offset), r.len as i64) != 0 {
+            return -1;
+        }
+    }
+    status::ok(0);
+    0
+}
+
+/// Element types for `cl_gpu_upload_typed` / `cl_gpu_download_typed`. Host
+/// data is always f32; on the GPU it is stored as f32, IEEE half (round to
+/// nearest even; out of range becomes ±inf), or a u8 holding
+/// `round(clamp(x, 0, 1) * 255)` that reads back as `b / 255`.
+pub(crate) const GPU_ELEM_F32: i32 = 0;
+pub(crate) const GPU_ELEM_F16: i32 = 1;
+pub(crate) const GPU_ELEM_UNORM8: i32 = 2;
+
+fn elem_size(elem: i32) -> Option<usize> {
+    match elem {
+        GPU_ELEM_F32 => Some(4),
+        GPU_ELEM_F16 => Some(2),
+        GPU_ELEM_UNORM8 => Some(1),
+        _ => None,
+    }
+}
+
+/// `src` (little-endian f32s) as GPU elements of type `elem`.
+pub(super) fn encode_elems(elem: i32, src: &[u8]) -> Vec<u8> {
+    let floats = src
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes(c.try_into().unwrap()));
+    match elem {
+        GPU_ELEM_F16 => {
+            let floats: Vec<f32> = floats.collect();
+            let mut halves = vec![f16::ZERO; floats.len()];
+            halves.convert_from_f32_slice(&floats);
+            halves.iter().flat_map(|h| h.to_le_bytes()).collect()
+        }
+        GPU_ELEM_UNORM8 => floats
+            .map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        _ => src.to_vec(),
+    }
+}
+
+/// GPU elements of type `elem` in `src` as little-endian f32s in `dst`.
+pub(super) fn decode_elems(elem: i32, src: &[u8], dst: &mut [u8]) {
+    let floats: Vec<f32> = match elem {
+        GPU_ELEM_F16 => {
+            let halves: Vec<f16> = src
+                .chunks_exact(2)
+                .map(|c| f16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            let mut floats = vec![0.0; halves.len()];
+            halves.convert_to_f32_slice(&mut floats);
+            floats
+        }
+        GPU_ELEM_UNORM8 => src.iter().map(|&b| b as f32 / 255.0).collect(),
+        _ => return dst.copy_from_slice(src),
+    };
+    for (out, x) in dst.chunks_exact_mut(4).zip(floats) {
+        out.copy_from_slice(&x.to_le_bytes());
+    }
+}
+
+/// Upload `count` f32s from `src_ptr` into buffer `buf_id`, converted to
+/// `elem` (`GPU_ELEM_*`) on the CPU first, so the buffer receives
+/// `count * size_of(elem)` bytes, zero-padded to a multiple of 4 as wgpu
+/// requires. Returns 0, or -1 for invalid arguments.
+pub(crate) unsafe extern "C" fn cl_gpu_upload_typed(
+    ctx_ptr: *const CraneliftGpuContext,
+    buf_id: i32,
+    src_ptr: *const u8,
+    count: i64,
+    elem: i32,
+) -> i32 {
+    let Some(width) = elem_size(elem) else {
+        return -1;
+    };
+    if buf_id < 0 || count <= 0 || src_ptr.is_null() {
+        return -1;
+    }
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr.cast_mut()) else {
+            return -1;
+        };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.upload_typed(
+                    buf_id as usize,
+                    elem,
+                    std::slice::from_raw_parts(src_ptr, count as usize * 4),
+                    (count as usize * width).next_multiple_of(4),
+                )
+            }
+        };
+        let bid = buf_id as usize;
+        let size = (count as usize * width).next_multiple_of(4);
+        if bid >= ctx.buffers.len() || size as u64 > ctx.buffers[bid].size() {
+            return -1;
+        }
+        let src = std::slice::from_raw_parts(src_ptr, count as usize * 4);
+        let mut data = encode_elems(elem, src);
+        data.resize(size, 0);
+        ctx.queue.write_buffer(&ctx.buffers[bid], 0, &data);
+        0
+    }))
+    .unwrap_or(-1)
+}
+
+/// Download `count` elements of type `elem` from the start of buffer
+/// `buf_id`, after any pending dispatch, and store them to `dst_ptr` as
+/// f32s. Returns 0, or -1 for invalid arguments.
+pub(crate) unsafe extern "C" fn cl_gpu_download_typed(
+    ctx_ptr: *mut CraneliftGpuContext,
+    buf_id: i32,
+    dst_ptr: *mut u8,
+    count: i64,
+    elem: i32,
+) -> i32 {
+    let Some(width) = elem_size(elem) else {
+        return -1;
+    };
+    if buf_id < 0 || count <= 0 || dst_ptr.is_null() {
+        return -1;
+    }
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
+            return -1;
+        };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.download_typed(
+                    buf_id as usize,
+                    elem,
+                    count as usize * width,
+                    std::slice::from_raw_parts_mut(dst_ptr, count as usize * 4),
+                )
+            }
+        };
+        let bid = buf_id as usize;
+        let size = count as usize * width;
+        // wgpu copies whole 4-byte words; round up and decode the prefix.
+        let padded = size.next_multiple_of(4) as u64;
+        if bid >= ctx.buffers.len() || padded > ctx.buffers[bid].size() {
+            return -1;
+        }
+        let dst = std::slice::from_raw_parts_mut(dst_ptr, count as usize * 4);
+        ctx.read_back(bid, 0, padded, |bytes| {
+            decode_elems(elem, &bytes[..size], dst)
+        });
+        0
+    }))
+    .unwrap_or(-1)
+}
+
+/// Start copying `size` bytes of buffer `buf_id` to `dst_ptr` without waiting
+/// for the GPU. Stores 0 at the i64 `flag_ptr` now, then 1 once the bytes are
+/// in `dst_ptr` (or -1 if mapping failed); the store has release ordering, so
+/// code that sees 1 also sees the data, and wakes threads blocked on the flag
+/// in `cl_thread_wait_until`. Later dispatches and downloads are
+/// submitted while the copy is in flight. Flags are set by the next GPU call
+/// on this context that finds the mapping resolved, `cl_gpu_poll`, or
+/// `cl_gpu_wait`, so completions may arrive out of submission order.
+/// Returns 0, or -1 for invalid arguments, including a misaligned flag.
+pub(crate) unsafe extern "C" fn cl_gpu_download_async(
+    ctx_ptr: *mut CraneliftGpuContext,
+    buf_id: i32,
+    dst_ptr: *mut u8,
+    size: i64,
+    flag_ptr: *mut i64,
+) -> i32 {
+    if buf_id < 0 || size <= 0 || dst_ptr.is_null() || flag_ptr.is_null() || !flag_ptr.is_aligned()
+    {
+        return -1;
+    }
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
+            return -1;
+        };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(cpu) => {
+                return cpu.download_async(
+                    buf_id as usize,
+                    std::slice::from_raw_parts_mut(dst_ptr, size as usize),
+                    flag_ptr,
+                )
+            }
+        };
+        let bid = buf_id as usize;
+        if bid >= ctx.buffers.len() || size as u64 > ctx.buffers[bid].size() {
+            return -1;
+        }
         let size = size as u64;
+        (*flag_ptr.cast::<AtomicI64>()).store(0, Ordering::Relaxed);
+        let staging = ctx.take_staging(size);
         let mut encoder = ctx.pending_encoder.take().unwrap_or_else(|| {
             ctx.device
                 .create_command_encoder(&CommandEncoderDescriptor { label: None })
         });
-        encoder.copy_buffer_to_buffer(&ctx.buffers[bid], 0, &ctx.staging_buffers[bid], 0, size);
+        encoder.copy_buffer_to_buffer(&ctx.buffers[bid], 0, &staging, 0, size);
         ctx.queue.submit(Some(encoder.finish()));
-        let slice = ctx.staging_buffers[bid].slice(..);
-        slice.map_async(wgpu::MapMode::Read, |_| {});
-        ctx.device.poll(wgpu::Maintain::Wait);
-        let mapped = slice.get_mapped_range();
-        let dst = std::slice::from_raw_parts_mut(dst_ptr, size as usize);
-        dst.copy_from_slice(&mapped);
-        drop(mapped);
-        ctx.staging_buffers[bid].unmap();
+        let state = Arc::new(AtomicI32::new(0));
+        let signal = state.clone();
+        staging
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |r| {
+                signal.store(if r.is_ok() { 1 } else { -1 }, Ordering::Release);
+            });
+        ctx.readbacks.push(Readback {
+            staging,
+            size,
+            dst: dst_ptr,
+            flag: flag_ptr,
+            state,
+        });
+        ctx.complete_readbacks(false);
+        0
+    }))
+    .unwrap_or(-1)
+}
+
+/// Finish any `cl_gpu_download_async` copies that are ready without blocking.
+/// Returns how many are still in flight, or -1 for a null context.
+pub(crate) unsafe extern "C" fn cl_gpu_poll(ctx_ptr: *mut CraneliftGpuContext) -> i32 {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
+            return -1;
+        };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(_) => return 0,
+        };
+        ctx.complete_readbacks(false) as i32
+    }))
+    .unwrap_or(-1)
+}
+
+/// Block until every `cl_gpu_download_async` copy has finished and its flag
+/// is set. Returns 0, or -1 for a null context.
+pub(crate) unsafe extern "C" fn cl_gpu_wait(ctx_ptr: *mut CraneliftGpuContext) -> i32 {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let Some(ctx) = read_ctx_mut::<CraneliftGpuContext>(ctx_ptr) else {
+            return -1;
+        };
+        let ctx = match ctx {
+            CraneliftGpuContext::Device(ctx) => ctx,
+            CraneliftGpuContext::Cpu(_) => return 0,
+        };
+        ctx.flush_pending();
+        while ctx.complete_readbacks(true) > 0 {}
         0
     }))
     .unwrap_or(-1)
@@ -382,6 +1250,10 @@ pub(crate) unsafe extern "C" fn cl_gpu_download(
 pub(crate) unsafe extern "C" fn cl_gpu_cleanup(ctx_slot_ptr: *mut *mut CraneliftGpuContext) {
     let ctx_ptr = clear_ctx_slot::<CraneliftGpuContext>(ctx_slot_ptr);
     if !ctx_ptr.is_null() {
+        // Pending readbacks still target caller memory; land them first.
+        if let CraneliftGpuContext::Device(ctx) = &mut *ctx_ptr {
+            while ctx.complete_readbacks(true) > 0 {}
+        }
         drop(Box::from_raw(ctx_ptr));
     }
 }
@@ -390,6 +1262,100 @@ pub(crate) unsafe extern "C" fn cl_gpu_cleanup(ctx_slot_ptr: *mut *mut Cranelift
 mod tests {
     use super::*;
 
+    fn adapter(name: &str, device_type: DeviceType) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend: wgpu::Backend::Vulkan,
+        }
+    }
+
+    fn f32_bytes(xs: &[f32]) -> Vec<u8> {
+        xs.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    fn round_trip(elem: i32, xs: &[f32]) -> Vec<f32> {
+        let encoded = encode_elems(elem, &f32_bytes(xs));
+        assert_eq!(encoded.len(), xs.len() * elem_size(elem).unwrap());
+        let mut out = vec![0u8; xs.len() * 4];
+        decode_elems(elem, &encoded, &mut out);
+        out.chunks_exact(4)
+            .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn typed_elements_round_trip() {
+        let xs = [0.0, -0.0, 1.0, -2.5, 0.1, 65504.0, 1e6, -1e6, 6e-8];
+        let halves = round_trip(GPU_ELEM_F16, &xs);
+        assert_eq!(&halves[..6], &[0.0, -0.0, 1.0, -2.5, 0.099975586, 65504.0]);
+        assert_eq!(&halves[6..8], &[f32::INFINITY, f32::NEG_INFINITY]);
+        assert!((halves[8] - 6e-8).abs() < 3e-8, "subnormal half");
+        assert!(round_trip(GPU_ELEM_F16, &[f32::NAN])[0].is_nan());
+
+        let bytes = encode_elems(GPU_ELEM_UNORM8, &f32_bytes(&[-1.0, 0.0, 0.5, 1.0, 7.0]));
+        assert_eq!(bytes, [0, 0, 128, 255, 255]);
+        let unorm = round_trip(GPU_ELEM_UNORM8, &[0.0, 0.25, 1.0]);
+        assert_eq!(unorm, [0.0, 64.0 / 255.0, 1.0]);
+
+        assert_eq!(round_trip(GPU_ELEM_F32, &xs), xs);
+        assert_eq!(elem_size(3), None);
+    }
+
+    fn mock_adapters() -> Vec<AdapterInfo> {
+        vec![
+            adapter("Intel(R) UHD Graphics 630", DeviceType::IntegratedGpu),
+            adapter("NVIDIA GeForce RTX 3080", DeviceType::DiscreteGpu),
+            adapter("llvmpipe (LLVM 15.0.7, 256 bits)", DeviceType::Cpu),
+        ]
+    }
+
+    #[test]
+    fn pick_adapter_follows_power_preference() {
+        let infos = mock_adapters();
+        let high = GpuPreferences::default();
+        assert_eq!(pick_adapter(&infos, &high), Ok(1));
+        let low = GpuPreferences {
+            power_preference: PowerPreference::LowPower,
+            ..Default::default()
+        };
+        assert_eq!(pick_adapter(&infos, &low), Ok(0));
+        assert_eq!(pick_adapter(&infos[2..], &high), Ok(0), "CPU as last resort");
+    }
+
+    #[test]
+    fn pick_adapter_filters_by_name_and_fallback() {
+        let infos = mock_adapters();
+        let by_name = GpuPreferences {
+            adapter_name_substring: Some("geforce".into()),
+            power_preference: PowerPreference::LowPower,
+            ..Default::default()
+        };
+        assert_eq!(pick_adapter(&infos, &by_name), Ok(1));
+        let fallback = GpuPreferences {
+            force_fallback_adapter: true,
+            ..Default::default()
+        };
+        assert_eq!(pick_adapter(&infos, &fallback), Ok(2));
+        assert!(pick_adapter(&infos[..2], &fallback).is_err());
+    }
+
+    #[test]
+    fn pick_adapter_error_lists_available() {
+        let prefs = GpuPreferences {
+            adapter_name_substring: Some("Radeon".into()),
+            ..Default::default()
+        };
+        let err = pick_adapter(&mock_adapters(), &prefs).unwrap_err();
+        assert!(err.contains("Radeon"));
+        assert!(err.contains("NVIDIA GeForce RTX 3080 (DiscreteGpu, Vulkan)"));
+        assert!(err.contains("llvmpipe"));
+    }
+
     const WGSL_VEC_ADD: &str = concat!(
         "@group(0) @binding(0) var<storage, read> a: array<f32>;\n",
         "@group(0) @binding(1) var<storage, read> b: array<f32>;\n",
@@ -437,6 +1403,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn cpu_fallback_transfers_and_flags() {
+        let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(cl_gpu_init_fallback(&mut slot, 1), 0);
+            assert!(matches!(*slot, CraneliftGpuContext::Cpu(_)));
+            let buf = cl_gpu_create_buffer(slot, 8);
+            let data = f32_bytes(&[1.5, -2.0]);
+            assert_eq!(cl_gpu_upload(slot, buf, data.as_ptr(), 8), 0);
+            assert_eq!(cl_gpu_upload(slot, buf, data.as_ptr(), 16), -1);
+
+            let mut out = [0u8; 4];
+            assert_eq!(cl_gpu_download_ptr(slot, buf, 4, out.as_mut_ptr(), 4), 0);
+            assert_eq!(out, data[4..]);
+            let mut flag = 0i64;
+            assert_eq!(
+                cl_gpu_download_async(slot, buf, out.as_mut_ptr(), 4, &mut flag),
+                0
+            );
+            assert_eq!((flag, out), (1, data[..4].try_into().unwrap()));
+
+            let bind = bind_desc(buf, false);
+            let pipeline = cl_gpu_create_pipeline(slot, c"".as_ptr().cast(), bind.as_ptr(), 1);
+            assert_eq!(pipeline, 0);
+            // No compiled functions to attach, so the dispatch has no work.
+            assert_eq!(cl_gpu_pipeline_cpu(slot, pipeline, 0), -1);
+            assert_eq!(cl_gpu_dispatch(slot, pipeline, 1, 1, 1), -1);
+            assert_eq!(cl_gpu_wait(slot), 0);
+            cl_gpu_cleanup(&mut slot);
+            assert!(slot.is_null());
+        }
+    }
+
     #[test]
     fn create_buffer_returns_sequential_ids() {
         let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
@@ -506,6 +1505,102 @@ mod tests {
         }
     }
 
+    #[test]
+    fn async_download_flags_wake_waiters() {
+        use crate::ffi::thread::{cl_thread_wait_until, WAIT_NE};
+        use std::sync::mpsc;
+        use std::time::Duration;
+
+        // A waiter blocks on the flag before the owner sets it, on the CPU
+        // fallback (set by the download itself) and on a device (set by
+        // `cl_gpu_poll` / `cl_gpu_wait`).
+        let waiter = |flag: &mut i64| {
+            let addr = flag as *mut i64 as usize;
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let r = unsafe { cl_thread_wait_until(addr as *const u8, 0, WAIT_NE) };
+                tx.send(r).unwrap();
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            rx
+        };
+        let data = f32_bytes(&[3.0; 16]);
+        let mut out = [0u8; 64];
+        for fallback in [true, false] {
+            let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+            unsafe {
+                if fallback {
+                    assert_eq!(cl_gpu_init_fallback(&mut slot, 1), 0);
+                } else {
+                    cl_gpu_init(&mut slot);
+                }
+                let buf = cl_gpu_create_buffer(slot, 64);
+                assert_eq!(cl_gpu_upload(slot, buf, data.as_ptr(), 64), 0);
+                let mut flag = 0i64;
+                let done = waiter(&mut flag);
+                assert_eq!(
+                    cl_gpu_download_async(slot, buf, out.as_mut_ptr(), 64, &mut flag),
+                    0
+                );
+                assert!(cl_gpu_poll(slot) >= 0);
+                assert_eq!(cl_gpu_wait(slot), 0);
+                assert_eq!(done.recv_timeout(Duration::from_secs(5)), Ok(0));
+                assert_eq!((flag, out), (1, data[..64].try_into().unwrap()));
+                let misaligned = (&mut flag as *mut i64).cast::<u8>().add(1).cast();
+                let dst = out.as_mut_ptr();
+                assert_eq!(cl_gpu_download_async(slot, buf, dst, 64, misaligned), -1);
+                cl_gpu_cleanup(&mut slot);
+            }
+        }
+    }
+
+    #[test]
+    fn async_downloads_capture_each_round() {
+        // dispatch ×2 then async download, three times: each readback sees
+        // the buffer as it was when queued, and every flag ends at 1.
+        let n: usize = 64;
+        let size = (n * 4) as i64;
+        let data = vec![1.0f32; n];
+        let mut rounds = vec![vec![0.0f32; n]; 3];
+        let mut flags = [-7i64; 3];
+        let bindings = bind_desc(0, false);
+
+        let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+        unsafe {
+            cl_gpu_init(&mut slot);
+            let buf = cl_gpu_create_buffer(slot, size);
+            let src = data.as_ptr() as *const u8;
+            assert_eq!(cl_gpu_upload(slot, buf, src, size), 0);
+            let pip = cl_gpu_create_pipeline(slot, WGSL_MUL2.as_ptr(), bindings.as_ptr(), 1);
+            for (round, flag) in rounds.iter_mut().zip(flags.iter_mut()) {
+                assert_eq!(cl_gpu_dispatch(slot, pip, 1, 1, 1), 0);
+                assert_eq!(cl_gpu_dispatch(slot, pip, 1, 1, 1), 0);
+                let dst = round.as_mut_ptr() as *mut u8;
+                assert_eq!(cl_gpu_download_async(slot, buf, dst, size, flag), 0);
+            }
+            assert!(cl_gpu_poll(slot) >= 0);
+            assert_eq!(cl_gpu_wait(slot), 0);
+            assert_eq!(cl_gpu_poll(slot), 0);
+            let mut flag = 0i64;
+            let null = std::ptr::null_mut();
+            assert_eq!(cl_gpu_download_async(slot, buf, null, size, &mut flag), -1);
+            let dst = rounds[0].as_mut_ptr() as *mut u8;
+            assert_eq!(
+                cl_gpu_download_async(slot, buf, dst, size * 2, &mut flag),
+                -1
+            );
+            cl_gpu_cleanup(&mut slot);
+        }
+
+        assert_eq!(flags, [1, 1, 1]);
+        for (round, expected) in rounds.iter().zip([4.0f32, 16.0, 64.0]) {
+            assert!(
+                round.iter().all(|&v| v == expected),
+                "{expected}: {round:?}"
+            );
+        }
+    }
+
     #[test]
     fn multiple_dispatches_before_download() {
         // pending_encoder batching: dispatch ×3 with data[i]*=2 each → data[i]*8
@@ -708,4 +1803,158 @@ mod tests {
             assert_eq!(cl_gpu_create_pipeline(null, data.as_ptr(), bind.as_ptr(), 0), -1);
         }
     }
+
+    fn region_table(entries: &[(i64, i32, bool)]) -> Vec<u8> {
+        let mut table = Vec::new();
+        for &(offset, len, read_only) in entries {
+            table.extend_from_slice(&offset.to_le_bytes());
+            table.extend_from_slice(&len.to_le_bytes());
+            table.extend_from_slice(&(read_only as i32).to_le_bytes());
+        }
+        table
+    }
+
+    #[test]
+    fn regions_bind_separately_without_cross_contamination() {
+        // a at 64, b at 320, result at 640, each 16 f32s; every other byte
+        // of memory is a sentinel that must survive the dispatch.
+        let n = 16;
+        let mut mem = vec![0xABu8; 1024];
+        for i in 0..n {
+            mem[64 + i * 4..68 + i * 4].copy_from_slice(&(i as f32).to_le_bytes());
+            mem[320 + i * 4..324 + i * 4].copy_from_slice(&(1000.0f32).to_le_bytes());
+        }
+        let before = mem.clone();
+        let len = (n * 4) as i32;
+        let table = region_table(&[(64, len, true), (320, len, true), (640, len, false)]);
+        let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+        unsafe {
+            cl_gpu_init(&mut slot);
+            let pip =
+                cl_gpu_create_pipeline_regions(slot, WGSL_VEC_ADD.as_ptr(), table.as_ptr(), 3);
+            assert!(pip >= 0, "create_pipeline_regions failed");
+            let ptr = mem.as_mut_ptr();
+            assert_eq!(cl_gpu_dispatch_regions(slot, pip, ptr, 1, 1, 1), 0);
+            assert_eq!(cl_gpu_dispatch_regions(slot, 99, ptr, 1, 1, 1), -1);
+            cl_gpu_cleanup(&mut slot);
+        }
+        for i in 0..n {
+            let got = f32::from_le_bytes(mem[640 + i * 4..644 + i * 4].try_into().unwrap());
+            assert_eq!(got, i as f32 + 1000.0, "index {i}");
+        }
+        assert_eq!(mem[..640], before[..640], "inputs and gaps untouched");
+        assert_eq!(mem[704..], before[704..]);
+    }
+
+    #[test]
+    fn check_shader_locates_parse_and_validation_errors() {
+        let ok = "@compute @workgroup_size(1)\nfn main() {\n}\n";
+        assert_eq!(check_shader(ok), Ok(()));
+        let (line, column, msg) =
+            check_shader("@compute @workgroup_size(1)\nfn main() {\n  let x = ;\n}\n").unwrap_err();
+        assert_eq!((line, column), (3, 11), "{msg}");
+        let (line, _, msg) = check_shader(
+            "@compute @workgroup_size(1)\nfn main() {\n  var x: f32 = 1.0;\n  x = 2u;\n}\n",
+        )
+        .unwrap_err();
+        assert_eq!(line, 4, "{msg}");
+    }
+
+    #[test]
+    fn region_table_must_match_shader_bindings() {
+        let len = 64;
+        let cases = [
+            // The shader's @binding(2) has no entry.
+            (vec![(0, len, true), (64, len, true)], 2),
+            // An entry the shader never binds.
+            (
+                vec![
+                    (0, len, true),
+                    (64, len, true),
+                    (128, len, false),
+                    (192, len, false),
+                ],
+                3,
+            ),
+            // The shader writes `result`, but its region is read-only.
+            (vec![(0, len, true), (64, len, true), (128, len, true)], 2),
+            // Not a whole number of words.
+            (vec![(0, len, true), (64, 6, true), (128, len, false)], 1),
+        ];
+        let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+        unsafe {
+            cl_gpu_init(&mut slot);
+            for (entries, binding) in cases {
+                let table = region_table(&entries);
+                let n = entries.len() as i32;
+                assert_eq!(
+                    cl_gpu_create_pipeline_regions(slot, WGSL_VEC_ADD.as_ptr(), table.as_ptr(), n),
+                    -1
+                );
+                let word = status::cl_last_status() as u64;
+                assert_eq!(base_types::status::status(word), INVALID_ARGUMENT);
+                assert_eq!(base_types::status::payload(word), binding);
+            }
+            let table = region_table(&[(0, len, true); 9]);
+            assert_eq!(
+                cl_gpu_create_pipeline_regions(slot, WGSL_VEC_ADD.as_ptr(), table.as_ptr(), 9),
+                -1
+            );
+            cl_gpu_cleanup(&mut slot);
+        }
+    }
+
+    /// Run a one-binding shader over `data` in place on context `slot`.
+    unsafe fn run_in_place(slot: *mut CraneliftGpuContext, shader: &str, data: &[f32]) -> Vec<f32> {
+        let size = (data.len() * 4) as i64;
+        let buf = cl_gpu_create_buffer(slot, size);
+        assert_eq!(cl_gpu_upload(slot, buf, data.as_ptr().cast(), size), 0);
+        let bind = bind_desc(buf, false);
+        let pip = cl_gpu_create_pipeline(slot, shader.as_ptr(), bind.as_ptr(), 1);
+        assert!(pip >= 0);
+        assert_eq!(cl_gpu_dispatch(slot, pip, 1, 1, 1), 0);
+        let mut out = vec![0.0f32; data.len()];
+        assert_eq!(cl_gpu_download(slot, buf, out.as_mut_ptr().cast(), size), 0);
+        out
+    }
+
+    #[test]
+    fn contexts_on_two_adapters_run_independent_kernels() {
+        if enumerate_adapters(Backends::all()).len() < 2 {
+            return; // Needs a second adapter.
+        }
+        let data: Vec<f32> = (0..64).map(|i| i as f32).collect();
+        let (mut a, mut b): (*mut CraneliftGpuContext, _) =
+            (std::ptr::null_mut(), std::ptr::null_mut());
+        unsafe {
+            assert_eq!(cl_gpu_init_adapter(&mut a, 0, 0), 0);
+            assert_eq!(cl_gpu_init_adapter(&mut b, 1, 0), 1);
+            let doubled = run_in_place(a, WGSL_MUL2, &data);
+            let incremented = run_in_place(b, WGSL_ADD1, &data);
+            cl_gpu_cleanup(&mut a);
+            cl_gpu_cleanup(&mut b);
+            let expected: Vec<f32> = data.iter().map(|x| x * 2.0).collect();
+            assert_eq!(doubled, expected);
+            let expected: Vec<f32> = data.iter().map(|x| x + 1.0).collect();
+            assert_eq!(incremented, expected);
+        }
+    }
+
+    #[test]
+    fn oversubscribed_adapters_wrap_onto_available_devices() {
+        let count = enumerate_adapters(Backends::all()).len() as i32;
+        if count == 0 {
+            return; // Needs an adapter.
+        }
+        let data = [1.0f32, 2.0, 3.0];
+        let mut slot: *mut CraneliftGpuContext = std::ptr::null_mut();
+        unsafe {
+            assert_eq!(cl_gpu_init_adapter(&mut slot, count, 0), -1);
+            assert!(slot.is_null());
+            assert_eq!(cl_gpu_init_adapter(&mut slot, count + 1, 1), 1 % count);
+            assert_eq!(run_in_place(slot, WGSL_MUL2, &data), [2.0, 4.0, 6.0]);
+            cl_gpu_cleanup(&mut slot);
+            assert_eq!(cl_gpu_init_adapter(&mut slot, -1, 1), -1);
+        }
+    }
 }
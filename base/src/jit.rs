@@ -1,18 +1,83 @@
 use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::CodegenError;
 use cranelift_jit::JITBuilder;
-use cranelift_module::Module;
+use cranelift_module::{Module, ModuleError};
 use std::sync::Arc;
 use tracing::info;
 
+use crate::budget::{self, StepBudget};
+#[cfg(feature = "cuda")]
+use crate::ffi::cuda;
+#[cfg(feature = "lmdb")]
+use crate::ffi::lmdb;
 use crate::ffi::{
-    cl_cosf, cl_powf, cl_sinf, cuda, file, ht, lmdb, net, stdio, thread, wgpu as gpu, window,
+    arena, cancel, checkpoint, checksum, cl_cosf, cl_powf, cl_sinf, clock, file, file_atomic,
+    file_handle, file_stream, ht, lz4, math, mem, nested, queue, random, status, stdio, thread,
+    trace,
 };
+#[cfg(feature = "net")]
+use crate::ffi::{http, net};
+#[cfg(feature = "gpu")]
+use crate::ffi::{wgpu as gpu, window};
+use crate::profile::{self, Hooks, ProfileState};
+use crate::Error;
+use base_types::ProfileKey;
 
 thread_local! {
     pub(crate) static THREAD_COMPILED_FNS: std::cell::RefCell<Option<Arc<Vec<unsafe extern "C" fn(*mut u8)>>>> = const { std::cell::RefCell::new(None) };
 }
 
-fn register_symbols(builder: &mut JITBuilder) {
+/// Receives each FFI symbol exported to CLIF code.
+trait SymbolSink {
+    fn symbol(&mut self, name: &'static str, ptr: *const u8);
+}
+
+impl SymbolSink for JITBuilder {
+    fn symbol(&mut self, name: &'static str, ptr: *const u8) {
+        JITBuilder::symbol(self, name, ptr);
+    }
+}
+
+impl SymbolSink for Vec<&'static str> {
+    fn symbol(&mut self, name: &'static str, _ptr: *const u8) {
+        self.push(name);
+    }
+}
+
+/// Names of every FFI symbol CLIF code may import.
+pub(crate) fn symbol_names() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    register_symbols(&mut names);
+    names
+}
+
+/// Symbol prefixes of the FFI families behind cargo features.
+const FEATURE_PREFIXES: &[(&str, &str)] = &[
+    ("cl_gpu_", "gpu"),
+    ("cl_window_", "gpu"),
+    ("cl_cuda_", "cuda"),
+    ("cl_cublas_", "cuda"),
+    ("cl_lmdb_", "lmdb"),
+    ("cl_net_", "net"),
+    ("cl_http_", "net"),
+];
+
+/// The cargo feature `name` belongs to, if that feature is not enabled in
+/// this build.
+pub(crate) fn disabled_feature(name: &str) -> Option<&'static str> {
+    let &(_, feature) = FEATURE_PREFIXES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))?;
+    let enabled = match feature {
+        "gpu" => cfg!(feature = "gpu"),
+        "cuda" => cfg!(feature = "cuda"),
+        "lmdb" => cfg!(feature = "lmdb"),
+        _ => cfg!(feature = "net"),
+    };
+    (!enabled).then_some(feature)
+}
+
+fn register_symbols(builder: &mut impl SymbolSink) {
     // Hash table
     builder.symbol("cl_ht_init", ht::cl_ht_init as *const u8);
     builder.symbol("cl_ht_cleanup", ht::cl_ht_cleanup as *const u8);
@@ -22,106 +87,236 @@ fn register_symbols(builder: &mut JITBuilder) {
     builder.symbol("ht_count", ht::cl_ht_count as *const u8);
     builder.symbol("ht_get_entry", ht::cl_ht_get_entry as *const u8);
     builder.symbol("ht_increment", ht::cl_ht_increment as *const u8);
-
-    // wgpu (cross-platform GPU)
-    builder.symbol("cl_gpu_init", gpu::cl_gpu_init as *const u8);
-    builder.symbol("cl_gpu_create_buffer", gpu::cl_gpu_create_buffer as *const u8);
-    builder.symbol("cl_gpu_create_pipeline", gpu::cl_gpu_create_pipeline as *const u8);
-    builder.symbol("cl_gpu_upload", gpu::cl_gpu_upload as *const u8);
-    builder.symbol("cl_gpu_upload_ptr", gpu::cl_gpu_upload_ptr as *const u8);
-    builder.symbol("cl_gpu_dispatch", gpu::cl_gpu_dispatch as *const u8);
-    builder.symbol("cl_gpu_download", gpu::cl_gpu_download as *const u8);
-    builder.symbol("cl_gpu_download_ptr", gpu::cl_gpu_download_ptr as *const u8);
-    builder.symbol("cl_gpu_cleanup", gpu::cl_gpu_cleanup as *const u8);
-
-    // Window / input / present (shares the wgpu device for zero-copy present)
-    builder.symbol("cl_window_init", window::cl_window_init as *const u8);
-    builder.symbol("cl_window_open", window::cl_window_open as *const u8);
-    builder.symbol("cl_window_poll", window::cl_window_poll as *const u8);
+    builder.symbol("ht_close", ht::cl_ht_close as *const u8);
+    builder.symbol("ht_handle_count", ht::cl_ht_handle_count as *const u8);
     builder.symbol(
-        "cl_window_present_gpu_buffer",
-        window::cl_window_present_gpu_buffer as *const u8,
+        "ht_create_with_capacity",
+        ht::cl_ht_create_with_capacity as *const u8,
     );
-    builder.symbol("cl_window_cleanup", window::cl_window_cleanup as *const u8);
-
-    // CUDA core
-    builder.symbol("cl_cuda_init", cuda::cl_cuda_init as *const u8);
-    builder.symbol("cl_cuda_create_buffer", cuda::cl_cuda_create_buffer as *const u8);
-    builder.symbol("cl_cuda_upload", cuda::cl_cuda_upload as *const u8);
-    builder.symbol("cl_cuda_upload_ptr", cuda::cl_cuda_upload_ptr as *const u8);
-    builder.symbol("cl_cuda_upload_ptr_offset", cuda::cl_cuda_upload_ptr_offset as *const u8);
-    builder.symbol("cl_cuda_upload_ptr_async", cuda::cl_cuda_upload_ptr_async as *const u8);
-    builder.symbol("cl_cuda_upload_ptr_offset_async", cuda::cl_cuda_upload_ptr_offset_async as *const u8);
-    builder.symbol("cl_cuda_download", cuda::cl_cuda_download as *const u8);
-    builder.symbol("cl_cuda_download_ptr", cuda::cl_cuda_download_ptr as *const u8);
-    builder.symbol("cl_cuda_download_ptr_offset", cuda::cl_cuda_download_ptr_offset as *const u8);
-    builder.symbol("cl_cuda_download_ptr_async", cuda::cl_cuda_download_ptr_async as *const u8);
-    builder.symbol("cl_cuda_free_buffer", cuda::cl_cuda_free_buffer as *const u8);
-    builder.symbol("cl_cuda_stream_create", cuda::cl_cuda_stream_create as *const u8);
-    builder.symbol("cl_cuda_stream_sync", cuda::cl_cuda_stream_sync as *const u8);
-    builder.symbol("cl_cuda_stream_destroy", cuda::cl_cuda_stream_destroy as *const u8);
-    builder.symbol("cl_cuda_event_create", cuda::cl_cuda_event_create as *const u8);
-    builder.symbol("cl_cuda_event_record", cuda::cl_cuda_event_record as *const u8);
-    builder.symbol("cl_cuda_stream_wait_event", cuda::cl_cuda_stream_wait_event as *const u8);
-    builder.symbol("cl_cuda_event_elapsed_ms_bits", cuda::cl_cuda_event_elapsed_ms_bits as *const u8);
-    builder.symbol("cl_cuda_event_destroy", cuda::cl_cuda_event_destroy as *const u8);
-    builder.symbol("cl_cuda_graph_begin_capture", cuda::cl_cuda_graph_begin_capture as *const u8);
-    builder.symbol("cl_cuda_graph_end_capture", cuda::cl_cuda_graph_end_capture as *const u8);
-    builder.symbol("cl_cuda_graph_upload", cuda::cl_cuda_graph_upload as *const u8);
-    builder.symbol("cl_cuda_graph_launch", cuda::cl_cuda_graph_launch as *const u8);
-    builder.symbol("cl_cuda_graph_destroy", cuda::cl_cuda_graph_destroy as *const u8);
-    builder.symbol("cl_cuda_pinned_alloc", cuda::cl_cuda_pinned_alloc as *const u8);
-    builder.symbol("cl_cuda_pinned_ptr", cuda::cl_cuda_pinned_ptr as *const u8);
-    builder.symbol("cl_cuda_pinned_free", cuda::cl_cuda_pinned_free as *const u8);
-    builder.symbol("cl_cuda_launch", cuda::cl_cuda_launch as *const u8);
-    builder.symbol("cl_cuda_launch_named", cuda::cl_cuda_launch_named as *const u8);
-    builder.symbol("cl_cuda_launch_on_stream", cuda::cl_cuda_launch_on_stream as *const u8);
-    builder.symbol("cl_cuda_launch_named_on_stream", cuda::cl_cuda_launch_named_on_stream as *const u8);
-    builder.symbol("cl_cuda_sync", cuda::cl_cuda_sync as *const u8);
-    builder.symbol("cl_cuda_cleanup", cuda::cl_cuda_cleanup as *const u8);
-
-    // cuBLAS
-    builder.symbol("cl_cublas_sgemm", cuda::cl_cublas_sgemm as *const u8);
-    builder.symbol("cl_cublas_sgemv", cuda::cl_cublas_sgemv as *const u8);
-    builder.symbol("cl_cublas_sgemv_on_stream", cuda::cl_cublas_sgemv_on_stream as *const u8);
-    builder.symbol("cl_cublas_sgemm_strided_batched", cuda::cl_cublas_sgemm_strided_batched as *const u8);
-    builder.symbol("cl_cublas_sgemm_strided_batched_on_stream", cuda::cl_cublas_sgemm_strided_batched_on_stream as *const u8);
+    builder.symbol("ht_remove", ht::cl_ht_remove as *const u8);
+    builder.symbol("ht_clear", ht::cl_ht_clear as *const u8);
+    builder.symbol("ht_stats", ht::cl_ht_stats as *const u8);
+
+    #[cfg(feature = "gpu")]
+    {
+        // wgpu (cross-platform GPU)
+        builder.symbol("cl_gpu_init", gpu::cl_gpu_init as *const u8);
+        builder.symbol("cl_gpu_create_buffer", gpu::cl_gpu_create_buffer as *const u8);
+        builder.symbol("cl_gpu_create_pipeline", gpu::cl_gpu_create_pipeline as *const u8);
+        builder.symbol("cl_gpu_upload", gpu::cl_gpu_upload as *const u8);
+        builder.symbol("cl_gpu_upload_ptr", gpu::cl_gpu_upload_ptr as *const u8);
+        builder.symbol("cl_gpu_dispatch", gpu::cl_gpu_dispatch as *const u8);
+        builder.symbol("cl_gpu_download", gpu::cl_gpu_download as *const u8);
+        builder.symbol("cl_gpu_download_ptr", gpu::cl_gpu_download_ptr as *const u8);
+        builder.symbol("cl_gpu_upload_typed", gpu::cl_gpu_upload_typed as *const u8);
+        builder.symbol("cl_gpu_download_typed", gpu::cl_gpu_download_typed as *const u8);
+        builder.symbol("cl_gpu_download_async", gpu::cl_gpu_download_async as *const u8);
+        builder.symbol("cl_gpu_poll", gpu::cl_gpu_poll as *const u8);
+        builder.symbol("cl_gpu_wait", gpu::cl_gpu_wait as *const u8);
+        builder.symbol("cl_gpu_cleanup", gpu::cl_gpu_cleanup as *const u8);
+        builder.symbol("cl_gpu_init_fallback", gpu::cl_gpu_init_fallback as *const u8);
+        builder.symbol("cl_gpu_init_adapter", gpu::cl_gpu_init_adapter as *const u8);
+        builder.symbol("cl_gpu_pipeline_cpu", gpu::cl_gpu_pipeline_cpu as *const u8);
+        builder.symbol(
+            "cl_gpu_create_pipeline_regions",
+            gpu::cl_gpu_create_pipeline_regions as *const u8,
+        );
+        builder.symbol(
+            "cl_gpu_dispatch_regions",
+            gpu::cl_gpu_dispatch_regions as *const u8,
+        );
+
+        // Window / input / present (shares the wgpu device for zero-copy present)
+        builder.symbol("cl_window_init", window::cl_window_init as *const u8);
+        builder.symbol("cl_window_open", window::cl_window_open as *const u8);
+        builder.symbol("cl_window_poll", window::cl_window_poll as *const u8);
+        builder.symbol(
+            "cl_window_present_gpu_buffer",
+            window::cl_window_present_gpu_buffer as *const u8,
+        );
+        builder.symbol("cl_window_cleanup", window::cl_window_cleanup as *const u8);
+    }
+
+    #[cfg(feature = "cuda")]
+    {
+        // CUDA core
+        builder.symbol("cl_cuda_init", cuda::cl_cuda_init as *const u8);
+        builder.symbol("cl_cuda_create_buffer", cuda::cl_cuda_create_buffer as *const u8);
+        builder.symbol("cl_cuda_upload", cuda::cl_cuda_upload as *const u8);
+        builder.symbol("cl_cuda_upload_ptr", cuda::cl_cuda_upload_ptr as *const u8);
+        builder.symbol("cl_cuda_upload_ptr_offset", cuda::cl_cuda_upload_ptr_offset as *const u8);
+        builder.symbol("cl_cuda_upload_ptr_async", cuda::cl_cuda_upload_ptr_async as *const u8);
+        builder.symbol("cl_cuda_upload_ptr_offset_async", cuda::cl_cuda_upload_ptr_offset_async as *const u8);
+        builder.symbol("cl_cuda_download", cuda::cl_cuda_download as *const u8);
+        builder.symbol("cl_cuda_download_ptr", cuda::cl_cuda_download_ptr as *const u8);
+        builder.symbol("cl_cuda_download_ptr_offset", cuda::cl_cuda_download_ptr_offset as *const u8);
+        builder.symbol("cl_cuda_download_ptr_async", cuda::cl_cuda_download_ptr_async as *const u8);
+        builder.symbol("cl_cuda_free_buffer", cuda::cl_cuda_free_buffer as *const u8);
+        builder.symbol("cl_cuda_stream_create", cuda::cl_cuda_stream_create as *const u8);
+        builder.symbol("cl_cuda_stream_sync", cuda::cl_cuda_stream_sync as *const u8);
+        builder.symbol("cl_cuda_stream_destroy", cuda::cl_cuda_stream_destroy as *const u8);
+        builder.symbol("cl_cuda_event_create", cuda::cl_cuda_event_create as *const u8);
+        builder.symbol("cl_cuda_event_record", cuda::cl_cuda_event_record as *const u8);
+        builder.symbol("cl_cuda_stream_wait_event", cuda::cl_cuda_stream_wait_event as *const u8);
+        builder.symbol("cl_cuda_event_elapsed_ms_bits", cuda::cl_cuda_event_elapsed_ms_bits as *const u8);
+        builder.symbol("cl_cuda_event_destroy", cuda::cl_cuda_event_destroy as *const u8);
+        builder.symbol("cl_cuda_graph_begin_capture", cuda::cl_cuda_graph_begin_capture as *const u8);
+        builder.symbol("cl_cuda_graph_end_capture", cuda::cl_cuda_graph_end_capture as *const u8);
+        builder.symbol("cl_cuda_graph_upload", cuda::cl_cuda_graph_upload as *const u8);
+        builder.symbol("cl_cuda_graph_launch", cuda::cl_cuda_graph_launch as *const u8);
+        builder.symbol("cl_cuda_graph_destroy", cuda::cl_cuda_graph_destroy as *const u8);
+        builder.symbol("cl_cuda_pinned_alloc", cuda::cl_cuda_pinned_alloc as *const u8);
+        builder.symbol("cl_cuda_pinned_ptr", cuda::cl_cuda_pinned_ptr as *const u8);
+        builder.symbol("cl_cuda_pinned_free", cuda::cl_cuda_pinned_free as *const u8);
+        builder.symbol("cl_cuda_launch", cuda::cl_cuda_launch as *const u8);
+        builder.symbol("cl_cuda_launch_named", cuda::cl_cuda_launch_named as *const u8);
+        builder.symbol("cl_cuda_launch_on_stream", cuda::cl_cuda_launch_on_stream as *const u8);
+        builder.symbol("cl_cuda_launch_named_on_stream", cuda::cl_cuda_launch_named_on_stream as *const u8);
+        builder.symbol("cl_cuda_sync", cuda::cl_cuda_sync as *const u8);
+        builder.symbol("cl_cuda_cleanup", cuda::cl_cuda_cleanup as *const u8);
+
+        // cuBLAS
+        builder.symbol("cl_cublas_sgemm", cuda::cl_cublas_sgemm as *const u8);
+        builder.symbol("cl_cublas_sgemv", cuda::cl_cublas_sgemv as *const u8);
+        builder.symbol("cl_cublas_sgemv_on_stream", cuda::cl_cublas_sgemv_on_stream as *const u8);
+        builder.symbol("cl_cublas_sgemm_strided_batched", cuda::cl_cublas_sgemm_strided_batched as *const u8);
+        builder.symbol("cl_cublas_sgemm_strided_batched_on_stream", cuda::cl_cublas_sgemm_strided_batched_on_stream as *const u8);
+    }
 
     // File + math + stdio
     builder.symbol("cl_file_read", file::cl_file_read as *const u8);
     builder.symbol("cl_file_read_to_ptr", file::cl_file_read_to_ptr as *const u8);
     builder.symbol("cl_file_write", file::cl_file_write as *const u8);
     builder.symbol("cl_file_write_from_ptr", file::cl_file_write_from_ptr as *const u8);
+    builder.symbol("cl_file_retry", file::cl_file_retry as *const u8);
+    builder.symbol("cl_file_write_atomic", file_atomic::cl_file_write_atomic as *const u8);
+    builder.symbol("cl_file_atomic_init", file_atomic::cl_file_atomic_init as *const u8);
+    builder.symbol("cl_file_atomic_cleanup", file_atomic::cl_file_atomic_cleanup as *const u8);
+    builder.symbol("cl_file_atomic_open", file_atomic::cl_file_atomic_open as *const u8);
+    builder.symbol("cl_file_atomic_write", file_atomic::cl_file_atomic_write as *const u8);
+    builder.symbol("cl_file_commit", file_atomic::cl_file_commit as *const u8);
+    builder.symbol("cl_file_abort", file_atomic::cl_file_abort as *const u8);
+    builder.symbol(
+        "cl_file_handle_init",
+        file_handle::cl_file_handle_init as *const u8,
+    );
+    builder.symbol(
+        "cl_file_handle_cleanup",
+        file_handle::cl_file_handle_cleanup as *const u8,
+    );
+    builder.symbol("cl_file_open", file_handle::cl_file_open as *const u8);
+    builder.symbol("cl_file_close", file_handle::cl_file_close as *const u8);
+    builder.symbol(
+        "cl_file_read_handle",
+        file_handle::cl_file_read_handle as *const u8,
+    );
+    builder.symbol(
+        "cl_file_write_handle",
+        file_handle::cl_file_write_handle as *const u8,
+    );
+    builder.symbol("cl_file_stream_start", file_stream::cl_file_stream_start as *const u8);
+    builder.symbol("cl_file_stream_end", file_stream::cl_file_stream_end as *const u8);
     builder.symbol("cl_sinf", cl_sinf as *const u8);
     builder.symbol("cl_cosf", cl_cosf as *const u8);
     builder.symbol("cl_powf", cl_powf as *const u8);
+    builder.symbol("cl_approx", math::cl_approx as *const u8);
     builder.symbol("cl_stdin_readline", stdio::cl_stdin_readline as *const u8);
     builder.symbol("cl_stdout_write", stdio::cl_stdout_write as *const u8);
 
-    // Net
-    builder.symbol("cl_net_init", net::cl_net_init as *const u8);
-    builder.symbol("cl_net_listen", net::cl_net_listen as *const u8);
-    builder.symbol(
-        "cl_net_listener_port",
-        net::cl_net_listener_port as *const u8,
-    );
-    builder.symbol("cl_net_connect", net::cl_net_connect as *const u8);
-    builder.symbol("cl_net_accept", net::cl_net_accept as *const u8);
-    builder.symbol("cl_net_send", net::cl_net_send as *const u8);
-    builder.symbol("cl_net_recv", net::cl_net_recv as *const u8);
-    builder.symbol("cl_net_cleanup", net::cl_net_cleanup as *const u8);
-
-    // LMDB
-    builder.symbol("cl_lmdb_init", lmdb::cl_lmdb_init as *const u8);
-    builder.symbol("cl_lmdb_open", lmdb::cl_lmdb_open as *const u8);
-    builder.symbol("cl_lmdb_put", lmdb::cl_lmdb_put as *const u8);
-    builder.symbol("cl_lmdb_get", lmdb::cl_lmdb_get as *const u8);
-    builder.symbol("cl_lmdb_delete", lmdb::cl_lmdb_delete as *const u8);
-    builder.symbol("cl_lmdb_begin_write_txn", lmdb::cl_lmdb_begin_write_txn as *const u8);
-    builder.symbol("cl_lmdb_commit_write_txn", lmdb::cl_lmdb_commit_write_txn as *const u8);
-    builder.symbol("cl_lmdb_cursor_scan", lmdb::cl_lmdb_cursor_scan as *const u8);
-    builder.symbol("cl_lmdb_sync", lmdb::cl_lmdb_sync as *const u8);
-    builder.symbol("cl_lmdb_cleanup", lmdb::cl_lmdb_cleanup as *const u8);
+    // Memory
+    builder.symbol("cl_mem_fill", mem::cl_mem_fill as *const u8);
+    builder.symbol("cl_mem_copy", mem::cl_mem_copy as *const u8);
+    builder.symbol("cl_mem_compare", mem::cl_mem_compare as *const u8);
+    builder.symbol("cl_mem_scan", mem::cl_mem_scan as *const u8);
+    builder.symbol("cl_mem_cond_write", mem::cl_mem_cond_write as *const u8);
+    builder.symbol("cl_mem_byteswap", mem::cl_mem_byteswap as *const u8);
+    builder.symbol("cl_mem_publish", mem::cl_mem_publish as *const u8);
+    builder.symbol("cl_mem_snapshot", mem::cl_mem_snapshot as *const u8);
+    builder.symbol("cl_mem_array_op", mem::cl_mem_array_op as *const u8);
+    builder.symbol("cl_lz4_compress", lz4::cl_lz4_compress as *const u8);
+    builder.symbol("cl_lz4_decompress", lz4::cl_lz4_decompress as *const u8);
+    builder.symbol("cl_checksum", checksum::cl_checksum as *const u8);
+
+    // Arena
+    builder.symbol("cl_arena_init", arena::cl_arena_init as *const u8);
+    builder.symbol("cl_arena_alloc", arena::cl_arena_alloc as *const u8);
+    builder.symbol("cl_arena_size", arena::cl_arena_size as *const u8);
+    builder.symbol("cl_arena_free", arena::cl_arena_free as *const u8);
+    builder.symbol("cl_arena_cleanup", arena::cl_arena_cleanup as *const u8);
+    builder.symbol("cl_arena_bump", arena::cl_arena_bump as *const u8);
+    builder.symbol("cl_arena_reset", arena::cl_arena_reset as *const u8);
+
+    // Queue
+    builder.symbol("cl_queue_init", queue::cl_queue_init as *const u8);
+    builder.symbol("cl_queue_push", queue::cl_queue_push as *const u8);
+    builder.symbol("cl_queue_pop", queue::cl_queue_pop as *const u8);
+
+    // Tracing
+    builder.symbol("cl_trace", trace::cl_trace as *const u8);
+
+    // Clocks
+    builder.symbol("cl_clock", clock::cl_clock as *const u8);
+    builder.symbol("cl_sleep", clock::cl_sleep as *const u8);
+
+    // Random numbers
+    builder.symbol("cl_random", random::cl_random as *const u8);
+    builder.symbol("cl_random_weighted", random::cl_random_weighted as *const u8);
+
+    // Cancellation
+    builder.symbol("cl_cancelled", cancel::cl_cancelled as *const u8);
+
+    // Completion status
+    builder.symbol("cl_last_status", status::cl_last_status as *const u8);
+
+    // Checkpoint
+    builder.symbol("cl_checkpoint", checkpoint::cl_checkpoint as *const u8);
+    builder.symbol("cl_execute_nested", nested::cl_execute_nested as *const u8);
+
+    #[cfg(feature = "net")]
+    {
+        // Net
+        builder.symbol("cl_net_init", net::cl_net_init as *const u8);
+        builder.symbol("cl_net_listen", net::cl_net_listen as *const u8);
+        builder.symbol(
+            "cl_net_listener_port",
+            net::cl_net_listener_port as *const u8,
+        );
+        builder.symbol("cl_net_connect", net::cl_net_connect as *const u8);
+        builder.symbol("cl_net_accept", net::cl_net_accept as *const u8);
+        builder.symbol("cl_net_send", net::cl_net_send as *const u8);
+        builder.symbol("cl_net_recv", net::cl_net_recv as *const u8);
+        builder.symbol("cl_net_recv_framed", net::cl_net_recv_framed as *const u8);
+        builder.symbol("cl_net_close", net::cl_net_close as *const u8);
+        builder.symbol("cl_net_retry", net::cl_net_retry as *const u8);
+        builder.symbol("cl_net_cleanup", net::cl_net_cleanup as *const u8);
+        builder.symbol("cl_http_request", http::cl_http_request as *const u8);
+    }
+
+    #[cfg(feature = "lmdb")]
+    {
+        // LMDB
+        builder.symbol("cl_lmdb_init", lmdb::cl_lmdb_init as *const u8);
+        builder.symbol("cl_lmdb_open", lmdb::cl_lmdb_open as *const u8);
+        builder.symbol("cl_lmdb_open_with", lmdb::cl_lmdb_open_with as *const u8);
+        builder.symbol("cl_lmdb_put", lmdb::cl_lmdb_put as *const u8);
+        builder.symbol("cl_lmdb_get", lmdb::cl_lmdb_get as *const u8);
+        builder.symbol(
+            "cl_lmdb_get_bounded",
+            lmdb::cl_lmdb_get_bounded as *const u8,
+        );
+        builder.symbol("cl_lmdb_delete", lmdb::cl_lmdb_delete as *const u8);
+        builder.symbol("cl_lmdb_begin_write_txn", lmdb::cl_lmdb_begin_write_txn as *const u8);
+        builder.symbol("cl_lmdb_commit_write_txn", lmdb::cl_lmdb_commit_write_txn as *const u8);
+        builder.symbol("cl_lmdb_cursor_scan", lmdb::cl_lmdb_cursor_scan as *const u8);
+        builder.symbol(
+            "cl_lmdb_cursor_scan_bounded",
+            lmdb::cl_lmdb_cursor_scan_bounded as *const u8,
+        );
+        builder.symbol("cl_lmdb_sync", lmdb::cl_lmdb_sync as *const u8);
+        builder.symbol("cl_lmdb_close", lmdb::cl_lmdb_close as *const u8);
+        builder.symbol("cl_lmdb_handle_count", lmdb::cl_lmdb_handle_count as *const u8);
+        builder.symbol("cl_lmdb_cleanup", lmdb::cl_lmdb_cleanup as *const u8);
+    }
 
     // Threads
     builder.symbol("cl_thread_init", thread::cl_thread_init as *const u8);
@@ -129,23 +324,55 @@ fn register_symbols(builder: &mut JITBuilder) {
     builder.symbol("cl_thread_join", thread::cl_thread_join as *const u8);
     builder.symbol("cl_thread_cleanup", thread::cl_thread_cleanup as *const u8);
     builder.symbol("cl_thread_call", thread::cl_thread_call as *const u8);
+    builder.symbol("cl_thread_pool_start", thread::cl_thread_pool_start as *const u8);
+    builder.symbol("cl_thread_pool_start_bounded", thread::cl_thread_pool_start_bounded as *const u8);
+    builder.symbol("cl_thread_pool_submit", thread::cl_thread_pool_submit as *const u8);
+    builder.symbol("cl_thread_pool_try_submit", thread::cl_thread_pool_try_submit as *const u8);
+    builder.symbol(
+        "cl_thread_pool_dispatch",
+        thread::cl_thread_pool_dispatch as *const u8,
+    );
+    builder.symbol(
+        "cl_thread_pool_dispatch_if",
+        thread::cl_thread_pool_dispatch_if as *const u8,
+    );
+    builder.symbol("cl_thread_pool_broadcast", thread::cl_thread_pool_broadcast as *const u8);
+    builder.symbol("cl_thread_pool_chain", thread::cl_thread_pool_chain as *const u8);
+    builder.symbol("cl_thread_pool_fence", thread::cl_thread_pool_fence as *const u8);
+    builder.symbol("cl_thread_pool_wait", thread::cl_thread_pool_wait as *const u8);
+    builder.symbol("cl_thread_pool_stop", thread::cl_thread_pool_stop as *const u8);
+    builder.symbol("cl_thread_wait_until", thread::cl_thread_wait_until as *const u8);
+    builder.symbol("cl_thread_wake", thread::cl_thread_wake as *const u8);
+    builder.symbol(
+        "cl_thread_barrier_init",
+        thread::cl_thread_barrier_init as *const u8,
+    );
+    builder.symbol(
+        "cl_thread_barrier_wait",
+        thread::cl_thread_barrier_wait as *const u8,
+    );
+}
+
+/// Compiled functions, plus the counters they update when built with
+/// `profiled` set or a step budget.
+pub(crate) struct Compiled {
+    pub(crate) module: cranelift_jit::JITModule,
+    pub(crate) fns: Arc<Vec<unsafe extern "C" fn(*mut u8)>>,
+    pub(crate) profile: Option<Arc<ProfileState>>,
+    pub(crate) budget: Option<Arc<StepBudget>>,
 }
 
 pub(crate) fn compile_cranelift_ir(
     clif_source: &str,
-) -> Result<
-    (
-        cranelift_jit::JITModule,
-        Arc<Vec<unsafe extern "C" fn(*mut u8)>>,
-    ),
-    String,
-> {
+    profiled: bool,
+    step_budget: Option<u64>,
+) -> Result<Compiled, Error> {
     info!(ir_len = clif_source.len(), "compiling Cranelift IR");
 
-    let mut functions =
-        cranelift_reader::parse_functions(clif_source).map_err(|e| format!("{e}"))?;
+    let mut functions = cranelift_reader::parse_functions(clif_source)
+        .map_err(|e| Error::ClifParse(format!("{e}")))?;
     if functions.is_empty() {
-        return Err("No functions in CLIF IR".into());
+        return Err(Error::ClifParse("No functions in CLIF IR".into()));
     }
 
     let mut flag_builder = settings::builder();
@@ -157,6 +384,16 @@ pub(crate) fn compile_cranelift_ir(
     let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
 
     register_symbols(&mut builder);
+    if profiled {
+        builder.symbol("cl_profile_enter", profile::cl_profile_enter as *const u8);
+        builder.symbol("cl_profile_exit", profile::cl_profile_exit as *const u8);
+    }
+    if step_budget.is_some() {
+        builder.symbol(
+            "cl_step_budget_exhausted",
+            budget::cl_step_budget_exhausted as *const u8,
+        );
+    }
 
     let mut module = cranelift_jit::JITModule::new(builder);
 
@@ -183,12 +420,21 @@ pub(crate) fn compile_cranelift_ir(
 
     // cranelift_reader parses `%name` as ExternalName::TestCase; fix up to ExternalName::User.
     // Imports declared here get FuncIds starting at N (the number of user functions).
-    for func in functions.iter_mut() {
+    let mut import_names: Vec<String> = Vec::new();
+    for (i, func) in functions.iter_mut().enumerate() {
         let mut fixups = Vec::new();
         for (fref, data) in func.dfg.ext_funcs.iter() {
             if let cranelift_codegen::ir::ExternalName::TestCase(testcase) = &data.name {
                 let name = testcase.to_string();
                 let name = name.strip_prefix('%').unwrap_or(&name).to_string();
+                if let Some(feature) = disabled_feature(&name) {
+                    return Err(Error::Compile {
+                        fn_idx: i,
+                        message: format!(
+                            "%{name} needs the `{feature}` feature, which this build of base leaves out"
+                        ),
+                    });
+                }
                 let sig = func.dfg.signatures[data.signature].clone();
                 fixups.push((fref, name, sig));
             }
@@ -197,6 +443,9 @@ pub(crate) fn compile_cranelift_ir(
             let fid = module
                 .declare_function(&name, cranelift_module::Linkage::Import, &sig)
                 .expect("Failed to declare imported function");
+            if fid.as_u32() as usize - func_ids.len() == import_names.len() {
+                import_names.push(name);
+            }
             let user_ref =
                 func.declare_imported_user_function(cranelift_codegen::ir::UserExternalName {
                     namespace: 0,
@@ -207,11 +456,25 @@ pub(crate) fn compile_cranelift_ir(
         }
     }
 
+    let profile = if profiled {
+        let state = instrument_functions(&mut module, &mut functions, &import_names);
+        Some(state)
+    } else {
+        None
+    };
+    let budget = step_budget.map(|limit| budget_functions(&mut module, &mut functions, limit));
+
     for (i, func) in functions.into_iter().enumerate() {
         let mut ctx = cranelift_codegen::Context::for_function(func);
         module
             .define_function(func_ids[i], &mut ctx)
-            .expect("Failed to compile function");
+            .map_err(|e| Error::Compile {
+                fn_idx: i,
+                message: match e {
+                    ModuleError::Compilation(CodegenError::Verifier(errors)) => errors.to_string(),
+                    other => other.to_string(),
+                },
+            })?;
     }
     module.finalize_definitions().unwrap();
 
@@ -227,6 +490,92 @@ pub(crate) fn compile_cranelift_ir(
         count = compiled_fns.len(),
         "Cranelift IR compiled successfully"
     );
-    Ok((module, Arc::new(compiled_fns)))
+    Ok(Compiled {
+        module,
+        fns: Arc::new(compiled_fns),
+        profile,
+        budget,
+    })
+}
+
+/// Add profiling hooks to every function. Profile ids are the user function
+/// indices, followed by one id per imported FFI symbol in FuncId order.
+fn instrument_functions(
+    module: &mut cranelift_jit::JITModule,
+    functions: &mut [cranelift_codegen::ir::Function],
+    import_names: &[String],
+) -> Arc<ProfileState> {
+    let (enter_sig, exit_sig) = Hooks::signatures(module.isa().default_call_conv());
+    let hooks = Hooks {
+        enter: module
+            .declare_function(
+                "cl_profile_enter",
+                cranelift_module::Linkage::Import,
+                &enter_sig,
+            )
+            .expect("Failed to declare profiling hook"),
+        exit: module
+            .declare_function(
+                "cl_profile_exit",
+                cranelift_module::Linkage::Import,
+                &exit_sig,
+            )
+            .expect("Failed to declare profiling hook"),
+    };
+
+    let user_fns = functions.len();
+    let keys = (0..user_fns as u32)
+        .map(ProfileKey::Function)
+        .chain(import_names.iter().cloned().map(ProfileKey::Ffi))
+        .collect();
+    let state = Arc::new(ProfileState::new(keys));
+
+    for (i, func) in functions.iter_mut().enumerate() {
+        let callees: std::collections::HashMap<_, _> = func
+            .dfg
+            .ext_funcs
+            .iter()
+            .filter_map(|(fref, data)| match &data.name {
+                cranelift_codegen::ir::ExternalName::User(user_ref) => {
+                    let index = func.params.user_named_funcs()[*user_ref].index as usize;
+                    (index >= user_fns).then_some((fref, index))
+                }
+                _ => None,
+            })
+            .collect();
+        profile::instrument(func, i, &hooks, Arc::as_ptr(&state), |fref| {
+            callees.get(&fref).copied()
+        });
+    }
+    state
 }
 
+/// Make every loop header count against a budget of `limit` steps.
+fn budget_functions(
+    module: &mut cranelift_jit::JITModule,
+    functions: &mut [cranelift_codegen::ir::Function],
+    limit: u64,
+) -> Arc<StepBudget> {
+    let hook = module
+        .declare_function(
+            "cl_step_budget_exhausted",
+            cranelift_module::Linkage::Import,
+            &budget::hook_signature(module.isa().default_call_conv()),
+        )
+        .expect("Failed to declare step budget hook");
+    let headers: Vec<_> = functions
+        .iter()
+        .enumerate()
+        .map(|(i, func)| budget::loop_headers(func, i))
+        .collect();
+    let counts: Vec<usize> = headers.iter().map(Vec::len).collect();
+    let all = headers.into_iter().flatten().collect();
+    let state = Arc::new(StepBudget::new(limit, all));
+    let mut first_id = 0;
+    for (func, count) in functions.iter_mut().zip(counts) {
+        let range = first_id..first_id + count;
+        budget::instrument(func, &state.headers()[range], first_id, hook, &state);
+        first_id += count;
+    }
+    state
+}
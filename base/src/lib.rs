@@ -1,24 +1,150 @@
 pub use arrow_array::RecordBatch;
 use arrow_array::{ArrayRef, Float64Array, Int64Array, StringArray};
 use arrow_schema::{DataType, Field, Schema};
-pub use base_types::{Algorithm, Artifact, OutputBatchSchema, OutputColumn, OutputType, Setup};
+pub use base_types::{
+    Algorithm, Artifact, ArtifactReport, FieldDiff, OutputBatchSchema, OutputColumn, OutputType,
+    Profile, ProfileEntry, ProfileKey, Setup, StringArg, Symbol, SymbolError, TraceEvent,
+};
 use std::{
+    ops::Range,
+    path::Path,
     pin::Pin,
-    sync::{Arc, Once},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Once,
+    },
+    time::{Duration, Instant},
 };
-use tracing::{debug, info, info_span};
+use tracing::{debug, error, info, info_span, warn, Span};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
+mod budget;
 mod ffi;
 mod jit;
+mod link;
+mod memory;
+mod profile;
+mod validate;
+
+pub use ffi::io_log::IoLog;
+pub use ffi::sandbox::PathSandbox;
+#[cfg(feature = "gpu")]
+pub use ffi::wgpu::GpuPreferences;
+pub use link::{link_artifacts, LinkError, FRAGMENT_ALIGN};
+pub use memory::MemoryHandle;
+pub use validate::{
+    analyze_artifact, infer_memory_size, memory_operands, validate_artifact, MemoryOperand,
+    ValidationIssue,
+};
+#[cfg(feature = "gpu")]
+pub use wgpu::{AdapterInfo, Backends, PowerPreference};
 
-use crate::jit::{compile_cranelift_ir, THREAD_COMPILED_FNS};
+use crate::budget::StepBudget;
+use crate::ffi::thread::{ThreadStats, THREAD_STATS};
+use crate::jit::{compile_cranelift_ir, Compiled, THREAD_COMPILED_FNS};
+use crate::profile::ProfileState;
 use base_types::IoOffsets;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     ClifParse(String),
     Execution(String),
+    /// Cranelift rejected function `fn_idx` while compiling it.
+    Compile { fn_idx: usize, message: String },
+    /// The execution was stopped through its `CancelHandle`.
+    Cancelled,
+    /// The execution did not finish within `execute_with_timeout`'s limit.
+    Timeout(Duration),
+    /// No GPU adapter satisfied `select_gpu_adapter`'s preferences.
+    GpuInit(String),
+    /// `Algorithm::fn_idx` does not name a compiled function.
+    FnIndexOutOfRange {
+        fn_idx: usize,
+        available: usize,
+    },
+    /// An `Algorithm` symbol could not be written into memory.
+    Symbol(SymbolError),
+    /// A checkpoint could not be read or does not fit in memory.
+    Checkpoint(String),
+    /// A `MemoryHandle` access reached past the end of memory.
+    MemoryOutOfBounds {
+        offset: usize,
+        len: usize,
+        size: usize,
+    },
+    /// A `MemoryHandle` u64 access was not 8-byte aligned.
+    MemoryUnaligned {
+        offset: usize,
+    },
+    /// The `Base` instance behind a `MemoryHandle` has been dropped.
+    MemoryReleased,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ClifParse(msg) => write!(f, "CLIF parse error: {msg}"),
+            Error::Execution(msg) => write!(f, "execution error: {msg}"),
+            Error::Compile { fn_idx, message } => {
+                write!(f, "failed to compile function {fn_idx}: {message}")
+            }
+            Error::Cancelled => write!(f, "execution cancelled"),
+            Error::Timeout(limit) => write!(f, "execution timed out after {limit:?}"),
+            Error::GpuInit(msg) => write!(f, "GPU init error: {msg}"),
+            Error::FnIndexOutOfRange { fn_idx, available } => {
+                write!(f, "fn_idx {fn_idx} out of range (have {available} fns)")
+            }
+            Error::Symbol(e) => write!(f, "{e}"),
+            Error::Checkpoint(msg) => write!(f, "checkpoint error: {msg}"),
+            Error::MemoryOutOfBounds { offset, len, size } => write!(
+                f,
+                "memory access {offset:#x}+{len} out of bounds (size {size:#x})"
+            ),
+            Error::MemoryUnaligned { offset } => {
+                write!(f, "memory access at {offset:#x} is not 8-byte aligned")
+            }
+            Error::MemoryReleased => write!(f, "memory released: the Base instance was dropped"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Timing and worker counters collected by `Base::execute_with_stats`.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionStats {
+    /// Wall time of the whole execution.
+    pub elapsed: Duration,
+    /// Threads started via `cl_thread_spawn`.
+    pub threads_spawned: u64,
+    /// Functions run inline via `cl_thread_call`.
+    pub thread_calls: u64,
+    /// Summed time spawned threads spent running their function.
+    pub thread_busy: Duration,
+    /// Summed time `cl_thread_join` blocked waiting for a worker.
+    pub join_wait: Duration,
+}
+
+/// Stops an in-progress `Base::execute*` call from another thread.
+///
+/// Cancellation is cooperative: blocking FFI primitives (`cl_thread_wait_until`,
+/// blocking `cl_queue_push`, `cl_net_accept` and the `cl_net_recv` calls)
+/// give up, and CLIF loops can poll `cl_cancelled`.
+/// The execution then returns `Error::Cancelled`. A cancel issued while idle
+/// applies to the next execution.
+#[derive(Clone)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+        ffi::thread::wake_all_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
 }
 
 pub struct Base {
@@ -27,6 +153,18 @@ pub struct Base {
     clif_fns: Option<Arc<Vec<unsafe extern "C" fn(*mut u8)>>>,
     _module: Option<cranelift_jit::JITModule>,
     io_offsets: IoOffsets,
+    cancel: Arc<AtomicBool>,
+    profile: Option<Arc<ProfileState>>,
+    budget: Option<Arc<StepBudget>>,
+    random_seed: Option<u64>,
+    sandbox: Option<Arc<PathSandbox>>,
+    io_log: Option<IoLog>,
+    copy_stream_min: Option<usize>,
+    memory_handle: MemoryHandle,
+    arena: Option<Range<usize>>,
+    /// Constant shader addresses each function reaches, with the WGSL
+    /// `initial_memory` holds there.
+    shaders: Vec<validate::ShaderSite>,
 }
 
 unsafe impl Send for Base {}
@@ -34,27 +172,70 @@ unsafe impl Sync for Base {}
 
 impl Base {
     pub fn new(setup: Setup) -> Result<Self, Error> {
+        Self::build(setup, false, None)
+    }
+
+    /// Like `new`, but the compiled code counts calls and wall time per user
+    /// function and per FFI primitive; read them with `take_profile`. The
+    /// hooks are compiled in only here, so `new` instances carry no overhead.
+    pub fn new_profiled(setup: Setup) -> Result<Self, Error> {
+        Self::build(setup, true, None)
+    }
+
+    /// Like `new`, but each execution may enter loops at most `max_steps`
+    /// times in total, across all functions and the threads it starts. A
+    /// loop header that finds the budget spent makes its function return
+    /// zeros, and the execution fails with `Error::Execution` naming the
+    /// loop, and noting when it makes no calls, the usual sign of a loop
+    /// that can never exit. Each loop iteration pays a counter update, so
+    /// this is meant for tests and untrusted algorithms; `new` instances
+    /// carry no overhead. The instances of one `execute_many` call share one
+    /// budget, refilled at the start of the call.
+    pub fn new_with_step_budget(setup: Setup, max_steps: u64) -> Result<Self, Error> {
+        Self::build(setup, false, Some(max_steps))
+    }
+
+    fn build(setup: Setup, profiled: bool, step_budget: Option<u64>) -> Result<Self, Error> {
         let header_end = setup
             .io_offsets
             .out_len
             .saturating_add(std::mem::size_of::<usize>());
+        let arena = setup.arena();
         let needed = setup
             .memory_size
             .max(setup.initial_memory.len())
-            .max(header_end);
+            .max(header_end)
+            .max(arena.as_ref().map_or(0, |r| r.end));
+        // Take the caller's buffer as-is: no copy when it already covers
+        // `needed`, one exact-size grow otherwise. Without initial contents,
+        // zeroed pages come straight from the allocator and are only touched
+        // when the algorithm uses them.
+        let shaders = validate::entry_shaders(&setup.cranelift_ir, &setup.initial_memory);
         let mut memory = setup.initial_memory;
-        memory.resize(needed, 0);
-        Self::from_parts(
+        if memory.is_empty() {
+            memory = vec![0u8; needed];
+        } else if memory.len() < needed {
+            memory.reserve_exact(needed - memory.len());
+            memory.resize(needed, 0);
+        }
+        let mut base = Self::from_parts(
             setup.cranelift_ir,
             setup.io_offsets,
             memory.into_boxed_slice(),
-        )
+            profiled,
+            step_budget,
+        )?;
+        base.arena = arena;
+        base.shaders = shaders;
+        Ok(base)
     }
 
     fn from_parts(
         cranelift_ir: String,
         io_offsets: IoOffsets,
         memory: Box<[u8]>,
+        profiled: bool,
+        step_budget: Option<u64>,
     ) -> Result<Self, Error> {
         let _span = info_span!("base_new", memory_size = memory.len()).entered();
         info!("creating Base instance");
@@ -62,11 +243,17 @@ impl Base {
         let mut memory = Pin::new(memory);
         let mem_ptr = memory.as_mut().as_mut_ptr();
 
-        let (module, clif_fns) = if !cranelift_ir.is_empty() {
-            let (module, fns) = compile_cranelift_ir(&cranelift_ir).map_err(Error::ClifParse)?;
-            (Some(module), Some(fns))
+        let (module, clif_fns, profile, budget) = if !cranelift_ir.is_empty() {
+            let Compiled {
+                module,
+                fns,
+                profile,
+                budget,
+            } = compile_cranelift_ir(&cranelift_ir, profiled, step_budget)
+                .inspect_err(|e| error!(error = %e, "compilation failed"))?;
+            (Some(module), Some(fns), profile, budget)
         } else {
-            (None, None)
+            (None, None, None, None)
         };
 
         // Set thread-local compiled fns so FFI functions (cl_thread_init etc.) work on interpreter thread
@@ -77,15 +264,93 @@ impl Base {
         }
 
         info!("Base instance created");
+        let memory_handle = MemoryHandle::new(mem_ptr, memory.len());
         Ok(Base {
             memory,
             mem_ptr,
             clif_fns,
             _module: module,
             io_offsets,
+            cancel: Arc::new(AtomicBool::new(false)),
+            profile,
+            budget,
+            random_seed: None,
+            sandbox: None,
+            io_log: None,
+            copy_stream_min: None,
+            memory_handle,
+            arena: None,
+            shaders: Vec::new(),
         })
     }
 
+    /// Counters accumulated since the last call, across all executions and
+    /// worker threads; they restart from zero. `None` unless the instance was
+    /// built with `new_profiled`.
+    pub fn take_profile(&self) -> Option<Profile> {
+        self.profile.as_ref().map(|state| state.take())
+    }
+
+    /// Make `cl_random` deterministic: with `Some(seed)`, every execution
+    /// draws the same values on each thread, pool job, and call order as
+    /// any other execution with that seed. `None` (the default) draws from
+    /// fresh random state each time.
+    pub fn set_random_seed(&mut self, seed: Option<u64>) {
+        self.random_seed = seed;
+    }
+
+    /// Confine the paths that file, file stream, checkpoint and LMDB calls
+    /// open, in this execution and the threads it starts: relative paths are
+    /// taken from `working_dir`, and with `allowed_path_prefixes` set, a path
+    /// that resolves (symlinks included) outside every prefix fails with
+    /// status `PATH_DENIED` without touching the filesystem. `None` (the
+    /// default) opens paths as given, relative to the process's directory.
+    pub fn set_path_sandbox(&mut self, sandbox: Option<PathSandbox>) {
+        self.sandbox = sandbox.map(|s| Arc::new(s.canonicalized()));
+    }
+
+    /// Record the file and network I/O of each execution, or replay it from
+    /// an earlier recording (see `IoLog`), for the execution and the threads
+    /// it starts. Logged are the reads and writes of `cl_file_read`,
+    /// `cl_file_read_to_ptr`, `cl_file_write` and `cl_file_write_from_ptr`,
+    /// and every `cl_net_*` call on a connection or listener. A replay never
+    /// touches the files or sockets: reads and receives are served from the
+    /// log, and writes and sends are compared with the recorded bytes. A
+    /// call the log has no entry for, a read whose recorded bytes exceed its
+    /// buffer, or a write that differs, fails with status `FAILED` and the
+    /// execution returns `Error::Execution`. Other I/O calls (file handles,
+    /// atomic writes, file streams, `cl_http_request`, `cl_checkpoint`) are
+    /// not logged, and a replay fails them the same way.
+    /// `execute_many` rejects its instances while a log is set. `None` (the
+    /// default) performs I/O as usual.
+    pub fn set_io_log(&mut self, log: Option<IoLog>) {
+        self.io_log = log;
+    }
+
+    /// Make `cl_mem_copy` write copies of at least `bytes` with streaming
+    /// (cache-bypassing) stores, in this execution and the threads it
+    /// starts. Streaming pays off once the destination would not fit in the
+    /// cache anyway, so the best threshold depends on the machine: lower it
+    /// where the cache is small, or raise it when the algorithm reads its
+    /// copies straight back. `None` (the default) streams from 8 MiB.
+    pub fn set_copy_stream_min(&mut self, bytes: Option<usize>) {
+        self.copy_stream_min = bytes;
+    }
+
+    /// A handle for reading and writing this instance's memory from other
+    /// threads, including while an execution runs; see `MemoryHandle`. It
+    /// addresses the instance's own memory, not the per-call copies that
+    /// `execute_many` runs on, and fails with `Error::MemoryReleased` once
+    /// the instance is dropped.
+    pub fn memory_handle(&self) -> MemoryHandle {
+        self.memory_handle.clone()
+    }
+
+    /// A handle that cancels whichever execution of this instance is running.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle(self.cancel.clone())
+    }
+
     pub fn execute(
         &mut self,
         algorithm: &Algorithm,
@@ -100,51 +365,441 @@ impl Base {
         data: &[u8],
         out: &mut [u8],
     ) -> Result<Vec<RecordBatch>, Error> {
-        let _span = info_span!("execute", fn_idx = algorithm.fn_idx).entered();
+        let _span = info_span!(
+            "execute",
+            execution = next_execution_id(),
+            fn_idx = algorithm.fn_idx,
+            label = algorithm.label(algorithm.fn_idx)
+        )
+        .entered();
         info!("starting execution");
+        self.run(algorithm, data, out)
+            .inspect_err(|e| error!(error = %e, "execution failed"))
+    }
 
-        // Write data/out pointer + length into reserved region so CLIF code can access
-        // the caller's buffer directly via pointer (zero-copy).
-        unsafe {
-            std::ptr::write_unaligned(
-                self.memory[self.io_offsets.data_ptr..].as_mut_ptr() as *mut *const u8,
-                data.as_ptr(),
-            );
-            std::ptr::write_unaligned(
-                self.memory[self.io_offsets.data_len..].as_mut_ptr() as *mut usize,
-                data.len(),
-            );
-            std::ptr::write_unaligned(
-                self.memory[self.io_offsets.out_ptr..].as_mut_ptr() as *mut *mut u8,
-                out.as_mut_ptr(),
-            );
-            std::ptr::write_unaligned(
-                self.memory[self.io_offsets.out_len..].as_mut_ptr() as *mut usize,
-                out.len(),
-            );
-        }
+    fn run(
+        &mut self,
+        algorithm: &Algorithm,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<Vec<RecordBatch>, Error> {
+        algorithm
+            .write_symbols(&mut self.memory)
+            .map_err(Error::Symbol)?;
+
+        write_io_slots(&mut self.memory, &self.io_offsets, data, out);
 
         if let Some(ref fns) = self.clif_fns {
             let fn_idx = algorithm.fn_idx as usize;
             if fn_idx >= fns.len() {
-                return Err(Error::Execution(format!(
-                    "fn_idx {fn_idx} out of range (have {} fns)",
-                    fns.len()
-                )));
+                return Err(Error::FnIndexOutOfRange {
+                    fn_idx,
+                    available: fns.len(),
+                });
+            }
+            self.check_shaders(fn_idx, &self.memory)?;
+            if self.cancel.swap(false, Ordering::AcqRel) {
+                return Err(Error::Cancelled);
             }
+            let io_log = match &self.io_log {
+                Some(log) => Some(Arc::new(ffi::io_log::Session::open(log).map_err(|e| {
+                    Error::Execution(format!("cannot open I/O log {log:?}: {e}"))
+                })?)),
+                None => None,
+            };
             debug!(fn_idx, "clif_call");
+            ffi::cancel::set_token(Some(self.cancel.clone()));
+            ffi::random::install(self.random_seed, 0);
+            ffi::sandbox::set(self.sandbox.clone());
+            ffi::io_log::set(io_log.clone());
+            ffi::arena::set_bump(self.bump_arena(self.mem_ptr));
+            ffi::mem::set_copy_stream_min(self.copy_stream_min);
+            if let Some(budget) = &self.budget {
+                budget.reset();
+            }
+            ffi::clock::begin();
             unsafe { fns[fn_idx](self.mem_ptr) };
+            ffi::cancel::set_token(None);
+            ffi::clock::set_clock(None);
+            ffi::sandbox::set(None);
+            ffi::io_log::set(None);
+            ffi::file::set_retry_policy(None);
+            ffi::arena::set_bump(None);
+            ffi::mem::set_copy_stream_min(None);
+            if let Some(msg) = self.budget.as_ref().and_then(|b| b.exhausted(algorithm)) {
+                self.cancel.store(false, Ordering::Release);
+                return Err(Error::Execution(msg));
+            }
+            if self.cancel.swap(false, Ordering::AcqRel) {
+                info!("execution cancelled");
+                return Err(Error::Cancelled);
+            }
+            if let Some(session) = io_log {
+                session.finish().map_err(Error::Execution)?;
+            }
         }
 
         let batches = build_record_batches(&self.memory, &algorithm.output);
         info!("execution complete");
         Ok(batches)
     }
+
+    /// Run each of `algorithms` on its own copy of this instance's memory, at
+    /// most `parallelism` at a time, sharing the compiled code, FFI runtime
+    /// and GPU device. Instances cannot see each other's memory, and the
+    /// handles they open live in their own copy, so one failing leaves the
+    /// others running; results come back in input order. The IO slots are
+    /// empty (no `data` or `out`), so instances take their inputs through
+    /// symbols and report through files or Arrow output. A cancel from
+    /// `cancel_handle` stops instances that have not started yet.
+    pub fn execute_many(
+        &self,
+        algorithms: &[Algorithm],
+        parallelism: usize,
+    ) -> Vec<Result<Vec<RecordBatch>, Error>> {
+        let _span = info_span!(
+            "execute_many",
+            execution = next_execution_id(),
+            instances = algorithms.len()
+        )
+        .entered();
+        if self.cancel.swap(false, Ordering::AcqRel) {
+            error!("execute_many cancelled before starting");
+            return algorithms.iter().map(|_| Err(Error::Cancelled)).collect();
+        }
+        if let Some(budget) = &self.budget {
+            budget.reset();
+        }
+        let next = AtomicUsize::new(0);
+        let workers = parallelism.clamp(1, algorithms.len().max(1));
+        let parent = Span::current();
+        let mut results: Vec<_> = std::thread::scope(|s| {
+            let workers: Vec<_> = (0..workers)
+                .map(|_| {
+                    s.spawn(|| {
+                        let _span = parent.enter();
+                        self.execute_worker(algorithms, &next)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("execute_many worker panicked"))
+                .collect()
+        });
+        self.cancel.store(false, Ordering::Release);
+        results.sort_by_key(|&(i, _)| i);
+        info!("execute_many complete");
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Run instances claimed from `next` until none are left.
+    fn execute_worker(
+        &self,
+        algorithms: &[Algorithm],
+        next: &AtomicUsize,
+    ) -> Vec<(usize, Result<Vec<RecordBatch>, Error>)> {
+        if let Some(ref fns) = self.clif_fns {
+            THREAD_COMPILED_FNS.with(|cell| *cell.borrow_mut() = Some(fns.clone()));
+        }
+        ffi::cancel::set_token(Some(self.cancel.clone()));
+        let mut done = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(algorithm) = algorithms.get(i) else {
+                break;
+            };
+            let _span = info_span!(
+                "instance",
+                index = i,
+                fn_idx = algorithm.fn_idx,
+                label = algorithm.label(algorithm.fn_idx)
+            )
+            .entered();
+            let result = self.execute_instance(algorithm);
+            if let Err(e) = &result {
+                error!(error = %e, "instance failed");
+            }
+            done.push((i, result));
+        }
+        ffi::cancel::set_token(None);
+        done
+    }
+
+    /// Fail with `Error::GpuInit`, before it runs, when function `fn_idx`, or
+    /// a function it reaches through calls or thread `fn_index` arguments,
+    /// creates a pipeline from invalid WGSL in `memory`, which already holds
+    /// the algorithm's symbols.
+    fn check_shaders(&self, fn_idx: usize, memory: &[u8]) -> Result<(), Error> {
+        let mut sites = self.shaders.iter().filter(|s| s.entry == fn_idx);
+        match sites.find_map(|s| s.error(memory)) {
+            Some(msg) => {
+                error!(fn_idx, "{msg}");
+                Err(Error::GpuInit(msg))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// A fresh bump arena in the memory at `mem_ptr`, if one is declared.
+    fn bump_arena(&self, mem_ptr: *mut u8) -> Option<Arc<ffi::arena::BumpArena>> {
+        let arena = self.arena.as_ref()?;
+        let start = unsafe { mem_ptr.add(arena.start) };
+        Some(Arc::new(ffi::arena::BumpArena::new(start, arena.len())))
+    }
+
+    fn execute_instance(&self, algorithm: &Algorithm) -> Result<Vec<RecordBatch>, Error> {
+        if self.cancel.load(Ordering::Acquire) {
+            return Err(Error::Cancelled);
+        }
+        if self.io_log.is_some() {
+            return Err(Error::Execution(
+                "execute_many does not support an I/O log".into(),
+            ));
+        }
+        let mut memory = self.memory.to_vec();
+        algorithm
+            .write_symbols(&mut memory)
+            .map_err(Error::Symbol)?;
+        write_io_slots(&mut memory, &self.io_offsets, &[], &mut []);
+        if let Some(ref fns) = self.clif_fns {
+            let fn_idx = algorithm.fn_idx as usize;
+            let f = *fns.get(fn_idx).ok_or(Error::FnIndexOutOfRange {
+                fn_idx,
+                available: fns.len(),
+            })?;
+            self.check_shaders(fn_idx, &memory)?;
+            debug!(fn_idx, "clif_call");
+            ffi::random::install(self.random_seed, 0);
+            ffi::sandbox::set(self.sandbox.clone());
+            ffi::arena::set_bump(self.bump_arena(memory.as_mut_ptr()));
+            ffi::mem::set_copy_stream_min(self.copy_stream_min);
+            ffi::clock::begin();
+            unsafe { f(memory.as_mut_ptr()) };
+            ffi::clock::set_clock(None);
+            ffi::sandbox::set(None);
+            ffi::file::set_retry_policy(None);
+            ffi::arena::set_bump(None);
+            ffi::mem::set_copy_stream_min(None);
+            if let Some(msg) = self.budget.as_ref().and_then(|b| b.exhausted(algorithm)) {
+                return Err(Error::Execution(msg));
+            }
+            if self.cancel.load(Ordering::Acquire) {
+                return Err(Error::Cancelled);
+            }
+        }
+        Ok(build_record_batches(&memory, &algorithm.output))
+    }
+
+    /// Restore memory from a `cl_checkpoint` snapshot, then run `algorithm`
+    /// as `execute_into` would. The algorithm continues from whatever progress
+    /// it recorded in memory before the snapshot; handles held by FFI state
+    /// (files, sockets, GPU buffers) are not part of a checkpoint.
+    pub fn execute_resume(
+        &mut self,
+        algorithm: &Algorithm,
+        checkpoint: impl AsRef<Path>,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<Vec<RecordBatch>, Error> {
+        let image = ffi::checkpoint::read_checkpoint(checkpoint.as_ref())
+            .map_err(|e| Error::Checkpoint(e.to_string()))
+            .and_then(|image| {
+                if image.len() > self.memory.len() {
+                    return Err(Error::Checkpoint(format!(
+                        "snapshot of {} bytes exceeds memory of {} bytes",
+                        image.len(),
+                        self.memory.len()
+                    )));
+                }
+                Ok(image)
+            })
+            .inspect_err(|e| error!(error = %e, "resume failed"))?;
+        self.memory[..image.len()].copy_from_slice(&image);
+        self.execute_into(algorithm, data, out)
+    }
+
+    /// Like `execute_into`, but cancels the execution once `timeout` elapses
+    /// and returns `Error::Timeout`. Cancellation is cooperative (see
+    /// `CancelHandle`), so FFI calls already in progress, such as a file
+    /// write, run to completion before the execution unwinds.
+    pub fn execute_with_timeout(
+        &mut self,
+        algorithm: &Algorithm,
+        data: &[u8],
+        out: &mut [u8],
+        timeout: Duration,
+    ) -> Result<Vec<RecordBatch>, Error> {
+        let handle = self.cancel_handle();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let watchdog = std::thread::spawn(move || {
+            let elapsed = matches!(
+                done_rx.recv_timeout(timeout),
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout)
+            );
+            // A cancel already pending is the caller's, not the deadline's.
+            let expired = elapsed && !handle.is_cancelled();
+            if expired {
+                handle.cancel();
+            }
+            expired
+        });
+        let result = self.execute_into(algorithm, data, out);
+        drop(done_tx);
+        let expired = watchdog.join().unwrap_or(false);
+        if expired {
+            // The deadline may pass after the execution last checked for a
+            // cancel; do not let that stale cancel leak into the next one.
+            self.cancel.store(false, Ordering::Release);
+        }
+        match result {
+            Err(Error::Cancelled) if expired => {
+                error!(?timeout, "execution timed out");
+                Err(Error::Timeout(timeout))
+            }
+            other => other,
+        }
+    }
+
+    /// Like `execute_into`, but also returns the events recorded by
+    /// `cl_trace` calls made on the executing thread. Outside this call
+    /// `cl_trace` is a no-op.
+    pub fn execute_traced(
+        &mut self,
+        algorithm: &Algorithm,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<(Vec<RecordBatch>, Vec<TraceEvent>), Error> {
+        ffi::trace::begin();
+        let result = self.execute_into(algorithm, data, out);
+        let events = ffi::trace::finish();
+        result.map(|batches| (batches, events))
+    }
+
+    /// Like `execute_into`, but also returns worker thread counters and
+    /// timings. Regular executions skip the bookkeeping entirely.
+    pub fn execute_with_stats(
+        &mut self,
+        algorithm: &Algorithm,
+        data: &[u8],
+        out: &mut [u8],
+    ) -> Result<(Vec<RecordBatch>, ExecutionStats), Error> {
+        let counters = Arc::new(ThreadStats::default());
+        THREAD_STATS.with(|cell| *cell.borrow_mut() = Some(counters.clone()));
+        let start = Instant::now();
+        let result = self.execute_into(algorithm, data, out);
+        let elapsed = start.elapsed();
+        THREAD_STATS.with(|cell| *cell.borrow_mut() = None);
+
+        let stats = ExecutionStats {
+            elapsed,
+            threads_spawned: counters.spawned.load(Ordering::Relaxed),
+            thread_calls: counters.inline_calls.load(Ordering::Relaxed),
+            thread_busy: Duration::from_nanos(counters.busy_ns.load(Ordering::Relaxed)),
+            join_wait: Duration::from_nanos(counters.join_wait_ns.load(Ordering::Relaxed)),
+        };
+        result.map(|batches| (batches, stats))
+    }
+}
+
+impl Drop for Base {
+    fn drop(&mut self) {
+        self.memory_handle.release();
+    }
+}
+
+/// Write the data/out pointers and lengths into the reserved IO slots so CLIF
+/// code can access the caller's buffers directly (zero-copy).
+fn write_io_slots(memory: &mut [u8], offsets: &IoOffsets, data: &[u8], out: &mut [u8]) {
+    unsafe {
+        std::ptr::write_unaligned(
+            memory[offsets.data_ptr..].as_mut_ptr() as *mut *const u8,
+            data.as_ptr(),
+        );
+        std::ptr::write_unaligned(
+            memory[offsets.data_len..].as_mut_ptr() as *mut usize,
+            data.len(),
+        );
+        std::ptr::write_unaligned(
+            memory[offsets.out_ptr..].as_mut_ptr() as *mut *mut u8,
+            out.as_mut_ptr(),
+        );
+        std::ptr::write_unaligned(
+            memory[offsets.out_len..].as_mut_ptr() as *mut usize,
+            out.len(),
+        );
+    }
+}
+
+/// Id carried by each execution's span, so log lines from its worker
+/// threads can be told apart from concurrent executions'.
+fn next_execution_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Host-side settings for a one-shot `run_with`. What the algorithm computes
+/// lives in the `Setup` and `Algorithm` of its artifact; these only decide
+/// how this machine runs it, so they are not part of the artifact. The
+/// default matches `run`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunOptions {
+    /// Cancel the execution once this elapses; see
+    /// `Base::execute_with_timeout`.
+    pub timeout: Option<Duration>,
+    /// Compile with a loop step budget; see `Base::new_with_step_budget`.
+    pub max_steps: Option<u64>,
+    /// See `Base::set_random_seed`.
+    pub random_seed: Option<u64>,
+    /// See `Base::set_path_sandbox`.
+    pub sandbox: Option<PathSandbox>,
+    /// See `Base::set_io_log`.
+    pub io_log: Option<IoLog>,
+    /// See `Base::set_copy_stream_min`.
+    pub copy_stream_min: Option<usize>,
 }
 
 pub fn run(setup: Setup, algorithm: Algorithm) -> Result<Vec<RecordBatch>, Error> {
-    let mut base = Base::new(setup)?;
-    base.execute(&algorithm, &[])
+    run_with(setup, algorithm, RunOptions::default())
+}
+
+/// `run` under `options`.
+pub fn run_with(
+    setup: Setup,
+    algorithm: Algorithm,
+    options: RunOptions,
+) -> Result<Vec<RecordBatch>, Error> {
+    let mut base = match options.max_steps {
+        Some(max_steps) => Base::new_with_step_budget(setup, max_steps)?,
+        None => Base::new(setup)?,
+    };
+    base.set_random_seed(options.random_seed);
+    base.set_path_sandbox(options.sandbox);
+    base.set_io_log(options.io_log);
+    base.set_copy_stream_min(options.copy_stream_min);
+    match options.timeout {
+        Some(timeout) => base.execute_with_timeout(&algorithm, &[], &mut [], timeout),
+        None => base.execute(&algorithm, &[]),
+    }
+}
+
+/// List the adapters wgpu can see on `backends`, e.g. to let a user choose
+/// one before calling `select_gpu_adapter`.
+#[cfg(feature = "gpu")]
+pub fn enumerate_gpu_adapters(backends: Backends) -> Vec<AdapterInfo> {
+    ffi::wgpu::enumerate_adapters(backends)
+}
+
+/// Pin the process-wide GPU device to the adapter best matching `prefs`.
+///
+/// The device is created once per process, so this must be called before
+/// any artifact runs a GPU FFI call; otherwise the default high-performance
+/// adapter is used.
+#[cfg(feature = "gpu")]
+pub fn select_gpu_adapter(prefs: &GpuPreferences) -> Result<AdapterInfo, Error> {
+    ffi::wgpu::select_adapter(prefs)
+        .map_err(Error::GpuInit)
+        .inspect_err(|e| error!(error = %e, "GPU adapter selection failed"))
 }
 
 pub fn init_tracing() {
@@ -165,20 +820,51 @@ pub fn init_tracing() {
     });
 }
 
+/// The little-endian u64 at `offset`, or 0 when it does not fit in memory.
+fn read_u64_at(memory: &[u8], offset: usize) -> u64 {
+    offset
+        .checked_add(8)
+        .and_then(|end| memory.get(offset..end))
+        .map_or(0, |bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Rows of `col` that can start inside memory: 8 bytes per numeric row, at
+/// least a NUL per string row.
+fn rows_in_memory(col: &OutputColumn, memory_len: usize) -> usize {
+    let available = memory_len.saturating_sub(col.data_offset);
+    match col.dtype {
+        OutputType::I64 | OutputType::F64 => available / 8,
+        OutputType::Utf8 => available,
+    }
+}
+
 fn build_record_batches(memory: &[u8], schemas: &[OutputBatchSchema]) -> Vec<RecordBatch> {
     let mut batches = Vec::with_capacity(schemas.len());
     for schema in schemas {
-        let row_count = if schema.row_count_offset + 8 <= memory.len() {
-            let bytes: [u8; 8] = memory[schema.row_count_offset..schema.row_count_offset + 8]
-                .try_into()
-                .unwrap();
-            u64::from_le_bytes(bytes) as usize
-        } else {
-            0
-        };
+        let mut row_count = read_u64_at(memory, schema.row_count_offset) as usize;
         if row_count == 0 {
             continue;
         }
+        // The count is whatever the algorithm stored, so it can exceed what
+        // memory holds; rows past every column's end would only be padding.
+        let limit = schema
+            .columns
+            .iter()
+            .map(|col| rows_in_memory(col, memory.len()))
+            .max()
+            .unwrap_or(0);
+        if row_count > limit {
+            warn!(
+                row_count,
+                limit,
+                row_count_offset = schema.row_count_offset,
+                "output row count exceeds memory, clamped"
+            );
+            row_count = limit;
+            if row_count == 0 {
+                continue;
+            }
+        }
 
         let mut fields = Vec::with_capacity(schema.columns.len());
         let mut arrays: Vec<ArrayRef> = Vec::with_capacity(schema.columns.len());
@@ -189,13 +875,8 @@ fn build_record_batches(memory: &[u8], schemas: &[OutputBatchSchema]) -> Vec<Rec
                     fields.push(Field::new(&col.name, DataType::Int64, false));
                     let mut values = Vec::with_capacity(row_count);
                     for i in 0..row_count {
-                        let off = col.data_offset + i * 8;
-                        if off + 8 <= memory.len() {
-                            let bytes: [u8; 8] = memory[off..off + 8].try_into().unwrap();
-                            values.push(i64::from_le_bytes(bytes));
-                        } else {
-                            values.push(0);
-                        }
+                        let off = col.data_offset.saturating_add(i * 8);
+                        values.push(read_u64_at(memory, off) as i64);
                     }
                     arrays.push(Arc::new(Int64Array::from(values)) as ArrayRef);
                 }
@@ -203,35 +884,25 @@ fn build_record_batches(memory: &[u8], schemas: &[OutputBatchSchema]) -> Vec<Rec
                     fields.push(Field::new(&col.name, DataType::Float64, false));
                     let mut values = Vec::with_capacity(row_count);
                     for i in 0..row_count {
-                        let off = col.data_offset + i * 8;
-                        if off + 8 <= memory.len() {
-                            let bytes: [u8; 8] = memory[off..off + 8].try_into().unwrap();
-                            values.push(f64::from_le_bytes(bytes));
-                        } else {
-                            values.push(0.0);
-                        }
+                        let off = col.data_offset.saturating_add(i * 8);
+                        values.push(f64::from_bits(read_u64_at(memory, off)));
                     }
                     arrays.push(Arc::new(Float64Array::from(values)) as ArrayRef);
                 }
                 OutputType::Utf8 => {
                     fields.push(Field::new(&col.name, DataType::Utf8, false));
                     let mut strings = Vec::with_capacity(row_count);
-                    let total_byte_len = if col.len_offset + 8 <= memory.len() {
-                        let bytes: [u8; 8] = memory[col.len_offset..col.len_offset + 8]
-                            .try_into()
-                            .unwrap();
-                        u64::from_le_bytes(bytes) as usize
-                    } else {
-                        0
-                    };
+                    let total_byte_len = read_u64_at(memory, col.len_offset) as usize;
                     if row_count == 1 {
-                        let end = (col.data_offset + total_byte_len).min(memory.len());
-                        let slice = &memory[col.data_offset..end];
+                        let start = col.data_offset.min(memory.len());
+                        let end = start.saturating_add(total_byte_len).min(memory.len());
+                        let slice = &memory[start..end];
                         let s = std::str::from_utf8(slice).unwrap_or("");
                         strings.push(s.to_string());
                     } else {
                         let mut pos = col.data_offset;
                         for _ in 0..row_count {
+                            pos = pos.min(memory.len());
                             let start = pos;
                             while pos < memory.len() && memory[pos] != 0 {
                                 pos += 1;
@@ -0,0 +1,421 @@
+//! Combining artifacts built as separate fragments (say a prologue that reads
+//! the input, a compute body, and an epilogue that writes results) into one.
+//!
+//! Each fragment keeps its own memory layout: it gets a region of the linked
+//! memory, and its entry functions are called with the start of that region
+//! as their memory base, so every base-relative address in it moves without
+//! being rewritten. Its functions are renumbered after the earlier
+//! fragments', fixing up `u0:N` call targets and the constant `fn_index`
+//! arguments of the thread calls, and the indices in `Algorithm::labels`.
+//! Function indices stored in memory, such as `cl_thread_pool_chain`'s stage
+//! table, cannot be renumbered, so only the first fragment may use them.
+//! Symbols, output schemas, initial memory and the bump arena are moved to
+//! the region.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use base_types::{Algorithm, Artifact, OutputBatchSchema, Setup, Symbol};
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::{ExternalName, Function, InstBuilder, Signature, UserFuncName};
+
+use crate::validate::fn_index_args;
+
+/// Alignment of each fragment's region in the linked memory.
+pub const FRAGMENT_ALIGN: usize = 64;
+
+/// FFI calls that read function indices from memory.
+const MEMORY_FN_INDEX: &[&str] = &["cl_thread_pool_chain"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkError {
+    /// `link_artifacts` was given no fragments.
+    Empty,
+    ClifParse {
+        fragment: usize,
+        message: String,
+    },
+    /// An algorithm's `fn_idx` does not name a function of its fragment.
+    FnIndexOutOfRange {
+        fragment: usize,
+        fn_idx: usize,
+    },
+    /// A thread call's `fn_index` is computed at run time, so it cannot be
+    /// renumbered.
+    DynamicFnIndex {
+        fragment: usize,
+        function: usize,
+        inst: String,
+    },
+    /// A fragment after the first imports `symbol`, which reads function
+    /// indices from memory, so they cannot be renumbered.
+    MemoryFnIndex {
+        fragment: usize,
+        function: usize,
+        symbol: String,
+    },
+    /// Two fragments declare symbol `name` with different lengths.
+    SymbolMismatch {
+        name: String,
+        len: u32,
+        other: u32,
+    },
+    /// Two fragments define an extra algorithm with this name.
+    DuplicateExtra(String),
+    /// A second fragment declares a bump arena; a linked artifact has one.
+    DuplicateArena {
+        fragment: usize,
+    },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Empty => write!(f, "no fragments to link"),
+            LinkError::ClifParse { fragment, message } => {
+                write!(f, "fragment {fragment}: CLIF parse error: {message}")
+            }
+            LinkError::FnIndexOutOfRange { fragment, fn_idx } => {
+                write!(f, "fragment {fragment}: fn_idx {fn_idx} out of range")
+            }
+            LinkError::DynamicFnIndex {
+                fragment,
+                function,
+                inst,
+            } => write!(
+                f,
+                "fragment {fragment}, function {function}: fn_index of `{inst}` is not a constant"
+            ),
+            LinkError::MemoryFnIndex {
+                fragment,
+                function,
+                symbol,
+            } => write!(
+                f,
+                "fragment {fragment}, function {function}: `{symbol}` reads fn indices from memory, which cannot be renumbered"
+            ),
+            LinkError::SymbolMismatch { name, len, other } => write!(
+                f,
+                "symbol `{name}` is {len} bytes in one fragment and {other} in another"
+            ),
+            LinkError::DuplicateExtra(name) => {
+                write!(f, "extra algorithm `{name}` is defined by two fragments")
+            }
+            LinkError::DuplicateArena { fragment } => {
+                write!(f, "fragment {fragment} declares a second arena")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Link `fragments` into one artifact whose main algorithm runs each
+/// fragment's main algorithm in order.
+///
+/// The linked artifact's IO slots are the first fragment's; their values are
+/// copied into every other fragment's IO slots before it runs. Symbols with
+/// the same name link fragments together: a later fragment's copy is filled
+/// from the previous declaration before the later fragment runs, so an
+/// epilogue can read the body's result by name. Only the first declaration of
+/// each name is kept in the linked algorithm, so setting it feeds the whole
+/// chain. Extra algorithms keep their names and run against their fragment's
+/// region.
+pub fn link_artifacts(fragments: &[Artifact]) -> Result<Artifact, LinkError> {
+    let first = fragments.first().ok_or(LinkError::Empty)?;
+    let io_offsets = first.setup.io_offsets;
+    let io_slots = |offsets: &base_types::IoOffsets| {
+        [
+            offsets.data_ptr,
+            offsets.data_len,
+            offsets.out_ptr,
+            offsets.out_len,
+        ]
+    };
+
+    let mut functions: Vec<Function> = Vec::new();
+    let mut regions = Vec::with_capacity(fragments.len());
+    let mut memory_size = 0usize;
+    let mut arena = None;
+    for (i, fragment) in fragments.iter().enumerate() {
+        let setup = &fragment.setup;
+        let base = memory_size.next_multiple_of(FRAGMENT_ALIGN);
+        if let Some(range) = setup.arena() {
+            if arena.is_some() {
+                return Err(LinkError::DuplicateArena { fragment: i });
+            }
+            arena = Some(base + range.start..base + range.end);
+        }
+        memory_size = base
+            + setup
+                .memory_size
+                .max(setup.initial_memory.len())
+                .max(setup.io_offsets.end())
+                .max(setup.arena().map_or(0, |r| r.end));
+        let mut parsed = if setup.cranelift_ir.is_empty() {
+            Vec::new()
+        } else {
+            cranelift_reader::parse_functions(&setup.cranelift_ir).map_err(|e| {
+                LinkError::ClifParse {
+                    fragment: i,
+                    message: e.to_string(),
+                }
+            })?
+        };
+        let fn_base = functions.len();
+        for (j, func) in parsed.iter_mut().enumerate() {
+            if let Some(symbol) = memory_fn_index_import(func).filter(|_| fn_base != 0) {
+                return Err(LinkError::MemoryFnIndex {
+                    fragment: i,
+                    function: j,
+                    symbol,
+                });
+            }
+            renumber(func, fn_base as u32).map_err(|inst| LinkError::DynamicFnIndex {
+                fragment: i,
+                function: j,
+                inst,
+            })?;
+        }
+        functions.extend(parsed);
+        regions.push((base, fn_base, functions.len()));
+    }
+
+    // The entry function of `alg` in fragment `i`, checked.
+    let entry = |i: usize, alg: &Algorithm| {
+        let (_, fn_base, fn_end) = regions[i];
+        let idx = fn_base + alg.fn_idx as usize;
+        if idx >= fn_end {
+            return Err(LinkError::FnIndexOutOfRange {
+                fragment: i,
+                fn_idx: alg.fn_idx as usize,
+            });
+        }
+        Ok((idx, functions[idx].signature.clone()))
+    };
+
+    let mut main = Algorithm::new(0);
+    let mut main_entry = Wrapper::default();
+    let mut shared: HashMap<&str, (u64, u32)> = HashMap::new();
+    let mut extras = HashMap::new();
+    let mut extra_entries = Vec::new();
+    for (i, fragment) in fragments.iter().enumerate() {
+        let base = regions[i].0;
+        let slots = io_slots(&io_offsets)
+            .into_iter()
+            .zip(io_slots(&fragment.setup.io_offsets).map(|s| base + s));
+
+        for symbol in &fragment.main.symbols {
+            let offset = base as u64 + symbol.offset;
+            match shared.get(symbol.name.as_str()) {
+                Some(&(_, len)) if len != symbol.len => {
+                    return Err(LinkError::SymbolMismatch {
+                        name: symbol.name.clone(),
+                        len,
+                        other: symbol.len,
+                    })
+                }
+                Some(&(src, len)) => main_entry.copy(src as usize, offset as usize, len as usize),
+                None => main.symbols.push(Symbol {
+                    offset,
+                    ..symbol.clone()
+                }),
+            }
+            shared.insert(&symbol.name, (offset, symbol.len));
+        }
+        for (src, dst) in slots.clone() {
+            main_entry.copy(src, dst, 8);
+        }
+        let (idx, sig) = entry(i, &fragment.main)?;
+        main_entry.call(idx, sig, base);
+        main.output
+            .extend(fragment.main.output.iter().map(|s| rebase_output(s, base)));
+        main.labels
+            .extend(renumber_labels(&fragment.main, regions[i].1));
+
+        let mut names: Vec<_> = fragment.extras.keys().collect();
+        names.sort();
+        for name in names {
+            let alg = &fragment.extras[name];
+            if extras.contains_key(name) {
+                return Err(LinkError::DuplicateExtra(name.clone()));
+            }
+            let mut wrapper = Wrapper::default();
+            for (src, dst) in slots.clone() {
+                wrapper.copy(src, dst, 8);
+            }
+            let (idx, sig) = entry(i, alg)?;
+            wrapper.call(idx, sig, base);
+            let linked = Algorithm {
+                fn_idx: 0,
+                output: alg.output.iter().map(|s| rebase_output(s, base)).collect(),
+                symbols: alg
+                    .symbols
+                    .iter()
+                    .map(|s| Symbol {
+                        offset: base as u64 + s.offset,
+                        ..s.clone()
+                    })
+                    .collect(),
+                labels: renumber_labels(alg, regions[i].1).collect(),
+            };
+            extras.insert(name.clone(), linked);
+            extra_entries.push((name.clone(), wrapper));
+        }
+    }
+
+    let mut cranelift_ir = String::new();
+    for func in &functions {
+        writeln!(cranelift_ir, "{}", func.display()).unwrap();
+    }
+    main.fn_idx = functions.len() as u32;
+    cranelift_ir += &main_entry.finish(functions.len());
+    for (n, (name, wrapper)) in extra_entries.into_iter().enumerate() {
+        let fn_idx = functions.len() + 1 + n;
+        extras.get_mut(&name).unwrap().fn_idx = fn_idx as u32;
+        cranelift_ir += &wrapper.finish(fn_idx);
+    }
+
+    let mut initial_memory = Vec::new();
+    if fragments.iter().any(|f| !f.setup.initial_memory.is_empty()) {
+        initial_memory = vec![0; memory_size];
+        for (fragment, &(base, ..)) in fragments.iter().zip(&regions) {
+            let bytes = &fragment.setup.initial_memory;
+            initial_memory[base..base + bytes.len()].copy_from_slice(bytes);
+        }
+    }
+    Ok(Artifact {
+        setup: Setup {
+            cranelift_ir,
+            memory_size,
+            io_offsets,
+            initial_memory,
+            arena_offset: arena.as_ref().map_or(0, |r| r.start),
+            arena_size: arena.map_or(0, |r| r.len()),
+        },
+        main,
+        extras,
+    })
+}
+
+/// Shift `func`'s own number, its calls to user functions, and the constant
+/// `fn_index` arguments of its thread calls by `fn_base`. Fails with the
+/// offending call when a `fn_index` is not a constant.
+fn renumber(func: &mut Function, fn_base: u32) -> Result<(), String> {
+    if fn_base == 0 {
+        return Ok(());
+    }
+    if let UserFuncName::User(name) = &mut func.name {
+        name.index += fn_base;
+    }
+    let callees: Vec<_> = func
+        .params
+        .user_named_funcs()
+        .iter()
+        .map(|(r, name)| (r, name.clone()))
+        .collect();
+    for (r, mut name) in callees {
+        if name.namespace == 0 {
+            name.index += fn_base;
+            func.params.reset_user_func_name(r, name);
+        }
+    }
+    for (inst, n, idx) in fn_index_args(func) {
+        let Some(idx) = idx else {
+            return Err(func.dfg.display_inst(inst).to_string());
+        };
+        let ty = func.dfg.value_type(func.dfg.inst_args(inst)[n]);
+        let value = FuncCursor::new(func)
+            .at_inst(inst)
+            .ins()
+            .iconst(ty, idx + i64::from(fn_base));
+        func.dfg.inst_args_mut(inst)[n] = value;
+    }
+    Ok(())
+}
+
+/// The first FFI function `func` imports from `MEMORY_FN_INDEX`.
+fn memory_fn_index_import(func: &Function) -> Option<String> {
+    func.dfg.ext_funcs.values().find_map(|ext| {
+        let ExternalName::TestCase(testcase) = &ext.name else {
+            return None;
+        };
+        let name = testcase.to_string();
+        let name = name.strip_prefix('%').unwrap_or(&name);
+        MEMORY_FN_INDEX.contains(&name).then(|| name.to_string())
+    })
+}
+
+fn renumber_labels(alg: &Algorithm, fn_base: usize) -> impl Iterator<Item = (u32, String)> + '_ {
+    alg.labels
+        .iter()
+        .map(move |(idx, label)| (idx + fn_base as u32, label.clone()))
+}
+
+fn rebase_output(schema: &OutputBatchSchema, base: usize) -> OutputBatchSchema {
+    let mut schema = schema.clone();
+    schema.row_count_offset += base;
+    for column in &mut schema.columns {
+        column.data_offset += base;
+        column.len_offset += base;
+    }
+    schema
+}
+
+/// CLIF text of a generated entry function, built up one step at a time.
+#[derive(Default)]
+struct Wrapper {
+    decls: String,
+    body: String,
+    values: usize,
+    callees: usize,
+}
+
+impl Wrapper {
+    fn value(&mut self) -> String {
+        self.values += 1;
+        format!("v{}", self.values)
+    }
+
+    fn addr(&mut self, offset: usize) -> String {
+        let v = self.value();
+        writeln!(self.body, "    {v} = iadd_imm v0, {offset}").unwrap();
+        v
+    }
+
+    /// Copy `len` bytes from memory offset `src` to `dst`.
+    fn copy(&mut self, src: usize, dst: usize, len: usize) {
+        if src == dst {
+            return;
+        }
+        let mut at = 0;
+        while at < len {
+            let (load, store, step) = match len - at {
+                8.. => ("load.i64", "store", 8),
+                _ => ("uload8.i64", "istore8", 1),
+            };
+            let (from, to) = (self.addr(src + at), self.addr(dst + at));
+            let v = self.value();
+            writeln!(self.body, "    {v} = {load} notrap {from}").unwrap();
+            writeln!(self.body, "    {store} notrap {v}, {to}").unwrap();
+            at += step;
+        }
+    }
+
+    /// Call user function `idx` with the memory base moved to `base`.
+    fn call(&mut self, idx: usize, sig: Signature, base: usize) {
+        let n = self.callees;
+        self.callees += 1;
+        writeln!(self.decls, "    sig{n} = {sig}").unwrap();
+        writeln!(self.decls, "    fn{n} = colocated u0:{idx} sig{n}").unwrap();
+        let v = self.addr(base);
+        writeln!(self.body, "    call fn{n}({v})").unwrap();
+    }
+
+    fn finish(self, idx: usize) -> String {
+        format!(
+            "function u0:{idx}(i64) system_v {{\n{}block0(v0: i64):\n{}    return\n}}\n",
+            self.decls, self.body
+        )
+    }
+}
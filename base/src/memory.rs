@@ -0,0 +1,153 @@
+//! Host access to an instance's memory from outside the execution, e.g. to
+//! feed a long-running algorithm or watch its progress counters.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use tracing::warn;
+
+use crate::{ffi, Error};
+
+/// Start and length of the memory a `Base` instance owns. Stored as an
+/// address so the handle stays `Send`.
+#[derive(Clone, Copy)]
+struct Region {
+    addr: usize,
+    len: usize,
+}
+
+/// Reads and writes an instance's memory while executions run, from any
+/// thread. Obtained from `Base::memory_handle`; every clone stops working,
+/// with `Error::MemoryReleased`, once the instance is dropped.
+///
+/// `read` and `write` copy bytes without synchronization: bytes written by
+/// the host are seen by code that reads them afterwards, but nothing orders
+/// them against concurrent accesses from the algorithm. To hand data over,
+/// write it first, then publish a flag with `write_u64`, which stores with
+/// release ordering and wakes `cl_thread_wait_until` callers blocked on that
+/// word. `read_u64` loads with acquire ordering, pairing with `cl_thread_wake`
+/// or an atomic store in the algorithm.
+#[derive(Clone)]
+pub struct MemoryHandle {
+    region: Arc<RwLock<Option<Region>>>,
+}
+
+impl MemoryHandle {
+    pub(crate) fn new(ptr: *mut u8, len: usize) -> MemoryHandle {
+        MemoryHandle {
+            region: Arc::new(RwLock::new(Some(Region {
+                addr: ptr as usize,
+                len,
+            }))),
+        }
+    }
+
+    /// Detach every clone; waits for accesses in progress to finish.
+    pub(crate) fn release(&self) {
+        *self.region.write().unwrap() = None;
+    }
+
+    /// Run `f` on the address of `offset..offset + len`, checked against the
+    /// memory and `align`, while the instance cannot be dropped.
+    fn with<R>(
+        &self,
+        offset: usize,
+        len: usize,
+        align: usize,
+        f: impl FnOnce(*mut u8) -> R,
+    ) -> Result<R, Error> {
+        let region = self.region.read().unwrap();
+        let region = region.ok_or(Error::MemoryReleased)?;
+        if offset.checked_add(len).is_none_or(|end| end > region.len) {
+            warn!(
+                offset,
+                len,
+                size = region.len,
+                "memory access out of bounds"
+            );
+            return Err(Error::MemoryOutOfBounds {
+                offset,
+                len,
+                size: region.len,
+            });
+        }
+        let ptr = (region.addr + offset) as *mut u8;
+        if ptr.align_offset(align) != 0 {
+            return Err(Error::MemoryUnaligned { offset });
+        }
+        Ok(f(ptr))
+    }
+
+    /// Size of the memory in bytes, or 0 once the instance is dropped.
+    pub fn len(&self) -> usize {
+        self.region.read().unwrap().map_or(0, |r| r.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn read(&self, offset: usize, len: usize) -> Result<Vec<u8>, Error> {
+        self.with(offset, len, 1, |ptr| unsafe {
+            std::slice::from_raw_parts(ptr, len).to_vec()
+        })
+    }
+
+    pub fn write(&self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        self.with(offset, data.len(), 1, |ptr| unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len())
+        })
+    }
+
+    /// Acquire-load the 8-byte-aligned u64 at `offset`.
+    pub fn read_u64(&self, offset: usize) -> Result<u64, Error> {
+        self.with(offset, 8, 8, |ptr| unsafe {
+            (*(ptr as *const AtomicU64)).load(Ordering::Acquire)
+        })
+    }
+
+    /// Release-store `value` to the 8-byte-aligned u64 at `offset` and wake
+    /// threads waiting on it in `cl_thread_wait_until`.
+    pub fn write_u64(&self, offset: usize, value: u64) -> Result<(), Error> {
+        self.with(offset, 8, 8, |ptr| unsafe {
+            ffi::thread::cl_thread_wake(ptr, value as i64);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_alignment_and_release() {
+        let mut memory = vec![0u64; 8];
+        let handle = MemoryHandle::new(memory.as_mut_ptr() as *mut u8, 64);
+        handle.write(3, b"abc").unwrap();
+        assert_eq!(handle.read(2, 5).unwrap(), b"\0abc\0");
+        handle.write_u64(8, 42).unwrap();
+        assert_eq!(handle.read_u64(8).unwrap(), 42);
+        assert!(matches!(
+            handle.read(60, 8),
+            Err(Error::MemoryOutOfBounds {
+                offset: 60,
+                len: 8,
+                size: 64
+            })
+        ));
+        assert!(matches!(
+            handle.write(usize::MAX, b"x"),
+            Err(Error::MemoryOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            handle.read_u64(4),
+            Err(Error::MemoryUnaligned { offset: 4 })
+        ));
+
+        let clone = handle.clone();
+        handle.release();
+        assert!(matches!(clone.read(0, 1), Err(Error::MemoryReleased)));
+        assert_eq!(clone.len(), 0);
+        assert_eq!(memory[1], 42);
+    }
+}
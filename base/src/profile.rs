@@ -0,0 +1,162 @@
+//! Compile-time instrumentation behind `Base::new_profiled`.
+//!
+//! Each user function gets an enter hook at the top of its entry block and an
+//! exit hook before every `return`; each FFI call site is wrapped the same
+//! way. The hooks receive the address of the `ProfileState` as an immediate,
+//! so worker threads need no extra context. Unprofiled instances compile the
+//! CLIF untouched and pay nothing.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use base_types::{Profile, ProfileEntry, ProfileKey};
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::ir::{
+    types, AbiParam, ExtFuncData, ExternalName, FuncRef, Function, InstBuilder, InstructionData,
+    Signature, UserExternalName,
+};
+use cranelift_module::FuncId;
+
+/// Call counts and inclusive times, indexed like `keys`.
+pub(crate) struct ProfileState {
+    keys: Vec<ProfileKey>,
+    calls: Vec<AtomicU64>,
+    nanos: Vec<AtomicU64>,
+}
+
+impl ProfileState {
+    pub(crate) fn new(keys: Vec<ProfileKey>) -> ProfileState {
+        ProfileState {
+            calls: keys.iter().map(|_| AtomicU64::new(0)).collect(),
+            nanos: keys.iter().map(|_| AtomicU64::new(0)).collect(),
+            keys,
+        }
+    }
+
+    /// The counters accumulated so far, then reset to zero.
+    pub(crate) fn take(&self) -> Profile {
+        let entries = self
+            .keys
+            .iter()
+            .zip(self.calls.iter().zip(&self.nanos))
+            .filter_map(|(key, (calls, nanos))| {
+                let calls = calls.swap(0, Ordering::Relaxed);
+                let total_ns = nanos.swap(0, Ordering::Relaxed);
+                (calls > 0).then(|| ProfileEntry {
+                    key: key.clone(),
+                    calls,
+                    total_ns,
+                    label: None,
+                })
+            })
+            .collect();
+        Profile { entries }
+    }
+}
+
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+pub(crate) unsafe extern "C" fn cl_profile_enter() -> i64 {
+    epoch().elapsed().as_nanos() as i64
+}
+
+pub(crate) unsafe extern "C" fn cl_profile_exit(state: *const ProfileState, id: i64, start: i64) {
+    let Some(state) = state.as_ref() else {
+        return;
+    };
+    let elapsed = (epoch().elapsed().as_nanos() as i64).saturating_sub(start);
+    let id = id as usize;
+    state.calls[id].fetch_add(1, Ordering::Relaxed);
+    state.nanos[id].fetch_add(elapsed as u64, Ordering::Relaxed);
+}
+
+/// Module ids of the two hooks, declared once per module.
+pub(crate) struct Hooks {
+    pub(crate) enter: FuncId,
+    pub(crate) exit: FuncId,
+}
+
+impl Hooks {
+    pub(crate) fn signatures(
+        call_conv: cranelift_codegen::isa::CallConv,
+    ) -> (Signature, Signature) {
+        let mut enter = Signature::new(call_conv);
+        enter.returns.push(AbiParam::new(types::I64));
+        let mut exit = Signature::new(call_conv);
+        exit.params.extend([AbiParam::new(types::I64); 3]);
+        (enter, exit)
+    }
+}
+
+pub(crate) fn import(func: &mut Function, id: FuncId, sig: Signature) -> FuncRef {
+    let signature = func.import_signature(sig);
+    let user_ref = func.declare_imported_user_function(UserExternalName {
+        namespace: 0,
+        index: id.as_u32(),
+    });
+    func.import_function(ExtFuncData {
+        name: ExternalName::user(user_ref),
+        signature,
+        colocated: false,
+    })
+}
+
+/// Wrap `func` (profile id `fn_id`) and the FFI calls it makes. `ffi_id`
+/// maps an imported callee to its profile id, or `None` for calls between
+/// user functions, which are timed by the callee's own hooks.
+pub(crate) fn instrument(
+    func: &mut Function,
+    fn_id: usize,
+    hooks: &Hooks,
+    state: *const ProfileState,
+    ffi_id: impl Fn(FuncRef) -> Option<usize>,
+) {
+    let (enter_sig, exit_sig) = Hooks::signatures(func.signature.call_conv);
+    let enter = import(func, hooks.enter, enter_sig);
+    let exit = import(func, hooks.exit, exit_sig);
+
+    let mut ffi_calls = Vec::new();
+    let mut returns = Vec::new();
+    for block in func.layout.blocks() {
+        for inst in func.layout.block_insts(block) {
+            match func.dfg.insts[inst] {
+                InstructionData::Call { func_ref, .. } => {
+                    if let Some(id) = ffi_id(func_ref) {
+                        ffi_calls.push((inst, id));
+                    }
+                }
+                ref data if data.opcode().is_return() => returns.push(inst),
+                _ => {}
+            }
+        }
+    }
+
+    let mut pos = FuncCursor::new(func);
+    let Some(entry) = pos.func.layout.entry_block() else {
+        return;
+    };
+    let exit_call = |pos: &mut FuncCursor, id: usize, start| {
+        let state = pos.ins().iconst(types::I64, state as i64);
+        let id = pos.ins().iconst(types::I64, id as i64);
+        pos.ins().call(exit, &[state, id, start]);
+    };
+
+    pos.goto_first_insertion_point(entry);
+    let call = pos.ins().call(enter, &[]);
+    let fn_start = pos.func.dfg.first_result(call);
+    for inst in returns {
+        pos.goto_inst(inst);
+        exit_call(&mut pos, fn_id, fn_start);
+    }
+    for (inst, id) in ffi_calls {
+        pos.goto_inst(inst);
+        let call = pos.ins().call(enter, &[]);
+        let start = pos.func.dfg.first_result(call);
+        pos.goto_after_inst(inst);
+        exit_call(&mut pos, id, start);
+    }
+}
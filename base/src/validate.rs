@@ -0,0 +1,1229 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use base_types::{Algorithm, Artifact, ArtifactReport, OutputType, StringArg, SymbolError};
+use cranelift_codegen::ir::{
+    ExternalName, Function, Inst, InstructionData, Opcode, Value, ValueDef,
+};
+use cranelift_codegen::settings;
+
+#[cfg(feature = "gpu")]
+use crate::ffi::wgpu::check_shader;
+use crate::jit::{disabled_feature, symbol_names};
+use crate::Error;
+
+/// A problem found by `validate_artifact` that would otherwise surface as a
+/// panic or silently wrong output at JIT or execution time. Issues in a
+/// function carry its label from the artifact's algorithms, if any (see
+/// `Artifact::function_label`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// An algorithm's `fn_idx` does not name a function in the CLIF source.
+    FnIndexOutOfRange {
+        algorithm: String,
+        fn_idx: usize,
+        available: usize,
+    },
+    /// A function imports `%name` that is not a registered FFI symbol.
+    UnknownSymbol {
+        function: usize,
+        label: Option<String>,
+        name: String,
+    },
+    /// A function imports `%name` from an FFI family whose cargo feature is
+    /// disabled in this build.
+    FeatureDisabled {
+        function: usize,
+        label: Option<String>,
+        name: String,
+        feature: &'static str,
+    },
+    /// The Cranelift verifier rejected a function.
+    Verifier {
+        function: usize,
+        label: Option<String>,
+        message: String,
+    },
+    /// An output schema reads past the end of the memory region.
+    OutputOutOfBounds {
+        algorithm: String,
+        offset: usize,
+        memory_size: usize,
+    },
+    /// A symbol overlaps another symbol or an IO slot, or ends past memory.
+    Symbol {
+        algorithm: String,
+        error: SymbolError,
+    },
+    /// A store or FFI destination with a constant address writes into a
+    /// symbol not declared writable (`Algorithm::declare_scratch`), clobbering
+    /// the value set there.
+    SymbolOverwritten {
+        algorithm: String,
+        symbol: String,
+        function: usize,
+        label: Option<String>,
+        inst: String,
+        operand: &'static str,
+    },
+    /// A load, store, or FFI memory argument with a constant address and
+    /// length reaches outside the memory region.
+    OperandOutOfBounds {
+        function: usize,
+        label: Option<String>,
+        inst: String,
+        operand: &'static str,
+        offset: i64,
+        len: u64,
+        memory_size: usize,
+    },
+    /// A load, store, or FFI memory argument with a constant address points
+    /// into the bump arena (`Setup::arena_offset`), whose bytes belong to
+    /// whichever `cl_arena_bump` allocation holds them at the time.
+    ArenaOperand {
+        function: usize,
+        label: Option<String>,
+        inst: String,
+        operand: &'static str,
+    },
+    /// The WGSL at a constant `shader_ptr` of a GPU pipeline call fails to
+    /// parse or validate; `line` and `column` are 1-based (0 when naga gives
+    /// no location). The source is read from `initial_memory`, and only in
+    /// builds with the `gpu` feature.
+    ShaderInvalid {
+        function: usize,
+        label: Option<String>,
+        inst: String,
+        offset: i64,
+        line: u32,
+        column: u32,
+        message: String,
+    },
+    /// A constant function index passed to a thread FFI call names no
+    /// function in the CLIF source.
+    FnArgOutOfRange {
+        function: usize,
+        label: Option<String>,
+        inst: String,
+        operand: &'static str,
+        fn_idx: i64,
+        available: usize,
+    },
+}
+
+/// A memory operand found by `memory_operands`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryOperand {
+    pub function: usize,
+    /// The instruction, as CLIF text.
+    pub inst: String,
+    /// `"address"` for loads and stores, else the FFI parameter name.
+    pub operand: &'static str,
+    /// The FFI symbol called, or `None` for loads and stores.
+    pub symbol: Option<&'static str>,
+    /// Whether the memory is written (stores, FFI destinations) rather than
+    /// read.
+    pub writes: bool,
+    /// Byte range from the start of memory, or `None` when the address or
+    /// length depends on run-time data (and so is not checked).
+    pub range: Option<Range<i64>>,
+}
+
+/// Length of the memory an FFI argument addresses.
+#[derive(Clone, Copy)]
+pub(crate) enum Len {
+    /// Given by another argument.
+    Arg(usize),
+    /// At least this many bytes (1 for a NUL-terminated string).
+    Bytes(u64),
+}
+
+/// How an FFI argument is interpreted, for the calls whose arguments can be
+/// checked statically.
+#[derive(Clone, Copy)]
+pub(crate) enum Operand {
+    /// Argument `.0` is a byte offset from the memory base passed as
+    /// argument 0.
+    Offset(usize, Len),
+    /// Argument `.0` is a pointer into memory.
+    Pointer(usize, Len),
+    /// Argument `.0` is the index of a compiled function.
+    FnIndex(usize),
+}
+
+use Len::{Arg, Bytes};
+use Operand::{FnIndex, Offset, Pointer};
+
+/// Named operands of each FFI call with statically checkable arguments.
+pub(crate) const FFI_OPERANDS: &[(&str, &[(&str, Operand)])] = &[
+    (
+        "cl_file_read",
+        &[
+            ("path_off", Offset(1, Bytes(1))),
+            ("dst_off", Offset(2, Arg(4))),
+        ],
+    ),
+    (
+        "cl_file_write",
+        &[
+            ("path_off", Offset(1, Bytes(1))),
+            ("src_off", Offset(2, Arg(4))),
+        ],
+    ),
+    (
+        "cl_file_write_atomic",
+        &[
+            ("path_off", Offset(1, Bytes(1))),
+            ("src_off", Offset(2, Arg(3))),
+        ],
+    ),
+    ("cl_file_atomic_open", &[("path_ptr", Pointer(1, Bytes(1)))]),
+    ("cl_file_atomic_write", &[("src_ptr", Pointer(2, Arg(4)))]),
+    ("cl_file_open", &[("path_ptr", Pointer(1, Bytes(1)))]),
+    ("cl_file_read_handle", &[("dst_ptr", Pointer(2, Arg(4)))]),
+    ("cl_file_write_handle", &[("src_ptr", Pointer(2, Arg(4)))]),
+    (
+        "cl_file_read_to_ptr",
+        &[
+            ("path_ptr", Pointer(0, Bytes(1))),
+            ("dst_ptr", Pointer(1, Arg(3))),
+        ],
+    ),
+    (
+        "cl_file_write_from_ptr",
+        &[
+            ("path_ptr", Pointer(0, Bytes(1))),
+            ("src_ptr", Pointer(1, Arg(3))),
+        ],
+    ),
+    (
+        "cl_file_stream_start",
+        &[
+            ("path_off", Offset(1, Bytes(1))),
+            ("ring_off", Offset(2, Bytes(16))),
+            ("config_off", Offset(4, Bytes(16))),
+        ],
+    ),
+    ("cl_mem_fill", &[("dst_off", Offset(1, Arg(2)))]),
+    (
+        "cl_mem_copy",
+        &[
+            ("dst_off", Offset(1, Arg(3))),
+            ("src_off", Offset(2, Arg(3))),
+        ],
+    ),
+    (
+        "cl_mem_compare",
+        &[("a_off", Offset(1, Arg(3))), ("b_off", Offset(2, Arg(3)))],
+    ),
+    // With `SCAN_MASKED` the mask after the pattern doubles the bytes read;
+    // only the pattern is checked. The default mode writes an i64 index at
+    // `out_off`; `SCAN_ALL`'s offset list after the count is not checked.
+    (
+        "cl_mem_scan",
+        &[
+            ("hay_off", Offset(1, Arg(2))),
+            ("pat_off", Offset(3, Arg(4))),
+            ("out_off", Offset(5, Bytes(8))),
+        ],
+    ),
+    // The written length shares `size` with the mode bits, so only the
+    // condition word is checked.
+    ("cl_mem_cond_write", &[("cond_off", Offset(1, Bytes(8)))]),
+    (
+        "cl_mem_byteswap",
+        &[
+            ("dst_off", Offset(1, Arg(4))),
+            ("src_off", Offset(2, Arg(4))),
+        ],
+    ),
+    (
+        "cl_mem_publish",
+        &[
+            ("seq_off", Offset(1, Bytes(8))),
+            ("dst_off", Offset(2, Arg(4))),
+            ("src_off", Offset(3, Arg(4))),
+        ],
+    ),
+    (
+        "cl_mem_snapshot",
+        &[
+            ("seq_off", Offset(1, Bytes(8))),
+            ("dst_off", Offset(2, Arg(4))),
+            ("src_off", Offset(3, Arg(4))),
+        ],
+    ),
+    (
+        "cl_mem_array_op",
+        &[
+            ("dst_off", Offset(1, Arg(4))),
+            ("a_off", Offset(2, Arg(4))),
+            ("b_off", Offset(3, Arg(4))),
+        ],
+    ),
+    (
+        "cl_lz4_compress",
+        &[
+            ("dst_off", Offset(1, Arg(4))),
+            ("src_off", Offset(2, Arg(3))),
+        ],
+    ),
+    (
+        "cl_lz4_decompress",
+        &[
+            ("dst_off", Offset(1, Arg(4))),
+            ("src_off", Offset(2, Arg(3))),
+        ],
+    ),
+    (
+        "cl_checksum",
+        &[
+            ("src_off", Offset(2, Arg(3))),
+            ("dst_off", Offset(4, Bytes(8))),
+        ],
+    ),
+    (
+        "cl_checkpoint",
+        &[
+            ("path_off", Offset(1, Bytes(1))),
+            ("inflight_off", Offset(3, Bytes(8))),
+        ],
+    ),
+    (
+        "cl_execute_nested",
+        &[
+            ("algo_off", Offset(1, Arg(2))),
+            ("window_off", Offset(3, Arg(4))),
+        ],
+    ),
+    ("cl_file_retry", &[("policy_ptr", Pointer(0, Bytes(6)))]),
+    ("cl_stdout_write", &[("src_off", Offset(1, Arg(2)))]),
+    (
+        "cl_random_weighted",
+        &[("weights_off", Offset(1, Bytes(8)))],
+    ),
+    ("cl_stdin_readline", &[("dst_off", Offset(1, Arg(2)))]),
+    ("cl_net_listen", &[("addr_ptr", Pointer(1, Bytes(1)))]),
+    ("cl_net_connect", &[("addr_ptr", Pointer(1, Bytes(1)))]),
+    ("cl_net_send", &[("src_ptr", Pointer(2, Arg(3)))]),
+    ("cl_net_recv", &[("dst_ptr", Pointer(2, Arg(3)))]),
+    ("cl_net_recv_framed", &[("dst_ptr", Pointer(2, Arg(3)))]),
+    ("cl_net_retry", &[("policy_ptr", Pointer(1, Bytes(6)))]),
+    ("cl_lmdb_open", &[("path_ptr", Pointer(1, Bytes(1)))]),
+    (
+        "cl_lmdb_open_with",
+        &[
+            ("path_ptr", Pointer(1, Bytes(1))),
+            ("opts_ptr", Pointer(2, Bytes(16))),
+        ],
+    ),
+    (
+        "cl_lmdb_put",
+        &[
+            ("key_ptr", Pointer(2, Arg(3))),
+            ("val_ptr", Pointer(4, Arg(5))),
+        ],
+    ),
+    ("cl_lmdb_get", &[("key_ptr", Pointer(2, Arg(3)))]),
+    (
+        "cl_lmdb_get_bounded",
+        &[
+            ("key_ptr", Pointer(2, Arg(3))),
+            ("result_ptr", Pointer(4, Bytes(4))),
+        ],
+    ),
+    (
+        "cl_lmdb_cursor_scan_bounded",
+        &[("result_ptr", Pointer(5, Arg(6)))],
+    ),
+    ("cl_lmdb_delete", &[("key_ptr", Pointer(2, Arg(3)))]),
+    ("cl_thread_spawn", &[("fn_index", FnIndex(1))]),
+    ("cl_thread_call", &[("fn_index", FnIndex(1))]),
+    ("cl_thread_pool_submit", &[("fn_index", FnIndex(2))]),
+    ("cl_thread_pool_try_submit", &[("fn_index", FnIndex(2))]),
+    (
+        "cl_thread_pool_dispatch",
+        &[
+            ("fn_index", FnIndex(2)),
+            ("block_ptr", Pointer(3, Bytes(8))),
+        ],
+    ),
+    (
+        "cl_thread_pool_dispatch_if",
+        &[
+            ("fn_index", FnIndex(2)),
+            ("block_ptr", Pointer(3, Bytes(8))),
+        ],
+    ),
+    ("cl_thread_pool_broadcast", &[("fn_index", FnIndex(2))]),
+    (
+        "cl_thread_barrier_init",
+        &[("barrier_ptr", Pointer(0, Bytes(24)))],
+    ),
+    (
+        "cl_thread_barrier_wait",
+        &[("barrier_ptr", Pointer(0, Bytes(24)))],
+    ),
+    ("cl_gpu_pipeline_cpu", &[("fn_index", FnIndex(2))]),
+    (
+        "cl_gpu_create_pipeline",
+        &[("shader_ptr", Pointer(1, Bytes(1)))],
+    ),
+    (
+        "cl_gpu_create_pipeline_regions",
+        &[
+            ("shader_ptr", Pointer(1, Bytes(1))),
+            ("table_ptr", Pointer(2, Bytes(16))),
+        ],
+    ),
+];
+
+/// A value whose run-time contents are known statically.
+#[derive(Clone, Copy)]
+enum Known {
+    /// The memory base plus this offset.
+    Base(i64),
+    Const(i64),
+}
+
+/// Follow `iconst`, `iadd_imm` and `iadd` back to a constant or to `base`,
+/// the entry function's memory pointer.
+fn resolve(func: &Function, value: Value, base: Option<Value>) -> Option<Known> {
+    let value = func.dfg.resolve_aliases(value);
+    if Some(value) == base {
+        return Some(Known::Base(0));
+    }
+    let ValueDef::Result(inst, 0) = func.dfg.value_def(value) else {
+        return None;
+    };
+    match func.dfg.insts[inst] {
+        InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } => Some(Known::Const(imm.bits())),
+        InstructionData::BinaryImm64 {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } => Some(match resolve(func, arg, base)? {
+            Known::Base(k) => Known::Base(k.wrapping_add(imm.bits())),
+            Known::Const(c) => Known::Const(c.wrapping_add(imm.bits())),
+        }),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args: [a, b],
+        } => match (resolve(func, a, base)?, resolve(func, b, base)?) {
+            (Known::Base(k), Known::Const(c)) | (Known::Const(c), Known::Base(k)) => {
+                Some(Known::Base(k.wrapping_add(c)))
+            }
+            (Known::Const(x), Known::Const(y)) => Some(Known::Const(x.wrapping_add(y))),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The `fn_index` arguments of the thread FFI calls in `func`: the call, the
+/// argument position, and the index when it is a constant.
+pub(crate) fn fn_index_args(func: &Function) -> Vec<(Inst, usize, Option<i64>)> {
+    let mut args = Vec::new();
+    for block in func.layout.blocks() {
+        for inst in func.layout.block_insts(block) {
+            let InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] else {
+                continue;
+            };
+            let ExternalName::TestCase(testcase) = &func.dfg.ext_funcs[func_ref].name else {
+                continue;
+            };
+            let name = testcase.to_string();
+            let name = name.strip_prefix('%').unwrap_or(&name);
+            let Some(&(_, table)) = FFI_OPERANDS.iter().find(|(n, _)| *n == name) else {
+                continue;
+            };
+            for &(_, kind) in table.iter() {
+                let FnIndex(n) = kind else { continue };
+                let value = func.dfg.inst_args(inst).get(n).copied();
+                let idx = match value.and_then(|v| resolve(func, v, None)) {
+                    Some(Known::Const(idx)) => Some(idx),
+                    _ => None,
+                };
+                args.push((inst, n, idx));
+            }
+        }
+    }
+    args
+}
+
+/// The functions `func` can hand control to: its calls to user functions
+/// and the constant `fn_index` arguments of its thread calls.
+fn callees(func: &Function) -> Vec<usize> {
+    let calls = func
+        .params
+        .user_named_funcs()
+        .values()
+        .filter(|name| name.namespace == 0)
+        .map(|name| name.index as usize);
+    let indices = fn_index_args(func)
+        .into_iter()
+        .filter_map(|(_, _, idx)| usize::try_from(idx?).ok());
+    calls.chain(indices).collect()
+}
+
+/// Address value, constant offset and access size of a load or store.
+fn load_store(func: &Function, inst: Inst) -> Option<(Value, i64, u64)> {
+    let (opcode, addr, offset, value) = match func.dfg.insts[inst] {
+        InstructionData::Load {
+            opcode,
+            arg,
+            offset,
+            ..
+        } => (opcode, arg, i64::from(offset), func.dfg.first_result(inst)),
+        InstructionData::Store {
+            opcode,
+            args: [value, addr],
+            offset,
+            ..
+        } => (opcode, addr, i64::from(offset), value),
+        InstructionData::LoadNoOffset {
+            opcode: Opcode::AtomicLoad,
+            arg,
+            ..
+        } => (Opcode::AtomicLoad, arg, 0, func.dfg.first_result(inst)),
+        InstructionData::StoreNoOffset {
+            opcode: Opcode::AtomicStore,
+            args: [value, addr],
+            ..
+        } => (Opcode::AtomicStore, addr, 0, value),
+        InstructionData::AtomicRmw {
+            args: [addr, _], ..
+        }
+        | InstructionData::AtomicCas {
+            args: [addr, _, _], ..
+        } => (Opcode::AtomicRmw, addr, 0, func.dfg.first_result(inst)),
+        _ => return None,
+    };
+    let size = match opcode {
+        Opcode::Uload8 | Opcode::Sload8 | Opcode::Istore8 => 1,
+        Opcode::Uload16 | Opcode::Sload16 | Opcode::Istore16 => 2,
+        Opcode::Uload32 | Opcode::Sload32 | Opcode::Istore32 => 4,
+        Opcode::Uload8x8
+        | Opcode::Sload8x8
+        | Opcode::Uload16x4
+        | Opcode::Sload16x4
+        | Opcode::Uload32x2
+        | Opcode::Sload32x2 => 8,
+        _ => u64::from(func.dfg.value_type(value).bytes()),
+    };
+    Some((addr, offset, size))
+}
+
+/// A memory operand as `check_operands` first records it.
+struct Found {
+    inst: Inst,
+    operand: &'static str,
+    symbol: Option<&'static str>,
+    writes: bool,
+    range: Option<Range<i64>>,
+}
+
+/// Walk every instruction of `functions`, returning the memory operands it
+/// finds and the issues among them. Only functions in `entries` are called
+/// with the memory base as their first parameter; elsewhere addresses are
+/// data-dependent.
+fn check_operands(
+    functions: &[Function],
+    entries: &HashSet<usize>,
+    memory_size: usize,
+) -> (Vec<MemoryOperand>, Vec<ValidationIssue>) {
+    let mut operands = Vec::new();
+    let mut issues = Vec::new();
+    for (i, func) in functions.iter().enumerate() {
+        let base = func
+            .layout
+            .entry_block()
+            .filter(|_| entries.contains(&i))
+            .and_then(|block| func.dfg.block_params(block).first().copied());
+        let known = |v: Value| resolve(func, v, base);
+        let mut found: Vec<Found> = Vec::new();
+        let mut bad_fns: Vec<(Inst, &'static str, i64)> = Vec::new();
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                if let Some((addr, offset, size)) = load_store(func, inst) {
+                    let range = match known(addr) {
+                        Some(Known::Base(k)) => {
+                            let start = k.wrapping_add(offset);
+                            Some(start..start.saturating_add(size as i64))
+                        }
+                        _ => None,
+                    };
+                    let opcode = func.dfg.insts[inst].opcode();
+                    found.push(Found {
+                        inst,
+                        operand: "address",
+                        symbol: None,
+                        writes: opcode.can_store() && !opcode.can_load(),
+                        range,
+                    });
+                    continue;
+                }
+                let InstructionData::Call { func_ref, .. } = func.dfg.insts[inst] else {
+                    continue;
+                };
+                let ExternalName::TestCase(testcase) = &func.dfg.ext_funcs[func_ref].name else {
+                    continue;
+                };
+                let name = testcase.to_string();
+                let name = name.strip_prefix('%').unwrap_or(&name);
+                let Some(&(symbol, table)) = FFI_OPERANDS.iter().find(|(n, _)| *n == name) else {
+                    continue;
+                };
+                let args = func.dfg.inst_args(inst);
+                let arg = |n: usize| args.get(n).and_then(|&v| known(v));
+                for &(operand, kind) in table.iter() {
+                    let (start, len) = match kind {
+                        Operand::FnIndex(n) => {
+                            if let Some(Known::Const(idx)) = arg(n) {
+                                if idx < 0 || idx as usize >= functions.len() {
+                                    bad_fns.push((inst, operand, idx));
+                                }
+                            }
+                            continue;
+                        }
+                        Operand::Offset(n, len) => {
+                            let start = match (arg(0), arg(n)) {
+                                (Some(Known::Base(b)), Some(Known::Const(o))) => {
+                                    Some(b.wrapping_add(o))
+                                }
+                                _ => None,
+                            };
+                            (start, len)
+                        }
+                        Operand::Pointer(n, len) => match arg(n) {
+                            Some(Known::Base(k)) => (Some(k), len),
+                            _ => (None, len),
+                        },
+                    };
+                    let len = match len {
+                        Len::Bytes(b) => Some(b as i64),
+                        Len::Arg(n) => match arg(n) {
+                            Some(Known::Const(c)) if c >= 0 => Some(c),
+                            _ => None,
+                        },
+                    };
+                    let range = start.zip(len).map(|(s, l)| s..s.saturating_add(l));
+                    found.push(Found {
+                        inst,
+                        operand,
+                        symbol: Some(symbol),
+                        writes: operand.starts_with("dst") || operand.starts_with("out"),
+                        range,
+                    });
+                }
+            }
+        }
+        // CLIF text without the trailing `; v1 = 0` constant annotations.
+        let text = |inst: Inst| {
+            let text = func.dfg.display_inst(inst).to_string();
+            text.split(" ;")
+                .next()
+                .unwrap_or_default()
+                .trim_end()
+                .to_string()
+        };
+        for Found {
+            inst,
+            operand,
+            symbol,
+            writes,
+            range,
+        } in found
+        {
+            let text = text(inst);
+            if let Some(r) = &range {
+                if r.start < 0 || r.end > memory_size as i64 {
+                    issues.push(ValidationIssue::OperandOutOfBounds {
+                        function: i,
+                        label: None,
+                        inst: text.clone(),
+                        operand,
+                        offset: r.start,
+                        len: (r.end - r.start) as u64,
+                        memory_size,
+                    });
+                }
+            }
+            operands.push(MemoryOperand {
+                function: i,
+                inst: text,
+                operand,
+                symbol,
+                writes,
+                range,
+            });
+        }
+        issues.extend(bad_fns.into_iter().map(|(inst, operand, fn_idx)| {
+            ValidationIssue::FnArgOutOfRange {
+                function: i,
+                label: None,
+                inst: text(inst),
+                operand,
+                fn_idx,
+                available: functions.len(),
+            }
+        }));
+    }
+    (operands, issues)
+}
+
+/// Without the `gpu` feature there is no WGSL front end to check with.
+#[cfg(not(feature = "gpu"))]
+fn check_shader(_src: &str) -> Result<(), (u32, u32, String)> {
+    Ok(())
+}
+
+/// The NUL-terminated source at `offset` in `memory`, empty past its end.
+fn shader_source(memory: &[u8], offset: usize) -> &[u8] {
+    let bytes = memory.get(offset..).unwrap_or_default();
+    &bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())]
+}
+
+/// Check the WGSL that each pipeline call's constant `shader_ptr` points at
+/// in `memory`. A shader past the end of `memory`, or empty there, is written
+/// at run time and left alone.
+fn shader_issues(operands: &[MemoryOperand], memory: &[u8]) -> Vec<ValidationIssue> {
+    let check = |op: &MemoryOperand| {
+        let start = usize::try_from(op.range.as_ref()?.start).ok()?;
+        let src = shader_source(memory, start);
+        if src.is_empty() {
+            return None;
+        }
+        let (line, column, message) = check_shader(&String::from_utf8_lossy(src)).err()?;
+        Some(ValidationIssue::ShaderInvalid {
+            function: op.function,
+            label: None,
+            inst: op.inst.clone(),
+            offset: start as i64,
+            line,
+            column,
+            message,
+        })
+    };
+    operands
+        .iter()
+        .filter(|op| op.operand == "shader_ptr")
+        .filter_map(check)
+        .collect()
+}
+
+/// The error of the WGSL `src` found at `offset`, if it is non-empty and
+/// invalid.
+fn shader_error(src: &[u8], offset: usize) -> Option<String> {
+    if src.is_empty() {
+        return None;
+    }
+    let (line, column, message) = check_shader(&String::from_utf8_lossy(src)).err()?;
+    Some(format!(
+        "shader at offset {offset}, line {line}, column {column}: {message}"
+    ))
+}
+
+/// A constant `shader_ptr` in `function` that `entry` reaches, itself or
+/// through calls and thread `fn_index` arguments, with the source
+/// `initial_memory` holds there and that source's error.
+pub(crate) struct ShaderSite {
+    pub(crate) entry: usize,
+    function: usize,
+    offset: usize,
+    src: Vec<u8>,
+    error: Option<String>,
+}
+
+impl ShaderSite {
+    /// The error of the shader at this site in `memory`, after the entry's
+    /// symbols are written. Only a source that no longer matches
+    /// `initial_memory` is checked again.
+    pub(crate) fn error(&self, memory: &[u8]) -> Option<String> {
+        let src = shader_source(memory, self.offset);
+        let msg = if src == self.src.as_slice() {
+            self.error.clone()
+        } else {
+            shader_error(src, self.offset)
+        }?;
+        Some(if self.function == self.entry {
+            msg
+        } else {
+            format!("function {}: {msg}", self.function)
+        })
+    }
+}
+
+/// The shader sites of each function, checked against `memory` as if every
+/// function were an entry point; `Base` fails an execution whose entry
+/// reaches an invalid one before running it.
+pub(crate) fn entry_shaders(cranelift_ir: &str, memory: &[u8]) -> Vec<ShaderSite> {
+    if !cfg!(feature = "gpu") || !cranelift_ir.contains("cl_gpu_create_pipeline") {
+        return Vec::new();
+    }
+    let Ok(functions) = parse(cranelift_ir) else {
+        return Vec::new();
+    };
+    let entries = (0..functions.len()).collect();
+    let (operands, _) = check_operands(&functions, &entries, memory.len());
+    let direct: Vec<(usize, usize, &[u8], Option<String>)> = operands
+        .iter()
+        .filter(|op| op.operand == "shader_ptr")
+        .filter_map(|op| {
+            let offset = usize::try_from(op.range.as_ref()?.start).ok()?;
+            let src = shader_source(memory, offset);
+            Some((op.function, offset, src, shader_error(src, offset)))
+        })
+        .collect();
+    if direct.is_empty() {
+        return Vec::new();
+    }
+    let graph: Vec<Vec<usize>> = functions.iter().map(callees).collect();
+    let mut sites = Vec::new();
+    for entry in 0..functions.len() {
+        let mut reached = HashSet::from([entry]);
+        let mut stack = vec![entry];
+        while let Some(f) = stack.pop() {
+            for &g in &graph[f] {
+                if g < functions.len() && reached.insert(g) {
+                    stack.push(g);
+                }
+            }
+        }
+        for (function, offset, src, error) in &direct {
+            if reached.contains(function) {
+                sites.push(ShaderSite {
+                    entry,
+                    function: *function,
+                    offset: *offset,
+                    src: src.to_vec(),
+                    error: error.clone(),
+                });
+            }
+        }
+    }
+    sites
+}
+
+/// List the memory operands of every load, store, and known FFI call in the
+/// artifact's CLIF, with the byte range each touches when it is fixed at
+/// compile time. Operands with data-dependent addresses (`range: None`) are
+/// the ones `validate_artifact` cannot check.
+pub fn memory_operands(artifact: &Artifact) -> Result<Vec<MemoryOperand>, Error> {
+    let functions = parse(&artifact.setup.cranelift_ir)?;
+    let memory_size = artifact
+        .setup
+        .memory_size
+        .max(artifact.setup.initial_memory.len());
+    Ok(check_operands(&functions, &entry_functions(artifact), memory_size).0)
+}
+
+/// The FFI family of `name`: its first word after `cl_`, e.g. `file` for
+/// `cl_file_read`.
+fn family(name: &str) -> &str {
+    let rest = name.strip_prefix("cl_").unwrap_or(name);
+    match rest.split('_').next().unwrap_or(rest) {
+        "sinf" | "cosf" | "powf" => "math",
+        "stdin" | "stdout" => "stdio",
+        "last" => "status",
+        word => word,
+    }
+}
+
+/// `range` minus every range in `holes`.
+fn subtract(range: Range<u64>, holes: &[Range<u64>]) -> Vec<Range<u64>> {
+    let mut pieces = vec![range];
+    for hole in holes {
+        pieces = pieces
+            .into_iter()
+            .flat_map(|p| {
+                if hole.end <= p.start || hole.start >= p.end {
+                    return vec![p];
+                }
+                [p.start..hole.start, hole.end..p.end]
+                    .into_iter()
+                    .filter(|r| r.start < r.end)
+                    .collect()
+            })
+            .collect();
+    }
+    pieces
+}
+
+/// Sort `ranges` and join those that overlap or touch.
+fn coalesce(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::new();
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+/// Report the FFI families, files, network addresses and LMDB paths an
+/// artifact uses, and its candidate input and output memory ranges, without
+/// compiling or executing it.
+///
+/// Path and address arguments at constant offsets are read from the main
+/// algorithm's initial memory and symbols; the rest are reported as
+/// `StringArg::Dynamic`. Inputs and outputs come from the operands
+/// `memory_operands` resolves, taken in function and layout order, so
+/// branches and calls can make them approximate; the IO slots are left out.
+pub fn analyze_artifact(artifact: &Artifact) -> Result<ArtifactReport, Error> {
+    let setup = &artifact.setup;
+    let functions = parse(&setup.cranelift_ir)?;
+    let memory_size = setup.memory_size.max(setup.initial_memory.len());
+    let mut memory = setup.initial_memory.clone();
+    memory.resize(memory_size, 0);
+    // Symbols that do not fit are `validate_artifact`'s concern.
+    let _ = artifact.main.write_symbols(&mut memory);
+
+    let mut report = ArtifactReport::default();
+    let mut symbols: Vec<String> = functions
+        .iter()
+        .flat_map(|func| func.dfg.ext_funcs.values())
+        .filter_map(|data| match &data.name {
+            ExternalName::TestCase(testcase) => {
+                let name = testcase.to_string();
+                Some(name.strip_prefix('%').unwrap_or(&name).to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+    let mut families: Vec<String> = symbols.iter().map(|s| family(s).to_string()).collect();
+    families.sort();
+    families.dedup();
+    report.ffi_symbols = symbols;
+    report.ffi_families = families;
+
+    let io = &setup.io_offsets;
+    let io_slots: Vec<Range<u64>> = [io.data_ptr, io.data_len, io.out_ptr, io.out_len]
+        .iter()
+        .map(|&off| off as u64..off as u64 + 8)
+        .collect();
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+    let operands = check_operands(&functions, &entry_functions(artifact), memory_size).0;
+    for op in operands {
+        let start = op.range.as_ref().map(|r| r.start);
+        let mut range = op.range.filter(|r| r.start >= 0 && r.start < r.end);
+        let list = match (op.symbol, op.operand) {
+            (
+                Some("cl_file_read" | "cl_file_read_to_ptr" | "cl_file_stream_start"),
+                "path_off" | "path_ptr",
+            ) => Some(&mut report.files_read),
+            (
+                Some(
+                    "cl_file_write"
+                    | "cl_file_write_from_ptr"
+                    | "cl_file_write_atomic"
+                    | "cl_file_atomic_open"
+                    | "cl_checkpoint",
+                ),
+                "path_off" | "path_ptr",
+            ) => Some(&mut report.files_written),
+            (Some("cl_net_listen" | "cl_net_connect"), "addr_ptr") => {
+                Some(&mut report.net_addresses)
+            }
+            (Some("cl_lmdb_open" | "cl_lmdb_open_with"), "path_ptr") => {
+                Some(&mut report.lmdb_paths)
+            }
+            _ => None,
+        };
+        if let Some(list) = list {
+            let text = start
+                .and_then(|s| memory.get(usize::try_from(s).ok()?..))
+                .and_then(|tail| tail.split(|&b| b == 0).next())
+                .filter(|text| !text.is_empty());
+            let arg = match text {
+                Some(text) => {
+                    // The whole string, NUL included, is read.
+                    range = range.map(|r| r.start..r.start + text.len() as i64 + 1);
+                    StringArg::Known(String::from_utf8_lossy(text).into_owned())
+                }
+                None => StringArg::Dynamic {
+                    function: op.function,
+                    inst: op.inst,
+                },
+            };
+            if !list.contains(&arg) {
+                list.push(arg);
+            }
+        }
+        let Some(range) = range else {
+            continue;
+        };
+        let range = range.start as u64..range.end as u64;
+        if op.writes {
+            writes.push(range);
+        } else {
+            let unwritten = subtract(range.clone(), &writes);
+            let unwritten = unwritten.into_iter().flat_map(|r| subtract(r, &io_slots));
+            report.inputs.extend(unwritten);
+            reads.push(range);
+        }
+    }
+    report.inputs = coalesce(report.inputs);
+    let outputs = writes
+        .into_iter()
+        .flat_map(|w| subtract(w, &reads))
+        .flat_map(|r| subtract(r, &io_slots))
+        .collect();
+    report.outputs = coalesce(outputs);
+    Ok(report)
+}
+
+fn parse(cranelift_ir: &str) -> Result<Vec<Function>, Error> {
+    if cranelift_ir.is_empty() {
+        return Ok(Vec::new());
+    }
+    cranelift_reader::parse_functions(cranelift_ir).map_err(|e| Error::ClifParse(format!("{e}")))
+}
+
+fn entry_functions(artifact: &Artifact) -> HashSet<usize> {
+    std::iter::once(&artifact.main)
+        .chain(artifact.extras.values())
+        .map(|alg| alg.fn_idx as usize)
+        .collect()
+}
+
+/// Raise `setup.memory_size` to the smallest size that holds everything the
+/// artifact statically addresses: the IO slots, the arena, every algorithm's output
+/// schemas and symbols, and the load, store and FFI operands whose address
+/// and length are constants (data-dependent ones are left to the caller).
+/// Never shrinks a larger size already set. Returns the resulting size.
+pub fn infer_memory_size(artifact: &mut Artifact) -> Result<usize, Error> {
+    let functions = parse(&artifact.setup.cranelift_ir)?;
+    let io = &artifact.setup.io_offsets;
+    let io_end = [io.data_ptr, io.data_len, io.out_ptr, io.out_len]
+        .iter()
+        .map(|&off| off as u64 + 8)
+        .max()
+        .unwrap_or(0);
+    let operands_end = check_operands(&functions, &entry_functions(artifact), usize::MAX)
+        .0
+        .into_iter()
+        .filter_map(|op| op.range.filter(|r| r.start >= 0).map(|r| r.end as u64))
+        .max()
+        .unwrap_or(0);
+    let algorithms_end = std::iter::once(&artifact.main)
+        .chain(artifact.extras.values())
+        .flat_map(|alg| {
+            let outputs = alg.output.iter().flat_map(|schema| {
+                std::iter::once(schema.row_count_offset as u64 + 8)
+                    .chain(schema.columns.iter().map(|c| c.data_offset as u64 + 1))
+                    .chain(
+                        schema
+                            .columns
+                            .iter()
+                            .filter(|c| c.dtype == OutputType::Utf8)
+                            .map(|c| c.len_offset as u64 + 8),
+                    )
+            });
+            let symbols = alg
+                .symbols
+                .iter()
+                .map(|s| s.offset.saturating_add(s.len as u64));
+            outputs.chain(symbols)
+        })
+        .max()
+        .unwrap_or(0);
+    let arena_end = artifact.setup.arena().map_or(0, |r| r.end as u64);
+    let needed = io_end.max(arena_end).max(operands_end).max(algorithms_end);
+    let setup = &mut artifact.setup;
+    setup.memory_size = setup.memory_size.max(needed as usize);
+    Ok(setup.memory_size)
+}
+
+/// Statically check an artifact without compiling or executing it.
+///
+/// Returns every issue found (empty when the artifact looks runnable), or
+/// `Error::ClifParse` if the CLIF source cannot be parsed at all.
+pub fn validate_artifact(artifact: &Artifact) -> Result<Vec<ValidationIssue>, Error> {
+    let setup = &artifact.setup;
+    let mut issues = Vec::new();
+
+    let functions = parse(&setup.cranelift_ir)?;
+
+    let known = symbol_names();
+    let flags = settings::Flags::new(settings::builder());
+    for (i, func) in functions.iter().enumerate() {
+        for (_, data) in func.dfg.ext_funcs.iter() {
+            if let ExternalName::TestCase(testcase) = &data.name {
+                let name = testcase.to_string();
+                let name = name.strip_prefix('%').unwrap_or(&name);
+                if let Some(feature) = disabled_feature(name) {
+                    issues.push(ValidationIssue::FeatureDisabled {
+                        function: i,
+                        label: None,
+                        name: name.to_string(),
+                        feature,
+                    });
+                } else if !known.contains(&name) {
+                    issues.push(ValidationIssue::UnknownSymbol {
+                        function: i,
+                        label: None,
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+        if let Err(errors) = cranelift_codegen::verify_function(func, &flags) {
+            issues.push(ValidationIssue::Verifier {
+                function: i,
+                label: None,
+                message: errors.to_string(),
+            });
+        }
+    }
+
+    // `Base` grows memory to cover the arena.
+    let arena = setup.arena();
+    let memory_size = setup
+        .memory_size
+        .max(setup.initial_memory.len())
+        .max(arena.as_ref().map_or(0, |r| r.end));
+    let entries = entry_functions(artifact);
+    let (operands, operand_issues) = check_operands(&functions, &entries, memory_size);
+    issues.extend(operand_issues);
+    issues.extend(shader_issues(&operands, &setup.initial_memory));
+    if let Some(arena) = &arena {
+        let (start, end) = (arena.start as i64, arena.end as i64);
+        let inside = operands.iter().filter(|op| {
+            op.range
+                .as_ref()
+                .is_some_and(|r| r.start < end && start < r.end)
+        });
+        issues.extend(inside.map(|op| ValidationIssue::ArenaOperand {
+            function: op.function,
+            label: None,
+            inst: op.inst.clone(),
+            operand: op.operand,
+        }));
+    }
+    let io = &setup.io_offsets;
+    let io_slots = [
+        ("the data_ptr slot", io.data_ptr),
+        ("the data_len slot", io.data_len),
+        ("the out_ptr slot", io.out_ptr),
+        ("the out_len slot", io.out_len),
+    ]
+    .map(|(what, off)| (what, off as u64..off as u64 + 8));
+    let reserved: Vec<_> = io_slots
+        .into_iter()
+        .chain(arena.map(|r| ("the arena", r.start as u64..r.end as u64)))
+        .collect();
+    let mut algorithms: Vec<(&str, &Algorithm)> = vec![("main", &artifact.main)];
+    let mut extras: Vec<_> = artifact.extras.iter().collect();
+    extras.sort_by(|a, b| a.0.cmp(b.0));
+    algorithms.extend(extras.into_iter().map(|(name, alg)| (name.as_str(), alg)));
+
+    for (name, alg) in algorithms {
+        let fn_idx = alg.fn_idx as usize;
+        if !functions.is_empty() && fn_idx >= functions.len() {
+            issues.push(ValidationIssue::FnIndexOutOfRange {
+                algorithm: name.to_string(),
+                fn_idx,
+                available: functions.len(),
+            });
+        }
+        for schema in &alg.output {
+            // The row count and string byte lengths are u64s; column data
+            // only needs to start in bounds.
+            let word = |offset: usize| (offset, offset.saturating_add(8));
+            let out_of_bounds = std::iter::once(word(schema.row_count_offset))
+                .chain(
+                    schema
+                        .columns
+                        .iter()
+                        .map(|c| (c.data_offset, c.data_offset.saturating_add(1))),
+                )
+                .chain(
+                    schema
+                        .columns
+                        .iter()
+                        .filter(|c| c.dtype == OutputType::Utf8)
+                        .map(|c| word(c.len_offset)),
+                )
+                .filter(|&(_, end)| end > memory_size);
+            for (offset, _) in out_of_bounds {
+                issues.push(ValidationIssue::OutputOutOfBounds {
+                    algorithm: name.to_string(),
+                    offset,
+                    memory_size,
+                });
+            }
+        }
+        if let Err(error) = alg.check_symbols(memory_size, &reserved) {
+            issues.push(ValidationIssue::Symbol {
+                algorithm: name.to_string(),
+                error,
+            });
+        }
+        for symbol in alg.symbols.iter().filter(|s| !s.writable) {
+            let start = symbol.offset as i64;
+            let end = start.saturating_add(symbol.len as i64);
+            let clobbering = operands.iter().filter(|op| {
+                op.writes
+                    && op
+                        .range
+                        .as_ref()
+                        .is_some_and(|r| r.start < end && start < r.end)
+            });
+            issues.extend(clobbering.map(|op| ValidationIssue::SymbolOverwritten {
+                algorithm: name.to_string(),
+                symbol: symbol.name.clone(),
+                function: op.function,
+                label: None,
+                inst: op.inst.clone(),
+                operand: op.operand,
+            }));
+        }
+    }
+
+    for issue in &mut issues {
+        issue.attach_label(artifact);
+    }
+    Ok(issues)
+}
+
+impl ValidationIssue {
+    fn attach_label(&mut self, artifact: &Artifact) {
+        let (function, label) = match self {
+            ValidationIssue::UnknownSymbol {
+                function, label, ..
+            }
+            | ValidationIssue::FeatureDisabled {
+                function, label, ..
+            }
+            | ValidationIssue::Verifier {
+                function, label, ..
+            }
+            | ValidationIssue::SymbolOverwritten {
+                function, label, ..
+            }
+            | ValidationIssue::OperandOutOfBounds {
+                function, label, ..
+            }
+            | ValidationIssue::ArenaOperand {
+                function, label, ..
+            }
+            | ValidationIssue::ShaderInvalid {
+                function, label, ..
+            }
+            | ValidationIssue::FnArgOutOfRange {
+                function, label, ..
+            } => (*function, label),
+            _ => return,
+        };
+        *label = artifact.function_label(function as u32).map(str::to_string);
+    }
+}
@@ -1,8 +1,8 @@
 use arrow_array::{Float64Array, Int64Array, StringArray};
 use arrow_schema::{DataType, Field, Schema};
-use base::{run, Base, RecordBatch};
+use base::{run, run_with, Base, RecordBatch, RunOptions};
 use base_types::{
-    Algorithm, Setup, OutputBatchSchema, OutputColumn, OutputType, IoOffsets,
+    Algorithm, Setup, OutputBatchSchema, OutputColumn, OutputType, IoOffsets, ProfileKey,
 };
 use std::fs;
 use std::sync::Arc;
@@ -23,14 +23,13 @@ fn cranelift_config(memory: Vec<u8>, cranelift_ir: String) -> Setup {
         memory_size: memory.len(),
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     }
 }
 
 fn cranelift_algorithm(fn_idx: u32) -> Algorithm {
-    Algorithm {
-        fn_idx,
-        output: vec![],
-    }
+    Algorithm::new(fn_idx)
 }
 
 fn create_cranelift_algorithm(
@@ -77,6 +76,58 @@ block0(v0: i64):
     assert_eq!(result, 42);
 }
 
+#[test]
+fn test_failed_file_write_logs_error_with_path() {
+    #[derive(Clone, Default)]
+    struct Capture(Arc<std::sync::Mutex<Vec<u8>>>);
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let test_file = temp_dir.path().join("missing_dir").join("out.bin");
+    let file_str = format!("{}\0", test_file.to_str().unwrap());
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = iconst.i64 3000
+    v2 = iconst.i64 2000
+    v3 = iconst.i64 0
+    v4 = iconst.i64 8
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    return
+}"#;
+    let mut memory = vec![0u8; 4096];
+    memory[3000..3000 + file_str.len()].copy_from_slice(file_str.as_bytes());
+    let (config, algorithm) = create_cranelift_algorithm(0, memory, clif_ir.to_string());
+
+    let capture = Capture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::DEBUG)
+        .finish();
+    tracing::subscriber::with_default(subscriber, || run(config, algorithm).unwrap());
+
+    let log = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+    let line = log
+        .lines()
+        .find(|l| l.contains("ERROR") && l.contains("file call failed"))
+        .unwrap_or_else(|| panic!("no error event in:\n{log}"));
+    assert!(line.contains("call=\"cl_file_write\""), "{line}");
+    assert!(line.contains(test_file.to_str().unwrap()), "{line}");
+    assert!(line.contains("execute{execution="), "{line}");
+    assert!(line.contains("fn_idx=0"), "{line}");
+}
+
 #[test]
 fn test_cranelift_arithmetic_add() {
     let temp_dir = TempDir::new().unwrap();
@@ -251,6 +302,7 @@ block3:
 }
 
 #[test]
+#[cfg(all(feature = "gpu", feature = "cuda", feature = "lmdb", feature = "net"))]
 fn test_clif_ffi_all_symbols_linkable() {
     // Authoritative check that every FFI symbol registered in jit.rs is
     // resolvable from CLIF. Generates a function that takes each symbol's
@@ -262,10 +314,14 @@ fn test_clif_ffi_all_symbols_linkable() {
     // is caught by a dedicated, fast-failing test.
     let symbols: &[&str] = &[
         "cl_ht_init", "cl_ht_cleanup", "ht_create", "ht_lookup", "ht_insert",
-        "ht_count", "ht_get_entry", "ht_increment",
+        "ht_count", "ht_get_entry", "ht_increment", "ht_close", "ht_handle_count",
+        "ht_create_with_capacity", "ht_remove", "ht_clear", "ht_stats",
         "cl_gpu_init", "cl_gpu_create_buffer", "cl_gpu_create_pipeline",
         "cl_gpu_upload", "cl_gpu_upload_ptr", "cl_gpu_dispatch", "cl_gpu_download",
-        "cl_gpu_download_ptr", "cl_gpu_cleanup",
+        "cl_gpu_download_ptr", "cl_gpu_download_async", "cl_gpu_poll", "cl_gpu_wait",
+        "cl_gpu_upload_typed", "cl_gpu_download_typed", "cl_gpu_cleanup",
+        "cl_gpu_init_fallback", "cl_gpu_init_adapter", "cl_gpu_pipeline_cpu",
+        "cl_gpu_create_pipeline_regions", "cl_gpu_dispatch_regions",
         "cl_cuda_init", "cl_cuda_create_buffer", "cl_cuda_upload",
         "cl_cuda_upload_ptr", "cl_cuda_upload_ptr_offset", "cl_cuda_upload_ptr_async",
         "cl_cuda_upload_ptr_offset_async", "cl_cuda_download", "cl_cuda_download_ptr",
@@ -281,15 +337,38 @@ fn test_clif_ffi_all_symbols_linkable() {
         "cl_cublas_sgemm", "cl_cublas_sgemv", "cl_cublas_sgemv_on_stream",
         "cl_cublas_sgemm_strided_batched", "cl_cublas_sgemm_strided_batched_on_stream",
         "cl_file_read", "cl_file_read_to_ptr", "cl_file_write", "cl_file_write_from_ptr",
-        "cl_sinf", "cl_cosf", "cl_powf",
+        "cl_file_retry",
+        "cl_file_write_atomic", "cl_file_atomic_init", "cl_file_atomic_cleanup",
+        "cl_file_atomic_open", "cl_file_atomic_write", "cl_file_commit", "cl_file_abort",
+        "cl_file_handle_init", "cl_file_handle_cleanup", "cl_file_open", "cl_file_close",
+        "cl_file_read_handle", "cl_file_write_handle",
+        "cl_file_stream_start", "cl_file_stream_end",
+        "cl_sinf", "cl_cosf", "cl_powf", "cl_approx",
         "cl_stdin_readline", "cl_stdout_write",
+        "cl_mem_fill", "cl_mem_copy", "cl_mem_compare", "cl_mem_scan", "cl_mem_cond_write", "cl_mem_byteswap",
+        "cl_mem_array_op", "cl_mem_publish", "cl_mem_snapshot",
+        "cl_lz4_compress", "cl_lz4_decompress", "cl_checksum",
+        "cl_arena_init", "cl_arena_alloc", "cl_arena_size", "cl_arena_free", "cl_arena_cleanup",
+        "cl_arena_bump", "cl_arena_reset",
+        "cl_queue_init", "cl_queue_push", "cl_queue_pop",
+        "cl_trace", "cl_clock", "cl_sleep", "cl_random", "cl_random_weighted",
+        "cl_cancelled", "cl_last_status", "cl_checkpoint", "cl_execute_nested",
         "cl_net_init", "cl_net_listen", "cl_net_listener_port", "cl_net_connect",
-        "cl_net_accept", "cl_net_send", "cl_net_recv", "cl_net_cleanup",
-        "cl_lmdb_init", "cl_lmdb_open", "cl_lmdb_put", "cl_lmdb_get", "cl_lmdb_delete",
-        "cl_lmdb_begin_write_txn", "cl_lmdb_commit_write_txn", "cl_lmdb_cursor_scan",
-        "cl_lmdb_sync", "cl_lmdb_cleanup",
+        "cl_net_accept", "cl_net_send", "cl_net_recv", "cl_net_recv_framed", "cl_net_close", "cl_net_cleanup",
+        "cl_net_retry",
+        "cl_http_request",
+        "cl_lmdb_init", "cl_lmdb_open", "cl_lmdb_open_with", "cl_lmdb_put", "cl_lmdb_get",
+        "cl_lmdb_get_bounded", "cl_lmdb_delete", "cl_lmdb_begin_write_txn",
+        "cl_lmdb_commit_write_txn", "cl_lmdb_cursor_scan", "cl_lmdb_cursor_scan_bounded",
+        "cl_lmdb_sync", "cl_lmdb_close", "cl_lmdb_handle_count",
+        "cl_lmdb_cleanup",
         "cl_thread_init", "cl_thread_spawn", "cl_thread_join", "cl_thread_cleanup",
-        "cl_thread_call",
+        "cl_thread_call", "cl_thread_pool_start", "cl_thread_pool_start_bounded",
+        "cl_thread_pool_submit", "cl_thread_pool_try_submit", "cl_thread_pool_dispatch",
+        "cl_thread_pool_dispatch_if",
+        "cl_thread_pool_broadcast", "cl_thread_pool_chain", "cl_thread_pool_fence",
+        "cl_thread_pool_wait", "cl_thread_pool_stop", "cl_thread_wait_until", "cl_thread_wake",
+        "cl_thread_barrier_init", "cl_thread_barrier_wait",
     ];
 
     let mut decls = String::new();
@@ -368,6 +447,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "gpu")]
 fn test_clif_ffi_gpu_smoke() {
     // Runtime smoke: exercises the wgpu FFI call path
     // (init → create_buffer → upload → dispatch → download → cleanup).
@@ -450,6 +530,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "net")]
 fn test_clif_ffi_net_smoke() {
     use std::io::{Read, Write};
     use std::net::TcpListener;
@@ -515,6 +596,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "lmdb")]
 fn test_clif_ffi_lmdb_smoke() {
     // Runtime smoke: exercises the lmdb FFI call path
     // (init → open → put → get → cursor_scan → cleanup).
@@ -574,6 +656,96 @@ block0(v0: i64):
     run(config, algorithm).unwrap();
 }
 
+#[test]
+#[cfg(feature = "lmdb")]
+fn test_clif_lmdb_open_with_read_only() {
+    // Options blocks at 128 (1GB map, NO_SYNC) and 144 (READ_ONLY), path at
+    // 256, key "k" at 512, value at 520, get buffer at 600. fn 0 opens with
+    // the first block and puts the value; fn 1 reopens read-only, tries to
+    // overwrite, then gets. out = [put rc, put status, get rc, value].
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = format!("{}\0", temp_dir.path().join("ro").to_str().unwrap());
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i32, i64, i32) -> i32 system_v
+    fn0 = %cl_lmdb_init sig0
+    fn1 = %cl_lmdb_open_with sig1
+    fn2 = %cl_lmdb_put sig2
+    fn3 = %cl_lmdb_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iadd_imm v0, 256
+    v4 = iadd_imm v0, 128
+    v5 = call fn1(v2, v3, v4)
+    v6 = iadd_imm v0, 512
+    v7 = iconst.i32 1
+    v8 = iadd_imm v0, 520
+    v9 = iconst.i32 8
+    v10 = call fn2(v2, v5, v6, v7, v8, v9)
+    call fn3(v1)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i32, i64, i32) -> i32 system_v
+    sig3 = (i64, i32, i64, i32, i64) -> i32 system_v
+    sig4 = () -> i64 system_v
+    fn0 = %cl_lmdb_init sig0
+    fn1 = %cl_lmdb_open_with sig1
+    fn2 = %cl_lmdb_put sig2
+    fn3 = %cl_lmdb_get sig3
+    fn4 = %cl_last_status sig4
+    fn5 = %cl_lmdb_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iadd_imm v0, 256
+    v4 = iadd_imm v0, 144
+    v5 = call fn1(v2, v3, v4)
+    v6 = iadd_imm v0, 512
+    v7 = iconst.i32 1
+    v8 = iadd_imm v0, 512
+    v9 = call fn2(v2, v5, v6, v7, v8, v7)
+    v10 = call fn4()
+    v11 = iadd_imm v0, 600
+    v12 = call fn3(v2, v5, v6, v7, v11)
+    call fn5(v1)
+    v13 = load.i64 v0+24
+    v14 = sextend.i64 v9
+    store.i64 v14, v13
+    store.i64 v10, v13+8
+    v15 = sextend.i64 v12
+    store.i64 v15, v13+16
+    v16 = load.i64 v0+604
+    store.i64 v16, v13+24
+    return
+}"#;
+
+    let mut memory = vec![0u8; 1024];
+    memory[128..136].copy_from_slice(&(1u64 << 30).to_le_bytes());
+    memory[140..144].copy_from_slice(&2u32.to_le_bytes());
+    memory[156..160].copy_from_slice(&1u32.to_le_bytes());
+    memory[256..256 + db_path.len()].copy_from_slice(db_path.as_bytes());
+    memory[512] = b'k';
+    memory[520..528].copy_from_slice(b"stored!!");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    base.execute(&cranelift_algorithm(0), &[]).unwrap();
+    let mut out = [0u8; 32];
+    base.execute_into(&cranelift_algorithm(1), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    assert_eq!(word(0) as i64, -1, "put on a read-only environment");
+    assert_eq!(base_types::status::status(word(1)), 13, "EACCES");
+    assert_eq!(word(2), 8);
+    assert_eq!(&out[24..], b"stored!!");
+}
+
 #[test]
 fn test_clif_ffi_thread_smoke() {
     // Runtime smoke: exercises the thread FFI call path
@@ -1013,10 +1185,14 @@ fn create_output_algorithm(
         memory_size: p.len(),
         io_offsets: compact_io_offsets(),
         initial_memory: p,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let algorithm = Algorithm {
         fn_idx: 0,
         output,
+        symbols: vec![],
+        labels: vec![],
     };
     (config, algorithm)
 }
@@ -1631,6 +1807,66 @@ block0(v0: i64):
     assert_eq!(batches[1], expected_1);
 }
 
+#[test]
+fn test_output_clamps_counts_and_offsets_past_memory() {
+    // CLIF stores a garbage row count (u64::MAX) at 2000 and a count of 1 at
+    // 2008. The first schema's I64 column has room for two rows before the
+    // end of memory and its Utf8 column starts past it; the second schema's
+    // single string starts past it too.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 -1
+    store.i64 v1, v0+2000
+    v2 = iconst.i64 1
+    store.i64 v2, v0+2008
+    return
+}"#;
+    let column = |name: &str, dtype, data_offset, len_offset| OutputColumn {
+        name: name.to_string(),
+        dtype,
+        data_offset,
+        len_offset,
+    };
+    let output = vec![
+        OutputBatchSchema {
+            row_count_offset: 2000,
+            columns: vec![
+                column("tail", OutputType::I64, 4080, 0),
+                column("gone", OutputType::Utf8, 5000, 4092),
+            ],
+        },
+        OutputBatchSchema {
+            row_count_offset: 2008,
+            columns: vec![column("gone", OutputType::Utf8, 5000, 16)],
+        },
+    ];
+    let (cfg, alg) = create_output_algorithm(clif_ir, vec![0u8; 4096], output);
+    let artifact = base::Artifact::new(cfg.clone(), alg.clone());
+    let offsets: Vec<_> = base::validate_artifact(&artifact)
+        .unwrap()
+        .into_iter()
+        .filter_map(|issue| match issue {
+            base::ValidationIssue::OutputOutOfBounds { offset, .. } => Some(offset),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(offsets, [5000, 4092, 5000]);
+    let mut grown = artifact.clone();
+    assert_eq!(base::infer_memory_size(&mut grown).unwrap(), 5001);
+
+    // The second batch has no row in memory, so it is skipped like an
+    // empty one.
+    let batches = run(cfg, alg).unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].num_rows(), 2, "clamped to the rows memory holds");
+    let strings = batches[0]
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!((strings.value(0), strings.value(1)), ("", ""));
+}
+
 #[test]
 fn test_base_single_execute_matches_standalone() {
     // Base::new + execute should produce the same result as standalone run.
@@ -1666,10 +1902,14 @@ block0(v0: i64):
         memory_size: memory.len(),
         io_offsets: compact_io_offsets(),
         initial_memory: memory.clone(),
+        arena_offset: 0,
+        arena_size: 0,
     };
     let alg1 = Algorithm {
         fn_idx: 0,
         output: output_schema.clone(),
+        symbols: vec![],
+        labels: vec![],
     };
     let batches1 = run(config1, alg1).unwrap();
 
@@ -1679,10 +1919,14 @@ block0(v0: i64):
         memory_size: memory.len(),
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let alg2 = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
     let mut base = Base::new(config2).unwrap();
     let batches2 = base.execute(&alg2, &[]).unwrap();
@@ -1728,6 +1972,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -1748,6 +1994,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema.clone(),
+                symbols: vec![],
+                labels: vec![],
             },
             &data1,
         )
@@ -1767,6 +2015,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &data2,
         )
@@ -1813,6 +2063,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -1830,6 +2082,8 @@ block0(v0: i64):
     let alg1 = Algorithm {
         fn_idx: 0,
         output: output_schema.clone(),
+        symbols: vec![],
+        labels: vec![],
     };
     let batches1 = base.execute(&alg1, &vec![0u8; 4096]).unwrap();
     let col1 = batches1[0]
@@ -1843,6 +2097,8 @@ block0(v0: i64):
     let alg2 = Algorithm {
         fn_idx: 1,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
     let batches2 = base.execute(&alg2, &vec![0u8; 4096]).unwrap();
     let col2 = batches2[0]
@@ -1877,6 +2133,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -1897,6 +2155,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema.clone(),
+                symbols: vec![],
+                labels: vec![],
             },
             &d1,
         )
@@ -1915,6 +2175,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema.clone(),
+                symbols: vec![],
+                labels: vec![],
             },
             &d2,
         )
@@ -1933,6 +2195,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &d3,
         )
@@ -1976,12 +2240,16 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: mem1,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config1).unwrap();
     base.execute(
         &Algorithm {
             fn_idx: 0,
             output: vec![],
+            symbols: vec![],
+            labels: vec![],
         },
         &[],
     )
@@ -1999,6 +2267,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: mem2,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base2 = Base::new(config2).unwrap();
     base2
@@ -2006,6 +2276,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: vec![],
+                symbols: vec![],
+                labels: vec![],
             },
             &[],
         )
@@ -2034,6 +2306,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2042,6 +2316,8 @@ block0(v0: i64):
         &Algorithm {
             fn_idx: 0,
             output: vec![],
+            symbols: vec![],
+            labels: vec![],
         },
         &vec![0u8; 4096],
     )
@@ -2052,6 +2328,8 @@ block0(v0: i64):
         &Algorithm {
             fn_idx: 0,
             output: vec![],
+            symbols: vec![],
+            labels: vec![],
         },
         &vec![0u8; 4096],
     )
@@ -2062,6 +2340,8 @@ block0(v0: i64):
         &Algorithm {
             fn_idx: 0,
             output: vec![],
+            symbols: vec![],
+            labels: vec![],
         },
         &vec![0u8; 4096],
     )
@@ -2093,6 +2373,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: mem,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2112,6 +2394,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &data,
         )
@@ -2155,6 +2439,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2163,6 +2449,8 @@ block0(v0: i64):
         &Algorithm {
             fn_idx: 0,
             output: vec![],
+            symbols: vec![],
+            labels: vec![],
         },
         &[],
     )
@@ -2185,6 +2473,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 1,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &data,
         )
@@ -2220,6 +2510,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2239,6 +2531,8 @@ block0(v0: i64):
                 &Algorithm {
                     fn_idx: 0,
                     output: output_schema.clone(),
+                    symbols: vec![],
+                    labels: vec![],
                 },
                 &[],
             )
@@ -2274,6 +2568,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2295,6 +2591,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema.clone(),
+                symbols: vec![],
+                labels: vec![],
             },
             &d1,
         )
@@ -2315,6 +2613,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &d2,
         )
@@ -2356,6 +2656,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2366,6 +2668,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: vec![],
+                symbols: vec![],
+                labels: vec![],
             },
             &d,
         )
@@ -2390,6 +2694,8 @@ block0(v0: i64):
             &Algorithm {
                 fn_idx: 0,
                 output: output_schema,
+                symbols: vec![],
+                labels: vec![],
             },
             &d,
         )
@@ -2416,6 +2722,8 @@ fn clif_parse_error_garbage_ir() {
         memory_size: 256,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let Err(err) = Base::new(config) else {
         panic!("expected ClifParse error for garbage IR");
@@ -2430,10 +2738,14 @@ fn clif_parse_error_via_run() {
         memory_size: 256,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let algorithm = Algorithm {
         fn_idx: 0,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
     let Err(err) = run(config, algorithm) else {
         panic!("expected ClifParse error for invalid CLIF via run()");
@@ -2448,6 +2760,8 @@ fn clif_parse_error_incomplete_function() {
         memory_size: 256,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let Err(err) = Base::new(config) else {
         panic!("expected ClifParse error for incomplete function");
@@ -2455,6 +2769,54 @@ fn clif_parse_error_incomplete_function() {
     assert!(matches!(err, base::Error::ClifParse(_)));
 }
 
+#[test]
+fn clif_compile_error_reports_function_index() {
+    // Function 1 parses but adds an i32 to an i64, which the verifier rejects.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i32 1
+    v2 = iadd v0, v1
+    return
+}"#;
+    let Err(err) = Base::new(cranelift_config(vec![0u8; 64], clif_ir.to_string())) else {
+        panic!("expected Compile error for ill-typed function");
+    };
+    match err {
+        base::Error::Compile { fn_idx, message } => {
+            assert_eq!(fn_idx, 1);
+            assert!(message.contains("iadd"), "verifier text missing: {message}");
+        }
+        other => panic!("expected Compile error, got {other:?}"),
+    }
+}
+
+#[test]
+fn execute_fn_index_out_of_range() {
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}"#;
+    let mut base = Base::new(cranelift_config(vec![0u8; 256], clif_ir.to_string())).unwrap();
+    let Err(err) = base.execute(&cranelift_algorithm(3), &[]) else {
+        panic!("expected FnIndexOutOfRange for fn_idx past the compiled functions");
+    };
+    assert!(matches!(
+        err,
+        base::Error::FnIndexOutOfRange {
+            fn_idx: 3,
+            available: 1
+        }
+    ));
+    assert_eq!(err.to_string(), "fn_idx 3 out of range (have 1 fns)");
+    let boxed: Box<dyn std::error::Error> = Box::new(err);
+    assert!(boxed.to_string().contains("out of range"));
+}
+
 #[test]
 fn clif_parse_error_empty_ir_no_error() {
     // Empty string should NOT error — it skips compilation entirely
@@ -2463,12 +2825,15 @@ fn clif_parse_error_empty_ir_no_error() {
         memory_size: 256,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let base = Base::new(config);
     assert!(base.is_ok());
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_clif_ffi_cuda_smoke() {
     // Runtime smoke: exercises the cuda FFI call path
     // (init → create_buffer → upload → launch → sync → download → cleanup).
@@ -2564,6 +2929,7 @@ block0(v0: i64):
 
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cublas_sgemv_on_stream_reuse() {
     let rows: usize = 2;
     let cols: usize = 3;
@@ -2640,11 +3006,15 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![0u8; mem_size],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let a1: [f32; 6] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
@@ -2683,6 +3053,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cublas_sgemm_strided_batched_on_stream_reuse() {
     let batch_count: usize = 2;
     let m: usize = 2;
@@ -2770,12 +3141,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![0u8; mem_size],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let a1: [f32; 12] = [
@@ -2863,6 +3238,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -2890,6 +3267,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let batches = base.execute(&alg, &data).unwrap();
@@ -2933,6 +3312,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: initial,
+        arena_offset: 0,
+        arena_size: 0,
     };
 
     let output_schema = vec![OutputBatchSchema {
@@ -2947,6 +3328,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let batches = run(config, alg).unwrap();
@@ -2984,6 +3367,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: initial,
+        arena_offset: 0,
+        arena_size: 0,
     };
 
     let output_schema = vec![OutputBatchSchema {
@@ -2998,6 +3383,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let batches = run(config, alg).unwrap();
@@ -3037,6 +3424,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3048,6 +3437,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     base.execute_into(&alg, &data, &mut out).unwrap();
@@ -3077,12 +3468,16 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 0,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     // Call 1: data=111
@@ -3128,6 +3523,8 @@ block0(v0: i64):
         memory_size: 256,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3156,6 +3553,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let batches = base.execute(&alg, &data).unwrap();
@@ -3207,6 +3606,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: initial,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3222,6 +3623,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     // Dynamic input = 7
@@ -3265,12 +3668,16 @@ block0(v0: i64):
         memory_size: 64,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 0,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     // Tiny shared memory (64 bytes) but large out buffer
@@ -3308,6 +3715,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
 
     let output_schema = vec![OutputBatchSchema {
@@ -3322,6 +3731,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let data = 777i64.to_le_bytes().to_vec();
@@ -3358,6 +3769,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
 
     let output_schema = vec![OutputBatchSchema {
@@ -3372,6 +3785,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     let data = vec![42u8]; // single byte
@@ -3407,6 +3822,8 @@ block0(v0: i64):
         memory_size: 4096,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3430,6 +3847,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 0,
         output: output_schema,
+        symbols: vec![],
+        labels: vec![],
     };
 
     // Call 1: 8-byte buffer
@@ -3467,6 +3886,222 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_cpu_fallback_matches_shader() {
+    // test_clif_ffi_gpu_smoke's doubling shader, with fn 1 attached as its
+    // CPU equivalent. Forcing the CPU path and letting init pick (a device
+    // when one exists) must download the same bytes.
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                    let i = gid.x;\n\
+                    if (i < arrayLength(&data)) { data[i] = data[i] * 2.0; }\n\
+                }\n";
+    let (shader_off, bind_off, data_off, mode_off) = (2000usize, 3000usize, 4000usize, 48usize);
+    let n: usize = 64;
+    let clif_ir = format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i32) -> i32 system_v
+    sig2 = (i64, i64) -> i32 system_v
+    sig3 = (i64, i32, i64, i64) -> i32 system_v
+    sig4 = (i64, i64, i64, i32) -> i32 system_v
+    sig5 = (i64, i32, i64) -> i32 system_v
+    sig6 = (i64, i32, i32, i32, i32) -> i32 system_v
+    fn0 = %cl_gpu_init_fallback sig1
+    fn1 = %cl_gpu_create_buffer sig2
+    fn2 = %cl_gpu_upload sig3
+    fn3 = %cl_gpu_create_pipeline sig4
+    fn4 = %cl_gpu_pipeline_cpu sig5
+    fn5 = %cl_gpu_dispatch sig6
+    fn6 = %cl_gpu_download sig3
+    fn7 = %cl_gpu_cleanup sig0
+block0(v0: i64):
+    v1 = load.i32 v0+{mode_off}
+    v2 = call fn0(v0, v1)
+    store v2, v0+{mode_off}
+    v3 = load.i64 notrap aligned v0
+    v4 = iconst.i64 {data_bytes}
+    v5 = call fn1(v3, v4)
+    v6 = iadd_imm v0, {data_off}
+    v7 = call fn2(v3, v5, v6, v4)
+    v8 = iadd_imm v0, {shader_off}
+    v9 = iadd_imm v0, {bind_off}
+    v10 = iconst.i32 1
+    v11 = call fn3(v3, v8, v9, v10)
+    v12 = iconst.i64 1
+    v13 = call fn4(v3, v11, v12)
+    v14 = call fn5(v3, v11, v10, v10, v10)
+    v15 = load.i64 v0+24
+    v16 = call fn6(v3, v5, v15, v4)
+    call fn7(v0)
+    return
+}}
+
+function u0:1(i64) system_v {{
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v0+32
+    v3 = iconst.i64 0
+    jump block1(v3)
+
+block1(v4: i64):
+    v5 = icmp ult v4, v2
+    brif v5, block2, block3
+
+block2:
+    v6 = iadd v1, v4
+    v7 = load.f32 v6
+    v8 = fadd v7, v7
+    store v8, v6
+    v9 = iadd_imm v4, 4
+    jump block1(v9)
+
+block3:
+    return
+}}"#,
+        data_bytes = n * 4,
+    );
+
+    let mut memory = vec![0u8; 6144];
+    memory[shader_off..shader_off + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+    // 1 binding: buf0 read_write
+    memory[bind_off..bind_off + 8].copy_from_slice(&[0; 8]);
+    for i in 0..n {
+        memory[data_off + i * 4..data_off + i * 4 + 4]
+            .copy_from_slice(&((i + 1) as f32).to_le_bytes());
+    }
+    let expected: Vec<u8> = (0..n)
+        .flat_map(|i| (2.0 * (i + 1) as f32).to_le_bytes())
+        .collect();
+
+    for force_cpu in [1u32, 0] {
+        let mut memory = memory.clone();
+        memory[mode_off..mode_off + 4].copy_from_slice(&force_cpu.to_le_bytes());
+        let mut base = Base::new(cranelift_config(memory, clif_ir.clone())).unwrap();
+        let mut out = vec![0u8; n * 4];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        assert_eq!(out, expected, "force_cpu={force_cpu}");
+        let on_device = base.memory_handle().read(mode_off, 4).unwrap();
+        if force_cpu == 1 {
+            assert_eq!(on_device, [0; 4]);
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_region_bindings_on_device_and_cpu() {
+    // Two read-only inputs and a read-write output bound straight from
+    // memory regions at unrelated offsets, with sentinel bytes between them.
+    // fn 1 is the CPU equivalent: its argument block holds the workgroup
+    // counts, then (address, length) for a, b, and result.
+    let wgsl = "@group(0) @binding(0) var<storage, read> a: array<f32>;\n\
+                @group(0) @binding(1) var<storage, read> b: array<f32>;\n\
+                @group(0) @binding(2) var<storage, read_write> result: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                    let i = gid.x;\n\
+                    if (i < arrayLength(&result)) { result[i] = a[i] + b[i]; }\n\
+                }\n";
+    let (shader_off, table_off, mode_off) = (2000usize, 3000usize, 48usize);
+    let (a_off, b_off, result_off) = (4000usize, 4512usize, 5200usize);
+    let n: usize = 64;
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i32) -> i32 system_v
+    sig2 = (i64, i64, i64, i32) -> i32 system_v
+    sig3 = (i64, i32, i64) -> i32 system_v
+    sig4 = (i64, i32, i64, i32, i32, i32) -> i32 system_v
+    fn0 = %cl_gpu_init_fallback sig1
+    fn1 = %cl_gpu_create_pipeline_regions sig2
+    fn2 = %cl_gpu_pipeline_cpu sig3
+    fn3 = %cl_gpu_dispatch_regions sig4
+    fn4 = %cl_gpu_cleanup sig0
+block0(v0: i64):
+    v1 = load.i32 v0+48
+    v2 = call fn0(v0, v1)
+    store v2, v0+48
+    v3 = load.i64 notrap aligned v0
+    v4 = iadd_imm v0, 2000
+    v5 = iadd_imm v0, 3000
+    v6 = iconst.i32 3
+    v7 = call fn1(v3, v4, v5, v6)
+    v8 = iconst.i64 1
+    v9 = call fn2(v3, v7, v8)
+    v10 = iconst.i32 1
+    v11 = call fn3(v3, v7, v0, v10, v10, v10)
+    store v11, v0+52
+    call fn4(v0)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v0+40
+    v3 = load.i64 v0+56
+    v4 = load.i64 v0+64
+    v5 = iconst.i64 0
+    jump block1(v5)
+
+block1(v6: i64):
+    v7 = icmp ult v6, v4
+    brif v7, block2, block3
+
+block2:
+    v8 = iadd v1, v6
+    v9 = load.f32 v8
+    v10 = iadd v2, v6
+    v11 = load.f32 v10
+    v12 = fadd v9, v11
+    v13 = iadd v3, v6
+    store v12, v13
+    v14 = iadd_imm v6, 4
+    jump block1(v14)
+
+block3:
+    return
+}"#;
+
+    let mut memory = vec![0xABu8; 6144];
+    memory[..64].fill(0);
+    memory[shader_off..shader_off + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+    memory[shader_off + wgsl.len()] = 0;
+    let len = (n * 4) as i32;
+    let regions = [(a_off, 1i32), (b_off, 1), (result_off, 0)];
+    for (i, (off, read_only)) in regions.into_iter().enumerate() {
+        let entry = table_off + i * 16;
+        memory[entry..entry + 8].copy_from_slice(&(off as i64).to_le_bytes());
+        memory[entry + 8..entry + 12].copy_from_slice(&len.to_le_bytes());
+        memory[entry + 12..entry + 16].copy_from_slice(&read_only.to_le_bytes());
+    }
+    for i in 0..n {
+        memory[a_off + i * 4..a_off + i * 4 + 4].copy_from_slice(&(i as f32).to_le_bytes());
+        memory[b_off + i * 4..b_off + i * 4 + 4].copy_from_slice(&0.25f32.to_le_bytes());
+    }
+    let expected: Vec<u8> = (0..n)
+        .flat_map(|i| (i as f32 + 0.25).to_le_bytes())
+        .collect();
+
+    for force_cpu in [1u32, 0] {
+        let mut memory = memory.clone();
+        memory[mode_off..mode_off + 4].copy_from_slice(&force_cpu.to_le_bytes());
+        let before = memory.clone();
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        base.execute(&cranelift_algorithm(0), &[]).unwrap();
+        let after = base.memory_handle().read(0, 6144).unwrap();
+        assert_eq!(&after[52..56], &0i32.to_le_bytes(), "force_cpu={force_cpu}");
+        let result_end = result_off + n * 4;
+        assert_eq!(&after[result_off..result_end], &expected[..], "force_cpu={force_cpu}");
+        assert_eq!(after[64..result_off], before[64..result_off]);
+        assert_eq!(after[result_end..], before[result_end..]);
+    }
+}
+
+#[test]
+#[cfg(feature = "gpu")]
 fn test_gpu_upload_ptr_download_ptr_vecadd() {
     // Tests cl_gpu_upload_ptr and cl_gpu_download_ptr via execute_into:
     // uploads A+B from caller's data pointer, computes C[i]=A[i]+B[i] on GPU,
@@ -3550,6 +4185,8 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3566,6 +4203,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     base.execute_into(&alg, &payload, &mut out).unwrap();
@@ -3584,79 +4223,419 @@ block0(v0: i64):
 }
 
 #[test]
-fn test_gpu_download_ptr_with_offset() {
-    // Tests cl_gpu_download_ptr with a non-zero buf_offset.
-    // Allocates a buffer with [A: 64 floats][B: 64 floats], uploads both,
-    // then downloads only the B portion (offset = 64*4) to the out pointer.
-    let n: usize = 64;
-
-    // Shader does nothing — we just want to test upload + offset download
+#[cfg(feature = "gpu")]
+fn test_gpu_invalid_shader_fails_before_running() {
+    // Both functions store 1 at out[0], create a pipeline from the WGSL at
+    // 0x100 and store the pipeline id and status word at out[8..24]. fn 0
+    // passes a constant shader address, fn 1 one computed from the offset
+    // stored at 0x80, which only the pipeline call itself can check.
     let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
                 @compute @workgroup_size(64)\n\
                 fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                \x20   data[gid.x] = dta[gid.x] * 2.0;\n\
                 }\n";
-
-    let shader_off: usize = 0x0100;
-    let bind_off: usize = 0x1100;
-    let mem_size: usize = 0x1200;
-
-    let clif_ir = format!(
-        r#"function u0:0(i64) system_v {{
-block0(v0: i64):
-    return
-}}
-
-function u0:1(i64) system_v {{
+    let function = |n: u32, shader: &str| {
+        format!(
+            r#"function u0:{n}(i64) system_v {{
     sig0 = (i64) system_v
     sig1 = (i64, i64) -> i32 system_v
-    sig2 = (i64, i32, i64, i64) -> i32 system_v
-    sig3 = (i64, i32, i64, i64, i64) -> i32 system_v
-
+    sig2 = (i64, i64, i64, i32) -> i32 system_v
+    sig3 = () -> i64 system_v
     fn0 = %cl_gpu_init sig0
     fn1 = %cl_gpu_create_buffer sig1
-    fn2 = %cl_gpu_upload_ptr sig2
-    fn3 = %cl_gpu_download_ptr sig3
+    fn2 = %cl_gpu_create_pipeline sig2
+    fn3 = %cl_last_status sig3
     fn4 = %cl_gpu_cleanup sig0
 
 block0(v0: i64):
-    v1 = load.i64 notrap aligned v0+0x08
-    v2 = load.i64 notrap aligned v0+0x10
-    v3 = load.i64 notrap aligned v0+0x18
-    v90 = iadd_imm v0, 0
-    call fn0(v90)
-
-    v91 = load.i64 notrap aligned v0+0
-    ; create buffer for full data (2*64*4 = 512 bytes)
-    v4 = call fn1(v91, v2)
-    ; upload all data from payload
-    v5 = call fn2(v91, v4, v1, v2)
-    ; download only second half: buf_offset = 256, size = 256, to out_ptr
-    v6 = iconst.i64 {half}
-    v7 = call fn3(v91, v4, v6, v3, v6)
-    call fn4(v90)
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 1
+    store v2, v1
+    call fn0(v0)
+    v3 = load.i64 v0
+    v4 = iconst.i64 256
+    v5 = call fn1(v3, v4)
+{shader}
+    v7 = iadd_imm v0, 0x400
+    v8 = iconst.i32 1
+    v9 = call fn2(v3, v6, v7, v8)
+    v10 = sextend.i64 v9
+    store v10, v1+8
+    v11 = call fn3()
+    store v11, v1+16
+    call fn4(v0)
     return
-}}"#,
-        half = n * 4,
+}}"#
+        )
+    };
+    let clif_ir = function(0, "    v6 = iadd_imm v0, 0x100")
+        + "\n\n"
+        + &function(1, "    v20 = load.i64 v0+0x80\n    v6 = iadd v0, v20");
+    let setup = |wgsl: &str| {
+        let mut memory = vec![0u8; 0x500];
+        memory[0x80..0x88].copy_from_slice(&0x100u64.to_le_bytes());
+        memory[0x100..0x100 + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+        cranelift_config(memory, clif_ir.clone())
+    };
+    let word = |out: &[u8], i: usize| i64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+
+    let artifact = base::Artifact::new(setup(wgsl), cranelift_algorithm(0));
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert!(
+        matches!(
+            &issues[..],
+            [base::ValidationIssue::ShaderInvalid {
+                function: 0,
+                offset: 0x100,
+                line: 4,
+                column: 19,
+                ..
+            }]
+        ),
+        "{issues:?}"
     );
 
-    let mut memory = vec![0u8; mem_size];
-    let shader_bytes = wgsl.as_bytes();
-    memory[shader_off..shader_off + shader_bytes.len()].copy_from_slice(shader_bytes);
-    memory[shader_off + shader_bytes.len()] = 0;
-    memory[bind_off..bind_off + 8].copy_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+    let mut base = Base::new(setup(wgsl)).unwrap();
+    let mut out = [0u8; 24];
+    let err = base
+        .execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap_err();
+    let msg = err.to_string();
+    assert!(matches!(err, base::Error::GpuInit(_)), "{msg}");
+    assert!(msg.contains("line 4, column 19"), "{msg}");
+    assert_eq!(word(&out, 0), 0, "fn 0 never ran");
+
+    base.execute_into(&cranelift_algorithm(1), &[], &mut out)
+        .unwrap();
+    assert_eq!((word(&out, 0), word(&out, 1)), (1, -1));
+    let status = base_types::status::status(word(&out, 2) as u64);
+    assert_eq!(status, base_types::status::INVALID_ARGUMENT);
+
+    let fixed = wgsl.replace("dta", "data");
+    let artifact = base::Artifact::new(setup(&fixed), cranelift_algorithm(0));
+    assert_eq!(base::validate_artifact(&artifact).unwrap(), []);
+    let mut base = Base::new(setup(&fixed)).unwrap();
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!((word(&out, 0), word(&out, 1)), (1, 0));
+}
 
-    let config = Setup {
-        cranelift_ir: clif_ir,
-        memory_size: mem_size,
-        io_offsets: compact_io_offsets(),
-        initial_memory: memory,
-    };
-    let mut base = Base::new(config).unwrap();
+#[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_invalid_shader_in_callee_fails_before_running() {
+    // fn 0 stores 1 at out[0] and calls fn 1, which creates a pipeline from
+    // the WGSL at 0x100 and stores the pipeline id at out[8].
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                \x20   data[gid.x] = dta[gid.x] * 2.0;\n\
+                }\n";
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = colocated u0:1 sig0
 
-    // Payload: [A: 1.0..64.0][B: 101.0..164.0]
-    let mut payload = vec![0u8; n * 4 * 2];
-    for i in 0..n {
-        let a_val = (i + 1) as f32;
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 1
+    store v2, v1
+    call fn0(v0)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i64, i64, i32) -> i32 system_v
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_create_pipeline sig2
+    fn3 = %cl_gpu_cleanup sig0
+
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    call fn0(v0)
+    v3 = load.i64 v0
+    v4 = iconst.i64 256
+    v5 = call fn1(v3, v4)
+    v6 = iadd_imm v0, 0x100
+    v7 = iadd_imm v0, 0x400
+    v8 = iconst.i32 1
+    v9 = call fn2(v3, v6, v7, v8)
+    v10 = sextend.i64 v9
+    store v10, v1+8
+    call fn3(v0)
+    return
+}"#;
+    let setup = |wgsl: &str| {
+        let mut memory = vec![0u8; 0x500];
+        memory[0x100..0x100 + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+        cranelift_config(memory, clif_ir.to_string())
+    };
+    let word = |out: &[u8], i: usize| i64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+
+    let mut base = Base::new(setup(wgsl)).unwrap();
+    let mut out = [0u8; 16];
+    let err = base
+        .execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap_err();
+    let msg = err.to_string();
+    assert!(matches!(err, base::Error::GpuInit(_)), "{msg}");
+    assert!(msg.contains("function 1: "), "{msg}");
+    assert!(msg.contains("line 4, column 19"), "{msg}");
+    assert_eq!(word(&out, 0), 0, "fn 0 never ran");
+
+    let fixed = wgsl.replace("dta", "data");
+    let mut base = Base::new(setup(&fixed)).unwrap();
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!((word(&out, 0), word(&out, 1)), (1, 0));
+}
+
+#[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_shader_check_sees_symbols() {
+    // Stores 1 at out[0], then creates a pipeline from the WGSL at 0x100 and
+    // stores its id at out[8]. The shader at 0x100 comes from a symbol.
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                \x20   data[gid.x] = data[gid.x] * 2.0;\n\
+                }\n";
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i64, i64, i32) -> i32 system_v
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_create_pipeline sig2
+    fn3 = %cl_gpu_cleanup sig0
+
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 1
+    store v2, v1
+    call fn0(v0)
+    v3 = load.i64 v0
+    v4 = iconst.i64 256
+    v5 = call fn1(v3, v4)
+    v6 = iadd_imm v0, 0x100
+    v7 = iadd_imm v0, 0x400
+    v8 = iconst.i32 1
+    v9 = call fn2(v3, v6, v7, v8)
+    v10 = sextend.i64 v9
+    store v10, v1+8
+    call fn3(v0)
+    return
+}"#;
+    let setup = |placeholder: &str| {
+        let mut memory = vec![0u8; 0x500];
+        memory[0x100..0x100 + placeholder.len()].copy_from_slice(placeholder.as_bytes());
+        cranelift_config(memory, clif_ir.to_string())
+    };
+    let algorithm = |shader: &str| {
+        let mut alg = cranelift_algorithm(0);
+        alg.symbols.push(base_types::Symbol {
+            name: "shader".into(),
+            offset: 0x100,
+            len: 0x300,
+            value: shader.as_bytes().to_vec(),
+            writable: false,
+        });
+        alg
+    };
+    let word = |out: &[u8], i: usize| i64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+
+    // A stale placeholder the symbol replaces does not fail the execution.
+    let mut base = Base::new(setup("not wgsl")).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&algorithm(wgsl), &[], &mut out).unwrap();
+    assert_eq!((word(&out, 0), word(&out, 1)), (1, 0));
+
+    // An invalid shader supplied by the symbol fails before running.
+    let mut base = Base::new(setup(wgsl)).unwrap();
+    let mut out = [0u8; 16];
+    let invalid = algorithm(&wgsl.replace("data[gid.x] *", "dta[gid.x] *"));
+    let err = base.execute_into(&invalid, &[], &mut out).unwrap_err();
+    let msg = err.to_string();
+    assert!(matches!(err, base::Error::GpuInit(_)), "{msg}");
+    assert!(msg.contains("line 4, column 19"), "{msg}");
+    assert_eq!(word(&out, 0), 0, "fn 0 never ran");
+}
+
+#[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_download_async_overlaps_dispatches() {
+    // Doubles the payload on the GPU, queues an async readback to out[0..256],
+    // doubles again while that copy is in flight, queues a second readback
+    // to out[256..512], then waits and writes both completion flags
+    // (kept at 0x40 and 0x48) to out[512..528].
+    let n: usize = 64;
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                    data[gid.x] = data[gid.x] * 2.0;\n\
+                }\n";
+
+    let shader_off: usize = 0x0100;
+    let bind_off: usize = 0x1100;
+    let mem_size: usize = 0x1200;
+
+    let clif_ir = format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i64) -> i32 system_v
+    sig3 = (i64, i64, i64, i32) -> i32 system_v
+    sig4 = (i64, i32, i32, i32, i32) -> i32 system_v
+    sig5 = (i64, i32, i64, i64, i64) -> i32 system_v
+    sig6 = (i64) -> i32 system_v
+
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_upload_ptr sig2
+    fn3 = %cl_gpu_create_pipeline sig3
+    fn4 = %cl_gpu_dispatch sig4
+    fn5 = %cl_gpu_download_async sig5
+    fn6 = %cl_gpu_wait sig6
+    fn7 = %cl_gpu_cleanup sig0
+
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+0x08
+    v2 = load.i64 notrap aligned v0+0x10
+    v3 = load.i64 notrap aligned v0+0x18
+    call fn0(v0)
+    v4 = load.i64 notrap aligned v0
+    v5 = call fn1(v4, v2)
+    v6 = call fn2(v4, v5, v1, v2)
+    v7 = iadd_imm v0, {shader_off}
+    v8 = iadd_imm v0, {bind_off}
+    v9 = iconst.i32 1
+    v10 = call fn3(v4, v7, v8, v9)
+    v11 = call fn4(v4, v10, v9, v9, v9)
+    v12 = iadd_imm v0, 0x40
+    v13 = call fn5(v4, v5, v3, v2, v12)
+    v14 = call fn4(v4, v10, v9, v9, v9)
+    v15 = iadd v3, v2
+    v16 = iadd_imm v0, 0x48
+    v17 = call fn5(v4, v5, v15, v2, v16)
+    v18 = call fn6(v4)
+    v19 = load.i64 notrap aligned v0+0x40
+    v20 = load.i64 notrap aligned v0+0x48
+    v21 = iadd v15, v2
+    store.i64 v19, v21
+    store.i64 v20, v21+8
+    call fn7(v0)
+    return
+}}"#,
+    );
+
+    let mut memory = vec![0u8; mem_size];
+    memory[shader_off..shader_off + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: mem_size,
+        io_offsets: compact_io_offsets(),
+        initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+
+    let payload: Vec<u8> = (0..n).flat_map(|i| (i as f32).to_le_bytes()).collect();
+    let mut out = vec![0u8; n * 8 + 16];
+    base.execute_into(&Algorithm::new(0), &payload, &mut out)
+        .unwrap();
+
+    let floats: Vec<f32> = out[..n * 8]
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    for i in 0..n {
+        assert_eq!(floats[i], i as f32 * 2.0, "first readback, element {i}");
+        assert_eq!(floats[n + i], i as f32 * 4.0, "second readback, {i}");
+    }
+    let flags = &out[n * 8..];
+    assert_eq!(i64::from_le_bytes(flags[..8].try_into().unwrap()), 1);
+    assert_eq!(i64::from_le_bytes(flags[8..].try_into().unwrap()), 1);
+}
+
+#[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_download_ptr_with_offset() {
+    // Tests cl_gpu_download_ptr with a non-zero buf_offset.
+    // Allocates a buffer with [A: 64 floats][B: 64 floats], uploads both,
+    // then downloads only the B portion (offset = 64*4) to the out pointer.
+    let n: usize = 64;
+
+    // Shader does nothing — we just want to test upload + offset download
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<f32>;\n\
+                @compute @workgroup_size(64)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                }\n";
+
+    let shader_off: usize = 0x0100;
+    let bind_off: usize = 0x1100;
+    let mem_size: usize = 0x1200;
+
+    let clif_ir = format!(
+        r#"function u0:0(i64) system_v {{
+block0(v0: i64):
+    return
+}}
+
+function u0:1(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i64) -> i32 system_v
+    sig3 = (i64, i32, i64, i64, i64) -> i32 system_v
+
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_upload_ptr sig2
+    fn3 = %cl_gpu_download_ptr sig3
+    fn4 = %cl_gpu_cleanup sig0
+
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+0x08
+    v2 = load.i64 notrap aligned v0+0x10
+    v3 = load.i64 notrap aligned v0+0x18
+    v90 = iadd_imm v0, 0
+    call fn0(v90)
+
+    v91 = load.i64 notrap aligned v0+0
+    ; create buffer for full data (2*64*4 = 512 bytes)
+    v4 = call fn1(v91, v2)
+    ; upload all data from payload
+    v5 = call fn2(v91, v4, v1, v2)
+    ; download only second half: buf_offset = 256, size = 256, to out_ptr
+    v6 = iconst.i64 {half}
+    v7 = call fn3(v91, v4, v6, v3, v6)
+    call fn4(v90)
+    return
+}}"#,
+        half = n * 4,
+    );
+
+    let mut memory = vec![0u8; mem_size];
+    let shader_bytes = wgsl.as_bytes();
+    memory[shader_off..shader_off + shader_bytes.len()].copy_from_slice(shader_bytes);
+    memory[shader_off + shader_bytes.len()] = 0;
+    memory[bind_off..bind_off + 8].copy_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0]);
+
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: mem_size,
+        io_offsets: compact_io_offsets(),
+        initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+
+    // Payload: [A: 1.0..64.0][B: 101.0..164.0]
+    let mut payload = vec![0u8; n * 4 * 2];
+    for i in 0..n {
+        let a_val = (i + 1) as f32;
         let b_val = (i + 101) as f32;
         payload[i * 4..i * 4 + 4].copy_from_slice(&a_val.to_le_bytes());
         payload[n * 4 + i * 4..n * 4 + i * 4 + 4].copy_from_slice(&b_val.to_le_bytes());
@@ -3666,6 +4645,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     base.execute_into(&alg, &payload, &mut out).unwrap();
@@ -3685,6 +4666,86 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "gpu")]
+fn test_gpu_f16_typed_transfer_doubles() {
+    // Uploads 64 f32s from the payload as f16 (128 bytes on the GPU), doubles
+    // them in a shader that unpacks the halves two per u32, and downloads
+    // them back into out as f32.
+    let n: usize = 64;
+    let wgsl = "@group(0) @binding(0) var<storage, read_write> data: array<u32>;\n\
+                @compute @workgroup_size(32)\n\
+                fn main(@builtin(global_invocation_id) gid: vec3<u32>) {\n\
+                    data[gid.x] = pack2x16float(unpack2x16float(data[gid.x]) * 2.0);\n\
+                }\n";
+
+    let shader_off: usize = 0x0100;
+    let bind_off: usize = 0x1100;
+    let mem_size: usize = 0x1200;
+
+    let clif_ir = format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i64, i32) -> i32 system_v
+    sig3 = (i64, i64, i64, i32) -> i32 system_v
+    sig4 = (i64, i32, i32, i32, i32) -> i32 system_v
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_upload_typed sig2
+    fn3 = %cl_gpu_create_pipeline sig3
+    fn4 = %cl_gpu_dispatch sig4
+    fn5 = %cl_gpu_download_typed sig2
+    fn6 = %cl_gpu_cleanup sig0
+
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+0x08
+    v2 = load.i64 notrap aligned v0+0x18
+    call fn0(v0)
+    v3 = load.i64 notrap aligned v0+0
+    v4 = iconst.i64 {bytes}
+    v5 = call fn1(v3, v4)
+    v6 = iconst.i64 {n}
+    v7 = iconst.i32 1
+    v8 = call fn2(v3, v5, v1, v6, v7)
+    v9 = iadd_imm v0, {shader_off}
+    v10 = iadd_imm v0, {bind_off}
+    v11 = call fn3(v3, v9, v10, v7)
+    v12 = call fn4(v3, v11, v7, v7, v7)
+    v13 = call fn5(v3, v5, v2, v6, v7)
+    call fn6(v0)
+    return
+}}"#,
+        bytes = n * 2,
+    );
+
+    let mut memory = vec![0u8; mem_size];
+    memory[shader_off..shader_off + wgsl.len()].copy_from_slice(wgsl.as_bytes());
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: mem_size,
+        io_offsets: compact_io_offsets(),
+        initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+
+    let input: Vec<f32> = (0..n).map(|i| (i as f32 - 20.0) * 0.3721).collect();
+    let payload: Vec<u8> = input.iter().flat_map(|x| x.to_le_bytes()).collect();
+    let mut out = vec![0u8; n * 4];
+    base.execute_into(&Algorithm::new(0), &payload, &mut out)
+        .unwrap();
+
+    for (i, x) in input.iter().enumerate() {
+        let got = f32::from_le_bytes(out[i * 4..i * 4 + 4].try_into().unwrap());
+        // One f16 rounding on upload (2^-11 relative); doubling is exact.
+        let tol = (2.0 * x).abs() * 2f32.powi(-11) + 1e-6;
+        assert!((got - 2.0 * x).abs() <= tol, "element {i}: {got} vs {}", 2.0 * x);
+    }
+}
+
+#[test]
+#[cfg(feature = "cuda")]
 fn test_cuda_upload_ptr_download_ptr_vecadd() {
     // Tests cl_cuda_upload_ptr and cl_cuda_download_ptr with execute_into.
     // Uploads A+B from caller's data pointer via PTX kernel C[i]=A[i]+B[i],
@@ -3809,6 +4870,8 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
@@ -3825,6 +4888,8 @@ block0(v0: i64):
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     base.execute_into(&alg, &payload, &mut out).unwrap();
@@ -3843,6 +4908,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cuda_download_ptr_different_data() {
     // Tests cl_cuda_upload_ptr and cl_cuda_download_ptr with two different payloads.
     // First execute: uploads A=[1..64] + B=[100..100], expects C=[101..164].
@@ -3959,12 +5025,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     // First execute: A=[1..64], B=[100..100]
@@ -4015,6 +5085,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cublas_sgemm_strided_batched_reuse() {
     // Exercises the cl_cublas_sgemm_strided_batched FFI directly with a small
     // batched GEMV-shaped workload:
@@ -4116,12 +5187,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![0u8; mem_size],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let a1: [f32; 12] = [
@@ -4180,6 +5255,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cuda_upload_ptr_offset_reuse() {
     // Verifies cl_cuda_upload_ptr_offset can update a subrange of an existing
     // device buffer across repeated execute_into calls.
@@ -4238,12 +5314,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![0u8; mem_size],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let payload1: [f32; 4] = [1.0, 2.0, 3.0, 4.0];
@@ -4284,6 +5364,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cuda_launch_named_reuses_named_kernel() {
     // Verifies cl_cuda_launch_named can launch a non-"main" entry point and
     // that repeated named launches in the same CUDA context remain correct.
@@ -4380,12 +5461,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: memory,
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let payload1: Vec<f32> = (1..=n).map(|x| x as f32).collect();
@@ -4426,6 +5511,7 @@ block0(v0: i64):
 }
 
 #[test]
+#[cfg(feature = "cuda")]
 fn test_cublas_sgemv_reuse() {
     // Directly exercises cl_cublas_sgemv with a small row-major 2x3 matrix.
     let rows: usize = 2;
@@ -4504,12 +5590,16 @@ block0(v0: i64):
         memory_size: mem_size,
         io_offsets: compact_io_offsets(),
         initial_memory: vec![0u8; mem_size],
+        arena_offset: 0,
+        arena_size: 0,
     };
     let mut base = Base::new(config).unwrap();
 
     let alg = Algorithm {
         fn_idx: 1,
         output: vec![],
+        symbols: vec![],
+        labels: vec![],
     };
 
     let a1: [f32; 6] = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
@@ -4559,3 +5649,4776 @@ block0(v0: i64):
     }
 }
 
+
+#[test]
+fn test_clif_simd_compare_select() {
+    // Vector compares produce an all-ones / all-zeros mask per lane, and
+    // bitselect blends two vectors under that mask. NaN lanes compare false
+    // for every ordered predicate, so the select falls through to `b` there.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.f32x4 notrap aligned v0+256
+    v3 = load.f32x4 notrap aligned v0+272
+    v4 = fcmp lt v2, v3
+    v5 = fcmp eq v2, v3
+    v6 = fcmp gt v2, v3
+    store.i32x4 v4, v1
+    store.i32x4 v5, v1+16
+    store.i32x4 v6, v1+32
+    v7 = bitcast.f32x4 v4
+    v8 = bitselect v7, v2, v3
+    store.f32x4 v8, v1+48
+    v9 = load.i32x4 notrap aligned v0+288
+    v10 = load.i32x4 notrap aligned v0+304
+    v11 = icmp slt v9, v10
+    v12 = bitselect v11, v9, v10
+    store.i32x4 v11, v1+64
+    store.i32x4 v12, v1+80
+    return
+}"#
+    .to_string();
+
+    let mut initial = vec![0u8; 4096];
+    let a = [1.0f32, f32::NAN, 3.0, -1.0];
+    let b = [2.0f32, 2.0, f32::NAN, -1.0];
+    let ia = [-5i32, 7, i32::MIN, 0];
+    let ib = [3i32, 7, i32::MAX, -1];
+    for i in 0..4 {
+        let o = i * 4;
+        initial[256 + o..260 + o].copy_from_slice(&a[i].to_le_bytes());
+        initial[272 + o..276 + o].copy_from_slice(&b[i].to_le_bytes());
+        initial[288 + o..292 + o].copy_from_slice(&ia[i].to_le_bytes());
+        initial[304 + o..308 + o].copy_from_slice(&ib[i].to_le_bytes());
+    }
+
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: 4096,
+        io_offsets: compact_io_offsets(),
+        initial_memory: initial,
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+    let mut out = vec![0u8; 96];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+
+    let lane_u32 = |off: usize, i: usize| {
+        u32::from_le_bytes(out[off + i * 4..off + i * 4 + 4].try_into().unwrap())
+    };
+    let lane_f32 = |off: usize, i: usize| {
+        f32::from_le_bytes(out[off + i * 4..off + i * 4 + 4].try_into().unwrap())
+    };
+    let lane_i32 = |off: usize, i: usize| {
+        i32::from_le_bytes(out[off + i * 4..off + i * 4 + 4].try_into().unwrap())
+    };
+
+    let lt = [u32::MAX, 0, 0, 0];
+    let eq = [0, 0, 0, u32::MAX];
+    for i in 0..4 {
+        assert_eq!(lane_u32(0, i), lt[i], "lt lane {i}");
+        assert_eq!(lane_u32(16, i), eq[i], "eq lane {i}");
+        assert_eq!(lane_u32(32, i), 0, "gt lane {i}");
+    }
+
+    // select(a < b, a, b): lane 0 takes a, NaN lanes take b.
+    assert_eq!(lane_f32(48, 0), 1.0);
+    assert_eq!(lane_f32(48, 1), 2.0);
+    assert!(lane_f32(48, 2).is_nan());
+    assert_eq!(lane_f32(48, 3), -1.0);
+
+    let slt = [u32::MAX, 0, u32::MAX, 0];
+    let min = [-5i32, 7, i32::MIN, -1];
+    for i in 0..4 {
+        assert_eq!(lane_u32(64, i), slt[i], "slt lane {i}");
+        assert_eq!(lane_i32(80, i), min[i], "select lane {i}");
+    }
+}
+
+
+#[test]
+fn test_clif_f64x2_fft_butterflies_match_scalar() {
+    // Butterfly kernel over m complex f64 pairs, one f64x2 (re, im) per
+    // element: t = w * b, a' = a + t, b' = a - t. Payload is
+    // [m: i64][a: m x 16][b: m x 16][w: m x 16]; out is [a'][b'].
+    // Every stage of a 256-point radix-2 FFT runs through it and the result
+    // is compared against a scalar f64 FFT.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v0+40
+    v3 = load.i64 v1
+    v4 = ishl_imm v3, 4
+    v5 = iadd_imm v1, 8
+    v6 = iadd v5, v4
+    v7 = iadd v6, v4
+    v8 = iadd v2, v4
+    v9 = f64const -0x1.0p0
+    v10 = f64const 0x1.0p0
+    v11 = splat.f64x2 v9
+    v12 = insertlane v11, v10, 1
+    v13 = iconst.i64 0
+    v14 = icmp_imm eq v3, 0
+    brif v14, block2, block1(v13)
+block1(v15: i64):
+    v16 = iadd v5, v15
+    v17 = load.f64x2 v16
+    v18 = iadd v6, v15
+    v19 = load.f64x2 v18
+    v20 = iadd v7, v15
+    v21 = load.f64x2 v20
+    v22 = extractlane v21, 0
+    v23 = extractlane v21, 1
+    v24 = splat.f64x2 v22
+    v25 = splat.f64x2 v23
+    v26 = extractlane v19, 0
+    v27 = extractlane v19, 1
+    v28 = insertlane v19, v27, 0
+    v29 = insertlane v28, v26, 1
+    v30 = fmul v24, v19
+    v31 = fmul v25, v29
+    v32 = fmul v31, v12
+    v33 = fadd v30, v32
+    v34 = fadd v17, v33
+    v35 = fsub v17, v33
+    v36 = iadd v2, v15
+    store.f64x2 v34, v36
+    v37 = iadd v8, v15
+    store.f64x2 v35, v37
+    v38 = iadd_imm v15, 16
+    v39 = icmp ult v38, v4
+    brif v39, block1(v38), block2
+block2:
+    return
+}"#;
+    let mut base = Base::new(Setup::new(clif_ir, 1024)).unwrap();
+
+    type C = (f64, f64);
+    let cmul = |w: C, b: C| (w.0 * b.0 - w.1 * b.1, w.0 * b.1 + w.1 * b.0);
+    let n = 256usize;
+    let bits = n.trailing_zeros();
+    let input: Vec<C> = (0..n)
+        .map(|i| {
+            let x = i as f64;
+            (
+                (x * 0.37).sin() + 0.25 * x.sqrt(),
+                (x * 1.3).cos() - 0.001 * x,
+            )
+        })
+        .collect();
+    let mut scalar: Vec<C> = (0..n)
+        .map(|i| input[i.reverse_bits() >> (usize::BITS - bits)])
+        .collect();
+    let mut simd = scalar.clone();
+
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let twiddle = |j: usize| {
+            let angle = -2.0 * std::f64::consts::PI * j as f64 / len as f64;
+            (angle.cos(), angle.sin())
+        };
+        for start in (0..n).step_by(len) {
+            for j in 0..half {
+                let (a, b) = (scalar[start + j], scalar[start + j + half]);
+                let t = cmul(twiddle(j), b);
+                scalar[start + j] = (a.0 + t.0, a.1 + t.1);
+                scalar[start + j + half] = (a.0 - t.0, a.1 - t.1);
+            }
+        }
+
+        // Gather the whole stage into one kernel call.
+        let m = n / 2;
+        let mut payload = (m as i64).to_le_bytes().to_vec();
+        let mut push = |c: C| {
+            payload.extend_from_slice(&c.0.to_le_bytes());
+            payload.extend_from_slice(&c.1.to_le_bytes());
+        };
+        let pairs: Vec<(usize, usize)> = (0..n)
+            .step_by(len)
+            .flat_map(|start| (0..half).map(move |j| (start + j, j)))
+            .collect();
+        pairs.iter().for_each(|&(i, _)| push(simd[i]));
+        pairs.iter().for_each(|&(i, _)| push(simd[i + half]));
+        pairs.iter().for_each(|&(_, j)| push(twiddle(j)));
+        let mut out = vec![0u8; m * 32];
+        base.execute_into(&Algorithm::new(0), &payload, &mut out)
+            .unwrap();
+        let lane = |k: usize| f64::from_le_bytes(out[k * 8..k * 8 + 8].try_into().unwrap());
+        for (p, &(i, _)) in pairs.iter().enumerate() {
+            simd[i] = (lane(2 * p), lane(2 * p + 1));
+            simd[i + half] = (lane(2 * (m + p)), lane(2 * (m + p) + 1));
+        }
+        len *= 2;
+    }
+
+    for (k, (s, v)) in scalar.iter().zip(&simd).enumerate() {
+        assert!(
+            (s.0 - v.0).abs() < 1e-12 && (s.1 - v.1).abs() < 1e-12,
+            "bin {k}: scalar {s:?}, f64x2 {v:?}"
+        );
+    }
+}
+
+#[test]
+fn test_clif_mixed_simd_widths_rejected() {
+    // An f64x2 value must not be silently reinterpreted as f32x4 lanes: the
+    // verifier rejects the mixed-width fadd when the function is compiled.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.f64x2 v0+256
+    v2 = load.f32x4 v0+272
+    v3 = fadd v1, v2
+    store.f64x2 v3, v0+288
+    return
+}"#;
+    let err = Base::new(Setup::new(clif_ir, 1024)).err().unwrap();
+    assert!(err.to_string().contains("expected f64x2"), "{err}");
+}
+
+#[test]
+fn test_clif_ffi_mem_smoke() {
+    // Runtime smoke for the memory primitives, pointed at the caller's out buffer.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_fill sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 0
+    v3 = iconst.i64 64
+    v4 = iconst.i64 0x11223344
+    v5 = iconst.i64 4
+    v6 = call fn0(v1, v2, v3, v4, v5)
+    return
+}"#
+    .to_string();
+
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: 4096,
+        io_offsets: compact_io_offsets(),
+        initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+    let mut out = vec![0u8; 64];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    for chunk in out.chunks_exact(4) {
+        assert_eq!(u32::from_le_bytes(chunk.try_into().unwrap()), 0x11223344);
+    }
+}
+
+#[test]
+fn test_clif_atomic_rmw_widths_across_threads() {
+    // Two spawned workers each run 10_000 iterations of a 4-byte fetch-add on
+    // one counter and an 8-byte fetch-sub on another. After joining, an
+    // exchange swaps in a sentinel and reports the previous counter value.
+    // Memory layout:
+    //   16-23:   thread context pointer slot
+    //   256-259: i32 counter (fetch-add)
+    //   264-271: i64 counter (fetch-sub, starts at 1_000_000)
+    //   272-279: i64 exchange target
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_spawn sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_join sig2
+    sig3 = (i64) system_v
+    fn3 = %cl_thread_cleanup sig3
+block0(v0: i64):
+    v1 = iadd_imm v0, 16
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+16
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3, v0)
+    v5 = call fn1(v2, v3, v0)
+    v6 = call fn2(v2, v4)
+    v7 = call fn2(v2, v5)
+    call fn3(v1)
+    v8 = iadd_imm v0, 272
+    v9 = load.i64 notrap aligned v0+264
+    v10 = atomic_rmw.i64 little xchg v8, v9
+    v11 = iconst.i64 -1
+    v12 = atomic_rmw.i64 little xchg v8, v11
+    v13 = load.i64 v0+24
+    v14 = load.i32 notrap aligned v0+256
+    store.i32 v14, v13
+    store.i64 v9, v13+8
+    store.i64 v10, v13+16
+    store.i64 v12, v13+24
+    v15 = load.i64 notrap aligned v0+272
+    store.i64 v15, v13+32
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iadd_imm v0, 256
+    v2 = iadd_imm v0, 264
+    v3 = iconst.i32 1
+    v4 = iconst.i64 3
+    v5 = iconst.i64 0
+    jump block1(v5)
+
+block1(v6: i64):
+    v7 = atomic_rmw.i32 little add v1, v3
+    v8 = atomic_rmw.i64 little sub v2, v4
+    v9 = iadd_imm v6, 1
+    v10 = icmp_imm ult v9, 10000
+    brif v10, block1(v9), block2
+
+block2:
+    return
+}"#
+    .to_string();
+
+    let mut initial = vec![0u8; 512];
+    initial[264..272].copy_from_slice(&1_000_000i64.to_le_bytes());
+    initial[272..280].copy_from_slice(&7i64.to_le_bytes());
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: 4096,
+        io_offsets: compact_io_offsets(),
+        initial_memory: initial,
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+    let mut out = vec![0u8; 40];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+
+    let i64_at = |off: usize| i64::from_le_bytes(out[off..off + 8].try_into().unwrap());
+    assert_eq!(u32::from_le_bytes(out[0..4].try_into().unwrap()), 20_000);
+    assert_eq!(i64_at(8), 1_000_000 - 2 * 10_000 * 3);
+    assert_eq!(i64_at(16), 7, "first exchange returns the initial value");
+    assert_eq!(i64_at(24), 940_000, "second exchange returns the swapped-in value");
+    assert_eq!(i64_at(32), -1);
+}
+
+#[test]
+fn test_clif_execute_traced_records_branch_outcomes() {
+    // A countdown loop traces each iteration (tag 1) with the counter and the
+    // taken/not-taken outcome of its conditional branch, then the exit (tag 2).
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_trace sig0
+block0(v0: i64):
+    v1 = iconst.i64 3
+    v2 = iconst.i64 0
+    jump block1(v1)
+
+block1(v3: i64):
+    v4 = iadd_imm v3, -1
+    v5 = icmp_imm ne v4, 0
+    v6 = uextend.i64 v5
+    v7 = iconst.i64 1
+    v8 = call fn0(v7, v3, v6, v2, v2)
+    brif v5, block1(v4), block2
+
+block2:
+    v9 = iconst.i64 2
+    v10 = call fn0(v9, v4, v2, v2, v2)
+    return
+}"#
+    .to_string();
+
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: 256,
+        io_offsets: compact_io_offsets(),
+        initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+    let (_, events) = base
+        .execute_traced(&cranelift_algorithm(0), &[], &mut [])
+        .unwrap();
+    let steps: Vec<(i64, [i64; 4])> = events.iter().map(|e| (e.tag, e.args)).collect();
+    assert_eq!(
+        steps,
+        [
+            (1, [3, 1, 0, 0]),
+            (1, [2, 1, 0, 0]),
+            (1, [1, 0, 0, 0]),
+            (2, [0, 0, 0, 0]),
+        ]
+    );
+    assert!(events.windows(2).all(|w| w[0].elapsed_ns <= w[1].elapsed_ns));
+}
+
+#[test]
+fn test_clif_execute_with_stats_counts_workers() {
+    // Spawns two workers, joins them, then runs one function inline.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_spawn sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_join sig2
+    sig3 = (i64) system_v
+    fn3 = %cl_thread_cleanup sig3
+    sig4 = (i64, i64, i64) -> i64 system_v
+    fn4 = %cl_thread_call sig4
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = iadd_imm v0, 128
+    v5 = call fn1(v2, v3, v4)
+    v6 = iadd_imm v0, 136
+    v7 = call fn1(v2, v3, v6)
+    v8 = call fn2(v2, v5)
+    v9 = call fn2(v2, v7)
+    v10 = iadd_imm v0, 144
+    v11 = call fn4(v2, v3, v10)
+    call fn3(v1)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 42
+    store.i64 v1, v0
+    return
+}"#
+    .to_string();
+
+    let config = Setup {
+        cranelift_ir: clif_ir,
+        memory_size: 256,
+        io_offsets: compact_io_offsets(),
+        initial_memory: vec![],
+        arena_offset: 0,
+        arena_size: 0,
+    };
+    let mut base = Base::new(config).unwrap();
+    let (_, stats) = base
+        .execute_with_stats(&cranelift_algorithm(0), &[], &mut [])
+        .unwrap();
+    assert_eq!(stats.threads_spawned, 2);
+    assert_eq!(stats.thread_calls, 1);
+    assert!(stats.elapsed >= stats.join_wait);
+
+    // Stats collection is scoped to the call.
+    let (_, stats) = base
+        .execute_with_stats(&cranelift_algorithm(0), &[], &mut [])
+        .unwrap();
+    assert_eq!(stats.threads_spawned, 2);
+}
+
+fn validation_artifact(cranelift_ir: &str, main: Algorithm) -> base::Artifact {
+    base::Artifact {
+        setup: cranelift_config(vec![0u8; 256], cranelift_ir.to_string()),
+        main,
+        extras: Default::default(),
+    }
+}
+
+#[test]
+fn validate_artifact_accepts_runnable_artifact() {
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64) -> i64 system_v
+    fn0 = %cl_stdout_write sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    v2 = call fn0(v0, v1, v1)
+    return
+}"#;
+    let artifact = validation_artifact(clif_ir, cranelift_algorithm(0));
+    assert_eq!(base::validate_artifact(&artifact).unwrap(), vec![]);
+}
+
+#[test]
+fn validate_artifact_reports_every_issue() {
+    // Unknown import in fn 0, a verifier failure in fn 1 (returns a value
+    // from a void signature), an extra pointing past the last function, and
+    // an output schema reading past the end of memory.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_does_not_exist sig0
+block0(v0: i64):
+    v1 = call fn0(v0)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    return v0
+}"#;
+    let main = Algorithm {
+        fn_idx: 0,
+        output: vec![OutputBatchSchema {
+            columns: vec![OutputColumn {
+                name: "x".to_string(),
+                dtype: OutputType::I64,
+                data_offset: 64,
+                len_offset: 0,
+            }],
+            row_count_offset: 252,
+        }],
+        symbols: vec![],
+        labels: vec![],
+    };
+    let mut artifact = validation_artifact(clif_ir, main);
+    artifact
+        .extras
+        .insert("late".to_string(), cranelift_algorithm(5));
+
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert_eq!(issues.len(), 4, "{issues:?}");
+    assert_eq!(
+        issues[0],
+        base::ValidationIssue::UnknownSymbol {
+            function: 0,
+            label: None,
+            name: "cl_does_not_exist".to_string()
+        }
+    );
+    assert!(matches!(issues[1], base::ValidationIssue::Verifier { function: 1, .. }));
+    assert_eq!(
+        issues[2],
+        base::ValidationIssue::OutputOutOfBounds {
+            algorithm: "main".to_string(),
+            offset: 252,
+            memory_size: 256
+        }
+    );
+    assert_eq!(
+        issues[3],
+        base::ValidationIssue::FnIndexOutOfRange {
+            algorithm: "late".to_string(),
+            fn_idx: 5,
+            available: 2
+        }
+    );
+}
+
+#[test]
+fn validate_artifact_reports_constant_operands_in_the_arena() {
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+200
+    store v1, v0+248
+    return
+}"#;
+    let mut artifact = validation_artifact(clif_ir, cranelift_algorithm(0));
+    assert!(base::validate_artifact(&artifact).unwrap().is_empty());
+    artifact.setup = artifact.setup.with_arena(192, 64);
+    let issues = base::validate_artifact(&artifact).unwrap();
+    let operands: Vec<_> = issues
+        .iter()
+        .map(|issue| match issue {
+            base::ValidationIssue::ArenaOperand { inst, .. } => inst.as_str(),
+            other => panic!("unexpected issue {other:?}"),
+        })
+        .collect();
+    assert_eq!(operands, ["v1 = load.i64 v0+200", "store.i64 v1, v0+248"]);
+
+    // The arena may end past `memory_size`: memory grows to cover it.
+    artifact.setup = artifact.setup.with_arena(1024, 64);
+    artifact.main.declare_scratch("tmp", 1000, 32).unwrap();
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert!(
+        matches!(
+            &issues[..],
+            [base::ValidationIssue::Symbol {
+                error: base::SymbolError::Overlap { other, .. },
+                ..
+            }] if other == "the arena"
+        ),
+        "{issues:?}"
+    );
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 1088);
+}
+
+#[test]
+fn validate_artifact_reports_function_labels() {
+    // fn 1 imports an unknown symbol; an extra labels it.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_does_not_exist sig0
+block0(v0: i64):
+    v1 = call fn0(v0)
+    return
+}"#;
+    let mut artifact = validation_artifact(clif_ir, cranelift_algorithm(0).labeled("entry"));
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert!(
+        matches!(
+            &issues[..],
+            [base::ValidationIssue::UnknownSymbol {
+                function: 1,
+                label: None,
+                ..
+            }]
+        ),
+        "{issues:?}"
+    );
+    artifact.extras.insert(
+        "checksum".to_string(),
+        cranelift_algorithm(1).labeled("write header checksum"),
+    );
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert_eq!(
+        issues,
+        [base::ValidationIssue::UnknownSymbol {
+            function: 1,
+            label: Some("write header checksum".to_string()),
+            name: "cl_does_not_exist".to_string()
+        }]
+    );
+}
+
+#[test]
+fn test_clif_compiled_out_feature_is_reported() {
+    // Each optional FFI family either links normally or, when its cargo
+    // feature is off, is named in both the validation issue and the
+    // compile error instead of failing as an unknown symbol.
+    let families = [
+        ("cl_gpu_poll", "gpu", cfg!(feature = "gpu")),
+        ("cl_cuda_sync", "cuda", cfg!(feature = "cuda")),
+        ("cl_lmdb_sync", "lmdb", cfg!(feature = "lmdb")),
+        ("cl_net_cleanup", "net", cfg!(feature = "net")),
+    ];
+    for (symbol, feature, enabled) in families {
+        let clif_ir = format!(
+            "function u0:0(i64) system_v {{
+    sig0 = (i64) -> i64 system_v
+    fn0 = %{symbol} sig0
+block0(v0: i64):
+    v1 = func_addr.i64 fn0
+    store.i64 v1, v0+64
+    return
+}}"
+        );
+        let artifact = validation_artifact(&clif_ir, cranelift_algorithm(0));
+        let issues = base::validate_artifact(&artifact).unwrap();
+        let result = Base::new(cranelift_config(vec![0u8; 256], clif_ir));
+        if enabled {
+            assert_eq!(issues, vec![], "{symbol}");
+            assert!(result.is_ok(), "{symbol}");
+            continue;
+        }
+        assert_eq!(
+            issues,
+            vec![base::ValidationIssue::FeatureDisabled {
+                function: 0,
+                label: None,
+                name: symbol.to_string(),
+                feature,
+            }]
+        );
+        match result {
+            Err(base::Error::Compile { fn_idx: 0, message }) => {
+                assert!(
+                    message.contains(symbol) && message.contains(feature),
+                    "{message}"
+                )
+            }
+            Err(other) => panic!("expected Compile error for {symbol}, got {other:?}"),
+            Ok(_) => panic!("expected Compile error for {symbol}"),
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn validate_artifact_checks_operand_bounds() {
+    // One out-of-range operand per class in entry fn 0 over 256 bytes: a
+    // store address, a cl_mem_copy offset, a cl_net_send pointer, and a
+    // cl_thread_spawn fn index. The store through out_ptr and everything in
+    // fn 1 (whose parameter is not the memory base) are data-dependent.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_copy sig0
+    sig1 = (i64, i64, i64, i64) -> i64 system_v
+    fn1 = %cl_net_send sig1
+    sig2 = (i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_spawn sig2
+block0(v0: i64):
+    v1 = iconst.i64 0
+    store.i64 v1, v0+300
+    v2 = iconst.i64 200
+    v3 = iconst.i64 100
+    v4 = call fn0(v0, v1, v2, v3, v1)
+    v5 = load.i64 v0+64
+    v6 = iadd_imm v0, 250
+    v7 = iconst.i64 16
+    v8 = call fn1(v5, v1, v6, v7)
+    v9 = iconst.i64 7
+    v10 = call fn2(v5, v9, v0)
+    v11 = load.i64 v0+24
+    store.i64 v1, v11
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 0
+    store.i64 v1, v0+4000
+    return
+}"#;
+    let artifact = validation_artifact(clif_ir, cranelift_algorithm(0));
+    let issues = base::validate_artifact(&artifact).unwrap();
+    let oob = |inst: &str, operand, offset, len| base::ValidationIssue::OperandOutOfBounds {
+        function: 0,
+        label: None,
+        inst: inst.to_string(),
+        operand,
+        offset,
+        len,
+        memory_size: 256,
+    };
+    assert_eq!(
+        issues,
+        vec![
+            oob("store.i64 v1, v0+300", "address", 300, 8),
+            oob("v4 = call fn0(v0, v1, v2, v3, v1)", "src_off", 200, 100),
+            oob("v8 = call fn1(v5, v1, v6, v7)", "src_ptr", 250, 16),
+            base::ValidationIssue::FnArgOutOfRange {
+                function: 0,
+                label: None,
+                inst: "v10 = call fn2(v5, v9, v0)".to_string(),
+                operand: "fn_index",
+                fn_idx: 7,
+                available: 2,
+            },
+        ]
+    );
+
+    let dynamic: Vec<_> = base::memory_operands(&artifact)
+        .unwrap()
+        .into_iter()
+        .filter(|op| op.range.is_none())
+        .map(|op| (op.function, op.inst))
+        .collect();
+    assert_eq!(
+        dynamic,
+        vec![
+            (0, "store.i64 v1, v11".to_string()),
+            (1, "store.i64 v1, v0+4000".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn infer_memory_size_covers_static_operands_and_never_shrinks() {
+    // A store at 4096 and a cl_mem_copy reading 5000..5100 from entry fn 0;
+    // fn 1's store is data-dependent and does not count.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_copy sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    store.i64 v1, v0+4096
+    v2 = iconst.i64 512
+    v3 = iconst.i64 5000
+    v4 = iconst.i64 100
+    v5 = call fn0(v0, v2, v3, v4, v1)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 0
+    store.i64 v1, v0+90000
+    return
+}"#;
+    let mut artifact = validation_artifact(clif_ir, cranelift_algorithm(0));
+    assert_ne!(base::validate_artifact(&artifact).unwrap(), vec![]);
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 5100);
+    assert_eq!(artifact.setup.memory_size, 5100);
+    assert_eq!(base::validate_artifact(&artifact).unwrap(), vec![]);
+
+    // Symbols and output schemas of every algorithm count too.
+    let mut extra = cranelift_algorithm(0);
+    extra.symbols.push(base_types::Symbol {
+        name: "limit".into(),
+        offset: 6000,
+        len: 8,
+        value: vec![],
+        writable: false,
+    });
+    artifact.extras.insert("extra".into(), extra);
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 6008);
+    // As an entry point, fn 1's base-relative store counts.
+    artifact
+        .extras
+        .insert("second".into(), cranelift_algorithm(1));
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 90008);
+
+    artifact.setup.memory_size = 1 << 20;
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 1 << 20);
+    assert!(matches!(
+        base::infer_memory_size(&mut validation_artifact("not clif", cranelift_algorithm(0))),
+        Err(base::Error::ClifParse(_))
+    ));
+}
+
+#[test]
+fn mem_scan_output_covers_the_whole_index() {
+    // The default mode stores an i64 at out_off, so 252 overruns 256 bytes.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_scan sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    v2 = iconst.i64 64
+    v3 = iconst.i64 4
+    v4 = iconst.i64 252
+    v5 = call fn0(v0, v1, v2, v2, v3, v4, v1)
+    return
+}"#;
+    let mut artifact = validation_artifact(clif_ir, cranelift_algorithm(0));
+    assert_eq!(
+        base::validate_artifact(&artifact).unwrap(),
+        vec![base::ValidationIssue::OperandOutOfBounds {
+            function: 0,
+            label: None,
+            inst: "v5 = call fn0(v0, v1, v2, v2, v3, v4, v1)".to_string(),
+            operand: "out_off",
+            offset: 252,
+            len: 8,
+            memory_size: 256,
+        }]
+    );
+    assert_eq!(base::infer_memory_size(&mut artifact).unwrap(), 260);
+}
+
+#[test]
+fn link_artifacts_matches_monolithic_execute_into() {
+    // test_execute_into_clif_writes_to_caller_out_buffer, split into a
+    // prologue, a body that runs its fn 1 through cl_thread_call and an
+    // epilogue with its own IO layout and a colocated call, handing values
+    // over through the shared symbols `x` and `y`.
+    let prologue = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+8
+    v2 = load.i64 v1
+    store v2, v0+64
+    return
+}"#;
+    let body = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_call sig1
+    fn2 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 56
+    call fn0(v1)
+    v2 = load.i64 v0+56
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3, v0)
+    call fn2(v1)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+40
+    v2 = imul_imm v1, 7
+    store v2, v0+48
+    return
+}"#;
+    let epilogue = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = colocated u0:1 sig0
+block0(v0: i64):
+    call fn0(v0)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+64
+    v2 = load.i64 v0+40
+    store v1, v2
+    return
+}"#;
+    let fragment = |ir: &str, io_offsets, symbols: &[(&str, u64)]| {
+        let mut main = cranelift_algorithm(0);
+        for &(name, offset) in symbols {
+            main.declare_symbol(name, offset, 8).unwrap();
+        }
+        base::Artifact {
+            setup: Setup {
+                io_offsets,
+                ..Setup::new(ir, 128)
+            },
+            main,
+            extras: Default::default(),
+        }
+    };
+    let mut fragments = [
+        fragment(prologue, compact_io_offsets(), &[("x", 64)]),
+        fragment(body, compact_io_offsets(), &[("x", 40), ("y", 48)]),
+        fragment(epilogue, IoOffsets::default(), &[("y", 64)]),
+    ];
+    fragments[2].main.set_label(1, "store result");
+    let linked = base::link_artifacts(&fragments).unwrap();
+    let fn_base = (prologue.matches("function").count() + body.matches("function").count()) as u32;
+    assert_eq!(
+        linked.main.labels,
+        [(fn_base + 1, "store result".to_string())]
+    );
+    assert_eq!(linked.setup.memory_size, 2 * 128 + 128);
+    assert_eq!(linked.setup.io_offsets, compact_io_offsets());
+    let names: Vec<_> = linked
+        .main
+        .symbols
+        .iter()
+        .map(|s| (&*s.name, s.offset))
+        .collect();
+    assert_eq!(names, [("x", 64), ("y", 128 + 48)]);
+
+    let mut base = Base::new(linked.setup.clone()).unwrap();
+    for input in [6i64, -3] {
+        let mut out = [0u8; 8];
+        base.execute_into(&linked.main, &input.to_le_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(i64::from_le_bytes(out), input * 7);
+    }
+
+    let mut mismatched = fragments.clone();
+    mismatched[2].main.symbols[0].len = 4;
+    assert_eq!(
+        base::link_artifacts(&mismatched),
+        Err(base::LinkError::SymbolMismatch {
+            name: "y".into(),
+            len: 8,
+            other: 4
+        })
+    );
+    assert_eq!(base::link_artifacts(&[]), Err(base::LinkError::Empty));
+}
+
+#[test]
+fn link_artifacts_rejects_chains_after_the_first_fragment() {
+    // The stage table at 64 holds (pool, fn index) pairs that linking
+    // cannot renumber.
+    let chain = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_thread_pool_chain sig0
+block0(v0: i64):
+    v1 = load.i64 v0+56
+    v2 = iadd_imm v0, 64
+    v3 = iconst.i64 1
+    v4 = iconst.i64 0
+    v5 = call fn0(v1, v2, v3, v0, v4)
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    return
+}"#;
+    let plain = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}"#;
+    let fragment = |ir: &str| base::Artifact::new(Setup::new(ir, 128), cranelift_algorithm(0));
+    assert!(base::link_artifacts(&[fragment(chain), fragment(plain)]).is_ok());
+    assert_eq!(
+        base::link_artifacts(&[fragment(plain), fragment(chain)]),
+        Err(base::LinkError::MemoryFnIndex {
+            fragment: 1,
+            function: 0,
+            symbol: "cl_thread_pool_chain".into(),
+        })
+    );
+}
+
+#[test]
+fn validate_artifact_parse_error() {
+    let artifact = validation_artifact("not clif", cranelift_algorithm(0));
+    assert!(matches!(
+        base::validate_artifact(&artifact),
+        Err(base::Error::ClifParse(_))
+    ));
+}
+
+#[test]
+fn default_constructors_validate_and_run() {
+    // Setup::new / with_initial_memory use the default IoOffsets layout
+    // (0x18..0x38), so out_ptr is read from 0x28.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+40
+    v2 = load.i64 v0+64
+    store.i64 v2, v1
+    return
+}"#;
+    let mut memory = vec![0u8; 128];
+    memory[64..72].copy_from_slice(&1234u64.to_le_bytes());
+    for (setup, expected) in [
+        (Setup::with_initial_memory(clif_ir, memory), 1234),
+        (Setup::new(clif_ir, 128), 0),
+    ] {
+        let artifact = base::Artifact::new(setup, Algorithm::new(0));
+        assert_eq!(base::validate_artifact(&artifact).unwrap(), vec![]);
+        let mut base = Base::new(artifact.setup).unwrap();
+        let mut out = [0u8; 8];
+        base.execute_into(&artifact.main, &[], &mut out).unwrap();
+        assert_eq!(u64::from_le_bytes(out), expected);
+    }
+}
+
+#[test]
+fn symbols_are_written_before_each_execution() {
+    // Reads the u64 symbol at 64 and the first byte of the string at 72.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+64
+    v2 = uload8.i64 v0+72
+    v3 = iadd v1, v2
+    v4 = load.i64 v0+40
+    store.i64 v3, v4
+    store.i64 v3, v0+64
+    return
+}"#;
+    let mut alg = Algorithm::new(0);
+    alg.declare_symbol("count", 64, 8).unwrap();
+    alg.declare_symbol("name", 72, 16).unwrap();
+    alg.set_symbol_u64("count", 1000).unwrap();
+    alg.set_symbol_str("name", "A").unwrap();
+    let artifact = base::Artifact::new(Setup::new(clif_ir, 128), alg);
+    // The constant-address store into `count` is reported, since `count` is
+    // not declared writable.
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert!(matches!(
+        issues.as_slice(),
+        [base::ValidationIssue::SymbolOverwritten { symbol, operand: "address", .. }] if symbol == "count"
+    ));
+
+    let mut base = Base::new(artifact.setup).unwrap();
+    for _ in 0..2 {
+        // The function overwrites `count`; the next run sees the symbol again.
+        let mut out = [0u8; 8];
+        base.execute_into(&artifact.main, &[], &mut out).unwrap();
+        assert_eq!(u64::from_le_bytes(out), 1000 + b'A' as u64);
+    }
+
+    let mut alg = Algorithm::new(0);
+    alg.declare_symbol("clobbers_out_ptr", 0x24, 8).unwrap();
+    alg.declare_symbol("past_end", 124, 8).unwrap();
+    alg.set_symbol_u64("clobbers_out_ptr", 1).unwrap();
+    alg.set_symbol_u64("past_end", 1).unwrap();
+    let artifact = base::Artifact::new(Setup::new(clif_ir, 128), alg);
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert_eq!(issues.len(), 1, "{issues:?}");
+    assert_eq!(
+        issues[0],
+        base::ValidationIssue::Symbol {
+            algorithm: "main".to_string(),
+            error: base::SymbolError::Overlap {
+                name: "clobbers_out_ptr".to_string(),
+                other: "the data_len slot".to_string()
+            }
+        }
+    );
+    let mut base = Base::new(artifact.setup).unwrap();
+    assert!(matches!(
+        base.execute(&artifact.main, &[]),
+        Err(base::Error::Symbol(base::SymbolError::OutOfBounds { .. }))
+    ));
+}
+
+#[test]
+fn validate_reports_writes_into_read_only_symbols() {
+    // cl_mem_fill zeroes 64..96, which reaches into the path at 88.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_fill sig0
+block0(v0: i64):
+    v1 = iconst.i64 64
+    v2 = iconst.i64 32
+    v3 = iconst.i64 0
+    v4 = call fn0(v0, v1, v2, v3)
+    return
+}"#;
+    let mut alg = Algorithm::new(0);
+    alg.declare_symbol("path", 88, 16).unwrap();
+    alg.set_symbol_str("path", "in.txt").unwrap();
+    let artifact = base::Artifact::new(Setup::new(clif_ir, 128), alg);
+    let issues = base::validate_artifact(&artifact).unwrap();
+    assert!(matches!(
+        issues.as_slice(),
+        [base::ValidationIssue::SymbolOverwritten { symbol, operand: "dst_off", .. }] if symbol == "path"
+    ));
+
+    // Declared as scratch, the same region may be written.
+    let mut alg = Algorithm::new(0);
+    alg.declare_scratch("scratch", 64, 32).unwrap();
+    let artifact = base::Artifact::new(Setup::new(clif_ir, 128), alg);
+    assert_eq!(base::validate_artifact(&artifact).unwrap(), vec![]);
+
+    // A scratch region that does not fit in memory is rejected.
+    let mut alg = Algorithm::new(0);
+    alg.declare_scratch("scratch", 64, 96).unwrap();
+    let artifact = base::Artifact::new(Setup::new(clif_ir, 128), alg);
+    assert_eq!(
+        base::validate_artifact(&artifact).unwrap(),
+        vec![base::ValidationIssue::Symbol {
+            algorithm: "main".to_string(),
+            error: base::SymbolError::OutOfBounds {
+                name: "scratch".to_string(),
+                memory_size: 128,
+            },
+        }]
+    );
+}
+
+#[test]
+fn test_clif_checkpoint_and_resume_counter() {
+    // Increments the counter at 256 up to 100. With a non-empty payload the
+    // run checkpoints memory at 50 and stops, standing in for a crash; a
+    // fresh instance then resumes from the snapshot and finishes the count.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_checkpoint sig0
+block0(v0: i64):
+    v1 = load.i64 v0+32
+    jump block1
+
+block1:
+    v2 = load.i64 v0+256
+    v3 = icmp_imm uge v2, 100
+    brif v3, block4, block2
+
+block2:
+    v4 = iadd_imm v2, 1
+    store.i64 v4, v0+256
+    v5 = icmp_imm eq v4, 50
+    v6 = icmp_imm ne v1, 0
+    v7 = band v5, v6
+    brif v7, block3, block1
+
+block3:
+    v8 = iconst.i64 512
+    v9 = iconst.i64 1024
+    v10 = iconst.i64 264
+    v11 = call fn0(v0, v8, v9, v10)
+    jump block4
+
+block4:
+    v12 = load.i64 v0+256
+    v13 = load.i64 v0+40
+    store.i64 v12, v13
+    return
+}"#;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("counter.ckpt");
+    let mut memory = vec![0u8; 1024];
+    let path_bytes = path.to_str().unwrap().as_bytes();
+    memory[512..512 + path_bytes.len()].copy_from_slice(path_bytes);
+    let setup = Setup::with_initial_memory(clif_ir, memory);
+    let alg = Algorithm::new(0);
+
+    let mut out = [0u8; 8];
+    let mut base = Base::new(setup.clone()).unwrap();
+    base.execute_into(&alg, &[1], &mut out).unwrap();
+    assert_eq!(u64::from_le_bytes(out), 50);
+    drop(base);
+
+    let mut base = Base::new(setup).unwrap();
+    base.execute_resume(&alg, &path, &[], &mut out).unwrap();
+    assert_eq!(u64::from_le_bytes(out), 100);
+
+    assert!(matches!(
+        base.execute_resume(&alg, temp_dir.path().join("missing"), &[], &mut out),
+        Err(base::Error::Checkpoint(_))
+    ));
+}
+
+#[test]
+fn test_clif_arena_file_larger_than_memory() {
+    // Shared memory is 1 KiB; a 100 KB file is read into an arena region and
+    // scanned for "NEED" there, with the region passed as cl_mem_scan's base
+    // pointer. out = [match offset, bytes read, free result].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_arena_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_arena_alloc sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_file_read_to_ptr sig2
+    sig3 = (i64, i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn3 = %cl_mem_scan sig3
+    sig4 = (i64, i64) -> i64 system_v
+    fn4 = %cl_arena_free sig4
+    sig5 = (i64) system_v
+    fn5 = %cl_arena_cleanup sig5
+block0(v0: i64):
+    v1 = iadd_imm v0, 72
+    call fn0(v1)
+    v2 = load.i64 v0+72
+    v3 = iconst.i64 100016
+    v4 = call fn1(v2, v3)
+    v5 = iadd_imm v0, 256
+    v6 = iconst.i64 0
+    v7 = iconst.i64 100000
+    v8 = call fn2(v5, v4, v6, v7)
+    v9 = iconst.i32 0x4445_454e
+    store.i32 v9, v4+100000
+    v10 = iconst.i64 4
+    v11 = iconst.i64 100008
+    v12 = call fn3(v4, v6, v8, v7, v10, v11, v6)
+    v13 = load.i64 v0+40
+    store.i64 v12, v13
+    store.i64 v8, v13+8
+    v14 = call fn4(v2, v4)
+    store.i64 v14, v13+16
+    call fn5(v1)
+    return
+}"#;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("big.bin");
+    let mut contents = vec![b'a'; 100_000];
+    contents[70_000..70_004].copy_from_slice(b"NEED");
+    std::fs::write(&path, &contents).unwrap();
+
+    let mut memory = vec![0u8; 1024];
+    let path_bytes = path.to_str().unwrap().as_bytes();
+    memory[256..256 + path_bytes.len()].copy_from_slice(path_bytes);
+    let mut base = Base::new(Setup::with_initial_memory(clif_ir, memory)).unwrap();
+    let mut out = [0u8; 24];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    let words: Vec<i64> = out
+        .chunks_exact(8)
+        .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    assert_eq!(words, [70_000, 100_000, 0]);
+}
+
+#[test]
+fn test_clif_big_endian_loads_and_byteswap() {
+    // The shapes emitted by Lean's cmpMem / cmpMemF64 with bigEndian set, on
+    // a network-order record: u32 0x01020304 at 256, i64 -5 at 264, f64 -2.5
+    // at 272. Then cl_mem_byteswap converts eight u16s at 288 into 320.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_byteswap sig0
+block0(v0: i64):
+    v1 = load.i32 v0+256
+    v2 = bswap v1
+    v3 = uextend.i64 v2
+    v4 = icmp_imm eq v3, 0x0102_0304
+    v5 = load.i64 v0+264
+    v6 = bswap v5
+    v7 = icmp_imm slt v6, 0
+    v8 = load.i64 v0+272
+    v9 = bswap v8
+    v10 = bitcast.f64 v9
+    v11 = f64const 0.0
+    v12 = fcmp lt v10, v11
+    v13 = iconst.i64 320
+    v14 = iconst.i64 288
+    v15 = iconst.i64 2
+    v16 = iconst.i64 16
+    v17 = call fn0(v0, v13, v14, v15, v16)
+    v18 = load.i64 v0+40
+    v19 = uextend.i64 v4
+    store v19, v18
+    v20 = uextend.i64 v7
+    store v20, v18+8
+    v21 = uextend.i64 v12
+    store v21, v18+16
+    store v6, v18+24
+    store v17, v18+32
+    return
+}"#;
+    let mut memory = vec![0u8; 512];
+    memory[256..260].copy_from_slice(&0x0102_0304u32.to_be_bytes());
+    memory[264..272].copy_from_slice(&(-5i64).to_be_bytes());
+    memory[272..280].copy_from_slice(&(-2.5f64).to_be_bytes());
+    let shorts: Vec<u16> = (0..8).map(|i| 0x1234 + i * 0x0101).collect();
+    for (i, v) in shorts.iter().enumerate() {
+        memory[288 + i * 2..290 + i * 2].copy_from_slice(&v.to_be_bytes());
+    }
+    let mut base = Base::new(Setup::with_initial_memory(clif_ir, memory)).unwrap();
+    let mut out = [0u8; 40];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    let words: Vec<i64> = out
+        .chunks_exact(8)
+        .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    assert_eq!(words, [1, 1, 1, -5, 16]);
+    let swapped = base.memory_handle().read(320, 16).unwrap();
+    let expected: Vec<u8> = shorts.iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(swapped, expected);
+}
+
+#[test]
+fn test_clif_mem_array_op_adds_whole_f32_arrays() {
+    // 1000 f32s at 256 plus 1000 at 4256, summed in place into the first array.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_array_op sig0
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 4256
+    v3 = iconst.i64 4000
+    v4 = iconst.i64 1
+    v5 = call fn0(v0, v1, v1, v2, v3, v4)
+    store v5, v0+8256
+    return
+}"#;
+    let mut memory = vec![0u8; 8264];
+    for i in 0..1000 {
+        memory[256 + i * 4..260 + i * 4].copy_from_slice(&(i as f32).to_le_bytes());
+        memory[4256 + i * 4..4260 + i * 4].copy_from_slice(&0.5f32.to_le_bytes());
+    }
+    let mut base = Base::new(Setup::with_initial_memory(clif_ir, memory)).unwrap();
+    base.execute(&Algorithm::new(0), &[]).unwrap();
+    let mem = base.memory_handle().read(256, 8008).unwrap();
+    assert_eq!(&mem[8000..], &4000i64.to_le_bytes());
+    let sums: Vec<f32> = mem[..4000]
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    let expected: Vec<f32> = (0..1000).map(|i| i as f32 + 0.5).collect();
+    assert_eq!(sums, expected);
+}
+
+#[test]
+fn test_clif_inline_compare_cas_and_cond_store() {
+    // The shapes emitted by Lean's memEqSmall (10 bytes: one word plus a
+    // two-byte tail), atomicCas and condStore, checked against cl_mem_compare.
+    // A at 256, B at 288 (equal), C at 320 (differs in the tail at byte 9).
+    // out = [eq(A,B), eq(A,C), cmp(A,B), cmp(A,C), cas prevs, cas result,
+    //        cond-store targets].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_compare sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    v2 = load.i64 v0+256
+    v3 = load.i64 v0+288
+    v4 = bxor v2, v3
+    v5 = bor v1, v4
+    v6 = uload8.i64 v0+264
+    v7 = uload8.i64 v0+296
+    v8 = bxor v6, v7
+    v9 = bor v5, v8
+    v10 = uload8.i64 v0+265
+    v11 = uload8.i64 v0+297
+    v12 = bxor v10, v11
+    v13 = bor v9, v12
+    v14 = icmp_imm eq v13, 0
+    v15 = load.i64 v0+320
+    v16 = bxor v2, v15
+    v17 = bor v1, v16
+    v18 = uload8.i64 v0+328
+    v19 = bxor v6, v18
+    v20 = bor v17, v19
+    v21 = uload8.i64 v0+329
+    v22 = bxor v10, v21
+    v23 = bor v20, v22
+    v24 = icmp_imm eq v23, 0
+    v25 = iconst.i64 256
+    v26 = iconst.i64 288
+    v27 = iconst.i64 320
+    v28 = iconst.i64 10
+    v29 = call fn0(v0, v25, v26, v28)
+    v30 = call fn0(v0, v25, v27, v28)
+    v31 = iadd_imm v0, 384
+    v32 = iconst.i64 5
+    v33 = iconst.i64 9
+    v34 = iconst.i64 11
+    v35 = atomic_cas.i64 little v31, v32, v33
+    v36 = atomic_cas.i64 little v31, v32, v34
+    v37 = iconst.i64 42
+    v38 = load.i64 v0+392
+    v39 = select v24, v37, v38
+    store v39, v0+392
+    v40 = load.i64 v0+400
+    v41 = select v14, v37, v40
+    store v41, v0+400
+    v42 = load.i64 v0+40
+    v43 = uextend.i64 v14
+    store v43, v42
+    v44 = uextend.i64 v24
+    store v44, v42+8
+    store v29, v42+16
+    store v30, v42+24
+    store v35, v42+32
+    store v36, v42+40
+    v45 = load.i64 v0+384
+    store v45, v42+48
+    v46 = load.i64 v0+392
+    store v46, v42+56
+    v47 = load.i64 v0+400
+    store v47, v42+64
+    return
+}"#;
+    let mut memory = vec![0u8; 512];
+    for off in [256, 288, 320] {
+        memory[off..off + 10].copy_from_slice(b"0123456789");
+    }
+    memory[329] = b'X';
+    memory[384..392].copy_from_slice(&5u64.to_le_bytes());
+    memory[392..400].copy_from_slice(&7u64.to_le_bytes());
+    let mut base = Base::new(Setup::with_initial_memory(clif_ir, memory)).unwrap();
+    let mut out = [0u8; 72];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    let words: Vec<i64> = out
+        .chunks_exact(8)
+        .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    assert_eq!(words, [1, 0, -1, 9, 5, 9, 9, 7, 42]);
+}
+
+#[test]
+fn test_clif_profile_counts_calls_and_finds_slow_read() {
+    // fn0 fills 5 times in a loop, calls fn1 (one fill) twice, then reads a
+    // 16 MiB file, which should dwarf every other FFI call.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_fill sig0
+    sig1 = (i64) system_v
+    fn1 = colocated u0:1 sig1
+    fn2 = %cl_file_read sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    jump block1(v1)
+block1(v2: i64):
+    v3 = iconst.i64 1024
+    v4 = iconst.i64 64
+    v5 = iconst.i64 7
+    v6 = iconst.i64 1
+    v7 = call fn0(v0, v3, v4, v5, v6)
+    v8 = iadd_imm v2, 1
+    v9 = icmp_imm slt v8, 5
+    brif v9, block1(v8), block2
+block2:
+    call fn1(v0)
+    call fn1(v0)
+    v10 = iconst.i64 512
+    v11 = iconst.i64 4096
+    v12 = iconst.i64 0
+    v13 = iconst.i64 16777216
+    v14 = call fn2(v0, v10, v11, v12, v13)
+    v15 = load.i64 v0+40
+    store.i64 v14, v15
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_fill sig0
+block0(v0: i64):
+    v1 = iconst.i64 2048
+    v2 = iconst.i64 64
+    v3 = iconst.i64 9
+    v4 = iconst.i64 1
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    return
+}"#;
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("big.bin");
+    std::fs::write(&path, vec![0x5a; 16 << 20]).unwrap();
+
+    let mut memory = vec![0u8; 4096 + (16 << 20)];
+    let path_bytes = path.to_str().unwrap().as_bytes();
+    memory[512..512 + path_bytes.len()].copy_from_slice(path_bytes);
+    let setup = Setup::with_initial_memory(clif_ir, memory);
+    assert!(Base::new(setup.clone()).unwrap().take_profile().is_none());
+
+    let mut base = Base::new_profiled(setup).unwrap();
+    let mut out = [0u8; 8];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    assert_eq!(i64::from_le_bytes(out), 16 << 20);
+
+    let profile = base.take_profile().unwrap();
+    let calls = |key: ProfileKey| profile.get(&key).map_or(0, |e| e.calls);
+    assert_eq!(calls(ProfileKey::Function(0)), 1);
+    assert_eq!(calls(ProfileKey::Function(1)), 2);
+    assert_eq!(calls(ProfileKey::Ffi("cl_mem_fill".into())), 7);
+    assert_eq!(calls(ProfileKey::Ffi("cl_file_read".into())), 1);
+    assert_eq!(profile.entries.len(), 4);
+
+    let slowest_ffi = profile
+        .top_n(profile.entries.len())
+        .into_iter()
+        .find(|e| matches!(e.key, ProfileKey::Ffi(_)))
+        .unwrap();
+    assert_eq!(slowest_ffi.key, ProfileKey::Ffi("cl_file_read".into()));
+    assert_eq!(profile.top_n(1)[0].key, ProfileKey::Function(0));
+
+    assert!(base.take_profile().unwrap().entries.is_empty());
+}
+
+#[test]
+fn test_clif_initial_memory_is_used_in_place() {
+    // The function reports its memory base pointer and the first word of
+    // memory; Base must run on the caller's buffer rather than a copy of it.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+40
+    store.i64 v0, v1
+    v2 = load.i64 v0+64
+    store.i64 v2, v1+8
+    return
+}"#;
+    let mut memory = vec![0u8; 1 << 20];
+    memory[64..72].copy_from_slice(&0x1234u64.to_le_bytes());
+    let original = memory.as_ptr() as u64;
+
+    let mut base = Base::new(Setup::with_initial_memory(clif_ir, memory)).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    assert_eq!(u64::from_le_bytes(out[..8].try_into().unwrap()), original);
+    assert_eq!(u64::from_le_bytes(out[8..].try_into().unwrap()), 0x1234);
+}
+
+#[test]
+fn test_clif_seeded_random_is_reproducible() {
+    // fn1 writes 64 cl_random draws to its argument. fn0 runs it on the
+    // executing thread into out[0..512] and on a spawned thread into
+    // out[512..1024].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_spawn sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_join sig2
+    fn3 = %cl_thread_cleanup sig0
+    fn4 = colocated u0:1 sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 v0+64
+    v3 = load.i64 v0+40
+    v4 = iadd_imm v3, 512
+    v5 = iconst.i64 1
+    v6 = call fn1(v2, v5, v4)
+    call fn4(v3)
+    v7 = call fn2(v2, v6)
+    call fn3(v1)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64) -> i64 system_v
+    fn0 = %cl_random sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    jump block1(v1)
+block1(v2: i64):
+    v3 = iconst.i64 0
+    v4 = iconst.i64 1000000
+    v5 = call fn0(v3, v4)
+    v6 = ishl_imm v2, 3
+    v7 = iadd v0, v6
+    store.i64 v5, v7
+    v8 = iadd_imm v2, 1
+    v9 = icmp_imm ult v8, 64
+    brif v9, block1(v8), block2
+block2:
+    return
+}"#;
+    let mut base = Base::new(Setup::new(clif_ir, 4096)).unwrap();
+    let mut run = |seed: Option<u64>| {
+        base.set_random_seed(seed);
+        let mut out = vec![0u8; 1024];
+        base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+        out
+    };
+    let first = run(Some(42));
+    assert_eq!(run(Some(42)), first);
+    assert_ne!(first[..512], first[512..], "threads draw from distinct streams");
+    assert_ne!(run(Some(43)), first);
+    assert_ne!(run(None), run(None));
+    assert!(first
+        .chunks_exact(8)
+        .all(|c| (0..=1_000_000).contains(&i64::from_le_bytes(c.try_into().unwrap()))));
+}
+
+#[test]
+fn test_clif_clock_sources() {
+    // out[0..16]: monotonic readings around a 10 ms cl_sleep. fn1 then
+    // records 100 sequence numbers from a spawned thread into out[16..816]
+    // while the executing thread records 100 into out[816..1616].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_clock sig0
+    fn1 = %cl_sleep sig0
+    sig1 = (i64) system_v
+    fn2 = %cl_thread_init sig1
+    sig2 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_spawn sig2
+    sig3 = (i64, i64) -> i64 system_v
+    fn4 = %cl_thread_join sig3
+    fn5 = %cl_thread_cleanup sig1
+    fn6 = colocated u0:1 sig1
+block0(v0: i64):
+    v1 = load.i64 v0+40
+    v2 = iconst.i64 1
+    v3 = call fn0(v2)
+    v4 = iconst.i64 10000000
+    v5 = call fn1(v4)
+    v6 = call fn0(v2)
+    store.i64 v3, v1
+    store.i64 v6, v1+8
+    v7 = iadd_imm v0, 64
+    call fn2(v7)
+    v8 = load.i64 v0+64
+    v9 = iadd_imm v1, 16
+    v10 = call fn3(v8, v2, v9)
+    v11 = iadd_imm v1, 816
+    call fn6(v11)
+    v12 = call fn4(v8, v10)
+    call fn5(v7)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_clock sig0
+block0(v0: i64):
+    v1 = iconst.i64 0
+    jump block1(v1)
+block1(v2: i64):
+    v3 = iconst.i64 2
+    v4 = call fn0(v3)
+    v5 = ishl_imm v2, 3
+    v6 = iadd v0, v5
+    store.i64 v4, v6
+    v7 = iadd_imm v2, 1
+    v8 = icmp_imm ult v7, 100
+    brif v8, block1(v7), block2
+block2:
+    return
+}"#;
+    let mut base = Base::new(Setup::new(clif_ir, 4096)).unwrap();
+    let mut out = vec![0u8; 1616];
+    base.execute_into(&Algorithm::new(0), &[], &mut out).unwrap();
+    let words: Vec<i64> = out
+        .chunks_exact(8)
+        .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+
+    let slept_ns = words[1] - words[0];
+    assert!(
+        (10_000_000..200_000_000).contains(&slept_ns),
+        "monotonic delta {slept_ns} ns"
+    );
+    let (spawned, inline) = words[2..].split_at(100);
+    for seqs in [spawned, inline] {
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+    }
+    let mut all: Vec<i64> = words[2..].to_vec();
+    all.sort_unstable();
+    assert_eq!(all, (0..200).collect::<Vec<i64>>());
+}
+
+#[test]
+fn test_clif_sleep_paces_and_yields_to_timeout() {
+    // Sleeps for the u64 nanoseconds at data[0] between two monotonic clock
+    // readings, which go to out[0..16].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_clock sig0
+    fn1 = %cl_sleep sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v1
+    v3 = iconst.i64 1
+    v4 = call fn0(v3)
+    v5 = call fn1(v2)
+    v6 = call fn0(v3)
+    v7 = load.i64 v0+40
+    store.i64 v4, v7
+    store.i64 v6, v7+8
+    return
+}"#;
+    let mut base = Base::new(Setup::new(clif_ir, 1024)).unwrap();
+    let mut out = [0u8; 16];
+    let data = 50_000_000u64.to_le_bytes();
+    base.execute_into(&Algorithm::new(0), &data, &mut out)
+        .unwrap();
+    let t0 = i64::from_le_bytes(out[..8].try_into().unwrap());
+    let t1 = i64::from_le_bytes(out[8..].try_into().unwrap());
+    assert!(
+        (50_000_000..500_000_000).contains(&(t1 - t0)),
+        "gap {} ns",
+        t1 - t0
+    );
+
+    let data = 60_000_000_000u64.to_le_bytes();
+    let start = std::time::Instant::now();
+    let err = base
+        .execute_with_timeout(
+            &Algorithm::new(0),
+            &data,
+            &mut out,
+            std::time::Duration::from_millis(50),
+        )
+        .unwrap_err();
+    assert!(matches!(err, base::Error::Timeout(_)), "{err:?}");
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_clif_timeout_reports_only_its_own_cancel() {
+    // Sleeps for the u64 nanoseconds at data[0].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_sleep sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v1
+    v3 = call fn0(v2)
+    return
+}"#;
+    let mut base = Base::new(Setup::new(clif_ir, 1024)).unwrap();
+    let limit = std::time::Duration::from_secs(60);
+    let short = 1_000u64.to_le_bytes();
+
+    // A failure other than a cancel leaves no cancel behind.
+    let err = base
+        .execute_with_timeout(&Algorithm::new(5), &short, &mut [], limit)
+        .unwrap_err();
+    assert!(
+        matches!(err, base::Error::FnIndexOutOfRange { .. }),
+        "{err:?}"
+    );
+    base.execute(&Algorithm::new(0), &short).unwrap();
+    base.execute_with_timeout(&Algorithm::new(0), &short, &mut [], limit)
+        .unwrap();
+    base.execute(&Algorithm::new(0), &short).unwrap();
+
+    // A cancel from the caller stays a cancel.
+    let handle = base.cancel_handle();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.cancel();
+    });
+    let long = 60_000_000_000u64.to_le_bytes();
+    let err = base
+        .execute_with_timeout(&Algorithm::new(0), &long, &mut [], limit)
+        .unwrap_err();
+    canceller.join().unwrap();
+    assert!(matches!(err, base::Error::Cancelled), "{err:?}");
+    base.execute(&Algorithm::new(0), &short).unwrap();
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn test_clif_http_get_into_memory() {
+    // Builds the request descriptor at 256 (method at 512, URL at 520, no
+    // headers or body), fetches into a 512-byte response buffer at 1024 and
+    // copies the returned code, body length and first 8 body bytes to out.
+    // The reply has no headers and ends at close, so the body starts at
+    // 1024 + 24.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        use std::io::{Read, Write};
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 256];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+        }
+        stream.write_all(b"HTTP/1.1 200 OK\r\n\r\nfetched!").unwrap();
+        String::from_utf8(request).unwrap()
+    });
+    let url = format!("http://127.0.0.1:{port}/data\0");
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64) -> i64 system_v
+    fn0 = %cl_http_request sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 512
+    store.i64 v1, v0+256
+    v2 = iadd_imm v0, 520
+    store.i64 v2, v0+264
+    v3 = iconst.i64 0
+    store.i64 v3, v0+272
+    store.i64 v3, v0+280
+    store.i64 v3, v0+288
+    v4 = iadd_imm v0, 256
+    v5 = iadd_imm v0, 1024
+    v6 = iconst.i64 512
+    v7 = call fn0(v4, v5, v6)
+    v8 = load.i64 v0+24
+    store.i64 v7, v8
+    v9 = load.i64 v0+1040
+    store.i64 v9, v8+8
+    v10 = load.i64 v0+1048
+    store.i64 v10, v8+16
+    return
+}"#;
+
+    let mut memory = vec![0u8; 2048];
+    memory[512..516].copy_from_slice(b"GET\0");
+    memory[520..520 + url.len()].copy_from_slice(url.as_bytes());
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 24];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert!(server.join().unwrap().starts_with("GET /data HTTP/1.1\r\n"));
+    assert_eq!(u64::from_le_bytes(out[0..8].try_into().unwrap()), 200);
+    assert_eq!(u64::from_le_bytes(out[8..16].try_into().unwrap()), 8);
+    assert_eq!(&out[16..24], b"fetched!");
+}
+
+#[test]
+fn test_clif_pool_broadcast_wait_on_one_item() {
+    // Broadcasts fn 1 over four 24-byte items at 256 with flags at 512:
+    // each job sleeps item[0] ns, then copies item[1] to item[2]. Items 2
+    // and 3 sleep 300ms. Main waits only for item 1's flag, records its copy
+    // and the countdown, then waits for the countdown to reach 0.
+    // out = [item 1 copy, countdown then, countdown after, item 3 copy].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_broadcast sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wait_until sig3
+    fn4 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 4
+    v4 = call fn1(v2, v3)
+    v5 = iconst.i64 1
+    v6 = iadd_imm v0, 256
+    v7 = iconst.i64 24
+    v8 = iadd_imm v0, 512
+    v9 = call fn2(v2, v4, v5, v6, v7, v3, v8)
+    v10 = iadd_imm v0, 528
+    v11 = iconst.i64 0
+    v12 = call fn3(v10, v5, v11)
+    v13 = load.i64 v0+296
+    v14 = atomic_load.i64 v8
+    v15 = call fn3(v8, v11, v11)
+    v16 = load.i64 v8
+    v17 = load.i64 v0+344
+    call fn4(v1)
+    v18 = load.i64 v0+24
+    store.i64 v13, v18
+    store.i64 v14, v18+8
+    store.i64 v16, v18+16
+    store.i64 v17, v18+24
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_sleep sig0
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = call fn0(v1)
+    v3 = load.i64 v0+8
+    store.i64 v3, v0+16
+    return
+}"#;
+
+    let mut memory = vec![0u8; 1024];
+    for (i, delay) in [0u64, 0, 300_000_000, 300_000_000].iter().enumerate() {
+        let off = 256 + 24 * i;
+        memory[off..off + 8].copy_from_slice(&delay.to_le_bytes());
+        memory[off + 8..off + 16].copy_from_slice(&(100 + i as u64).to_le_bytes());
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 32];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    assert_eq!(word(0), 101, "item 1 visible once its flag is set");
+    assert!(word(1) >= 2, "items 2 and 3 still pending: {}", word(1));
+    assert_eq!(word(2), 0);
+    assert_eq!(word(3), 103);
+}
+
+#[test]
+fn test_clif_barrier_orders_two_phases_on_pool_workers() {
+    // Four pool jobs share memory: each takes an id from 128, sleeps id *
+    // 20ms, appends 100 + id to the log at 512 (slot counter at 136), waits
+    // on the barrier at 320, then appends 200 + id. The barrier's return
+    // values are summed at 144. out = [8 log entries, leader count].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_broadcast sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wait_until sig3
+    fn4 = %cl_thread_cleanup sig0
+    fn5 = %cl_thread_barrier_init sig1
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 4
+    v4 = call fn1(v2, v3)
+    v5 = iadd_imm v0, 320
+    v6 = call fn5(v5, v3)
+    v7 = iconst.i64 1
+    v8 = iconst.i64 0
+    v9 = iadd_imm v0, 192
+    v10 = call fn2(v2, v4, v7, v0, v8, v3, v9)
+    v11 = call fn3(v9, v8, v8)
+    call fn4(v1)
+    v12 = load.i64 v0+24
+    v13 = iadd_imm v0, 512
+    v15 = load.i64 v13
+    store.i64 v15, v12
+    v16 = load.i64 v13+8
+    store.i64 v16, v12+8
+    v17 = load.i64 v13+16
+    store.i64 v17, v12+16
+    v18 = load.i64 v13+24
+    store.i64 v18, v12+24
+    v19 = load.i64 v13+32
+    store.i64 v19, v12+32
+    v20 = load.i64 v13+40
+    store.i64 v20, v12+40
+    v21 = load.i64 v13+48
+    store.i64 v21, v12+48
+    v22 = load.i64 v13+56
+    store.i64 v22, v12+56
+    v23 = load.i64 v0+144
+    store.i64 v23, v12+64
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_sleep sig0
+    fn1 = %cl_thread_barrier_wait sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 128
+    v2 = iconst.i64 1
+    v3 = atomic_rmw.i64 little add v1, v2
+    v4 = imul_imm v3, 20000000
+    v5 = call fn0(v4)
+    v6 = iadd_imm v0, 136
+    v7 = atomic_rmw.i64 little add v6, v2
+    v8 = ishl_imm v7, 3
+    v9 = iadd v0, v8
+    v10 = iadd_imm v3, 100
+    store.i64 v10, v9+512
+    v11 = iadd_imm v0, 320
+    v12 = call fn1(v11)
+    v13 = iadd_imm v0, 144
+    v14 = atomic_rmw.i64 little add v13, v12
+    v15 = atomic_rmw.i64 little add v6, v2
+    v16 = ishl_imm v15, 3
+    v17 = iadd v0, v16
+    v18 = iadd_imm v3, 200
+    store.i64 v18, v17+512
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 1024], clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 72];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    let log: Vec<u64> = (0..8).map(word).collect();
+    assert_eq!(log[..4], [100, 101, 102, 103], "phase 1 in sleep order: {log:?}");
+    let mut phase2 = log[4..].to_vec();
+    phase2.sort();
+    assert_eq!(phase2, [200, 201, 202, 203], "no phase 2 entry before phase 1: {log:?}");
+    assert_eq!(word(8), 1, "exactly one leader");
+}
+
+#[test]
+fn test_clif_pool_chain_matches_per_stage_waits() {
+    // Three stages over the two u64 at 256, each on its own pool: fn 2
+    // (w * 31 + 7), fn 3 (w ^ w >> 13), fn 4 (rotl 17). fn 0 runs them as
+    // one chain (stage pairs at 128, flag at 192) and waits once; fn 1
+    // submits each stage and waits on its pool before the next. Both copy
+    // the words to out.
+    let stages = r#"
+function u0:2(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = imul_imm v1, 31
+    v3 = iadd_imm v2, 7
+    store.i64 v3, v0
+    v4 = load.i64 v0+8
+    v5 = imul_imm v4, 31
+    v6 = iadd_imm v5, 7
+    store.i64 v6, v0+8
+    return
+}
+
+function u0:3(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = ushr_imm v1, 13
+    v3 = bxor v1, v2
+    store.i64 v3, v0
+    v4 = load.i64 v0+8
+    v5 = ushr_imm v4, 13
+    v6 = bxor v4, v5
+    store.i64 v6, v0+8
+    return
+}
+
+function u0:4(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = rotl_imm v1, 17
+    store.i64 v2, v0
+    v3 = load.i64 v0+8
+    v4 = rotl_imm v3, 17
+    store.i64 v4, v0+8
+    return
+}"#;
+    let clif_ir = format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_chain sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wait_until sig3
+    fn4 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3)
+    v5 = call fn1(v2, v3)
+    v6 = call fn1(v2, v3)
+    v7 = iconst.i64 2
+    v8 = iconst.i64 3
+    v9 = iconst.i64 4
+    store.i64 v4, v0+128
+    store.i64 v7, v0+136
+    store.i64 v5, v0+144
+    store.i64 v8, v0+152
+    store.i64 v6, v0+160
+    store.i64 v9, v0+168
+    v10 = iadd_imm v0, 128
+    v11 = iadd_imm v0, 256
+    v12 = iadd_imm v0, 192
+    v13 = call fn2(v2, v10, v8, v11, v12)
+    v14 = iconst.i64 0
+    v15 = call fn3(v12, v3, v14)
+    call fn4(v1)
+    v16 = load.i64 v0+24
+    v17 = load.i64 v0+256
+    store.i64 v17, v16
+    v18 = load.i64 v0+264
+    store.i64 v18, v16+8
+    return
+}}
+
+function u0:1(i64) system_v {{
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_submit sig2
+    fn3 = %cl_thread_pool_wait sig1
+    fn4 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3)
+    v5 = call fn1(v2, v3)
+    v6 = call fn1(v2, v3)
+    v7 = iadd_imm v0, 256
+    v8 = iconst.i64 2
+    v9 = call fn2(v2, v4, v8, v7)
+    v10 = call fn3(v2, v4)
+    v11 = iconst.i64 3
+    v12 = call fn2(v2, v5, v11, v7)
+    v13 = call fn3(v2, v5)
+    v14 = iconst.i64 4
+    v15 = call fn2(v2, v6, v14, v7)
+    v16 = call fn3(v2, v6)
+    call fn4(v1)
+    v17 = load.i64 v0+24
+    v18 = load.i64 v0+256
+    store.i64 v18, v17
+    v19 = load.i64 v0+264
+    store.i64 v19, v17+8
+    return
+}}
+{stages}"#
+    );
+
+    let words = [0x0123_4567_89ab_cdefu64, 0xfedc_ba98_7654_3210];
+    let mut memory = vec![0u8; 512];
+    for (i, w) in words.iter().enumerate() {
+        memory[256 + 8 * i..264 + 8 * i].copy_from_slice(&w.to_le_bytes());
+    }
+    let run = |fn_idx: u32| {
+        let mut base = Base::new(cranelift_config(memory.clone(), clif_ir.clone())).unwrap();
+        let mut out = [0u8; 16];
+        base.execute_into(&cranelift_algorithm(fn_idx), &[], &mut out)
+            .unwrap();
+        out
+    };
+    let chained = run(0);
+    assert_eq!(chained, run(1));
+    let expected: Vec<u8> = words
+        .iter()
+        .map(|&w| w.wrapping_mul(31).wrapping_add(7))
+        .map(|w| w ^ (w >> 13))
+        .flat_map(|w| w.rotate_left(17).to_le_bytes())
+        .collect();
+    assert_eq!(chained.as_slice(), expected.as_slice());
+}
+
+#[test]
+fn test_clif_lz4_roundtrip() {
+    // Compresses the 40000 bytes at 1024 into 1024 + 64K, decompresses that
+    // into 1024 + 128K, and compares with the input via cl_mem_compare (-1
+    // when equal). out = [compressed len, decompressed len, compare].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_lz4_compress sig0
+    fn1 = %cl_lz4_decompress sig0
+    sig1 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_mem_compare sig1
+block0(v0: i64):
+    v1 = iconst.i64 1024
+    v2 = iconst.i64 40000
+    v3 = iconst.i64 66560
+    v4 = iconst.i64 65536
+    v5 = call fn0(v0, v3, v1, v2, v4)
+    v6 = iconst.i64 132096
+    v7 = call fn1(v0, v6, v3, v5, v4)
+    v8 = call fn2(v0, v1, v6, v2)
+    v9 = load.i64 v0+24
+    store.i64 v5, v9
+    store.i64 v7, v9+8
+    store.i64 v8, v9+16
+    return
+}"#;
+
+    // Compressible text followed by pseudo-random bytes.
+    let mut input = b"lz4 block round trip; ".repeat(1000);
+    let mut x = 0x9E37_79B9u32;
+    input.extend((input.len()..40000).map(|_| {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        x as u8
+    }));
+    let mut memory = vec![0u8; 1024 + 3 * 65536];
+    memory[1024..1024 + input.len()].copy_from_slice(&input);
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 24];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| i64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    assert!(word(0) > 0 && word(0) < 20000, "compressed {}", word(0));
+    assert_eq!(word(1), 40000);
+    assert_eq!(word(2), -1, "round trip matches the input");
+}
+
+#[test]
+fn test_clif_checksum_stamps_frame() {
+    // "123456789" at 1024. CRC-32 of the whole string into 1040, CRC-32C in
+    // two pieces (4 + 5 bytes, continuing) into 1048, XXH64 into 1056; the
+    // three slots are then copied to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_checksum sig0
+block0(v0: i64):
+    v1 = iconst.i64 1024
+    v2 = iconst.i64 9
+    v3 = iconst.i64 0
+    v4 = iconst.i64 1040
+    v5 = call fn0(v0, v3, v1, v2, v4)
+    v6 = iconst.i64 1
+    v7 = iconst.i64 4
+    v8 = iconst.i64 1048
+    v9 = call fn0(v0, v6, v1, v7, v8)
+    v10 = iconst.i64 257
+    v11 = iconst.i64 1028
+    v12 = iconst.i64 5
+    v13 = call fn0(v0, v10, v11, v12, v8)
+    v14 = iconst.i64 2
+    v15 = iconst.i64 1056
+    v16 = call fn0(v0, v14, v1, v2, v15)
+    v17 = load.i64 v0+24
+    v18 = load.i64 v0+1040
+    v19 = load.i64 v0+1048
+    v20 = load.i64 v0+1056
+    store.i64 v18, v17
+    store.i64 v19, v17+8
+    store.i64 v20, v17+16
+    return
+}"#;
+
+    let mut memory = vec![0u8; 2048];
+    memory[1024..1033].copy_from_slice(b"123456789");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 24];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    assert_eq!(word(0), 0xCBF4_3926, "CRC-32");
+    assert_eq!(word(1), 0xE306_9283, "CRC-32C in two pieces");
+    assert_eq!(word(2), 0x8CB8_41DB_40E6_AE83, "XXH64");
+}
+
+#[test]
+fn test_execute_many_isolates_instances() {
+    let temp_dir = TempDir::new().unwrap();
+    // Bumps the counter at 600 and writes [record, counter] to the file named
+    // by the "path" symbol. Each instance has its own memory, so every file
+    // must hold a counter of 1.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = load.i64 v0+600
+    v2 = iadd_imm v1, 1
+    store.i64 v2, v0+520
+    store.i64 v2, v0+600
+    v3 = iconst.i64 256
+    v4 = iconst.i64 512
+    v5 = iconst.i64 0
+    v6 = iconst.i64 16
+    v7 = call fn0(v0, v3, v4, v5, v6)
+    return
+}"#;
+    let base = Base::new(cranelift_config(vec![0u8; 1024], clif_ir.to_string())).unwrap();
+
+    let algorithms: Vec<Algorithm> = (0..50u64)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("instance_{i}.bin"));
+            // Instance 25 names a function that does not exist.
+            let mut alg = cranelift_algorithm(if i == 25 { 7 } else { 0 });
+            alg.declare_symbol("path", 256, 200).unwrap();
+            alg.declare_symbol("record", 512, 8).unwrap();
+            alg.set_symbol_str("path", path.to_str().unwrap()).unwrap();
+            alg.set_symbol_u64("record", 1000 + i).unwrap();
+            alg
+        })
+        .collect();
+    let results = base.execute_many(&algorithms, 8);
+
+    assert_eq!(results.len(), 50);
+    for (i, result) in results.iter().enumerate() {
+        let path = temp_dir.path().join(format!("instance_{i}.bin"));
+        if i == 25 {
+            assert!(matches!(
+                result,
+                Err(base::Error::FnIndexOutOfRange { fn_idx: 7, .. })
+            ));
+            assert!(!path.exists());
+            continue;
+        }
+        assert!(result.is_ok(), "instance {i}: {result:?}");
+        let bytes = std::fs::read(&path).unwrap();
+        let record = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let counter = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        assert_eq!((record, counter), (1000 + i as u64, 1), "instance {i}");
+    }
+}
+
+#[test]
+fn test_clif_compare_conditions_per_type() {
+    // Each comparison stores its i8 0/1 result to out, in the order the
+    // conditions are listed: three f64 pairs (less, equal, NaN) and one u64
+    // pair whose left operand is above i64::MAX.
+    const FCMP: [&str; 6] = ["gt", "ge", "lt", "le", "eq", "ne"];
+    const ICMP: [&str; 10] = [
+        "eq", "ne", "ugt", "uge", "ult", "ule", "sgt", "sge", "slt", "sle",
+    ];
+    let mut body = String::from("    v1 = load.i64 v0+24\n");
+    let mut v = 2;
+    let mut out = 0;
+    let pairs = [("f64", &FCMP[..]); 3]
+        .into_iter()
+        .chain([("i64", &ICMP[..])]);
+    for (pair, (ty, conds)) in pairs.enumerate() {
+        let (a, b) = (v, v + 1);
+        body += &format!("    v{a} = load.{ty} v0+{}\n", 256 + 16 * pair);
+        body += &format!("    v{b} = load.{ty} v0+{}\n", 264 + 16 * pair);
+        v += 2;
+        for cond in conds {
+            let op = if ty == "f64" { "fcmp" } else { "icmp" };
+            body += &format!("    v{v} = {op} {cond} v{a}, v{b}\n");
+            body += &format!("    store.i8 v{v}, v1+{out}\n");
+            v += 1;
+            out += 1;
+        }
+    }
+    let clif_ir = format!("function u0:0(i64) system_v {{\nblock0(v0: i64):\n{body}    return\n}}");
+
+    let mut memory = vec![0u8; 512];
+    let words: [u64; 8] = [
+        1.0f64.to_bits(),
+        2.0f64.to_bits(),
+        2.0f64.to_bits(),
+        2.0f64.to_bits(),
+        f64::NAN.to_bits(),
+        1.0f64.to_bits(),
+        u64::MAX - 1,
+        1,
+    ];
+    for (i, w) in words.iter().enumerate() {
+        memory[256 + 8 * i..264 + 8 * i].copy_from_slice(&w.to_le_bytes());
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir)).unwrap();
+    let mut out = [0xAAu8; 28];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    //                 gt ge lt le eq ne
+    assert_eq!(out[0..6], [0, 0, 1, 1, 0, 1], "1.0 vs 2.0");
+    assert_eq!(out[6..12], [0, 1, 0, 1, 1, 0], "2.0 vs 2.0");
+    assert_eq!(out[12..18], [0, 0, 0, 0, 0, 1], "NaN vs 1.0");
+    // eq ne ugt uge ult ule sgt sge slt sle
+    assert_eq!(
+        out[18..28],
+        [0, 1, 1, 1, 0, 0, 0, 0, 1, 1],
+        "u64::MAX - 1 vs 1"
+    );
+}
+
+#[test]
+fn test_clif_file_paths_matrix() {
+    // Writes 16 bytes from 2048 to the path at 256, reads them back into 3072
+    // and copies them to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+    fn1 = %cl_file_read sig0
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 2048
+    v3 = iconst.i64 0
+    v4 = iconst.i64 16
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    v6 = iconst.i64 3072
+    v7 = call fn1(v0, v1, v6, v3, v4)
+    v8 = load.i64 v0+24
+    v9 = load.i64 v0+3072
+    v10 = load.i64 v0+3080
+    store.i64 v9, v8
+    store.i64 v10, v8+8
+    return
+}"#;
+
+    let temp_dir = TempDir::new().unwrap();
+    // Past Windows' 260-character MAX_PATH in total, each component well
+    // under the 255-byte limit.
+    let mut long_dir = temp_dir.path().to_path_buf();
+    for i in 0..6 {
+        long_dir.push(format!("{i}_{}", "d".repeat(48)));
+    }
+    std::fs::create_dir_all(&long_dir).unwrap();
+    std::fs::create_dir_all(temp_dir.path().join("sub dir")).unwrap();
+    let paths = [
+        temp_dir.path().join("naïve résumé 日本語.bin"),
+        temp_dir.path().join("with spaces  .bin"),
+        // Joined with '/' as an algorithm concatenating a listing would.
+        std::path::PathBuf::from(format!("{}/sub dir/entry.bin", temp_dir.path().display())),
+        long_dir.join("long.bin"),
+    ];
+    assert!(paths[3].as_os_str().len() > 260);
+
+    for (i, path) in paths.iter().enumerate() {
+        let path_str = path.to_str().unwrap();
+        let mut memory = vec![0u8; 4096];
+        memory[256..256 + path_str.len()].copy_from_slice(path_str.as_bytes());
+        let payload = [i as u8 + 1; 16];
+        memory[2048..2064].copy_from_slice(&payload);
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        let mut out = [0u8; 16];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        assert_eq!(out, payload, "{path_str}");
+        assert_eq!(std::fs::read(path).unwrap(), payload, "{path_str}");
+    }
+}
+
+#[test]
+fn analyze_artifact_reports_files_and_memory_ranges() {
+    use base::{analyze_artifact, Artifact, ArtifactReport, StringArg};
+
+    // test_cranelift_arithmetic_add: loads 2000/2008, stores the sum at 2016
+    // and writes it to the file named at 3000.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = load.i64 v0+2000
+    v2 = load.i64 v0+2008
+    v3 = iadd v1, v2
+    store.i64 v3, v0+2016
+    v4 = iconst.i64 3000
+    v5 = iconst.i64 2016
+    v6 = iconst.i64 0
+    v7 = iconst.i64 8
+    v8 = call fn0(v0, v4, v5, v6, v7)
+    return
+}"#;
+    let path = "/tmp/cranelift_add.txt";
+    let mut memory = vec![0u8; 4096];
+    memory[3000..3000 + path.len()].copy_from_slice(path.as_bytes());
+    let artifact = Artifact::new(
+        cranelift_config(memory, clif_ir.to_string()),
+        cranelift_algorithm(0),
+    );
+    assert_eq!(
+        analyze_artifact(&artifact).unwrap(),
+        ArtifactReport {
+            ffi_symbols: vec!["cl_file_write".into()],
+            ffi_families: vec!["file".into()],
+            files_written: vec![StringArg::Known(path.into())],
+            inputs: vec![2000..2016, 3000..3000 + path.len() as u64 + 1],
+            ..Default::default()
+        }
+    );
+
+    // test_execute_many_isolates_instances: the path comes from a symbol;
+    // without a value for it the path is only known at run time.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = load.i64 v0+600
+    v2 = iadd_imm v1, 1
+    store.i64 v2, v0+520
+    store.i64 v2, v0+600
+    v3 = iconst.i64 256
+    v4 = iconst.i64 512
+    v5 = iconst.i64 0
+    v6 = iconst.i64 16
+    v7 = call fn0(v0, v3, v4, v5, v6)
+    return
+}"#;
+    let mut main = cranelift_algorithm(0);
+    main.declare_symbol("path", 256, 200).unwrap();
+    main.declare_symbol("record", 512, 8).unwrap();
+    let mut artifact = Artifact::new(cranelift_config(vec![0u8; 1024], clif_ir.to_string()), main);
+    let report = analyze_artifact(&artifact).unwrap();
+    assert_eq!(
+        report.files_written,
+        vec![StringArg::Dynamic {
+            function: 0,
+            inst: "v7 = call fn0(v0, v3, v4, v5, v6)".into()
+        }]
+    );
+    assert_eq!(report.inputs, vec![256..257, 512..520, 600..608]);
+    assert!(report.outputs.is_empty());
+
+    artifact
+        .main
+        .set_symbol_str("path", "/data/out.bin")
+        .unwrap();
+    let report = analyze_artifact(&artifact).unwrap();
+    assert_eq!(
+        report.files_written,
+        vec![StringArg::Known("/data/out.bin".into())]
+    );
+    assert_eq!(report.inputs, vec![256..270, 512..520, 600..608]);
+    assert_eq!(report.ffi_families, vec!["file"]);
+    let json = report.to_json_string();
+    assert!(json.contains("/data/out.bin"), "{json}");
+}
+
+#[test]
+fn test_clif_file_stream_consumes_through_ring() {
+    // Streams the file named at 256 through a 4 KiB ring (header at 512,
+    // config at 528, data at 1024) in 1000-byte chunks. The consumer waits
+    // for more bytes on the head word, folds them into an FNV-1a hash, and
+    // hands the space back through the tail, until the head's EOF bit is set.
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("stream.bin");
+    let mut x = 0x9E37_79B9_7F4A_7C15u64;
+    let data: Vec<u8> = (0..300_001)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect();
+    std::fs::write(&path, &data).unwrap();
+
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_stream_start sig0
+    sig1 = (i64) -> i64 system_v
+    fn1 = %cl_file_stream_end sig1
+    sig2 = (i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_wait_until sig2
+    sig3 = (i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wake sig3
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 512
+    v3 = iconst.i64 1024
+    v4 = iconst.i64 528
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    v6 = iadd_imm v0, 512
+    v7 = iadd_imm v0, 520
+    v8 = iconst.i64 0
+    v9 = iconst.i64 0xcbf29ce484222325
+    jump block1(v8, v9)
+
+block1(v10: i64, v11: i64):
+    v12 = iadd_imm v10, 1
+    v13 = iconst.i64 2
+    v14 = call fn2(v6, v12, v13)
+    v15 = load.i64 v0+512
+    v16 = band_imm v15, 0x7fffffffffffffff
+    jump block2(v10, v11)
+
+block2(v20: i64, v21: i64):
+    v22 = icmp uge v20, v16
+    brif v22, block3, block4
+
+block4:
+    v23 = band_imm v20, 4095
+    v24 = iadd v0, v23
+    v25 = uload8.i64 v24+1024
+    v26 = bxor v21, v25
+    v27 = imul_imm v26, 0x100000001b3
+    v28 = iadd_imm v20, 1
+    jump block2(v28, v27)
+
+block3:
+    v30 = call fn3(v7, v20)
+    v31 = icmp_imm slt v15, 0
+    brif v31, block5, block1(v20, v21)
+
+block5:
+    v32 = call fn1(v5)
+    v33 = load.i64 v0+24
+    store.i64 v21, v33
+    store.i64 v20, v33+8
+    store.i64 v32, v33+16
+    return
+}"#;
+
+    let mut memory = vec![0u8; 1024 + 4096];
+    let path_bytes = path.to_str().unwrap().as_bytes();
+    memory[256..256 + path_bytes.len()].copy_from_slice(path_bytes);
+    memory[528..536].copy_from_slice(&1000u64.to_le_bytes());
+    memory[536..544].copy_from_slice(&4096u64.to_le_bytes());
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 24];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+    let fnv = data.iter().fold(0xcbf2_9ce4_8422_2325u64, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+    });
+    assert_eq!(word(0), fnv, "hash of every byte in order");
+    assert_eq!(word(1), data.len() as u64, "bytes consumed");
+    assert_eq!(word(2), data.len() as u64, "bytes read");
+}
+
+#[test]
+fn test_clif_simd_checked_integer_ops() {
+    // Mirrors the Lean iaddSatI32x4 / isubSatI32x4 / iaddOvf / imulOvfI32x4
+    // and imulOvfI64x2 emitters: operands at 256 and 272, results and
+    // per-lane overflow masks written to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i32x4 notrap aligned v0+256
+    v2 = load.i32x4 notrap aligned v0+272
+    v3 = iadd v1, v2
+    v4 = bxor v1, v3
+    v5 = bxor v2, v3
+    v6 = band v4, v5
+    v7 = iconst.i64 31
+    v8 = sshr v6, v7
+    v9 = sshr v1, v7
+    v10 = iconst.i32 0x7fffffff
+    v11 = splat.i32x4 v10
+    v12 = bxor v9, v11
+    v13 = bitselect v8, v12, v3
+    v14 = isub v1, v2
+    v15 = bxor v1, v2
+    v16 = bxor v1, v14
+    v17 = band v15, v16
+    v18 = sshr v17, v7
+    v19 = bitselect v18, v12, v14
+    v20 = imul v1, v2
+    v21 = swiden_low v1
+    v22 = swiden_low v2
+    v23 = imul v21, v22
+    v24 = iconst.i64 32
+    v25 = ishl v23, v24
+    v26 = sshr v25, v24
+    v27 = icmp ne v23, v26
+    v28 = bitcast.i8x16 little v27
+    v29 = swiden_high v1
+    v30 = swiden_high v2
+    v31 = imul v29, v30
+    v32 = ishl v31, v24
+    v33 = sshr v32, v24
+    v34 = icmp ne v31, v33
+    v35 = bitcast.i8x16 little v34
+    v36 = shuffle v28, v35, 0x1b1a1918131211100b0a090803020100
+    v37 = bitcast.i32x4 little v36
+    v38 = vhigh_bits.i32 v8
+    v39 = load.i64 v0+24
+    store v13, v39
+    store v19, v39+16
+    store v3, v39+32
+    store v8, v39+48
+    store v20, v39+64
+    store v37, v39+80
+    store v38, v39+96
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64x2 notrap aligned v0+256
+    v2 = load.i64x2 notrap aligned v0+272
+    v3 = iadd v1, v2
+    v4 = bxor v1, v3
+    v5 = bxor v2, v3
+    v6 = band v4, v5
+    v7 = iconst.i64 63
+    v8 = sshr v6, v7
+    v9 = extractlane v1, 0
+    v10 = extractlane v2, 0
+    v11, v12 = smul_overflow v9, v10
+    v13 = extractlane v1, 1
+    v14 = extractlane v2, 1
+    v15, v16 = smul_overflow v13, v14
+    v17 = iconst.i64 0
+    v18 = splat.i64x2 v17
+    v19 = insertlane v18, v11, 0
+    v20 = insertlane v19, v15, 1
+    v21 = uextend.i64 v12
+    v22 = ineg v21
+    v23 = uextend.i64 v16
+    v24 = ineg v23
+    v25 = insertlane v18, v22, 0
+    v26 = insertlane v25, v24, 1
+    v27 = load.i64 v0+24
+    store v3, v27
+    store v8, v27+16
+    store v20, v27+32
+    store v26, v27+48
+    return
+}"#;
+
+    fn run(clif_ir: &str, fn_idx: u32, a: &[u8], b: &[u8], out: &mut [u8]) {
+        let mut memory = vec![0u8; 512];
+        memory[256..256 + a.len()].copy_from_slice(a);
+        memory[272..272 + b.len()].copy_from_slice(b);
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        base.execute_into(&cranelift_algorithm(fn_idx), &[], out)
+            .unwrap();
+    }
+    fn mask(overflowed: bool) -> i64 {
+        -(overflowed as i64)
+    }
+
+    const MAX: i32 = i32::MAX;
+    const MIN: i32 = i32::MIN;
+    let cases32: [([i32; 4], [i32; 4]); 5] = [
+        ([MAX, MAX, MIN, MIN], [0, 1, 0, -1]),
+        ([MIN, MAX, -1, 0], [MIN, MIN, MIN, MIN]),
+        ([MAX, 46_340, 46_341, -65_536], [-1, 46_340, 46_341, 32_768]),
+        ([MIN, 1 << 30, -7, 123_456], [-1, 2, 9, -654_321]),
+        ([MAX - 5, MIN + 5, 100, -100], [5, -5, MAX, MIN]),
+    ];
+    for (a, b) in cases32 {
+        let bytes = |v: [i32; 4]| v.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let mut out = [0u8; 104];
+        run(clif_ir, 0, &bytes(a), &bytes(b), &mut out);
+        let lane = |block: usize, i: usize| {
+            let at = block * 16 + i * 4;
+            i32::from_le_bytes(out[at..at + 4].try_into().unwrap())
+        };
+        let mut high_bits = 0;
+        for i in 0..4 {
+            let (x, y) = (a[i], b[i]);
+            let ctx = format!("lane {i}: {x}, {y}");
+            assert_eq!(lane(0, i), x.saturating_add(y), "sat add {ctx}");
+            assert_eq!(lane(1, i), x.saturating_sub(y), "sat sub {ctx}");
+            assert_eq!(lane(2, i), x.wrapping_add(y), "add {ctx}");
+            let add_overflow = x.checked_add(y).is_none();
+            assert_eq!(lane(3, i) as i64, mask(add_overflow), "add mask {ctx}");
+            high_bits |= (add_overflow as i32) << i;
+            assert_eq!(lane(4, i), x.wrapping_mul(y), "mul {ctx}");
+            let mul_overflow = x.checked_mul(y).is_none();
+            assert_eq!(lane(5, i) as i64, mask(mul_overflow), "mul mask {ctx}");
+        }
+        assert_eq!(lane(6, 0), high_bits, "vhigh_bits of the add mask");
+    }
+
+    let cases64: [([i64; 2], [i64; 2]); 4] = [
+        ([i64::MAX, i64::MIN], [1, -1]),
+        ([i64::MAX, i64::MIN], [0, 0]),
+        (
+            [3_037_000_499, 3_037_000_500],
+            [3_037_000_499, 3_037_000_500],
+        ),
+        ([i64::MIN, 1 << 40], [-1, 1 << 22]),
+    ];
+    for (a, b) in cases64 {
+        let bytes = |v: [i64; 2]| v.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let mut out = [0u8; 64];
+        run(clif_ir, 1, &bytes(a), &bytes(b), &mut out);
+        let lane = |block: usize, i: usize| {
+            let at = block * 16 + i * 8;
+            i64::from_le_bytes(out[at..at + 8].try_into().unwrap())
+        };
+        for i in 0..2 {
+            let (x, y) = (a[i], b[i]);
+            let ctx = format!("lane {i}: {x}, {y}");
+            assert_eq!(lane(0, i), x.wrapping_add(y), "add {ctx}");
+            assert_eq!(
+                lane(1, i),
+                mask(x.checked_add(y).is_none()),
+                "add mask {ctx}"
+            );
+            assert_eq!(lane(2, i), x.wrapping_mul(y), "mul {ctx}");
+            assert_eq!(
+                lane(3, i),
+                mask(x.checked_mul(y).is_none()),
+                "mul mask {ctx}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_clif_branch_on_typed_comparison() {
+    // Mirrors the Lean cmpMem / cmpMemF64 emitters: an i64 at 256, an f64 at
+    // 264 and a u32 at 272 whose upper four bytes hold stale data. Flags go
+    // to out[0..8]; out[8] and out[9] record which way two brifs went, one
+    // on `i64 < 0` and one the legacy way, on the whole word at 272.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+256
+    v2 = iconst.i64 0
+    v3 = icmp slt v1, v2
+    v4 = iconst.i64 0
+    v5 = icmp sge v1, v4
+    v6 = load.f64 notrap aligned v0+264
+    v7 = f64const 0.0
+    v8 = fcmp eq v6, v7
+    v9 = fcmp ne v6, v7
+    v10 = fcmp lt v6, v7
+    v11 = fcmp ge v6, v7
+    v12 = uload32.i64 v0+272
+    v13 = iconst.i64 0
+    v14 = icmp eq v12, v13
+    v15 = iconst.i64 5
+    v16 = icmp ult v12, v15
+    v17 = load.i64 v0+24
+    store v3, v17
+    store v5, v17+1
+    store v8, v17+2
+    store v9, v17+3
+    store v10, v17+4
+    store v11, v17+5
+    store v14, v17+6
+    store v16, v17+7
+    brif v3, block1, block2
+
+block1:
+    v18 = iconst.i8 1
+    store v18, v17+8
+    jump block3
+
+block2:
+    v19 = iconst.i8 2
+    store v19, v17+8
+    jump block3
+
+block3:
+    v20 = load.i64 v0+272
+    brif v20, block4, block5
+
+block4:
+    v21 = iconst.i8 1
+    store v21, v17+9
+    return
+
+block5:
+    v22 = iconst.i8 2
+    store v22, v17+9
+    return
+}"#;
+
+    let run = |int: i64, float: f64, word: u64| {
+        let mut memory = vec![0u8; 512];
+        memory[256..264].copy_from_slice(&int.to_le_bytes());
+        memory[264..272].copy_from_slice(&float.to_le_bytes());
+        memory[272..280].copy_from_slice(&word.to_le_bytes());
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        let mut out = [0u8; 10];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        out
+    };
+
+    for int in [i64::MIN, -1, 0, 1, i64::MAX] {
+        let out = run(int, 1.0, 1);
+        assert_eq!(out[0], (int < 0) as u8, "{int} < 0");
+        assert_eq!(out[1], (int >= 0) as u8, "{int} >= 0");
+        assert_eq!(out[8], if int < 0 { 1 } else { 2 }, "branch on {int} < 0");
+    }
+
+    for float in [0.0, -0.0, 1e-300, -1e-300, f64::NAN, f64::INFINITY] {
+        let out = run(0, float, 1);
+        assert_eq!(out[2], (float == 0.0) as u8, "{float} == 0.0");
+        assert_eq!(out[3], (float != 0.0) as u8, "{float} != 0.0");
+        assert_eq!(out[4], (float < 0.0) as u8, "{float} < 0.0");
+        assert_eq!(out[5], (float >= 0.0) as u8, "{float} >= 0.0");
+    }
+    assert_eq!(run(0, -0.0, 1)[2], 1, "-0.0 compares equal to 0.0");
+
+    // Only the low four bytes are the u32; the legacy branch still sees the
+    // stale upper half as nonzero.
+    let out = run(0, 0.0, 0xdead_beef_0000_0000);
+    assert_eq!(out[6], 1, "u32 == 0 despite stale upper bytes");
+    assert_eq!(out[7], 1, "u32 < 5");
+    assert_eq!(out[9], 1, "legacy brif on the word is taken");
+    let out = run(0, 0.0, 0xffff_fffb);
+    assert_eq!(out[6..8], [0, 0], "0xfffffffb is not below 5 unsigned");
+    assert_eq!(run(0, 0.0, 0)[9], 2, "legacy brif on zero falls through");
+}
+
+#[test]
+fn test_clif_ht_close_recycles_handles() {
+    // Creates and closes 1000 tables, then creates one more: the old handle
+    // 0 is stale by then and its close fails, while inserts and lookups go
+    // to the new table. out = [last handle, stale close rc, live tables,
+    // lookup length] as i32s.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    sig1 = (i64) -> i32 system_v
+    sig2 = (i64, i32) -> i32 system_v
+    sig3 = (i64, i64, i32, i64, i32) system_v
+    sig4 = (i64, i64, i32, i64) -> i32 system_v
+    fn0 = %cl_ht_init sig0
+    fn1 = %ht_create sig1
+    fn2 = %ht_close sig2
+    fn3 = %ht_insert sig3
+    fn4 = %ht_lookup sig4
+    fn5 = %ht_handle_count sig1
+    fn6 = %cl_ht_cleanup sig0
+block0(v0: i64):
+    call fn0(v0)
+    v1 = load.i64 v0
+    v2 = iconst.i64 0
+    jump block1(v2)
+
+block1(v3: i64):
+    v4 = call fn1(v1)
+    v5 = call fn2(v1, v4)
+    v6 = iadd_imm v3, 1
+    v7 = icmp_imm ult v6, 1000
+    brif v7, block1(v6), block2
+
+block2:
+    v8 = call fn1(v1)
+    v9 = iconst.i32 0
+    v10 = call fn2(v1, v9)
+    v11 = iadd_imm v0, 256
+    v12 = iconst.i32 1
+    v13 = iadd_imm v0, 264
+    v14 = iconst.i32 5
+    call fn3(v1, v11, v12, v13, v14)
+    v15 = iadd_imm v0, 272
+    v16 = call fn4(v1, v11, v12, v15)
+    v17 = call fn5(v1)
+    v18 = load.i64 v0+24
+    store v8, v18
+    store v10, v18+4
+    store v17, v18+8
+    store v16, v18+12
+    call fn6(v0)
+    return
+}"#;
+
+    let mut memory = vec![0u8; 512];
+    memory[256] = b'k';
+    memory[264..269].copy_from_slice(b"value");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| i32::from_le_bytes(out[i * 4..i * 4 + 4].try_into().unwrap());
+    assert_eq!(word(0) & 0xffff, 0, "the freed slot is reused");
+    assert_ne!(word(0), 0, "under a new generation");
+    assert_eq!(word(1), -1, "stale handle 0 no longer closes anything");
+    assert_eq!(word(2), 1, "one live table");
+    assert_eq!(word(3), 5, "lookup reaches the new table");
+}
+
+#[test]
+fn test_clif_approx_selects_function() {
+    // Calls cl_approx for each function on the f64 at 256, with the Newton
+    // step count at 264, and stores the seven results to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, f64, i64) -> f64 system_v
+    fn0 = %cl_approx sig0
+block0(v0: i64):
+    v1 = load.f64 v0+256
+    v2 = load.i64 v0+264
+    v3 = load.i64 v0+24
+    v4 = iconst.i64 0
+    jump block1(v4)
+
+block1(v5: i64):
+    v6 = call fn0(v5, v1, v2)
+    v7 = ishl_imm v5, 3
+    v8 = iadd v3, v7
+    store v6, v8
+    v9 = iadd_imm v5, 1
+    v10 = icmp_imm ult v9, 7
+    brif v10, block1(v9), block2
+
+block2:
+    return
+}"#;
+
+    let run = |x: f64, iters: i64| {
+        let mut memory = vec![0u8; 512];
+        memory[256..264].copy_from_slice(&x.to_le_bytes());
+        memory[264..272].copy_from_slice(&iters.to_le_bytes());
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        let mut out = [0u8; 56];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        out.chunks_exact(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    };
+
+    let x = 16.0f64;
+    assert_eq!(
+        run(x, 0),
+        [4.0, 0.25, x.exp(), x.ln(), x.sin(), x.cos(), 0.0625]
+    );
+    let newton = run(2.0, 4);
+    assert!((newton[0] - 2f64.sqrt()).abs() < 1e-15);
+    assert!((newton[1] - 0.5f64.sqrt()).abs() < 1e-15);
+    assert_eq!(newton[2], 2f64.exp(), "libm ignores the step count");
+
+    let negative = run(-1.0, 0);
+    assert!(negative[0].is_nan() && negative[3].is_nan());
+    assert_eq!(run(0.0, 0)[3], f64::NEG_INFINITY);
+    assert_eq!(run(1000.0, 0)[2], f64::INFINITY);
+}
+
+#[test]
+fn test_clif_random_weighted_counts() {
+    // Draws 4000 indices from the weights [1, 0, 3] at 256 and counts them
+    // in out[0..3] as u64s; out[3] is the result for the all-zero weights
+    // at 288.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64) -> i64 system_v
+    fn0 = %cl_random_weighted sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 0
+    jump block1(v2)
+
+block1(v3: i64):
+    v4 = iconst.i64 256
+    v5 = iconst.i64 3
+    v6 = call fn0(v0, v4, v5)
+    v7 = ishl_imm v6, 3
+    v8 = iadd v1, v7
+    v9 = load.i64 v8
+    v10 = iadd_imm v9, 1
+    store v10, v8
+    v11 = iadd_imm v3, 1
+    v12 = icmp_imm ult v11, 4000
+    brif v12, block1(v11), block2
+
+block2:
+    v13 = iconst.i64 288
+    v14 = iconst.i64 2
+    v15 = call fn0(v0, v13, v14)
+    store v15, v1+24
+    return
+}"#;
+    let mut memory = vec![0u8; 512];
+    for (i, w) in [1.0f64, 0.0, 3.0].iter().enumerate() {
+        memory[256 + 8 * i..264 + 8 * i].copy_from_slice(&w.to_le_bytes());
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    base.set_random_seed(Some(9));
+    let mut run = || {
+        let mut out = [0u8; 32];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        out.chunks_exact(8)
+            .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>()
+    };
+    let counts = run();
+    assert_eq!(counts[0] + counts[2], 4000);
+    assert_eq!(counts[1], 0);
+    assert!((800..1200).contains(&counts[0]), "{counts:?}");
+    assert_eq!(counts[3], -1);
+    assert_eq!(run(), counts, "seeded runs repeat");
+}
+
+#[test]
+fn test_clif_arena_bump_reuses_offsets_each_iteration() {
+    // Three iterations each take 24 and 8 bytes from the 64-byte arena at
+    // 512, store the iteration number in the second allocation, and reset:
+    // out = [a, b] offsets per iteration. Then a 40-byte allocation c, a
+    // 32-byte one that no longer fits: out[6..10] = [c, d, status, c+24].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) -> i64 system_v
+    fn0 = %cl_arena_bump sig0
+    sig1 = () system_v
+    fn1 = %cl_arena_reset sig1
+    sig2 = () -> i64 system_v
+    fn2 = %cl_last_status sig2
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 0
+    jump block1(v2)
+
+block1(v3: i64):
+    v4 = iconst.i64 24
+    v5 = call fn0(v4)
+    v6 = iconst.i64 8
+    v7 = call fn0(v6)
+    store v3, v7
+    v8 = isub v5, v0
+    v9 = isub v7, v0
+    v10 = ishl_imm v3, 4
+    v11 = iadd v1, v10
+    store v8, v11
+    store v9, v11+8
+    call fn1()
+    v12 = iadd_imm v3, 1
+    v13 = icmp_imm ult v12, 3
+    brif v13, block1(v12), block2
+
+block2:
+    v14 = iconst.i64 40
+    v15 = call fn0(v14)
+    v16 = isub v15, v0
+    store v16, v1+48
+    v17 = iconst.i64 32
+    v18 = call fn0(v17)
+    store v18, v1+56
+    v19 = call fn2()
+    store v19, v1+64
+    v20 = load.i64 v15+24
+    store v20, v1+72
+    return
+}"#;
+    let setup = cranelift_config(vec![0u8; 512], clif_ir.to_string()).with_arena(512, 64);
+    let mut base = Base::new(setup).unwrap();
+    for _ in 0..2 {
+        let mut out = [0u8; 80];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+        let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+        for i in 0..3 {
+            assert_eq!((word(2 * i), word(2 * i + 1)), (512, 536), "iteration {i}");
+        }
+        assert_eq!(word(6), 512, "each execution starts with an empty arena");
+        assert_eq!(word(7), 0);
+        let status = word(8);
+        assert_eq!(
+            base_types::status::status(status),
+            base_types::status::TOO_LARGE
+        );
+        assert_eq!(base_types::status::payload(status), 24, "bytes still free");
+        assert_eq!(word(9), 2, "reset leaves the last iteration's value");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_clif_path_sandbox() {
+    // Writes 4 bytes to the paths at 256, 512 and 768 (relative, absolute
+    // outside the sandbox, symlink leading outside): out = [rc, status] as
+    // i64 pairs. Without allowed prefixes, only the working directory
+    // applies and all three writes go through.
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("root");
+    fs::create_dir(&root).unwrap();
+    let outside = temp_dir.path().join("outside.bin");
+    std::os::unix::fs::symlink(&outside, root.join("link.bin")).unwrap();
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+    sig1 = () -> i64 system_v
+    fn1 = %cl_last_status sig1
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 0
+    jump block1(v2)
+
+block1(v3: i64):
+    v4 = imul_imm v3, 256
+    v5 = iadd_imm v4, 256
+    v6 = iconst.i64 1024
+    v7 = iconst.i64 0
+    v8 = iconst.i64 4
+    v9 = call fn0(v0, v5, v6, v7, v8)
+    v10 = call fn1()
+    v11 = ishl_imm v3, 4
+    v12 = iadd v1, v11
+    store v9, v12
+    store v10, v12+8
+    v13 = iadd_imm v3, 1
+    v14 = icmp_imm ult v13, 3
+    brif v14, block1(v13), block2
+
+block2:
+    return
+}"#;
+
+    let mut memory = vec![0u8; 2048];
+    let outside_str = format!("{}\0", outside.to_str().unwrap());
+    for (off, path) in [(256, "out.bin\0"), (512, &outside_str), (768, "link.bin\0")] {
+        memory[off..off + path.len()].copy_from_slice(path.as_bytes());
+    }
+    memory[1024..1028].copy_from_slice(b"data");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    base.set_path_sandbox(Some(base::PathSandbox {
+        working_dir: Some(root.clone()),
+        allowed_path_prefixes: Some(vec![root.clone()]),
+    }));
+    let mut out = [0u8; 48];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = |i: usize| u64::from_le_bytes(out[i * 8..i * 8 + 8].try_into().unwrap());
+
+    assert_eq!(word(0), 4);
+    assert_eq!(fs::read(root.join("out.bin")).unwrap(), b"data");
+    for i in [2, 4] {
+        assert_eq!(word(i) as i64, -1);
+        let status = base_types::status::status(word(i + 1));
+        assert_eq!(status, base_types::status::PATH_DENIED);
+    }
+    assert!(!outside.exists());
+
+    base.set_path_sandbox(Some(base::PathSandbox {
+        working_dir: Some(root.clone()),
+        allowed_path_prefixes: None,
+    }));
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!(fs::read(&outside).unwrap(), b"data");
+}
+
+#[test]
+fn test_clif_io_log_records_and_replays_file_io() {
+    // Reads the whole file named at 256 into 1024, then writes what it read
+    // to the file named at 512: out = [read rc, write rc, first 8 bytes].
+    let temp_dir = TempDir::new().unwrap();
+    let input = temp_dir.path().join("in.bin");
+    let output = temp_dir.path().join("out.bin");
+    let log = temp_dir.path().join("io.log");
+    fs::write(&input, b"recorded input").unwrap();
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_read sig0
+    fn1 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = iconst.i64 256
+    v3 = iconst.i64 512
+    v4 = iconst.i64 1024
+    v5 = iconst.i64 0
+    v6 = call fn0(v0, v2, v4, v5, v5)
+    v7 = call fn1(v0, v3, v4, v5, v6)
+    v8 = load.i64 v0+1024
+    store v6, v1
+    store v7, v1+8
+    store v8, v1+16
+    return
+}"#;
+    let run = |out_path: &std::path::Path, io_log: base::IoLog| {
+        let mut memory = vec![0u8; 2048];
+        for (off, path) in [(256, &input), (512, &out_path.to_path_buf())] {
+            let path = format!("{}\0", path.to_str().unwrap());
+            memory[off..off + path.len()].copy_from_slice(path.as_bytes());
+        }
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        base.set_io_log(Some(io_log));
+        let mut out = [0u8; 24];
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .map(|_| out)
+    };
+
+    let recorded = run(&output, base::IoLog::Record(log.clone())).unwrap();
+    assert_eq!(&recorded[..8], &14i64.to_le_bytes());
+    assert_eq!(&recorded[16..], b"recorded");
+    assert_eq!(fs::read(&output).unwrap(), b"recorded input");
+
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+    let replayed = run(&output, base::IoLog::Replay(log.clone())).unwrap();
+    assert_eq!(replayed, recorded);
+    assert!(!output.exists(), "replayed writes are only checked");
+
+    let other = temp_dir.path().join("other.bin");
+    match run(&other, base::IoLog::Replay(log)) {
+        Err(base::Error::Execution(msg)) => assert!(msg.contains("cl_file_write"), "{msg}"),
+        other => panic!("expected a divergence, got {other:?}"),
+    }
+    assert!(!other.exists());
+}
+
+#[test]
+fn test_clif_io_log_replay_refuses_unlogged_io() {
+    // Atomically writes the 4 bytes at 1024 to the file named at 256; the
+    // log does not cover cl_file_write_atomic.
+    let temp_dir = TempDir::new().unwrap();
+    let output = temp_dir.path().join("out.bin");
+    let log = temp_dir.path().join("io.log");
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write_atomic sig0
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 1024
+    v3 = iconst.i64 4
+    v4 = call fn0(v0, v1, v2, v3)
+    return
+}"#;
+    let run = |io_log: base::IoLog| {
+        let mut memory = vec![0u8; 2048];
+        let path = format!("{}\0", output.to_str().unwrap());
+        memory[256..256 + path.len()].copy_from_slice(path.as_bytes());
+        memory[1024..1028].copy_from_slice(b"data");
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        base.set_io_log(Some(io_log));
+        base.execute(&cranelift_algorithm(0), &[])
+    };
+
+    run(base::IoLog::Record(log.clone())).unwrap();
+    assert_eq!(fs::read(&output).unwrap(), b"data");
+    fs::remove_file(&output).unwrap();
+    match run(base::IoLog::Replay(log)) {
+        Err(base::Error::Execution(msg)) => {
+            assert!(msg.contains("cl_file_write_atomic"), "{msg}")
+        }
+        other => panic!("expected a divergence, got {other:?}"),
+    }
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_clif_record_driven_mem_copy() {
+    // Mirrors the Lean forEachRecord / recordField emitters: one cl_mem_copy
+    // call in a loop over 100 records {dst_off, src_off, size} at 4096, each
+    // copying a 16-byte block from the source area at 8192 to a scattered
+    // destination in 12288..16288, which is then copied word by word to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_copy sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 4096
+    v2 = iconst.i64 100
+    v3 = iconst.i64 24
+    v4 = iconst.i64 0
+    jump block1(v4)
+
+block1(v5: i64):
+    v6 = icmp ult v5, v2
+    brif v6, block2(v5), block3(v4)
+
+block2(v7: i64):
+    v8 = imul v7, v3
+    v9 = iadd v1, v8
+    v10 = load.i64 v9
+    v11 = iadd_imm v9, 8
+    v12 = load.i64 v11
+    v13 = iadd_imm v9, 16
+    v14 = load.i64 v13
+    v15 = iconst.i64 1
+    v16 = call fn0(v0, v10, v12, v14, v15)
+    v17 = iadd_imm v7, 1
+    jump block1(v17)
+
+block3(v18: i64):
+    v19 = iadd v0, v18
+    v20 = load.i64 v19+12288
+    v21 = load.i64 v0+24
+    v22 = iadd v21, v18
+    store v20, v22
+    v23 = iadd_imm v18, 8
+    v24 = icmp_imm ult v23, 4000
+    brif v24, block3(v23), block4
+
+block4:
+    return
+}"#;
+
+    let mut memory = vec![0u8; 16384];
+    let dst = |i: usize| 12288 + (i * 37 % 100) * 40;
+    for i in 0..100 {
+        let rec = 4096 + i * 24;
+        for (k, v) in [dst(i), 8192 + i * 16, 16].into_iter().enumerate() {
+            memory[rec + k * 8..rec + k * 8 + 8].copy_from_slice(&(v as u64).to_le_bytes());
+        }
+        memory[8192 + i * 16..8192 + i * 16 + 16].fill(i as u8 + 1);
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = vec![0u8; 4000];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+
+    for i in 0..100 {
+        let at = dst(i) - 12288;
+        assert_eq!(out[at..at + 16], [i as u8 + 1; 16], "block {i}");
+        assert_eq!(out[at + 16..at + 40], [0; 24], "gap after block {i}");
+    }
+}
+
+#[test]
+fn test_clif_memory_handle_feeds_waiting_execution() {
+    // The algorithm publishes 7 at 248, then blocks until the host sets the
+    // flag at 256 and doubles the value the host wrote at 264 into out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64) -> i64 system_v
+    fn0 = %cl_thread_wake sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_wait_until sig1
+block0(v0: i64):
+    v1 = iadd_imm v0, 248
+    v2 = iconst.i64 7
+    v3 = call fn0(v1, v2)
+    v4 = iadd_imm v0, 256
+    v5 = iconst.i64 1
+    v6 = iconst.i64 0
+    v7 = call fn1(v4, v5, v6)
+    v8 = load.i64 v0+264
+    v9 = iadd v8, v8
+    v10 = load.i64 v0+24
+    store.i64 v9, v10
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 512], clif_ir.to_string())).unwrap();
+    let handle = base.memory_handle();
+    assert_eq!(handle.len(), 512);
+    let mut out = [0u8; 8];
+    std::thread::scope(|s| {
+        let host = handle.clone();
+        s.spawn(move || {
+            while host.read_u64(248).unwrap() != 7 {
+                std::thread::yield_now();
+            }
+            host.write(264, &21u64.to_le_bytes()).unwrap();
+            host.write_u64(256, 1).unwrap();
+        });
+        base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+            .unwrap();
+    });
+    assert_eq!(u64::from_le_bytes(out), 42);
+    assert_eq!(handle.read(264, 8).unwrap(), 21u64.to_le_bytes());
+    assert!(matches!(
+        handle.read(510, 8),
+        Err(base::Error::MemoryOutOfBounds { .. })
+    ));
+
+    drop(base);
+    assert!(matches!(
+        handle.read_u64(256),
+        Err(base::Error::MemoryReleased)
+    ));
+    assert!(matches!(
+        handle.write(0, &[1]),
+        Err(base::Error::MemoryReleased)
+    ));
+}
+
+#[test]
+fn test_clif_pool_fence_orders_cross_worker_handoff() {
+    // 1000 rounds on a 4-worker pool: main stores the round number at 256,
+    // fn 1 writes 3x it to 264 (then an inline `fence`), and fn 2, queued
+    // behind a pool fence, adds 264 to the total at 272. Main waits on a
+    // second fence's flag at 200 before the next round; every access to the
+    // words is a plain load or store.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_submit sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_pool_fence sig3
+    fn4 = %cl_thread_wait_until sig3
+    fn5 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 4
+    v4 = call fn1(v2, v3)
+    v5 = iconst.i64 0
+    jump block1(v5)
+
+block1(v6: i64):
+    store.i64 v6, v0+256
+    v7 = iconst.i64 1
+    v8 = iadd_imm v0, 256
+    v9 = call fn2(v2, v4, v7, v8)
+    v10 = iadd_imm v0, 192
+    v11 = call fn3(v2, v4, v10)
+    v12 = iconst.i64 2
+    v13 = call fn2(v2, v4, v12, v8)
+    v14 = iadd_imm v0, 200
+    v15 = call fn3(v2, v4, v14)
+    v16 = iconst.i64 0
+    v17 = call fn4(v14, v7, v16)
+    v18 = iadd_imm v6, 1
+    v19 = icmp_imm ult v18, 1000
+    brif v19, block1(v18), block2
+
+block2:
+    call fn5(v1)
+    v20 = load.i64 v0+24
+    v21 = load.i64 v0+272
+    store.i64 v21, v20
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = imul_imm v1, 3
+    store.i64 v2, v0+8
+    fence
+    return
+}
+
+function u0:2(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+8
+    v2 = load.i64 v0+16
+    v3 = iadd v1, v2
+    store.i64 v3, v0+16
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 512], clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 8];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!(u64::from_le_bytes(out), 3 * (0..1000u64).sum::<u64>());
+}
+
+#[test]
+fn test_clif_file_handle_appends_records() {
+    // Open the path at 1024 once for append, write 100 8-byte records by
+    // handle (the loop counter, staged at 2048), and close it. The path-based
+    // cl_file_write then writes "done" from 3000 to the path at 1536.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_file_handle_init sig0
+    fn1 = %cl_file_handle_cleanup sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn2 = %cl_file_open sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn3 = %cl_file_close sig2
+    sig3 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn4 = %cl_file_write_handle sig3
+    fn5 = %cl_file_write sig3
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 v0+64
+    v3 = iadd_imm v0, 1024
+    v4 = iconst.i64 12
+    v5 = call fn2(v2, v3, v4)
+    v6 = iadd_imm v0, 2048
+    v7 = iconst.i64 -1
+    v8 = iconst.i64 8
+    v9 = iconst.i64 0
+    jump block1(v9)
+
+block1(v10: i64):
+    store v10, v0+2048
+    v11 = call fn4(v2, v5, v6, v7, v8)
+    v12 = iadd_imm v10, 1
+    v13 = icmp_imm ult v12, 100
+    brif v13, block1(v12), block2
+
+block2:
+    v14 = call fn3(v2, v5)
+    call fn1(v1)
+    v15 = iconst.i64 1536
+    v16 = iconst.i64 3000
+    v17 = iconst.i64 4
+    v18 = call fn5(v0, v15, v16, v9, v17)
+    v19 = load.i64 v0+24
+    store v14, v19
+    store v18, v19+8
+    return
+}"#;
+    let dir = TempDir::new().unwrap();
+    let records = dir.path().join("records.bin");
+    let done = dir.path().join("done.txt");
+    let mut memory = vec![0u8; 4096];
+    for (off, path) in [(1024, &records), (1536, &done)] {
+        let path = path.to_str().unwrap();
+        memory[off..off + path.len()].copy_from_slice(path.as_bytes());
+    }
+    memory[3000..3004].copy_from_slice(b"done");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    assert_eq!(&out[..8], &0i64.to_le_bytes(), "close result");
+    assert_eq!(&out[8..], &4i64.to_le_bytes(), "path-based write");
+    let expected: Vec<u8> = (0..100u64).flat_map(|i| i.to_le_bytes()).collect();
+    assert_eq!(fs::read(&records).unwrap(), expected);
+    assert_eq!(fs::read(&done).unwrap(), b"done");
+}
+
+#[test]
+fn test_clif_atomic_file_commit_or_keep_previous() {
+    // fn 0 writes one chunk, then a second write fails (negative offset) and
+    // it bails out to cleanup without committing; fn 1 writes both chunks,
+    // out of order, and commits. Path at 1024, chunks at 2048 and 2058; the
+    // last call's result goes to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_file_atomic_init sig0
+    fn1 = %cl_file_atomic_cleanup sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn2 = %cl_file_atomic_open sig1
+    fn3 = %cl_file_commit sig1
+    sig2 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn4 = %cl_file_atomic_write sig2
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 v0+64
+    v3 = iadd_imm v0, 1024
+    v4 = call fn2(v2, v3)
+    v5 = iadd_imm v0, 2048
+    v6 = iconst.i64 0
+    v7 = iconst.i64 10
+    v8 = call fn4(v2, v4, v5, v6, v7)
+    v9 = iconst.i64 -1
+    v10 = iadd_imm v0, 2058
+    v11 = iconst.i64 9
+    v12 = call fn4(v2, v4, v10, v9, v11)
+    v13 = icmp_imm slt v12, 0
+    brif v13, block2(v12), block1
+
+block1:
+    v14 = call fn3(v2, v4)
+    jump block2(v14)
+
+block2(v15: i64):
+    call fn1(v1)
+    v16 = load.i64 v0+24
+    store.i64 v15, v16
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_file_atomic_init sig0
+    fn1 = %cl_file_atomic_cleanup sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn2 = %cl_file_atomic_open sig1
+    fn3 = %cl_file_commit sig1
+    sig2 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn4 = %cl_file_atomic_write sig2
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 v0+64
+    v3 = iadd_imm v0, 1024
+    v4 = call fn2(v2, v3)
+    v5 = iadd_imm v0, 2058
+    v6 = iconst.i64 10
+    v7 = iconst.i64 9
+    v8 = call fn4(v2, v4, v5, v6, v7)
+    v9 = iadd_imm v0, 2048
+    v10 = iconst.i64 0
+    v11 = call fn4(v2, v4, v9, v10, v6)
+    v12 = call fn3(v2, v4)
+    call fn1(v1)
+    v13 = load.i64 v0+24
+    store.i64 v12, v13
+    return
+}"#;
+
+    let dir = TempDir::new().unwrap();
+    let dest = dir.path().join("result.lz4");
+    fs::write(&dest, b"previous").unwrap();
+    let path = dest.to_str().unwrap();
+    let mut memory = vec![0u8; 4096];
+    memory[1024..1024 + path.len()].copy_from_slice(path.as_bytes());
+    memory[2048..2067].copy_from_slice(b"chunk-one|chunk-two");
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let names = || -> Vec<_> {
+        fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect()
+    };
+
+    let mut out = [0u8; 8];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!(i64::from_le_bytes(out), -1);
+    assert_eq!(fs::read(&dest).unwrap(), b"previous");
+    assert_eq!(names(), ["result.lz4"], "temporary file removed");
+
+    base.execute_into(&cranelift_algorithm(1), &[], &mut out)
+        .unwrap();
+    assert_eq!(i64::from_le_bytes(out), 0);
+    assert_eq!(fs::read(&dest).unwrap(), b"chunk-one|chunk-two");
+    assert_eq!(names(), ["result.lz4"]);
+}
+
+#[test]
+fn test_clif_branch_on_f64_compare_until_threshold() {
+    // Mirrors the Lean doWhile2 emitter: x *= 0.7 and n += 1 until x drops
+    // below 1e-3, branching on the fcmp result directly. Stores n and x.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = f64const 0x1.0p0
+    v2 = iconst.i64 0
+    jump block1(v1, v2)
+
+block1(v3: f64, v4: i64):
+    v5 = f64const 0x1.6666666666666p-1
+    v6 = fmul v3, v5
+    v7 = iadd_imm v4, 1
+    v8 = f64const 0x1.0624dd2f1a9fcp-10
+    v9 = fcmp ge v6, v8
+    brif v9, block1(v6, v7), block2(v6, v7)
+
+block2(v10: f64, v11: i64):
+    v12 = load.i64 v0+24
+    store.i64 v11, v12
+    store.f64 v10, v12+8
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 256], clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+
+    let (mut x, mut n) = (1.0f64, 0u64);
+    loop {
+        x *= 0.7;
+        n += 1;
+        if x < 1e-3 {
+            break;
+        }
+    }
+    assert_eq!(n, 20);
+    assert_eq!(u64::from_le_bytes(out[..8].try_into().unwrap()), n);
+    assert_eq!(f64::from_le_bytes(out[8..].try_into().unwrap()), x);
+}
+
+#[test]
+fn test_clif_thread_pool_copy_jobs() {
+    // 1000 jobs through a 4-worker pool: job i copies the u64 at
+    // 1024 + 8*i to 9024 + 8*i. The regions are then compared with
+    // cl_mem_compare (-1 when equal) and the result written to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_submit sig2
+    sig3 = (i64, i64) -> i64 system_v
+    fn3 = %cl_thread_pool_wait sig3
+    sig4 = (i64, i64) -> i64 system_v
+    fn4 = %cl_thread_pool_stop sig4
+    sig5 = (i64) system_v
+    fn5 = %cl_thread_cleanup sig5
+    sig6 = (i64, i64, i64, i64) -> i64 system_v
+    fn6 = %cl_mem_compare sig6
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 4
+    v4 = call fn1(v2, v3)
+    v5 = iconst.i64 1
+    v6 = iconst.i64 0
+    jump block1(v6)
+
+block1(v7: i64):
+    v8 = ishl_imm v7, 3
+    v9 = iadd v0, v8
+    v10 = iadd_imm v9, 1024
+    v11 = call fn2(v2, v4, v5, v10)
+    v12 = iadd_imm v7, 1
+    v13 = icmp_imm ult v12, 1000
+    brif v13, block1(v12), block2
+
+block2:
+    v14 = call fn3(v2, v4)
+    v15 = call fn4(v2, v4)
+    call fn5(v1)
+    v16 = iconst.i64 1024
+    v17 = iconst.i64 9024
+    v18 = iconst.i64 8000
+    v19 = call fn6(v0, v16, v17, v18)
+    v20 = load.i64 v0+24
+    store.i64 v19, v20
+    store.i64 v14, v20+8
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    store.i64 v1, v0+8000
+    return
+}"#;
+
+    let mut memory = vec![0u8; 17_024];
+    for i in 0..1000u64 {
+        let off = 1024 + 8 * i as usize;
+        memory[off..off + 8].copy_from_slice(&(i * 3 + 1).to_le_bytes());
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    assert_eq!(i64::from_le_bytes(out[0..8].try_into().unwrap()), -1);
+    assert_eq!(i64::from_le_bytes(out[8..16].try_into().unwrap()), 0);
+}
+
+#[test]
+fn test_clif_bounded_pool_try_submit_retries() {
+    // Same copy jobs as above through a 2-worker pool that queues at most 4,
+    // submitted with cl_thread_pool_try_submit: a -2 (queue full) result
+    // branches back and retries the same job. out = [compare, wait result].
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start_bounded sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_try_submit sig2
+    sig3 = (i64, i64) -> i64 system_v
+    fn3 = %cl_thread_pool_wait sig3
+    fn4 = %cl_thread_pool_stop sig3
+    fn5 = %cl_thread_cleanup sig0
+    fn6 = %cl_mem_compare sig2
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 2
+    v4 = iconst.i64 4
+    v5 = call fn1(v2, v3, v4)
+    v6 = iconst.i64 1
+    v7 = iconst.i64 0
+    jump block1(v7)
+
+block1(v8: i64):
+    v9 = ishl_imm v8, 3
+    v10 = iadd v0, v9
+    v11 = iadd_imm v10, 1024
+    v12 = call fn2(v2, v5, v6, v11)
+    brif v12, block1(v8), block3
+
+block3:
+    v13 = iadd_imm v8, 1
+    v14 = icmp_imm ult v13, 1000
+    brif v14, block1(v13), block2
+
+block2:
+    v15 = call fn3(v2, v5)
+    v16 = call fn4(v2, v5)
+    call fn5(v1)
+    v17 = iconst.i64 1024
+    v18 = iconst.i64 9024
+    v19 = iconst.i64 8000
+    v20 = call fn6(v0, v17, v18, v19)
+    v21 = load.i64 v0+24
+    store.i64 v20, v21
+    store.i64 v15, v21+8
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    store.i64 v1, v0+8000
+    return
+}"#;
+
+    let mut memory = vec![0u8; 17_024];
+    for i in 0..1000u64 {
+        let off = 1024 + 8 * i as usize;
+        memory[off..off + 8].copy_from_slice(&(i * 3 + 1).to_le_bytes());
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    assert_eq!(i64::from_le_bytes(out[0..8].try_into().unwrap()), -1);
+    assert_eq!(i64::from_le_bytes(out[8..16].try_into().unwrap()), 0);
+}
+
+#[test]
+fn test_clif_wait_until_wake_handshake() {
+    // Producer/consumer handshake with no busy-waiting. The consumer (fn 1)
+    // waits for flag 256 == 1, doubles the u64 at 264 into 272, then wakes
+    // flag 280 with 2. Main writes the input, wakes 256, and waits on 280.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_spawn sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_join sig2
+    sig3 = (i64) system_v
+    fn3 = %cl_thread_cleanup sig3
+    sig4 = (i64, i64, i64) -> i64 system_v
+    fn4 = %cl_thread_wait_until sig4
+    sig5 = (i64, i64) -> i64 system_v
+    fn5 = %cl_thread_wake sig5
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3, v0)
+    v5 = iconst.i64 21
+    store.i64 v5, v0+264
+    v6 = iadd_imm v0, 256
+    v7 = call fn5(v6, v3)
+    v8 = iadd_imm v0, 280
+    v9 = iconst.i64 2
+    v10 = call fn4(v8, v9, v9)
+    v11 = load.i64 v0+272
+    v12 = call fn2(v2, v4)
+    call fn3(v1)
+    v13 = load.i64 v0+24
+    store.i64 v11, v13
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64, i64) -> i64 system_v
+    fn0 = %cl_thread_wait_until sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_wake sig1
+block0(v0: i64):
+    v1 = iadd_imm v0, 256
+    v2 = iconst.i64 1
+    v3 = iconst.i64 0
+    v4 = call fn0(v1, v2, v3)
+    v5 = load.i64 v0+264
+    v6 = iadd v5, v5
+    store.i64 v6, v0+272
+    v7 = iadd_imm v0, 280
+    v8 = iconst.i64 2
+    v9 = call fn1(v7, v8)
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 512], clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 8];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    assert_eq!(u64::from_le_bytes(out), 42);
+}
+
+#[test]
+fn test_clif_queue_producer_consumer() {
+    // A spawned producer (fn 1) pushes the u64s 1..=1000 through an 8-slot
+    // ring at 512, blocking when full. Main pops until it has seen 1000
+    // packets and writes their sum.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_spawn sig1
+    sig2 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_join sig2
+    sig3 = (i64) system_v
+    fn3 = %cl_thread_cleanup sig3
+    sig4 = (i64, i64, i64, i64) -> i64 system_v
+    fn4 = %cl_queue_init sig4
+    sig5 = (i64, i64, i64) -> i64 system_v
+    fn5 = %cl_queue_pop sig5
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = iconst.i64 512
+    v3 = iconst.i64 8
+    v4 = call fn4(v0, v2, v3, v3)
+    v5 = load.i64 notrap aligned v0+64
+    v6 = iconst.i64 1
+    v7 = call fn1(v5, v6, v0)
+    v8 = iconst.i64 0
+    jump block1(v8, v8)
+
+block1(v10: i64, v11: i64):
+    v12 = iconst.i64 320
+    v13 = call fn5(v0, v2, v12)
+    brif v13, block2, block1(v10, v11)
+
+block2:
+    v14 = load.i64 v0+320
+    v15 = iadd v11, v14
+    v16 = iadd_imm v10, 1
+    v17 = icmp_imm slt v16, 1000
+    brif v17, block1(v16, v15), block3
+
+block3:
+    v18 = call fn2(v5, v7)
+    call fn3(v1)
+    v19 = load.i64 v0+24
+    store.i64 v15, v19
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_queue_push sig0
+block0(v0: i64):
+    v1 = iconst.i64 1
+    jump block1(v1)
+
+block1(v2: i64):
+    store.i64 v2, v0+304
+    v3 = iconst.i64 512
+    v4 = iconst.i64 304
+    v5 = iconst.i64 8
+    v6 = iconst.i64 1
+    v7 = call fn0(v0, v3, v4, v5, v6)
+    v8 = iadd_imm v2, 1
+    v9 = icmp_imm sle v8, 1000
+    brif v9, block1(v8), block2
+
+block2:
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 1024], clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 8];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out).unwrap();
+    assert_eq!(u64::from_le_bytes(out), 500_500);
+}
+
+#[test]
+fn test_clif_cancel_stops_blocked_wait() {
+    // Waits on a flag at 256 that is never set, then spins until
+    // cl_cancelled reports the cancellation.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64) -> i64 system_v
+    fn0 = %cl_thread_wait_until sig0
+    sig1 = () -> i64 system_v
+    fn1 = %cl_cancelled sig1
+block0(v0: i64):
+    v1 = iadd_imm v0, 256
+    v2 = iconst.i64 1
+    v3 = iconst.i64 0
+    v4 = call fn0(v1, v2, v3)
+    jump block1
+
+block1:
+    v5 = call fn1()
+    brif v5, block2, block1
+
+block2:
+    return
+}"#;
+
+    let mut base = Base::new(cranelift_config(vec![0u8; 512], clif_ir.to_string())).unwrap();
+    let handle = base.cancel_handle();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        handle.cancel();
+        tx.send(std::time::Instant::now()).unwrap();
+    });
+
+    let result = base.execute(&cranelift_algorithm(0), &[]);
+    let returned = std::time::Instant::now();
+    canceller.join().unwrap();
+    let cancelled_at = rx.recv().unwrap();
+    assert!(matches!(result, Err(base::Error::Cancelled)));
+    assert!(returned.duration_since(cancelled_at) < std::time::Duration::from_millis(50));
+
+    // The cancel was consumed: the next execution runs normally once the
+    // flag is set.
+    let ok_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}"#;
+    let mut base = Base::new(cranelift_config(vec![0u8; 64], ok_ir.to_string())).unwrap();
+    let handle = base.cancel_handle();
+    handle.cancel();
+    assert!(matches!(
+        base.execute(&cranelift_algorithm(0), &[]),
+        Err(base::Error::Cancelled)
+    ));
+    assert!(!handle.is_cancelled());
+    assert!(base.execute(&cranelift_algorithm(0), &[]).is_ok());
+}
+
+#[test]
+fn test_clif_timeout_never_leaves_truncated_file() {
+    // fn 0 writes 256 KiB then blocks on a flag nobody sets; fn 1 blocks
+    // first and only writes if the wait succeeded. Either way the deadline
+    // must yield a complete file or none at all.
+    const PAYLOAD: usize = 256 * 1024;
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_wait_until sig1
+block0(v0: i64):
+    v1 = iconst.i64 512
+    v2 = iconst.i64 4096
+    v3 = iconst.i64 0
+    v4 = iconst.i64 262144
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    v6 = iadd_imm v0, 256
+    v7 = iconst.i64 1
+    v8 = call fn1(v6, v7, v3)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+    sig1 = (i64, i64, i64) -> i64 system_v
+    fn1 = %cl_thread_wait_until sig1
+block0(v0: i64):
+    v1 = iadd_imm v0, 256
+    v2 = iconst.i64 1
+    v3 = iconst.i64 0
+    v4 = call fn1(v1, v2, v3)
+    brif v4, block2, block1
+
+block1:
+    v5 = iconst.i64 512
+    v6 = iconst.i64 4096
+    v7 = iconst.i64 262144
+    v8 = call fn0(v0, v5, v6, v3, v7)
+    return
+
+block2:
+    return
+}"#;
+
+    for fn_idx in [0, 1] {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("timeout.bin");
+        let path_str = format!("{}\0", path.to_str().unwrap());
+        let mut memory = vec![0u8; 4096 + PAYLOAD];
+        memory[512..512 + path_str.len()].copy_from_slice(path_str.as_bytes());
+        for (i, b) in memory[4096..].iter_mut().enumerate() {
+            *b = (i % 251) as u8 + 1;
+        }
+        let expected = memory[4096..].to_vec();
+
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        let limit = std::time::Duration::from_millis(100);
+        let start = std::time::Instant::now();
+        let result = base.execute_with_timeout(&cranelift_algorithm(fn_idx), &[], &mut [], limit);
+        assert!(matches!(result, Err(base::Error::Timeout(d)) if d == limit));
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+
+        match fs::read(&path) {
+            Ok(contents) => {
+                assert_eq!(fn_idx, 0, "fn 1 must not write after a cancelled wait");
+                assert_eq!(contents, expected);
+            }
+            Err(_) => assert_eq!(fn_idx, 1, "fn 0 writes before blocking"),
+        }
+
+        // The timeout's cancellation is consumed, not left for the next run.
+        assert!(!base.cancel_handle().is_cancelled());
+    }
+}
+
+#[test]
+fn test_clif_branch_on_file_write_status() {
+    // Writes into a directory that does not exist, then branches on the low
+    // 32 bits of cl_last_status: out = [status word, 1 if failed else 2].
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("no-such-dir").join("out.bin");
+    let path_str = format!("{}\0", path.to_str().unwrap());
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+    sig1 = () -> i64 system_v
+    fn1 = %cl_last_status sig1
+block0(v0: i64):
+    v1 = iconst.i64 512
+    v2 = iconst.i64 1024
+    v3 = iconst.i64 0
+    v4 = iconst.i64 8
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    v6 = call fn1()
+    v7 = load.i64 v0+24
+    store.i64 v6, v7
+    v8 = ireduce.i32 v6
+    brif v8, block1, block2
+
+block1:
+    v9 = iconst.i64 1
+    store.i64 v9, v7+8
+    return
+
+block2:
+    v10 = iconst.i64 2
+    store.i64 v10, v7+8
+    return
+}"#;
+
+    let mut memory = vec![0u8; 2048];
+    memory[512..512 + path_str.len()].copy_from_slice(path_str.as_bytes());
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let word = u64::from_le_bytes(out[0..8].try_into().unwrap());
+    assert_eq!(base_types::status::status(word), 2, "ENOENT");
+    assert_eq!(base_types::status::payload(word), 0);
+    assert_eq!(u64::from_le_bytes(out[8..16].try_into().unwrap()), 1);
+    assert!(!path.exists());
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn test_clif_net_accept_thread_concurrent_with_client() {
+    // fn 1 runs on a spawned thread: accept on the listener handle stored at
+    // 304, receive 8 bytes and send them back doubled. Meanwhile main
+    // connects, sends 21 and writes the reply to out, all through one
+    // network context at offset 8.
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr_str = format!("127.0.0.1:{port}\0");
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_net_init sig0
+    fn1 = %cl_net_cleanup sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn2 = %cl_net_listen sig1
+    fn3 = %cl_net_connect sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn4 = %cl_net_send sig2
+    fn5 = %cl_net_recv sig2
+    fn6 = %cl_thread_init sig0
+    fn7 = %cl_thread_cleanup sig0
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn8 = %cl_thread_spawn sig3
+    sig4 = (i64, i64) -> i64 system_v
+    fn9 = %cl_thread_join sig4
+block0(v0: i64):
+    v1 = iadd_imm v0, 8
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+8
+    v3 = iadd_imm v0, 512
+    v4 = call fn2(v2, v3)
+    store.i64 v4, v0+304
+    v5 = iadd_imm v0, 64
+    call fn6(v5)
+    v6 = load.i64 notrap aligned v0+64
+    v7 = iconst.i64 1
+    v8 = call fn8(v6, v7, v0)
+    v9 = call fn3(v2, v3)
+    v10 = iconst.i64 21
+    store.i64 v10, v0+320
+    v11 = iadd_imm v0, 320
+    v12 = iconst.i64 8
+    v13 = call fn4(v2, v9, v11, v12)
+    v14 = iadd_imm v0, 328
+    v15 = call fn5(v2, v9, v14, v12)
+    v16 = call fn9(v6, v8)
+    call fn7(v5)
+    call fn1(v1)
+    v17 = load.i64 v0+328
+    v18 = load.i64 v0+24
+    store.i64 v17, v18
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = (i64, i64) -> i64 system_v
+    fn0 = %cl_net_accept sig0
+    sig1 = (i64, i64, i64, i64) -> i64 system_v
+    fn1 = %cl_net_recv sig1
+    fn2 = %cl_net_send sig1
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+8
+    v2 = load.i64 v0+304
+    v3 = call fn0(v1, v2)
+    v4 = iadd_imm v0, 400
+    v5 = iconst.i64 8
+    v6 = call fn1(v1, v3, v4, v5)
+    v7 = load.i64 v0+400
+    v8 = iadd v7, v7
+    store.i64 v8, v0+400
+    v9 = call fn2(v1, v3, v4, v5)
+    return
+}"#;
+
+    let mut memory = vec![0u8; 1024];
+    memory[512..512 + addr_str.len()].copy_from_slice(addr_str.as_bytes());
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 8];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    assert_eq!(u64::from_le_bytes(out), 42);
+}
+
+#[test]
+fn test_clif_pool_dispatch_reuses_one_fn_with_different_operands() {
+    // fn 1 copies 16 bytes from the address in its second operand word to
+    // the one in its first. Main dispatches it three times, with operand
+    // blocks at 256, 288 and 320 (completion flag, dst, src) naming sources
+    // 1024, 1040, 1056 and destinations 2048, 2064, 2080, waits on each
+    // block's flag, and returns the 48 destination bytes.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    fn1 = %cl_thread_cleanup sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_pool_dispatch sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn4 = %cl_thread_wait_until sig3
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 v0+64
+    v3 = iconst.i64 2
+    v4 = call fn2(v2, v3)
+    v5 = iconst.i64 0
+    jump block1(v5)
+
+block1(v6: i64):
+    v7 = imul_imm v6, 32
+    v8 = iadd_imm v7, 256
+    v9 = iadd v0, v8
+    v10 = imul_imm v6, 16
+    v11 = iadd v0, v10
+    v12 = iadd_imm v11, 2048
+    store.i64 v12, v9+8
+    v13 = iadd_imm v11, 1024
+    store.i64 v13, v9+16
+    v14 = iconst.i64 1
+    v15 = call fn3(v2, v4, v14, v9)
+    v16 = iadd_imm v6, 1
+    v17 = icmp_imm ult v16, 3
+    brif v17, block1(v16), block2(v5)
+
+block2(v18: i64):
+    v19 = imul_imm v18, 32
+    v20 = iadd_imm v19, 256
+    v21 = iadd v0, v20
+    v22 = iconst.i64 1
+    v23 = iconst.i64 0
+    v24 = call fn4(v21, v22, v23)
+    v25 = iadd_imm v18, 1
+    v26 = icmp_imm ult v25, 3
+    brif v26, block2(v25), block3
+
+block3:
+    call fn1(v1)
+    v27 = load.i64 v0+24
+    v28 = load.i64 v0+2048
+    store.i64 v28, v27
+    v29 = load.i64 v0+2056
+    store.i64 v29, v27+8
+    v30 = load.i64 v0+2064
+    store.i64 v30, v27+16
+    v31 = load.i64 v0+2072
+    store.i64 v31, v27+24
+    v32 = load.i64 v0+2080
+    store.i64 v32, v27+32
+    v33 = load.i64 v0+2088
+    store.i64 v33, v27+40
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = load.i64 v0+8
+    v3 = load.i64 v2
+    v4 = load.i64 v2+8
+    store.i64 v3, v1
+    store.i64 v4, v1+8
+    return
+}"#;
+
+    let mut memory = vec![0u8; 4096];
+    for (i, b) in memory[1024..1072].iter_mut().enumerate() {
+        *b = i as u8 + 1;
+    }
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    let mut out = [0u8; 48];
+    base.execute_into(&cranelift_algorithm(0), &[], &mut out)
+        .unwrap();
+    let expected: Vec<u8> = (1..=48).collect();
+    assert_eq!(out.to_vec(), expected);
+}
+
+#[test]
+fn test_clif_step_budget_stops_a_spinning_loop() {
+    // fn 0 waits on a flag at 256 that nothing sets, by spinning on a load;
+    // fn 1 sums 1..=1000 into 264; fn 2 calls fn 0 from inside a loop.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    jump block1
+
+block1:
+    v1 = load.i64 v0+256
+    brif v1, block2, block1
+
+block2:
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 0
+    v2 = iconst.i64 1
+    jump block1(v1, v2)
+
+block1(v3: i64, v4: i64):
+    v5 = iadd v3, v4
+    v6 = iadd_imm v4, 1
+    v7 = icmp_imm sle v6, 1000
+    brif v7, block1(v5, v6), block2(v5)
+
+block2(v8: i64):
+    store v8, v0+264
+    return
+}
+
+function u0:2(i64) system_v {
+    sig0 = (i64) system_v
+    fn0 = colocated u0:0 sig0
+block0(v0: i64):
+    jump block1
+
+block1:
+    call fn0(v0)
+    jump block1
+}"#;
+
+    let setup = cranelift_config(vec![0u8; 512], clif_ir.to_string());
+    let mut base = Base::new_with_step_budget(setup, 10_000).unwrap();
+    let started = std::time::Instant::now();
+    let err = base.execute(&cranelift_algorithm(0), &[]).unwrap_err();
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    let msg = err.to_string();
+    assert!(msg.contains("10000") && msg.contains("function 0"), "{msg}");
+    assert!(msg.contains("no calls"), "{msg}");
+
+    // The budget is refilled for each execution.
+    for _ in 0..3 {
+        base.execute(&cranelift_algorithm(1), &[]).unwrap();
+        assert_eq!(base.memory_handle().read(264, 8).unwrap(), 500_500u64.to_le_bytes());
+    }
+
+    // The first header to run out is reported, and the caller's loop unwinds
+    // at its own next header.
+    let msg = base
+        .execute(&cranelift_algorithm(2), &[])
+        .unwrap_err()
+        .to_string();
+    assert!(msg.contains("function 0"), "{msg}");
+    assert!(!base.cancel_handle().is_cancelled());
+
+    // A labeled function is named in the error.
+    let mut labeled = cranelift_algorithm(2).labeled("poll loop");
+    labeled.set_label(0, "wait for ready flag");
+    let msg = base.execute(&labeled, &[]).unwrap_err().to_string();
+    assert!(msg.contains("function 0 (`wait for ready flag`)"), "{msg}");
+}
+
+#[test]
+fn test_clif_step_budget_refills_for_execute_many() {
+    // fn 0 spins on a flag nothing sets; fn 1 runs 1000 loop iterations;
+    // fn 2 has no loops.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    jump block1
+
+block1:
+    v1 = load.i64 v0+256
+    brif v1, block2, block1
+
+block2:
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 0
+    jump block1(v1)
+
+block1(v2: i64):
+    v3 = iadd_imm v2, 1
+    v4 = icmp_imm slt v3, 1000
+    brif v4, block1(v3), block2
+
+block2:
+    store v3, v0+264
+    return
+}
+
+function u0:2(i64) system_v {
+block0(v0: i64):
+    v1 = iconst.i64 7
+    store v1, v0+264
+    return
+}"#;
+
+    let setup = cranelift_config(vec![0u8; 512], clif_ir.to_string());
+    let mut base = Base::new_with_step_budget(setup, 1500).unwrap();
+    let msg = base
+        .execute(&cranelift_algorithm(0), &[])
+        .unwrap_err()
+        .to_string();
+    assert!(msg.contains("function 0"), "{msg}");
+
+    // A budget spent by an earlier execution does not fail later instances.
+    let results = base.execute_many(&[cranelift_algorithm(2), cranelift_algorithm(2)], 2);
+    assert!(results.iter().all(Result::is_ok), "{results:?}");
+
+    // Each call gets the full budget: 1000 steps fit once, not twice.
+    for _ in 0..2 {
+        let results = base.execute_many(&[cranelift_algorithm(1)], 1);
+        assert!(results[0].is_ok(), "{results:?}");
+    }
+    let results = base.execute_many(&[cranelift_algorithm(1), cranelift_algorithm(1)], 1);
+    let msg = results[1].as_ref().unwrap_err().to_string();
+    assert!(msg.contains("function 1"), "{msg}");
+}
+
+#[test]
+fn test_clif_execute_nested_runs_child_in_window() {
+    // fn 0 runs the child blob at 1024 with 2048..2304 as its memory, then
+    // copies the marker the child left there to 520. fn 1 is the child.
+    // fn 2 makes a strict call with a truncated blob.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_execute_nested sig0
+block0(v0: i64):
+    v1 = load.i64 v0+256
+    v2 = iconst.i64 1024
+    v3 = iconst.i64 2048
+    v4 = iconst.i64 256
+    v5 = iconst.i64 0
+    v6 = call fn0(v0, v2, v1, v3, v4, v5)
+    store v6, v0+512
+    v7 = load.i64 v0+2176
+    store v7, v0+520
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i64 v0+192
+    v2 = iadd_imm v1, 1
+    store v2, v0+128
+    return
+}
+
+function u0:2(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_execute_nested sig0
+block0(v0: i64):
+    v1 = iconst.i64 1024
+    v2 = iconst.i64 3
+    v3 = iconst.i64 2048
+    v4 = iconst.i64 256
+    v5 = iconst.i64 1
+    v6 = call fn0(v0, v1, v2, v3, v4, v5)
+    store v6, v0+512
+    return
+}"#;
+
+    let mut child = Algorithm::new(1);
+    child.declare_symbol("seed", 192, 8).unwrap();
+    child.set_symbol_u64("seed", 0xABC).unwrap();
+    let blob = child.to_bytes();
+    let mut memory = vec![0u8; 4096];
+    memory[256..264].copy_from_slice(&(blob.len() as u64).to_le_bytes());
+    memory[1024..1024 + blob.len()].copy_from_slice(&blob);
+
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    base.execute(&cranelift_algorithm(0), &[]).unwrap();
+    let mem = base.memory_handle().read(512, 16).unwrap();
+    assert_eq!(mem[..8], 0i64.to_le_bytes());
+    assert_eq!(mem[8..], 0xABDu64.to_le_bytes(), "parent sees the child's marker");
+
+    // Strict mode turns the failed call into a stopped execution.
+    assert!(matches!(
+        base.execute(&cranelift_algorithm(2), &[]),
+        Err(base::Error::Cancelled)
+    ));
+    let mem = base.memory_handle().read(512, 8).unwrap();
+    assert_eq!(mem, (-1i64).to_le_bytes());
+}
+
+#[test]
+fn test_clif_simd_broadcast_and_lane_store() {
+    // Mirrors the Lean loadSplatF32 / loadSplatI32 and storeLane emitters:
+    // the scalar at 256 is broadcast, multiplied by the vector at 272, and
+    // each lane of the product stored to out separately, last lane first.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.f32 notrap aligned v0+256
+    v2 = splat.f32x4 v1
+    v3 = load.f32x4 notrap aligned v0+272
+    v4 = fmul v2, v3
+    v5 = load.i64 v0+24
+    v6 = extractlane v4, 3
+    store v6, v5
+    v7 = extractlane v4, 2
+    store v7, v5+4
+    v8 = extractlane v4, 1
+    store v8, v5+8
+    v9 = extractlane v4, 0
+    store v9, v5+12
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    v1 = load.i32 v0+256
+    v2 = splat.i32x4 v1
+    v3 = load.i32x4 notrap aligned v0+272
+    v4 = imul v2, v3
+    v5 = load.i64 v0+24
+    v6 = extractlane v4, 3
+    store v6, v5
+    v7 = extractlane v4, 2
+    store v7, v5+4
+    v8 = extractlane v4, 1
+    store v8, v5+8
+    v9 = extractlane v4, 0
+    store v9, v5+12
+    return
+}"#;
+
+    fn run(clif_ir: &str, fn_idx: u32, scalar: [u8; 4], vector: &[u8]) -> Vec<u8> {
+        let mut memory = vec![0u8; 512];
+        memory[256..260].copy_from_slice(&scalar);
+        memory[272..288].copy_from_slice(vector);
+        let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+        let mut out = [0u8; 16];
+        base.execute_into(&cranelift_algorithm(fn_idx), &[], &mut out)
+            .unwrap();
+        out.to_vec()
+    }
+
+    let scale = 0.25f32;
+    let xs = [1.5f32, -8.0, 3.0e10, f32::MIN_POSITIVE];
+    let bytes: Vec<u8> = xs.iter().flat_map(|x| x.to_le_bytes()).collect();
+    let expected: Vec<u8> = xs.iter().rev().flat_map(|x| (scale * x).to_le_bytes()).collect();
+    assert_eq!(run(clif_ir, 0, scale.to_le_bytes(), &bytes), expected);
+
+    let k = -3i32;
+    let ns = [7i32, -11, i32::MAX, 0];
+    let bytes: Vec<u8> = ns.iter().flat_map(|n| n.to_le_bytes()).collect();
+    let expected: Vec<u8> = ns.iter().rev().flat_map(|n| k.wrapping_mul(*n).to_le_bytes()).collect();
+    assert_eq!(run(clif_ir, 1, k.to_le_bytes(), &bytes), expected);
+}
+
+#[test]
+fn test_run_with_options() {
+    // fn 0 writes "data" to the relative path at 256; fn 1 spins until
+    // cancelled, checking once per iteration.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_write sig0
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 512
+    v3 = iconst.i64 0
+    v4 = iconst.i64 4
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    return
+}
+
+function u0:1(i64) system_v {
+    sig0 = () -> i64 system_v
+    fn0 = %cl_cancelled sig0
+block0(v0: i64):
+    jump block1
+
+block1:
+    v1 = call fn0()
+    brif v1, block2, block1
+
+block2:
+    return
+}"#;
+    let temp_dir = TempDir::new().unwrap();
+    let mut memory = vec![0u8; 1024];
+    let name = format!("run_with_{}.bin\0", std::process::id());
+    memory[256..256 + name.len()].copy_from_slice(name.as_bytes());
+    memory[512..516].copy_from_slice(b"data");
+    let setup = cranelift_config(memory, clif_ir.to_string());
+
+    // The default behaves as `run`: no sandbox, so the path is taken from
+    // the process's directory.
+    let cwd_file = std::env::current_dir()
+        .unwrap()
+        .join(name.trim_end_matches('\0'));
+    run_with(setup.clone(), cranelift_algorithm(0), RunOptions::default()).unwrap();
+    assert_eq!(fs::read(&cwd_file).unwrap(), b"data");
+    fs::remove_file(&cwd_file).unwrap();
+
+    let options = RunOptions {
+        sandbox: Some(base::PathSandbox {
+            working_dir: Some(temp_dir.path().to_path_buf()),
+            allowed_path_prefixes: None,
+        }),
+        ..RunOptions::default()
+    };
+    run_with(setup.clone(), cranelift_algorithm(0), options).unwrap();
+    let sandboxed = temp_dir.path().join(name.trim_end_matches('\0'));
+    assert_eq!(fs::read(sandboxed).unwrap(), b"data");
+    assert!(!cwd_file.exists());
+
+    let timeout = std::time::Duration::from_millis(20);
+    let options = RunOptions {
+        timeout: Some(timeout),
+        ..RunOptions::default()
+    };
+    assert!(matches!(
+        run_with(setup.clone(), cranelift_algorithm(1), options),
+        Err(base::Error::Timeout(t)) if t == timeout
+    ));
+    let options = RunOptions {
+        max_steps: Some(1000),
+        ..RunOptions::default()
+    };
+    assert!(matches!(
+        run_with(setup, cranelift_algorithm(1), options),
+        Err(base::Error::Execution(_))
+    ));
+}
+
+#[test]
+fn test_clif_mem_copy_under_a_lower_stream_threshold() {
+    // Copies 3000 bytes from 1029 to 4101 on 1 worker: with a 256-byte
+    // threshold the copy streams, with odd offsets and length at both ends.
+    let clif_ir = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_copy sig0
+block0(v0: i64):
+    v1 = iconst.i64 4101
+    v2 = iconst.i64 1029
+    v3 = iconst.i64 3000
+    v4 = iconst.i64 1
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    return
+}"#;
+
+    let memory: Vec<u8> = (0..8192).map(|i| (i % 251) as u8).collect();
+    let expected = memory[1029..4029].to_vec();
+    for threshold in [Some(256), None] {
+        let setup = cranelift_config(memory.clone(), clif_ir.to_string());
+        let mut base = Base::new(setup).unwrap();
+        base.set_copy_stream_min(threshold);
+        base.execute(&cranelift_algorithm(0), &[]).unwrap();
+        let copied = base.memory_handle().read(4101, 3000).unwrap();
+        assert_eq!(copied, expected, "threshold {threshold:?}");
+    }
+}
+
+#[test]
+fn test_clif_simd_publish_and_refresh_vectors() {
+    // Mirrors the Lean publishVectors / refreshVectors emitters. fn 0 adds
+    // the f32x4 vectors at 256 and 272 and publishes the sum and the first
+    // operand to slots 0 and 1 at 512, then raises the flag at 600. fn 1
+    // refreshes both slots and writes their lane-wise product to out.
+    let clif_ir = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    v1 = load.f32x4 notrap aligned v0+256
+    v2 = load.f32x4 notrap aligned v0+272
+    v3 = fadd v1, v2
+    v4 = iadd_imm v0, 512
+    store.f32x4 notrap aligned v3, v4
+    v5 = iadd_imm v4, 16
+    store.f32x4 notrap aligned v1, v5
+    fence
+    v6 = iconst.i64 1
+    store v6, v0+600
+    return
+}
+
+function u0:1(i64) system_v {
+block0(v0: i64):
+    fence
+    v1 = iadd_imm v0, 512
+    v2 = load.f32x4 notrap aligned v1
+    v3 = iadd_imm v1, 16
+    v4 = load.f32x4 notrap aligned v3
+    v5 = fmul v2, v4
+    v6 = load.i64 v0+24
+    store v5, v6
+    return
+}"#;
+
+    let f32s = |xs: [f32; 4]| -> Vec<u8> { xs.iter().flat_map(|x| x.to_le_bytes()).collect() };
+    let mut memory = vec![0u8; 1024];
+    memory[256..272].copy_from_slice(&f32s([1.0, 2.0, 3.0, 4.0]));
+    memory[272..288].copy_from_slice(&f32s([0.5, -2.0, 10.0, 0.25]));
+    let mut base = Base::new(cranelift_config(memory, clif_ir.to_string())).unwrap();
+    base.execute(&cranelift_algorithm(0), &[]).unwrap();
+    let handle = base.memory_handle();
+    assert_eq!(handle.read(512, 16).unwrap(), f32s([1.5, 0.0, 13.0, 4.25]));
+    assert_eq!(handle.read(528, 16).unwrap(), f32s([1.0, 2.0, 3.0, 4.0]));
+    assert_eq!(handle.read(600, 8).unwrap(), 1u64.to_le_bytes());
+
+    // Patch slot 1 from the host; the refresh picks it up.
+    handle.write(528, &f32s([2.0, 2.0, 2.0, -1.0])).unwrap();
+    let mut out = [0u8; 16];
+    base.execute_into(&cranelift_algorithm(1), &[], &mut out)
+        .unwrap();
+    assert_eq!(out.to_vec(), f32s([3.0, 0.0, 26.0, -4.25]));
+}
@@ -96,13 +96,13 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         std::hint::black_box(burn_saxpy_cuda(2.0, &x, &y));
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-        let burn_ms = harness::median_of(iterations, || {
+        let burn_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(burn_saxpy_cuda(2.0, &x, &y));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -116,9 +116,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: label,
-            col_a_ms: Some(burn_ms),
-            col_b_ms: None,
-            base_ms,
+            col_a: Some(burn_time),
+            col_b: None,
+            base: base_time,
+            bytes: None,
             verified: Some(burn_ok && base_ok),
         });
     }
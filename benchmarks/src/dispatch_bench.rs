@@ -0,0 +1,168 @@
+use crate::harness::{self, BenchResult, Timing};
+use base::{Algorithm, Base, Setup};
+
+// ---------------------------------------------------------------------------
+// Chained Dispatch Benchmark
+//
+// A three-stage pipeline, each stage on its own single-worker pool, run
+// CHAINS times over one word. "Per-stage wait" submits each stage and waits
+// on its pool before the next; Base passes the whole pipeline to
+// cl_thread_pool_chain and waits once on its completion flag. The stages are
+// a few instructions each, so the difference is the dispatch overhead.
+// ---------------------------------------------------------------------------
+
+const CHAINS: usize = 10_000;
+
+fn dispatch_clif() -> String {
+    format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_chain sig2
+    sig3 = (i64, i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wait_until sig3
+    fn4 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3)
+    v5 = call fn1(v2, v3)
+    v6 = call fn1(v2, v3)
+    v7 = iconst.i64 2
+    v8 = iconst.i64 3
+    v9 = iconst.i64 4
+    store.i64 v4, v0+128
+    store.i64 v7, v0+136
+    store.i64 v5, v0+144
+    store.i64 v8, v0+152
+    store.i64 v6, v0+160
+    store.i64 v9, v0+168
+    v10 = iadd_imm v0, 128
+    v11 = iadd_imm v0, 256
+    v12 = iadd_imm v0, 192
+    v13 = iconst.i64 0
+    jump block1(v13)
+
+block1(v14: i64):
+    v15 = call fn2(v2, v10, v8, v11, v12)
+    v16 = call fn3(v12, v3, v13)
+    v17 = iadd_imm v14, 1
+    v18 = icmp_imm ult v17, {chains}
+    brif v18, block1(v17), block2
+
+block2:
+    call fn4(v1)
+    v19 = load.i64 v0+40
+    v20 = load.i64 v0+256
+    store.i64 v20, v19
+    return
+}}
+
+function u0:1(i64) system_v {{
+    sig0 = (i64) system_v
+    fn0 = %cl_thread_init sig0
+    sig1 = (i64, i64) -> i64 system_v
+    fn1 = %cl_thread_pool_start sig1
+    sig2 = (i64, i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_pool_submit sig2
+    fn3 = %cl_thread_pool_wait sig1
+    fn4 = %cl_thread_cleanup sig0
+block0(v0: i64):
+    v1 = iadd_imm v0, 64
+    call fn0(v1)
+    v2 = load.i64 notrap aligned v0+64
+    v3 = iconst.i64 1
+    v4 = call fn1(v2, v3)
+    v5 = call fn1(v2, v3)
+    v6 = call fn1(v2, v3)
+    v7 = iadd_imm v0, 256
+    v8 = iconst.i64 2
+    v9 = iconst.i64 3
+    v10 = iconst.i64 4
+    v11 = iconst.i64 0
+    jump block1(v11)
+
+block1(v12: i64):
+    v13 = call fn2(v2, v4, v8, v7)
+    v14 = call fn3(v2, v4)
+    v15 = call fn2(v2, v5, v9, v7)
+    v16 = call fn3(v2, v5)
+    v17 = call fn2(v2, v6, v10, v7)
+    v18 = call fn3(v2, v6)
+    v19 = iadd_imm v12, 1
+    v20 = icmp_imm ult v19, {chains}
+    brif v20, block1(v19), block2
+
+block2:
+    call fn4(v1)
+    v21 = load.i64 v0+40
+    v22 = load.i64 v0+256
+    store.i64 v22, v21
+    return
+}}
+
+function u0:2(i64) system_v {{
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = imul_imm v1, 31
+    v3 = iadd_imm v2, 7
+    store.i64 v3, v0
+    return
+}}
+
+function u0:3(i64) system_v {{
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = ushr_imm v1, 13
+    v3 = bxor v1, v2
+    store.i64 v3, v0
+    return
+}}
+
+function u0:4(i64) system_v {{
+block0(v0: i64):
+    v1 = load.i64 v0
+    v2 = rotl_imm v1, 17
+    store.i64 v2, v0
+    return
+}}"#,
+        chains = CHAINS
+    )
+}
+
+fn pipeline_time(fn_idx: u32, iterations: usize) -> (Timing, [u8; 8]) {
+    let clif = dispatch_clif();
+    let mut out = [0u8; 8];
+    let time = harness::time_of(iterations, || {
+        let setup = Setup::with_initial_memory(&clif, vec![0u8; 512]);
+        let mut base = Base::new(setup).expect("Base::new failed");
+        let start = std::time::Instant::now();
+        let _ = base.execute_into(&Algorithm::new(fn_idx), &[], &mut out);
+        start.elapsed().as_secs_f64() * 1000.0
+    });
+    (time, out)
+}
+
+pub fn run(iterations: usize) -> Vec<BenchResult> {
+    let (chained_time, chained) = pipeline_time(0, iterations);
+    let (per_stage_time, per_stage) = pipeline_time(1, iterations);
+    println!(
+        "\n  per stage: {:.2} us waited, {:.2} us chained",
+        per_stage_time.median_ms * 1000.0 / (3 * CHAINS) as f64,
+        chained_time.median_ms * 1000.0 / (3 * CHAINS) as f64
+    );
+
+    vec![BenchResult {
+        name: "3-stage chain".into(),
+        col_a: Some(per_stage_time),
+        col_b: None,
+        base: chained_time,
+        bytes: None,
+        verified: Some(chained == per_stage),
+    }]
+}
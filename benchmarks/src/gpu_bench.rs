@@ -183,13 +183,13 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
             std::hint::black_box(burn_vec_add_gpu(&a, &b, &burn_dev));
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-            let burn_ms = harness::median_of(iterations, || {
+            let burn_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 std::hint::black_box(burn_vec_add_gpu(&a, &b, &burn_dev));
                 start.elapsed().as_secs_f64() * 1000.0
             });
 
-            let base_ms = harness::median_of(iterations, || {
+            let base_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
                 start.elapsed().as_secs_f64() * 1000.0
@@ -205,9 +205,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
             results.push(BenchResult {
                 name: format!("VecAdd {}", format_count(n)),
-                col_a_ms: Some(burn_ms),
-                col_b_ms: None,
-                base_ms,
+                col_a: Some(burn_time),
+                col_b: None,
+                base: base_time,
+                bytes: None,
                 verified: Some(verified),
             });
         }
@@ -233,13 +234,13 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
             std::hint::black_box(burn_matmul_gpu(&a, &b, n, &burn_dev));
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-            let burn_ms = harness::median_of(iterations, || {
+            let burn_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 std::hint::black_box(burn_matmul_gpu(&a, &b, n, &burn_dev));
                 start.elapsed().as_secs_f64() * 1000.0
             });
 
-            let base_ms = harness::median_of(iterations, || {
+            let base_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
                 start.elapsed().as_secs_f64() * 1000.0
@@ -250,9 +251,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
             results.push(BenchResult {
                 name: format!("MatMul {}x{}", n, n),
-                col_a_ms: Some(burn_ms),
-                col_b_ms: None,
-                base_ms,
+                col_a: Some(burn_time),
+                col_b: None,
+                base: base_time,
+                bytes: None,
                 verified: Some(verified),
             });
         }
@@ -281,13 +283,13 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
             std::hint::black_box(burn_reduction_gpu(&data, num_groups, &burn_dev));
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-            let burn_ms = harness::median_of(iterations, || {
+            let burn_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 std::hint::black_box(burn_reduction_gpu(&data, num_groups, &burn_dev));
                 start.elapsed().as_secs_f64() * 1000.0
             });
 
-            let base_ms = harness::median_of(iterations, || {
+            let base_time = harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
                 start.elapsed().as_secs_f64() * 1000.0
@@ -304,9 +306,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
             results.push(BenchResult {
                 name: format!("Reduction {}", format_count(n)),
-                col_a_ms: Some(burn_ms),
-                col_b_ms: None,
-                base_ms,
+                col_a: Some(burn_time),
+                col_b: None,
+                base: base_time,
+                bytes: None,
                 verified: Some(verified),
             });
         }
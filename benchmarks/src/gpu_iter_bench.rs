@@ -351,6 +351,168 @@ fn wgpu_iterative(data: &[f32], passes: usize) -> f64 {
     sum
 }
 
+// ---------------------------------------------------------------------------
+// Readback overlap: ROUNDS x (scale kernel + read the buffer back), with each
+// readback either blocking (cl_gpu_download_ptr) or queued with
+// cl_gpu_download_async and collected by one cl_gpu_wait at the end. With
+// async readback the next round's kernel runs while earlier copies are being
+// mapped, so the total should come in under the blocking (per-round) sum.
+// ---------------------------------------------------------------------------
+
+const ROUNDS: usize = 16;
+const READBACK_SHADER_OFF: usize = 0x100;
+const READBACK_BIND_OFF: usize = 0x1100;
+const READBACK_FLAGS_OFF: usize = 0x1200;
+
+fn readback_clif(n: usize) -> String {
+    let groups = n.div_ceil(64);
+    format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i64) -> i32 system_v
+    sig3 = (i64, i64, i64, i32) -> i32 system_v
+    sig4 = (i64, i32, i32, i32, i32) -> i32 system_v
+    sig5 = (i64, i32, i64, i64, i64) -> i32 system_v
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_upload_ptr sig2
+    fn3 = %cl_gpu_create_pipeline sig3
+    fn4 = %cl_gpu_dispatch sig4
+    fn5 = %cl_gpu_download_ptr sig5
+    fn6 = %cl_gpu_cleanup sig0
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+0x18
+    v2 = load.i64 notrap aligned v0+0x20
+    v3 = load.i64 notrap aligned v0+0x28
+    v4 = iadd_imm v0, 0x40
+    call fn0(v4)
+    v5 = load.i64 notrap aligned v0+0x40
+    v6 = call fn1(v5, v2)
+    v7 = call fn2(v5, v6, v1, v2)
+    v8 = iadd_imm v0, {shader}
+    v9 = iadd_imm v0, {bind}
+    v10 = iconst.i32 1
+    v11 = call fn3(v5, v8, v9, v10)
+    v12 = iconst.i32 {groups}
+    v13 = iconst.i64 0
+    jump block1(v13)
+block1(v14: i64):
+    v15 = call fn4(v5, v11, v12, v10, v10)
+    v16 = imul v14, v2
+    v17 = iadd v3, v16
+    v18 = call fn5(v5, v6, v13, v17, v2)
+    v19 = iadd_imm v14, 1
+    v20 = icmp_imm ult v19, {rounds}
+    brif v20, block1(v19), block2
+block2:
+    call fn6(v4)
+    return
+}}
+
+function u0:1(i64) system_v {{
+    sig0 = (i64) system_v
+    sig1 = (i64, i64) -> i32 system_v
+    sig2 = (i64, i32, i64, i64) -> i32 system_v
+    sig3 = (i64, i64, i64, i32) -> i32 system_v
+    sig4 = (i64, i32, i32, i32, i32) -> i32 system_v
+    sig5 = (i64, i32, i64, i64, i64) -> i32 system_v
+    sig6 = (i64) -> i32 system_v
+    fn0 = %cl_gpu_init sig0
+    fn1 = %cl_gpu_create_buffer sig1
+    fn2 = %cl_gpu_upload_ptr sig2
+    fn3 = %cl_gpu_create_pipeline sig3
+    fn4 = %cl_gpu_dispatch sig4
+    fn5 = %cl_gpu_download_async sig5
+    fn6 = %cl_gpu_wait sig6
+    fn7 = %cl_gpu_cleanup sig0
+block0(v0: i64):
+    v1 = load.i64 notrap aligned v0+0x18
+    v2 = load.i64 notrap aligned v0+0x20
+    v3 = load.i64 notrap aligned v0+0x28
+    v4 = iadd_imm v0, 0x40
+    call fn0(v4)
+    v5 = load.i64 notrap aligned v0+0x40
+    v6 = call fn1(v5, v2)
+    v7 = call fn2(v5, v6, v1, v2)
+    v8 = iadd_imm v0, {shader}
+    v9 = iadd_imm v0, {bind}
+    v10 = iconst.i32 1
+    v11 = call fn3(v5, v8, v9, v10)
+    v12 = iconst.i32 {groups}
+    v13 = iconst.i64 0
+    jump block1(v13)
+block1(v14: i64):
+    v15 = call fn4(v5, v11, v12, v10, v10)
+    v16 = imul v14, v2
+    v17 = iadd v3, v16
+    v18 = ishl_imm v14, 3
+    v19 = iadd_imm v18, {flags}
+    v20 = iadd v0, v19
+    v21 = call fn5(v5, v6, v17, v2, v20)
+    v22 = iadd_imm v14, 1
+    v23 = icmp_imm ult v22, {rounds}
+    brif v23, block1(v22), block2
+block2:
+    v24 = call fn6(v5)
+    call fn7(v4)
+    return
+}}"#,
+        shader = READBACK_SHADER_OFF,
+        bind = READBACK_BIND_OFF,
+        flags = READBACK_FLAGS_OFF,
+        rounds = ROUNDS,
+    )
+}
+
+fn readback_overlap(iterations: usize) {
+    let n = 1_000_000usize;
+    let mut memory = vec![0u8; READBACK_FLAGS_OFF + ROUNDS * 8];
+    let shader = WGSL_SCALE.as_bytes();
+    memory[READBACK_SHADER_OFF..READBACK_SHADER_OFF + shader.len()].copy_from_slice(shader);
+    let setup = base::Setup::with_initial_memory(&readback_clif(n), memory);
+    let mut base_instance = base::Base::new(setup).expect("Base::new failed");
+
+    let data = gen_positive_floats(n, 7);
+    let payload: Vec<u8> = data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let mut blocking_out = vec![0u8; payload.len() * ROUNDS];
+    let mut async_out = vec![0u8; payload.len() * ROUNDS];
+    let blocking = base::Algorithm::new(0);
+    let overlapped = base::Algorithm::new(1);
+
+    let mut time = |alg: &base::Algorithm, out: &mut [u8]| {
+        let _ = base_instance.execute_into(alg, &payload, out);
+        harness::median_of(iterations, || {
+            let start = std::time::Instant::now();
+            let _ = base_instance.execute_into(alg, &payload, out);
+            start.elapsed().as_secs_f64() * 1000.0
+        })
+    };
+    let blocking_ms = time(&blocking, &mut blocking_out);
+    let async_ms = time(&overlapped, &mut async_out);
+
+    eprintln!(
+        "\n=== GPU readback overlap: {} x (scale {} + readback) ===",
+        ROUNDS,
+        format_count(n)
+    );
+    eprintln!(
+        "  blocking readback: {:>8.2} ms ({:.2} ms per round)",
+        blocking_ms,
+        blocking_ms / ROUNDS as f64
+    );
+    eprintln!(
+        "  async readback:    {:>8.2} ms ({:.0}% of the per-round sum){}",
+        async_ms,
+        100.0 * async_ms / blocking_ms,
+        if blocking_out == async_out {
+            ""
+        } else {
+            "  VERIFY FAIL: outputs differ"
+        }
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Verification
 // ---------------------------------------------------------------------------
@@ -404,21 +566,21 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
         // Raw wgpu (GPU-resident)
-        let wgpu_ms = harness::median_of(iterations, || {
+        let wgpu_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(wgpu_iterative(&data, passes));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
         // Burn (GPU-resident)
-        let burn_ms = harness::median_of(iterations, || {
+        let burn_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(burn_iterative(&data, passes));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
         // Base+GPU (GPU-resident via CLIF loop)
-        let clif_ms = harness::median_of(iterations, || {
+        let clif_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -435,12 +597,15 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: label,
-            col_a_ms: Some(wgpu_ms),
-            col_b_ms: Some(burn_ms),
-            base_ms: clif_ms,
+            col_a: Some(wgpu_time),
+            col_b: Some(burn_time),
+            base: clif_time,
+            bytes: None,
             verified: Some(wgpu_ok && burn_ok && clif_ok),
         });
     }
 
+    readback_overlap(iterations);
+
     results
 }
@@ -1,16 +1,46 @@
+use base::Profile;
+use serde::{Deserialize, Serialize};
+
+/// Spread of one column's rounds, in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+}
+
+impl Timing {
+    pub fn from_samples(mut times: Vec<f64>) -> Self {
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let n = times.len();
+        Self {
+            min_ms: times[0],
+            median_ms: times[n / 2],
+            mean_ms: times.iter().sum::<f64>() / n as f64,
+            p95_ms: times[(n * 95).div_ceil(100) - 1],
+        }
+    }
+}
+
 pub struct BenchResult {
     pub name: String,
-    pub col_a_ms: Option<f64>,
-    pub col_b_ms: Option<f64>,
-    pub base_ms: f64,
+    pub col_a: Option<Timing>,
+    pub col_b: Option<Timing>,
+    pub base: Timing,
+    /// Bytes one round processes, when a throughput figure makes sense.
+    pub bytes: Option<u64>,
     pub verified: Option<bool>,
 }
 
+/// Run a benchmark function `iterations` times and summarize the rounds.
+pub fn time_of(iterations: usize, mut f: impl FnMut() -> f64) -> Timing {
+    Timing::from_samples((0..iterations).map(|_| f()).collect())
+}
+
 /// Run a benchmark function `iterations` times and return the median.
-pub fn median_of(iterations: usize, mut f: impl FnMut() -> f64) -> f64 {
-    let mut times: Vec<f64> = (0..iterations).map(|_| f()).collect();
-    times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
-    times[times.len() / 2]
+pub fn median_of(iterations: usize, f: impl FnMut() -> f64) -> f64 {
+    time_of(iterations, f).median_ms
 }
 
 fn fmt_ms(ms: Option<f64>) -> String {
@@ -50,9 +80,9 @@ pub fn print_results(results: &[BenchResult], col_a: &str, col_b: &str) {
         println!(
             "{:<name_w$} {:>col_w$} {:>col_w$} {:>col_w$} {:>6}",
             r.name,
-            fmt_ms(r.col_a_ms),
-            fmt_ms(r.col_b_ms),
-            fmt_ms(Some(r.base_ms)),
+            fmt_ms(r.col_a.map(|t| t.median_ms)),
+            fmt_ms(r.col_b.map(|t| t.median_ms)),
+            fmt_ms(Some(r.base.median_ms)),
             fmt_check(r.verified),
             name_w = name_w,
             col_w = col_w
@@ -61,10 +91,6 @@ pub fn print_results(results: &[BenchResult], col_a: &str, col_b: &str) {
     println!();
 }
 
-pub fn print_burn_table(results: &[BenchResult]) {
-    print_results(results, "Rust", "Burn");
-}
-
 /// Print a 2-column table (col_a + Base only, no col_b).
 pub fn print_results_2col(results: &[BenchResult], col_a: &str) {
     let name_w = 20;
@@ -86,8 +112,8 @@ pub fn print_results_2col(results: &[BenchResult], col_a: &str) {
         println!(
             "{:<name_w$} {:>col_w$} {:>col_w$} {:>6}",
             r.name,
-            fmt_ms(r.col_a_ms),
-            fmt_ms(Some(r.base_ms)),
+            fmt_ms(r.col_a.map(|t| t.median_ms)),
+            fmt_ms(Some(r.base.median_ms)),
             fmt_check(r.verified),
             name_w = name_w,
             col_w = col_w
@@ -96,6 +122,208 @@ pub fn print_results_2col(results: &[BenchResult], col_a: &str) {
     println!();
 }
 
+/// Print the `n` most expensive entries of a `Base::take_profile` result,
+/// with a bar scaled to the slowest.
+pub fn print_profile(title: &str, profile: &Profile, n: usize) {
+    let top = profile.top_n(n);
+    let Some(max_ns) = top.first().map(|e| e.total_ns.max(1)) else {
+        return;
+    };
+    let name_w = 24;
+    let bar_w = 30;
+
+    println!();
+    println!("Profile: {}", title);
+    println!(
+        "{:<name_w$} {:>10} {:>12}  {}",
+        "Entry",
+        "Calls",
+        "Total",
+        "",
+        name_w = name_w
+    );
+    println!("{}", "-".repeat(name_w + 10 + 12 + bar_w + 4));
+    for e in top {
+        let bar = (e.total_ns as u128 * bar_w as u128 / max_ns as u128) as usize;
+        println!(
+            "{:<name_w$} {:>10} {:>12}  {}",
+            e.to_string(),
+            e.calls,
+            fmt_ms(Some(e.total_ns as f64 / 1e6)),
+            "#".repeat(bar),
+            name_w = name_w
+        );
+    }
+    println!();
+}
+
+// ---------------------------------------------------------------------------
+// Machine-readable output and baseline comparison
+// ---------------------------------------------------------------------------
+
+/// One column of one table row: the unit `--output` files hold and
+/// `--baseline` compares.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub bench: String,
+    pub name: String,
+    pub column: String,
+    pub rounds: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    /// GB/s at the median, for rows that report their bytes.
+    pub gb_per_s: Option<f64>,
+    pub verified: Option<bool>,
+    /// Target architecture of the run (`x86_64`, `aarch64`, ...), so output
+    /// files from different machines can be told apart; empty in files
+    /// written before it was recorded.
+    #[serde(default)]
+    pub arch: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Every table printed in one run, kept for `--output` and `--baseline`.
+pub struct Report {
+    rounds: usize,
+    records: Vec<Record>,
+}
+
+impl Report {
+    pub fn new(rounds: usize) -> Self {
+        Self {
+            rounds,
+            records: Vec::new(),
+        }
+    }
+
+    /// Print `results` under the given column labels (three columns when
+    /// `col_b` is set, two otherwise) and record each column of each row.
+    pub fn add(&mut self, bench: &str, results: &[BenchResult], col_a: &str, col_b: Option<&str>) {
+        match col_b {
+            Some(col_b) => print_results(results, col_a, col_b),
+            None => print_results_2col(results, col_a),
+        }
+        for r in results {
+            let columns = [
+                (Some(col_a), r.col_a),
+                (col_b, r.col_b),
+                (Some("Base"), Some(r.base)),
+            ];
+            for (column, timing) in columns {
+                let (Some(column), Some(t)) = (column, timing) else {
+                    continue;
+                };
+                self.records.push(Record {
+                    bench: bench.to_string(),
+                    name: r.name.clone(),
+                    column: column.to_string(),
+                    rounds: self.rounds,
+                    min_ms: t.min_ms,
+                    median_ms: t.median_ms,
+                    mean_ms: t.mean_ms,
+                    p95_ms: t.p95_ms,
+                    gb_per_s: r.bytes.map(|b| b as f64 / t.median_ms / 1e6),
+                    verified: r.verified,
+                    arch: std::env::consts::ARCH.to_string(),
+                });
+            }
+        }
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn write(&self, format: OutputFormat, path: &std::path::Path) -> std::io::Result<()> {
+        let text = match format {
+            OutputFormat::Json => serde_json::to_string_pretty(&self.records)?,
+            OutputFormat::Csv => to_csv(&self.records),
+        };
+        std::fs::write(path, text)
+    }
+}
+
+fn to_csv(records: &[Record]) -> String {
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    let mut out = String::from(
+        "bench,name,column,rounds,min_ms,median_ms,mean_ms,p95_ms,gb_per_s,verified,arch\n",
+    );
+    for r in records {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{}\n",
+            quote(&r.bench),
+            quote(&r.name),
+            quote(&r.column),
+            r.rounds,
+            r.min_ms,
+            r.median_ms,
+            r.mean_ms,
+            r.p95_ms,
+            opt(r.gb_per_s.map(|v| v.to_string())),
+            opt(r.verified.map(|v| v.to_string())),
+            quote(&r.arch),
+        ));
+    }
+    out
+}
+
+/// Read the records of an earlier `--output json` run.
+pub fn load_baseline(path: &std::path::Path) -> Result<Vec<Record>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub bench: String,
+    pub name: String,
+    pub baseline_ms: f64,
+    pub current_ms: f64,
+    pub percent: f64,
+}
+
+/// Rows whose Base median grew by more than `threshold_pct` percent over
+/// the baseline. Rows match on bench, name and column; the reference columns
+/// time code outside this repo and are not compared, and rows present on
+/// only one side are skipped.
+pub fn regressions(baseline: &[Record], current: &[Record], threshold_pct: f64) -> Vec<Regression> {
+    current
+        .iter()
+        .filter(|r| r.column == "Base")
+        .filter_map(|r| {
+            let old = baseline
+                .iter()
+                .find(|b| b.bench == r.bench && b.name == r.name && b.column == r.column)?;
+            let percent = (r.median_ms - old.median_ms) / old.median_ms * 100.0;
+            (percent > threshold_pct).then(|| Regression {
+                bench: r.bench.clone(),
+                name: r.name.clone(),
+                baseline_ms: old.median_ms,
+                current_ms: r.median_ms,
+                percent,
+            })
+        })
+        .collect()
+}
+
 // ---------------------------------------------------------------------------
 // Shared utilities used across benchmark files
 // ---------------------------------------------------------------------------
@@ -145,3 +373,87 @@ pub fn f32_sum(data: &[u8]) -> f64 {
         .map(|c| f32::from_le_bytes(c.try_into().unwrap()) as f64)
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(bench: &str, name: &str, column: &str, median_ms: f64) -> Record {
+        Record {
+            bench: bench.into(),
+            name: name.into(),
+            column: column.into(),
+            rounds: 10,
+            min_ms: median_ms,
+            median_ms,
+            mean_ms: median_ms,
+            p95_ms: median_ms,
+            gb_per_s: None,
+            verified: Some(true),
+            arch: "x86_64".into(),
+        }
+    }
+
+    #[test]
+    fn regressions_flag_only_base_rows_past_the_threshold() {
+        let baseline = vec![
+            record("sort", "Sort (1M)", "Base", 100.0),
+            record("sort", "Sort (1M)", "Rust", 100.0),
+            record("sort", "Sort (10M)", "Base", 100.0),
+            record("csv", "CSV (10K)", "Base", 100.0),
+            record("csv", "CSV (gone)", "Base", 100.0),
+        ];
+        let current = vec![
+            record("sort", "Sort (1M)", "Base", 125.0),
+            record("sort", "Sort (1M)", "Rust", 300.0),
+            record("sort", "Sort (10M)", "Base", 109.0),
+            record("csv", "CSV (10K)", "Base", 50.0),
+            record("csv", "CSV (new)", "Base", 900.0),
+        ];
+        let found = regressions(&baseline, &current, 10.0);
+        assert_eq!(
+            found,
+            vec![Regression {
+                bench: "sort".into(),
+                name: "Sort (1M)".into(),
+                baseline_ms: 100.0,
+                current_ms: 125.0,
+                percent: 25.0,
+            }]
+        );
+        assert_eq!(regressions(&baseline, &current, 30.0), vec![]);
+        assert_eq!(regressions(&baseline, &current, 5.0).len(), 2);
+    }
+
+    #[test]
+    fn baseline_round_trips_through_json() {
+        let dir = std::env::temp_dir().join(format!("bench-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("baseline.json");
+        let mut report = Report::new(3);
+        report.add(
+            "memcopy",
+            &[BenchResult {
+                name: "MemCopy (64MB)".into(),
+                col_a: Some(Timing::from_samples(vec![4.0, 2.0, 3.0])),
+                col_b: None,
+                base: Timing::from_samples(vec![1.0, 2.0, 4.0]),
+                bytes: Some(64 << 20),
+                verified: None,
+            }],
+            "Rust",
+            None,
+        );
+        report.write(OutputFormat::Json, &path).unwrap();
+        let loaded = load_baseline(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded, report.records());
+        assert_eq!(loaded.len(), 2);
+        let base = &loaded[1];
+        assert_eq!(base.column, "Base");
+        assert_eq!((base.min_ms, base.median_ms, base.p95_ms), (1.0, 2.0, 4.0));
+        assert!((base.mean_ms - 7.0 / 3.0).abs() < 1e-12);
+        assert!((base.gb_per_s.unwrap() - (64 << 20) as f64 / 2e6).abs() < 1e-9);
+        assert!(regressions(&loaded, report.records(), 0.0).is_empty());
+    }
+}
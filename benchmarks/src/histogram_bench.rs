@@ -221,20 +221,20 @@ pub fn run(rounds: usize) -> Vec<BenchResult> {
             rayon_histogram(&mut file_buf, input_path, &rayon_out, w);
             let _ = base_instance.execute(&artifact.main, &base_payload);
 
-            let rust_ms = harness::median_of(rounds, || {
+            let rust_time = harness::time_of(rounds, || {
                 let start = std::time::Instant::now();
                 rust_histogram(&mut file_buf, input_path, &rust_out, w);
                 start.elapsed().as_secs_f64() * 1000.0
             });
 
-            let rayon_ms = harness::median_of(rounds, || {
+            let rayon_time = harness::time_of(rounds, || {
                 let start = std::time::Instant::now();
                 rayon_histogram(&mut file_buf, input_path, &rayon_out, w);
                 start.elapsed().as_secs_f64() * 1000.0
             });
 
             let mut base_ok = true;
-            let base_ms = harness::median_of(rounds, || {
+            let base_time = harness::time_of(rounds, || {
                 let start = std::time::Instant::now();
                 if base_instance.execute(&artifact.main, &base_payload).is_err() {
                     base_ok = false;
@@ -269,9 +269,10 @@ pub fn run(rounds: usize) -> Vec<BenchResult> {
 
             results.push(BenchResult {
                 name: label,
-                col_a_ms: Some(rust_ms),
-                col_b_ms: Some(rayon_ms),
-                base_ms,
+                col_a: Some(rust_time),
+                col_b: Some(rayon_time),
+                base: base_time,
+                bytes: None,
                 verified: Some(rust_verified && rayon_verified && base_verified),
             });
         }
@@ -85,7 +85,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let payload = build_payload(&json_path, &output_path);
 
         // Pure Rust (streaming parser, no serde)
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let sum = rust_json_sum(&json_path);
             let ms = start.elapsed().as_secs_f64() * 1000.0;
@@ -103,7 +103,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let _ = fs::remove_file(&output_path);
         let _ = base_instance.execute(&artifact.main, &payload);
 
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let _ = fs::remove_file(&output_path);
             let start = std::time::Instant::now();
             let _ = base_instance.execute(&artifact.main, &payload);
@@ -134,9 +134,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: format!("JSON ({})", format_count(n)),
-            col_a_ms: Some(rust_ms),
-            col_b_ms: None,
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: None,
+            base: base_time,
+            bytes: None,
             verified,
         });
     }
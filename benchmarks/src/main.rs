@@ -1,14 +1,18 @@
 mod csv_bench;
 mod cuda_bench;
+mod dispatch_bench;
 mod gpu_bench;
 mod gpu_iter_bench;
 mod harness;
 mod histogram_bench;
 mod json_bench;
 mod matmul_bench;
+mod memcopy_bench;
+mod memory_bench;
 mod reduction_bench;
 mod regex_bench;
 mod sort_bench;
+mod stream_bench;
 mod string_search_bench;
 mod vecops_bench;
 mod wordcount_bench;
@@ -20,8 +24,14 @@ fn print_usage() {
     eprintln!();
     eprintln!("  --bench <name>     Benchmark to run: csv, json, regex, burn, vecops, reduction,");
     eprintln!("                     gpu, gpu-iter, cuda,");
-    eprintln!("                     histogram, sort, strsearch, wc, all (default: all)");
+    eprintln!("                     histogram, sort, strsearch, wc, memcopy, memory,");
+    eprintln!("                     dispatch, stream, all (default: all)");
     eprintln!("  --rounds <n>       Rounds per measurement (default: 10)");
+    eprintln!("  --output <format>  Also write every result as json or csv");
+    eprintln!("  --out-file <path>  Where --output writes (default: benchmarks.<format>)");
+    eprintln!("  --baseline <path>  Compare Base medians with an earlier --output json run");
+    eprintln!("                     and exit nonzero on regressions");
+    eprintln!("  --threshold <pct>  Slowdown that counts as a regression (default: 10)");
     eprintln!("  --help             Show this help");
 }
 
@@ -41,6 +51,10 @@ fn main() {
 
     let mut bench = "all".to_string();
     let mut rounds: usize = 10;
+    let mut output: Option<harness::OutputFormat> = None;
+    let mut out_file: Option<String> = None;
+    let mut baseline: Option<String> = None;
+    let mut threshold: f64 = 10.0;
 
     let mut i = 1;
     while i < args.len() {
@@ -57,6 +71,28 @@ fn main() {
                     rounds = args[i].parse().unwrap_or(5);
                 }
             }
+            "--output" => {
+                i += 1;
+                output = args.get(i).and_then(|s| harness::OutputFormat::parse(s));
+                if output.is_none() {
+                    eprintln!("--output takes json or csv");
+                    std::process::exit(1);
+                }
+            }
+            "--out-file" => {
+                i += 1;
+                out_file = args.get(i).cloned();
+            }
+            "--baseline" => {
+                i += 1;
+                baseline = args.get(i).cloned();
+            }
+            "--threshold" => {
+                i += 1;
+                if i < args.len() {
+                    threshold = args[i].parse().unwrap_or(10.0);
+                }
+            }
             "--help" | "-h" => {
                 print_usage();
                 return;
@@ -83,69 +119,127 @@ fn main() {
     let run_sort = bench == "all" || bench == "sort";
     let run_strsearch = bench == "all" || bench == "strsearch";
     let run_wc = bench == "all" || bench == "wc";
+    let run_memcopy = bench == "all" || bench == "memcopy";
+    let run_memory = bench == "all" || bench == "memory";
+    let run_dispatch = bench == "all" || bench == "dispatch";
+    let run_stream = bench == "all" || bench == "stream";
+
+    let mut report = harness::Report::new(rounds);
 
     if run_csv {
         let results = csv_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("csv", &results, "Rust", None);
     }
 
     if run_json {
         let results = json_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("json", &results, "Rust", None);
     }
 
     if run_regex {
         let results = regex_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("regex", &results, "Rust", None);
     }
 
     if run_matmul {
         let results = matmul_bench::run(rounds);
-        harness::print_burn_table(&results);
+        report.add("matmul", &results, "Rust", Some("Burn"));
     }
 
     if run_vecops {
         let results = vecops_bench::run(rounds);
-        harness::print_burn_table(&results);
+        report.add("vecops", &results, "Rust", Some("Burn"));
     }
 
     if run_reduction {
         let results = reduction_bench::run(rounds);
-        harness::print_burn_table(&results);
+        report.add("reduction", &results, "Rust", Some("Burn"));
     }
 
     if run_gpu {
         let results = gpu_bench::run(rounds);
-        harness::print_results_2col(&results, "Burn(wgpu)");
+        report.add("gpu", &results, "Burn(wgpu)", None);
     }
 
     if run_gpu_iter {
         let results = gpu_iter_bench::run(rounds);
-        harness::print_results(&results, "Raw wgpu", "Burn");
+        report.add("gpu-iter", &results, "Raw wgpu", Some("Burn"));
     }
 
     if run_cuda {
         let results = cuda_bench::run(rounds);
-        harness::print_results_2col(&results, "Burn(cuda)");
+        report.add("cuda", &results, "Burn(cuda)", None);
     }
 
     if run_histogram {
         let results = histogram_bench::run(rounds);
-        harness::print_results(&results, "Rust", "Rayon");
+        report.add("histogram", &results, "Rust", Some("Rayon"));
     }
 
     if run_sort {
         let results = sort_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("sort", &results, "Rust", None);
     }
 
     if run_strsearch {
         let results = string_search_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("strsearch", &results, "Rust", None);
     }
 
     if run_wc {
         let results = wordcount_bench::run(rounds);
-        harness::print_results_2col(&results, "Rust");
+        report.add("wc", &results, "Rust", None);
+    }
+
+    if run_memcopy {
+        let results = memcopy_bench::run(rounds);
+        report.add("memcopy", &results, "Rust", Some("1 worker"));
+        let results = memcopy_bench::run_streaming(rounds);
+        report.add("memcopy_stream", &results, "Cached", None);
+    }
+
+    if run_memory {
+        let results = memory_bench::run(rounds);
+        report.add("memory", &results, "Copy", None);
+    }
+
+    if run_dispatch {
+        let results = dispatch_bench::run(rounds);
+        report.add("dispatch", &results, "Per-stage wait", None);
+    }
+
+    if run_stream {
+        let results = stream_bench::run(rounds);
+        report.add("stream", &results, "Chunked read", None);
+    }
+
+    if let Some(format) = output {
+        let ext = match format {
+            harness::OutputFormat::Json => "json",
+            harness::OutputFormat::Csv => "csv",
+        };
+        let path = out_file.unwrap_or_else(|| format!("benchmarks.{}", ext));
+        if let Err(e) = report.write(format, std::path::Path::new(&path)) {
+            eprintln!("Could not write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = baseline {
+        let old = harness::load_baseline(std::path::Path::new(&path)).unwrap_or_else(|e| {
+            eprintln!("Could not read baseline {}", e);
+            std::process::exit(1);
+        });
+        let found = harness::regressions(&old, report.records(), threshold);
+        for r in &found {
+            println!(
+                "REGRESSION {} / {}: {:.1}ms -> {:.1}ms (+{:.1}%)",
+                r.bench, r.name, r.baseline_ms, r.current_ms, r.percent
+            );
+        }
+        if !found.is_empty() {
+            std::process::exit(1);
+        }
+        println!("No regressions beyond {}% against {}", threshold, path);
     }
 }
@@ -93,19 +93,19 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let payload = build_payload(a, b, n, n, n);
 
         // Raw Rust
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(rust_matmul(a, b, n, n, n));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
         // Burn (NdArray CPU)
-        let burn_ms = {
+        let burn_time = {
             use burn::tensor::{Tensor, TensorData};
             let device = Default::default();
             let a_t = Tensor::<B, 2>::from_data(TensorData::new(a.to_vec(), [n, n]), &device);
             let b_t = Tensor::<B, 2>::from_data(TensorData::new(b.to_vec(), [n, n]), &device);
-            harness::median_of(iterations, || {
+            harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 let c_t = a_t.clone().matmul(b_t.clone());
                 std::hint::black_box(c_t.sum().into_scalar());
@@ -119,7 +119,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         // Warmup
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -133,9 +133,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: format!("MatMul ({}x{})", n, n),
-            col_a_ms: Some(rust_ms),
-            col_b_ms: Some(burn_ms),
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: Some(burn_time),
+            base: base_time,
+            bytes: None,
             verified,
         });
     }
@@ -0,0 +1,130 @@
+use crate::harness::{self, BenchResult};
+use base::{Algorithm, Base, Setup};
+
+// ---------------------------------------------------------------------------
+// Memory Copy Benchmark
+//
+// Compares a single Rust copy_from_slice with cl_mem_copy on 1 and 4 workers,
+// copying a region of shared memory into a second region of the same size.
+// The sizes sweep across cl_mem_copy's tiers: one cached memcpy at 1MB,
+// streaming stores from 8MB, and parallel chunks beyond.
+//
+// The CLIF is small enough to inline; the payload carries [size, workers].
+// The instance is profiled, so each size also prints where the time went.
+//
+// `run_streaming` isolates the store tier: the same one-worker copies with
+// the streaming threshold raised past every size (cached stores) and
+// lowered to zero (streaming stores).
+// ---------------------------------------------------------------------------
+
+const SRC_OFF: usize = 4096;
+
+const MEMCOPY_CLIF: &str = r#"function u0:0(i64) system_v {
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_mem_copy sig0
+block0(v0: i64):
+    v1 = load.i64 v0+24
+    v2 = load.i64 v1
+    v3 = load.i64 v1+8
+    v4 = iconst.i64 4096
+    v5 = iadd_imm v2, 4096
+    v6 = call fn0(v0, v5, v4, v2, v3)
+    return
+}"#;
+
+fn payload(size: usize, workers: u64) -> [u8; 16] {
+    let mut p = [0u8; 16];
+    p[..8].copy_from_slice(&(size as u64).to_le_bytes());
+    p[8..].copy_from_slice(&workers.to_le_bytes());
+    p
+}
+
+pub fn run(iterations: usize) -> Vec<BenchResult> {
+    let sizes: &[usize] = &[1 << 20, 16 << 20, 64 << 20, 256 << 20, 1 << 30];
+    let mut results = Vec::new();
+
+    for &size in sizes {
+        // Rust: one thread, one memcpy between two separate buffers.
+        let src: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let mut dst = vec![0u8; size];
+        let rust_time = harness::time_of(iterations, || {
+            let start = std::time::Instant::now();
+            dst.copy_from_slice(&src);
+            std::hint::black_box(&dst);
+            start.elapsed().as_secs_f64() * 1000.0
+        });
+
+        let mut memory = vec![0u8; SRC_OFF + 2 * size];
+        memory[SRC_OFF..SRC_OFF + size].copy_from_slice(&src);
+        drop(dst);
+        let mut base =
+            Base::new_profiled(Setup::with_initial_memory(MEMCOPY_CLIF.to_string(), memory))
+                .expect("Base::new_profiled failed");
+        let algorithm = Algorithm::new(0);
+
+        let mut time_workers = |workers: u64| {
+            let p = payload(size, workers);
+            let _ = base.execute(&algorithm, &p);
+            harness::time_of(iterations, || {
+                let start = std::time::Instant::now();
+                let _ = base.execute(&algorithm, &p);
+                start.elapsed().as_secs_f64() * 1000.0
+            })
+        };
+        let one_worker = time_workers(1);
+        let four_workers = time_workers(4);
+        if let Some(profile) = base.take_profile() {
+            harness::print_profile(&format!("MemCopy ({}MB)", size >> 20), &profile, 4);
+        }
+
+        results.push(BenchResult {
+            name: format!("MemCopy ({}MB)", size >> 20),
+            col_a: Some(rust_time),
+            col_b: Some(one_worker),
+            base: four_workers,
+            bytes: Some(size as u64),
+            verified: None,
+        });
+    }
+
+    results
+}
+
+pub fn run_streaming(iterations: usize) -> Vec<BenchResult> {
+    let sizes: &[usize] = &[1 << 20, 8 << 20, 64 << 20, 256 << 20];
+    let mut results = Vec::new();
+
+    for &size in sizes {
+        let mut memory = vec![0u8; SRC_OFF + 2 * size];
+        for (i, b) in memory[SRC_OFF..SRC_OFF + size].iter_mut().enumerate() {
+            *b = (i % 251) as u8;
+        }
+        let mut base = Base::new(Setup::with_initial_memory(MEMCOPY_CLIF.to_string(), memory))
+            .expect("Base::new failed");
+        let algorithm = Algorithm::new(0);
+        let p = payload(size, 1);
+
+        let mut time_threshold = |threshold: usize| {
+            base.set_copy_stream_min(Some(threshold));
+            let _ = base.execute(&algorithm, &p);
+            harness::time_of(iterations, || {
+                let start = std::time::Instant::now();
+                let _ = base.execute(&algorithm, &p);
+                start.elapsed().as_secs_f64() * 1000.0
+            })
+        };
+        let cached = time_threshold(usize::MAX);
+        let streamed = time_threshold(0);
+
+        results.push(BenchResult {
+            name: format!("MemCopy streamed ({}MB)", size >> 20),
+            col_a: Some(cached),
+            col_b: None,
+            base: streamed,
+            bytes: Some(size as u64),
+            verified: None,
+        });
+    }
+
+    results
+}
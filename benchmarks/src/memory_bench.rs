@@ -0,0 +1,90 @@
+use crate::harness::{self, BenchResult, Timing};
+use base::{Algorithm, Base, Setup};
+
+// ---------------------------------------------------------------------------
+// Memory Handoff Benchmark
+//
+// Startup cost of a 1GB Base running a no-op function: Base::new plus one
+// execute. "Copy" is what a copying handoff would add on top: one memcpy of
+// the region. Resident set growth across each startup is printed alongside,
+// since zero-copy should keep it at ~1x the region (and near zero for
+// untouched zeroed memory).
+// ---------------------------------------------------------------------------
+
+const SIZE: usize = 1 << 30;
+
+const NOOP_CLIF: &str = r#"function u0:0(i64) system_v {
+block0(v0: i64):
+    return
+}"#;
+
+/// Current resident set size in bytes, from /proc/self/status (0 elsewhere).
+fn rss_bytes() -> u64 {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|s| {
+            s.lines()
+                .find(|l| l.starts_with("VmRSS:"))
+                .and_then(|l| l.split_whitespace().nth(1))
+                .and_then(|kb| kb.parse::<u64>().ok())
+        })
+        .map_or(0, |kb| kb * 1024)
+}
+
+fn startup_time(make_setup: impl Fn() -> Setup, label: &str, iterations: usize) -> Timing {
+    let algorithm = Algorithm::new(0);
+    let mut rss_growth = 0;
+    let time = harness::time_of(iterations, || {
+        let setup = make_setup();
+        let before = rss_bytes();
+        let start = std::time::Instant::now();
+        let mut base = Base::new(setup).expect("Base::new failed");
+        let _ = base.execute(&algorithm, &[]);
+        let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+        rss_growth = rss_bytes().saturating_sub(before);
+        elapsed
+    });
+    println!("  {}: RSS growth {}MB", label, rss_growth >> 20);
+    time
+}
+
+pub fn run(iterations: usize) -> Vec<BenchResult> {
+    let filled: Vec<u8> = (0..SIZE).map(|i| (i % 251) as u8).collect();
+    let copy_time = harness::time_of(iterations, || {
+        let start = std::time::Instant::now();
+        let copy = filled.clone();
+        std::hint::black_box(&copy);
+        start.elapsed().as_secs_f64() * 1000.0
+    });
+
+    println!();
+    let initial_time = startup_time(
+        || Setup::with_initial_memory(NOOP_CLIF, filled.clone()),
+        "initial_memory (1GB)",
+        iterations,
+    );
+    let zeroed_time = startup_time(
+        || Setup::new(NOOP_CLIF, SIZE),
+        "memory_size (1GB)",
+        iterations,
+    );
+
+    vec![
+        BenchResult {
+            name: "Startup initial 1GB".into(),
+            col_a: Some(copy_time),
+            col_b: None,
+            base: initial_time,
+            bytes: None,
+            verified: None,
+        },
+        BenchResult {
+            name: "Startup zeroed 1GB".into(),
+            col_a: Some(copy_time),
+            col_b: None,
+            base: zeroed_time,
+            bytes: None,
+            verified: None,
+        },
+    ]
+}
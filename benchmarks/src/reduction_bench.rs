@@ -59,18 +59,18 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let payload = build_payload(&data);
 
         // Raw Rust
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(rust_sum(&data));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
         // Burn (NdArray CPU)
-        let burn_ms = {
+        let burn_time = {
             use burn::tensor::{Tensor, TensorData};
             let device = Default::default();
             let t = Tensor::<B, 1>::from_data(TensorData::new(data.to_vec(), [n]), &device);
-            harness::median_of(iterations, || {
+            harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 std::hint::black_box(t.clone().sum().into_scalar());
                 start.elapsed().as_secs_f64() * 1000.0
@@ -83,7 +83,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         // Warmup
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -97,9 +97,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: format!("Sum ({})", format_count(n)),
-            col_a_ms: Some(rust_ms),
-            col_b_ms: Some(burn_ms),
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: Some(burn_time),
+            base: base_time,
+            bytes: None,
             verified,
         });
     }
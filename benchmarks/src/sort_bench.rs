@@ -75,7 +75,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let mut out_buf = vec![0u8; out_size];
 
         // Rust
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(rust_sort(&values));
             start.elapsed().as_secs_f64() * 1000.0
@@ -85,7 +85,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
         // Base
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -98,9 +98,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: label,
-            col_a_ms: Some(rust_ms),
-            col_b_ms: None,
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: None,
+            base: base_time,
+            bytes: None,
             verified: Some(rust_ok && base_ok),
         });
     }
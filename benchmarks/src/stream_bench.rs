@@ -0,0 +1,172 @@
+use crate::harness::{self, BenchResult, Timing};
+use base::{Algorithm, Base, Setup};
+
+// ---------------------------------------------------------------------------
+// File Streaming Benchmark
+//
+// FNV-1a over a FILE_BYTES file. "Chunked read" calls cl_file_read for each
+// CHUNK-byte piece and hashes it before reading the next, so reads and
+// compute take turns. Base starts cl_file_stream_start once and hashes from
+// a RING-byte ring while the reader thread fills the space behind it.
+// ---------------------------------------------------------------------------
+
+const FILE_BYTES: usize = 2 << 30;
+const CHUNK: usize = 1 << 20;
+const RING: usize = 4 << 20;
+
+// Path at 256, ring header at 512, config at 528, data at 1024.
+const DATA: usize = 1024;
+
+fn stream_clif() -> String {
+    format!(
+        r#"function u0:0(i64) system_v {{
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_stream_start sig0
+    sig1 = (i64) -> i64 system_v
+    fn1 = %cl_file_stream_end sig1
+    sig2 = (i64, i64, i64) -> i64 system_v
+    fn2 = %cl_thread_wait_until sig2
+    sig3 = (i64, i64) -> i64 system_v
+    fn3 = %cl_thread_wake sig3
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 512
+    v3 = iconst.i64 {data}
+    v4 = iconst.i64 528
+    v5 = call fn0(v0, v1, v2, v3, v4)
+    v6 = iadd_imm v0, 512
+    v7 = iadd_imm v0, 520
+    v8 = iconst.i64 0
+    v9 = iconst.i64 0xcbf29ce484222325
+    jump block1(v8, v9)
+
+block1(v10: i64, v11: i64):
+    v12 = iadd_imm v10, 1
+    v13 = iconst.i64 2
+    v14 = call fn2(v6, v12, v13)
+    v15 = load.i64 v0+512
+    v16 = band_imm v15, 0x7fffffffffffffff
+    jump block2(v10, v11)
+
+block2(v20: i64, v21: i64):
+    v22 = icmp uge v20, v16
+    brif v22, block3, block4
+
+block4:
+    v23 = band_imm v20, {ring_mask}
+    v24 = iadd v0, v23
+    v25 = uload8.i64 v24+{data}
+    v26 = bxor v21, v25
+    v27 = imul_imm v26, 0x100000001b3
+    v28 = iadd_imm v20, 1
+    jump block2(v28, v27)
+
+block3:
+    v30 = call fn3(v7, v20)
+    v31 = icmp_imm slt v15, 0
+    brif v31, block5, block1(v20, v21)
+
+block5:
+    v32 = call fn1(v5)
+    v33 = load.i64 v0+40
+    store.i64 v21, v33
+    store.i64 v20, v33+8
+    return
+}}
+
+function u0:1(i64) system_v {{
+    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v
+    fn0 = %cl_file_read sig0
+block0(v0: i64):
+    v1 = iconst.i64 256
+    v2 = iconst.i64 {data}
+    v3 = iconst.i64 {chunk}
+    v4 = iconst.i64 0
+    v5 = iconst.i64 0xcbf29ce484222325
+    jump block1(v4, v5)
+
+block1(v10: i64, v11: i64):
+    v12 = call fn0(v0, v1, v2, v10, v3)
+    v13 = icmp_imm sle v12, 0
+    brif v13, block5, block2(v4, v11)
+
+block2(v20: i64, v21: i64):
+    v22 = icmp uge v20, v12
+    brif v22, block3, block4
+
+block4:
+    v24 = iadd v0, v20
+    v25 = uload8.i64 v24+{data}
+    v26 = bxor v21, v25
+    v27 = imul_imm v26, 0x100000001b3
+    v28 = iadd_imm v20, 1
+    jump block2(v28, v27)
+
+block3:
+    v30 = iadd v10, v12
+    jump block1(v30, v21)
+
+block5:
+    v33 = load.i64 v0+40
+    store.i64 v11, v33
+    store.i64 v10, v33+8
+    return
+}}"#,
+        data = DATA,
+        ring_mask = RING - 1,
+        chunk = CHUNK
+    )
+}
+
+fn write_input(path: &std::path::Path) {
+    use std::io::Write;
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    let mut x = 0x9E37_79B9_7F4A_7C15u64;
+    let mut block = vec![0u8; CHUNK];
+    for _ in 0..FILE_BYTES / CHUNK {
+        for b in block.iter_mut() {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            *b = x as u8;
+        }
+        file.write_all(&block).unwrap();
+    }
+    file.flush().unwrap();
+}
+
+fn hash_time(path: &std::path::Path, fn_idx: u32, iterations: usize) -> (Timing, [u8; 16]) {
+    let clif = stream_clif();
+    let mut memory = vec![0u8; DATA + RING.max(CHUNK)];
+    let path_bytes = path.to_str().unwrap().as_bytes();
+    memory[256..256 + path_bytes.len()].copy_from_slice(path_bytes);
+    memory[528..536].copy_from_slice(&(CHUNK as u64).to_le_bytes());
+    memory[536..544].copy_from_slice(&(RING as u64).to_le_bytes());
+    let mut out = [0u8; 16];
+    let time = harness::time_of(iterations, || {
+        let setup = Setup::with_initial_memory(&clif, memory.clone());
+        let mut base = Base::new(setup).expect("Base::new failed");
+        let start = std::time::Instant::now();
+        let _ = base.execute_into(&Algorithm::new(fn_idx), &[], &mut out);
+        start.elapsed().as_secs_f64() * 1000.0
+    });
+    (time, out)
+}
+
+pub fn run(iterations: usize) -> Vec<BenchResult> {
+    let path = std::env::temp_dir().join("base_stream_bench.bin");
+    write_input(&path);
+    let (stream_time, streamed) = hash_time(&path, 0, iterations);
+    let (chunked_time, chunked) = hash_time(&path, 1, iterations);
+    let _ = std::fs::remove_file(&path);
+    let total = u64::from_le_bytes(streamed[8..].try_into().unwrap());
+
+    vec![BenchResult {
+        name: format!("FNV-1a {} GiB", FILE_BYTES >> 30),
+        col_a: Some(chunked_time),
+        col_b: None,
+        base: stream_time,
+        bytes: Some(FILE_BYTES as u64),
+        verified: Some(streamed == chunked && total == FILE_BYTES as u64),
+    }]
+}
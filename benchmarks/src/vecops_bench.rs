@@ -71,19 +71,19 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let payload = build_payload(&a, &b);
 
         // Raw Rust
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             std::hint::black_box(rust_vec_add(&a, &b));
             start.elapsed().as_secs_f64() * 1000.0
         });
 
         // Burn (NdArray CPU)
-        let burn_ms = {
+        let burn_time = {
             use burn::tensor::{Tensor, TensorData};
             let device = Default::default();
             let a_t = Tensor::<B, 1>::from_data(TensorData::new(a.to_vec(), [n]), &device);
             let b_t = Tensor::<B, 1>::from_data(TensorData::new(b.to_vec(), [n]), &device);
-            harness::median_of(iterations, || {
+            harness::time_of(iterations, || {
                 let start = std::time::Instant::now();
                 let c_t = a_t.clone() + b_t.clone();
                 std::hint::black_box(c_t.sum().into_scalar());
@@ -97,7 +97,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         // Warmup
         let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
 
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let _ = base_instance.execute_into(&artifact.main, &payload, &mut out_buf);
             start.elapsed().as_secs_f64() * 1000.0
@@ -111,9 +111,11 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: format!("VecAdd ({})", format_count(n)),
-            col_a_ms: Some(rust_ms),
-            col_b_ms: Some(burn_ms),
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: Some(burn_time),
+            base: base_time,
+            // Both input arrays, so GB/s can be compared across machines.
+            bytes: Some(n as u64 * 8),
             verified,
         });
     }
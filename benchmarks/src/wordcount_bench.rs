@@ -78,7 +78,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
         let payload = build_payload(&text_path, &output_path);
 
         // Pure Rust
-        let rust_ms = harness::median_of(iterations, || {
+        let rust_time = harness::time_of(iterations, || {
             let start = std::time::Instant::now();
             let got = rust_wordcount(&text_path);
             let ms = start.elapsed().as_secs_f64() * 1000.0;
@@ -90,7 +90,7 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         // Base (Cranelift JIT) — fresh instance per execution because HT state
         // accumulates across execute() calls (ht_increment on handle 0 persists).
-        let base_ms = harness::median_of(iterations, || {
+        let base_time = harness::time_of(iterations, || {
             let _ = fs::remove_file(&output_path);
             let artifact = Artifact::from_bytes(WC_ARTIFACT);
             let mut base_instance = base::Base::new(artifact.setup).expect("Base::new failed");
@@ -135,9 +135,10 @@ pub fn run(iterations: usize) -> Vec<BenchResult> {
 
         results.push(BenchResult {
             name: format!("WC ({})", format_count(n)),
-            col_a_ms: Some(rust_ms),
-            col_b_ms: None,
-            base_ms,
+            col_a: Some(rust_time),
+            col_b: None,
+            base: base_time,
+            bytes: None,
             verified,
         });
     }
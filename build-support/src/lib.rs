@@ -109,7 +109,7 @@ fn ensure_success(output: Output, header: &str, panic_message: &str) -> Output {
 
 fn write_binaries(lean_file: &Path, generator_out_dir: &Path) {
     for (artifact_name, artifact) in read_generated_artifacts(lean_file, generator_out_dir) {
-        let binary = bincode::serialize(&artifact).expect("Failed to serialize bincode");
+        let binary = artifact.to_versioned_bytes();
         fs::write(
             generator_out_dir.join(format!("{artifact_name}.bin")),
             binary,
@@ -197,10 +197,14 @@ mod tests {
                     out_len: 0x30,
                 },
                 initial_memory: vec![1, 2, 3],
+                arena_offset: 0,
+                arena_size: 0,
             },
             main: Algorithm {
                 fn_idx: 1,
                 output: vec![],
+                symbols: vec![],
+                labels: vec![],
             },
             extras: HashMap::new(),
         }
@@ -0,0 +1,54 @@
+/* C bindings for Base, implemented by the c-base crate (libc_base). */
+
+#ifndef BASE_H
+#define BASE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Return codes of base_execute. Failures also leave a message for
+   base_last_error. */
+#define BASE_OK 0
+#define BASE_ERR_INVALID_ARGUMENT -1
+#define BASE_ERR_CLIF_PARSE -2
+#define BASE_ERR_COMPILE -3
+#define BASE_ERR_EXECUTION -4
+#define BASE_ERR_CANCELLED -5
+#define BASE_ERR_TIMEOUT -6
+#define BASE_ERR_GPU_INIT -7
+#define BASE_ERR_FN_INDEX -8
+#define BASE_ERR_SYMBOL -9
+#define BASE_ERR_CHECKPOINT -10
+
+/* A compiled instance with its memory region. */
+typedef struct Base Base;
+
+/* JIT-compile the CLIF source clif_ir into a new instance whose memory starts
+   as the initial_len bytes at initial_memory (NULL when 0), zero-extended to
+   memory_size bytes. The IO slots use the default offsets (data pointer and
+   length at 0x18/0x20, out pointer and length at 0x28/0x30). Returns NULL on
+   failure. */
+Base *base_new(const char *clif_ir, const uint8_t *initial_memory, size_t initial_len,
+               size_t memory_size);
+
+/* Run function fn_idx with data readable and out writable through the IO
+   slots. Returns BASE_OK or a negative BASE_ERR_* code. */
+int32_t base_execute(Base *base, uint32_t fn_idx, const uint8_t *data, size_t data_len,
+                     uint8_t *out, size_t out_len);
+
+/* Free an instance from base_new. NULL is ignored. */
+void base_free(Base *base);
+
+/* Message of the last failed call on this thread, or NULL. Valid until the
+   next failing call on the same thread. */
+const char *base_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BASE_H */
@@ -0,0 +1,165 @@
+//! C bindings for Base. The functions here are declared in `include/base.h`;
+//! keep the two in sync (`tests/c_tests.rs` checks the names).
+//!
+//! Failures return a negative `BASE_ERR_*` code and leave a message for
+//! `base_last_error` on the calling thread.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use base::{Algorithm, Base, Error, Setup};
+
+pub const BASE_OK: i32 = 0;
+pub const BASE_ERR_INVALID_ARGUMENT: i32 = -1;
+pub const BASE_ERR_CLIF_PARSE: i32 = -2;
+pub const BASE_ERR_COMPILE: i32 = -3;
+pub const BASE_ERR_EXECUTION: i32 = -4;
+pub const BASE_ERR_CANCELLED: i32 = -5;
+pub const BASE_ERR_TIMEOUT: i32 = -6;
+pub const BASE_ERR_GPU_INIT: i32 = -7;
+pub const BASE_ERR_FN_INDEX: i32 = -8;
+pub const BASE_ERR_SYMBOL: i32 = -9;
+pub const BASE_ERR_CHECKPOINT: i32 = -10;
+
+/// The `BASE_ERR_*` code for `error`.
+pub fn error_code(error: &Error) -> i32 {
+    match error {
+        Error::ClifParse(_) => BASE_ERR_CLIF_PARSE,
+        Error::Compile { .. } => BASE_ERR_COMPILE,
+        Error::Cancelled => BASE_ERR_CANCELLED,
+        Error::Timeout(_) => BASE_ERR_TIMEOUT,
+        Error::GpuInit(_) => BASE_ERR_GPU_INIT,
+        Error::FnIndexOutOfRange { .. } => BASE_ERR_FN_INDEX,
+        Error::Symbol(_) => BASE_ERR_SYMBOL,
+        Error::Checkpoint(_) => BASE_ERR_CHECKPOINT,
+        _ => BASE_ERR_EXECUTION,
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|cell| *cell.borrow_mut() = Some(message));
+}
+
+/// Record `error` for `base_last_error` and return its code.
+fn fail(error: &Error) -> i32 {
+    set_error(error.to_string());
+    error_code(error)
+}
+
+/// `(ptr, len)` from C as a slice; NULL is only accepted with length 0.
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (ptr.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(std::slice::from_raw_parts(ptr, len)),
+    }
+}
+
+/// The message of the last failed call on this thread, or NULL. Valid until
+/// the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn base_last_error() -> *const c_char {
+    LAST_ERROR.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |m| m.as_ptr())
+    })
+}
+
+/// JIT-compile the NUL-terminated CLIF source `clif_ir` into a new instance
+/// whose memory starts as the `initial_len` bytes at `initial_memory` (NULL
+/// when 0) and is zero-extended to `memory_size` bytes. The IO slots use the
+/// default offsets (data at 0x18/0x20, out at 0x28/0x30). Returns NULL on
+/// failure; free the instance with `base_free`.
+///
+/// # Safety
+/// `clif_ir` must be a valid C string and `initial_memory` must point to
+/// `initial_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn base_new(
+    clif_ir: *const c_char,
+    initial_memory: *const u8,
+    initial_len: usize,
+    memory_size: usize,
+) -> *mut Base {
+    if clif_ir.is_null() {
+        set_error("clif_ir is NULL");
+        return std::ptr::null_mut();
+    }
+    let Some(initial) = slice(initial_memory, initial_len) else {
+        set_error("initial_memory is NULL");
+        return std::ptr::null_mut();
+    };
+    let clif_ir = CStr::from_ptr(clif_ir).to_string_lossy().into_owned();
+    let mut setup = Setup::with_initial_memory(clif_ir, initial.to_vec());
+    setup.memory_size = setup.memory_size.max(memory_size);
+    match catch_unwind(|| Base::new(setup)) {
+        Ok(Ok(base)) => Box::into_raw(Box::new(base)),
+        Ok(Err(e)) => {
+            fail(&e);
+            std::ptr::null_mut()
+        }
+        Err(_) => {
+            set_error("panic while compiling");
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Run function `fn_idx` with `data` readable and `out` writable through the
+/// IO slots. Returns `BASE_OK` or a negative `BASE_ERR_*` code.
+///
+/// # Safety
+/// `base` must come from `base_new` and not be used from another thread
+/// meanwhile; `data` and `out` must point to `data_len` readable and
+/// `out_len` writable bytes (either may be NULL when its length is 0).
+#[no_mangle]
+pub unsafe extern "C" fn base_execute(
+    base: *mut Base,
+    fn_idx: u32,
+    data: *const u8,
+    data_len: usize,
+    out: *mut u8,
+    out_len: usize,
+) -> i32 {
+    let (Some(base), Some(data)) = (base.as_mut(), slice(data, data_len)) else {
+        set_error("base or data is NULL");
+        return BASE_ERR_INVALID_ARGUMENT;
+    };
+    let out: &mut [u8] = match (out.is_null(), out_len) {
+        (true, 0) => &mut [],
+        (true, _) => {
+            set_error("out is NULL");
+            return BASE_ERR_INVALID_ARGUMENT;
+        }
+        (false, _) => std::slice::from_raw_parts_mut(out, out_len),
+    };
+    let algorithm = Algorithm::new(fn_idx);
+    match catch_unwind(AssertUnwindSafe(|| {
+        base.execute_into(&algorithm, data, out)
+    })) {
+        Ok(Ok(_)) => BASE_OK,
+        Ok(Err(e)) => fail(&e),
+        Err(_) => {
+            set_error("panic during execution");
+            BASE_ERR_EXECUTION
+        }
+    }
+}
+
+/// Free an instance from `base_new`. NULL is ignored.
+///
+/// # Safety
+/// `base` must come from `base_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn base_free(base: *mut Base) {
+    if !base.is_null() {
+        drop(Box::from_raw(base));
+    }
+}
@@ -0,0 +1,68 @@
+//! Compiles `tests/test_base.c` against `include/base.h` and the crate's
+//! cdylib with the system C compiler (`$CC`, default `cc`), then runs it.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_program_round_trip() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // The test binary sits in target/<profile>/deps next to libc_base.
+    let lib_dir = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let tmp = tempfile::TempDir::new().unwrap();
+    let exe = tmp.path().join("test_base");
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(&cc)
+        .arg(manifest_dir.join("tests/test_base.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lc_base", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .status()
+        .unwrap_or_else(|e| panic!("failed to spawn `{cc}`: {e}"));
+    assert!(status.success(), "compiling test_base.c failed");
+
+    let path = tmp.path().join("c api out.bin");
+    let output = Command::new(&exe).arg(&path).output().unwrap();
+    assert!(
+        output.status.success(),
+        "test_base failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        std::fs::read(&path).unwrap(),
+        [1, 2, 3, 4, 5, 6, 7, 8].repeat(2)
+    );
+}
+
+#[test]
+fn header_declares_every_export() {
+    let header = include_str!("../include/base.h");
+    let source = include_str!("../src/lib.rs");
+    for line in source.lines() {
+        let Some(rest) = line.split("extern \"C\" fn ").nth(1) else {
+            continue;
+        };
+        let name = rest.split('(').next().unwrap();
+        assert!(
+            header.contains(&format!("{name}(")),
+            "{name} missing from base.h"
+        );
+    }
+    for line in source.lines().filter(|l| l.starts_with("pub const BASE_")) {
+        let name = line["pub const ".len()..].split(':').next().unwrap();
+        let value = line.split("= ").nth(1).unwrap().trim_end_matches(';');
+        assert!(
+            header.contains(&format!("#define {name} {value}\n")),
+            "{name} = {value} missing from base.h"
+        );
+    }
+}
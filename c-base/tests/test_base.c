@@ -0,0 +1,83 @@
+/* Exercises the C API end to end: copies 8 bytes of input within memory,
+   writes both copies to the file named by argv[1], and returns them through
+   out. Exits non-zero with a message on the first failed check. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "base.h"
+
+#define CHECK(cond)                                                          \
+    do {                                                                     \
+        if (!(cond)) {                                                       \
+            const char *err = base_last_error();                             \
+            fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__,      \
+                    __LINE__, #cond, err ? err : "no error");                \
+            return 1;                                                        \
+        }                                                                    \
+    } while (0)
+
+/* Loads 8 input bytes through the data pointer at 0x18 into 256, copies them
+   to 264 with cl_mem_copy, writes 256..272 to the path at 512 and stores the
+   same 16 bytes through the out pointer at 0x28. */
+static const char *CLIF =
+    "function u0:0(i64) system_v {\n"
+    "    sig0 = (i64, i64, i64, i64, i64) -> i64 system_v\n"
+    "    fn0 = %cl_mem_copy sig0\n"
+    "    fn1 = %cl_file_write sig0\n"
+    "block0(v0: i64):\n"
+    "    v1 = load.i64 v0+24\n"
+    "    v2 = load.i64 v1\n"
+    "    store.i64 v2, v0+256\n"
+    "    v3 = iconst.i64 264\n"
+    "    v4 = iconst.i64 256\n"
+    "    v5 = iconst.i64 8\n"
+    "    v6 = iconst.i64 1\n"
+    "    v7 = call fn0(v0, v3, v4, v5, v6)\n"
+    "    v8 = iconst.i64 512\n"
+    "    v9 = iconst.i64 0\n"
+    "    v10 = iconst.i64 16\n"
+    "    v11 = call fn1(v0, v8, v4, v9, v10)\n"
+    "    v12 = load.i64 v0+40\n"
+    "    v13 = load.i64 v0+256\n"
+    "    v14 = load.i64 v0+264\n"
+    "    store.i64 v13, v12\n"
+    "    store.i64 v14, v12+8\n"
+    "    return\n"
+    "}\n";
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+    const char *path = argv[1];
+
+    uint8_t memory[1024] = {0};
+    CHECK(strlen(path) < sizeof(memory) - 512);
+    memcpy(memory + 512, path, strlen(path));
+
+    Base *base = base_new(CLIF, memory, sizeof(memory), 0);
+    CHECK(base != NULL);
+
+    const uint8_t data[8] = {1, 2, 3, 4, 5, 6, 7, 8};
+    uint8_t out[16] = {0};
+    CHECK(base_execute(base, 0, data, sizeof(data), out, sizeof(out)) == BASE_OK);
+    CHECK(memcmp(out, data, 8) == 0 && memcmp(out + 8, data, 8) == 0);
+
+    uint8_t file[17] = {0};
+    FILE *f = fopen(path, "rb");
+    CHECK(f != NULL);
+    size_t n = fread(file, 1, sizeof(file), f);
+    fclose(f);
+    CHECK(n == 16 && memcmp(file, out, 16) == 0);
+
+    CHECK(base_execute(base, 3, data, sizeof(data), out, sizeof(out)) == BASE_ERR_FN_INDEX);
+    CHECK(strstr(base_last_error(), "out of range") != NULL);
+    CHECK(base_execute(base, 0, NULL, 8, out, sizeof(out)) == BASE_ERR_INVALID_ARGUMENT);
+    base_free(base);
+
+    CHECK(base_new("function u0:0(i64) {", NULL, 0, 64) == NULL);
+    CHECK(strstr(base_last_error(), "CLIF parse error") != NULL);
+    base_free(NULL);
+
+    printf("c-base: all checks passed\n");
+    return 0;
+}
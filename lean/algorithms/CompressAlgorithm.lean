@@ -399,7 +399,8 @@ def buildCompressor {bs : Nat} (_p : LZ4Params bs) : Setup × Algorithm :=
     initial_memory := payload
   }
   let alg : Algorithm := {
-    fn_idx := IR.mainFnIdx
+    fn_idx := IR.mainFnIdx,
+    symbols := [{ name := "input_path", offset := inputFilename_off, len := filenameRegionSize }]
   }
   (cfg, alg)
 
@@ -271,7 +271,8 @@ def fftConfig : Setup := {
 }
 
 def fftAlgorithm : Algorithm := {
-    fn_idx := IR.mainFnIdx
+    fn_idx := IR.mainFnIdx,
+    symbols := [{ name := "input_path", offset := inputFilename_off, len := filenameRegionSize }]
   }
 
 end Algorithm
@@ -946,7 +946,9 @@ def satConfig : Setup := {
 }
 
 def satAlgorithm : Algorithm := {
-    fn_idx := IR.mainFnIdx
+    fn_idx := IR.mainFnIdx,
+    symbols := [{ name := "input_path", offset := inputFilename_off,
+                  len := outputFilename_off - inputFilename_off }]
   }
 
 end Algorithm
@@ -554,7 +554,8 @@ def sha256Config : Setup := {
 }
 
 def sha256Algorithm : Algorithm := {
-    fn_idx := IR.mainFnIdx
+    fn_idx := IR.mainFnIdx,
+    symbols := [{ name := "input_path", offset := f.inputFilename.offset, len := 256 }]
   }
 
 end Algorithm
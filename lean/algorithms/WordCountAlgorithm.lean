@@ -10,7 +10,7 @@ namespace WordCountBench
 /-
   Word frequency counting: parse words, ht_increment, format word\tcount\n output.
   Payload: "input_path\0output_path\0"
-  HT context at offset 0x00, colocated ht_create/ht_increment/ht_count/ht_get_entry.
+  HT context at offset 0x00, colocated ht_create_with_capacity/ht_increment/ht_count/ht_get_entry.
 -/
 
 def CURRENT_KEY     : Nat := 0x0038
@@ -22,6 +22,8 @@ def OUTPUT_BUF      : Nat := 0x4000
 def INPUT_DATA      : Nat := 0x14000
 def MAX_TEXT_BYTES  : Nat := 512 * 1024 * 1024
 def MEM_SIZE        : Nat := INPUT_DATA + MAX_TEXT_BYTES
+-- Initial table capacity, so typical vocabularies load without rehashing
+def HT_CAPACITY     : Nat := 4096
 
 structure WcCtx where
   ptr       : Val
@@ -233,7 +235,7 @@ def mainFn : IRBuilder Unit := do
   let fnHtClean← declareFFI "cl_ht_cleanup"  [.i64] none
   let fnRead   ← declareFileRead
   let fnWrite  ← declareFileWrite
-  let fnCreate ← declareColocatedFFI "ht_create"    [.i64]                   (some .i32)
+  let fnCreate ← declareColocatedFFI "ht_create_with_capacity" [.i64, .i64] (some .i32)
   let fnIncr   ← declareColocatedFFI "ht_increment" [.i64, .i64, .i32, .i64] (some .i64)
   let fnCount  ← declareColocatedFFI "ht_count"     [.i64]                   (some .i32)
   let fnGet    ← declareColocatedFFI "ht_get_entry"  [.i64, .i32, .i64, .i64] (some .i32)
@@ -278,7 +280,7 @@ def mainFn : IRBuilder Unit := do
   let fileSize ← readFile ptr fnRead INPUT_PATH_OFF INPUT_DATA
   let inputBase← absAddr ptr INPUT_DATA
   let ctxPtr   ← load64 (← absAddr ptr 0)
-  let _        ← call fnCreate [ctxPtr]
+  let _        ← call fnCreate [ctxPtr, ← iconst64 HT_CAPACITY]
   jump skipWS.ref [zero, ctxPtr]
 
   emitParsePhase k fileSize inputBase
@@ -48,6 +48,9 @@ structure Setup where
   memory_size : Nat
   io_offsets : IoOffsets := {}
   initial_memory : List UInt8 := []
+  /-- Bump arena handed out by `cl_arena_bump`; none when `arena_size` is 0. -/
+  arena_offset : Nat := 0
+  arena_size : Nat := 0
   deriving Repr
 
 namespace ContextSlots
@@ -66,19 +69,50 @@ instance : ToJson Setup where
     ("cranelift_ir", toJson c.cranelift_ir),
     ("memory_size", toJson c.memory_size),
     ("io_offsets", toJson c.io_offsets),
-    ("initial_memory", toJson c.initial_memory)
+    ("initial_memory", toJson c.initial_memory),
+    ("arena_offset", toJson c.arena_offset),
+    ("arena_size", toJson c.arena_size)
+  ]
+
+/-- A named region of memory the host fills in by name before each run
+    (`Algorithm::set_symbol_str` / `set_symbol_u64` on the Rust side), instead
+    of patching a hard-coded offset. `writable` marks scratch space the code
+    writes on purpose; validation reports constant-address writes into any
+    other symbol. -/
+structure Symbol where
+  name : String
+  offset : Nat
+  len : Nat
+  writable : Bool := false
+
+instance : ToJson Symbol where
+  toJson s := Json.mkObj [
+    ("name", toJson s.name),
+    ("offset", toJson s.offset),
+    ("len", toJson s.len),
+    ("writable", toJson s.writable)
   ]
 
 structure Algorithm where
   fn_idx : UInt32
   output : List Json := []
+  symbols : List Symbol := []
+  /-- Names of user functions by index, shown in validation issues, execution
+      errors, tracing spans and profiles. -/
+  labels : List (UInt32 × String) := []
 
 instance : ToJson Algorithm where
   toJson alg := Json.mkObj [
     ("fn_idx", toJson alg.fn_idx),
-    ("output", Json.arr alg.output.toArray)
+    ("output", Json.arr alg.output.toArray),
+    ("symbols", toJson alg.symbols),
+    ("labels", Json.arr (alg.labels.map fun (i, l) => Json.arr #[toJson i, toJson l]).toArray)
   ]
 
+/-- Label the function `alg` runs. -/
+def Algorithm.labeled (alg : Algorithm) (label : String) : Algorithm :=
+  { alg with labels := alg.labels ++ [(alg.fn_idx, label)] }
+
 /- Output-schema JSON builders. `Algorithm.output` is a list of these schema
    objects; each becomes one Arrow RecordBatch. The CLIF code must store the
    row count at `row_count_offset` and the column data at each column's
@@ -14,6 +14,83 @@ def declareFileRead : IRBuilder FnRef :=
 def declareFileWrite : IRBuilder FnRef :=
   declareFFI "cl_file_write" [.i64, .i64, .i64, .i64, .i64] (some .i64)
 
+/-- Declare cl_file_retry: (policy_ptr) -> 0.
+    Retries EINTR, EAGAIN and EIO failures of this thread's file calls under
+    a 6-byte policy laid out as for cl_net_retry; null turns retries off. -/
+def declareFileRetry : IRBuilder FnRef :=
+  declareFFI "cl_file_retry" [.i64] (some .i64)
+
+/-- Declare cl_file_write_atomic: (ptr, fname_off, src_off, size) -> bytes_written.
+    Replaces the file through a synced temporary file and rename; on failure
+    the previous file is left as it was. -/
+def declareFileWriteAtomic : IRBuilder FnRef :=
+  declareFFI "cl_file_write_atomic" [.i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_file_atomic_init / cl_file_atomic_cleanup: (ctx_slot_ptr).
+    Cleanup discards every writer that was not committed. -/
+def declareFileAtomicInit : IRBuilder FnRef :=
+  declareFFI "cl_file_atomic_init" [.i64] none
+
+def declareFileAtomicCleanup : IRBuilder FnRef :=
+  declareFFI "cl_file_atomic_cleanup" [.i64] none
+
+/-- Declare cl_file_atomic_open: (ctx, path_ptr) -> handle or -1 -/
+def declareFileAtomicOpen : IRBuilder FnRef :=
+  declareFFI "cl_file_atomic_open" [.i64, .i64] (some .i64)
+
+/-- Declare cl_file_atomic_write: (ctx, handle, src_ptr, file_offset, size) -> size or -1 -/
+def declareFileAtomicWrite : IRBuilder FnRef :=
+  declareFFI "cl_file_atomic_write" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_file_commit / cl_file_abort: (ctx, handle) -> 0 or -1.
+    Commit renames the pending file over its destination; abort drops it. -/
+def declareFileCommit : IRBuilder FnRef :=
+  declareFFI "cl_file_commit" [.i64, .i64] (some .i64)
+
+def declareFileAbort : IRBuilder FnRef :=
+  declareFFI "cl_file_abort" [.i64, .i64] (some .i64)
+
+/-- Declare cl_file_handle_init / cl_file_handle_cleanup: (ctx_slot_ptr).
+    Cleanup syncs and closes every file still open. -/
+def declareFileHandleInit : IRBuilder FnRef :=
+  declareFFI "cl_file_handle_init" [.i64] none
+
+def declareFileHandleCleanup : IRBuilder FnRef :=
+  declareFFI "cl_file_handle_cleanup" [.i64] none
+
+/-- `cl_file_open` mode flags. -/
+def openRead : Nat := 1
+def openWrite : Nat := 2
+def openAppend : Nat := 4
+def openCreate : Nat := 8
+def openTruncate : Nat := 16
+
+/-- Declare cl_file_open: (ctx, path_ptr, flags) -> handle or -1 -/
+def declareFileOpen : IRBuilder FnRef :=
+  declareFFI "cl_file_open" [.i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_file_close: (ctx, handle) -> 0 or -1 -/
+def declareFileClose : IRBuilder FnRef :=
+  declareFFI "cl_file_close" [.i64, .i64] (some .i64)
+
+/-- Declare cl_file_read_handle / cl_file_write_handle: (ctx, handle, ptr, file_offset, size) -> bytes or -1.
+    A negative `file_offset` uses the current position. -/
+def declareFileReadHandle : IRBuilder FnRef :=
+  declareFFI "cl_file_read_handle" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+def declareFileWriteHandle : IRBuilder FnRef :=
+  declareFFI "cl_file_write_handle" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_file_stream_start: (ptr, fname_off, ring_off, dst_off, config_off) -> handle.
+    Reads the file ahead into the ring at `dst_off` (chunk size and capacity at
+    `config_off`); returns 0 on failure. -/
+def declareFileStreamStart : IRBuilder FnRef :=
+  declareFFI "cl_file_stream_start" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_file_stream_end: (handle) -> total_bytes_read -/
+def declareFileStreamEnd : IRBuilder FnRef :=
+  declareFFI "cl_file_stream_end" [.i64] (some .i64)
+
 /-- Declare cl_stdin_readline: (ptr, dst_off, max_len) -> bytes_read -/
 def declareStdinReadline : IRBuilder FnRef :=
   declareFFI "cl_stdin_readline" [.i64, .i64, .i64] (some .i64)
@@ -22,6 +99,158 @@ def declareStdinReadline : IRBuilder FnRef :=
 def declareStdoutWrite : IRBuilder FnRef :=
   declareFFI "cl_stdout_write" [.i64, .i64, .i64] (some .i64)
 
+/-- Declare cl_mem_fill: (ptr, dst_off, size, pattern, width) -> bytes_filled.
+    `width` (1, 2, 4, or 8) selects how many low bytes of `pattern` repeat. -/
+def declareMemFill : IRBuilder FnRef :=
+  declareFFI "cl_mem_fill" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_copy: (ptr, dst_off, src_off, size, workers) -> size copied -/
+def declareMemCopy : IRBuilder FnRef :=
+  declareFFI "cl_mem_copy" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_compare: (ptr, a_off, b_off, size) -> -1 if equal, else first differing index -/
+def declareMemCompare : IRBuilder FnRef :=
+  declareFFI "cl_mem_compare" [.i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_scan: (ptr, hay_off, hay_len, pat_off, pat_len, out_off, flags) -> first offset or match count.
+    flags bit 0 = all matches, bit 1 = non-overlapping, bit 2 = masked (a mask of pat_len bytes
+    follows the pattern; 0xFF = exact, 0x00 = wildcard), bits 32.. = result capacity -/
+def declareMemScan : IRBuilder FnRef :=
+  declareFFI "cl_mem_scan" [.i64, .i64, .i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_cond_write: (ptr, cond_off, dst_off, src_off, size) -> bytes written (0 if not taken).
+    Bits 60..63 of `size` pick the mode: 1 = copy if the 8 condition bytes are non-zero,
+    2 = select `src_off` or the `size` bytes after it, 3 = copy if i64 > 0, 4 = copy if f64 > 0 -/
+def declareMemCondWrite : IRBuilder FnRef :=
+  declareFFI "cl_mem_cond_write" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_byteswap: (ptr, dst_off, src_off, width, size) -> size or -1.
+    Reverses the bytes of each `width`-byte (2, 4, or 8) element; `size` must be a multiple of `width`. -/
+def declareMemByteswap : IRBuilder FnRef :=
+  declareFFI "cl_mem_byteswap" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_publish: (ptr, seq_off, dst_off, src_off, size) -> size or -1.
+    Seqlock writer: the u64 at `seq_off` is odd during the copy and 2 higher after it. -/
+def declareMemPublish : IRBuilder FnRef :=
+  declareFFI "cl_mem_publish" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_mem_snapshot: (ptr, seq_off, dst_off, src_off, size) -> size, -1, or -2 if cancelled.
+    Seqlock reader: retries the copy until no `cl_mem_publish` overlapped it. -/
+def declareMemSnapshot : IRBuilder FnRef :=
+  declareFFI "cl_mem_snapshot" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- `cl_mem_array_op` element operations. -/
+def arrayAddF32 : Nat := 1
+def arraySubF32 : Nat := 2
+def arrayMulF32 : Nat := 3
+def arrayAddI32 : Nat := 4
+def arraySubI32 : Nat := 5
+def arrayMulI32 : Nat := 6
+
+/-- Declare cl_mem_array_op: (ptr, dst_off, a_off, b_off, size, op) -> size or -1.
+    Applies `op` to each pair of 4-byte elements; `dst_off` may equal a source but not partially overlap one. -/
+def declareMemArrayOp : IRBuilder FnRef :=
+  declareFFI "cl_mem_array_op" [.i64, .i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_lz4_compress: (ptr, dst_off, src_off, size, capacity) -> compressed length or -1.
+    `capacity` of `size + size / 255 + 16` always suffices. -/
+def declareLz4Compress : IRBuilder FnRef :=
+  declareFFI "cl_lz4_compress" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_lz4_decompress: (ptr, dst_off, src_off, size, capacity) -> decompressed length or -1 -/
+def declareLz4Decompress : IRBuilder FnRef :=
+  declareFFI "cl_lz4_decompress" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_checksum: (ptr, algo, src_off, size, dst_off) -> size or -1.
+    algo: 0 = CRC-32, 1 = CRC-32C, 2 = XXH64; add 256 to continue the CRC at dst_off. -/
+def declareChecksum : IRBuilder FnRef :=
+  declareFFI "cl_checksum" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_arena_init: (ctx_slot_ptr) -> void -/
+def declareArenaInit : IRBuilder FnRef :=
+  declareFFI "cl_arena_init" [.i64] none
+
+/-- Declare cl_arena_alloc: (ctx, size) -> address of a zeroed region, or 0 -/
+def declareArenaAlloc : IRBuilder FnRef :=
+  declareFFI "cl_arena_alloc" [.i64, .i64] (some .i64)
+
+/-- Declare cl_arena_size: (ctx, addr) -> region size, or -1 -/
+def declareArenaSize : IRBuilder FnRef :=
+  declareFFI "cl_arena_size" [.i64, .i64] (some .i64)
+
+/-- Declare cl_arena_free: (ctx, addr) -> 0, or -1 for an unknown address -/
+def declareArenaFree : IRBuilder FnRef :=
+  declareFFI "cl_arena_free" [.i64, .i64] (some .i64)
+
+/-- Declare cl_arena_cleanup: (ctx_slot_ptr) -> void -/
+def declareArenaCleanup : IRBuilder FnRef :=
+  declareFFI "cl_arena_cleanup" [.i64] none
+
+/-- Declare cl_arena_bump: (size) -> address in the Setup arena, or 0 when full -/
+def declareArenaBump : IRBuilder FnRef :=
+  declareFFI "cl_arena_bump" [.i64] (some .i64)
+
+/-- Declare cl_arena_reset: () -> void -/
+def declareArenaReset : IRBuilder FnRef :=
+  declareFFI "cl_arena_reset" [] none
+
+/-- Declare cl_queue_init: (ptr, ring_off, capacity, slot_size) -> ring bytes -/
+def declareQueueInit : IRBuilder FnRef :=
+  declareFFI "cl_queue_init" [.i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_queue_push: (ptr, ring_off, src_off, size, flags) -> size, or 0 when full -/
+def declareQueuePush : IRBuilder FnRef :=
+  declareFFI "cl_queue_push" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_queue_pop: (ptr, ring_off, dst_off) -> packet length, or 0 when empty -/
+def declareQueuePop : IRBuilder FnRef :=
+  declareFFI "cl_queue_pop" [.i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_trace: (tag, a, b, c, d) -> 1 if recorded, 0 when not tracing -/
+def declareTrace : IRBuilder FnRef :=
+  declareFFI "cl_trace" [.i64, .i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_clock: (source: 0 wall ns, 1 monotonic ns, 2 sequence) -> reading -/
+def declareClock : IRBuilder FnRef :=
+  declareFFI "cl_clock" [.i64] (some .i64)
+
+/-- Declare cl_sleep: (ns) -> 0 | -2 if cancelled -/
+def declareSleep : IRBuilder FnRef :=
+  declareFFI "cl_sleep" [.i64] (some .i64)
+
+/-- Declare cl_random: (min, max) -> uniform value in [min, max] -/
+def declareRandom : IRBuilder FnRef :=
+  declareFFI "cl_random" [.i64, .i64] (some .i64)
+
+/-- Declare cl_random_weighted: (ptr, weights_off, count) -> index in [0, count)
+    drawn in proportion to `count` f64 weights, or -1 (status INVALID_ARGUMENT)
+    if a weight is negative or non-finite or they sum to zero -/
+def declareRandomWeighted : IRBuilder FnRef :=
+  declareFFI "cl_random_weighted" [.i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_approx: (func, x: f64, iters) -> f64.
+    func 0 sqrt, 1 rsqrt, 2 exp, 3 ln, 4 sin, 5 cos, 6 1/x; `iters` Newton
+    steps for sqrt/rsqrt/1/x (0 = exact), ignored by the rest -/
+def declareApprox : IRBuilder FnRef :=
+  declareFFI "cl_approx" [.i64, .f64, .i64] (some .f64)
+
+/-- Declare cl_cancelled: () -> 1 once the execution is cancelled, else 0 -/
+def declareCancelled : IRBuilder FnRef :=
+  declareFFI "cl_cancelled" [] (some .i64)
+
+/-- Declare cl_last_status: () -> completion word (low 32 bits status, high 32 bits payload) -/
+def declareLastStatus : IRBuilder FnRef :=
+  declareFFI "cl_last_status" [] (some .i64)
+
+/-- Declare cl_checkpoint: (ptr, path_off, len, inflight_off) -> len, -2 while work is in flight, or -1 -/
+def declareCheckpoint : IRBuilder FnRef :=
+  declareFFI "cl_checkpoint" [.i64, .i64, .i64, .i64] (some .i64)
+
+/-- Declare cl_execute_nested: (ptr, algo_off, algo_len, window_off, window_len, flags) -> 0 or -1.
+    flags bit 0 = strict (a failure cancels the execution), bits 8..16 = depth limit (0 = 4) -/
+def declareExecuteNested : IRBuilder FnRef :=
+  declareFFI "cl_execute_nested" [.i64, .i64, .i64, .i64, .i64, .i64] (some .i64)
+
 /-- GPU FFI function bundle -/
 structure GpuSetup where
   fnInit : FnRef
@@ -43,6 +272,54 @@ def declareGpuFFI : IRBuilder GpuSetup := do
   let fnCleanup ← declareFFI "cl_gpu_cleanup" [.i64] none
   pure { fnInit, fnCreateBuffer, fnUpload, fnDownload, fnCreatePipeline, fnDispatch, fnCleanup }
 
+/-- Declare cl_gpu_download_async: (ctx, buf_id, dst_ptr, size, flag_ptr) -> 0 or -1; *flag_ptr becomes 1 once the bytes land -/
+def declareGpuDownloadAsync : IRBuilder FnRef :=
+  declareFFI "cl_gpu_download_async" [.i64, .i32, .i64, .i64, .i64] (some .i32)
+
+/-- Declare cl_gpu_upload_typed: (ctx, buf_id, src_ptr, count, elem) -> 0 or -1.
+    Converts `count` host f32s to `elem` (0 f32, 1 f16, 2 unorm8) before the upload -/
+def declareGpuUploadTyped : IRBuilder FnRef :=
+  declareFFI "cl_gpu_upload_typed" [.i64, .i32, .i64, .i64, .i32] (some .i32)
+
+/-- Declare cl_gpu_download_typed: (ctx, buf_id, dst_ptr, count, elem) -> 0 or -1.
+    Reads `count` `elem` values back and stores them as f32 -/
+def declareGpuDownloadTyped : IRBuilder FnRef :=
+  declareFFI "cl_gpu_download_typed" [.i64, .i32, .i64, .i64, .i32] (some .i32)
+
+/-- Declare cl_gpu_poll: (ctx) -> async readbacks still in flight -/
+def declareGpuPoll : IRBuilder FnRef :=
+  declareFFI "cl_gpu_poll" [.i64] (some .i32)
+
+/-- Declare cl_gpu_wait: (ctx) -> 0 once every async readback has landed -/
+def declareGpuWait : IRBuilder FnRef :=
+  declareFFI "cl_gpu_wait" [.i64] (some .i32)
+
+/-- Declare cl_gpu_init_fallback: (ctx_slot, force_cpu) -> 1 on a GPU device, 0 on the CPU stand-in -/
+def declareGpuInitFallback : IRBuilder FnRef :=
+  declareFFI "cl_gpu_init_fallback" [.i64, .i32] (some .i32)
+
+/-- Declare cl_gpu_init_adapter: (ctx_slot, adapter, allow_oversubscribe) -> adapter index used or -1.
+    Adapters are numbered as base::enumerate_gpu_adapters(Backends::all()) lists them; past the last one,
+    allow_oversubscribe != 0 wraps onto adapter % count -/
+def declareGpuInitAdapter : IRBuilder FnRef :=
+  declareFFI "cl_gpu_init_adapter" [.i64, .i32, .i32] (some .i32)
+
+/-- Declare cl_gpu_pipeline_cpu: (ctx, pipeline_id, fn_index) -> 0 or -1.
+    fn_index runs instead of the shader on the CPU stand-in, given
+    [wg_x, wg_y, wg_z, (buffer ptr, byte len) per binding] as i64s -/
+def declareGpuPipelineCpu : IRBuilder FnRef :=
+  declareFFI "cl_gpu_pipeline_cpu" [.i64, .i32, .i64] (some .i32)
+
+/-- Declare cl_gpu_create_pipeline_regions: (ctx, shader_ptr, table_ptr, n_regions) -> pipeline id or -1.
+    Up to 8 16-byte entries [offset i64, len i32, read_only i32]; entry n is bound at @binding(n) -/
+def declareGpuCreatePipelineRegions : IRBuilder FnRef :=
+  declareFFI "cl_gpu_create_pipeline_regions" [.i64, .i64, .i64, .i32] (some .i32)
+
+/-- Declare cl_gpu_dispatch_regions: (ctx, pipeline_id, ptr, wg_x, wg_y, wg_z) -> 0 or -1.
+    Copies each region at its offset from ptr in, and the read-write ones back out -/
+def declareGpuDispatchRegions : IRBuilder FnRef :=
+  declareFFI "cl_gpu_dispatch_regions" [.i64, .i32, .i64, .i32, .i32, .i32] (some .i32)
+
 def gpuCtxSlotPtr (ptr : Val) (slotOffset : Nat := ContextSlots.wgpu) : IRBuilder Val :=
   absAddr ptr slotOffset
 
@@ -187,22 +464,41 @@ def windowCleanup (win : WindowSetup) (ptr : Val) (slotOffset : Nat := ContextSl
 structure LmdbSetup where
   fnInit : FnRef
   fnOpen : FnRef
+  fnOpenWith : FnRef
   fnBeginWriteTxn : FnRef
   fnPut : FnRef
   fnCommitWriteTxn : FnRef
   fnCursorScan : FnRef
   fnCleanup : FnRef
 
-/-- Declare all 7 LMDB FFI functions -/
+/-- Declare all 8 LMDB FFI functions -/
 def declareLmdbFFI : IRBuilder LmdbSetup := do
   let fnInit ← declareFFI "cl_lmdb_init" [.i64] none
   let fnOpen ← declareFFI "cl_lmdb_open" [.i64, .i64, .i32] (some .i32)
+  let fnOpenWith ← declareFFI "cl_lmdb_open_with" [.i64, .i64, .i64] (some .i32)
   let fnBeginWriteTxn ← declareFFI "cl_lmdb_begin_write_txn" [.i64, .i32] (some .i32)
   let fnPut ← declareFFI "cl_lmdb_put" [.i64, .i32, .i64, .i32, .i64, .i32] (some .i32)
   let fnCommitWriteTxn ← declareFFI "cl_lmdb_commit_write_txn" [.i64, .i32] (some .i32)
   let fnCursorScan ← declareFFI "cl_lmdb_cursor_scan" [.i64, .i32, .i64, .i32, .i32, .i64] (some .i32)
   let fnCleanup ← declareFFI "cl_lmdb_cleanup" [.i64] none
-  pure { fnInit, fnOpen, fnBeginWriteTxn, fnPut, fnCommitWriteTxn, fnCursorScan, fnCleanup }
+  pure { fnInit, fnOpen, fnOpenWith, fnBeginWriteTxn, fnPut, fnCommitWriteTxn, fnCursorScan, fnCleanup }
+
+/-- Declare cl_lmdb_close: (ctx, handle) -> 0, or -1 for an unknown or stale
+    handle; and cl_lmdb_handle_count: (ctx) -> open environments. -/
+def declareLmdbClose : IRBuilder (FnRef × FnRef) := do
+  let fnClose ← declareFFI "cl_lmdb_close" [.i64, .i32] (some .i32)
+  let fnCount ← declareFFI "cl_lmdb_handle_count" [.i64] (some .i32)
+  pure (fnClose, fnCount)
+
+/-- Declare cl_lmdb_get_bounded: (ctx, handle, key_ptr, key_len, result_ptr,
+    capacity) -> full value length, writing the u32 length and at most
+    `capacity` value bytes (0 = length only), or -1; and
+    cl_lmdb_cursor_scan_bounded: (ctx, handle, key_ptr, key_len, max_entries,
+    result_ptr, capacity) -> entries, writing at most `capacity` bytes. -/
+def declareLmdbBounded : IRBuilder (FnRef × FnRef) := do
+  let fnGet ← declareFFI "cl_lmdb_get_bounded" [.i64, .i32, .i64, .i32, .i64, .i32] (some .i32)
+  let fnScan ← declareFFI "cl_lmdb_cursor_scan_bounded" [.i64, .i32, .i64, .i32, .i32, .i64, .i32] (some .i32)
+  pure (fnGet, fnScan)
 
 -- ---------------------------------------------------------------------------
 -- Hash-table FFI wrappers
@@ -221,6 +517,28 @@ def declareHtFFI : IRBuilder HtSetup := do
   let fnInsert ← declareColocatedFFI "ht_insert" [.i64, .i64, .i32, .i64, .i32] none
   pure { fnCreate, fnLookup, fnInsert }
 
+/-- Declare ht_close: (ctx, handle) -> 0, or -1 for an unknown or stale handle;
+    and ht_handle_count: (ctx) -> live tables. -/
+def declareHtClose : IRBuilder (FnRef × FnRef) := do
+  let fnClose ← declareColocatedFFI "ht_close" [.i64, .i32] (some .i32)
+  let fnCount ← declareColocatedFFI "ht_handle_count" [.i64] (some .i32)
+  pure (fnClose, fnCount)
+
+/-- Declare ht_create_with_capacity: (ctx, capacity) -> handle of a table with
+    room for `capacity` entries, or u32::MAX if that cannot be allocated -/
+def declareHtCreateWithCapacity : IRBuilder FnRef :=
+  declareColocatedFFI "ht_create_with_capacity" [.i64, .i64] (some .i32)
+
+/-- Declare ht_remove: (ctx, key_ptr, key_len) -> 0, or -1 if absent;
+    ht_clear: (ctx) -> 0 once the table is empty (capacity kept);
+    and ht_stats: (ctx, out_ptr) -> 0, writing u64 entries, u64 capacity,
+    u64 key+value bytes and u32 longest chain to out_ptr (28 bytes). -/
+def declareHtMaintenance : IRBuilder (FnRef × FnRef × FnRef) := do
+  let fnRemove ← declareColocatedFFI "ht_remove" [.i64, .i64, .i32] (some .i32)
+  let fnClear ← declareColocatedFFI "ht_clear" [.i64] (some .i32)
+  let fnStats ← declareColocatedFFI "ht_stats" [.i64, .i64] (some .i32)
+  pure (fnRemove, fnClear, fnStats)
+
 /-- Initialise the HT context; pass `ptr` (shared-memory base) — context ptr written to ptr[0]. -/
 def htInit (ptr : Val) : IRBuilder Unit := do
   let fnInit ← declareFFI "cl_ht_init" [.i64] none
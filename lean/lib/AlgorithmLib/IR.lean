@@ -9,7 +9,7 @@ namespace IR
 inductive ClifTy where
   | i8 | i32 | i64
   | f32 | f64
-  | f32x4 | i8x16
+  | f32x4 | f64x2 | i32x4 | i64x2 | i8x16
   deriving Repr, BEq
 
 /-- An SSA value reference -/
@@ -37,6 +37,11 @@ inductive ICmpCond where
   | eq | ne | uge | ugt | ule | ult | slt | sle | sgt | sge
   deriving Repr
 
+/-- Read-modify-write operations for `atomic_rmw` -/
+inductive AtomicRmwOp where
+  | add | sub | xchg
+  deriving Repr
+
 /-- A single CLIF instruction -/
 inductive Inst where
   | iconst (dst : Val) (ty : ClifTy) (value : Int)
@@ -47,6 +52,7 @@ inductive Inst where
   | ineg (dst : Val) (a : Val)
   | ishl (dst : Val) (a b : Val)
   | ushr (dst : Val) (a b : Val)
+  | sshr (dst : Val) (a b : Val)
   | band (dst : Val) (a b : Val)
   | bandNot (dst : Val) (a b : Val)
   | bor (dst : Val) (a b : Val)
@@ -69,11 +75,13 @@ inductive Inst where
   | fadd (dst a b : Val)
   | fsub (dst a b : Val)
   | fmul (dst a b : Val)
+  | fdiv (dst a b : Val)
   | fmax (dst a b : Val)
   | fmin (dst a b : Val)
   | fpromote (dst a : Val)
   | splat (dst : Val) (ty : ClifTy) (src : Val)
   | extractlane (dst : Val) (src : Val) (lane : Nat)
+  | insertlane (dst : Val) (src val : Val) (lane : Nat)
   | storeTyped (ty : ClifTy) (val addr : Val)
   | rawInst (s : String)
   -- Additional float / int ops
@@ -83,7 +91,17 @@ inductive Inst where
   | bitcast (dst : Val) (ty : ClifTy) (src : Val)
   | ctz (dst a : Val)
   | popcnt (dst a : Val)
+  | bswap (dst a : Val)
   | vhighBits (dst a : Val)
+  | bitselect (dst mask a b : Val)
+  | atomicRmw (dst : Val) (ty : ClifTy) (op : AtomicRmwOp) (addr val : Val)
+  | atomicCas (dst : Val) (ty : ClifTy) (addr expected replacement : Val)
+  | fence
+  -- Checked integer SIMD
+  | vbitcast (dst : Val) (ty : ClifTy) (src : Val)
+  | swiden (dst : Val) (high : Bool) (a : Val)
+  | shuffle (dst : Val) (a b : Val) (mask : String)
+  | smulOverflow (dst flag : Val) (a b : Val)
 
 /-- A declared block with its parameter values -/
 structure DeclaredBlock where
@@ -281,6 +299,13 @@ def ishlImm (a : Val) (imm : Int) : IRBuilder Val := do
 def ushrImm (a : Val) (imm : Int) : IRBuilder Val := do
   let c ← iconst64 imm; ushr a c
 
+/-- Arithmetic (sign-filling) right shift; lane-wise on vectors. -/
+def sshr (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.sshr v a b); pure v
+
+def sshrImm (a : Val) (imm : Int) : IRBuilder Val := do
+  let c ← iconst64 imm; sshr a c
+
 -- ---------------------------------------------------------------------------
 -- Instruction emitters — float / SIMD
 -- ---------------------------------------------------------------------------
@@ -306,6 +331,9 @@ def fsub (a b : Val) : IRBuilder Val := do
 def fmul (a b : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.fmul v a b); pure v
 
+def fdiv (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fdiv v a b); pure v
+
 def fmax (a b : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.fmax v a b); pure v
 
@@ -321,6 +349,9 @@ def splat (ty : ClifTy) (src : Val) : IRBuilder Val := do
 def extractlane (src : Val) (lane : Nat) : IRBuilder Val := do
   let v ← freshVal; emit (.extractlane v src lane); pure v
 
+def insertlane (src val : Val) (lane : Nat) : IRBuilder Val := do
+  let v ← freshVal; emit (.insertlane v src val lane); pure v
+
 def loadF32 (addr : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.load v "load.f32 notrap aligned" addr); pure v
 
@@ -330,15 +361,41 @@ def loadF64 (addr : Val) : IRBuilder Val := do
 def loadF32x4 (addr : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.load v "load.f32x4 notrap aligned" addr); pure v
 
+/-- Two f64 lanes per 128-bit register. `fadd`/`fsub`/`fmul`/`fdiv` work
+    lane-wise on the result; the CLIF verifier rejects mixing it with an
+    f32x4 operand, so `Base::new` fails instead of reinterpreting lanes. -/
+def loadF64x2 (addr : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.load v "load.f64x2 notrap aligned" addr); pure v
+
 def loadI8x16 (addr : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.load v "load.i8x16 notrap aligned" addr); pure v
 
+def loadI32x4 (addr : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.load v "load.i32x4 notrap aligned" addr); pure v
+
+/-- Two i64 lanes per 128-bit register, e.g. for counters that outgrow i32x4.
+    `iadd`/`isub`/`imul` work lane-wise on the result. -/
+def loadI64x2 (addr : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.load v "load.i64x2 notrap aligned" addr); pure v
+
 def storeF32 (val addr : Val) : IRBuilder Unit :=
   emit (.storeTyped .f32 val addr)
 
 def storeF64 (val addr : Val) : IRBuilder Unit :=
   emit (.storeTyped .f64 val addr)
 
+def storeF32x4 (val addr : Val) : IRBuilder Unit :=
+  emit (.storeTyped .f32x4 val addr)
+
+def storeF64x2 (val addr : Val) : IRBuilder Unit :=
+  emit (.storeTyped .f64x2 val addr)
+
+def storeI32x4 (val addr : Val) : IRBuilder Unit :=
+  emit (.storeTyped .i32x4 val addr)
+
+def storeI64x2 (val addr : Val) : IRBuilder Unit :=
+  emit (.storeTyped .i64x2 val addr)
+
 def storeI64 (val addr : Val) : IRBuilder Unit :=
   emit (.storeTyped .i64 val addr)
 
@@ -357,24 +414,58 @@ def fneg (a : Val) : IRBuilder Val := do
 def fcvtFromSint (ty : ClifTy) (src : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.fcvtFromSint v ty src); pure v
 
+/-- Float comparisons yield an i8 0/1 (a lane mask on vectors). Any NaN
+    operand makes every comparison false except `fcmpNe`, which is true. -/
 def fcmpGt (a b : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.fcmp v "gt" a b); pure v
 
+def fcmpGe (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fcmp v "ge" a b); pure v
+
+def fcmpLt (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fcmp v "lt" a b); pure v
+
+def fcmpLe (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fcmp v "le" a b); pure v
+
+def fcmpEq (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fcmp v "eq" a b); pure v
+
+def fcmpNe (a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.fcmp v "ne" a b); pure v
+
 def bitcastI64 (a : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.bitcast v .i64 a); pure v
 
 def bitcastF64 (a : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.bitcast v .f64 a); pure v
 
+def bitcastF32x4 (a : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.bitcast v .f32x4 a); pure v
+
+def bitcastI32x4 (a : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.bitcast v .i32x4 a); pure v
+
 def ctz32 (a : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.ctz v a); pure v
 
 def popcnt32 (a : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.popcnt v a); pure v
 
+/-- Reverse the byte order of an i16/i32/i64. -/
+def bswap (a : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.bswap v a); pure v
+
 def vhighBits (src : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.vhighBits v src); pure v
 
+/-- Lane-wise blend: bits set in `mask` take `a`, clear bits take `b`.
+    On f32x4/i32x4 an fcmp/icmp result is an all-ones/all-zeros lane mask
+    (NaN compares false), so `bitselect (← fcmpLt x y) x y` is a branchless min.
+    All three operands must share a type; bitcast an fcmp mask to f32x4 first. -/
+def bitselect (mask a b : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.bitselect v mask a b); pure v
+
 
 -- ---------------------------------------------------------------------------
 -- Instruction emitters — type conversion
@@ -420,6 +511,71 @@ def load_i8 (addr : Val) : IRBuilder Val := do
 def load_i16 (addr : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.load v "load.i16" addr); pure v
 
+/-- The f32 at `addr` in all four lanes of an f32x4, e.g. a scale factor to
+    multiply a loaded vector by. Cranelift folds the load into the splat
+    (one broadcast load on x86 and aarch64). -/
+def loadSplatF32 (addr : Val) : IRBuilder Val := do
+  let s ← loadF32 addr; splat .f32x4 s
+
+/-- The i32 at `addr` in all four lanes of an i32x4. -/
+def loadSplatI32 (addr : Val) : IRBuilder Val := do
+  let s ← load32 addr; splat .i32x4 s
+
+/-- Store lane `lane` of `src` to `addr` as a `laneTy` (.f32 for an f32x4,
+    .i32 for an i32x4, and so on). -/
+def storeLane (laneTy : ClifTy) (src : Val) (lane : Nat) (addr : Val) : IRBuilder Unit := do
+  let v ← extractlane src lane; emit (.storeTyped laneTy v addr)
+
+/-- Atomic read-modify-write of the `ty`-wide (i32 or i64) value at `addr`;
+    returns the previous value. `addr` must be naturally aligned for `ty`. -/
+def atomicRmw (ty : ClifTy) (op : AtomicRmwOp) (addr val : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.atomicRmw v ty op addr val); pure v
+
+def atomicFetchAdd (ty : ClifTy) (addr val : Val) : IRBuilder Val :=
+  atomicRmw ty .add addr val
+
+def atomicFetchSub (ty : ClifTy) (addr val : Val) : IRBuilder Val :=
+  atomicRmw ty .sub addr val
+
+def atomicExchange (ty : ClifTy) (addr val : Val) : IRBuilder Val :=
+  atomicRmw ty .xchg addr val
+
+/-- Atomically store `replacement` at `addr` if it holds `expected`; returns
+    the previous value either way, so success is `icmp eq prev expected`.
+    Inline, with no FFI round trip. -/
+def atomicCas (ty : ClifTy) (addr expected replacement : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.atomicCas v ty addr expected replacement); pure v
+
+/-- Sequentially consistent fence: plain loads and stores before it are
+    ordered before those after it, as seen from other threads. Put one after
+    writing data that another thread will find through a plain flag store. -/
+def fence : IRBuilder Unit :=
+  emit .fence
+
+/-- Store each `ty` vector in `vals` to its own 16-byte slot from `addr`
+    (slot i at `addr + 16 * i`), then `fence`, so another thread that sees a
+    flag stored after this sees every lane. Without the fence, a concurrent
+    reader may see any mix of old and new slot bytes. -/
+def publishVectors (ty : ClifTy) (vals : List Val) (addr : Val) : IRBuilder Unit := do
+  let mut i := 0
+  for v in vals do
+    let slot ← iaddImm addr (16 * i)
+    emit (.storeTyped ty v slot)
+    i := i + 1
+  fence
+
+/-- Reload `n` vectors with `load` (e.g. `loadF32x4`) from the slots
+    `publishVectors` writes, after a `fence` that orders the loads after
+    whatever told this thread the slots were ready. -/
+def refreshVectors (load : Val → IRBuilder Val) (n : Nat) (addr : Val) :
+    IRBuilder (List Val) := do
+  fence
+  let mut vals : List Val := []
+  for i in [0:n] do
+    let slot ← iaddImm addr (16 * i)
+    vals := vals ++ [← load slot]
+  pure vals
+
 -- ---------------------------------------------------------------------------
 -- Instruction emitters — comparison and selection
 -- ---------------------------------------------------------------------------
@@ -433,6 +589,159 @@ def icmpImm (cond : ICmpCond) (a : Val) (imm : Int) : IRBuilder Val := do
 def select' (cond a b : Val) : IRBuilder Val := do
   let v ← freshVal; emit (.select v cond a b); pure v
 
+/-- Inline equality of the `n` bytes at `a` and `b`, unrolled as 8-byte
+    words plus a byte tail; returns an i8 flag (1 = equal). For short,
+    statically sized regions this avoids a `cl_mem_compare` call; use the FFI
+    for large or dynamic sizes. -/
+def memEqSmall (a b : Val) (n : Nat) : IRBuilder Val := do
+  let mut acc ← iconst64 0
+  for i in List.range (n / 8) do
+    let x ← load64 (← iaddImm a (i * 8))
+    let y ← load64 (← iaddImm b (i * 8))
+    acc ← bor acc (← bxor x y)
+  for i in List.range (n % 8) do
+    let off := (n / 8) * 8 + i
+    let x ← uload8_64 (← iaddImm a off)
+    let y ← uload8_64 (← iaddImm b off)
+    acc ← bor acc (← bxor x y)
+  icmpImm .eq acc 0
+
+/-- Store `val` at `addr` only when `cond` is non-zero, without branching:
+    the old value is reloaded and written back otherwise. Not atomic. -/
+def condStore (cond val addr : Val) : IRBuilder Unit := do
+  let old ← load64 addr
+  store (← select' cond val old) addr
+
+/-- Width and signedness of the integer `cmpMem` reads. -/
+inductive MemIntTy where
+  | u32 | u64 | i64
+  deriving BEq, Repr
+
+/-- Relation `cmpMem` / `cmpMemF64` test between the loaded value and the
+    immediate. -/
+inductive MemCmpMode where
+  | eq | ne | lt | ge
+  deriving BEq, Repr
+
+/-- Load the `ty` integer at `addr` as an i64, from big-endian bytes when
+    `bigEndian` is set. -/
+def loadMemInt (ty : MemIntTy) (addr : Val) (bigEndian : Bool := false) : IRBuilder Val := do
+  match ty, bigEndian with
+  | .u32, false => uload32_64 addr
+  | .u32, true => uextend64 (← bswap (← load32 addr))
+  | _, false => load64 addr
+  | _, true => bswap (← load64 addr)
+
+/-- Compare the `ty` integer at `addr` with `imm`; returns an i8 0/1 for
+    `brif`. Only the value's own bytes are read, so unlike `brif` on a loaded
+    word, a stale high half left by an earlier wider store cannot take the
+    branch. `lt`/`ge` are unsigned for `u32`/`u64` and signed for `i64`.
+    `bigEndian` reads a network-order value. -/
+def cmpMem (ty : MemIntTy) (mode : MemCmpMode) (addr : Val) (imm : Int)
+    (bigEndian : Bool := false) : IRBuilder Val := do
+  let x ← loadMemInt ty addr bigEndian
+  let cond : ICmpCond := match mode, ty with
+    | .eq, _ => .eq
+    | .ne, _ => .ne
+    | .lt, .i64 => .slt
+    | .ge, .i64 => .sge
+    | .lt, _ => .ult
+    | .ge, _ => .uge
+  icmpImm cond x imm
+
+/-- Compare the f64 at `addr` with the CLIF float literal `imm` (e.g. "0.0").
+    IEEE ordering: -0.0 equals 0.0, and a NaN satisfies only `ne`.
+    `bigEndian` reads a network-order value. -/
+def cmpMemF64 (mode : MemCmpMode) (addr : Val) (imm : String)
+    (bigEndian : Bool := false) : IRBuilder Val := do
+  let x ← if bigEndian then bitcastF64 (← bswap (← load64 addr)) else loadF64 addr
+  let c ← fconst64 imm
+  match mode with
+  | .eq => fcmpEq x c
+  | .ne => fcmpNe x c
+  | .lt => fcmpLt x c
+  | .ge => fcmpGe x c
+
+-- ---------------------------------------------------------------------------
+-- Instruction emitters — checked integer SIMD
+-- ---------------------------------------------------------------------------
+
+/-- Reinterpret a vector as `ty` when the lane counts differ (lanes are
+    little-endian in memory order). -/
+def vbitcast (ty : ClifTy) (src : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.vbitcast v ty src); pure v
+
+/-- Sign-extend the low (or high) two i32 lanes of an i32x4 to an i64x2. -/
+def swidenLow (a : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.swiden v false a); pure v
+
+def swidenHigh (a : Val) : IRBuilder Val := do
+  let v ← freshVal; emit (.swiden v true a); pure v
+
+/-- Byte shuffle of two i8x16: byte `i` of the result is byte `mask[i]` of
+    `a ++ b`, with `mask` a little-endian u128 literal. -/
+def shuffle (a b : Val) (mask : String) : IRBuilder Val := do
+  let v ← freshVal; emit (.shuffle v a b mask); pure v
+
+/-- Scalar signed multiply returning the wrapped product and an i8 0/1
+    overflow flag. -/
+def smulOverflow (a b : Val) : IRBuilder (Val × Val) := do
+  let v ← freshVal; let f ← freshVal; emit (.smulOverflow v f a b); pure (v, f)
+
+/-- Signed `a + b` on i32x4 (`bits` = 32) or i64x2 (`bits` = 64). Returns the
+    wrapping sum and a mask with every bit set in the lanes that overflowed,
+    so `vhighBits` of the mask is non-zero exactly when a lane needs a wider
+    path, and the mask can be stored next to the result. -/
+def iaddOvf (bits : Nat) (a b : Val) : IRBuilder (Val × Val) := do
+  let s ← iadd a b
+  let m ← sshrImm (← band (← bxor a s) (← bxor b s)) (bits - 1)
+  pure (s, m)
+
+/-- Signed `a - b` with an overflow mask, as `iaddOvf`. -/
+def isubOvf (bits : Nat) (a b : Val) : IRBuilder (Val × Val) := do
+  let s ← isub a b
+  let m ← sshrImm (← band (← bxor a b) (← bxor a s)) (bits - 1)
+  pure (s, m)
+
+/-- Signed `a * b` on i32x4 with an overflow mask, as `iaddOvf`. Each product
+    is formed exactly in i64 lanes and checked against its low 32 bits. -/
+def imulOvfI32x4 (a b : Val) : IRBuilder (Val × Val) := do
+  let p ← imul a b
+  let fits (x y : Val) : IRBuilder Val := do
+    let wide ← imul x y
+    let low ← sshrImm (← ishlImm wide 32) 32
+    vbitcast .i8x16 (← icmp .ne wide low)
+  let lo ← fits (← swidenLow a) (← swidenLow b)
+  let hi ← fits (← swidenHigh a) (← swidenHigh b)
+  -- Bytes 0-3 and 8-11 of each i64 mask, in lane order.
+  let m ← shuffle lo hi "0x1b1a1918131211100b0a090803020100"
+  pure (p, ← vbitcast .i32x4 m)
+
+/-- Signed `a * b` on i64x2 with an overflow mask, as `iaddOvf`. -/
+def imulOvfI64x2 (a b : Val) : IRBuilder (Val × Val) := do
+  let (p0, f0) ← smulOverflow (← extractlane a 0) (← extractlane b 0)
+  let (p1, f1) ← smulOverflow (← extractlane a 1) (← extractlane b 1)
+  let zero ← splat .i64x2 (← iconst64 0)
+  let p ← insertlane (← insertlane zero p0 0) p1 1
+  let m0 ← ineg (← uextend64 f0)
+  let m1 ← ineg (← uextend64 f1)
+  let m ← insertlane (← insertlane zero m0 0) m1 1
+  pure (p, m)
+
+/-- Signed saturating `a + b` on i32x4: lanes that would overflow clamp to
+    i32::MAX or i32::MIN instead of wrapping. -/
+def iaddSatI32x4 (a b : Val) : IRBuilder Val := do
+  let (s, m) ← iaddOvf 32 a b
+  -- An overflowing lane saturates toward the sign of `a`.
+  let sat ← bxor (← sshrImm a 31) (← splat .i32x4 (← iconst32 0x7fffffff))
+  bitselect m sat s
+
+/-- Signed saturating `a - b` on i32x4, as `iaddSatI32x4`. -/
+def isubSatI32x4 (a b : Val) : IRBuilder Val := do
+  let (s, m) ← isubOvf 32 a b
+  let sat ← bxor (← sshrImm a 31) (← splat .i32x4 (← iconst32 0x7fffffff))
+  bitselect m sat s
+
 -- ---------------------------------------------------------------------------
 -- Instruction emitters — calls
 -- ---------------------------------------------------------------------------
@@ -578,6 +887,23 @@ def whileLoop2 (a b : ClifTy) (ia ib : Val)
   startBlock exit
   return (exit.param 0, exit.param 1)
 
+/-- `doWhile2 a b ia ib body` — loop with two carries that tests after the
+    body: `body x y` returns the next carries and the continue flag, which
+    can come straight from an `fcmp`/`icmp` on a value the body just computed
+    (e.g. repeat until a residual drops below a threshold); the flag feeds
+    the `brif` with no store and reload. Runs at least once and returns the
+    final carries. -/
+def doWhile2 (a b : ClifTy) (ia ib : Val)
+    (body : Val → Val → IRBuilder (Val × Val × Val)) : IRBuilder (Val × Val) := do
+  let bdy  ← declareBlock [a, b]
+  let exit ← declareBlock [a, b]
+  jump bdy.ref [ia, ib]
+  startBlock bdy
+  let (nx, ny, again) ← body (bdy.param 0) (bdy.param 1)
+  brif again bdy.ref [nx, ny] exit.ref [nx, ny]
+  startBlock exit
+  return (exit.param 0, exit.param 1)
+
 /-- `forLoopAcc2 ty aTy bTy limit ia ib body` — counter loop with two
     accumulator carries.  Body returns `(nextA, nextB)`. -/
 def forLoopAcc2 (ty : LoopTy) (aTy bTy : ClifTy)
@@ -601,6 +927,23 @@ def forLoopAcc2 (ty : LoopTy) (aTy bTy : ClifTy)
   startBlock exit
   return (exit.param 0, exit.param 1)
 
+/-- `forEachRecord base stride count body` — data-driven loop over `count`
+    parameter records laid out `stride` bytes apart from address `base`.
+    `body i rec` runs with the counter and the record's address; operands
+    that vary per iteration (offsets, sizes) are read from the record with
+    `recordField` at run time, so one call site replaces an unrolled run of
+    calls that differ only in their constants. -/
+def forEachRecord (base : Val) (stride : Nat) (count : Val)
+    (body : Val → Val → IRBuilder Unit) : IRBuilder Unit := do
+  let step ← iconst64 stride
+  forLoop .i64 count fun i => do
+    let rec_ ← iadd base (← imul i step)
+    body i rec_
+
+/-- The i64 field `off` bytes into a `forEachRecord` record. -/
+def recordField (rec_ : Val) (off : Nat) : IRBuilder Val := do
+  load64 (← iaddImm rec_ off)
+
 -- ---------------------------------------------------------------------------
 -- String renderer
 -- ---------------------------------------------------------------------------
@@ -612,6 +955,9 @@ def renderClifTy : ClifTy → String
   | .f32 => "f32"
   | .f64 => "f64"
   | .f32x4 => "f32x4"
+  | .f64x2 => "f64x2"
+  | .i32x4 => "i32x4"
+  | .i64x2 => "i64x2"
   | .i8x16 => "i8x16"
 
 def renderVal (v : Val) : String := s!"v{v.id}"
@@ -630,6 +976,11 @@ def renderICmpCond : ICmpCond → String
   | .sgt => "sgt"
   | .sge => "sge"
 
+def renderAtomicRmwOp : AtomicRmwOp → String
+  | .add => "add"
+  | .sub => "sub"
+  | .xchg => "xchg"
+
 def renderArgs (vals : List Val) : String :=
   String.intercalate ", " (vals.map renderVal)
 
@@ -650,6 +1001,8 @@ def renderInst : Inst → String
     s!"    {renderVal dst} = ishl {renderVal a}, {renderVal b}"
   | .ushr dst a b =>
     s!"    {renderVal dst} = ushr {renderVal a}, {renderVal b}"
+  | .sshr dst a b =>
+    s!"    {renderVal dst} = sshr {renderVal a}, {renderVal b}"
   | .band dst a b =>
     s!"    {renderVal dst} = band {renderVal a}, {renderVal b}"
   | .bandNot dst a b =>
@@ -695,11 +1048,14 @@ def renderInst : Inst → String
   | .fadd dst a b => s!"    {renderVal dst} = fadd {renderVal a}, {renderVal b}"
   | .fsub dst a b => s!"    {renderVal dst} = fsub {renderVal a}, {renderVal b}"
   | .fmul dst a b => s!"    {renderVal dst} = fmul {renderVal a}, {renderVal b}"
+  | .fdiv dst a b => s!"    {renderVal dst} = fdiv {renderVal a}, {renderVal b}"
   | .fmax dst a b => s!"    {renderVal dst} = fmax {renderVal a}, {renderVal b}"
   | .fmin dst a b => s!"    {renderVal dst} = fmin {renderVal a}, {renderVal b}"
   | .fpromote dst a => s!"    {renderVal dst} = fpromote.f64 {renderVal a}"
   | .splat dst ty src => s!"    {renderVal dst} = splat.{renderClifTy ty} {renderVal src}"
   | .extractlane dst src lane => s!"    {renderVal dst} = extractlane {renderVal src}, {lane}"
+  | .insertlane dst src val lane =>
+    s!"    {renderVal dst} = insertlane {renderVal src}, {renderVal val}, {lane}"
   | .storeTyped ty val addr =>
     s!"    store.{renderClifTy ty} notrap aligned {renderVal val}, {renderVal addr}"
   | .rawInst s => s!"    {s}"
@@ -710,7 +1066,23 @@ def renderInst : Inst → String
   | .bitcast dst ty src => s!"    {renderVal dst} = bitcast.{renderClifTy ty} {renderVal src}"
   | .ctz dst a => s!"    {renderVal dst} = ctz {renderVal a}"
   | .popcnt dst a => s!"    {renderVal dst} = popcnt {renderVal a}"
+  | .bswap dst a => s!"    {renderVal dst} = bswap {renderVal a}"
   | .vhighBits dst a => s!"    {renderVal dst} = vhigh_bits.i32 {renderVal a}"
+  | .bitselect dst m a b =>
+    s!"    {renderVal dst} = bitselect {renderVal m}, {renderVal a}, {renderVal b}"
+  | .atomicRmw dst ty op addr val =>
+    s!"    {renderVal dst} = atomic_rmw.{renderClifTy ty} little {renderAtomicRmwOp op} {renderVal addr}, {renderVal val}"
+  | .atomicCas dst ty addr e r =>
+    s!"    {renderVal dst} = atomic_cas.{renderClifTy ty} little {renderVal addr}, {renderVal e}, {renderVal r}"
+  | .fence => "    fence"
+  | .vbitcast dst ty src =>
+    s!"    {renderVal dst} = bitcast.{renderClifTy ty} little {renderVal src}"
+  | .swiden dst high a =>
+    s!"    {renderVal dst} = {if high then "swiden_high" else "swiden_low"} {renderVal a}"
+  | .shuffle dst a b mask =>
+    s!"    {renderVal dst} = shuffle {renderVal a}, {renderVal b}, {mask}"
+  | .smulOverflow dst flag a b =>
+    s!"    {renderVal dst}, {renderVal flag} = smul_overflow {renderVal a}, {renderVal b}"
 
 def renderSigDecl (s : SigDecl) : String :=
   let params := String.intercalate ", " (s.params.map renderClifTy)